@@ -1,5 +1,5 @@
 //! Methods for backpropagation of gradients.
-use crate::op::{BinaryOp, Op, ReduceOp, UnaryOp};
+use crate::op::{BinaryOp, Op, ReduceOp, ScatterReduceOp, UnaryOp};
 use crate::{Error, Result, Tensor, TensorId};
 use std::collections::HashMap;
 
@@ -55,6 +55,7 @@ impl Tensor {
                     Op::IndexAdd(t1, t2, t3, _)
                     | Op::Scatter(t1, t2, t3, _)
                     | Op::ScatterAdd(t1, t2, t3, _)
+                    | Op::ScatterReduce(t1, t2, t3, _, _, _)
                     | Op::CustomOp3(t1, t2, t3, _)
                     | Op::WhereCond(t1, t2, t3) => {
                         let (tg, nodes) = walk(t1, nodes, already_seen);
@@ -442,6 +443,92 @@ impl Tensor {
                         let src_sum_grad = grads.or_insert(src)?;
                         *src_sum_grad = src_sum_grad.add(&src_grad)?;
                     }
+                    Op::ScatterReduce(init, indexes, src, dim, op, include_self) => {
+                        let dim = *dim;
+                        let ones = src.ones_like()?;
+                        // The number of values from `src` reduced on each output position.
+                        let scattered = init.zeros_like()?.scatter_add(indexes, &ones, dim)?;
+                        // How much the initial value contributes to the output, this is zero
+                        // for the positions that have been scattered to when `include_self` is
+                        // false.
+                        let self_mask = if *include_self {
+                            init.ones_like()?
+                        } else {
+                            scattered.eq(0f64)?.to_dtype(grad.dtype())?
+                        };
+                        let (init_grad, src_grad) = match op {
+                            ScatterReduceOp::Sum => {
+                                (grad.mul(&self_mask)?, grad.gather(indexes, dim)?)
+                            }
+                            ScatterReduceOp::Mean => {
+                                let grad = grad.div(&(scattered + &self_mask)?)?;
+                                (grad.mul(&self_mask)?, grad.gather(indexes, dim)?)
+                            }
+                            ScatterReduceOp::Max | ScatterReduceOp::Min => {
+                                // The gradient is evenly split between the values that are
+                                // equal to the output.
+                                let src_mask = src
+                                    .eq(&node.gather(indexes, dim)?)?
+                                    .to_dtype(grad.dtype())?;
+                                let init_mask =
+                                    init.eq(*node)?.to_dtype(grad.dtype())?.mul(&self_mask)?;
+                                let ties = init_mask.scatter_add(indexes, &src_mask, dim)?;
+                                let grad = grad.div(&ties)?;
+                                let src_grad = grad.gather(indexes, dim)?.mul(&src_mask)?;
+                                (grad.mul(&init_mask)?, src_grad)
+                            }
+                            ScatterReduceOp::Prod => {
+                                // The gradient for a value is the product of the other values
+                                // reduced on the same position. This is computed from the
+                                // product of the non-zero values and the number of zeros so
+                                // that there is no division by zero.
+                                let src_zero = src.eq(0f64)?.to_dtype(grad.dtype())?;
+                                let init_zero =
+                                    init.eq(0f64)?.to_dtype(grad.dtype())?.mul(&self_mask)?;
+                                let src_nz = src.add(&src_zero)?;
+                                let init_nz = init.add(&init_zero)?.mul(&self_mask)?;
+                                let init_nz = init_nz.add(&self_mask.affine(-1., 1.)?)?;
+                                let nz_prod = init_nz.scatter_reduce(
+                                    indexes,
+                                    &src_nz,
+                                    dim,
+                                    ScatterReduceOp::Prod,
+                                    true,
+                                )?;
+                                let zeros = init_zero.scatter_add(indexes, &src_zero, dim)?;
+                                let no_zero = zeros.eq(0f64)?.to_dtype(grad.dtype())?;
+                                let one_zero = zeros.eq(1f64)?.to_dtype(grad.dtype())?;
+                                let grad_of = |nz: &Tensor,
+                                               is_zero: &Tensor,
+                                               nz_prod: &Tensor,
+                                               no_zero: &Tensor,
+                                               one_zero: &Tensor|
+                                 -> Result<Tensor> {
+                                    let if_zero = is_zero.mul(one_zero)?;
+                                    let if_non_zero =
+                                        is_zero.affine(-1., 1.)?.mul(no_zero)?.div(nz)?;
+                                    nz_prod.mul(&if_zero.add(&if_non_zero)?)
+                                };
+                                let init_grad =
+                                    grad_of(&init_nz, &init_zero, &nz_prod, &no_zero, &one_zero)?
+                                        .mul(&self_mask)?
+                                        .mul(&grad)?;
+                                let src_grad = grad_of(
+                                    &src_nz,
+                                    &src_zero,
+                                    &nz_prod.gather(indexes, dim)?,
+                                    &no_zero.gather(indexes, dim)?,
+                                    &one_zero.gather(indexes, dim)?,
+                                )?
+                                .mul(&grad.gather(indexes, dim)?)?;
+                                (init_grad, src_grad)
+                            }
+                        };
+                        let init_sum_grad = grads.or_insert(init)?;
+                        *init_sum_grad = init_sum_grad.add(&init_grad)?;
+                        let src_sum_grad = grads.or_insert(src)?;
+                        *src_sum_grad = src_sum_grad.add(&src_grad)?;
+                    }
                     Op::IndexAdd(init, indexes, src, dim) => {
                         let init_sum_grad = grads.or_insert(init)?;
                         *init_sum_grad = init_sum_grad.add(&grad)?;
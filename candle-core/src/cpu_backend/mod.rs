@@ -1,6 +1,6 @@
 //! Implementation of Backend Fns for CPU
 use crate::backend::{BackendDevice, BackendStorage};
-use crate::op::{BinaryOpT, CmpOp, ReduceOp, UnaryOpT};
+use crate::op::{BinaryOpT, CmpOp, ReduceOp, ScatterReduceOp, UnaryOpT};
 use crate::{DType, Error, IntDType, Layout, Result, Shape, WithDType};
 use float8::F8E4M3;
 use half::{bf16, f16};
@@ -801,6 +801,112 @@ impl<I: IntDType, M: ElemUpdate> Map2InPlace for Scatter<'_, I, M> {
     }
 }
 
+struct ScatterReduce<'a, I: IntDType> {
+    ids: &'a [I],
+    ids_l: &'a Layout,
+    dim: usize,
+    op: ScatterReduceOp,
+    include_self: bool,
+}
+
+impl<I: IntDType> Map2InPlace for ScatterReduce<'_, I> {
+    const OP: &'static str = "scatter-reduce";
+    fn f<T: WithDType>(
+        &self,
+        dst: &mut [T],
+        dst_l: &Layout,
+        src: &[T],
+        src_l: &Layout,
+    ) -> Result<()> {
+        let dst = match dst_l.contiguous_offsets() {
+            None => Err(Error::RequiresContiguous {
+                op: "scatter-reduce",
+            }
+            .bt())?,
+            Some((o1, o2)) => &mut dst[o1..o2],
+        };
+        let src = match src_l.contiguous_offsets() {
+            None => Err(Error::RequiresContiguous {
+                op: "scatter-reduce",
+            }
+            .bt())?,
+            Some((o1, o2)) => &src[o1..o2],
+        };
+        let ids = match self.ids_l.contiguous_offsets() {
+            Some((a, b)) => &self.ids[a..b],
+            None => Err(Error::RequiresContiguous {
+                op: "scatter-reduce",
+            }
+            .bt())?,
+        };
+
+        let dim = self.dim;
+        let ids_dims = self.ids_l.dims();
+        let dst_dims = dst_l.dims();
+        let dst_dim_len = dst_dims[dim];
+        let dst_right_len: usize = dst_dims[dim + 1..].iter().product();
+
+        let ids_left_len: usize = ids_dims[..dim].iter().product();
+        let ids_dim_len = ids_dims[dim];
+        let ids_right_len: usize = ids_dims[dim + 1..].iter().product();
+
+        // The number of values reduced on each destination element, `self` included.
+        let mut counts = vec![self.include_self as u32; dst.len()];
+        for left_i in 0..ids_left_len {
+            let start_ids_idx = left_i * ids_right_len * ids_dim_len;
+            let start_dst_idx = left_i * dst_right_len * dst_dim_len;
+            for i in 0..ids_dim_len {
+                let start_ids_idx = start_ids_idx + i * ids_right_len;
+                for right_i in 0..dst_right_len {
+                    let ids_idx = start_ids_idx + right_i;
+                    let index = ids[ids_idx];
+                    if index == I::max_value() {
+                        continue;
+                    }
+                    let index = index.as_usize();
+                    if index >= dst_dim_len {
+                        Err(Error::InvalidIndex {
+                            index,
+                            size: dst_dim_len,
+                            op: "scatter-reduce",
+                        }
+                        .bt())?
+                    }
+                    let dst_idx = start_dst_idx + index * dst_right_len + right_i;
+                    let (d, v) = (&mut dst[dst_idx], src[ids_idx]);
+                    if counts[dst_idx] == 0 {
+                        *d = v
+                    } else {
+                        match self.op {
+                            ScatterReduceOp::Sum | ScatterReduceOp::Mean => *d += v,
+                            ScatterReduceOp::Prod => *d *= v,
+                            ScatterReduceOp::Max => {
+                                if v > *d {
+                                    *d = v
+                                }
+                            }
+                            ScatterReduceOp::Min => {
+                                if v < *d {
+                                    *d = v
+                                }
+                            }
+                        }
+                    }
+                    counts[dst_idx] += 1;
+                }
+            }
+        }
+        if self.op == ScatterReduceOp::Mean {
+            for (d, &c) in dst.iter_mut().zip(counts.iter()) {
+                if c > 1 {
+                    *d /= T::from_f64(c as f64)
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
 struct IndexAdd<'a, I: IntDType> {
     ids: &'a [I],
     dim: usize,
@@ -1660,6 +1766,47 @@ impl CpuStorage {
         D::cpu_storage_as_slice(self)
     }
 
+    #[allow(clippy::too_many_arguments)]
+    pub(crate) fn scatter_reduce_set(
+        &mut self,
+        l: &Layout,
+        ids: &Self,
+        ids_l: &Layout,
+        src: &Self,
+        src_l: &Layout,
+        dim: usize,
+        op: ScatterReduceOp,
+        include_self: bool,
+    ) -> Result<()> {
+        match ids {
+            Self::U8(ids) => ScatterReduce {
+                ids,
+                ids_l,
+                dim,
+                op,
+                include_self,
+            }
+            .map(self, l, src, src_l),
+            Self::U32(ids) => ScatterReduce {
+                ids,
+                ids_l,
+                dim,
+                op,
+                include_self,
+            }
+            .map(self, l, src, src_l),
+            Self::I64(ids) => ScatterReduce {
+                ids,
+                ids_l,
+                dim,
+                op,
+                include_self,
+            }
+            .map(self, l, src, src_l),
+            _ => Err(Error::UnsupportedDTypeForOp(self.dtype(), "scatter-reduce").bt()),
+        }
+    }
+
     pub fn concat(storages: &[CpuStorage]) -> Result<CpuStorage> {
         let storage0 = &storages[0];
         let s = match storage0 {
@@ -79,6 +79,7 @@ pub mod safetensors;
 pub mod scalar;
 pub mod shape;
 mod sort;
+pub mod sparse;
 mod storage;
 pub mod streaming;
 mod strided_index;
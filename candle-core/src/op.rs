@@ -72,6 +72,29 @@ pub enum UnaryOp {
     Sign,
 }
 
+/// The reduction used by [`Tensor::scatter_reduce`] and [`Tensor::index_reduce`] when several
+/// values are scattered on the same position.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ScatterReduceOp {
+    Sum,
+    Prod,
+    Mean,
+    Max,
+    Min,
+}
+
+impl ScatterReduceOp {
+    pub(crate) fn name(&self) -> &'static str {
+        match self {
+            Self::Sum => "sum",
+            Self::Prod => "prod",
+            Self::Mean => "mean",
+            Self::Max => "max",
+            Self::Min => "min",
+        }
+    }
+}
+
 #[derive(Clone)]
 pub enum Op {
     Binary(Tensor, Tensor, BinaryOp),
@@ -83,6 +106,8 @@ pub enum Op {
     Gather(Tensor, Tensor, usize),
     Scatter(Tensor, Tensor, Tensor, usize),
     ScatterAdd(Tensor, Tensor, Tensor, usize),
+    // The last argument is `include_self`.
+    ScatterReduce(Tensor, Tensor, Tensor, usize, ScatterReduceOp, bool),
     IndexSelect(Tensor, Tensor, usize),
     IndexAdd(Tensor, Tensor, Tensor, usize),
     WhereCond(Tensor, Tensor, Tensor),
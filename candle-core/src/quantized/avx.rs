@@ -1,3 +1,10 @@
+use super::iq_grids::{
+    IQ1S_GRID, IQ2S_GRID, IQ2XS_GRID, IQ2XXS_GRID, IQ3XXS_GRID, KSIGNS_IQ2XS, KVALUES_IQ4NL,
+};
+use super::iq_quants::{
+    tq1_trit, BlockIQ1M, BlockIQ1S, BlockIQ2S, BlockIQ2XS, BlockIQ2XXS, BlockIQ3S, BlockIQ3XXS,
+    BlockIQ4NL, BlockIQ4XS, BlockTQ1_0, BlockTQ2_0,
+};
 use super::k_quants::{
     BlockQ2K, BlockQ3K, BlockQ4K, BlockQ4_0, BlockQ5K, BlockQ6K, BlockQ8K, BlockQ8_0, QK8_0, QK_K,
 };
@@ -667,3 +674,413 @@ pub(crate) fn vec_dot_q8k_q8k(n: usize, xs: &[BlockQ8K], ys: &[BlockQ8K]) -> f32
         hsum_float_8(acc)
     }
 }
+
+/// Maps 32 packed 4-bit indexes to the non-linear iq4 values, the low nibbles end up in the
+/// first 16 bytes.
+#[inline(always)]
+unsafe fn iq4nl_values_32(qs: *const u8) -> __m256i {
+    let values = _mm_loadu_si128(KVALUES_IQ4NL.as_ptr() as *const __m128i);
+    let m4 = _mm_set1_epi8(0xF);
+    let q = _mm_loadu_si128(qs as *const __m128i);
+    let lo = _mm_shuffle_epi8(values, _mm_and_si128(q, m4));
+    let hi = _mm_shuffle_epi8(values, _mm_and_si128(_mm_srli_epi16(q, 4), m4));
+    mm256_set_m128i(hi, lo)
+}
+
+#[inline(always)]
+pub(crate) fn vec_dot_iq4nl_q8_0(n: usize, xs: &[BlockIQ4NL], ys: &[BlockQ8_0]) -> f32 {
+    debug_assert!(
+        n.is_multiple_of(QK8_0),
+        "vec_dot_iq4nl_q8_0: {n} is not divisible by {QK8_0}"
+    );
+    unsafe {
+        let mut acc = _mm256_setzero_ps();
+        for (x, y) in xs.iter().zip(ys.iter()) {
+            let d = _mm256_set1_ps(f16::to_f32(x.d) * f16::to_f32(y.d));
+            let bx = iq4nl_values_32(x.qs.as_ptr());
+            let by = _mm256_loadu_si256(y.qs.as_ptr() as *const __m256i);
+            let q = mul_sum_i8_pairs_float(bx, by);
+            acc = _mm256_fmadd_ps(d, q, acc);
+        }
+        hsum_float_8(acc)
+    }
+}
+
+#[inline(always)]
+pub(crate) fn vec_dot_iq4xs_q8k(n: usize, xs: &[BlockIQ4XS], ys: &[BlockQ8K]) -> f32 {
+    debug_assert!(
+        n.is_multiple_of(QK_K),
+        "vec_dot_iq4xs_q8k: {n} is not divisible by {QK_K}"
+    );
+    unsafe {
+        let mut acc = _mm256_setzero_ps();
+        for (x, y) in xs.iter().zip(ys.iter()) {
+            let mut sumi = _mm256_setzero_si256();
+            for ib in 0..QK_K / 32 {
+                let bx = iq4nl_values_32(x.qs.as_ptr().add(16 * ib));
+                // The q8k values can be -128 so the sign trick cannot be used here.
+                let by = _mm256_loadu_si256(y.qs.as_ptr().add(32 * ib) as *const __m256i);
+                let bx0 = _mm256_cvtepi8_epi16(_mm256_extracti128_si256(bx, 0));
+                let by0 = _mm256_cvtepi8_epi16(_mm256_extracti128_si256(by, 0));
+                let bx1 = _mm256_cvtepi8_epi16(_mm256_extracti128_si256(bx, 1));
+                let by1 = _mm256_cvtepi8_epi16(_mm256_extracti128_si256(by, 1));
+                let p = _mm256_add_epi32(_mm256_madd_epi16(bx0, by0), _mm256_madd_epi16(bx1, by1));
+                let ls = _mm256_set1_epi32(x.scale(ib));
+                sumi = _mm256_add_epi32(sumi, _mm256_mullo_epi32(p, ls));
+            }
+            let d = _mm256_set1_ps(x.d.to_f32() * y.d);
+            acc = _mm256_fmadd_ps(d, _mm256_cvtepi32_ps(sumi), acc);
+        }
+        hsum_float_8(acc)
+    }
+}
+
+/// Expands 32 sign bits, one per value, to a mask where the bytes of the negative values are -1.
+#[inline(always)]
+unsafe fn sign_mask_32(signs: u32) -> __m256i {
+    let shuffle = _mm256_set_epi64x(
+        0x0303030303030303,
+        0x0202020202020202,
+        0x0101010101010101,
+        0,
+    );
+    let bits = _mm256_set1_epi64x(0x8040201008040201u64 as i64);
+    let signs = _mm256_shuffle_epi8(_mm256_set1_epi32(signs as i32), shuffle);
+    _mm256_cmpeq_epi8(_mm256_and_si256(signs, bits), bits)
+}
+
+/// Negates the bytes of `x` for which `mask` is -1.
+#[inline(always)]
+unsafe fn apply_signs(x: __m256i, mask: __m256i) -> __m256i {
+    _mm256_sub_epi8(_mm256_xor_si256(x, mask), mask)
+}
+
+/// Returns the dot products of the first 16 and of the last 16 signed bytes of `x` and `y`, each
+/// spread over 8 i32 lanes. The q8k values can be -128 so the sign trick cannot be used here.
+#[inline(always)]
+unsafe fn dot_i8_halves(x: __m256i, y: __m256i) -> (__m256i, __m256i) {
+    let x0 = _mm256_cvtepi8_epi16(_mm256_extracti128_si256(x, 0));
+    let y0 = _mm256_cvtepi8_epi16(_mm256_extracti128_si256(y, 0));
+    let x1 = _mm256_cvtepi8_epi16(_mm256_extracti128_si256(x, 1));
+    let y1 = _mm256_cvtepi8_epi16(_mm256_extracti128_si256(y, 1));
+    (_mm256_madd_epi16(x0, y0), _mm256_madd_epi16(x1, y1))
+}
+
+#[inline(always)]
+unsafe fn load_q8k_32(y: &BlockQ8K, offset: usize) -> __m256i {
+    _mm256_loadu_si256(y.qs.as_ptr().add(offset) as *const __m256i)
+}
+
+/// Packs the sign bytes of 4 groups of 8 values, stored as 7 bits in the iq2xxs/iq3xxs format.
+#[inline(always)]
+fn ksigns_32(aux: u32) -> u32 {
+    (0..4).fold(0, |acc, l| {
+        acc | (KSIGNS_IQ2XS[((aux >> (7 * l)) & 127) as usize] as u32) << (8 * l)
+    })
+}
+
+#[inline(always)]
+pub(crate) fn vec_dot_iq2xxs_q8k(n: usize, xs: &[BlockIQ2XXS], ys: &[BlockQ8K]) -> f32 {
+    debug_assert!(
+        n.is_multiple_of(QK_K),
+        "vec_dot_iq2xxs_q8k: {n} is not divisible by {QK_K}"
+    );
+    unsafe {
+        let mut acc = _mm256_setzero_ps();
+        for (x, y) in xs.iter().zip(ys.iter()) {
+            let mut sumi = _mm256_setzero_si256();
+            for ib in 0..QK_K / 32 {
+                let aux0 = x.qs[4 * ib] as u32 | (x.qs[4 * ib + 1] as u32) << 16;
+                let aux1 = x.qs[4 * ib + 2] as u32 | (x.qs[4 * ib + 3] as u32) << 16;
+                let g = |l: u32| IQ2XXS_GRID[((aux0 >> (8 * l)) & 0xff) as usize] as i64;
+                let q = _mm256_set_epi64x(g(3), g(2), g(1), g(0));
+                let q = apply_signs(q, sign_mask_32(ksigns_32(aux1)));
+                let (p0, p1) = dot_i8_halves(q, load_q8k_32(y, 32 * ib));
+                let ls = _mm256_set1_epi32(2 * (aux1 >> 28) as i32 + 1);
+                sumi = _mm256_add_epi32(sumi, _mm256_mullo_epi32(_mm256_add_epi32(p0, p1), ls));
+            }
+            let d = _mm256_set1_ps(x.d.to_f32() * y.d);
+            acc = _mm256_fmadd_ps(d, _mm256_cvtepi32_ps(sumi), acc);
+        }
+        0.125 * hsum_float_8(acc)
+    }
+}
+
+#[inline(always)]
+pub(crate) fn vec_dot_iq2xs_q8k(n: usize, xs: &[BlockIQ2XS], ys: &[BlockQ8K]) -> f32 {
+    debug_assert!(
+        n.is_multiple_of(QK_K),
+        "vec_dot_iq2xs_q8k: {n} is not divisible by {QK_K}"
+    );
+    unsafe {
+        let mut acc = _mm256_setzero_ps();
+        for (x, y) in xs.iter().zip(ys.iter()) {
+            let mut sumi = _mm256_setzero_si256();
+            for ib in 0..QK_K / 32 {
+                let qs = &x.qs[4 * ib..4 * (ib + 1)];
+                let g = |l: usize| IQ2XS_GRID[(qs[l] & 511) as usize] as i64;
+                let q = _mm256_set_epi64x(g(3), g(2), g(1), g(0));
+                let signs = (0..4).fold(0u32, |acc, l| {
+                    acc | (KSIGNS_IQ2XS[(qs[l] >> 9) as usize] as u32) << (8 * l)
+                });
+                let q = apply_signs(q, sign_mask_32(signs));
+                let (p0, p1) = dot_i8_halves(q, load_q8k_32(y, 32 * ib));
+                let ls0 = _mm256_set1_epi32(2 * (x.scales[ib] & 0xf) as i32 + 1);
+                let ls1 = _mm256_set1_epi32(2 * (x.scales[ib] >> 4) as i32 + 1);
+                sumi = _mm256_add_epi32(sumi, _mm256_mullo_epi32(p0, ls0));
+                sumi = _mm256_add_epi32(sumi, _mm256_mullo_epi32(p1, ls1));
+            }
+            let d = _mm256_set1_ps(x.d.to_f32() * y.d);
+            acc = _mm256_fmadd_ps(d, _mm256_cvtepi32_ps(sumi), acc);
+        }
+        0.125 * hsum_float_8(acc)
+    }
+}
+
+#[inline(always)]
+pub(crate) fn vec_dot_iq2s_q8k(n: usize, xs: &[BlockIQ2S], ys: &[BlockQ8K]) -> f32 {
+    debug_assert!(
+        n.is_multiple_of(QK_K),
+        "vec_dot_iq2s_q8k: {n} is not divisible by {QK_K}"
+    );
+    unsafe {
+        let mut acc = _mm256_setzero_ps();
+        for (x, y) in xs.iter().zip(ys.iter()) {
+            let mut sumi = _mm256_setzero_si256();
+            for ib in 0..QK_K / 32 {
+                let g = |l: usize| IQ2S_GRID[x.grid_index(4 * ib + l)] as i64;
+                let q = _mm256_set_epi64x(g(3), g(2), g(1), g(0));
+                let signs = &x.qs[QK_K / 8 + 4 * ib..QK_K / 8 + 4 * (ib + 1)];
+                let signs = u32::from_le_bytes([signs[0], signs[1], signs[2], signs[3]]);
+                let q = apply_signs(q, sign_mask_32(signs));
+                let (p0, p1) = dot_i8_halves(q, load_q8k_32(y, 32 * ib));
+                let ls0 = _mm256_set1_epi32(2 * (x.scales[ib] & 0xf) as i32 + 1);
+                let ls1 = _mm256_set1_epi32(2 * (x.scales[ib] >> 4) as i32 + 1);
+                sumi = _mm256_add_epi32(sumi, _mm256_mullo_epi32(p0, ls0));
+                sumi = _mm256_add_epi32(sumi, _mm256_mullo_epi32(p1, ls1));
+            }
+            let d = _mm256_set1_ps(x.d.to_f32() * y.d);
+            acc = _mm256_fmadd_ps(d, _mm256_cvtepi32_ps(sumi), acc);
+        }
+        0.125 * hsum_float_8(acc)
+    }
+}
+
+#[inline(always)]
+pub(crate) fn vec_dot_iq3xxs_q8k(n: usize, xs: &[BlockIQ3XXS], ys: &[BlockQ8K]) -> f32 {
+    debug_assert!(
+        n.is_multiple_of(QK_K),
+        "vec_dot_iq3xxs_q8k: {n} is not divisible by {QK_K}"
+    );
+    unsafe {
+        let mut acc = _mm256_setzero_ps();
+        for (x, y) in xs.iter().zip(ys.iter()) {
+            let mut sumi = _mm256_setzero_si256();
+            for ib in 0..QK_K / 32 {
+                let aux = x.scales_and_signs(ib);
+                let g = |k: usize| IQ3XXS_GRID[x.qs[8 * ib + k] as usize] as i32;
+                let q = _mm256_set_epi32(g(7), g(6), g(5), g(4), g(3), g(2), g(1), g(0));
+                let q = apply_signs(q, sign_mask_32(ksigns_32(aux)));
+                let (p0, p1) = dot_i8_halves(q, load_q8k_32(y, 32 * ib));
+                let ls = _mm256_set1_epi32(2 * (aux >> 28) as i32 + 1);
+                sumi = _mm256_add_epi32(sumi, _mm256_mullo_epi32(_mm256_add_epi32(p0, p1), ls));
+            }
+            let d = _mm256_set1_ps(x.d.to_f32() * y.d);
+            acc = _mm256_fmadd_ps(d, _mm256_cvtepi32_ps(sumi), acc);
+        }
+        0.25 * hsum_float_8(acc)
+    }
+}
+
+#[inline(always)]
+pub(crate) fn vec_dot_iq3s_q8k(n: usize, xs: &[BlockIQ3S], ys: &[BlockQ8K]) -> f32 {
+    debug_assert!(
+        n.is_multiple_of(QK_K),
+        "vec_dot_iq3s_q8k: {n} is not divisible by {QK_K}"
+    );
+    unsafe {
+        let mut acc = _mm256_setzero_ps();
+        for (x, y) in xs.iter().zip(ys.iter()) {
+            let mut sumi = _mm256_setzero_si256();
+            for ib in 0..QK_K / 32 {
+                let g = |k: usize| x.grid(ib, k) as i32;
+                let q = _mm256_set_epi32(g(7), g(6), g(5), g(4), g(3), g(2), g(1), g(0));
+                let signs = &x.signs[4 * ib..4 * (ib + 1)];
+                let signs = u32::from_le_bytes([signs[0], signs[1], signs[2], signs[3]]);
+                let q = apply_signs(q, sign_mask_32(signs));
+                let (p0, p1) = dot_i8_halves(q, load_q8k_32(y, 32 * ib));
+                let ls = (x.scales[ib / 2] >> (4 * (ib % 2))) & 0xf;
+                let ls = _mm256_set1_epi32(2 * ls as i32 + 1);
+                sumi = _mm256_add_epi32(sumi, _mm256_mullo_epi32(_mm256_add_epi32(p0, p1), ls));
+            }
+            let d = _mm256_set1_ps(x.d.to_f32() * y.d);
+            acc = _mm256_fmadd_ps(d, _mm256_cvtepi32_ps(sumi), acc);
+        }
+        hsum_float_8(acc)
+    }
+}
+
+/// The iq1 values are in `{-1, 0, 1}` and the deltas are `+/- 1/8`, so `8 * (q + delta)` is
+/// computed exactly on bytes. `deltas` holds the sign of the delta for each group of 8 values.
+#[inline(always)]
+unsafe fn iq1_values_x8(grid: [u64; 4], deltas: __m256i) -> __m256i {
+    let q = _mm256_set_epi64x(
+        grid[3] as i64,
+        grid[2] as i64,
+        grid[1] as i64,
+        grid[0] as i64,
+    );
+    _mm256_add_epi8(_mm256_sign_epi8(_mm256_set1_epi8(8), q), deltas)
+}
+
+#[inline(always)]
+pub(crate) fn vec_dot_iq1s_q8k(n: usize, xs: &[BlockIQ1S], ys: &[BlockQ8K]) -> f32 {
+    debug_assert!(
+        n.is_multiple_of(QK_K),
+        "vec_dot_iq1s_q8k: {n} is not divisible by {QK_K}"
+    );
+    unsafe {
+        let mut acc = _mm256_setzero_ps();
+        for (x, y) in xs.iter().zip(ys.iter()) {
+            let mut sumi = _mm256_setzero_si256();
+            for ib in 0..QK_K / 32 {
+                let qh = x.qh[ib];
+                let grid = std::array::from_fn(|l| {
+                    IQ1S_GRID[x.qs[4 * ib + l] as usize | (((qh >> (3 * l)) & 7) as usize) << 8]
+                });
+                let deltas = _mm256_set1_epi8(if qh & 0x8000 != 0 { -1 } else { 1 });
+                let q = iq1_values_x8(grid, deltas);
+                let (p0, p1) = dot_i8_halves(q, load_q8k_32(y, 32 * ib));
+                let ls = _mm256_set1_epi32(2 * ((qh >> 12) & 7) as i32 + 1);
+                sumi = _mm256_add_epi32(sumi, _mm256_mullo_epi32(_mm256_add_epi32(p0, p1), ls));
+            }
+            let d = _mm256_set1_ps(x.d.to_f32() * y.d);
+            acc = _mm256_fmadd_ps(d, _mm256_cvtepi32_ps(sumi), acc);
+        }
+        0.125 * hsum_float_8(acc)
+    }
+}
+
+#[inline(always)]
+pub(crate) fn vec_dot_iq1m_q8k(n: usize, xs: &[BlockIQ1M], ys: &[BlockQ8K]) -> f32 {
+    debug_assert!(
+        n.is_multiple_of(QK_K),
+        "vec_dot_iq1m_q8k: {n} is not divisible by {QK_K}"
+    );
+    unsafe {
+        let mut acc = _mm256_setzero_ps();
+        for (x, y) in xs.iter().zip(ys.iter()) {
+            let sc = x.sc();
+            let mut sumi = _mm256_setzero_si256();
+            for ib in 0..QK_K / 32 {
+                let groups: [(usize, f32); 4] = std::array::from_fn(|l| x.group(ib, l));
+                let grid = groups.map(|(idx, _)| IQ1S_GRID[idx]);
+                let delta = |l: usize| {
+                    if groups[l].1 < 0. {
+                        -1
+                    } else {
+                        0x0101010101010101
+                    }
+                };
+                let deltas = _mm256_set_epi64x(delta(3), delta(2), delta(1), delta(0));
+                let q = iq1_values_x8(grid, deltas);
+                let (p0, p1) = dot_i8_halves(q, load_q8k_32(y, 32 * ib));
+                let ls = |half: usize| {
+                    let ls = (sc[ib / 2] >> (6 * (ib % 2) + 3 * half)) & 7;
+                    _mm256_set1_epi32(2 * ls as i32 + 1)
+                };
+                sumi = _mm256_add_epi32(sumi, _mm256_mullo_epi32(p0, ls(0)));
+                sumi = _mm256_add_epi32(sumi, _mm256_mullo_epi32(p1, ls(1)));
+            }
+            let d = _mm256_set1_ps(BlockIQ1M::d(&sc) * y.d);
+            acc = _mm256_fmadd_ps(d, _mm256_cvtepi32_ps(sumi), acc);
+        }
+        0.125 * hsum_float_8(acc)
+    }
+}
+
+/// Extracts the `n`-th ternary digit of 16 packed bytes stored as i16, the digits being
+/// recovered by multiplications as in `tq1_trit`, and maps it to `{-1, 0, 1}`.
+#[inline(always)]
+unsafe fn tq1_trits_16(q: __m256i, n: usize) -> __m256i {
+    const POW3: [i16; 5] = [1, 3, 9, 27, 81];
+    let q = _mm256_and_si256(
+        _mm256_mullo_epi16(q, _mm256_set1_epi16(POW3[n])),
+        _mm256_set1_epi16(0xff),
+    );
+    let q = _mm256_srli_epi16(_mm256_mullo_epi16(q, _mm256_set1_epi16(3)), 8);
+    _mm256_sub_epi16(q, _mm256_set1_epi16(1))
+}
+
+#[inline(always)]
+pub(crate) fn vec_dot_tq1_0_q8k(n: usize, xs: &[BlockTQ1_0], ys: &[BlockQ8K]) -> f32 {
+    debug_assert!(
+        n.is_multiple_of(QK_K),
+        "vec_dot_tq1_0_q8k: {n} is not divisible by {QK_K}"
+    );
+    unsafe {
+        let mut acc = _mm256_setzero_ps();
+        for (x, y) in xs.iter().zip(ys.iter()) {
+            let mut sumi = _mm256_setzero_si256();
+            // The first 32 bytes of qs hold 5 * 32 values.
+            let q = _mm256_loadu_si256(x.qs.as_ptr() as *const __m256i);
+            let q0 = _mm256_cvtepu8_epi16(_mm256_extracti128_si256(q, 0));
+            let q1 = _mm256_cvtepu8_epi16(_mm256_extracti128_si256(q, 1));
+            for n in 0..5 {
+                let y = load_q8k_32(y, 32 * n);
+                let y0 = _mm256_cvtepi8_epi16(_mm256_extracti128_si256(y, 0));
+                let y1 = _mm256_cvtepi8_epi16(_mm256_extracti128_si256(y, 1));
+                sumi = _mm256_add_epi32(sumi, _mm256_madd_epi16(tq1_trits_16(q0, n), y0));
+                sumi = _mm256_add_epi32(sumi, _mm256_madd_epi16(tq1_trits_16(q1, n), y1));
+            }
+            // The next 16 bytes hold 5 * 16 values.
+            let q = _mm_loadu_si128(x.qs.as_ptr().add(32) as *const __m128i);
+            let q = _mm256_cvtepu8_epi16(q);
+            for n in 0..5 {
+                let y = _mm_loadu_si128(y.qs.as_ptr().add(160 + 16 * n) as *const __m128i);
+                let y = _mm256_cvtepi8_epi16(y);
+                sumi = _mm256_add_epi32(sumi, _mm256_madd_epi16(tq1_trits_16(q, n), y));
+            }
+            // The last 16 values are stored in qh.
+            let mut sumh = 0i32;
+            for n in 0..4 {
+                for (j, &qh) in x.qh.iter().enumerate() {
+                    sumh += (tq1_trit(qh, n) - 1) * y.qs[240 + 4 * n + j] as i32
+                }
+            }
+            let sumi = _mm256_add_epi32(sumi, _mm256_setr_epi32(sumh, 0, 0, 0, 0, 0, 0, 0));
+            let d = _mm256_set1_ps(x.d.to_f32() * y.d);
+            acc = _mm256_fmadd_ps(d, _mm256_cvtepi32_ps(sumi), acc);
+        }
+        hsum_float_8(acc)
+    }
+}
+
+#[inline(always)]
+pub(crate) fn vec_dot_tq2_0_q8k(n: usize, xs: &[BlockTQ2_0], ys: &[BlockQ8K]) -> f32 {
+    debug_assert!(
+        n.is_multiple_of(QK_K),
+        "vec_dot_tq2_0_q8k: {n} is not divisible by {QK_K}"
+    );
+    unsafe {
+        let m3 = _mm256_set1_epi8(3);
+        let one = _mm256_set1_epi8(1);
+        let mut acc = _mm256_setzero_ps();
+        for (x, y) in xs.iter().zip(ys.iter()) {
+            let mut sumi = _mm256_setzero_si256();
+            for j in (0..QK_K / 4).step_by(32) {
+                let q = _mm256_loadu_si256(x.qs.as_ptr().add(j) as *const __m256i);
+                for l in 0..4 {
+                    let shift = _mm_cvtsi32_si128(2 * l as i32);
+                    let q = _mm256_and_si256(_mm256_srl_epi16(q, shift), m3);
+                    let q = _mm256_sub_epi8(q, one);
+                    let (p0, p1) = dot_i8_halves(q, load_q8k_32(y, 4 * j + 32 * l));
+                    sumi = _mm256_add_epi32(sumi, _mm256_add_epi32(p0, p1));
+                }
+            }
+            let d = _mm256_set1_ps(x.d.to_f32() * y.d);
+            acc = _mm256_fmadd_ps(d, _mm256_cvtepi32_ps(sumi), acc);
+        }
+        hsum_float_8(acc)
+    }
+}
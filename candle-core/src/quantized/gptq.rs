@@ -0,0 +1,322 @@
+//! GPTQ and AWQ group-quantized weights.
+//!
+//! These checkpoints store the weights of linear layers as small integers packed in `int32`
+//! words, together with a scale and a zero point per group of input features:
+//! - `qweight`: the packed quantized values.
+//! - `qzeros`: the packed zero points, one per group and output feature.
+//! - `scales`: the scales, one per group and output feature.
+//! - `g_idx`: optional, the group of each input feature. This is used by GPTQ checkpoints that
+//!   have been quantized with act-order (`desc_act`) where groups are not contiguous.
+//!
+//! The dequantized weight is `w[k, n] = scales[g, n] * (q[k, n] - zeros[g, n])` with `g` the
+//! group of input feature `k`. The matmul runs on the cpu by dequantizing the weights on the fly,
+//! on other devices the weights are dequantized when building the [`super::QMatMul`].
+use crate::{CpuStorage, DType, Device, Layout, Result, Shape, Tensor};
+use half::f16;
+use rayon::prelude::*;
+
+/// The AWQ packing interleaves the values of 8 consecutive output features, the `i`-th nibble of
+/// a word holds the feature `[0, 2, 4, 6, 1, 3, 5, 7][i]`, i.e. feature `o` is in nibble
+/// `AWQ_SHIFT[o]`.
+const AWQ_SHIFT: [usize; 8] = [0, 4, 1, 5, 2, 6, 3, 7];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GptqFormat {
+    /// AutoGPTQ checkpoints, `qweight` is packed along the input features and the zero points
+    /// are stored with an offset of -1.
+    Gptq,
+    /// GPTQ checkpoints using the `gptq_v2` format, the zero points are stored without offset.
+    GptqV2,
+    /// AutoAWQ checkpoints using the GEMM layout, `qweight` is packed along the output features.
+    Awq,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct GptqConfig {
+    pub format: GptqFormat,
+    /// The number of bits per quantized value, either 4 or 8.
+    pub bits: usize,
+    /// The number of input features per group, `None` uses a single group per output feature.
+    pub group_size: Option<usize>,
+}
+
+impl GptqConfig {
+    pub fn gptq(bits: usize, group_size: usize) -> Self {
+        Self {
+            format: GptqFormat::Gptq,
+            bits,
+            group_size: Some(group_size),
+        }
+    }
+
+    pub fn awq(group_size: usize) -> Self {
+        Self {
+            format: GptqFormat::Awq,
+            bits: 4,
+            group_size: Some(group_size),
+        }
+    }
+}
+
+/// The weight of a GPTQ or AWQ linear layer, this is stored on the cpu. The values and zero
+/// points are kept packed as in the checkpoint and only unpacked in the matmul kernel.
+#[derive(Debug, Clone)]
+pub struct GptqTensor {
+    config: GptqConfig,
+    in_features: usize,
+    out_features: usize,
+    /// The packed quantized values, `(in_features / pack, out_features)` for GPTQ and
+    /// `(in_features, out_features / 8)` for AWQ.
+    qweight: Vec<u32>,
+    /// The packed zero points, `(n_groups, out_features / pack)`.
+    qzeros: Vec<u32>,
+    /// The scales, `(n_groups, out_features)`.
+    scales: Vec<f16>,
+    g_idx: Vec<u32>,
+}
+
+fn to_u32_vec(t: &Tensor) -> Result<Vec<u32>> {
+    let t = t.flatten_all()?;
+    match t.dtype() {
+        DType::U32 => t.to_vec1::<u32>(),
+        DType::I32 => Ok(t.to_vec1::<i32>()?.into_iter().map(|v| v as u32).collect()),
+        DType::I64 => Ok(t.to_vec1::<i64>()?.into_iter().map(|v| v as u32).collect()),
+        dtype => crate::bail!("unexpected dtype for gptq packed values {dtype:?}"),
+    }
+}
+
+impl GptqTensor {
+    /// Builds the weight from the tensors of a GPTQ or AWQ checkpoint. `qweight`, `qzeros` and
+    /// `g_idx` use an integer dtype while `scales` can use any float dtype, the scales are
+    /// stored as f16.
+    pub fn new(
+        config: GptqConfig,
+        qweight: &Tensor,
+        qzeros: &Tensor,
+        scales: &Tensor,
+        g_idx: Option<&Tensor>,
+    ) -> Result<Self> {
+        let bits = config.bits;
+        if bits != 4 && bits != 8 {
+            crate::bail!("unsupported number of bits for gptq weights {bits}")
+        }
+        if config.format == GptqFormat::Awq && bits != 4 {
+            crate::bail!("awq weights only support 4 bits, got {bits}")
+        }
+        let pack = 32 / bits;
+        let (n_groups, out_features) = scales.dims2()?;
+        if !out_features.is_multiple_of(pack) {
+            crate::bail!("{out_features} output features cannot be packed by {pack}")
+        }
+        let (d0, d1) = qweight.dims2()?;
+        let in_features = match config.format {
+            GptqFormat::Gptq | GptqFormat::GptqV2 => {
+                if d1 != out_features {
+                    crate::bail!(
+                        "qweight {:?} and scales {:?} mismatch",
+                        qweight.shape(),
+                        scales.shape()
+                    )
+                }
+                d0 * pack
+            }
+            GptqFormat::Awq => {
+                if d1 * pack != out_features {
+                    crate::bail!(
+                        "qweight {:?} and scales {:?} mismatch",
+                        qweight.shape(),
+                        scales.shape()
+                    )
+                }
+                d0
+            }
+        };
+        if qzeros.dims2()? != (n_groups, out_features / pack) {
+            crate::bail!(
+                "unexpected qzeros shape {:?} for scales {:?}",
+                qzeros.shape(),
+                scales.shape()
+            )
+        }
+        let g_idx = match g_idx {
+            Some(g_idx) => {
+                let g_idx = to_u32_vec(g_idx)?;
+                if g_idx.len() != in_features {
+                    crate::bail!("g_idx has {} values, expected {in_features}", g_idx.len())
+                }
+                if let Some(g) = g_idx.iter().find(|&&g| g as usize >= n_groups) {
+                    crate::bail!("g_idx value {g} is out of range for {n_groups} groups")
+                }
+                g_idx
+            }
+            None => {
+                let group_size = config.group_size.unwrap_or(in_features);
+                if in_features.div_ceil(group_size) != n_groups {
+                    crate::bail!(
+                        "{n_groups} groups do not match {in_features} features with group size {group_size}"
+                    )
+                }
+                (0..in_features).map(|k| (k / group_size) as u32).collect()
+            }
+        };
+        let scales = scales
+            .to_dtype(DType::F16)?
+            .flatten_all()?
+            .to_vec1::<f16>()?;
+        Ok(Self {
+            config,
+            in_features,
+            out_features,
+            qweight: to_u32_vec(qweight)?,
+            qzeros: to_u32_vec(qzeros)?,
+            scales,
+            g_idx,
+        })
+    }
+
+    pub fn config(&self) -> &GptqConfig {
+        &self.config
+    }
+
+    pub fn in_features(&self) -> usize {
+        self.in_features
+    }
+
+    pub fn out_features(&self) -> usize {
+        self.out_features
+    }
+
+    /// The shape of the weight, `(out_features, in_features)` as for the other linear layers.
+    pub fn shape(&self) -> Shape {
+        Shape::from((self.out_features, self.in_features))
+    }
+
+    /// The size used by the packed values, zero points, scales and group indexes.
+    pub fn storage_size_in_bytes(&self) -> usize {
+        self.qweight.len() * 4
+            + self.qzeros.len() * 4
+            + self.scales.len() * 2
+            + self.g_idx.len() * 4
+    }
+
+    /// Returns true if the groups are not contiguous, i.e. the weights use act-order.
+    pub fn has_act_order(&self) -> bool {
+        self.g_idx.windows(2).any(|w| w[1] < w[0])
+    }
+
+    /// Dequantizes the output features `start..start + dst.len()` of row `k` of the
+    /// `(in_features, out_features)` weight.
+    fn dequantize_row(&self, k: usize, start: usize, dst: &mut [f32]) {
+        let n = self.out_features;
+        let bits = self.config.bits;
+        let (pack, mask) = (32 / bits, (1u32 << bits) - 1);
+        let g = self.g_idx[k] as usize;
+        let scales = &self.scales[g * n + start..g * n + start + dst.len()];
+        let zeros = &self.qzeros[g * (n / pack)..(g + 1) * (n / pack)];
+        match self.config.format {
+            GptqFormat::Gptq | GptqFormat::GptqV2 => {
+                let offset = (self.config.format == GptqFormat::Gptq) as u32;
+                let shift = bits * (k % pack);
+                let words =
+                    &self.qweight[(k / pack) * n + start..(k / pack) * n + start + dst.len()];
+                for (j, ((d, &w), &s)) in dst.iter_mut().zip(words).zip(scales).enumerate() {
+                    let c = start + j;
+                    let q = (w >> shift) & mask;
+                    let z = ((zeros[c / pack] >> (bits * (c % pack))) & mask) + offset;
+                    *d = (q as f32 - z as f32) * s.to_f32()
+                }
+            }
+            GptqFormat::Awq => {
+                let words = &self.qweight[k * (n / 8)..(k + 1) * (n / 8)];
+                for (j, (d, &s)) in dst.iter_mut().zip(scales).enumerate() {
+                    let c = start + j;
+                    let shift = 4 * AWQ_SHIFT[c % 8];
+                    let q = (words[c / 8] >> shift) & 0xf;
+                    let z = (zeros[c / 8] >> shift) & 0xf;
+                    *d = (q as f32 - z as f32) * s.to_f32()
+                }
+            }
+        }
+    }
+
+    /// Returns the dequantized weight with shape `(out_features, in_features)`.
+    pub fn dequantize(&self, device: &Device) -> Result<Tensor> {
+        let mut w = vec![0f32; self.in_features * self.out_features];
+        for (k, row) in w.chunks_exact_mut(self.out_features).enumerate() {
+            self.dequantize_row(k, 0, row)
+        }
+        Tensor::from_vec(w, (self.in_features, self.out_features), device)?
+            .t()?
+            .contiguous()
+    }
+
+    /// Computes `xs @ w^T` for `xs` with shape `(m, in_features)`, `m` must not be zero.
+    fn matmul(&self, xs: &[f32], m: usize, dst: &mut [f32]) {
+        // The output features are split in chunks that are processed in parallel, within a chunk
+        // the weights are unpacked and dequantized one row at a time.
+        const CHUNK: usize = 256;
+        let (k_dim, n) = (self.in_features, self.out_features);
+        let chunks: Vec<(usize, Vec<f32>)> = (0..n)
+            .step_by(CHUNK)
+            .collect::<Vec<_>>()
+            .into_par_iter()
+            .map(|start| {
+                let len = usize::min(CHUNK, n - start);
+                let mut acc = vec![0f32; m * len];
+                let mut row = vec![0f32; len];
+                for k in 0..k_dim {
+                    self.dequantize_row(k, start, &mut row);
+                    for (i, acc) in acc.chunks_exact_mut(len).enumerate() {
+                        let x = xs[i * k_dim + k];
+                        if x == 0. {
+                            continue;
+                        }
+                        for (a, &r) in acc.iter_mut().zip(row.iter()) {
+                            *a += x * r
+                        }
+                    }
+                }
+                (start, acc)
+            })
+            .collect();
+        for (start, acc) in chunks {
+            let len = acc.len() / m;
+            for (i, acc) in acc.chunks_exact(len).enumerate() {
+                dst[i * n + start..i * n + start + len].copy_from_slice(acc)
+            }
+        }
+    }
+}
+
+impl crate::CustomOp1 for GptqTensor {
+    fn name(&self) -> &'static str {
+        "gptq-matmul"
+    }
+
+    fn cpu_fwd(&self, storage: &CpuStorage, layout: &Layout) -> Result<(CpuStorage, Shape)> {
+        let src_shape = layout.shape();
+        if src_shape.rank() < 2 {
+            crate::bail!("input tensor has only one dimension {layout:?}")
+        }
+        let mut dst_shape = src_shape.dims().to_vec();
+        let k = dst_shape.pop().unwrap();
+        if k != self.in_features {
+            crate::bail!(
+                "input tensor {layout:?} incompatible with {:?}",
+                self.shape()
+            )
+        }
+        dst_shape.push(self.out_features);
+        let dst_shape = Shape::from(dst_shape);
+        let xs = match layout.contiguous_offsets() {
+            Some((o1, o2)) => &storage.as_slice::<f32>()?[o1..o2],
+            None => crate::bail!("input tensor is not contiguous {layout:?}"),
+        };
+        let m = src_shape.elem_count() / k.max(1);
+        let mut dst = vec![0f32; dst_shape.elem_count()];
+        if m > 0 {
+            self.matmul(xs, m, &mut dst);
+        }
+        Ok((CpuStorage::F32(dst), dst_shape))
+    }
+}
@@ -1,11 +1,12 @@
 use std::collections::HashMap;
 use std::fs::File;
-use std::io::{Cursor, Read};
+use std::io::{Cursor, Read, Write};
 use std::path::Path;
+use std::sync::{Arc, Mutex};
 
-use byteorder::{LittleEndian, ReadBytesExt};
+use byteorder::{LittleEndian, ReadBytesExt, WriteBytesExt};
 
-use crate::Result;
+use crate::{DType, Result, Tensor};
 
 pub fn load_imatrix<P: AsRef<Path>>(fname: P) -> Result<HashMap<String, Vec<f32>>> {
     let mut all_data = HashMap::new();
@@ -83,3 +84,132 @@ pub fn load_imatrix<P: AsRef<Path>>(fname: P) -> Result<HashMap<String, Vec<f32>
 
     Ok(all_data)
 }
+
+/// The accumulated statistics for a single weight.
+#[derive(Debug, Clone)]
+pub struct ImatrixEntry {
+    /// The sum of the squared activations for each input feature.
+    pub sums: Vec<f64>,
+    /// The number of activation rows that have been accumulated.
+    pub rows: usize,
+    /// The number of chunks that contributed to this entry.
+    pub ncall: usize,
+}
+
+impl ImatrixEntry {
+    /// The mean squared activation for each input feature, this is the value returned by
+    /// [`load_imatrix`].
+    pub fn mean(&self) -> Vec<f32> {
+        let rows = self.rows.max(1) as f64;
+        self.sums.iter().map(|s| (s / rows) as f32).collect()
+    }
+}
+
+/// Writes an importance matrix using the legacy llama.cpp format. Each entry stores the mean
+/// squared activations multiplied by the number of calls, the number of calls and the name of the
+/// dataset are written at the end of the file.
+pub fn save_imatrix<P: AsRef<Path>>(
+    fname: P,
+    entries: &HashMap<String, ImatrixEntry>,
+    last_call: usize,
+    dataset: &str,
+) -> Result<()> {
+    let mut names: Vec<&String> = entries.keys().collect();
+    names.sort();
+    let mut buffer = Vec::new();
+    buffer.write_i32::<LittleEndian>(names.len() as i32)?;
+    for name in names {
+        let entry = &entries[name];
+        buffer.write_i32::<LittleEndian>(name.len() as i32)?;
+        buffer.write_all(name.as_bytes())?;
+        let ncall = entry.ncall.max(1);
+        buffer.write_i32::<LittleEndian>(ncall as i32)?;
+        buffer.write_i32::<LittleEndian>(entry.sums.len() as i32)?;
+        for v in entry.mean() {
+            buffer.write_f32::<LittleEndian>(v * ncall as f32)?;
+        }
+    }
+    buffer.write_i32::<LittleEndian>(last_call as i32)?;
+    buffer.write_i32::<LittleEndian>(dataset.len() as i32)?;
+    buffer.write_all(dataset.as_bytes())?;
+    std::fs::write(&fname, buffer).map_err(|e| {
+        crate::Error::msg(format!(
+            "Failed to write {}: {}",
+            fname.as_ref().display(),
+            e
+        ))
+    })?;
+    Ok(())
+}
+
+/// Accumulates the squared activations fed to the linear layers of a model, the resulting
+/// importance matrix can be used with `QTensor::quantize_imatrix`.
+///
+/// The collector is cheap to clone and all the clones share the same statistics. Calibration
+/// text is processed in chunks, [`ImatrixCollector::next_chunk`] should be called after each
+/// chunk so that the number of calls matches the llama.cpp convention.
+#[derive(Debug, Clone, Default)]
+pub struct ImatrixCollector {
+    entries: Arc<Mutex<HashMap<String, ImatrixEntry>>>,
+    chunks: Arc<Mutex<usize>>,
+}
+
+impl ImatrixCollector {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Records the activations `xs` used as input of the weight `name`, the last dimension of
+    /// `xs` is the input feature dimension.
+    pub fn record(&self, name: &str, xs: &Tensor) -> Result<()> {
+        let dim = xs.dim(crate::D::Minus1)?;
+        let xs = xs.to_dtype(DType::F32)?.reshape(((), dim))?;
+        let rows = xs.dim(0)?;
+        let sums = xs.sqr()?.sum(0)?.to_vec1::<f32>()?;
+        let chunk = *self.chunks.lock().unwrap();
+        let mut entries = self.entries.lock().unwrap();
+        let entry = entries
+            .entry(name.to_string())
+            .or_insert_with(|| ImatrixEntry {
+                sums: vec![0.; dim],
+                rows: 0,
+                ncall: 0,
+            });
+        if entry.sums.len() != dim {
+            crate::bail!(
+                "imatrix size mismatch for {name}, got {dim}, expected {}",
+                entry.sums.len()
+            )
+        }
+        for (s, v) in entry.sums.iter_mut().zip(sums.iter()) {
+            *s += *v as f64
+        }
+        entry.rows += rows;
+        entry.ncall = chunk + 1;
+        Ok(())
+    }
+
+    /// Marks the end of a calibration chunk.
+    pub fn next_chunk(&self) {
+        *self.chunks.lock().unwrap() += 1
+    }
+
+    /// The number of completed chunks.
+    pub fn chunks(&self) -> usize {
+        *self.chunks.lock().unwrap()
+    }
+
+    pub fn entries(&self) -> HashMap<String, ImatrixEntry> {
+        self.entries.lock().unwrap().clone()
+    }
+
+    /// Returns the importance matrix in the same form as [`load_imatrix`].
+    pub fn imatrix(&self) -> HashMap<String, Vec<f32>> {
+        let entries = self.entries.lock().unwrap();
+        entries.iter().map(|(k, v)| (k.clone(), v.mean())).collect()
+    }
+
+    pub fn save<P: AsRef<Path>>(&self, fname: P, dataset: &str) -> Result<()> {
+        save_imatrix(fname, &self.entries(), self.chunks(), dataset)
+    }
+}
@@ -0,0 +1,3982 @@
+//! Lookup tables used by the importance-quantization (IQ) formats.
+//!
+//! These are the same tables as the ones used in ggml, the grids list the valid combinations of
+//! 8 (or 4 for the 3-bit formats) quantized values. Each byte of a grid entry holds the magnitude
+//! of one value, signs are stored separately.
+// https://github.com/ggml-org/llama.cpp/blob/master/ggml/src/ggml-common.h
+
+pub(crate) const KMASK_IQ2XS: [u8; 8] = [1, 2, 4, 8, 16, 32, 64, 128];
+
+/// Maps 7 sign bits to 8 sign bits, the last one being set so that the number of negative
+/// values is even.
+pub(crate) const KSIGNS_IQ2XS: [u8; 128] = [
+    0, 129, 130, 3, 132, 5, 6, 135, 136, 9, 10, 139, 12, 141, 142, 15, 144, 17, 18, 147, 20, 149,
+    150, 23, 24, 153, 154, 27, 156, 29, 30, 159, 160, 33, 34, 163, 36, 165, 166, 39, 40, 169, 170,
+    43, 172, 45, 46, 175, 48, 177, 178, 51, 180, 53, 54, 183, 184, 57, 58, 187, 60, 189, 190, 63,
+    192, 65, 66, 195, 68, 197, 198, 71, 72, 201, 202, 75, 204, 77, 78, 207, 80, 209, 210, 83, 212,
+    85, 86, 215, 216, 89, 90, 219, 92, 221, 222, 95, 96, 225, 226, 99, 228, 101, 102, 231, 232,
+    105, 106, 235, 108, 237, 238, 111, 240, 113, 114, 243, 116, 245, 246, 119, 120, 249, 250, 123,
+    252, 125, 126, 255,
+];
+
+pub(crate) const KVALUES_IQ4NL: [i8; 16] = [
+    -127, -104, -83, -65, -49, -35, -22, -10, 1, 13, 25, 38, 53, 69, 89, 113,
+];
+
+pub(crate) const IQ2XXS_GRID: [u64; 256] = [
+    0x0808080808080808,
+    0x080808080808082b,
+    0x0808080808081919,
+    0x0808080808082b08,
+    0x0808080808082b2b,
+    0x0808080808190819,
+    0x0808080808191908,
+    0x08080808082b0808,
+    0x08080808082b082b,
+    0x08080808082b2b08,
+    0x08080808082b2b2b,
+    0x0808080819080819,
+    0x0808080819081908,
+    0x0808080819190808,
+    0x0808080819192b08,
+    0x08080808192b0819,
+    0x08080808192b1908,
+    0x080808082b080808,
+    0x080808082b08082b,
+    0x080808082b082b2b,
+    0x080808082b2b082b,
+    0x0808081908080819,
+    0x0808081908081908,
+    0x0808081908190808,
+    0x0808081908191919,
+    0x0808081919080808,
+    0x080808192b081908,
+    0x080808192b192b08,
+    0x0808082b08080808,
+    0x0808082b0808082b,
+    0x0808082b082b082b,
+    0x0808082b2b08082b,
+    0x0808190808080819,
+    0x0808190808081908,
+    0x0808190808190808,
+    0x08081908082b0819,
+    0x08081908082b1908,
+    0x0808190819080808,
+    0x080819081908082b,
+    0x0808190819082b08,
+    0x08081908192b0808,
+    0x080819082b080819,
+    0x080819082b081908,
+    0x080819082b190808,
+    0x080819082b2b1908,
+    0x0808191908080808,
+    0x080819190808082b,
+    0x0808191908082b08,
+    0x08081919082b0808,
+    0x080819191908192b,
+    0x08081919192b2b19,
+    0x080819192b080808,
+    0x080819192b190819,
+    0x0808192b08082b19,
+    0x0808192b08190808,
+    0x0808192b19080808,
+    0x0808192b2b081908,
+    0x0808192b2b2b1908,
+    0x08082b0808080808,
+    0x08082b0808081919,
+    0x08082b0808082b08,
+    0x08082b0808191908,
+    0x08082b08082b2b08,
+    0x08082b0819080819,
+    0x08082b0819081908,
+    0x08082b0819190808,
+    0x08082b081919082b,
+    0x08082b082b082b08,
+    0x08082b1908081908,
+    0x08082b1919080808,
+    0x08082b2b0808082b,
+    0x08082b2b08191908,
+    0x0819080808080819,
+    0x0819080808081908,
+    0x0819080808190808,
+    0x08190808082b0819,
+    0x0819080819080808,
+    0x08190808192b0808,
+    0x081908082b081908,
+    0x081908082b190808,
+    0x081908082b191919,
+    0x0819081908080808,
+    0x0819081908082b08,
+    0x08190819082b0808,
+    0x0819081919190808,
+    0x0819081919192b2b,
+    0x081908192b080808,
+    0x0819082b082b1908,
+    0x0819082b19081919,
+    0x0819190808080808,
+    0x0819190808082b08,
+    0x08191908082b0808,
+    0x08191908082b1919,
+    0x0819190819082b19,
+    0x081919082b080808,
+    0x0819191908192b08,
+    0x08191919192b082b,
+    0x0819192b08080808,
+    0x0819192b0819192b,
+    0x08192b0808080819,
+    0x08192b0808081908,
+    0x08192b0808190808,
+    0x08192b0819080808,
+    0x08192b082b080819,
+    0x08192b1908080808,
+    0x08192b1908081919,
+    0x08192b192b2b0808,
+    0x08192b2b19190819,
+    0x082b080808080808,
+    0x082b08080808082b,
+    0x082b080808082b2b,
+    0x082b080819081908,
+    0x082b0808192b0819,
+    0x082b08082b080808,
+    0x082b08082b08082b,
+    0x082b0819082b2b19,
+    0x082b081919082b08,
+    0x082b082b08080808,
+    0x082b082b0808082b,
+    0x082b190808080819,
+    0x082b190808081908,
+    0x082b190808190808,
+    0x082b190819080808,
+    0x082b19081919192b,
+    0x082b191908080808,
+    0x082b191919080819,
+    0x082b1919192b1908,
+    0x082b192b2b190808,
+    0x082b2b0808082b08,
+    0x082b2b08082b0808,
+    0x082b2b082b191908,
+    0x082b2b2b19081908,
+    0x1908080808080819,
+    0x1908080808081908,
+    0x1908080808190808,
+    0x1908080808192b08,
+    0x19080808082b0819,
+    0x19080808082b1908,
+    0x1908080819080808,
+    0x1908080819082b08,
+    0x190808081919192b,
+    0x19080808192b0808,
+    0x190808082b080819,
+    0x190808082b081908,
+    0x190808082b190808,
+    0x1908081908080808,
+    0x19080819082b0808,
+    0x19080819192b0819,
+    0x190808192b080808,
+    0x190808192b081919,
+    0x1908082b08080819,
+    0x1908082b08190808,
+    0x1908082b19082b08,
+    0x1908082b1919192b,
+    0x1908082b192b2b08,
+    0x1908190808080808,
+    0x1908190808082b08,
+    0x19081908082b0808,
+    0x190819082b080808,
+    0x190819082b192b19,
+    0x190819190819082b,
+    0x19081919082b1908,
+    0x1908192b08080808,
+    0x19082b0808080819,
+    0x19082b0808081908,
+    0x19082b0808190808,
+    0x19082b0819080808,
+    0x19082b0819081919,
+    0x19082b1908080808,
+    0x19082b1919192b08,
+    0x19082b19192b0819,
+    0x19082b192b08082b,
+    0x19082b2b19081919,
+    0x19082b2b2b190808,
+    0x1919080808080808,
+    0x1919080808082b08,
+    0x1919080808190819,
+    0x1919080808192b19,
+    0x19190808082b0808,
+    0x191908082b080808,
+    0x191908082b082b08,
+    0x1919081908081908,
+    0x191908191908082b,
+    0x191908192b2b1908,
+    0x1919082b2b190819,
+    0x191919082b190808,
+    0x191919082b19082b,
+    0x1919191908082b2b,
+    0x1919192b08080819,
+    0x1919192b19191908,
+    0x19192b0808080808,
+    0x19192b0808190819,
+    0x19192b0808192b19,
+    0x19192b08192b1908,
+    0x19192b1919080808,
+    0x19192b2b08082b08,
+    0x192b080808081908,
+    0x192b080808190808,
+    0x192b080819080808,
+    0x192b0808192b2b08,
+    0x192b081908080808,
+    0x192b081919191919,
+    0x192b082b08192b08,
+    0x192b082b192b0808,
+    0x192b190808080808,
+    0x192b190808081919,
+    0x192b191908190808,
+    0x192b19190819082b,
+    0x192b19192b081908,
+    0x192b2b081908082b,
+    0x2b08080808080808,
+    0x2b0808080808082b,
+    0x2b08080808082b2b,
+    0x2b08080819080819,
+    0x2b0808082b08082b,
+    0x2b08081908081908,
+    0x2b08081908192b08,
+    0x2b08081919080808,
+    0x2b08082b08190819,
+    0x2b08190808080819,
+    0x2b08190808081908,
+    0x2b08190808190808,
+    0x2b08190808191919,
+    0x2b08190819080808,
+    0x2b081908192b0808,
+    0x2b08191908080808,
+    0x2b0819191908192b,
+    0x2b0819192b191908,
+    0x2b08192b08082b19,
+    0x2b08192b19080808,
+    0x2b08192b192b0808,
+    0x2b082b080808082b,
+    0x2b082b1908081908,
+    0x2b082b2b08190819,
+    0x2b19080808081908,
+    0x2b19080808190808,
+    0x2b190808082b1908,
+    0x2b19080819080808,
+    0x2b1908082b2b0819,
+    0x2b1908190819192b,
+    0x2b1908192b080808,
+    0x2b19082b19081919,
+    0x2b19190808080808,
+    0x2b191908082b082b,
+    0x2b19190819081908,
+    0x2b19191919190819,
+    0x2b192b082b080819,
+    0x2b192b19082b0808,
+    0x2b2b08080808082b,
+    0x2b2b080819190808,
+    0x2b2b08082b081919,
+    0x2b2b081908082b19,
+    0x2b2b082b08080808,
+    0x2b2b190808192b08,
+    0x2b2b2b0819190808,
+    0x2b2b2b1908081908,
+];
+
+pub(crate) const IQ2XS_GRID: [u64; 512] = [
+    0x0808080808080808,
+    0x080808080808082b,
+    0x0808080808081919,
+    0x0808080808082b08,
+    0x0808080808082b2b,
+    0x0808080808190819,
+    0x0808080808191908,
+    0x080808080819192b,
+    0x0808080808192b19,
+    0x08080808082b0808,
+    0x08080808082b082b,
+    0x08080808082b1919,
+    0x08080808082b2b08,
+    0x0808080819080819,
+    0x0808080819081908,
+    0x080808081908192b,
+    0x0808080819082b19,
+    0x0808080819190808,
+    0x080808081919082b,
+    0x0808080819191919,
+    0x0808080819192b08,
+    0x08080808192b0819,
+    0x08080808192b1908,
+    0x080808082b080808,
+    0x080808082b08082b,
+    0x080808082b081919,
+    0x080808082b082b08,
+    0x080808082b190819,
+    0x080808082b191908,
+    0x080808082b192b19,
+    0x080808082b2b0808,
+    0x0808081908080819,
+    0x0808081908081908,
+    0x080808190808192b,
+    0x0808081908082b19,
+    0x0808081908190808,
+    0x080808190819082b,
+    0x0808081908191919,
+    0x0808081908192b08,
+    0x0808081908192b2b,
+    0x08080819082b0819,
+    0x08080819082b1908,
+    0x0808081919080808,
+    0x080808191908082b,
+    0x0808081919081919,
+    0x0808081919082b08,
+    0x0808081919190819,
+    0x0808081919191908,
+    0x08080819192b0808,
+    0x08080819192b2b08,
+    0x080808192b080819,
+    0x080808192b081908,
+    0x080808192b190808,
+    0x0808082b08080808,
+    0x0808082b0808082b,
+    0x0808082b08081919,
+    0x0808082b08082b08,
+    0x0808082b08190819,
+    0x0808082b08191908,
+    0x0808082b082b0808,
+    0x0808082b19080819,
+    0x0808082b19081908,
+    0x0808082b19190808,
+    0x0808082b19191919,
+    0x0808082b2b080808,
+    0x0808082b2b082b2b,
+    0x0808190808080819,
+    0x0808190808081908,
+    0x080819080808192b,
+    0x0808190808082b19,
+    0x0808190808190808,
+    0x080819080819082b,
+    0x0808190808191919,
+    0x0808190808192b08,
+    0x08081908082b0819,
+    0x08081908082b1908,
+    0x0808190819080808,
+    0x080819081908082b,
+    0x0808190819081919,
+    0x0808190819082b08,
+    0x0808190819190819,
+    0x0808190819191908,
+    0x080819081919192b,
+    0x08081908192b0808,
+    0x080819082b080819,
+    0x080819082b081908,
+    0x080819082b190808,
+    0x0808191908080808,
+    0x080819190808082b,
+    0x0808191908081919,
+    0x0808191908082b08,
+    0x0808191908190819,
+    0x0808191908191908,
+    0x08081919082b0808,
+    0x0808191919080819,
+    0x0808191919081908,
+    0x0808191919190808,
+    0x08081919192b0819,
+    0x080819192b080808,
+    0x0808192b08080819,
+    0x0808192b08081908,
+    0x0808192b08190808,
+    0x0808192b082b192b,
+    0x0808192b19080808,
+    0x0808192b1908082b,
+    0x0808192b2b081908,
+    0x08082b0808080808,
+    0x08082b080808082b,
+    0x08082b0808081919,
+    0x08082b0808082b08,
+    0x08082b0808082b2b,
+    0x08082b0808190819,
+    0x08082b0808191908,
+    0x08082b08082b0808,
+    0x08082b08082b1919,
+    0x08082b0819080819,
+    0x08082b0819081908,
+    0x08082b0819190808,
+    0x08082b0819192b08,
+    0x08082b082b080808,
+    0x08082b082b2b0808,
+    0x08082b082b2b2b2b,
+    0x08082b1908080819,
+    0x08082b1908081908,
+    0x08082b1908190808,
+    0x08082b1919080808,
+    0x08082b192b080819,
+    0x08082b192b082b19,
+    0x08082b2b08080808,
+    0x08082b2b082b0808,
+    0x08082b2b082b2b08,
+    0x08082b2b2b19192b,
+    0x08082b2b2b2b0808,
+    0x0819080808080819,
+    0x0819080808081908,
+    0x081908080808192b,
+    0x0819080808082b19,
+    0x0819080808190808,
+    0x081908080819082b,
+    0x0819080808191919,
+    0x0819080808192b08,
+    0x08190808082b0819,
+    0x08190808082b1908,
+    0x0819080819080808,
+    0x081908081908082b,
+    0x0819080819081919,
+    0x0819080819082b08,
+    0x0819080819190819,
+    0x0819080819191908,
+    0x08190808192b0808,
+    0x08190808192b2b2b,
+    0x081908082b080819,
+    0x081908082b081908,
+    0x081908082b190808,
+    0x0819081908080808,
+    0x081908190808082b,
+    0x0819081908081919,
+    0x0819081908082b08,
+    0x0819081908190819,
+    0x0819081908191908,
+    0x08190819082b0808,
+    0x0819081919080819,
+    0x0819081919081908,
+    0x0819081919190808,
+    0x081908192b080808,
+    0x081908192b191908,
+    0x081908192b19192b,
+    0x0819082b08080819,
+    0x0819082b08081908,
+    0x0819082b0808192b,
+    0x0819082b08190808,
+    0x0819082b19080808,
+    0x0819082b192b0808,
+    0x0819190808080808,
+    0x081919080808082b,
+    0x0819190808081919,
+    0x0819190808082b08,
+    0x0819190808190819,
+    0x0819190808191908,
+    0x08191908082b0808,
+    0x0819190819080819,
+    0x0819190819081908,
+    0x0819190819082b19,
+    0x0819190819190808,
+    0x08191908192b1908,
+    0x081919082b080808,
+    0x0819191908080819,
+    0x0819191908081908,
+    0x0819191908190808,
+    0x0819191919080808,
+    0x0819192b08080808,
+    0x0819192b08191908,
+    0x0819192b19082b19,
+    0x08192b0808080819,
+    0x08192b0808081908,
+    0x08192b0808190808,
+    0x08192b080819082b,
+    0x08192b0819080808,
+    0x08192b0819191908,
+    0x08192b082b08192b,
+    0x08192b1908080808,
+    0x08192b1908081919,
+    0x08192b19192b192b,
+    0x08192b2b19190819,
+    0x08192b2b2b2b2b19,
+    0x082b080808080808,
+    0x082b08080808082b,
+    0x082b080808081919,
+    0x082b080808082b08,
+    0x082b080808082b2b,
+    0x082b080808190819,
+    0x082b080808191908,
+    0x082b0808082b0808,
+    0x082b080819080819,
+    0x082b080819081908,
+    0x082b080819190808,
+    0x082b08082b080808,
+    0x082b08082b2b0808,
+    0x082b081908080819,
+    0x082b081908081908,
+    0x082b081908190808,
+    0x082b081919080808,
+    0x082b081919082b08,
+    0x082b0819192b1919,
+    0x082b082b08080808,
+    0x082b082b082b082b,
+    0x082b082b2b080808,
+    0x082b082b2b2b2b08,
+    0x082b190808080819,
+    0x082b190808081908,
+    0x082b190808190808,
+    0x082b1908082b2b19,
+    0x082b190819080808,
+    0x082b191908080808,
+    0x082b191919080819,
+    0x082b19191919082b,
+    0x082b19192b192b19,
+    0x082b192b08080819,
+    0x082b192b08192b2b,
+    0x082b192b2b2b192b,
+    0x082b2b0808080808,
+    0x082b2b0808082b08,
+    0x082b2b0808082b2b,
+    0x082b2b08082b0808,
+    0x082b2b0819191919,
+    0x082b2b082b082b08,
+    0x082b2b082b2b082b,
+    0x082b2b19192b2b08,
+    0x082b2b192b190808,
+    0x082b2b2b08082b08,
+    0x082b2b2b082b0808,
+    0x082b2b2b2b08082b,
+    0x082b2b2b2b082b08,
+    0x082b2b2b2b082b2b,
+    0x1908080808080819,
+    0x1908080808081908,
+    0x190808080808192b,
+    0x1908080808082b19,
+    0x1908080808190808,
+    0x190808080819082b,
+    0x1908080808191919,
+    0x1908080808192b08,
+    0x19080808082b0819,
+    0x19080808082b1908,
+    0x1908080819080808,
+    0x190808081908082b,
+    0x1908080819081919,
+    0x1908080819082b08,
+    0x1908080819082b2b,
+    0x1908080819190819,
+    0x1908080819191908,
+    0x19080808192b0808,
+    0x19080808192b1919,
+    0x190808082b080819,
+    0x190808082b081908,
+    0x190808082b190808,
+    0x1908081908080808,
+    0x190808190808082b,
+    0x1908081908081919,
+    0x1908081908082b08,
+    0x1908081908190819,
+    0x1908081908191908,
+    0x19080819082b0808,
+    0x1908081919080819,
+    0x1908081919081908,
+    0x1908081919190808,
+    0x190808192b080808,
+    0x190808192b081919,
+    0x190808192b2b082b,
+    0x1908082b08080819,
+    0x1908082b08081908,
+    0x1908082b08190808,
+    0x1908082b0819082b,
+    0x1908082b082b2b19,
+    0x1908082b19080808,
+    0x1908190808080808,
+    0x190819080808082b,
+    0x1908190808081919,
+    0x1908190808082b08,
+    0x1908190808190819,
+    0x1908190808191908,
+    0x1908190808192b19,
+    0x19081908082b0808,
+    0x1908190819080819,
+    0x1908190819081908,
+    0x1908190819190808,
+    0x190819082b080808,
+    0x190819082b191908,
+    0x1908191908080819,
+    0x1908191908081908,
+    0x1908191908190808,
+    0x19081919082b1908,
+    0x1908191919080808,
+    0x190819192b192b2b,
+    0x1908192b08080808,
+    0x1908192b08082b2b,
+    0x1908192b19081908,
+    0x1908192b19190808,
+    0x19082b0808080819,
+    0x19082b0808081908,
+    0x19082b0808190808,
+    0x19082b0819080808,
+    0x19082b0819081919,
+    0x19082b0819191908,
+    0x19082b08192b082b,
+    0x19082b1908080808,
+    0x19082b1908190819,
+    0x19082b1919081908,
+    0x19082b1919190808,
+    0x19082b19192b2b19,
+    0x19082b2b08081908,
+    0x1919080808080808,
+    0x191908080808082b,
+    0x1919080808081919,
+    0x1919080808082b08,
+    0x1919080808190819,
+    0x1919080808191908,
+    0x19190808082b0808,
+    0x19190808082b2b08,
+    0x1919080819080819,
+    0x1919080819081908,
+    0x1919080819190808,
+    0x191908082b080808,
+    0x1919081908080819,
+    0x1919081908081908,
+    0x1919081908190808,
+    0x1919081908191919,
+    0x1919081919080808,
+    0x191908191908082b,
+    0x1919082b08080808,
+    0x1919082b19081908,
+    0x1919082b2b2b2b2b,
+    0x1919190808080819,
+    0x1919190808081908,
+    0x1919190808190808,
+    0x19191908082b0819,
+    0x1919190819080808,
+    0x19191908192b0808,
+    0x191919082b080819,
+    0x191919082b2b0819,
+    0x1919191908080808,
+    0x1919191908082b08,
+    0x191919192b080808,
+    0x191919192b082b08,
+    0x1919192b082b0819,
+    0x1919192b192b2b08,
+    0x1919192b2b2b0819,
+    0x19192b0808080808,
+    0x19192b0808191908,
+    0x19192b0819080819,
+    0x19192b0819190808,
+    0x19192b082b192b19,
+    0x19192b1908192b2b,
+    0x19192b1919080808,
+    0x19192b191908082b,
+    0x19192b2b2b081919,
+    0x192b080808080819,
+    0x192b080808081908,
+    0x192b080808190808,
+    0x192b080819080808,
+    0x192b080819191908,
+    0x192b0808192b082b,
+    0x192b08082b08192b,
+    0x192b08082b2b2b19,
+    0x192b081908080808,
+    0x192b082b082b1908,
+    0x192b082b19082b2b,
+    0x192b082b2b19082b,
+    0x192b190808080808,
+    0x192b19080819192b,
+    0x192b191908190808,
+    0x192b191919080808,
+    0x192b191919081919,
+    0x192b19192b2b1908,
+    0x192b2b0808080819,
+    0x192b2b08192b2b2b,
+    0x192b2b19082b1919,
+    0x192b2b2b0808192b,
+    0x192b2b2b19191908,
+    0x192b2b2b192b082b,
+    0x2b08080808080808,
+    0x2b0808080808082b,
+    0x2b08080808081919,
+    0x2b08080808082b08,
+    0x2b08080808190819,
+    0x2b08080808191908,
+    0x2b080808082b0808,
+    0x2b080808082b2b2b,
+    0x2b08080819080819,
+    0x2b08080819081908,
+    0x2b08080819190808,
+    0x2b0808082b080808,
+    0x2b0808082b08082b,
+    0x2b0808082b2b2b08,
+    0x2b0808082b2b2b2b,
+    0x2b08081908080819,
+    0x2b08081908081908,
+    0x2b0808190808192b,
+    0x2b08081908190808,
+    0x2b08081919080808,
+    0x2b08081919190819,
+    0x2b08081919192b19,
+    0x2b08082b08080808,
+    0x2b08082b082b0808,
+    0x2b08082b2b080808,
+    0x2b08082b2b08082b,
+    0x2b08082b2b2b0808,
+    0x2b08082b2b2b2b08,
+    0x2b08190808080819,
+    0x2b08190808081908,
+    0x2b08190808190808,
+    0x2b0819080819082b,
+    0x2b08190808191919,
+    0x2b08190819080808,
+    0x2b081908192b0808,
+    0x2b0819082b082b19,
+    0x2b08191908080808,
+    0x2b08191919081908,
+    0x2b0819192b2b1919,
+    0x2b08192b08192b08,
+    0x2b08192b192b2b2b,
+    0x2b082b0808080808,
+    0x2b082b0808082b08,
+    0x2b082b08082b1919,
+    0x2b082b0819192b2b,
+    0x2b082b082b080808,
+    0x2b082b082b08082b,
+    0x2b082b082b2b2b08,
+    0x2b082b190808192b,
+    0x2b082b2b082b082b,
+    0x2b082b2b2b080808,
+    0x2b082b2b2b082b08,
+    0x2b082b2b2b19192b,
+    0x2b082b2b2b2b2b08,
+    0x2b19080808080819,
+    0x2b19080808081908,
+    0x2b19080808190808,
+    0x2b19080819080808,
+    0x2b1908081919192b,
+    0x2b1908082b081908,
+    0x2b19081908080808,
+    0x2b190819082b082b,
+    0x2b190819192b1908,
+    0x2b19082b1919192b,
+    0x2b19082b2b082b19,
+    0x2b19190808080808,
+    0x2b19190808081919,
+    0x2b19190819081908,
+    0x2b19190819190808,
+    0x2b19190819192b08,
+    0x2b191919082b2b19,
+    0x2b1919192b190808,
+    0x2b1919192b19082b,
+    0x2b19192b19080819,
+    0x2b192b0819190819,
+    0x2b192b082b2b192b,
+    0x2b192b1919082b19,
+    0x2b192b2b08191919,
+    0x2b192b2b192b0808,
+    0x2b2b080808080808,
+    0x2b2b08080808082b,
+    0x2b2b080808082b08,
+    0x2b2b080808082b2b,
+    0x2b2b0808082b0808,
+    0x2b2b0808082b2b2b,
+    0x2b2b08082b2b0808,
+    0x2b2b081919190819,
+    0x2b2b081919192b19,
+    0x2b2b08192b2b192b,
+    0x2b2b082b08080808,
+    0x2b2b082b0808082b,
+    0x2b2b082b08082b08,
+    0x2b2b082b082b2b2b,
+    0x2b2b082b2b080808,
+    0x2b2b082b2b2b0808,
+    0x2b2b190819080808,
+    0x2b2b19082b191919,
+    0x2b2b192b192b1919,
+    0x2b2b192b2b192b08,
+    0x2b2b2b0808082b2b,
+    0x2b2b2b08082b0808,
+    0x2b2b2b08082b082b,
+    0x2b2b2b08082b2b08,
+    0x2b2b2b082b2b0808,
+    0x2b2b2b082b2b2b08,
+    0x2b2b2b1908081908,
+    0x2b2b2b192b081908,
+    0x2b2b2b192b08192b,
+    0x2b2b2b2b082b2b08,
+    0x2b2b2b2b082b2b2b,
+    0x2b2b2b2b2b190819,
+    0x2b2b2b2b2b2b2b2b,
+];
+
+pub(crate) const IQ2S_GRID: [u64; 1024] = [
+    0x0808080808080808,
+    0x080808080808082b,
+    0x0808080808081919,
+    0x0808080808082b08,
+    0x0808080808082b2b,
+    0x0808080808190819,
+    0x0808080808191908,
+    0x080808080819192b,
+    0x0808080808192b19,
+    0x08080808082b0808,
+    0x08080808082b082b,
+    0x08080808082b1919,
+    0x08080808082b2b08,
+    0x0808080819080819,
+    0x0808080819081908,
+    0x080808081908192b,
+    0x0808080819082b19,
+    0x0808080819190808,
+    0x080808081919082b,
+    0x0808080819191919,
+    0x0808080819192b08,
+    0x08080808192b0819,
+    0x08080808192b1908,
+    0x08080808192b192b,
+    0x08080808192b2b19,
+    0x080808082b080808,
+    0x080808082b08082b,
+    0x080808082b081919,
+    0x080808082b082b08,
+    0x080808082b190819,
+    0x080808082b191908,
+    0x080808082b2b0808,
+    0x080808082b2b1919,
+    0x080808082b2b2b2b,
+    0x0808081908080819,
+    0x0808081908081908,
+    0x080808190808192b,
+    0x0808081908082b19,
+    0x0808081908190808,
+    0x080808190819082b,
+    0x0808081908191919,
+    0x0808081908192b08,
+    0x08080819082b0819,
+    0x08080819082b1908,
+    0x0808081919080808,
+    0x080808191908082b,
+    0x0808081919081919,
+    0x0808081919082b08,
+    0x0808081919190819,
+    0x0808081919191908,
+    0x080808191919192b,
+    0x0808081919192b19,
+    0x08080819192b0808,
+    0x08080819192b1919,
+    0x08080819192b2b08,
+    0x080808192b080819,
+    0x080808192b081908,
+    0x080808192b190808,
+    0x080808192b19082b,
+    0x080808192b191919,
+    0x080808192b2b0819,
+    0x080808192b2b1908,
+    0x0808082b08080808,
+    0x0808082b0808082b,
+    0x0808082b08081919,
+    0x0808082b08082b08,
+    0x0808082b08190819,
+    0x0808082b08191908,
+    0x0808082b082b0808,
+    0x0808082b082b2b2b,
+    0x0808082b19080819,
+    0x0808082b19081908,
+    0x0808082b1908192b,
+    0x0808082b19082b19,
+    0x0808082b19190808,
+    0x0808082b19191919,
+    0x0808082b2b080808,
+    0x0808082b2b081919,
+    0x0808082b2b082b2b,
+    0x0808082b2b191908,
+    0x0808082b2b2b082b,
+    0x0808190808080819,
+    0x0808190808081908,
+    0x080819080808192b,
+    0x0808190808082b19,
+    0x0808190808190808,
+    0x080819080819082b,
+    0x0808190808191919,
+    0x0808190808192b08,
+    0x08081908082b0819,
+    0x08081908082b1908,
+    0x08081908082b192b,
+    0x08081908082b2b19,
+    0x0808190819080808,
+    0x080819081908082b,
+    0x0808190819081919,
+    0x0808190819082b08,
+    0x0808190819082b2b,
+    0x0808190819190819,
+    0x0808190819191908,
+    0x080819081919192b,
+    0x0808190819192b19,
+    0x08081908192b0808,
+    0x08081908192b082b,
+    0x08081908192b1919,
+    0x080819082b080819,
+    0x080819082b081908,
+    0x080819082b08192b,
+    0x080819082b082b19,
+    0x080819082b190808,
+    0x080819082b191919,
+    0x080819082b192b08,
+    0x080819082b2b0819,
+    0x080819082b2b1908,
+    0x0808191908080808,
+    0x080819190808082b,
+    0x0808191908081919,
+    0x0808191908082b08,
+    0x0808191908082b2b,
+    0x0808191908190819,
+    0x0808191908191908,
+    0x080819190819192b,
+    0x0808191908192b19,
+    0x08081919082b0808,
+    0x08081919082b1919,
+    0x08081919082b2b08,
+    0x0808191919080819,
+    0x0808191919081908,
+    0x080819191908192b,
+    0x0808191919082b19,
+    0x0808191919190808,
+    0x080819191919082b,
+    0x0808191919191919,
+    0x0808191919192b08,
+    0x08081919192b0819,
+    0x08081919192b1908,
+    0x080819192b080808,
+    0x080819192b08082b,
+    0x080819192b081919,
+    0x080819192b082b08,
+    0x080819192b190819,
+    0x080819192b191908,
+    0x080819192b2b0808,
+    0x0808192b08080819,
+    0x0808192b08081908,
+    0x0808192b0808192b,
+    0x0808192b08082b19,
+    0x0808192b08190808,
+    0x0808192b08191919,
+    0x0808192b19080808,
+    0x0808192b19081919,
+    0x0808192b19082b08,
+    0x0808192b19190819,
+    0x0808192b19191908,
+    0x0808192b192b0808,
+    0x0808192b2b080819,
+    0x0808192b2b081908,
+    0x0808192b2b190808,
+    0x08082b0808080808,
+    0x08082b080808082b,
+    0x08082b0808081919,
+    0x08082b0808082b08,
+    0x08082b0808190819,
+    0x08082b0808191908,
+    0x08082b080819192b,
+    0x08082b0808192b19,
+    0x08082b08082b0808,
+    0x08082b08082b1919,
+    0x08082b08082b2b2b,
+    0x08082b0819080819,
+    0x08082b0819081908,
+    0x08082b081908192b,
+    0x08082b0819082b19,
+    0x08082b0819190808,
+    0x08082b081919082b,
+    0x08082b0819191919,
+    0x08082b0819192b08,
+    0x08082b08192b0819,
+    0x08082b08192b1908,
+    0x08082b082b080808,
+    0x08082b082b081919,
+    0x08082b082b191908,
+    0x08082b082b2b2b2b,
+    0x08082b1908080819,
+    0x08082b1908081908,
+    0x08082b1908190808,
+    0x08082b190819082b,
+    0x08082b1908191919,
+    0x08082b1908192b08,
+    0x08082b19082b0819,
+    0x08082b1919080808,
+    0x08082b1919081919,
+    0x08082b1919082b08,
+    0x08082b1919190819,
+    0x08082b1919191908,
+    0x08082b19192b0808,
+    0x08082b192b080819,
+    0x08082b192b190808,
+    0x08082b2b08080808,
+    0x08082b2b08190819,
+    0x08082b2b08191908,
+    0x08082b2b082b082b,
+    0x08082b2b082b2b08,
+    0x08082b2b082b2b2b,
+    0x08082b2b19190808,
+    0x08082b2b2b192b19,
+    0x0819080808080819,
+    0x0819080808081908,
+    0x081908080808192b,
+    0x0819080808082b19,
+    0x0819080808190808,
+    0x081908080819082b,
+    0x0819080808191919,
+    0x0819080808192b08,
+    0x08190808082b0819,
+    0x08190808082b1908,
+    0x08190808082b192b,
+    0x0819080819080808,
+    0x081908081908082b,
+    0x0819080819081919,
+    0x0819080819082b08,
+    0x0819080819190819,
+    0x0819080819191908,
+    0x081908081919192b,
+    0x0819080819192b19,
+    0x08190808192b0808,
+    0x08190808192b082b,
+    0x08190808192b1919,
+    0x08190808192b2b08,
+    0x081908082b080819,
+    0x081908082b081908,
+    0x081908082b08192b,
+    0x081908082b190808,
+    0x081908082b191919,
+    0x081908082b192b08,
+    0x081908082b2b0819,
+    0x081908082b2b1908,
+    0x0819081908080808,
+    0x081908190808082b,
+    0x0819081908081919,
+    0x0819081908082b08,
+    0x0819081908082b2b,
+    0x0819081908190819,
+    0x0819081908191908,
+    0x081908190819192b,
+    0x0819081908192b19,
+    0x08190819082b0808,
+    0x08190819082b082b,
+    0x08190819082b1919,
+    0x08190819082b2b08,
+    0x0819081919080819,
+    0x0819081919081908,
+    0x081908191908192b,
+    0x0819081919082b19,
+    0x0819081919190808,
+    0x081908191919082b,
+    0x0819081919191919,
+    0x0819081919192b08,
+    0x08190819192b0819,
+    0x08190819192b1908,
+    0x081908192b080808,
+    0x081908192b08082b,
+    0x081908192b081919,
+    0x081908192b082b08,
+    0x081908192b190819,
+    0x081908192b191908,
+    0x0819082b08080819,
+    0x0819082b08081908,
+    0x0819082b08082b19,
+    0x0819082b08190808,
+    0x0819082b08191919,
+    0x0819082b082b0819,
+    0x0819082b082b1908,
+    0x0819082b19080808,
+    0x0819082b19081919,
+    0x0819082b19190819,
+    0x0819082b19191908,
+    0x0819082b2b080819,
+    0x0819082b2b081908,
+    0x0819082b2b190808,
+    0x0819190808080808,
+    0x081919080808082b,
+    0x0819190808081919,
+    0x0819190808082b08,
+    0x0819190808190819,
+    0x0819190808191908,
+    0x081919080819192b,
+    0x0819190808192b19,
+    0x08191908082b0808,
+    0x08191908082b1919,
+    0x08191908082b2b08,
+    0x0819190819080819,
+    0x0819190819081908,
+    0x081919081908192b,
+    0x0819190819082b19,
+    0x0819190819190808,
+    0x081919081919082b,
+    0x0819190819191919,
+    0x0819190819192b08,
+    0x08191908192b0819,
+    0x08191908192b1908,
+    0x081919082b080808,
+    0x081919082b08082b,
+    0x081919082b081919,
+    0x081919082b082b08,
+    0x081919082b190819,
+    0x081919082b191908,
+    0x081919082b2b0808,
+    0x0819191908080819,
+    0x0819191908081908,
+    0x081919190808192b,
+    0x0819191908082b19,
+    0x0819191908190808,
+    0x081919190819082b,
+    0x0819191908191919,
+    0x0819191908192b08,
+    0x08191919082b0819,
+    0x08191919082b1908,
+    0x0819191919080808,
+    0x081919191908082b,
+    0x0819191919081919,
+    0x0819191919082b08,
+    0x0819191919190819,
+    0x0819191919191908,
+    0x08191919192b0808,
+    0x081919192b080819,
+    0x081919192b081908,
+    0x081919192b190808,
+    0x0819192b08080808,
+    0x0819192b08081919,
+    0x0819192b08082b08,
+    0x0819192b08190819,
+    0x0819192b08191908,
+    0x0819192b082b0808,
+    0x0819192b19080819,
+    0x0819192b19081908,
+    0x0819192b19190808,
+    0x0819192b2b080808,
+    0x0819192b2b2b2b2b,
+    0x08192b0808080819,
+    0x08192b0808081908,
+    0x08192b080808192b,
+    0x08192b0808082b19,
+    0x08192b0808190808,
+    0x08192b0808191919,
+    0x08192b0808192b08,
+    0x08192b08082b0819,
+    0x08192b0819080808,
+    0x08192b081908082b,
+    0x08192b0819081919,
+    0x08192b0819082b08,
+    0x08192b0819190819,
+    0x08192b0819191908,
+    0x08192b08192b0808,
+    0x08192b082b080819,
+    0x08192b082b081908,
+    0x08192b1908080808,
+    0x08192b190808082b,
+    0x08192b1908081919,
+    0x08192b1908082b08,
+    0x08192b1908190819,
+    0x08192b1908191908,
+    0x08192b19082b0808,
+    0x08192b1919080819,
+    0x08192b1919081908,
+    0x08192b1919190808,
+    0x08192b19192b2b19,
+    0x08192b192b2b082b,
+    0x08192b2b08081908,
+    0x08192b2b08190808,
+    0x08192b2b19080808,
+    0x08192b2b1919192b,
+    0x082b080808080808,
+    0x082b08080808082b,
+    0x082b080808081919,
+    0x082b080808082b08,
+    0x082b080808190819,
+    0x082b080808191908,
+    0x082b08080819192b,
+    0x082b080808192b19,
+    0x082b0808082b0808,
+    0x082b0808082b1919,
+    0x082b0808082b2b2b,
+    0x082b080819080819,
+    0x082b080819081908,
+    0x082b080819190808,
+    0x082b08081919082b,
+    0x082b080819191919,
+    0x082b0808192b1908,
+    0x082b08082b080808,
+    0x082b08082b082b2b,
+    0x082b08082b191908,
+    0x082b08082b2b2b2b,
+    0x082b081908080819,
+    0x082b081908081908,
+    0x082b081908190808,
+    0x082b08190819082b,
+    0x082b081908191919,
+    0x082b0819082b0819,
+    0x082b081919080808,
+    0x082b08191908082b,
+    0x082b081919081919,
+    0x082b081919190819,
+    0x082b081919191908,
+    0x082b0819192b0808,
+    0x082b08192b080819,
+    0x082b08192b081908,
+    0x082b08192b190808,
+    0x082b082b08080808,
+    0x082b082b08082b2b,
+    0x082b082b082b082b,
+    0x082b082b082b2b08,
+    0x082b082b082b2b2b,
+    0x082b082b19081908,
+    0x082b082b19190808,
+    0x082b082b2b082b08,
+    0x082b082b2b082b2b,
+    0x082b082b2b2b2b08,
+    0x082b190808080819,
+    0x082b190808081908,
+    0x082b19080808192b,
+    0x082b190808082b19,
+    0x082b190808190808,
+    0x082b190808191919,
+    0x082b190808192b08,
+    0x082b1908082b0819,
+    0x082b1908082b1908,
+    0x082b190819080808,
+    0x082b19081908082b,
+    0x082b190819081919,
+    0x082b190819082b08,
+    0x082b190819190819,
+    0x082b190819191908,
+    0x082b1908192b0808,
+    0x082b19082b080819,
+    0x082b19082b081908,
+    0x082b19082b190808,
+    0x082b191908080808,
+    0x082b191908081919,
+    0x082b191908082b08,
+    0x082b191908190819,
+    0x082b191908191908,
+    0x082b1919082b0808,
+    0x082b191919080819,
+    0x082b191919081908,
+    0x082b191919190808,
+    0x082b1919192b192b,
+    0x082b19192b080808,
+    0x082b192b08080819,
+    0x082b192b08081908,
+    0x082b192b08190808,
+    0x082b192b19080808,
+    0x082b192b19192b19,
+    0x082b2b0808080808,
+    0x082b2b0808081919,
+    0x082b2b0808190819,
+    0x082b2b0808191908,
+    0x082b2b0819080819,
+    0x082b2b0819081908,
+    0x082b2b0819190808,
+    0x082b2b082b082b2b,
+    0x082b2b082b2b2b2b,
+    0x082b2b1908080819,
+    0x082b2b1908081908,
+    0x082b2b1908190808,
+    0x082b2b192b191919,
+    0x082b2b2b08082b2b,
+    0x082b2b2b082b082b,
+    0x082b2b2b192b1908,
+    0x082b2b2b2b082b08,
+    0x082b2b2b2b082b2b,
+    0x1908080808080819,
+    0x1908080808081908,
+    0x190808080808192b,
+    0x1908080808082b19,
+    0x1908080808190808,
+    0x190808080819082b,
+    0x1908080808191919,
+    0x1908080808192b08,
+    0x1908080808192b2b,
+    0x19080808082b0819,
+    0x19080808082b1908,
+    0x19080808082b192b,
+    0x1908080819080808,
+    0x190808081908082b,
+    0x1908080819081919,
+    0x1908080819082b08,
+    0x1908080819082b2b,
+    0x1908080819190819,
+    0x1908080819191908,
+    0x190808081919192b,
+    0x1908080819192b19,
+    0x19080808192b0808,
+    0x19080808192b082b,
+    0x19080808192b1919,
+    0x190808082b080819,
+    0x190808082b081908,
+    0x190808082b190808,
+    0x190808082b191919,
+    0x190808082b192b08,
+    0x190808082b2b0819,
+    0x190808082b2b1908,
+    0x1908081908080808,
+    0x190808190808082b,
+    0x1908081908081919,
+    0x1908081908082b08,
+    0x1908081908190819,
+    0x1908081908191908,
+    0x190808190819192b,
+    0x1908081908192b19,
+    0x19080819082b0808,
+    0x19080819082b082b,
+    0x19080819082b1919,
+    0x1908081919080819,
+    0x1908081919081908,
+    0x190808191908192b,
+    0x1908081919082b19,
+    0x1908081919190808,
+    0x190808191919082b,
+    0x1908081919191919,
+    0x1908081919192b08,
+    0x19080819192b0819,
+    0x19080819192b1908,
+    0x190808192b080808,
+    0x190808192b08082b,
+    0x190808192b081919,
+    0x190808192b082b08,
+    0x190808192b190819,
+    0x190808192b191908,
+    0x190808192b2b0808,
+    0x1908082b08080819,
+    0x1908082b08081908,
+    0x1908082b08190808,
+    0x1908082b0819082b,
+    0x1908082b08191919,
+    0x1908082b08192b08,
+    0x1908082b082b1908,
+    0x1908082b19080808,
+    0x1908082b19081919,
+    0x1908082b19082b08,
+    0x1908082b19190819,
+    0x1908082b19191908,
+    0x1908082b192b0808,
+    0x1908082b2b080819,
+    0x1908082b2b081908,
+    0x1908190808080808,
+    0x190819080808082b,
+    0x1908190808081919,
+    0x1908190808082b08,
+    0x1908190808082b2b,
+    0x1908190808190819,
+    0x1908190808191908,
+    0x190819080819192b,
+    0x1908190808192b19,
+    0x19081908082b0808,
+    0x19081908082b082b,
+    0x19081908082b1919,
+    0x19081908082b2b08,
+    0x1908190819080819,
+    0x1908190819081908,
+    0x190819081908192b,
+    0x1908190819082b19,
+    0x1908190819190808,
+    0x190819081919082b,
+    0x1908190819191919,
+    0x1908190819192b08,
+    0x19081908192b0819,
+    0x19081908192b1908,
+    0x190819082b080808,
+    0x190819082b08082b,
+    0x190819082b081919,
+    0x190819082b082b08,
+    0x190819082b190819,
+    0x190819082b191908,
+    0x190819082b2b0808,
+    0x1908191908080819,
+    0x1908191908081908,
+    0x190819190808192b,
+    0x1908191908082b19,
+    0x1908191908190808,
+    0x190819190819082b,
+    0x1908191908191919,
+    0x1908191908192b08,
+    0x19081919082b0819,
+    0x19081919082b1908,
+    0x1908191919080808,
+    0x190819191908082b,
+    0x1908191919081919,
+    0x1908191919082b08,
+    0x1908191919190819,
+    0x1908191919191908,
+    0x19081919192b0808,
+    0x19081919192b2b2b,
+    0x190819192b080819,
+    0x190819192b081908,
+    0x190819192b190808,
+    0x1908192b08080808,
+    0x1908192b0808082b,
+    0x1908192b08081919,
+    0x1908192b08082b08,
+    0x1908192b08190819,
+    0x1908192b08191908,
+    0x1908192b082b0808,
+    0x1908192b19080819,
+    0x1908192b19081908,
+    0x1908192b19190808,
+    0x1908192b2b080808,
+    0x1908192b2b2b1919,
+    0x19082b0808080819,
+    0x19082b0808081908,
+    0x19082b0808082b19,
+    0x19082b0808190808,
+    0x19082b080819082b,
+    0x19082b0808191919,
+    0x19082b0808192b08,
+    0x19082b08082b0819,
+    0x19082b08082b1908,
+    0x19082b0819080808,
+    0x19082b081908082b,
+    0x19082b0819081919,
+    0x19082b0819082b08,
+    0x19082b0819190819,
+    0x19082b0819191908,
+    0x19082b08192b0808,
+    0x19082b082b081908,
+    0x19082b082b190808,
+    0x19082b1908080808,
+    0x19082b190808082b,
+    0x19082b1908081919,
+    0x19082b1908082b08,
+    0x19082b1908190819,
+    0x19082b1908191908,
+    0x19082b19082b0808,
+    0x19082b1919080819,
+    0x19082b1919081908,
+    0x19082b1919190808,
+    0x19082b192b080808,
+    0x19082b192b19192b,
+    0x19082b2b08080819,
+    0x19082b2b08081908,
+    0x19082b2b08190808,
+    0x19082b2b19080808,
+    0x1919080808080808,
+    0x191908080808082b,
+    0x1919080808081919,
+    0x1919080808082b08,
+    0x1919080808190819,
+    0x1919080808191908,
+    0x191908080819192b,
+    0x1919080808192b19,
+    0x19190808082b0808,
+    0x19190808082b082b,
+    0x19190808082b1919,
+    0x19190808082b2b08,
+    0x1919080819080819,
+    0x1919080819081908,
+    0x191908081908192b,
+    0x1919080819082b19,
+    0x1919080819190808,
+    0x191908081919082b,
+    0x1919080819191919,
+    0x1919080819192b08,
+    0x19190808192b0819,
+    0x19190808192b1908,
+    0x191908082b080808,
+    0x191908082b08082b,
+    0x191908082b081919,
+    0x191908082b082b08,
+    0x191908082b190819,
+    0x191908082b191908,
+    0x1919081908080819,
+    0x1919081908081908,
+    0x191908190808192b,
+    0x1919081908082b19,
+    0x1919081908190808,
+    0x191908190819082b,
+    0x1919081908191919,
+    0x1919081908192b08,
+    0x19190819082b0819,
+    0x19190819082b1908,
+    0x1919081919080808,
+    0x191908191908082b,
+    0x1919081919081919,
+    0x1919081919082b08,
+    0x1919081919190819,
+    0x1919081919191908,
+    0x19190819192b0808,
+    0x191908192b080819,
+    0x191908192b081908,
+    0x191908192b190808,
+    0x1919082b08080808,
+    0x1919082b08081919,
+    0x1919082b08082b08,
+    0x1919082b08190819,
+    0x1919082b08191908,
+    0x1919082b082b0808,
+    0x1919082b19080819,
+    0x1919082b19081908,
+    0x1919082b19190808,
+    0x1919082b192b2b19,
+    0x1919082b2b080808,
+    0x1919190808080819,
+    0x1919190808081908,
+    0x191919080808192b,
+    0x1919190808082b19,
+    0x1919190808190808,
+    0x191919080819082b,
+    0x1919190808191919,
+    0x1919190808192b08,
+    0x19191908082b0819,
+    0x19191908082b1908,
+    0x1919190819080808,
+    0x191919081908082b,
+    0x1919190819081919,
+    0x1919190819082b08,
+    0x1919190819190819,
+    0x1919190819191908,
+    0x19191908192b0808,
+    0x191919082b080819,
+    0x191919082b081908,
+    0x191919082b190808,
+    0x1919191908080808,
+    0x191919190808082b,
+    0x1919191908081919,
+    0x1919191908082b08,
+    0x1919191908190819,
+    0x1919191908191908,
+    0x19191919082b0808,
+    0x1919191919080819,
+    0x1919191919081908,
+    0x1919191919190808,
+    0x191919192b080808,
+    0x1919192b08080819,
+    0x1919192b08081908,
+    0x1919192b08190808,
+    0x1919192b082b192b,
+    0x1919192b19080808,
+    0x19192b0808080808,
+    0x19192b080808082b,
+    0x19192b0808081919,
+    0x19192b0808082b08,
+    0x19192b0808190819,
+    0x19192b0808191908,
+    0x19192b08082b0808,
+    0x19192b0819080819,
+    0x19192b0819081908,
+    0x19192b0819190808,
+    0x19192b0819192b2b,
+    0x19192b082b080808,
+    0x19192b1908080819,
+    0x19192b1908081908,
+    0x19192b1908190808,
+    0x19192b1919080808,
+    0x19192b2b08080808,
+    0x19192b2b08192b19,
+    0x19192b2b2b081919,
+    0x19192b2b2b2b2b08,
+    0x192b080808080819,
+    0x192b080808081908,
+    0x192b08080808192b,
+    0x192b080808190808,
+    0x192b08080819082b,
+    0x192b080808191919,
+    0x192b080808192b08,
+    0x192b0808082b0819,
+    0x192b0808082b1908,
+    0x192b080819080808,
+    0x192b080819081919,
+    0x192b080819082b08,
+    0x192b080819190819,
+    0x192b080819191908,
+    0x192b0808192b0808,
+    0x192b08082b081908,
+    0x192b08082b190808,
+    0x192b081908080808,
+    0x192b08190808082b,
+    0x192b081908081919,
+    0x192b081908082b08,
+    0x192b081908190819,
+    0x192b081908191908,
+    0x192b0819082b0808,
+    0x192b081919080819,
+    0x192b081919081908,
+    0x192b081919190808,
+    0x192b08192b080808,
+    0x192b08192b192b19,
+    0x192b082b08081908,
+    0x192b082b08190808,
+    0x192b082b19080808,
+    0x192b082b1919192b,
+    0x192b082b2b2b0819,
+    0x192b190808080808,
+    0x192b190808081919,
+    0x192b190808082b08,
+    0x192b190808190819,
+    0x192b190808191908,
+    0x192b1908082b0808,
+    0x192b190819080819,
+    0x192b190819081908,
+    0x192b190819190808,
+    0x192b19082b080808,
+    0x192b191908080819,
+    0x192b191908081908,
+    0x192b191908190808,
+    0x192b191919080808,
+    0x192b191919082b2b,
+    0x192b1919192b2b08,
+    0x192b19192b19082b,
+    0x192b192b08080808,
+    0x192b192b2b191908,
+    0x192b2b0808080819,
+    0x192b2b0808081908,
+    0x192b2b0808190808,
+    0x192b2b08192b1919,
+    0x192b2b082b192b08,
+    0x192b2b1908080808,
+    0x192b2b19082b2b2b,
+    0x192b2b2b1908082b,
+    0x192b2b2b2b2b0819,
+    0x2b08080808080808,
+    0x2b0808080808082b,
+    0x2b08080808081919,
+    0x2b08080808082b08,
+    0x2b08080808190819,
+    0x2b08080808191908,
+    0x2b08080808192b19,
+    0x2b080808082b0808,
+    0x2b080808082b1919,
+    0x2b08080819080819,
+    0x2b08080819081908,
+    0x2b08080819190808,
+    0x2b0808081919082b,
+    0x2b08080819191919,
+    0x2b08080819192b08,
+    0x2b080808192b0819,
+    0x2b0808082b080808,
+    0x2b0808082b081919,
+    0x2b0808082b190819,
+    0x2b0808082b191908,
+    0x2b08081908080819,
+    0x2b08081908081908,
+    0x2b08081908082b19,
+    0x2b08081908190808,
+    0x2b0808190819082b,
+    0x2b08081908191919,
+    0x2b08081908192b08,
+    0x2b080819082b0819,
+    0x2b080819082b1908,
+    0x2b08081919080808,
+    0x2b0808191908082b,
+    0x2b08081919081919,
+    0x2b08081919082b08,
+    0x2b08081919190819,
+    0x2b08081919191908,
+    0x2b0808192b080819,
+    0x2b0808192b081908,
+    0x2b0808192b190808,
+    0x2b0808192b2b2b19,
+    0x2b08082b08080808,
+    0x2b08082b08081919,
+    0x2b08082b08082b2b,
+    0x2b08082b08190819,
+    0x2b08082b08191908,
+    0x2b08082b19080819,
+    0x2b08082b19081908,
+    0x2b08082b19190808,
+    0x2b08190808080819,
+    0x2b08190808081908,
+    0x2b0819080808192b,
+    0x2b08190808082b19,
+    0x2b08190808190808,
+    0x2b0819080819082b,
+    0x2b08190808191919,
+    0x2b08190808192b08,
+    0x2b081908082b0819,
+    0x2b08190819080808,
+    0x2b0819081908082b,
+    0x2b08190819081919,
+    0x2b08190819082b08,
+    0x2b08190819190819,
+    0x2b08190819191908,
+    0x2b081908192b0808,
+    0x2b0819082b080819,
+    0x2b0819082b081908,
+    0x2b0819082b190808,
+    0x2b08191908080808,
+    0x2b0819190808082b,
+    0x2b08191908081919,
+    0x2b08191908082b08,
+    0x2b08191908190819,
+    0x2b08191908191908,
+    0x2b081919082b0808,
+    0x2b08191919080819,
+    0x2b08191919081908,
+    0x2b08191919190808,
+    0x2b0819192b080808,
+    0x2b0819192b082b2b,
+    0x2b08192b08080819,
+    0x2b08192b08081908,
+    0x2b08192b08190808,
+    0x2b08192b082b2b19,
+    0x2b08192b19080808,
+    0x2b082b0808080808,
+    0x2b082b0808081919,
+    0x2b082b0808190819,
+    0x2b082b0808191908,
+    0x2b082b0819080819,
+    0x2b082b0819081908,
+    0x2b082b0819190808,
+    0x2b082b082b2b082b,
+    0x2b082b1908080819,
+    0x2b082b1908081908,
+    0x2b082b1919080808,
+    0x2b082b19192b1919,
+    0x2b082b2b082b082b,
+    0x2b082b2b19192b08,
+    0x2b082b2b19192b2b,
+    0x2b082b2b2b08082b,
+    0x2b082b2b2b2b082b,
+    0x2b19080808080819,
+    0x2b19080808081908,
+    0x2b19080808082b19,
+    0x2b19080808190808,
+    0x2b1908080819082b,
+    0x2b19080808191919,
+    0x2b19080808192b08,
+    0x2b190808082b1908,
+    0x2b19080819080808,
+    0x2b1908081908082b,
+    0x2b19080819081919,
+    0x2b19080819082b08,
+    0x2b19080819190819,
+    0x2b19080819191908,
+    0x2b190808192b0808,
+    0x2b1908082b080819,
+    0x2b1908082b081908,
+    0x2b1908082b190808,
+    0x2b19081908080808,
+    0x2b19081908081919,
+    0x2b19081908190819,
+    0x2b19081908191908,
+    0x2b19081919080819,
+    0x2b19081919081908,
+    0x2b19081919190808,
+    0x2b19081919192b2b,
+    0x2b19082b08080819,
+    0x2b19082b08081908,
+    0x2b19082b08190808,
+    0x2b19082b19080808,
+    0x2b19082b2b2b192b,
+    0x2b19190808080808,
+    0x2b1919080808082b,
+    0x2b19190808081919,
+    0x2b19190808082b08,
+    0x2b19190808190819,
+    0x2b19190808191908,
+    0x2b191908082b0808,
+    0x2b19190819080819,
+    0x2b19190819081908,
+    0x2b19190819190808,
+    0x2b1919082b080808,
+    0x2b1919082b19192b,
+    0x2b19191908080819,
+    0x2b19191908081908,
+    0x2b19191908190808,
+    0x2b19191919080808,
+    0x2b1919192b192b08,
+    0x2b1919192b2b0819,
+    0x2b19192b08080808,
+    0x2b19192b1908192b,
+    0x2b19192b192b1908,
+    0x2b192b0808080819,
+    0x2b192b0808081908,
+    0x2b192b0808190808,
+    0x2b192b08082b192b,
+    0x2b192b0819080808,
+    0x2b192b082b2b2b19,
+    0x2b192b1908080808,
+    0x2b192b1919082b19,
+    0x2b192b191919082b,
+    0x2b192b2b2b190808,
+    0x2b2b080808080808,
+    0x2b2b080808081919,
+    0x2b2b080808082b2b,
+    0x2b2b080808191908,
+    0x2b2b0808082b082b,
+    0x2b2b0808082b2b2b,
+    0x2b2b080819080819,
+    0x2b2b080819081908,
+    0x2b2b080819190808,
+    0x2b2b08082b2b082b,
+    0x2b2b08082b2b2b2b,
+    0x2b2b081919080808,
+    0x2b2b0819192b1919,
+    0x2b2b082b0808082b,
+    0x2b2b082b08082b2b,
+    0x2b2b082b082b082b,
+    0x2b2b082b082b2b08,
+    0x2b2b082b082b2b2b,
+    0x2b2b082b2b08082b,
+    0x2b2b082b2b082b08,
+    0x2b2b082b2b082b2b,
+    0x2b2b082b2b2b2b08,
+    0x2b2b190808080819,
+    0x2b2b190808081908,
+    0x2b2b190808190808,
+    0x2b2b190819080808,
+    0x2b2b19082b082b19,
+    0x2b2b19082b2b1908,
+    0x2b2b191908080808,
+    0x2b2b191908192b19,
+    0x2b2b192b19190819,
+    0x2b2b2b0808082b2b,
+    0x2b2b2b08082b2b08,
+    0x2b2b2b082b2b082b,
+    0x2b2b2b1919191908,
+    0x2b2b2b192b08192b,
+    0x2b2b2b2b08082b08,
+    0x2b2b2b2b08082b2b,
+    0x2b2b2b2b082b0808,
+    0x2b2b2b2b082b082b,
+    0x2b2b2b2b082b2b08,
+    0x2b2b2b2b2b082b08,
+    0x2b2b2b2b2b2b2b2b,
+];
+
+pub(crate) const IQ3XXS_GRID: [u32; 256] = [
+    0x04040404, 0x04040414, 0x04040424, 0x04040c0c, 0x04040c1c, 0x04040c3e, 0x04041404, 0x04041414,
+    0x04041c0c, 0x04042414, 0x04043e1c, 0x04043e2c, 0x040c040c, 0x040c041c, 0x040c0c04, 0x040c0c14,
+    0x040c140c, 0x040c142c, 0x040c1c04, 0x040c1c14, 0x040c240c, 0x040c2c24, 0x040c3e04, 0x04140404,
+    0x04140414, 0x04140424, 0x04140c0c, 0x04141404, 0x04141414, 0x04141c0c, 0x04141c1c, 0x04141c3e,
+    0x04142c0c, 0x04142c3e, 0x04143e2c, 0x041c040c, 0x041c043e, 0x041c0c04, 0x041c0c14, 0x041c142c,
+    0x041c3e04, 0x04240c1c, 0x04241c3e, 0x04242424, 0x04242c3e, 0x04243e1c, 0x04243e2c, 0x042c040c,
+    0x042c043e, 0x042c1c14, 0x042c2c14, 0x04341c2c, 0x04343424, 0x043e0c04, 0x043e0c24, 0x043e0c34,
+    0x043e241c, 0x043e340c, 0x0c04040c, 0x0c04041c, 0x0c040c04, 0x0c040c14, 0x0c04140c, 0x0c04141c,
+    0x0c041c04, 0x0c041c14, 0x0c041c24, 0x0c04243e, 0x0c042c04, 0x0c0c0404, 0x0c0c0414, 0x0c0c0c0c,
+    0x0c0c1404, 0x0c0c1414, 0x0c14040c, 0x0c14041c, 0x0c140c04, 0x0c140c14, 0x0c14140c, 0x0c141c04,
+    0x0c143e14, 0x0c1c0404, 0x0c1c0414, 0x0c1c1404, 0x0c1c1c0c, 0x0c1c2434, 0x0c1c3434, 0x0c24040c,
+    0x0c24042c, 0x0c242c04, 0x0c2c1404, 0x0c2c1424, 0x0c2c2434, 0x0c2c3e0c, 0x0c34042c, 0x0c3e1414,
+    0x0c3e2404, 0x14040404, 0x14040414, 0x14040c0c, 0x14040c1c, 0x14041404, 0x14041414, 0x14041434,
+    0x14041c0c, 0x14042414, 0x140c040c, 0x140c041c, 0x140c042c, 0x140c0c04, 0x140c0c14, 0x140c140c,
+    0x140c1c04, 0x140c341c, 0x140c343e, 0x140c3e04, 0x14140404, 0x14140414, 0x14140c0c, 0x14140c3e,
+    0x14141404, 0x14141414, 0x14141c3e, 0x14142404, 0x14142c2c, 0x141c040c, 0x141c0c04, 0x141c0c24,
+    0x141c3e04, 0x141c3e24, 0x14241c2c, 0x14242c1c, 0x142c041c, 0x142c143e, 0x142c240c, 0x142c3e24,
+    0x143e040c, 0x143e041c, 0x143e0c34, 0x143e242c, 0x1c04040c, 0x1c040c04, 0x1c040c14, 0x1c04140c,
+    0x1c04141c, 0x1c042c04, 0x1c04342c, 0x1c043e14, 0x1c0c0404, 0x1c0c0414, 0x1c0c1404, 0x1c0c1c0c,
+    0x1c0c2424, 0x1c0c2434, 0x1c14040c, 0x1c14041c, 0x1c140c04, 0x1c14142c, 0x1c142c14, 0x1c143e14,
+    0x1c1c0c0c, 0x1c1c1c1c, 0x1c241c04, 0x1c24243e, 0x1c243e14, 0x1c2c0404, 0x1c2c0434, 0x1c2c1414,
+    0x1c2c2c2c, 0x1c340c24, 0x1c341c34, 0x1c34341c, 0x1c3e1c1c, 0x1c3e3404, 0x24040424, 0x24040c3e,
+    0x24041c2c, 0x24041c3e, 0x24042c1c, 0x24042c3e, 0x240c3e24, 0x24141404, 0x24141c3e, 0x24142404,
+    0x24143404, 0x24143434, 0x241c043e, 0x241c242c, 0x24240424, 0x24242c0c, 0x24243424, 0x242c142c,
+    0x242c241c, 0x242c3e04, 0x243e042c, 0x243e0c04, 0x243e0c14, 0x243e1c04, 0x2c040c14, 0x2c04240c,
+    0x2c043e04, 0x2c0c0404, 0x2c0c0434, 0x2c0c1434, 0x2c0c2c2c, 0x2c140c24, 0x2c141c14, 0x2c143e14,
+    0x2c1c0414, 0x2c1c2c1c, 0x2c240c04, 0x2c24141c, 0x2c24143e, 0x2c243e14, 0x2c2c0414, 0x2c2c1c0c,
+    0x2c342c04, 0x2c3e1424, 0x2c3e2414, 0x34041424, 0x34042424, 0x34042434, 0x34043424, 0x340c140c,
+    0x340c340c, 0x34140c3e, 0x34143424, 0x341c1c04, 0x341c1c34, 0x34242424, 0x342c042c, 0x342c2c14,
+    0x34341c1c, 0x343e041c, 0x343e140c, 0x3e04041c, 0x3e04042c, 0x3e04043e, 0x3e040c04, 0x3e041c14,
+    0x3e042c14, 0x3e0c1434, 0x3e0c2404, 0x3e140c14, 0x3e14242c, 0x3e142c14, 0x3e1c0404, 0x3e1c0c2c,
+    0x3e1c1c1c, 0x3e1c3404, 0x3e24140c, 0x3e24240c, 0x3e2c0404, 0x3e2c0414, 0x3e2c1424, 0x3e341c04,
+];
+
+pub(crate) const IQ3S_GRID: [u32; 512] = [
+    0x01010101, 0x01010103, 0x01010105, 0x0101010b, 0x0101010f, 0x01010301, 0x01010303, 0x01010305,
+    0x01010309, 0x0101030d, 0x01010501, 0x01010503, 0x0101050b, 0x01010707, 0x01010901, 0x01010905,
+    0x0101090b, 0x0101090f, 0x01010b03, 0x01010b07, 0x01010d01, 0x01010d05, 0x01010f03, 0x01010f09,
+    0x01010f0f, 0x01030101, 0x01030103, 0x01030105, 0x01030109, 0x01030301, 0x01030303, 0x0103030b,
+    0x01030501, 0x01030507, 0x0103050f, 0x01030703, 0x0103070b, 0x01030909, 0x01030d03, 0x01030d0b,
+    0x01030f05, 0x01050101, 0x01050103, 0x0105010b, 0x0105010f, 0x01050301, 0x01050307, 0x0105030d,
+    0x01050503, 0x0105050b, 0x01050701, 0x01050709, 0x01050905, 0x0105090b, 0x0105090f, 0x01050b03,
+    0x01050b07, 0x01050f01, 0x01050f07, 0x01070107, 0x01070303, 0x0107030b, 0x01070501, 0x01070505,
+    0x01070703, 0x01070707, 0x0107070d, 0x01070909, 0x01070b01, 0x01070b05, 0x01070d0f, 0x01070f03,
+    0x01070f0b, 0x01090101, 0x01090307, 0x0109030f, 0x01090503, 0x01090509, 0x01090705, 0x01090901,
+    0x01090907, 0x01090b03, 0x01090f01, 0x010b0105, 0x010b0109, 0x010b0501, 0x010b0505, 0x010b050d,
+    0x010b0707, 0x010b0903, 0x010b090b, 0x010b090f, 0x010b0d0d, 0x010b0f07, 0x010d010d, 0x010d0303,
+    0x010d0307, 0x010d0703, 0x010d0b05, 0x010d0f03, 0x010f0101, 0x010f0105, 0x010f0109, 0x010f0501,
+    0x010f0505, 0x010f050d, 0x010f0707, 0x010f0b01, 0x010f0b09, 0x03010101, 0x03010103, 0x03010105,
+    0x03010109, 0x03010301, 0x03010303, 0x03010307, 0x0301030b, 0x0301030f, 0x03010501, 0x03010505,
+    0x03010703, 0x03010709, 0x0301070d, 0x03010b09, 0x03010b0d, 0x03010d03, 0x03010f05, 0x03030101,
+    0x03030103, 0x03030107, 0x0303010d, 0x03030301, 0x03030309, 0x03030503, 0x03030701, 0x03030707,
+    0x03030903, 0x03030b01, 0x03030b05, 0x03030f01, 0x03030f0d, 0x03050101, 0x03050305, 0x0305030b,
+    0x0305030f, 0x03050501, 0x03050509, 0x03050705, 0x03050901, 0x03050907, 0x03050b0b, 0x03050d01,
+    0x03050f05, 0x03070103, 0x03070109, 0x0307010f, 0x03070301, 0x03070307, 0x03070503, 0x0307050f,
+    0x03070701, 0x03070709, 0x03070903, 0x03070d05, 0x03070f01, 0x03090107, 0x0309010b, 0x03090305,
+    0x03090309, 0x03090703, 0x03090707, 0x03090905, 0x0309090d, 0x03090b01, 0x03090b09, 0x030b0103,
+    0x030b0301, 0x030b0307, 0x030b0503, 0x030b0701, 0x030b0705, 0x030b0b03, 0x030d0501, 0x030d0509,
+    0x030d050f, 0x030d0909, 0x030d090d, 0x030f0103, 0x030f0107, 0x030f0301, 0x030f0305, 0x030f0503,
+    0x030f070b, 0x030f0903, 0x030f0d05, 0x030f0f01, 0x05010101, 0x05010103, 0x05010107, 0x0501010b,
+    0x0501010f, 0x05010301, 0x05010305, 0x05010309, 0x0501030d, 0x05010503, 0x05010507, 0x0501050f,
+    0x05010701, 0x05010705, 0x05010903, 0x05010907, 0x0501090b, 0x05010b01, 0x05010b05, 0x05010d0f,
+    0x05010f01, 0x05010f07, 0x05010f0b, 0x05030101, 0x05030105, 0x05030301, 0x05030307, 0x0503030f,
+    0x05030505, 0x0503050b, 0x05030703, 0x05030709, 0x05030905, 0x05030b03, 0x05050103, 0x05050109,
+    0x0505010f, 0x05050503, 0x05050507, 0x05050701, 0x0505070f, 0x05050903, 0x05050b07, 0x05050b0f,
+    0x05050f03, 0x05050f09, 0x05070101, 0x05070105, 0x0507010b, 0x05070303, 0x05070505, 0x05070509,
+    0x05070703, 0x05070707, 0x05070905, 0x05070b01, 0x05070d0d, 0x05090103, 0x0509010f, 0x05090501,
+    0x05090507, 0x05090705, 0x0509070b, 0x05090903, 0x05090f05, 0x05090f0b, 0x050b0109, 0x050b0303,
+    0x050b0505, 0x050b070f, 0x050b0901, 0x050b0b07, 0x050b0f01, 0x050d0101, 0x050d0105, 0x050d010f,
+    0x050d0503, 0x050d0b0b, 0x050d0d03, 0x050f010b, 0x050f0303, 0x050f050d, 0x050f0701, 0x050f0907,
+    0x050f0b01, 0x07010105, 0x07010303, 0x07010307, 0x0701030b, 0x0701030f, 0x07010505, 0x07010703,
+    0x07010707, 0x0701070b, 0x07010905, 0x07010909, 0x0701090f, 0x07010b03, 0x07010d07, 0x07010f03,
+    0x07030103, 0x07030107, 0x0703010b, 0x07030309, 0x07030503, 0x07030507, 0x07030901, 0x07030d01,
+    0x07030f05, 0x07030f0d, 0x07050101, 0x07050305, 0x07050501, 0x07050705, 0x07050709, 0x07050b01,
+    0x07070103, 0x07070301, 0x07070309, 0x07070503, 0x07070507, 0x0707050f, 0x07070701, 0x07070903,
+    0x07070907, 0x0707090f, 0x07070b0b, 0x07070f07, 0x07090107, 0x07090303, 0x0709030d, 0x07090505,
+    0x07090703, 0x07090b05, 0x07090d01, 0x07090d09, 0x070b0103, 0x070b0301, 0x070b0305, 0x070b050b,
+    0x070b0705, 0x070b0909, 0x070b0b0d, 0x070b0f07, 0x070d030d, 0x070d0903, 0x070f0103, 0x070f0107,
+    0x070f0501, 0x070f0505, 0x070f070b, 0x09010101, 0x09010109, 0x09010305, 0x09010501, 0x09010509,
+    0x0901050f, 0x09010705, 0x09010903, 0x09010b01, 0x09010f01, 0x09030105, 0x0903010f, 0x09030303,
+    0x09030307, 0x09030505, 0x09030701, 0x0903070b, 0x09030907, 0x09030b03, 0x09030b0b, 0x09050103,
+    0x09050107, 0x09050301, 0x0905030b, 0x09050503, 0x09050707, 0x09050901, 0x09050b0f, 0x09050d05,
+    0x09050f01, 0x09070109, 0x09070303, 0x09070307, 0x09070501, 0x09070505, 0x09070703, 0x0907070b,
+    0x09090101, 0x09090105, 0x09090509, 0x0909070f, 0x09090901, 0x09090f03, 0x090b010b, 0x090b010f,
+    0x090b0503, 0x090b0d05, 0x090d0307, 0x090d0709, 0x090d0d01, 0x090f0301, 0x090f030b, 0x090f0701,
+    0x090f0907, 0x090f0b03, 0x0b010105, 0x0b010301, 0x0b010309, 0x0b010505, 0x0b010901, 0x0b010909,
+    0x0b01090f, 0x0b010b05, 0x0b010d0d, 0x0b010f09, 0x0b030103, 0x0b030107, 0x0b03010b, 0x0b030305,
+    0x0b030503, 0x0b030705, 0x0b030f05, 0x0b050101, 0x0b050303, 0x0b050507, 0x0b050701, 0x0b05070d,
+    0x0b050b07, 0x0b070105, 0x0b07010f, 0x0b070301, 0x0b07050f, 0x0b070909, 0x0b070b03, 0x0b070d0b,
+    0x0b070f07, 0x0b090103, 0x0b090109, 0x0b090501, 0x0b090705, 0x0b09090d, 0x0b0b0305, 0x0b0b050d,
+    0x0b0b0b03, 0x0b0b0b07, 0x0b0d0905, 0x0b0f0105, 0x0b0f0109, 0x0b0f0505, 0x0d010303, 0x0d010307,
+    0x0d01030b, 0x0d010703, 0x0d010707, 0x0d010d01, 0x0d030101, 0x0d030501, 0x0d03050f, 0x0d030d09,
+    0x0d050305, 0x0d050709, 0x0d050905, 0x0d050b0b, 0x0d050d05, 0x0d050f01, 0x0d070101, 0x0d070309,
+    0x0d070503, 0x0d070901, 0x0d09050b, 0x0d090907, 0x0d090d05, 0x0d0b0101, 0x0d0b0107, 0x0d0b0709,
+    0x0d0b0d01, 0x0d0d010b, 0x0d0d0901, 0x0d0f0303, 0x0d0f0307, 0x0f010101, 0x0f010109, 0x0f01010f,
+    0x0f010501, 0x0f010505, 0x0f01070d, 0x0f010901, 0x0f010b09, 0x0f010d05, 0x0f030105, 0x0f030303,
+    0x0f030509, 0x0f030907, 0x0f03090b, 0x0f050103, 0x0f050109, 0x0f050301, 0x0f05030d, 0x0f050503,
+    0x0f050701, 0x0f050b03, 0x0f070105, 0x0f070705, 0x0f07070b, 0x0f070b07, 0x0f090103, 0x0f09010b,
+    0x0f090307, 0x0f090501, 0x0f090b01, 0x0f0b0505, 0x0f0b0905, 0x0f0d0105, 0x0f0d0703, 0x0f0f0101,
+];
+
+pub(crate) const IQ1S_DELTA: f32 = 0.125;
+pub(crate) const IQ1M_DELTA: f32 = 0.125;
+
+/// The IQ1 grids hold signed values in {-1, 0, 1}, stored as `i8`.
+pub(crate) const IQ1S_GRID: [u64; 2048] = [
+    0xffffffffffffffff,
+    0xffffffffffffff01,
+    0xffffffffffff0000,
+    0xffffffffffff01ff,
+    0xffffffffffff0101,
+    0xffffffffff00ff00,
+    0xffffffffff000000,
+    0xffffffffff01ffff,
+    0xffffffffff01ff01,
+    0xffffffffff0101ff,
+    0xffffffffff010101,
+    0xffffffff00ff0000,
+    0xffffffff0000ff00,
+    0xffffffff000000ff,
+    0xffffffff00000001,
+    0xffffffff00010000,
+    0xffffffff01ffffff,
+    0xffffffff01ffff01,
+    0xffffffff01ff01ff,
+    0xffffffff01ff0101,
+    0xffffffff01000000,
+    0xffffffff0101ffff,
+    0xffffffff0101ff01,
+    0xffffffff010101ff,
+    0xffffffff01010101,
+    0xffffff00ffff00ff,
+    0xffffff00ffff0000,
+    0xffffff00ff00ff00,
+    0xffffff00ff0000ff,
+    0xffffff00ff000001,
+    0xffffff00ff000100,
+    0xffffff00ff000101,
+    0xffffff00ff010000,
+    0xffffff0000ffff00,
+    0xffffff0000ff0001,
+    0xffffff0000ff0100,
+    0xffffff000000ff01,
+    0xffffff0000000000,
+    0xffffff0000000101,
+    0xffffff000001ff00,
+    0xffffff00000100ff,
+    0xffffff0000010001,
+    0xffffff00000101ff,
+    0xffffff0001ff0000,
+    0xffffff000100ff00,
+    0xffffff00010000ff,
+    0xffffff0001000001,
+    0xffffff0001010000,
+    0xffffff01ffffffff,
+    0xffffff01ffffff01,
+    0xffffff01ffff01ff,
+    0xffffff01ffff0101,
+    0xffffff01ff000000,
+    0xffffff01ff01ffff,
+    0xffffff01ff01ff01,
+    0xffffff01ff0101ff,
+    0xffffff01ff010101,
+    0xffffff0100ff0000,
+    0xffffff010000ff00,
+    0xffffff0100000100,
+    0xffffff01000100ff,
+    0xffffff0100010100,
+    0xffffff0101ffffff,
+    0xffffff0101ffff01,
+    0xffffff0101ff01ff,
+    0xffffff0101ff0101,
+    0xffffff010100ff00,
+    0xffffff0101000000,
+    0xffffff0101000100,
+    0xffffff010101ffff,
+    0xffffff010101ff01,
+    0xffffff01010101ff,
+    0xffffff0101010101,
+    0xffff00ffff00ff00,
+    0xffff00ffff0000ff,
+    0xffff00ffff000001,
+    0xffff00ffff010000,
+    0xffff00ff00ffff00,
+    0xffff00ff00ff0100,
+    0xffff00ff00000000,
+    0xffff00ff00000101,
+    0xffff00ff000100ff,
+    0xffff00ff00010000,
+    0xffff00ff0100ff00,
+    0xffff00ff01000100,
+    0xffff00ff01010000,
+    0xffff0000ffffff00,
+    0xffff0000ffff00ff,
+    0xffff0000ffff0000,
+    0xffff0000ffff0001,
+    0xffff0000ff000000,
+    0xffff0000ff0001ff,
+    0xffff0000ff000101,
+    0xffff0000ff010100,
+    0xffff000000ffffff,
+    0xffff000000ff0000,
+    0xffff000000ff0101,
+    0xffff00000000ffff,
+    0xffff00000000ff00,
+    0xffff0000000000ff,
+    0xffff000000000000,
+    0xffff000000000001,
+    0xffff000000000100,
+    0xffff00000001ffff,
+    0xffff00000001ff01,
+    0xffff000000010000,
+    0xffff0000000101ff,
+    0xffff000000010101,
+    0xffff000001ffff00,
+    0xffff00000100ff00,
+    0xffff000001000000,
+    0xffff0000010001ff,
+    0xffff000001000101,
+    0xffff00000101ff00,
+    0xffff0000010100ff,
+    0xffff000001010000,
+    0xffff000001010001,
+    0xffff000001010100,
+    0xffff0001ff0000ff,
+    0xffff0001ff000100,
+    0xffff000100ffff00,
+    0xffff000100ff00ff,
+    0xffff00010000ffff,
+    0xffff00010000ff01,
+    0xffff000100000000,
+    0xffff0001000001ff,
+    0xffff00010001ffff,
+    0xffff00010001ff00,
+    0xffff000100010001,
+    0xffff000100010100,
+    0xffff000101ff0000,
+    0xffff00010100ff00,
+    0xffff0001010000ff,
+    0xffff000101000100,
+    0xffff01ffffffffff,
+    0xffff01ffffffff01,
+    0xffff01ffffff01ff,
+    0xffff01ffffff0101,
+    0xffff01ffff000000,
+    0xffff01ffff01ffff,
+    0xffff01ffff01ff01,
+    0xffff01ffff0101ff,
+    0xffff01ffff010101,
+    0xffff01ff00ff0000,
+    0xffff01ff0000ff00,
+    0xffff01ff00000001,
+    0xffff01ff00010000,
+    0xffff01ff01ffffff,
+    0xffff01ff01ffff01,
+    0xffff01ff01ff01ff,
+    0xffff01ff01ff0101,
+    0xffff01ff01000000,
+    0xffff01ff0101ffff,
+    0xffff01ff0101ff01,
+    0xffff01ff010101ff,
+    0xffff01ff01010101,
+    0xffff0100ffff0000,
+    0xffff0100ff00ff00,
+    0xffff0100ff0000ff,
+    0xffff0100ff000100,
+    0xffff0100ff0100ff,
+    0xffff0100ff010000,
+    0xffff010000ffff00,
+    0xffff01000000ffff,
+    0xffff01000000ff00,
+    0xffff010000000000,
+    0xffff01000001ff00,
+    0xffff0100000100ff,
+    0xffff010000010100,
+    0xffff01000100ff00,
+    0xffff0100010000ff,
+    0xffff010001000001,
+    0xffff010001000100,
+    0xffff010001010000,
+    0xffff0101ffffffff,
+    0xffff0101ffffff01,
+    0xffff0101ffff01ff,
+    0xffff0101ffff0101,
+    0xffff0101ff000000,
+    0xffff0101ff01ffff,
+    0xffff0101ff01ff01,
+    0xffff0101ff0101ff,
+    0xffff0101ff010101,
+    0xffff010100ff0000,
+    0xffff01010000ff00,
+    0xffff010100000100,
+    0xffff01010001ff00,
+    0xffff010100010000,
+    0xffff010101ffffff,
+    0xffff010101ffff01,
+    0xffff010101ff0000,
+    0xffff010101ff01ff,
+    0xffff010101ff0101,
+    0xffff010101000000,
+    0xffff01010101ffff,
+    0xffff01010101ff01,
+    0xffff0101010101ff,
+    0xffff010101010101,
+    0xff00ffffff00ffff,
+    0xff00ffffff00ff00,
+    0xff00ffffff0000ff,
+    0xff00ffffff000100,
+    0xff00ffffff0100ff,
+    0xff00ffffff010000,
+    0xff00ffff00ffff00,
+    0xff00ffff00ff00ff,
+    0xff00ffff0000ffff,
+    0xff00ffff00000000,
+    0xff00ffff000001ff,
+    0xff00ffff0001ff00,
+    0xff00ffff000100ff,
+    0xff00ffff00010000,
+    0xff00ffff00010100,
+    0xff00ffff0100ff00,
+    0xff00ffff010000ff,
+    0xff00ffff01000001,
+    0xff00ffff0101ff00,
+    0xff00ffff01010000,
+    0xff00ff00ffffff00,
+    0xff00ff00ffff00ff,
+    0xff00ff00ffff0001,
+    0xff00ff00ffff0100,
+    0xff00ff00ff00ffff,
+    0xff00ff00ff00ff01,
+    0xff00ff00ff000000,
+    0xff00ff00ff0001ff,
+    0xff00ff00ff01ff00,
+    0xff00ff00ff0100ff,
+    0xff00ff00ff010100,
+    0xff00ff0000ff0000,
+    0xff00ff0000ff0101,
+    0xff00ff000000ffff,
+    0xff00ff000000ff00,
+    0xff00ff000000ff01,
+    0xff00ff00000000ff,
+    0xff00ff0000000000,
+    0xff00ff0000000001,
+    0xff00ff0000000100,
+    0xff00ff000001ffff,
+    0xff00ff0000010000,
+    0xff00ff0001ff00ff,
+    0xff00ff000100ff01,
+    0xff00ff0001000000,
+    0xff00ff000101ff00,
+    0xff00ff00010100ff,
+    0xff00ff01ff00ff00,
+    0xff00ff01ff0000ff,
+    0xff00ff01ff000001,
+    0xff00ff01ff010000,
+    0xff00ff0100ffffff,
+    0xff00ff0100ff0001,
+    0xff00ff0100ff0100,
+    0xff00ff010000ff01,
+    0xff00ff0100000000,
+    0xff00ff01000001ff,
+    0xff00ff0100000101,
+    0xff00ff01000100ff,
+    0xff00ff0100010001,
+    0xff00ff0101ff0000,
+    0xff00ff010100ff00,
+    0xff00ff01010000ff,
+    0xff00ff0101000001,
+    0xff00ff0101010000,
+    0xff0000ffffffff00,
+    0xff0000ffffff0001,
+    0xff0000ffffff0100,
+    0xff0000ffff0000ff,
+    0xff0000ffff000000,
+    0xff0000ffff0001ff,
+    0xff0000ffff000100,
+    0xff0000ffff01ff00,
+    0xff0000ffff010001,
+    0xff0000ff00ffff00,
+    0xff0000ff00ff0000,
+    0xff0000ff00ff0001,
+    0xff0000ff00ff01ff,
+    0xff0000ff00ff0101,
+    0xff0000ff0000ff00,
+    0xff0000ff000000ff,
+    0xff0000ff00000000,
+    0xff0000ff00000001,
+    0xff0000ff00000100,
+    0xff0000ff0001ff01,
+    0xff0000ff00010000,
+    0xff0000ff000101ff,
+    0xff0000ff01ff00ff,
+    0xff0000ff01ff0100,
+    0xff0000ff0100ffff,
+    0xff0000ff010000ff,
+    0xff0000ff01000000,
+    0xff0000ff010001ff,
+    0xff0000ff01000100,
+    0xff0000ff01000101,
+    0xff0000ff0101ff00,
+    0xff0000ff010100ff,
+    0xff0000ff01010000,
+    0xff0000ff01010100,
+    0xff000000ffffff01,
+    0xff000000ffff0000,
+    0xff000000ffff0101,
+    0xff000000ff00ff00,
+    0xff000000ff0000ff,
+    0xff000000ff000000,
+    0xff000000ff000001,
+    0xff000000ff000100,
+    0xff000000ff01ffff,
+    0xff000000ff01ff01,
+    0xff000000ff010000,
+    0xff000000ff0101ff,
+    0xff000000ff010101,
+    0xff00000000ffff00,
+    0xff00000000ff00ff,
+    0xff00000000ff0000,
+    0xff00000000ff0001,
+    0xff0000000000ff00,
+    0xff0000000000ff01,
+    0xff000000000000ff,
+    0xff00000000000000,
+    0xff00000000000001,
+    0xff00000000000100,
+    0xff00000000000101,
+    0xff0000000001ff00,
+    0xff000000000100ff,
+    0xff00000000010000,
+    0xff00000000010001,
+    0xff00000000010100,
+    0xff00000001ffffff,
+    0xff00000001ffff01,
+    0xff00000001ff00ff,
+    0xff00000001ff0000,
+    0xff00000001ff01ff,
+    0xff00000001ff0101,
+    0xff0000000100ffff,
+    0xff0000000100ff00,
+    0xff000000010000ff,
+    0xff00000001000000,
+    0xff00000001000001,
+    0xff00000001000100,
+    0xff00000001000101,
+    0xff0000000101ffff,
+    0xff0000000101ff01,
+    0xff00000001010000,
+    0xff000001ffffff00,
+    0xff000001ffff00ff,
+    0xff000001ffff0000,
+    0xff000001ffff0001,
+    0xff000001ff000000,
+    0xff000001ff000001,
+    0xff000001ff0001ff,
+    0xff000001ff000101,
+    0xff000001ff01ff00,
+    0xff000001ff010001,
+    0xff00000100ffffff,
+    0xff00000100ffff01,
+    0xff00000100ff00ff,
+    0xff00000100ff0000,
+    0xff00000100ff01ff,
+    0xff00000100ff0101,
+    0xff0000010000ff00,
+    0xff00000100000000,
+    0xff00000100000001,
+    0xff000001000001ff,
+    0xff00000100000100,
+    0xff0000010001ff00,
+    0xff000001000100ff,
+    0xff00000100010000,
+    0xff000001000101ff,
+    0xff00000100010100,
+    0xff00000100010101,
+    0xff00000101ff0001,
+    0xff00000101ff0101,
+    0xff0000010100ff01,
+    0xff00000101000000,
+    0xff000001010100ff,
+    0xff00000101010100,
+    0xff0001ffff00ff00,
+    0xff0001ffff000001,
+    0xff0001ffff010000,
+    0xff0001ff00ffff00,
+    0xff0001ff00ff00ff,
+    0xff0001ff00ff0001,
+    0xff0001ff00ff0100,
+    0xff0001ff0000ffff,
+    0xff0001ff00000000,
+    0xff0001ff000001ff,
+    0xff0001ff00000101,
+    0xff0001ff0001ffff,
+    0xff0001ff0001ff00,
+    0xff0001ff000100ff,
+    0xff0001ff00010001,
+    0xff0001ff00010100,
+    0xff0001ff01ff0000,
+    0xff0001ff0100ff00,
+    0xff0001ff010000ff,
+    0xff0001ff01010000,
+    0xff000100ff00ffff,
+    0xff000100ff00ff01,
+    0xff000100ff000000,
+    0xff000100ff000101,
+    0xff000100ff01ff00,
+    0xff000100ff010000,
+    0xff00010000ffff01,
+    0xff00010000ff00ff,
+    0xff00010000ff0000,
+    0xff00010000ff01ff,
+    0xff0001000000ff00,
+    0xff000100000000ff,
+    0xff00010000000000,
+    0xff00010000000001,
+    0xff00010000000100,
+    0xff00010000000101,
+    0xff0001000001ffff,
+    0xff00010000010000,
+    0xff00010000010101,
+    0xff00010001ff0100,
+    0xff0001000100ff00,
+    0xff0001000100ff01,
+    0xff00010001000000,
+    0xff000100010001ff,
+    0xff0001000101ff00,
+    0xff00010001010001,
+    0xff00010001010100,
+    0xff000101ffff0100,
+    0xff000101ff000001,
+    0xff000101ff0100ff,
+    0xff000101ff010001,
+    0xff00010100ff00ff,
+    0xff00010100ff0001,
+    0xff00010100ff0100,
+    0xff0001010000ffff,
+    0xff0001010000ff01,
+    0xff00010100000000,
+    0xff000101000001ff,
+    0xff0001010001ff00,
+    0xff00010100010001,
+    0xff00010100010100,
+    0xff00010101ff0000,
+    0xff0001010100ff00,
+    0xff00010101000001,
+    0xff00010101000101,
+    0xff01ffffffffffff,
+    0xff01ffffffffff01,
+    0xff01ffffffff01ff,
+    0xff01ffffffff0101,
+    0xff01ffffff000000,
+    0xff01ffffff01ffff,
+    0xff01ffffff01ff01,
+    0xff01ffffff010000,
+    0xff01ffffff0101ff,
+    0xff01ffffff010101,
+    0xff01ffff00ff0000,
+    0xff01ffff0000ff00,
+    0xff01ffff00000100,
+    0xff01ffff0001ff00,
+    0xff01ffff00010000,
+    0xff01ffff01ffffff,
+    0xff01ffff01ffff01,
+    0xff01ffff01ff01ff,
+    0xff01ffff01ff0101,
+    0xff01ffff01000000,
+    0xff01ffff0101ffff,
+    0xff01ffff0101ff01,
+    0xff01ffff01010000,
+    0xff01ffff010101ff,
+    0xff01ffff01010101,
+    0xff01ff00ffff0000,
+    0xff01ff00ff00ff00,
+    0xff01ff00ff0000ff,
+    0xff01ff00ff000100,
+    0xff01ff00ff010000,
+    0xff01ff0000ffff01,
+    0xff01ff0000ff00ff,
+    0xff01ff0000ff0100,
+    0xff01ff0000000000,
+    0xff01ff00000001ff,
+    0xff01ff0000000101,
+    0xff01ff000001ff00,
+    0xff01ff00000100ff,
+    0xff01ff0000010000,
+    0xff01ff0000010001,
+    0xff01ff0001ff0000,
+    0xff01ff000100ffff,
+    0xff01ff0001000001,
+    0xff01ff0001000100,
+    0xff01ff0001010000,
+    0xff01ff01ffffff00,
+    0xff01ff01ffff01ff,
+    0xff01ff01ffff0101,
+    0xff01ff01ff00ff00,
+    0xff01ff01ff000000,
+    0xff01ff01ff01ffff,
+    0xff01ff01ff01ff01,
+    0xff01ff01ff0101ff,
+    0xff01ff01ff010101,
+    0xff01ff0100ff0000,
+    0xff01ff010000ff00,
+    0xff01ff0100000001,
+    0xff01ff0100000100,
+    0xff01ff0100010000,
+    0xff01ff0101ffff00,
+    0xff01ff0101ff01ff,
+    0xff01ff0101ff0101,
+    0xff01ff010100ff00,
+    0xff01ff0101000000,
+    0xff01ff010101ffff,
+    0xff01ff010101ff01,
+    0xff01ff01010101ff,
+    0xff01ff0101010101,
+    0xff0100ffffff0000,
+    0xff0100ffff0000ff,
+    0xff0100ffff000001,
+    0xff0100ffff000100,
+    0xff0100ffff010000,
+    0xff0100ff00ff00ff,
+    0xff0100ff00ff0000,
+    0xff0100ff00ff0001,
+    0xff0100ff00ff0100,
+    0xff0100ff0000ff01,
+    0xff0100ff00000000,
+    0xff0100ff000001ff,
+    0xff0100ff00000101,
+    0xff0100ff00010001,
+    0xff0100ff01ff0000,
+    0xff0100ff0100ff00,
+    0xff0100ff010000ff,
+    0xff0100ff01000100,
+    0xff0100ff0101ff00,
+    0xff0100ff01010000,
+    0xff010000ffff0100,
+    0xff010000ff000000,
+    0xff010000ff01ff00,
+    0xff010000ff010100,
+    0xff01000000ffffff,
+    0xff01000000ff0000,
+    0xff01000000ff01ff,
+    0xff0100000000ff00,
+    0xff010000000000ff,
+    0xff01000000000000,
+    0xff01000000000100,
+    0xff0100000001ff01,
+    0xff01000000010000,
+    0xff010000000101ff,
+    0xff01000001ff0100,
+    0xff0100000100ffff,
+    0xff010000010000ff,
+    0xff01000001000000,
+    0xff010000010001ff,
+    0xff01000001000101,
+    0xff0100000101ff00,
+    0xff010000010100ff,
+    0xff01000001010001,
+    0xff01000001010100,
+    0xff010001ffff0000,
+    0xff010001ff00ffff,
+    0xff010001ff00ff01,
+    0xff010001ff000100,
+    0xff010001ff010000,
+    0xff01000100ffff00,
+    0xff01000100ff0100,
+    0xff01000100000000,
+    0xff0100010001ffff,
+    0xff0100010001ff00,
+    0xff01000100010100,
+    0xff01000101ff00ff,
+    0xff01000101ff0001,
+    0xff0100010100ffff,
+    0xff01000101000101,
+    0xff0101ffffffffff,
+    0xff0101ffffffff01,
+    0xff0101ffffff01ff,
+    0xff0101ffffff0101,
+    0xff0101ffff000000,
+    0xff0101ffff01ffff,
+    0xff0101ffff01ff01,
+    0xff0101ffff0101ff,
+    0xff0101ffff010101,
+    0xff0101ff00ff0000,
+    0xff0101ff0000ff00,
+    0xff0101ff000000ff,
+    0xff0101ff00010000,
+    0xff0101ff01ffffff,
+    0xff0101ff01ffff01,
+    0xff0101ff01ff01ff,
+    0xff0101ff01ff0101,
+    0xff0101ff0101ffff,
+    0xff0101ff0101ff01,
+    0xff0101ff010101ff,
+    0xff0101ff01010101,
+    0xff010100ffff0100,
+    0xff010100ff00ff00,
+    0xff010100ff0000ff,
+    0xff010100ff000100,
+    0xff010100ff010000,
+    0xff01010000ff0001,
+    0xff01010000ff0100,
+    0xff0101000000ff01,
+    0xff01010000000000,
+    0xff0101000001ff00,
+    0xff010100000100ff,
+    0xff01010000010001,
+    0xff01010000010100,
+    0xff01010001ff0000,
+    0xff0101000100ffff,
+    0xff01010001000001,
+    0xff01010001000100,
+    0xff010100010100ff,
+    0xff01010001010000,
+    0xff010101ffffffff,
+    0xff010101ffffff01,
+    0xff010101ffff01ff,
+    0xff010101ffff0101,
+    0xff010101ff01ffff,
+    0xff010101ff01ff01,
+    0xff010101ff0101ff,
+    0xff010101ff010101,
+    0xff01010100ff0000,
+    0xff0101010000ff00,
+    0xff01010100000001,
+    0xff01010100000100,
+    0xff01010100010000,
+    0xff01010101ffffff,
+    0xff01010101ffff01,
+    0xff01010101ff01ff,
+    0xff01010101ff0101,
+    0xff01010101000000,
+    0xff0101010101ffff,
+    0xff0101010101ff01,
+    0xff010101010101ff,
+    0xff01010101010101,
+    0x00ffffffffff0000,
+    0x00ffffffff00ff00,
+    0x00ffffffff000001,
+    0x00ffffffff010000,
+    0x00ffffff00ff0100,
+    0x00ffffff0000ff01,
+    0x00ffffff00000000,
+    0x00ffffff000001ff,
+    0x00ffffff00000101,
+    0x00ffffff0001ff00,
+    0x00ffffff000100ff,
+    0x00ffffff00010001,
+    0x00ffffff010000ff,
+    0x00ffffff01000100,
+    0x00ffffff0101ff00,
+    0x00ffffff01010001,
+    0x00ffff00ffffffff,
+    0x00ffff00ffffff00,
+    0x00ffff00ffff00ff,
+    0x00ffff00ffff0001,
+    0x00ffff00ffff0100,
+    0x00ffff00ff00ff01,
+    0x00ffff00ff000000,
+    0x00ffff00ff000001,
+    0x00ffff00ff0001ff,
+    0x00ffff00ff000101,
+    0x00ffff00ff01ff00,
+    0x00ffff00ff010001,
+    0x00ffff00ff010100,
+    0x00ffff0000ff0000,
+    0x00ffff0000ff01ff,
+    0x00ffff0000ff0101,
+    0x00ffff000000ff00,
+    0x00ffff00000000ff,
+    0x00ffff0000000000,
+    0x00ffff0000000001,
+    0x00ffff0000000100,
+    0x00ffff0000000101,
+    0x00ffff0000010000,
+    0x00ffff00000101ff,
+    0x00ffff0000010101,
+    0x00ffff0001ffff00,
+    0x00ffff0001ff00ff,
+    0x00ffff0001ff0001,
+    0x00ffff000100ffff,
+    0x00ffff000100ff01,
+    0x00ffff0001000000,
+    0x00ffff000101ffff,
+    0x00ffff000101ff00,
+    0x00ffff000101ff01,
+    0x00ffff01ffff0000,
+    0x00ffff01ff00ff00,
+    0x00ffff01ff0000ff,
+    0x00ffff01ff000001,
+    0x00ffff01ff010000,
+    0x00ffff0100ffff00,
+    0x00ffff010000ff01,
+    0x00ffff0100000000,
+    0x00ffff0100000101,
+    0x00ffff01000100ff,
+    0x00ffff0100010100,
+    0x00ffff0101ff0100,
+    0x00ffff01010000ff,
+    0x00ffff0101010000,
+    0x00ff00ffffffff00,
+    0x00ff00ffff000000,
+    0x00ff00ffff000100,
+    0x00ff00ffff010100,
+    0x00ff00ff00ff0000,
+    0x00ff00ff00ff01ff,
+    0x00ff00ff00ff0101,
+    0x00ff00ff0000ff00,
+    0x00ff00ff000000ff,
+    0x00ff00ff00000000,
+    0x00ff00ff00000001,
+    0x00ff00ff0001ff00,
+    0x00ff00ff0001ff01,
+    0x00ff00ff00010000,
+    0x00ff00ff000101ff,
+    0x00ff00ff00010101,
+    0x00ff00ff01ffff00,
+    0x00ff00ff01ff0001,
+    0x00ff00ff01ff0100,
+    0x00ff00ff0100ffff,
+    0x00ff00ff0100ff01,
+    0x00ff00ff01000000,
+    0x00ff00ff0101ffff,
+    0x00ff00ff0101ff00,
+    0x00ff00ff01010100,
+    0x00ff0000ffffff00,
+    0x00ff0000ffffff01,
+    0x00ff0000ffff0000,
+    0x00ff0000ffff0101,
+    0x00ff0000ff00ff00,
+    0x00ff0000ff0000ff,
+    0x00ff0000ff000000,
+    0x00ff0000ff000001,
+    0x00ff0000ff000100,
+    0x00ff0000ff01ffff,
+    0x00ff0000ff010000,
+    0x00ff0000ff010101,
+    0x00ff000000ffff00,
+    0x00ff000000ff00ff,
+    0x00ff000000ff0000,
+    0x00ff000000ff0001,
+    0x00ff000000ff0100,
+    0x00ff00000000ffff,
+    0x00ff00000000ff00,
+    0x00ff0000000000ff,
+    0x00ff000000000000,
+    0x00ff000000000001,
+    0x00ff0000000001ff,
+    0x00ff000000000100,
+    0x00ff00000001ff00,
+    0x00ff0000000100ff,
+    0x00ff000000010000,
+    0x00ff000000010001,
+    0x00ff000000010100,
+    0x00ff000001ffff01,
+    0x00ff000001ff00ff,
+    0x00ff000001ff0000,
+    0x00ff000001ff01ff,
+    0x00ff00000100ff00,
+    0x00ff0000010000ff,
+    0x00ff000001000000,
+    0x00ff000001000001,
+    0x00ff000001000100,
+    0x00ff000001000101,
+    0x00ff000001010000,
+    0x00ff0000010101ff,
+    0x00ff000001010101,
+    0x00ff0001ffffff00,
+    0x00ff0001ffff0000,
+    0x00ff0001ffff0100,
+    0x00ff0001ff0000ff,
+    0x00ff0001ff000000,
+    0x00ff0001ff0001ff,
+    0x00ff0001ff000101,
+    0x00ff0001ff01ff00,
+    0x00ff0001ff0100ff,
+    0x00ff0001ff010100,
+    0x00ff000100ffffff,
+    0x00ff000100ffff01,
+    0x00ff000100ff0000,
+    0x00ff000100ff01ff,
+    0x00ff00010000ffff,
+    0x00ff00010000ff00,
+    0x00ff00010000ff01,
+    0x00ff000100000000,
+    0x00ff000100000001,
+    0x00ff000100000100,
+    0x00ff00010001ff01,
+    0x00ff000100010000,
+    0x00ff0001000101ff,
+    0x00ff000101ffff00,
+    0x00ff000101ff0000,
+    0x00ff000101ff0101,
+    0x00ff0001010000ff,
+    0x00ff000101000000,
+    0x00ff00010101ff00,
+    0x00ff0001010100ff,
+    0x00ff000101010001,
+    0x00ff01ffffff0000,
+    0x00ff01ffff00ff00,
+    0x00ff01ffff000000,
+    0x00ff01ffff000101,
+    0x00ff01ffff010000,
+    0x00ff01ff00ffff01,
+    0x00ff01ff00ff0100,
+    0x00ff01ff0000ffff,
+    0x00ff01ff00000000,
+    0x00ff01ff000001ff,
+    0x00ff01ff0001ff00,
+    0x00ff01ff000100ff,
+    0x00ff01ff00010001,
+    0x00ff01ff00010100,
+    0x00ff01ff01ff0000,
+    0x00ff01ff0100ff00,
+    0x00ff01ff010000ff,
+    0x00ff01ff01000001,
+    0x00ff01ff01000100,
+    0x00ff01ff01010000,
+    0x00ff0100ffffff00,
+    0x00ff0100ffff0000,
+    0x00ff0100ffff0001,
+    0x00ff0100ffff0101,
+    0x00ff0100ff00ffff,
+    0x00ff0100ff0000ff,
+    0x00ff0100ff000000,
+    0x00ff0100ff0001ff,
+    0x00ff0100ff01ff00,
+    0x00ff0100ff0100ff,
+    0x00ff0100ff010001,
+    0x00ff010000ffffff,
+    0x00ff010000ff0000,
+    0x00ff010000ff0101,
+    0x00ff01000000ff00,
+    0x00ff01000000ff01,
+    0x00ff0100000000ff,
+    0x00ff010000000000,
+    0x00ff010000000001,
+    0x00ff010000000100,
+    0x00ff01000001ffff,
+    0x00ff01000001ff01,
+    0x00ff010000010000,
+    0x00ff010000010001,
+    0x00ff010000010101,
+    0x00ff010001ff0001,
+    0x00ff010001ff0100,
+    0x00ff01000100ff01,
+    0x00ff010001000000,
+    0x00ff010001000001,
+    0x00ff0100010001ff,
+    0x00ff01000101ff00,
+    0x00ff0100010100ff,
+    0x00ff010001010001,
+    0x00ff010001010100,
+    0x00ff0101ff000001,
+    0x00ff010100ff00ff,
+    0x00ff010100ff0001,
+    0x00ff010100ff0100,
+    0x00ff010100000000,
+    0x00ff0101000001ff,
+    0x00ff010100000101,
+    0x00ff0101000100ff,
+    0x00ff010100010100,
+    0x00ff0101010000ff,
+    0x00ff010101010000,
+    0x0000ffffffffff00,
+    0x0000ffffffff00ff,
+    0x0000ffffffff0000,
+    0x0000ffffffff0001,
+    0x0000ffffffff0100,
+    0x0000ffffff00ff01,
+    0x0000ffffff000000,
+    0x0000ffffff000101,
+    0x0000ffffff01ff00,
+    0x0000ffffff0100ff,
+    0x0000ffffff010100,
+    0x0000ffff00ffffff,
+    0x0000ffff00ff0000,
+    0x0000ffff00ff01ff,
+    0x0000ffff0000ff00,
+    0x0000ffff000000ff,
+    0x0000ffff00000000,
+    0x0000ffff00000001,
+    0x0000ffff00000100,
+    0x0000ffff00010000,
+    0x0000ffff000101ff,
+    0x0000ffff01ff0001,
+    0x0000ffff01ff0100,
+    0x0000ffff01000000,
+    0x0000ffff010001ff,
+    0x0000ffff0101ffff,
+    0x0000ffff0101ff00,
+    0x0000ffff01010001,
+    0x0000ffff01010100,
+    0x0000ff00ffff0000,
+    0x0000ff00ffff01ff,
+    0x0000ff00ffff0100,
+    0x0000ff00ffff0101,
+    0x0000ff00ff00ff00,
+    0x0000ff00ff0000ff,
+    0x0000ff00ff000000,
+    0x0000ff00ff000001,
+    0x0000ff00ff0001ff,
+    0x0000ff00ff000100,
+    0x0000ff00ff01ffff,
+    0x0000ff00ff010000,
+    0x0000ff00ff010001,
+    0x0000ff00ff0101ff,
+    0x0000ff00ff010101,
+    0x0000ff0000ffff00,
+    0x0000ff0000ff00ff,
+    0x0000ff0000ff0000,
+    0x0000ff0000ff0001,
+    0x0000ff0000ff0100,
+    0x0000ff000000ffff,
+    0x0000ff000000ff00,
+    0x0000ff000000ff01,
+    0x0000ff00000000ff,
+    0x0000ff0000000000,
+    0x0000ff0000000001,
+    0x0000ff00000001ff,
+    0x0000ff0000000100,
+    0x0000ff0000000101,
+    0x0000ff000001ff00,
+    0x0000ff00000100ff,
+    0x0000ff0000010000,
+    0x0000ff0000010001,
+    0x0000ff0000010100,
+    0x0000ff0001ffff01,
+    0x0000ff0001ff0000,
+    0x0000ff000100ff00,
+    0x0000ff00010000ff,
+    0x0000ff0001000000,
+    0x0000ff0001000001,
+    0x0000ff0001000100,
+    0x0000ff000101ffff,
+    0x0000ff0001010000,
+    0x0000ff0001010101,
+    0x0000ff01ffffff00,
+    0x0000ff01ffff0001,
+    0x0000ff01ff00ff01,
+    0x0000ff01ff000000,
+    0x0000ff01ff000101,
+    0x0000ff01ff01ff00,
+    0x0000ff01ff0100ff,
+    0x0000ff0100ffff01,
+    0x0000ff0100ff0000,
+    0x0000ff0100ff0101,
+    0x0000ff010000ff00,
+    0x0000ff01000000ff,
+    0x0000ff0100000000,
+    0x0000ff0100000001,
+    0x0000ff0100000100,
+    0x0000ff010001ff01,
+    0x0000ff0100010000,
+    0x0000ff0101ff0000,
+    0x0000ff010100ffff,
+    0x0000ff010100ff01,
+    0x0000ff0101000000,
+    0x0000ff0101000100,
+    0x0000ff0101000101,
+    0x0000ff01010100ff,
+    0x000000ffffff00ff,
+    0x000000ffffff0000,
+    0x000000ffff00ff00,
+    0x000000ffff0000ff,
+    0x000000ffff000000,
+    0x000000ffff000001,
+    0x000000ffff0001ff,
+    0x000000ffff000100,
+    0x000000ffff01ff00,
+    0x000000ffff010000,
+    0x000000ffff0101ff,
+    0x000000ffff010101,
+    0x000000ff00ffff00,
+    0x000000ff00ff00ff,
+    0x000000ff00ff0000,
+    0x000000ff00ff0001,
+    0x000000ff00ff0100,
+    0x000000ff00ff0101,
+    0x000000ff0000ffff,
+    0x000000ff0000ff00,
+    0x000000ff000000ff,
+    0x000000ff00000000,
+    0x000000ff00000001,
+    0x000000ff000001ff,
+    0x000000ff00000100,
+    0x000000ff00000101,
+    0x000000ff0001ff00,
+    0x000000ff0001ff01,
+    0x000000ff000100ff,
+    0x000000ff00010000,
+    0x000000ff00010001,
+    0x000000ff00010100,
+    0x000000ff01ffffff,
+    0x000000ff01ff01ff,
+    0x000000ff01ff0101,
+    0x000000ff0100ff00,
+    0x000000ff010000ff,
+    0x000000ff01000000,
+    0x000000ff01000001,
+    0x000000ff01000100,
+    0x000000ff0101ff00,
+    0x000000ff010100ff,
+    0x000000ff01010000,
+    0x000000ff01010101,
+    0x00000000ffffff00,
+    0x00000000ffffff01,
+    0x00000000ffff00ff,
+    0x00000000ffff0000,
+    0x00000000ffff0001,
+    0x00000000ffff0100,
+    0x00000000ff00ffff,
+    0x00000000ff00ff00,
+    0x00000000ff00ff01,
+    0x00000000ff0000ff,
+    0x00000000ff000000,
+    0x00000000ff000001,
+    0x00000000ff000100,
+    0x00000000ff000101,
+    0x00000000ff01ff00,
+    0x00000000ff0100ff,
+    0x00000000ff010000,
+    0x00000000ff010001,
+    0x00000000ff010100,
+    0x0000000000ffffff,
+    0x0000000000ffff00,
+    0x0000000000ffff01,
+    0x0000000000ff00ff,
+    0x0000000000ff0000,
+    0x0000000000ff0001,
+    0x0000000000ff01ff,
+    0x0000000000ff0100,
+    0x000000000000ffff,
+    0x000000000000ff00,
+    0x000000000000ff01,
+    0x00000000000000ff,
+    0x0000000000000000,
+    0x0000000000000001,
+    0x00000000000001ff,
+    0x0000000000000100,
+    0x0000000000000101,
+    0x000000000001ffff,
+    0x000000000001ff00,
+    0x00000000000100ff,
+    0x0000000000010000,
+    0x0000000000010001,
+    0x00000000000101ff,
+    0x0000000000010100,
+    0x0000000000010101,
+    0x0000000001ffff00,
+    0x0000000001ff00ff,
+    0x0000000001ff0000,
+    0x0000000001ff0100,
+    0x0000000001ff0101,
+    0x000000000100ffff,
+    0x000000000100ff00,
+    0x00000000010000ff,
+    0x0000000001000000,
+    0x0000000001000001,
+    0x00000000010001ff,
+    0x0000000001000100,
+    0x000000000101ff00,
+    0x00000000010100ff,
+    0x0000000001010000,
+    0x0000000001010001,
+    0x0000000001010100,
+    0x00000001ffffffff,
+    0x00000001ffffff00,
+    0x00000001ffffff01,
+    0x00000001ffff00ff,
+    0x00000001ffff0001,
+    0x00000001ffff01ff,
+    0x00000001ffff0100,
+    0x00000001ff00ff00,
+    0x00000001ff0000ff,
+    0x00000001ff000000,
+    0x00000001ff0001ff,
+    0x00000001ff000100,
+    0x00000001ff01ffff,
+    0x00000001ff01ff00,
+    0x00000001ff01ff01,
+    0x00000001ff0100ff,
+    0x00000001ff010000,
+    0x00000001ff010001,
+    0x00000001ff0101ff,
+    0x00000001ff010100,
+    0x0000000100ffff00,
+    0x0000000100ff0000,
+    0x0000000100ff0001,
+    0x0000000100ff01ff,
+    0x0000000100ff0100,
+    0x0000000100ff0101,
+    0x000000010000ffff,
+    0x000000010000ff00,
+    0x000000010000ff01,
+    0x00000001000000ff,
+    0x0000000100000000,
+    0x0000000100000001,
+    0x00000001000001ff,
+    0x0000000100000100,
+    0x0000000100000101,
+    0x000000010001ff00,
+    0x00000001000100ff,
+    0x0000000100010000,
+    0x0000000100010100,
+    0x0000000101ffff01,
+    0x0000000101ff0000,
+    0x0000000101ff0001,
+    0x0000000101ff01ff,
+    0x0000000101ff0100,
+    0x0000000101ff0101,
+    0x000000010100ff00,
+    0x0000000101000000,
+    0x0000000101000101,
+    0x000000010101ff01,
+    0x0000000101010000,
+    0x0000000101010001,
+    0x00000001010101ff,
+    0x0000000101010100,
+    0x000001ffffff00ff,
+    0x000001ffffff0000,
+    0x000001ffffff0001,
+    0x000001ffffff0100,
+    0x000001ffff00ffff,
+    0x000001ffff000000,
+    0x000001ffff0001ff,
+    0x000001ffff01ff00,
+    0x000001ffff010101,
+    0x000001ff00ff0000,
+    0x000001ff00ff01ff,
+    0x000001ff00ff0101,
+    0x000001ff0000ff00,
+    0x000001ff000000ff,
+    0x000001ff00000000,
+    0x000001ff00000001,
+    0x000001ff000001ff,
+    0x000001ff00000100,
+    0x000001ff0001ffff,
+    0x000001ff0001ff01,
+    0x000001ff000100ff,
+    0x000001ff00010000,
+    0x000001ff01ffff01,
+    0x000001ff01ff0100,
+    0x000001ff0100ffff,
+    0x000001ff0100ff01,
+    0x000001ff01000000,
+    0x000001ff010001ff,
+    0x000001ff0101ff00,
+    0x000001ff01010100,
+    0x00000100ffffff00,
+    0x00000100ffffff01,
+    0x00000100ffff0000,
+    0x00000100ffff0101,
+    0x00000100ff00ff00,
+    0x00000100ff0000ff,
+    0x00000100ff000000,
+    0x00000100ff000001,
+    0x00000100ff000100,
+    0x00000100ff010000,
+    0x0000010000ffff00,
+    0x0000010000ff00ff,
+    0x0000010000ff0000,
+    0x0000010000ff0001,
+    0x0000010000ff0100,
+    0x000001000000ffff,
+    0x000001000000ff00,
+    0x000001000000ff01,
+    0x00000100000000ff,
+    0x0000010000000000,
+    0x0000010000000001,
+    0x00000100000001ff,
+    0x0000010000000100,
+    0x0000010000000101,
+    0x000001000001ff00,
+    0x00000100000100ff,
+    0x0000010000010000,
+    0x0000010000010001,
+    0x0000010000010100,
+    0x0000010001ffff00,
+    0x0000010001ff0000,
+    0x0000010001ff0100,
+    0x000001000100ff00,
+    0x00000100010000ff,
+    0x0000010001000000,
+    0x0000010001000001,
+    0x00000100010001ff,
+    0x0000010001000100,
+    0x0000010001010000,
+    0x00000101ffff00ff,
+    0x00000101ffff01ff,
+    0x00000101ff000000,
+    0x00000101ff000101,
+    0x00000101ff01ffff,
+    0x00000101ff010000,
+    0x00000101ff010001,
+    0x00000101ff010100,
+    0x0000010100ff0000,
+    0x0000010100ff01ff,
+    0x0000010100ff0100,
+    0x000001010000ff00,
+    0x0000010100000000,
+    0x0000010100000001,
+    0x00000101000001ff,
+    0x0000010100000100,
+    0x000001010001ff01,
+    0x0000010100010000,
+    0x00000101000101ff,
+    0x0000010100010101,
+    0x0000010101ffff00,
+    0x0000010101ff0101,
+    0x000001010100ff01,
+    0x0000010101000000,
+    0x0000010101000001,
+    0x00000101010001ff,
+    0x0000010101000101,
+    0x000001010101ff00,
+    0x0001ffffffff0000,
+    0x0001ffffff0000ff,
+    0x0001ffffff000001,
+    0x0001ffffff000100,
+    0x0001ffffff010000,
+    0x0001ffff00ff00ff,
+    0x0001ffff0000ffff,
+    0x0001ffff00000000,
+    0x0001ffff00000001,
+    0x0001ffff000001ff,
+    0x0001ffff00000101,
+    0x0001ffff0001ff00,
+    0x0001ffff000100ff,
+    0x0001ffff00010001,
+    0x0001ffff00010100,
+    0x0001ffff01ffff00,
+    0x0001ffff01000001,
+    0x0001ffff01010000,
+    0x0001ff00ffffff00,
+    0x0001ff00ffff00ff,
+    0x0001ff00ffff0001,
+    0x0001ff00ffff0100,
+    0x0001ff00ff00ff01,
+    0x0001ff00ff000000,
+    0x0001ff00ff01ff00,
+    0x0001ff00ff01ff01,
+    0x0001ff00ff010001,
+    0x0001ff00ff010100,
+    0x0001ff0000ff0000,
+    0x0001ff0000ff0100,
+    0x0001ff000000ff00,
+    0x0001ff0000000000,
+    0x0001ff0000000001,
+    0x0001ff0000000100,
+    0x0001ff0000010000,
+    0x0001ff0000010001,
+    0x0001ff0000010101,
+    0x0001ff0001ff00ff,
+    0x0001ff0001ff0101,
+    0x0001ff000100ff01,
+    0x0001ff0001000000,
+    0x0001ff000101ff00,
+    0x0001ff0001010001,
+    0x0001ff0001010100,
+    0x0001ff01ff00ff00,
+    0x0001ff01ff000001,
+    0x0001ff01ff000100,
+    0x0001ff0100ffffff,
+    0x0001ff0100ffff00,
+    0x0001ff0100ff0001,
+    0x0001ff0100000000,
+    0x0001ff0100000001,
+    0x0001ff01000001ff,
+    0x0001ff010001ffff,
+    0x0001ff0101ff0000,
+    0x0001ff010100ff00,
+    0x0001ff0101000001,
+    0x0001ff0101010000,
+    0x000100ffff00ff00,
+    0x000100ffff00ff01,
+    0x000100ffff000000,
+    0x000100ffff000001,
+    0x000100ffff000101,
+    0x000100ffff01ff00,
+    0x000100ffff010001,
+    0x000100ffff010100,
+    0x000100ff00ffffff,
+    0x000100ff00ffff01,
+    0x000100ff00ff0000,
+    0x000100ff00ff01ff,
+    0x000100ff00ff0101,
+    0x000100ff0000ff00,
+    0x000100ff000000ff,
+    0x000100ff00000000,
+    0x000100ff00000001,
+    0x000100ff00000100,
+    0x000100ff00000101,
+    0x000100ff0001ffff,
+    0x000100ff0001ff01,
+    0x000100ff00010000,
+    0x000100ff01ff00ff,
+    0x000100ff01ff0000,
+    0x000100ff01ff0100,
+    0x000100ff0100ffff,
+    0x000100ff0100ff01,
+    0x000100ff010000ff,
+    0x000100ff01000000,
+    0x000100ff01000001,
+    0x000100ff010001ff,
+    0x000100ff01000101,
+    0x000100ff0101ff00,
+    0x000100ff010100ff,
+    0x000100ff01010100,
+    0x00010000ffff0000,
+    0x00010000ffff01ff,
+    0x00010000ffff0101,
+    0x00010000ff00ff00,
+    0x00010000ff000000,
+    0x00010000ff000001,
+    0x00010000ff000100,
+    0x0001000000ff00ff,
+    0x0001000000ff0000,
+    0x0001000000ff0001,
+    0x0001000000ff0100,
+    0x000100000000ffff,
+    0x000100000000ff00,
+    0x00010000000000ff,
+    0x0001000000000000,
+    0x0001000000000001,
+    0x0001000000000100,
+    0x000100000001ff00,
+    0x00010000000100ff,
+    0x0001000000010000,
+    0x0001000000010001,
+    0x0001000000010100,
+    0x0001000001ff0001,
+    0x0001000001ff0100,
+    0x0001000001ff0101,
+    0x000100000100ff00,
+    0x0001000001000000,
+    0x0001000001000001,
+    0x0001000001000100,
+    0x0001000001000101,
+    0x000100000101ff01,
+    0x0001000001010000,
+    0x0001000001010001,
+    0x00010000010101ff,
+    0x00010001ffffff01,
+    0x00010001ffff0100,
+    0x00010001ff000000,
+    0x00010001ff01ffff,
+    0x00010001ff010001,
+    0x00010001ff0101ff,
+    0x00010001ff010100,
+    0x0001000100ffffff,
+    0x0001000100ff0000,
+    0x0001000100ff01ff,
+    0x0001000100ff0101,
+    0x000100010000ff00,
+    0x00010001000000ff,
+    0x0001000100000000,
+    0x0001000100000001,
+    0x00010001000001ff,
+    0x0001000100000101,
+    0x000100010001ffff,
+    0x0001000100010000,
+    0x00010001000101ff,
+    0x0001000101ffffff,
+    0x0001000101ffff01,
+    0x0001000101ff0000,
+    0x0001000101ff0101,
+    0x00010001010000ff,
+    0x0001000101000001,
+    0x00010001010001ff,
+    0x0001000101000100,
+    0x000100010101ffff,
+    0x00010001010100ff,
+    0x0001000101010001,
+    0x0001000101010101,
+    0x000101ffff000001,
+    0x000101ffff000100,
+    0x000101ffff010000,
+    0x000101ff00ffff00,
+    0x000101ff0000ff01,
+    0x000101ff00000000,
+    0x000101ff00000101,
+    0x000101ff0001ff00,
+    0x000101ff00010100,
+    0x000101ff01ff0000,
+    0x000101ff0100ff00,
+    0x000101ff010001ff,
+    0x000101ff01010001,
+    0x00010100ffffff00,
+    0x00010100ffff00ff,
+    0x00010100ff00ffff,
+    0x00010100ff000000,
+    0x00010100ff01ff00,
+    0x00010100ff0100ff,
+    0x00010100ff010001,
+    0x00010100ff010100,
+    0x0001010000ffffff,
+    0x0001010000ffff00,
+    0x0001010000ff0000,
+    0x0001010000ff0001,
+    0x0001010000ff01ff,
+    0x000101000000ff00,
+    0x00010100000000ff,
+    0x0001010000000000,
+    0x0001010000000001,
+    0x0001010000000100,
+    0x000101000001ffff,
+    0x0001010000010000,
+    0x0001010000010101,
+    0x0001010001ffff01,
+    0x0001010001ff00ff,
+    0x0001010001ff0101,
+    0x0001010001000000,
+    0x000101000101ff00,
+    0x00010100010100ff,
+    0x0001010001010000,
+    0x0001010001010100,
+    0x00010101ff00ff00,
+    0x00010101ff000001,
+    0x00010101ff0001ff,
+    0x0001010100ffff00,
+    0x0001010100ff00ff,
+    0x0001010100ff0100,
+    0x000101010000ffff,
+    0x0001010100000000,
+    0x00010101000001ff,
+    0x0001010100000101,
+    0x00010101000100ff,
+    0x0001010100010000,
+    0x0001010100010100,
+    0x0001010101ff0001,
+    0x00010101010000ff,
+    0x00010101010001ff,
+    0x0001010101000101,
+    0x0001010101010001,
+    0x01ffffffffffffff,
+    0x01ffffffffffff01,
+    0x01ffffffffff01ff,
+    0x01ffffffffff0101,
+    0x01ffffffff01ffff,
+    0x01ffffffff01ff01,
+    0x01ffffffff0101ff,
+    0x01ffffffff010101,
+    0x01ffffff00ff0000,
+    0x01ffffff0000ffff,
+    0x01ffffff0000ff00,
+    0x01ffffff000000ff,
+    0x01ffffff00000001,
+    0x01ffffff00000100,
+    0x01ffffff00010000,
+    0x01ffffff01ffffff,
+    0x01ffffff01ffff01,
+    0x01ffffff01ff01ff,
+    0x01ffffff01ff0101,
+    0x01ffffff01000000,
+    0x01ffffff0101ffff,
+    0x01ffffff0101ff01,
+    0x01ffffff010101ff,
+    0x01ffffff01010101,
+    0x01ffff00ffff0000,
+    0x01ffff00ff00ff00,
+    0x01ffff00ff0000ff,
+    0x01ffff00ff000001,
+    0x01ffff00ff000100,
+    0x01ffff00ff010000,
+    0x01ffff0000ffff00,
+    0x01ffff0000ff00ff,
+    0x01ffff0000ff0100,
+    0x01ffff000000ffff,
+    0x01ffff000000ff01,
+    0x01ffff0000000000,
+    0x01ffff0000000001,
+    0x01ffff00000001ff,
+    0x01ffff0000000100,
+    0x01ffff00000100ff,
+    0x01ffff0000010001,
+    0x01ffff0000010100,
+    0x01ffff0001ff0000,
+    0x01ffff0001ff0100,
+    0x01ffff00010000ff,
+    0x01ffff0001000001,
+    0x01ffff0001000100,
+    0x01ffff0001010000,
+    0x01ffff01ffffffff,
+    0x01ffff01ffffff01,
+    0x01ffff01ffff01ff,
+    0x01ffff01ffff0101,
+    0x01ffff01ff000000,
+    0x01ffff01ff01ffff,
+    0x01ffff01ff01ff01,
+    0x01ffff01ff0101ff,
+    0x01ffff01ff010101,
+    0x01ffff010000ff00,
+    0x01ffff01000000ff,
+    0x01ffff0100000100,
+    0x01ffff0100010000,
+    0x01ffff0101ffffff,
+    0x01ffff0101ffff01,
+    0x01ffff0101ff01ff,
+    0x01ffff0101ff0101,
+    0x01ffff0101000000,
+    0x01ffff010101ffff,
+    0x01ffff010101ff01,
+    0x01ffff01010101ff,
+    0x01ffff0101010101,
+    0x01ff00ffff0000ff,
+    0x01ff00ffff000100,
+    0x01ff00ff00ffff00,
+    0x01ff00ff00ff00ff,
+    0x01ff00ff0000ff00,
+    0x01ff00ff00000000,
+    0x01ff00ff00000101,
+    0x01ff00ff0001ff00,
+    0x01ff00ff000100ff,
+    0x01ff00ff00010100,
+    0x01ff00ff010000ff,
+    0x01ff00ff01000100,
+    0x01ff0000ffffff00,
+    0x01ff0000ffff0100,
+    0x01ff0000ff00ff01,
+    0x01ff0000ff000000,
+    0x01ff0000ff000101,
+    0x01ff0000ff010001,
+    0x01ff0000ff010100,
+    0x01ff000000ffffff,
+    0x01ff000000ffff00,
+    0x01ff000000ff0000,
+    0x01ff000000ff01ff,
+    0x01ff00000000ff00,
+    0x01ff0000000000ff,
+    0x01ff000000000000,
+    0x01ff000000000001,
+    0x01ff000000000100,
+    0x01ff000000000101,
+    0x01ff000000010000,
+    0x01ff000000010001,
+    0x01ff0000000101ff,
+    0x01ff000000010101,
+    0x01ff000001ffff00,
+    0x01ff000001ff00ff,
+    0x01ff000001ff0001,
+    0x01ff000001ff0100,
+    0x01ff00000100ffff,
+    0x01ff00000100ff01,
+    0x01ff000001000000,
+    0x01ff0000010001ff,
+    0x01ff000001010001,
+    0x01ff0001ff00ff00,
+    0x01ff0001ff000001,
+    0x01ff0001ff000100,
+    0x01ff0001ff010000,
+    0x01ff000100ffff00,
+    0x01ff000100ff00ff,
+    0x01ff000100ff0100,
+    0x01ff000100ff0101,
+    0x01ff00010000ffff,
+    0x01ff000100000000,
+    0x01ff000100000100,
+    0x01ff000100000101,
+    0x01ff00010001ff00,
+    0x01ff000100010001,
+    0x01ff000100010101,
+    0x01ff000101ff0000,
+    0x01ff00010100ff00,
+    0x01ff000101000101,
+    0x01ff0001010100ff,
+    0x01ff01ffffffffff,
+    0x01ff01ffffffff01,
+    0x01ff01ffffff01ff,
+    0x01ff01ffffff0101,
+    0x01ff01ffff000000,
+    0x01ff01ffff01ffff,
+    0x01ff01ffff01ff01,
+    0x01ff01ffff0101ff,
+    0x01ff01ffff010101,
+    0x01ff01ff00ffff00,
+    0x01ff01ff00ff0000,
+    0x01ff01ff0000ff00,
+    0x01ff01ff000000ff,
+    0x01ff01ff00000100,
+    0x01ff01ff00010000,
+    0x01ff01ff00010100,
+    0x01ff01ff01ffffff,
+    0x01ff01ff01ffff01,
+    0x01ff01ff01ff01ff,
+    0x01ff01ff01ff0101,
+    0x01ff01ff01000000,
+    0x01ff01ff0101ffff,
+    0x01ff01ff0101ff01,
+    0x01ff01ff010101ff,
+    0x01ff01ff01010101,
+    0x01ff0100ffff0000,
+    0x01ff0100ffff0001,
+    0x01ff0100ff00ff00,
+    0x01ff0100ff0000ff,
+    0x01ff0100ff000001,
+    0x01ff0100ff010000,
+    0x01ff010000ffff00,
+    0x01ff010000ff00ff,
+    0x01ff010000ff0001,
+    0x01ff010000ff0100,
+    0x01ff01000000ffff,
+    0x01ff01000000ff01,
+    0x01ff010000000000,
+    0x01ff010000000101,
+    0x01ff01000001ff00,
+    0x01ff0100000100ff,
+    0x01ff010001ff0000,
+    0x01ff010001000001,
+    0x01ff010001000100,
+    0x01ff010001010000,
+    0x01ff0101ffffffff,
+    0x01ff0101ffffff01,
+    0x01ff0101ffff01ff,
+    0x01ff0101ffff0101,
+    0x01ff0101ff000000,
+    0x01ff0101ff01ffff,
+    0x01ff0101ff01ff01,
+    0x01ff0101ff0101ff,
+    0x01ff0101ff010101,
+    0x01ff010100ff0000,
+    0x01ff01010000ff00,
+    0x01ff0101000000ff,
+    0x01ff010100000001,
+    0x01ff010101ffffff,
+    0x01ff010101ffff01,
+    0x01ff010101ff01ff,
+    0x01ff010101ff0101,
+    0x01ff010101000000,
+    0x01ff01010101ffff,
+    0x01ff01010101ff01,
+    0x01ff0101010101ff,
+    0x01ff010101010101,
+    0x0100ffffffff0000,
+    0x0100ffffff00ff00,
+    0x0100ffffff000001,
+    0x0100ffffff0001ff,
+    0x0100ffffff000100,
+    0x0100ffffff010000,
+    0x0100ffff00ffff00,
+    0x0100ffff00ff0001,
+    0x0100ffff00ff0100,
+    0x0100ffff00000000,
+    0x0100ffff000001ff,
+    0x0100ffff00000101,
+    0x0100ffff00010100,
+    0x0100ffff00010101,
+    0x0100ffff01ff0000,
+    0x0100ffff0100ff00,
+    0x0100ffff010000ff,
+    0x0100ffff01000001,
+    0x0100ffff01000100,
+    0x0100ffff01010000,
+    0x0100ff00ffffff00,
+    0x0100ff00ffff00ff,
+    0x0100ff00ffff0001,
+    0x0100ff00ffff0100,
+    0x0100ff00ff00ffff,
+    0x0100ff00ff000000,
+    0x0100ff00ff0001ff,
+    0x0100ff00ff000101,
+    0x0100ff00ff01ff00,
+    0x0100ff00ff0100ff,
+    0x0100ff00ff010001,
+    0x0100ff00ff010100,
+    0x0100ff0000ffffff,
+    0x0100ff0000ff0000,
+    0x0100ff000000ffff,
+    0x0100ff000000ff00,
+    0x0100ff00000000ff,
+    0x0100ff0000000000,
+    0x0100ff0000000001,
+    0x0100ff0000000100,
+    0x0100ff000001ff01,
+    0x0100ff0000010000,
+    0x0100ff0001ff00ff,
+    0x0100ff0001ff0001,
+    0x0100ff000100ff01,
+    0x0100ff0001000000,
+    0x0100ff00010001ff,
+    0x0100ff000101ff00,
+    0x0100ff00010100ff,
+    0x0100ff0001010001,
+    0x0100ff0001010100,
+    0x0100ff01ffff0000,
+    0x0100ff01ff00ff00,
+    0x0100ff01ff0000ff,
+    0x0100ff01ff000100,
+    0x0100ff01ff010000,
+    0x0100ff0100ff00ff,
+    0x0100ff0100ff0001,
+    0x0100ff0100ff0100,
+    0x0100ff010000ffff,
+    0x0100ff010000ff01,
+    0x0100ff0100000000,
+    0x0100ff01000001ff,
+    0x0100ff0100010001,
+    0x0100ff0100010100,
+    0x0100ff0101ff0000,
+    0x0100ff01010000ff,
+    0x0100ff0101000001,
+    0x0100ff0101010100,
+    0x010000ffffffff00,
+    0x010000ffffff00ff,
+    0x010000ffffff0001,
+    0x010000ffff00ffff,
+    0x010000ffff000000,
+    0x010000ffff0001ff,
+    0x010000ffff010001,
+    0x010000ff00ffffff,
+    0x010000ff00ff0101,
+    0x010000ff0000ff00,
+    0x010000ff000000ff,
+    0x010000ff00000000,
+    0x010000ff00000001,
+    0x010000ff000001ff,
+    0x010000ff00000100,
+    0x010000ff0001ffff,
+    0x010000ff0001ff00,
+    0x010000ff0001ff01,
+    0x010000ff00010000,
+    0x010000ff01ff00ff,
+    0x010000ff01ff0001,
+    0x010000ff0100ff01,
+    0x010000ff010000ff,
+    0x010000ff01000000,
+    0x010000ff010001ff,
+    0x010000ff0101ff00,
+    0x010000ff01010100,
+    0x01000000ffffffff,
+    0x01000000ffff0000,
+    0x01000000ffff01ff,
+    0x01000000ffff0101,
+    0x01000000ff00ffff,
+    0x01000000ff00ff00,
+    0x01000000ff0000ff,
+    0x01000000ff000000,
+    0x01000000ff000001,
+    0x01000000ff000100,
+    0x01000000ff01ff00,
+    0x01000000ff010000,
+    0x01000000ff010100,
+    0x01000000ff010101,
+    0x0100000000ffff00,
+    0x0100000000ff00ff,
+    0x0100000000ff0000,
+    0x0100000000ff0001,
+    0x0100000000ff0100,
+    0x010000000000ffff,
+    0x010000000000ff00,
+    0x010000000000ff01,
+    0x01000000000000ff,
+    0x0100000000000000,
+    0x0100000000000001,
+    0x01000000000001ff,
+    0x0100000000000100,
+    0x0100000000000101,
+    0x010000000001ff00,
+    0x01000000000100ff,
+    0x0100000000010000,
+    0x0100000000010001,
+    0x0100000000010100,
+    0x0100000001ffff00,
+    0x0100000001ff0000,
+    0x0100000001ff01ff,
+    0x010000000100ff00,
+    0x010000000100ff01,
+    0x01000000010000ff,
+    0x0100000001000000,
+    0x0100000001000001,
+    0x0100000001000100,
+    0x0100000001000101,
+    0x010000000101ffff,
+    0x010000000101ff01,
+    0x0100000001010000,
+    0x01000000010101ff,
+    0x0100000001010101,
+    0x01000001ffffff00,
+    0x01000001ffff00ff,
+    0x01000001ff00ffff,
+    0x01000001ff000000,
+    0x01000001ff000100,
+    0x01000001ff01ffff,
+    0x01000001ff010001,
+    0x01000001ff010100,
+    0x0100000100ff0000,
+    0x0100000100ff01ff,
+    0x0100000100ff0100,
+    0x010000010000ff00,
+    0x010000010000ff01,
+    0x0100000100000000,
+    0x0100000100000001,
+    0x0100000100000100,
+    0x0100000100010000,
+    0x01000001000101ff,
+    0x0100000101ffff01,
+    0x0100000101ff00ff,
+    0x0100000101ff0100,
+    0x0100000101ff0101,
+    0x010000010100ff01,
+    0x01000001010000ff,
+    0x0100000101000000,
+    0x01000001010100ff,
+    0x0100000101010001,
+    0x0100000101010100,
+    0x010001ffffff0000,
+    0x010001ffff000001,
+    0x010001ffff000100,
+    0x010001ffff010000,
+    0x010001ff00ffff00,
+    0x010001ff00ff0001,
+    0x010001ff0000ffff,
+    0x010001ff0000ff01,
+    0x010001ff00000000,
+    0x010001ff00000001,
+    0x010001ff00000101,
+    0x010001ff000100ff,
+    0x010001ff00010000,
+    0x010001ff01ff0000,
+    0x010001ff0100ff00,
+    0x010001ff01000001,
+    0x010001ff01000100,
+    0x010001ff01010000,
+    0x01000100ffff00ff,
+    0x01000100ffff0001,
+    0x01000100ffff0100,
+    0x01000100ff00ffff,
+    0x01000100ff00ff01,
+    0x01000100ff000000,
+    0x01000100ff0001ff,
+    0x01000100ff000101,
+    0x01000100ff01ffff,
+    0x01000100ff01ff00,
+    0x01000100ff0100ff,
+    0x01000100ff010001,
+    0x0100010000ffffff,
+    0x0100010000ffff01,
+    0x0100010000ff0000,
+    0x0100010000ff01ff,
+    0x0100010000ff0101,
+    0x010001000000ff00,
+    0x01000100000000ff,
+    0x0100010000000000,
+    0x0100010000000001,
+    0x0100010000000100,
+    0x010001000001ff01,
+    0x0100010000010000,
+    0x0100010000010001,
+    0x0100010000010101,
+    0x0100010001ffff00,
+    0x0100010001ff00ff,
+    0x010001000100ffff,
+    0x010001000100ff01,
+    0x0100010001000000,
+    0x0100010001000101,
+    0x010001000101ff00,
+    0x0100010001010001,
+    0x01000101ffff0000,
+    0x01000101ff000000,
+    0x01000101ff010000,
+    0x0100010100ff00ff,
+    0x0100010100ff0001,
+    0x0100010100ff0100,
+    0x010001010000ffff,
+    0x0100010100000000,
+    0x01000101000001ff,
+    0x010001010001ff00,
+    0x0100010101ff0000,
+    0x010001010100ff00,
+    0x01000101010000ff,
+    0x0100010101000000,
+    0x0100010101000001,
+    0x0101ffffffffffff,
+    0x0101ffffffffff01,
+    0x0101ffffffff01ff,
+    0x0101ffffffff0101,
+    0x0101ffffff000000,
+    0x0101ffffff01ffff,
+    0x0101ffffff01ff01,
+    0x0101ffffff0101ff,
+    0x0101ffffff010101,
+    0x0101ffff00ff0000,
+    0x0101ffff0000ff00,
+    0x0101ffff000000ff,
+    0x0101ffff00000001,
+    0x0101ffff00000100,
+    0x0101ffff01ffffff,
+    0x0101ffff01ffff01,
+    0x0101ffff01ff01ff,
+    0x0101ffff01ff0101,
+    0x0101ffff01000000,
+    0x0101ffff0101ffff,
+    0x0101ffff0101ff01,
+    0x0101ffff010101ff,
+    0x0101ffff01010101,
+    0x0101ff00ffff0000,
+    0x0101ff00ffff0100,
+    0x0101ff00ff00ff00,
+    0x0101ff00ff0000ff,
+    0x0101ff00ff000001,
+    0x0101ff00ff000100,
+    0x0101ff00ff000101,
+    0x0101ff0000ff0001,
+    0x0101ff0000ff0100,
+    0x0101ff000000ff00,
+    0x0101ff0000000000,
+    0x0101ff00000001ff,
+    0x0101ff0000000101,
+    0x0101ff000001ff00,
+    0x0101ff00000100ff,
+    0x0101ff0001ff0000,
+    0x0101ff000100ffff,
+    0x0101ff000100ff01,
+    0x0101ff0001000001,
+    0x0101ff0001000100,
+    0x0101ff01ffffff01,
+    0x0101ff01ffff01ff,
+    0x0101ff01ffff0101,
+    0x0101ff01ff00ffff,
+    0x0101ff01ff000100,
+    0x0101ff01ff01ff01,
+    0x0101ff01ff0101ff,
+    0x0101ff01ff010101,
+    0x0101ff0100ff0000,
+    0x0101ff010000ff00,
+    0x0101ff0100000001,
+    0x0101ff0100000100,
+    0x0101ff0100010000,
+    0x0101ff0101ffffff,
+    0x0101ff0101ffff01,
+    0x0101ff0101ff01ff,
+    0x0101ff0101ff0101,
+    0x0101ff0101000000,
+    0x0101ff010101ffff,
+    0x0101ff010101ff01,
+    0x0101ff01010101ff,
+    0x0101ff0101010101,
+    0x010100ffff000100,
+    0x010100ffff010000,
+    0x010100ff00ffff00,
+    0x010100ff00ff00ff,
+    0x010100ff0000ffff,
+    0x010100ff000000ff,
+    0x010100ff00000000,
+    0x010100ff000001ff,
+    0x010100ff00000101,
+    0x010100ff0001ff00,
+    0x010100ff00010000,
+    0x010100ff00010001,
+    0x010100ff000101ff,
+    0x010100ff00010100,
+    0x010100ff01ff0000,
+    0x01010000ffff0001,
+    0x01010000ffff0100,
+    0x01010000ff00ffff,
+    0x01010000ff00ff01,
+    0x01010000ff000000,
+    0x01010000ff0001ff,
+    0x01010000ff010001,
+    0x01010000ff010100,
+    0x0101000000ffff01,
+    0x0101000000ff0000,
+    0x010100000000ff00,
+    0x01010000000000ff,
+    0x0101000000000000,
+    0x0101000000000001,
+    0x0101000000000100,
+    0x0101000000010000,
+    0x0101000000010101,
+    0x0101000001ffff00,
+    0x0101000001ff00ff,
+    0x0101000001ff0000,
+    0x0101000001ff0001,
+    0x0101000001ff0100,
+    0x010100000100ff01,
+    0x0101000001000000,
+    0x01010000010001ff,
+    0x01010001ffff0000,
+    0x01010001ff00ff00,
+    0x01010001ff000001,
+    0x01010001ff000101,
+    0x01010001ff01ff00,
+    0x01010001ff010000,
+    0x0101000100ff00ff,
+    0x0101000100ff0001,
+    0x0101000100ff0101,
+    0x010100010000ff01,
+    0x0101000100000000,
+    0x0101000100000001,
+    0x01010001000001ff,
+    0x010100010001ffff,
+    0x010100010001ff01,
+    0x0101000101ff0001,
+    0x010100010100ffff,
+    0x0101000101000000,
+    0x0101000101000001,
+    0x0101000101000100,
+    0x010100010101ff00,
+    0x01010001010100ff,
+    0x0101000101010001,
+    0x010101ffffffffff,
+    0x010101ffffffff01,
+    0x010101ffffff01ff,
+    0x010101ffffff0101,
+    0x010101ffff01ffff,
+    0x010101ffff01ff01,
+    0x010101ffff0101ff,
+    0x010101ffff010101,
+    0x010101ff0000ff00,
+    0x010101ff000000ff,
+    0x010101ff00000001,
+    0x010101ff00000100,
+    0x010101ff01ffffff,
+    0x010101ff01ffff01,
+    0x010101ff01ff01ff,
+    0x010101ff01ff0101,
+    0x010101ff01000000,
+    0x010101ff0101ffff,
+    0x010101ff0101ff01,
+    0x010101ff010101ff,
+    0x010101ff01010101,
+    0x01010100ffff0000,
+    0x01010100ff0000ff,
+    0x01010100ff000100,
+    0x01010100ff01ff00,
+    0x01010100ff010000,
+    0x0101010000ffff00,
+    0x010101000000ffff,
+    0x0101010000000000,
+    0x0101010000000101,
+    0x010101000001ff00,
+    0x0101010000010001,
+    0x0101010000010100,
+    0x010101000100ffff,
+    0x0101010001000001,
+    0x01010101ffffffff,
+    0x01010101ffffff01,
+    0x01010101ffff01ff,
+    0x01010101ffff0101,
+    0x01010101ff01ffff,
+    0x01010101ff01ff01,
+    0x01010101ff0101ff,
+    0x01010101ff010101,
+    0x010101010000ff00,
+    0x01010101000000ff,
+    0x0101010100000001,
+    0x0101010101ffffff,
+    0x0101010101ffff01,
+    0x0101010101ff01ff,
+    0x0101010101ff0101,
+    0x0101010101000000,
+    0x010101010101ffff,
+    0x010101010101ff01,
+    0x01010101010101ff,
+    0x0101010101010101,
+];
@@ -0,0 +1,1797 @@
+//! Importance-quantization (IQ) and ternary (TQ) block formats.
+//!
+//! The IQ formats store groups of 4 or 8 values as indexes into a fixed grid of allowed
+//! combinations, see `iq_grids`. These formats are designed to be used with an importance matrix
+//! (imatrix) so that the quantization error is minimized where it matters the most.
+//!
+//! All the vec-dot kernels have an avx2 version, only IQ4_NL and IQ4_XS also have neon and
+//! simd128 versions, the other types use the generic implementation on these targets.
+// https://github.com/ggml-org/llama.cpp/blob/master/ggml/src/ggml-quants.c
+use super::iq_grids::*;
+use super::k_quants::{BlockQ8K, BlockQ8_0, GgmlType, QK8_0, QK_K};
+use super::utils::{group_for_dequantization, group_for_quantization, nearest_int};
+use super::GgmlDType;
+use half::f16;
+use std::sync::OnceLock;
+
+pub const QK4_NL: usize = 32;
+
+const GROUP_MAX_EPS: f32 = 1e-15;
+
+#[derive(Debug, Clone, PartialEq)]
+#[repr(C)]
+pub struct BlockIQ2XXS {
+    pub(crate) d: f16,
+    pub(crate) qs: [u16; QK_K / 8],
+}
+const _: () = assert!(2 + QK_K / 8 * 2 == std::mem::size_of::<BlockIQ2XXS>());
+
+#[derive(Debug, Clone, PartialEq)]
+#[repr(C)]
+pub struct BlockIQ2XS {
+    pub(crate) d: f16,
+    pub(crate) qs: [u16; QK_K / 8],
+    pub(crate) scales: [u8; QK_K / 32],
+}
+const _: () = assert!(2 + QK_K / 8 * 2 + QK_K / 32 == std::mem::size_of::<BlockIQ2XS>());
+
+#[derive(Debug, Clone, PartialEq)]
+#[repr(C)]
+pub struct BlockIQ2S {
+    pub(crate) d: f16,
+    pub(crate) qs: [u8; QK_K / 4],
+    pub(crate) qh: [u8; QK_K / 32],
+    pub(crate) scales: [u8; QK_K / 32],
+}
+const _: () = assert!(2 + QK_K / 4 + QK_K / 16 == std::mem::size_of::<BlockIQ2S>());
+
+#[derive(Debug, Clone, PartialEq)]
+#[repr(C)]
+pub struct BlockIQ3XXS {
+    pub(crate) d: f16,
+    pub(crate) qs: [u8; 3 * QK_K / 8],
+}
+const _: () = assert!(2 + 3 * QK_K / 8 == std::mem::size_of::<BlockIQ3XXS>());
+
+#[derive(Debug, Clone, PartialEq)]
+#[repr(C)]
+pub struct BlockIQ3S {
+    pub(crate) d: f16,
+    pub(crate) qs: [u8; QK_K / 4],
+    pub(crate) qh: [u8; QK_K / 32],
+    pub(crate) signs: [u8; QK_K / 8],
+    pub(crate) scales: [u8; QK_K / 64],
+}
+const _: () = assert!(2 + 13 * QK_K / 32 + QK_K / 64 == std::mem::size_of::<BlockIQ3S>());
+
+#[derive(Debug, Clone, PartialEq)]
+#[repr(C)]
+pub struct BlockIQ1S {
+    pub(crate) d: f16,
+    pub(crate) qs: [u8; QK_K / 8],
+    pub(crate) qh: [u16; QK_K / 32],
+}
+const _: () = assert!(2 + QK_K / 8 + QK_K / 16 == std::mem::size_of::<BlockIQ1S>());
+
+/// The super-block scale is stored in the top 4 bits of the 4 `u16` values that make `scales`.
+#[derive(Debug, Clone, PartialEq)]
+#[repr(C)]
+pub struct BlockIQ1M {
+    pub(crate) qs: [u8; QK_K / 8],
+    pub(crate) qh: [u8; QK_K / 16],
+    pub(crate) scales: [u8; QK_K / 32],
+}
+const _: () = assert!(QK_K / 8 + QK_K / 16 + QK_K / 32 == std::mem::size_of::<BlockIQ1M>());
+
+#[derive(Debug, Clone, PartialEq)]
+#[repr(C)]
+pub struct BlockIQ4NL {
+    pub(crate) d: f16,
+    pub(crate) qs: [u8; QK4_NL / 2],
+}
+const _: () = assert!(2 + QK4_NL / 2 == std::mem::size_of::<BlockIQ4NL>());
+
+#[derive(Debug, Clone, PartialEq)]
+#[repr(C)]
+pub struct BlockIQ4XS {
+    pub(crate) d: f16,
+    pub(crate) scales_h: u16,
+    pub(crate) scales_l: [u8; QK_K / 64],
+    pub(crate) qs: [u8; QK_K / 2],
+}
+const _: () = assert!(2 + 2 + QK_K / 64 + QK_K / 2 == std::mem::size_of::<BlockIQ4XS>());
+
+/// Ternary quantization, 5 values are packed per byte in `qs` and 4 values per byte in `qh`.
+#[derive(Debug, Clone, PartialEq)]
+#[repr(C)]
+pub struct BlockTQ1_0 {
+    pub(crate) qs: [u8; (QK_K - 4 * QK_K / 64) / 5],
+    pub(crate) qh: [u8; QK_K / 64],
+    pub(crate) d: f16,
+}
+const _: () =
+    assert!((QK_K - 4 * QK_K / 64) / 5 + QK_K / 64 + 2 == std::mem::size_of::<BlockTQ1_0>());
+
+/// Ternary quantization using 2 bits per value.
+#[derive(Debug, Clone, PartialEq)]
+#[repr(C)]
+pub struct BlockTQ2_0 {
+    pub(crate) qs: [u8; QK_K / 4],
+    pub(crate) d: f16,
+}
+const _: () = assert!(QK_K / 4 + 2 == std::mem::size_of::<BlockTQ2_0>());
+
+/// The set of allowed combinations for `dim` consecutive values.
+///
+/// Each coordinate of a grid point takes one of a small number of levels so the full lattice is
+/// small enough to be enumerated. For each lattice point that is not part of the grid we store
+/// the grid points that are the closest to it, quantizing a vector then amounts to rounding each
+/// coordinate to its nearest level and picking the best candidate.
+struct Grid {
+    dim: usize,
+    levels: Vec<f32>,
+    values: Vec<[f32; 8]>,
+    /// The candidates for lattice point `i` are `candidates[offsets[i]..offsets[i + 1]]`.
+    offsets: Vec<u32>,
+    candidates: Vec<u16>,
+}
+
+impl Grid {
+    fn new(dim: usize, values: Vec<[f32; 8]>) -> Self {
+        let mut levels: Vec<f32> = values.iter().flat_map(|e| e[..dim].to_vec()).collect();
+        levels.sort_by(|a, b| a.total_cmp(b));
+        levels.dedup();
+        let n_levels = levels.len();
+        let points: Vec<[u8; 8]> = values
+            .iter()
+            .map(|e| {
+                let mut p = [0u8; 8];
+                for (p, v) in p.iter_mut().zip(e[..dim].iter()) {
+                    *p = levels.iter().position(|l| l == v).unwrap() as u8
+                }
+                p
+            })
+            .collect();
+        let n_lattice = n_levels.pow(dim as u32);
+        let mut on_grid = vec![None; n_lattice];
+        for (idx, p) in points.iter().enumerate() {
+            on_grid[Self::lattice_index(n_levels, &p[..dim])] = Some(idx as u16)
+        }
+        let mut offsets = Vec::with_capacity(n_lattice + 1);
+        let mut candidates = Vec::with_capacity(n_lattice);
+        let mut q = [0u8; 8];
+        let mut dists = vec![0u32; points.len()];
+        offsets.push(0);
+        for (lattice_idx, on_grid) in on_grid.iter().enumerate() {
+            if let Some(idx) = on_grid {
+                candidates.push(*idx);
+                offsets.push(candidates.len() as u32);
+                continue;
+            }
+            let mut rem = lattice_idx;
+            for q in q[..dim].iter_mut() {
+                *q = (rem % n_levels) as u8;
+                rem /= n_levels;
+            }
+            for (d, p) in dists.iter_mut().zip(points.iter()) {
+                *d = (0..dim)
+                    .map(|j| (p[j] as i32 - q[j] as i32).pow(2) as u32)
+                    .sum()
+            }
+            // Keep the grid points in the two closest distance shells.
+            let d1 = *dists.iter().min().unwrap();
+            let d2 = dists
+                .iter()
+                .filter(|&&d| d > d1)
+                .min()
+                .copied()
+                .unwrap_or(d1);
+            candidates.extend((0..points.len() as u16).filter(|&i| dists[i as usize] <= d2));
+            offsets.push(candidates.len() as u32);
+        }
+        Self {
+            dim,
+            levels,
+            values,
+            offsets,
+            candidates,
+        }
+    }
+
+    fn lattice_index(n_levels: usize, p: &[u8]) -> usize {
+        p.iter()
+            .rev()
+            .fold(0, |acc, &l| acc * n_levels + l as usize)
+    }
+
+    fn from_u64(grid: &[u64], signed: bool) -> Self {
+        let entries = grid
+            .iter()
+            .map(|g| {
+                let mut e = [0f32; 8];
+                for (j, e) in e.iter_mut().enumerate() {
+                    let b = (g >> (8 * j)) as u8;
+                    *e = if signed { b as i8 as f32 } else { b as f32 }
+                }
+                e
+            })
+            .collect();
+        Self::new(8, entries)
+    }
+
+    fn from_u32(grid: &[u32]) -> Self {
+        let entries = grid
+            .iter()
+            .map(|g| {
+                let mut e = [0f32; 8];
+                for (j, e) in e[..4].iter_mut().enumerate() {
+                    *e = ((g >> (8 * j)) as u8) as f32
+                }
+                e
+            })
+            .collect();
+        Self::new(4, entries)
+    }
+
+    fn level(&self, point: usize, j: usize) -> f32 {
+        self.values[point][j]
+    }
+
+    fn max_level(&self) -> f32 {
+        self.levels[self.levels.len() - 1]
+    }
+
+    /// Returns the grid point minimizing `sum_j w_j (t_j - g_j)^2`.
+    fn search(&self, t: &[f32], w: &[f32]) -> usize {
+        let n_levels = self.levels.len();
+        let mut lattice_idx = 0;
+        for &t in t.iter().rev() {
+            let mut best = 0;
+            for l in 1..n_levels {
+                if (t - self.levels[l]).abs() < (t - self.levels[best]).abs() {
+                    best = l
+                }
+            }
+            lattice_idx = lattice_idx * n_levels + best
+        }
+        let start = self.offsets[lattice_idx] as usize;
+        let end = self.offsets[lattice_idx + 1] as usize;
+        let candidates = &self.candidates[start..end];
+        if candidates.len() == 1 {
+            return candidates[0] as usize;
+        }
+        let (t, w) = (&t[..self.dim], &w[..self.dim]);
+        let mut best = (f32::INFINITY, 0);
+        for &c in candidates.iter() {
+            let v = &self.values[c as usize][..self.dim];
+            let mut err = 0f32;
+            for j in 0..self.dim {
+                err += w[j] * (t[j] - v[j]) * (t[j] - v[j]);
+                if err >= best.0 {
+                    break;
+                }
+            }
+            if err < best.0 {
+                best = (err, c as usize)
+            }
+        }
+        best.1
+    }
+}
+
+macro_rules! grid {
+    ($fn_name:ident, $init:expr) => {
+        fn $fn_name() -> &'static Grid {
+            static GRID: OnceLock<Grid> = OnceLock::new();
+            GRID.get_or_init(|| $init)
+        }
+    };
+}
+
+grid!(iq2xxs_grid, Grid::from_u64(&IQ2XXS_GRID, false));
+grid!(iq2xs_grid, Grid::from_u64(&IQ2XS_GRID, false));
+grid!(iq2s_grid, Grid::from_u64(&IQ2S_GRID, false));
+grid!(iq3xxs_grid, Grid::from_u32(&IQ3XXS_GRID));
+grid!(iq3s_grid, Grid::from_u32(&IQ3S_GRID));
+grid!(iq1s_grid, Grid::from_u64(&IQ1S_GRID, true));
+
+/// The grid points and shifts selected for a sub-block, values are reconstructed as
+/// `scale * (level + shift)`.
+struct GridFit {
+    scale: f32,
+    points: Vec<u16>,
+    shifts: Vec<usize>,
+    sumqx: f32,
+    sumq2: f32,
+}
+
+/// Selects the best grid points for the values `xs` at a fixed scale. Each group of `group`
+/// values shares one of the `shifts`.
+fn fit_points(
+    xs: &[f32],
+    ws: &[f32],
+    grid: &Grid,
+    shifts: &[f32],
+    group: usize,
+    scale: f32,
+) -> GridFit {
+    let dim = grid.dim;
+    let inv_scale = 1. / scale;
+    let mut fit = GridFit {
+        scale,
+        points: Vec::with_capacity(xs.len() / dim),
+        shifts: Vec::with_capacity(xs.len() / group),
+        sumqx: 0.,
+        sumq2: 0.,
+    };
+    let mut t = [0f32; 8];
+    let mut points = Vec::with_capacity(group / dim);
+    for (xg, wg) in xs.chunks_exact(group).zip(ws.chunks_exact(group)) {
+        let mut best = (f32::INFINITY, 0, 0., 0., vec![]);
+        for (shift_idx, &shift) in shifts.iter().enumerate() {
+            let (mut err, mut sumqx, mut sumq2) = (0., 0., 0.);
+            points.clear();
+            for (xc, wc) in xg.chunks_exact(dim).zip(wg.chunks_exact(dim)) {
+                for (t, &x) in t.iter_mut().zip(xc.iter()) {
+                    *t = x * inv_scale - shift
+                }
+                let p = grid.search(&t[..dim], wc);
+                for j in 0..dim {
+                    let q = grid.level(p, j) + shift;
+                    err += wc[j] * (xc[j] - scale * q).powi(2);
+                    sumqx += wc[j] * xc[j] * q;
+                    sumq2 += wc[j] * q * q;
+                }
+                points.push(p as u16)
+            }
+            if err < best.0 {
+                best = (err, shift_idx, sumqx, sumq2, points.clone())
+            }
+        }
+        fit.points.extend_from_slice(&best.4);
+        fit.shifts.push(best.1);
+        fit.sumqx += best.2;
+        fit.sumq2 += best.3;
+    }
+    fit
+}
+
+/// Searches for the scale and grid points that minimize the weighted quantization error of `xs`.
+/// The candidate scales map the largest absolute value to `top` within `+/- ntry * step`.
+fn fit_grid(
+    xs: &[f32],
+    ws: &[f32],
+    grid: &Grid,
+    shifts: &[f32],
+    group: usize,
+    top: f32,
+    (ntry, step): (i32, f32),
+) -> GridFit {
+    let amax = xs.iter().fold(0f32, |acc, x| acc.max(x.abs()));
+    if amax < GROUP_MAX_EPS {
+        return GridFit {
+            scale: 0.,
+            points: vec![0; xs.len() / grid.dim],
+            shifts: vec![0; xs.len() / group],
+            sumqx: 0.,
+            sumq2: 0.,
+        };
+    }
+    let score = |fit: &GridFit| {
+        if fit.sumq2 > 0. && fit.sumqx > 0. {
+            fit.sumqx * fit.sumqx / fit.sumq2
+        } else {
+            0.
+        }
+    };
+    let mut best: Option<(f32, GridFit)> = None;
+    for itry in -ntry..=ntry {
+        let scale = amax / top * (1. + step * itry as f32);
+        let mut fit = fit_points(xs, ws, grid, shifts, group, scale);
+        let s = score(&fit);
+        if s > 0. && best.as_ref().is_none_or(|b| s > b.0) {
+            fit.scale = fit.sumqx / fit.sumq2;
+            best = Some((s, fit))
+        }
+    }
+    let Some((mut best_score, mut best)) = best else {
+        return fit_points(xs, ws, grid, shifts, group, amax / top);
+    };
+    // Refine the points now that the scale has been adjusted.
+    for _ in 0..2 {
+        let mut fit = fit_points(xs, ws, grid, shifts, group, best.scale);
+        let s = score(&fit);
+        if s <= best_score {
+            break;
+        }
+        fit.scale = fit.sumqx / fit.sumq2;
+        (best_score, best) = (s, fit)
+    }
+    best
+}
+
+/// Computes the per-value importance used when searching for the quantized values.
+fn quant_weights(xs: &[f32], imatrix: Option<&[f32]>, ws: &mut [f32]) {
+    match imatrix {
+        Some(qw) => {
+            let sigma2 = xs.iter().map(|x| x * x).sum::<f32>() / xs.len() as f32;
+            for ((w, &x), &qw) in ws.iter_mut().zip(xs.iter()).zip(qw.iter()) {
+                *w = qw * (sigma2 + x * x).sqrt()
+            }
+        }
+        None => {
+            for (w, &x) in ws.iter_mut().zip(xs.iter()) {
+                *w = x * x
+            }
+        }
+    }
+}
+
+/// Returns the imatrix weights for each super-block of `block_size` values.
+fn imatrix_for_blocks<'a>(
+    imatrix_weights: Option<&'a [f32]>,
+    n_per_row: usize,
+    block_size: usize,
+) -> impl Fn(usize) -> Option<&'a [f32]> {
+    move |blk_idx| {
+        imatrix_weights.map(|qw| {
+            let offset = (blk_idx % (n_per_row / block_size)) * block_size;
+            &qw[offset..offset + block_size]
+        })
+    }
+}
+
+/// Splits a group of 8 values in magnitudes and sign bits. When `even_signs` is set, the number
+/// of negative values is forced to be even by flipping the sign of the least important value.
+fn split_signs(xs: &[f32], ws: &[f32], even_signs: bool, mags: &mut [f32]) -> u8 {
+    let mut signs = 0u8;
+    for (j, (m, &x)) in mags.iter_mut().zip(xs.iter()).enumerate() {
+        *m = x.abs();
+        if x < 0. {
+            signs |= 1 << j
+        }
+    }
+    if even_signs && signs.count_ones() % 2 == 1 {
+        let mut imin = 0;
+        for j in 1..8 {
+            if ws[j] * xs[j] * xs[j] < ws[imin] * xs[imin] * xs[imin] {
+                imin = j
+            }
+        }
+        signs ^= 1 << imin;
+        mags[imin] = -mags[imin];
+    }
+    signs
+}
+
+/// Quantizes the per sub-block scales as `d * (base + step * ls)` with `ls` in `0..=max_ls`.
+fn quantize_scales(scales: &[f32], (base, step, max_ls): (f32, f32, u8), ls: &mut [u8]) -> f16 {
+    let max_scale = scales.iter().fold(0f32, |acc, &s| acc.max(s));
+    if max_scale < GROUP_MAX_EPS {
+        ls.fill(0);
+        return f16::ZERO;
+    }
+    let d = f16::from_f32(max_scale / (base + step * max_ls as f32));
+    let id = 1. / d.to_f32();
+    for (ls, &s) in ls.iter_mut().zip(scales.iter()) {
+        *ls = nearest_int((s * id - base) / step).clamp(0, max_ls as i32) as u8
+    }
+    d
+}
+
+fn best_index_iq4nl(x: f32) -> u8 {
+    let mut best = 0;
+    for (i, &v) in KVALUES_IQ4NL.iter().enumerate().skip(1) {
+        if (x - v as f32).abs() < (x - KVALUES_IQ4NL[best] as f32).abs() {
+            best = i
+        }
+    }
+    best as u8
+}
+
+/// Computes the scale and 4-bit non-linear quants for a block of 32 values.
+fn quantize_iq4nl_block(xs: &[f32], ws: &[f32], l: &mut [u8]) -> f32 {
+    const NTRY: i32 = 7;
+    let (mut amax, mut max) = (0f32, 0f32);
+    for &x in xs.iter() {
+        if x.abs() > amax {
+            amax = x.abs();
+            max = x;
+        }
+    }
+    if amax < GROUP_MAX_EPS {
+        l.fill(0);
+        return 0.;
+    }
+    let values0 = KVALUES_IQ4NL[0] as f32;
+    let eval = |id: f32, l: &mut [u8]| {
+        let (mut sumqx, mut sumq2) = (0f32, 0f32);
+        for ((l, &x), &w) in l.iter_mut().zip(xs.iter()).zip(ws.iter()) {
+            *l = best_index_iq4nl(id * x);
+            let q = KVALUES_IQ4NL[*l as usize] as f32;
+            sumqx += w * q * x;
+            sumq2 += w * q * q;
+        }
+        (sumqx, sumq2)
+    };
+    let (sumqx, sumq2) = eval(values0 / max, l);
+    let mut d = if sumq2 > 0. { sumqx / sumq2 } else { 0. };
+    let mut best = d * sumqx;
+    let mut tmp = [0u8; QK4_NL];
+    for itry in -NTRY..=NTRY {
+        let id = (itry as f32 + values0) / max;
+        let (sumqx, sumq2) = eval(id, &mut tmp[..xs.len()]);
+        if sumq2 > 0. && sumqx * sumqx > best * sumq2 {
+            d = sumqx / sumq2;
+            best = d * sumqx;
+        }
+    }
+    d
+}
+
+fn requantize_iq4nl(xs: &[f32], d: f32, l: &mut [u8]) {
+    let id = if d != 0. { 1. / d } else { 0. };
+    for (l, &x) in l.iter_mut().zip(xs.iter()) {
+        *l = best_index_iq4nl(id * x)
+    }
+}
+
+impl BlockIQ4NL {
+    fn quantize(xs: &[f32], ys: &mut [Self], imatrix: Option<&[f32]>, n_per_row: usize) {
+        let imatrix = imatrix_for_blocks(imatrix, n_per_row, QK4_NL);
+        for (blk_idx, (block, x)) in group_for_quantization(xs, ys).into_iter().enumerate() {
+            let mut ws = [0f32; QK4_NL];
+            let mut l = [0u8; QK4_NL];
+            quant_weights(x, imatrix(blk_idx), &mut ws);
+            let d = f16::from_f32(quantize_iq4nl_block(x, &ws, &mut l));
+            requantize_iq4nl(x, d.to_f32(), &mut l);
+            block.d = d;
+            for j in 0..QK4_NL / 2 {
+                block.qs[j] = l[j] | (l[j + QK4_NL / 2] << 4)
+            }
+        }
+    }
+}
+
+impl GgmlType for BlockIQ4NL {
+    const DTYPE: GgmlDType = GgmlDType::IQ4NL;
+    const BLCK_SIZE: usize = QK4_NL;
+    type VecDotType = BlockQ8_0;
+
+    #[allow(unreachable_code)]
+    fn vec_dot(n: usize, xs: &[Self], ys: &[Self::VecDotType]) -> f32 {
+        #[cfg(target_feature = "avx2")]
+        return super::avx::vec_dot_iq4nl_q8_0(n, xs, ys);
+
+        #[cfg(target_feature = "neon")]
+        return super::neon::vec_dot_iq4nl_q8_0(n, xs, ys);
+
+        #[cfg(target_feature = "simd128")]
+        return super::simd128::vec_dot_iq4nl_q8_0(n, xs, ys);
+
+        Self::vec_dot_unopt(n, xs, ys)
+    }
+
+    fn vec_dot_unopt(n: usize, xs: &[Self], ys: &[Self::VecDotType]) -> f32 {
+        debug_assert!(
+            n.is_multiple_of(QK8_0),
+            "vec_dot_iq4nl_q8_0: {n} is not divisible by {QK8_0}"
+        );
+        let mut sumf = 0f32;
+        for (x, y) in xs.iter().zip(ys.iter()) {
+            let mut sumi = 0i32;
+            for j in 0..QK4_NL / 2 {
+                let v0 = KVALUES_IQ4NL[(x.qs[j] & 0xf) as usize] as i32;
+                let v1 = KVALUES_IQ4NL[(x.qs[j] >> 4) as usize] as i32;
+                sumi += v0 * y.qs[j] as i32 + v1 * y.qs[j + QK4_NL / 2] as i32
+            }
+            sumf += sumi as f32 * x.d.to_f32() * y.d.to_f32()
+        }
+        sumf
+    }
+
+    fn from_float(xs: &[f32], ys: &mut [Self]) {
+        Self::quantize(xs, ys, None, xs.len())
+    }
+
+    fn from_float_imatrix(xs: &[f32], ys: &mut [Self], imatrix_weights: &[f32], n_per_row: usize) {
+        Self::quantize(xs, ys, Some(imatrix_weights), n_per_row)
+    }
+
+    fn to_float(xs: &[Self], ys: &mut [f32]) {
+        for (block, y) in group_for_dequantization(xs, ys) {
+            let d = block.d.to_f32();
+            for j in 0..QK4_NL / 2 {
+                y[j] = d * KVALUES_IQ4NL[(block.qs[j] & 0xf) as usize] as f32;
+                y[j + QK4_NL / 2] = d * KVALUES_IQ4NL[(block.qs[j] >> 4) as usize] as f32;
+            }
+        }
+    }
+}
+
+impl BlockIQ4XS {
+    pub(crate) fn scale(&self, ib: usize) -> i32 {
+        let ls = ((self.scales_l[ib / 2] >> (4 * (ib % 2))) & 0xf)
+            | ((((self.scales_h >> (2 * ib)) & 3) as u8) << 4);
+        ls as i32 - 32
+    }
+
+    fn quantize(xs: &[f32], ys: &mut [Self], imatrix: Option<&[f32]>, n_per_row: usize) {
+        let imatrix = imatrix_for_blocks(imatrix, n_per_row, QK_K);
+        for (blk_idx, (block, x)) in group_for_quantization(xs, ys).into_iter().enumerate() {
+            let mut ws = [0f32; QK_K];
+            let mut l = [0u8; QK_K];
+            let mut scales = [0f32; QK_K / 32];
+            quant_weights(x, imatrix(blk_idx), &mut ws);
+            let mut max_scale = 0f32;
+            let mut amax_scale = 0f32;
+            for (ib, scale) in scales.iter_mut().enumerate() {
+                let r = 32 * ib..32 * (ib + 1);
+                *scale = quantize_iq4nl_block(&x[r.clone()], &ws[r.clone()], &mut l[r]);
+                if scale.abs() > amax_scale {
+                    amax_scale = scale.abs();
+                    max_scale = *scale;
+                }
+            }
+            block.scales_h = 0;
+            block.scales_l = [0; QK_K / 64];
+            if amax_scale < GROUP_MAX_EPS {
+                block.d = f16::ZERO;
+                block.qs = [0; QK_K / 2];
+                continue;
+            }
+            let d = f16::from_f32(-max_scale / 32.);
+            let id = 1. / d.to_f32();
+            for (ib, &scale) in scales.iter().enumerate() {
+                let ls = nearest_int(id * scale).clamp(-32, 31);
+                let dl = d.to_f32() * ls as f32;
+                let r = 32 * ib..32 * (ib + 1);
+                requantize_iq4nl(&x[r.clone()], dl, &mut l[r]);
+                let ls = (ls + 32) as u8;
+                block.scales_l[ib / 2] |= (ls & 0xf) << (4 * (ib % 2));
+                block.scales_h |= ((ls >> 4) as u16) << (2 * ib);
+            }
+            block.d = d;
+            for ib in 0..QK_K / 32 {
+                for j in 0..16 {
+                    block.qs[16 * ib + j] = l[32 * ib + j] | (l[32 * ib + 16 + j] << 4)
+                }
+            }
+        }
+    }
+}
+
+impl GgmlType for BlockIQ4XS {
+    const DTYPE: GgmlDType = GgmlDType::IQ4XS;
+    const BLCK_SIZE: usize = QK_K;
+    type VecDotType = BlockQ8K;
+
+    #[allow(unreachable_code)]
+    fn vec_dot(n: usize, xs: &[Self], ys: &[Self::VecDotType]) -> f32 {
+        #[cfg(target_feature = "avx2")]
+        return super::avx::vec_dot_iq4xs_q8k(n, xs, ys);
+
+        #[cfg(target_feature = "neon")]
+        return super::neon::vec_dot_iq4xs_q8k(n, xs, ys);
+
+        #[cfg(target_feature = "simd128")]
+        return super::simd128::vec_dot_iq4xs_q8k(n, xs, ys);
+
+        Self::vec_dot_unopt(n, xs, ys)
+    }
+
+    fn vec_dot_unopt(n: usize, xs: &[Self], ys: &[Self::VecDotType]) -> f32 {
+        debug_assert!(
+            n.is_multiple_of(QK_K),
+            "vec_dot_iq4xs_q8k: {n} is not divisible by {QK_K}"
+        );
+        let mut sumf = 0f32;
+        for (x, y) in xs.iter().zip(ys.iter()) {
+            let mut sumi = 0i32;
+            for ib in 0..QK_K / 32 {
+                let qs = &x.qs[16 * ib..16 * (ib + 1)];
+                let q8 = &y.qs[32 * ib..32 * (ib + 1)];
+                let mut s = 0i32;
+                for j in 0..16 {
+                    s += KVALUES_IQ4NL[(qs[j] & 0xf) as usize] as i32 * q8[j] as i32;
+                    s += KVALUES_IQ4NL[(qs[j] >> 4) as usize] as i32 * q8[j + 16] as i32;
+                }
+                sumi += s * x.scale(ib)
+            }
+            sumf += sumi as f32 * x.d.to_f32() * y.d
+        }
+        sumf
+    }
+
+    fn from_float(xs: &[f32], ys: &mut [Self]) {
+        Self::quantize(xs, ys, None, xs.len())
+    }
+
+    fn from_float_imatrix(xs: &[f32], ys: &mut [Self], imatrix_weights: &[f32], n_per_row: usize) {
+        Self::quantize(xs, ys, Some(imatrix_weights), n_per_row)
+    }
+
+    fn to_float(xs: &[Self], ys: &mut [f32]) {
+        for (block, y) in group_for_dequantization(xs, ys) {
+            let d = block.d.to_f32();
+            for ib in 0..QK_K / 32 {
+                let dl = d * block.scale(ib) as f32;
+                let qs = &block.qs[16 * ib..16 * (ib + 1)];
+                let y = &mut y[32 * ib..32 * (ib + 1)];
+                for j in 0..16 {
+                    y[j] = dl * KVALUES_IQ4NL[(qs[j] & 0xf) as usize] as f32;
+                    y[j + 16] = dl * KVALUES_IQ4NL[(qs[j] >> 4) as usize] as f32;
+                }
+            }
+        }
+    }
+}
+
+#[inline(always)]
+fn grid_byte(g: u64, j: usize) -> i32 {
+    ((g >> (8 * j)) & 0xff) as i32
+}
+
+#[inline(always)]
+fn apply_sign(v: i32, signs: u8, j: usize) -> i32 {
+    if signs & KMASK_IQ2XS[j] != 0 {
+        -v
+    } else {
+        v
+    }
+}
+
+/// Quantizes a sub-block of 16 or 32 values split in groups of 8 magnitudes and signs.
+fn fit_signed_grid(
+    xs: &[f32],
+    ws: &[f32],
+    grid: &Grid,
+    even_signs: bool,
+    signs: &mut [u8],
+) -> GridFit {
+    let mut mags = [0f32; 32];
+    let mags = &mut mags[..xs.len()];
+    for (i, s) in signs.iter_mut().enumerate() {
+        let r = 8 * i..8 * (i + 1);
+        *s = split_signs(&xs[r.clone()], &ws[r.clone()], even_signs, &mut mags[r])
+    }
+    fit_grid(
+        mags,
+        ws,
+        grid,
+        &[0.],
+        xs.len(),
+        grid.max_level(),
+        (7, 0.025),
+    )
+}
+
+/// Recomputes the grid points of a sub-block once its scale has been quantized.
+fn refit_signed_grid(xs: &[f32], ws: &[f32], grid: &Grid, signs: &[u8], scale: f32) -> Vec<u16> {
+    let mut mags = [0f32; 32];
+    let mags = &mut mags[..xs.len()];
+    for (i, (m, &x)) in mags.iter_mut().zip(xs.iter()).enumerate() {
+        *m = if signs[i / 8] & KMASK_IQ2XS[i % 8] != 0 {
+            -x
+        } else {
+            x
+        }
+    }
+    fit_points(mags, ws, grid, &[0.], xs.len(), scale).points
+}
+
+impl BlockIQ2XXS {
+    fn quantize(xs: &[f32], ys: &mut [Self], imatrix: Option<&[f32]>, n_per_row: usize) {
+        let grid = iq2xxs_grid();
+        let imatrix = imatrix_for_blocks(imatrix, n_per_row, QK_K);
+        for (blk_idx, (block, x)) in group_for_quantization(xs, ys).into_iter().enumerate() {
+            let mut ws = [0f32; QK_K];
+            quant_weights(x, imatrix(blk_idx), &mut ws);
+            let mut signs = [0u8; QK_K / 8];
+            let mut points = [0u16; QK_K / 8];
+            let mut scales = [0f32; QK_K / 32];
+            for ib in 0..QK_K / 32 {
+                let r = 32 * ib..32 * (ib + 1);
+                let signs = &mut signs[4 * ib..4 * (ib + 1)];
+                let fit = fit_signed_grid(&x[r.clone()], &ws[r], grid, true, signs);
+                scales[ib] = fit.scale;
+                points[4 * ib..4 * (ib + 1)].copy_from_slice(&fit.points);
+            }
+            let mut ls = [0u8; QK_K / 32];
+            block.d = quantize_scales(&scales, (0.125, 0.25, 15), &mut ls);
+            let d = block.d.to_f32();
+            for ib in 0..QK_K / 32 {
+                let r = 32 * ib..32 * (ib + 1);
+                if scales[ib] > 0. && d > 0. {
+                    let dl = d * (0.5 + ls[ib] as f32) * 0.25;
+                    let signs = &signs[4 * ib..4 * (ib + 1)];
+                    let p = refit_signed_grid(&x[r.clone()], &ws[r], grid, signs, dl);
+                    points[4 * ib..4 * (ib + 1)].copy_from_slice(&p);
+                }
+                let mut aux0 = 0u32;
+                let mut aux1 = (ls[ib] as u32) << 28;
+                for l in 0..4 {
+                    aux0 |= (points[4 * ib + l] as u32) << (8 * l);
+                    aux1 |= ((signs[4 * ib + l] & 127) as u32) << (7 * l);
+                }
+                block.qs[4 * ib] = aux0 as u16;
+                block.qs[4 * ib + 1] = (aux0 >> 16) as u16;
+                block.qs[4 * ib + 2] = aux1 as u16;
+                block.qs[4 * ib + 3] = (aux1 >> 16) as u16;
+            }
+        }
+    }
+}
+
+impl GgmlType for BlockIQ2XXS {
+    const DTYPE: GgmlDType = GgmlDType::IQ2XXS;
+    const BLCK_SIZE: usize = QK_K;
+    type VecDotType = BlockQ8K;
+
+    #[allow(unreachable_code)]
+    fn vec_dot(n: usize, xs: &[Self], ys: &[Self::VecDotType]) -> f32 {
+        #[cfg(target_feature = "avx2")]
+        return super::avx::vec_dot_iq2xxs_q8k(n, xs, ys);
+
+        Self::vec_dot_unopt(n, xs, ys)
+    }
+
+    fn vec_dot_unopt(n: usize, xs: &[Self], ys: &[Self::VecDotType]) -> f32 {
+        debug_assert!(
+            n.is_multiple_of(QK_K),
+            "vec_dot_iq2xxs_q8k: {n} is not divisible by {QK_K}"
+        );
+        let mut sumf = 0f32;
+        for (x, y) in xs.iter().zip(ys.iter()) {
+            let mut bsum = 0i32;
+            for ib in 0..QK_K / 32 {
+                let aux0 = x.qs[4 * ib] as u32 | (x.qs[4 * ib + 1] as u32) << 16;
+                let aux1 = x.qs[4 * ib + 2] as u32 | (x.qs[4 * ib + 3] as u32) << 16;
+                let ls = 2 * (aux1 >> 28) as i32 + 1;
+                let mut sumi = 0i32;
+                for l in 0..4 {
+                    let g = IQ2XXS_GRID[((aux0 >> (8 * l)) & 0xff) as usize];
+                    let signs = KSIGNS_IQ2XS[((aux1 >> (7 * l)) & 127) as usize];
+                    let q8 = &y.qs[32 * ib + 8 * l..32 * ib + 8 * (l + 1)];
+                    for (j, &q8) in q8.iter().enumerate() {
+                        sumi += apply_sign(grid_byte(g, j) * q8 as i32, signs, j)
+                    }
+                }
+                bsum += sumi * ls
+            }
+            sumf += x.d.to_f32() * y.d * bsum as f32
+        }
+        0.125 * sumf
+    }
+
+    fn from_float(xs: &[f32], ys: &mut [Self]) {
+        Self::quantize(xs, ys, None, xs.len())
+    }
+
+    fn from_float_imatrix(xs: &[f32], ys: &mut [Self], imatrix_weights: &[f32], n_per_row: usize) {
+        Self::quantize(xs, ys, Some(imatrix_weights), n_per_row)
+    }
+
+    fn to_float(xs: &[Self], ys: &mut [f32]) {
+        for (block, y) in group_for_dequantization(xs, ys) {
+            let d = block.d.to_f32();
+            for ib in 0..QK_K / 32 {
+                let aux0 = block.qs[4 * ib] as u32 | (block.qs[4 * ib + 1] as u32) << 16;
+                let aux1 = block.qs[4 * ib + 2] as u32 | (block.qs[4 * ib + 3] as u32) << 16;
+                let db = d * (0.5 + (aux1 >> 28) as f32) * 0.25;
+                for l in 0..4 {
+                    let g = IQ2XXS_GRID[((aux0 >> (8 * l)) & 0xff) as usize];
+                    let signs = KSIGNS_IQ2XS[((aux1 >> (7 * l)) & 127) as usize];
+                    for j in 0..8 {
+                        y[32 * ib + 8 * l + j] = db * apply_sign(grid_byte(g, j), signs, j) as f32
+                    }
+                }
+            }
+        }
+    }
+}
+
+impl BlockIQ2XS {
+    fn quantize(xs: &[f32], ys: &mut [Self], imatrix: Option<&[f32]>, n_per_row: usize) {
+        let grid = iq2xs_grid();
+        let imatrix = imatrix_for_blocks(imatrix, n_per_row, QK_K);
+        for (blk_idx, (block, x)) in group_for_quantization(xs, ys).into_iter().enumerate() {
+            let mut ws = [0f32; QK_K];
+            quant_weights(x, imatrix(blk_idx), &mut ws);
+            let mut signs = [0u8; QK_K / 8];
+            let mut points = [0u16; QK_K / 8];
+            let mut scales = [0f32; QK_K / 16];
+            for ib in 0..QK_K / 16 {
+                let r = 16 * ib..16 * (ib + 1);
+                let signs = &mut signs[2 * ib..2 * (ib + 1)];
+                let fit = fit_signed_grid(&x[r.clone()], &ws[r], grid, true, signs);
+                scales[ib] = fit.scale;
+                points[2 * ib..2 * (ib + 1)].copy_from_slice(&fit.points);
+            }
+            let mut ls = [0u8; QK_K / 16];
+            block.d = quantize_scales(&scales, (0.125, 0.25, 15), &mut ls);
+            let d = block.d.to_f32();
+            for ib in 0..QK_K / 16 {
+                let r = 16 * ib..16 * (ib + 1);
+                if scales[ib] > 0. && d > 0. {
+                    let dl = d * (0.5 + ls[ib] as f32) * 0.25;
+                    let signs = &signs[2 * ib..2 * (ib + 1)];
+                    let p = refit_signed_grid(&x[r.clone()], &ws[r], grid, signs, dl);
+                    points[2 * ib..2 * (ib + 1)].copy_from_slice(&p);
+                }
+            }
+            for (i, q) in block.qs.iter_mut().enumerate() {
+                *q = points[i] | ((signs[i] & 127) as u16) << 9
+            }
+            for (i, s) in block.scales.iter_mut().enumerate() {
+                *s = ls[2 * i] | (ls[2 * i + 1] << 4)
+            }
+        }
+    }
+}
+
+impl GgmlType for BlockIQ2XS {
+    const DTYPE: GgmlDType = GgmlDType::IQ2XS;
+    const BLCK_SIZE: usize = QK_K;
+    type VecDotType = BlockQ8K;
+
+    #[allow(unreachable_code)]
+    fn vec_dot(n: usize, xs: &[Self], ys: &[Self::VecDotType]) -> f32 {
+        #[cfg(target_feature = "avx2")]
+        return super::avx::vec_dot_iq2xs_q8k(n, xs, ys);
+
+        Self::vec_dot_unopt(n, xs, ys)
+    }
+
+    fn vec_dot_unopt(n: usize, xs: &[Self], ys: &[Self::VecDotType]) -> f32 {
+        debug_assert!(
+            n.is_multiple_of(QK_K),
+            "vec_dot_iq2xs_q8k: {n} is not divisible by {QK_K}"
+        );
+        let mut sumf = 0f32;
+        for (x, y) in xs.iter().zip(ys.iter()) {
+            let mut bsum = 0i32;
+            for ib in 0..QK_K / 16 {
+                let ls = 2 * ((x.scales[ib / 2] >> (4 * (ib % 2))) & 0xf) as i32 + 1;
+                let mut sumi = 0i32;
+                for l in 0..2 {
+                    let q = x.qs[2 * ib + l];
+                    let g = IQ2XS_GRID[(q & 511) as usize];
+                    let signs = KSIGNS_IQ2XS[(q >> 9) as usize];
+                    let q8 = &y.qs[16 * ib + 8 * l..16 * ib + 8 * (l + 1)];
+                    for (j, &q8) in q8.iter().enumerate() {
+                        sumi += apply_sign(grid_byte(g, j) * q8 as i32, signs, j)
+                    }
+                }
+                bsum += sumi * ls
+            }
+            sumf += x.d.to_f32() * y.d * bsum as f32
+        }
+        0.125 * sumf
+    }
+
+    fn from_float(xs: &[f32], ys: &mut [Self]) {
+        Self::quantize(xs, ys, None, xs.len())
+    }
+
+    fn from_float_imatrix(xs: &[f32], ys: &mut [Self], imatrix_weights: &[f32], n_per_row: usize) {
+        Self::quantize(xs, ys, Some(imatrix_weights), n_per_row)
+    }
+
+    fn to_float(xs: &[Self], ys: &mut [f32]) {
+        for (block, y) in group_for_dequantization(xs, ys) {
+            let d = block.d.to_f32();
+            for (i, &q) in block.qs.iter().enumerate() {
+                let ib = i / 2;
+                let db = d * (0.5 + ((block.scales[ib / 2] >> (4 * (ib % 2))) & 0xf) as f32) * 0.25;
+                let g = IQ2XS_GRID[(q & 511) as usize];
+                let signs = KSIGNS_IQ2XS[(q >> 9) as usize];
+                for j in 0..8 {
+                    y[8 * i + j] = db * apply_sign(grid_byte(g, j), signs, j) as f32
+                }
+            }
+        }
+    }
+}
+
+impl BlockIQ2S {
+    fn quantize(xs: &[f32], ys: &mut [Self], imatrix: Option<&[f32]>, n_per_row: usize) {
+        let grid = iq2s_grid();
+        let imatrix = imatrix_for_blocks(imatrix, n_per_row, QK_K);
+        for (blk_idx, (block, x)) in group_for_quantization(xs, ys).into_iter().enumerate() {
+            let mut ws = [0f32; QK_K];
+            quant_weights(x, imatrix(blk_idx), &mut ws);
+            let mut signs = [0u8; QK_K / 8];
+            let mut points = [0u16; QK_K / 8];
+            let mut scales = [0f32; QK_K / 16];
+            for ib in 0..QK_K / 16 {
+                let r = 16 * ib..16 * (ib + 1);
+                let signs = &mut signs[2 * ib..2 * (ib + 1)];
+                let fit = fit_signed_grid(&x[r.clone()], &ws[r], grid, false, signs);
+                scales[ib] = fit.scale;
+                points[2 * ib..2 * (ib + 1)].copy_from_slice(&fit.points);
+            }
+            let mut ls = [0u8; QK_K / 16];
+            block.d = quantize_scales(&scales, (0.125, 0.25, 15), &mut ls);
+            let d = block.d.to_f32();
+            for ib in 0..QK_K / 16 {
+                let r = 16 * ib..16 * (ib + 1);
+                if scales[ib] > 0. && d > 0. {
+                    let dl = d * (0.5 + ls[ib] as f32) * 0.25;
+                    let signs = &signs[2 * ib..2 * (ib + 1)];
+                    let p = refit_signed_grid(&x[r.clone()], &ws[r], grid, signs, dl);
+                    points[2 * ib..2 * (ib + 1)].copy_from_slice(&p);
+                }
+            }
+            block.qh = [0; QK_K / 32];
+            for (i, &p) in points.iter().enumerate() {
+                block.qs[i] = p as u8;
+                block.qs[QK_K / 8 + i] = signs[i];
+                block.qh[i / 4] |= ((p >> 8) as u8 & 3) << (2 * (i % 4));
+            }
+            for (i, s) in block.scales.iter_mut().enumerate() {
+                *s = ls[2 * i] | (ls[2 * i + 1] << 4)
+            }
+        }
+    }
+
+    pub(crate) fn grid_index(&self, i: usize) -> usize {
+        self.qs[i] as usize | (((self.qh[i / 4] >> (2 * (i % 4))) & 3) as usize) << 8
+    }
+}
+
+impl GgmlType for BlockIQ2S {
+    const DTYPE: GgmlDType = GgmlDType::IQ2S;
+    const BLCK_SIZE: usize = QK_K;
+    type VecDotType = BlockQ8K;
+
+    #[allow(unreachable_code)]
+    fn vec_dot(n: usize, xs: &[Self], ys: &[Self::VecDotType]) -> f32 {
+        #[cfg(target_feature = "avx2")]
+        return super::avx::vec_dot_iq2s_q8k(n, xs, ys);
+
+        Self::vec_dot_unopt(n, xs, ys)
+    }
+
+    fn vec_dot_unopt(n: usize, xs: &[Self], ys: &[Self::VecDotType]) -> f32 {
+        debug_assert!(
+            n.is_multiple_of(QK_K),
+            "vec_dot_iq2s_q8k: {n} is not divisible by {QK_K}"
+        );
+        let mut sumf = 0f32;
+        for (x, y) in xs.iter().zip(ys.iter()) {
+            let mut bsum = 0i32;
+            for ib in 0..QK_K / 16 {
+                let ls = 2 * ((x.scales[ib / 2] >> (4 * (ib % 2))) & 0xf) as i32 + 1;
+                let mut sumi = 0i32;
+                for l in 0..2 {
+                    let i = 2 * ib + l;
+                    let g = IQ2S_GRID[x.grid_index(i)];
+                    let signs = x.qs[QK_K / 8 + i];
+                    let q8 = &y.qs[8 * i..8 * (i + 1)];
+                    for (j, &q8) in q8.iter().enumerate() {
+                        sumi += apply_sign(grid_byte(g, j) * q8 as i32, signs, j)
+                    }
+                }
+                bsum += sumi * ls
+            }
+            sumf += x.d.to_f32() * y.d * bsum as f32
+        }
+        0.125 * sumf
+    }
+
+    fn from_float(xs: &[f32], ys: &mut [Self]) {
+        Self::quantize(xs, ys, None, xs.len())
+    }
+
+    fn from_float_imatrix(xs: &[f32], ys: &mut [Self], imatrix_weights: &[f32], n_per_row: usize) {
+        Self::quantize(xs, ys, Some(imatrix_weights), n_per_row)
+    }
+
+    fn to_float(xs: &[Self], ys: &mut [f32]) {
+        for (block, y) in group_for_dequantization(xs, ys) {
+            let d = block.d.to_f32();
+            for i in 0..QK_K / 8 {
+                let ib = i / 2;
+                let db = d * (0.5 + ((block.scales[ib / 2] >> (4 * (ib % 2))) & 0xf) as f32) * 0.25;
+                let g = IQ2S_GRID[block.grid_index(i)];
+                let signs = block.qs[QK_K / 8 + i];
+                for j in 0..8 {
+                    y[8 * i + j] = db * apply_sign(grid_byte(g, j), signs, j) as f32
+                }
+            }
+        }
+    }
+}
+
+impl BlockIQ3XXS {
+    fn quantize(xs: &[f32], ys: &mut [Self], imatrix: Option<&[f32]>, n_per_row: usize) {
+        let grid = iq3xxs_grid();
+        let imatrix = imatrix_for_blocks(imatrix, n_per_row, QK_K);
+        for (blk_idx, (block, x)) in group_for_quantization(xs, ys).into_iter().enumerate() {
+            let mut ws = [0f32; QK_K];
+            quant_weights(x, imatrix(blk_idx), &mut ws);
+            let mut signs = [0u8; QK_K / 8];
+            let mut points = [0u16; QK_K / 4];
+            let mut scales = [0f32; QK_K / 32];
+            for ib in 0..QK_K / 32 {
+                let r = 32 * ib..32 * (ib + 1);
+                let signs = &mut signs[4 * ib..4 * (ib + 1)];
+                let fit = fit_signed_grid(&x[r.clone()], &ws[r], grid, true, signs);
+                scales[ib] = fit.scale;
+                points[8 * ib..8 * (ib + 1)].copy_from_slice(&fit.points);
+            }
+            let mut ls = [0u8; QK_K / 32];
+            block.d = quantize_scales(&scales, (0.25, 0.5, 15), &mut ls);
+            let d = block.d.to_f32();
+            for ib in 0..QK_K / 32 {
+                let r = 32 * ib..32 * (ib + 1);
+                if scales[ib] > 0. && d > 0. {
+                    let dl = d * (0.5 + ls[ib] as f32) * 0.5;
+                    let signs = &signs[4 * ib..4 * (ib + 1)];
+                    let p = refit_signed_grid(&x[r.clone()], &ws[r], grid, signs, dl);
+                    points[8 * ib..8 * (ib + 1)].copy_from_slice(&p);
+                }
+                let mut aux = (ls[ib] as u32) << 28;
+                for l in 0..4 {
+                    aux |= ((signs[4 * ib + l] & 127) as u32) << (7 * l);
+                }
+                block.qs[QK_K / 4 + 4 * ib..QK_K / 4 + 4 * (ib + 1)]
+                    .copy_from_slice(&aux.to_le_bytes());
+            }
+            for (q, &p) in block.qs[..QK_K / 4].iter_mut().zip(points.iter()) {
+                *q = p as u8
+            }
+        }
+    }
+
+    pub(crate) fn scales_and_signs(&self, ib: usize) -> u32 {
+        let o = QK_K / 4 + 4 * ib;
+        u32::from_le_bytes([self.qs[o], self.qs[o + 1], self.qs[o + 2], self.qs[o + 3]])
+    }
+}
+
+impl GgmlType for BlockIQ3XXS {
+    const DTYPE: GgmlDType = GgmlDType::IQ3XXS;
+    const BLCK_SIZE: usize = QK_K;
+    type VecDotType = BlockQ8K;
+
+    #[allow(unreachable_code)]
+    fn vec_dot(n: usize, xs: &[Self], ys: &[Self::VecDotType]) -> f32 {
+        #[cfg(target_feature = "avx2")]
+        return super::avx::vec_dot_iq3xxs_q8k(n, xs, ys);
+
+        Self::vec_dot_unopt(n, xs, ys)
+    }
+
+    fn vec_dot_unopt(n: usize, xs: &[Self], ys: &[Self::VecDotType]) -> f32 {
+        debug_assert!(
+            n.is_multiple_of(QK_K),
+            "vec_dot_iq3xxs_q8k: {n} is not divisible by {QK_K}"
+        );
+        let mut sumf = 0f32;
+        for (x, y) in xs.iter().zip(ys.iter()) {
+            let mut bsum = 0i32;
+            for ib in 0..QK_K / 32 {
+                let aux = x.scales_and_signs(ib);
+                let ls = 2 * (aux >> 28) as i32 + 1;
+                let mut sumi = 0i32;
+                for l in 0..4 {
+                    let signs = KSIGNS_IQ2XS[((aux >> (7 * l)) & 127) as usize];
+                    let g1 = IQ3XXS_GRID[x.qs[8 * ib + 2 * l] as usize] as u64;
+                    let g2 = IQ3XXS_GRID[x.qs[8 * ib + 2 * l + 1] as usize] as u64;
+                    let q8 = &y.qs[32 * ib + 8 * l..32 * ib + 8 * (l + 1)];
+                    for j in 0..4 {
+                        sumi += apply_sign(grid_byte(g1, j) * q8[j] as i32, signs, j);
+                        sumi += apply_sign(grid_byte(g2, j) * q8[j + 4] as i32, signs, j + 4);
+                    }
+                }
+                bsum += sumi * ls
+            }
+            sumf += x.d.to_f32() * y.d * bsum as f32
+        }
+        0.25 * sumf
+    }
+
+    fn from_float(xs: &[f32], ys: &mut [Self]) {
+        Self::quantize(xs, ys, None, xs.len())
+    }
+
+    fn from_float_imatrix(xs: &[f32], ys: &mut [Self], imatrix_weights: &[f32], n_per_row: usize) {
+        Self::quantize(xs, ys, Some(imatrix_weights), n_per_row)
+    }
+
+    fn to_float(xs: &[Self], ys: &mut [f32]) {
+        for (block, y) in group_for_dequantization(xs, ys) {
+            let d = block.d.to_f32();
+            for ib in 0..QK_K / 32 {
+                let aux = block.scales_and_signs(ib);
+                let db = d * (0.5 + (aux >> 28) as f32) * 0.5;
+                for l in 0..4 {
+                    let signs = KSIGNS_IQ2XS[((aux >> (7 * l)) & 127) as usize];
+                    let g1 = IQ3XXS_GRID[block.qs[8 * ib + 2 * l] as usize] as u64;
+                    let g2 = IQ3XXS_GRID[block.qs[8 * ib + 2 * l + 1] as usize] as u64;
+                    let y = &mut y[32 * ib + 8 * l..32 * ib + 8 * (l + 1)];
+                    for j in 0..4 {
+                        y[j] = db * apply_sign(grid_byte(g1, j), signs, j) as f32;
+                        y[j + 4] = db * apply_sign(grid_byte(g2, j), signs, j + 4) as f32;
+                    }
+                }
+            }
+        }
+    }
+}
+
+impl BlockIQ3S {
+    fn quantize(xs: &[f32], ys: &mut [Self], imatrix: Option<&[f32]>, n_per_row: usize) {
+        let grid = iq3s_grid();
+        let imatrix = imatrix_for_blocks(imatrix, n_per_row, QK_K);
+        for (blk_idx, (block, x)) in group_for_quantization(xs, ys).into_iter().enumerate() {
+            let mut ws = [0f32; QK_K];
+            quant_weights(x, imatrix(blk_idx), &mut ws);
+            let mut points = [0u16; QK_K / 4];
+            let mut scales = [0f32; QK_K / 32];
+            for ib in 0..QK_K / 32 {
+                let r = 32 * ib..32 * (ib + 1);
+                let signs = &mut block.signs[4 * ib..4 * (ib + 1)];
+                let fit = fit_signed_grid(&x[r.clone()], &ws[r], grid, false, signs);
+                scales[ib] = fit.scale;
+                points[8 * ib..8 * (ib + 1)].copy_from_slice(&fit.points);
+            }
+            let mut ls = [0u8; QK_K / 32];
+            block.d = quantize_scales(&scales, (1., 2., 15), &mut ls);
+            let d = block.d.to_f32();
+            block.qh = [0; QK_K / 32];
+            for ib in 0..QK_K / 32 {
+                let r = 32 * ib..32 * (ib + 1);
+                if scales[ib] > 0. && d > 0. {
+                    let dl = d * (1. + 2. * ls[ib] as f32);
+                    let signs = &block.signs[4 * ib..4 * (ib + 1)];
+                    let p = refit_signed_grid(&x[r.clone()], &ws[r], grid, signs, dl);
+                    points[8 * ib..8 * (ib + 1)].copy_from_slice(&p);
+                }
+                for k in 0..8 {
+                    let p = points[8 * ib + k];
+                    block.qs[8 * ib + k] = p as u8;
+                    block.qh[ib] |= ((p >> 8) as u8 & 1) << k;
+                }
+            }
+            for (i, s) in block.scales.iter_mut().enumerate() {
+                *s = ls[2 * i] | (ls[2 * i + 1] << 4)
+            }
+        }
+    }
+
+    pub(crate) fn grid(&self, ib: usize, k: usize) -> u64 {
+        let idx = self.qs[8 * ib + k] as usize | (((self.qh[ib] >> k) & 1) as usize) << 8;
+        IQ3S_GRID[idx] as u64
+    }
+}
+
+impl GgmlType for BlockIQ3S {
+    const DTYPE: GgmlDType = GgmlDType::IQ3S;
+    const BLCK_SIZE: usize = QK_K;
+    type VecDotType = BlockQ8K;
+
+    #[allow(unreachable_code)]
+    fn vec_dot(n: usize, xs: &[Self], ys: &[Self::VecDotType]) -> f32 {
+        #[cfg(target_feature = "avx2")]
+        return super::avx::vec_dot_iq3s_q8k(n, xs, ys);
+
+        Self::vec_dot_unopt(n, xs, ys)
+    }
+
+    fn vec_dot_unopt(n: usize, xs: &[Self], ys: &[Self::VecDotType]) -> f32 {
+        debug_assert!(
+            n.is_multiple_of(QK_K),
+            "vec_dot_iq3s_q8k: {n} is not divisible by {QK_K}"
+        );
+        let mut sumf = 0f32;
+        for (x, y) in xs.iter().zip(ys.iter()) {
+            let mut bsum = 0i32;
+            for ib in 0..QK_K / 32 {
+                let ls = 2 * ((x.scales[ib / 2] >> (4 * (ib % 2))) & 0xf) as i32 + 1;
+                let mut sumi = 0i32;
+                for l in 0..4 {
+                    let signs = x.signs[4 * ib + l];
+                    let (g1, g2) = (x.grid(ib, 2 * l), x.grid(ib, 2 * l + 1));
+                    let q8 = &y.qs[32 * ib + 8 * l..32 * ib + 8 * (l + 1)];
+                    for j in 0..4 {
+                        sumi += apply_sign(grid_byte(g1, j) * q8[j] as i32, signs, j);
+                        sumi += apply_sign(grid_byte(g2, j) * q8[j + 4] as i32, signs, j + 4);
+                    }
+                }
+                bsum += sumi * ls
+            }
+            sumf += x.d.to_f32() * y.d * bsum as f32
+        }
+        sumf
+    }
+
+    fn from_float(xs: &[f32], ys: &mut [Self]) {
+        Self::quantize(xs, ys, None, xs.len())
+    }
+
+    fn from_float_imatrix(xs: &[f32], ys: &mut [Self], imatrix_weights: &[f32], n_per_row: usize) {
+        Self::quantize(xs, ys, Some(imatrix_weights), n_per_row)
+    }
+
+    fn to_float(xs: &[Self], ys: &mut [f32]) {
+        for (block, y) in group_for_dequantization(xs, ys) {
+            let d = block.d.to_f32();
+            for ib in 0..QK_K / 32 {
+                let db = d * (1 + 2 * ((block.scales[ib / 2] >> (4 * (ib % 2))) & 0xf)) as f32;
+                for l in 0..4 {
+                    let signs = block.signs[4 * ib + l];
+                    let (g1, g2) = (block.grid(ib, 2 * l), block.grid(ib, 2 * l + 1));
+                    let y = &mut y[32 * ib + 8 * l..32 * ib + 8 * (l + 1)];
+                    for j in 0..4 {
+                        y[j] = db * apply_sign(grid_byte(g1, j), signs, j) as f32;
+                        y[j + 4] = db * apply_sign(grid_byte(g2, j), signs, j + 4) as f32;
+                    }
+                }
+            }
+        }
+    }
+}
+
+#[inline(always)]
+fn iq1_value(g: u64, j: usize) -> i32 {
+    ((g >> (8 * j)) as u8 as i8) as i32
+}
+
+impl BlockIQ1S {
+    fn quantize(xs: &[f32], ys: &mut [Self], imatrix: Option<&[f32]>, n_per_row: usize) {
+        let grid = iq1s_grid();
+        let shifts = [IQ1S_DELTA, -IQ1S_DELTA];
+        let imatrix = imatrix_for_blocks(imatrix, n_per_row, QK_K);
+        for (blk_idx, (block, x)) in group_for_quantization(xs, ys).into_iter().enumerate() {
+            let mut ws = [0f32; QK_K];
+            quant_weights(x, imatrix(blk_idx), &mut ws);
+            let mut fits = Vec::with_capacity(QK_K / 32);
+            for ib in 0..QK_K / 32 {
+                let r = 32 * ib..32 * (ib + 1);
+                let top = 1. + IQ1S_DELTA;
+                fits.push(fit_grid(
+                    &x[r.clone()],
+                    &ws[r],
+                    grid,
+                    &shifts,
+                    32,
+                    top,
+                    (7, 0.05),
+                ))
+            }
+            let scales: Vec<f32> = fits.iter().map(|f| f.scale).collect();
+            let mut ls = [0u8; QK_K / 32];
+            block.d = quantize_scales(&scales, (1., 2., 7), &mut ls);
+            let d = block.d.to_f32();
+            for (ib, fit) in fits.iter_mut().enumerate() {
+                let r = 32 * ib..32 * (ib + 1);
+                if fit.scale > 0. && d > 0. {
+                    let dl = d * (2. * ls[ib] as f32 + 1.);
+                    *fit = fit_points(&x[r.clone()], &ws[r], grid, &shifts, 32, dl);
+                }
+                let mut qh = (ls[ib] as u16) << 12;
+                if fit.shifts[0] == 1 {
+                    qh |= 0x8000
+                }
+                for (l, &p) in fit.points.iter().enumerate() {
+                    block.qs[4 * ib + l] = p as u8;
+                    qh |= ((p >> 8) & 7) << (3 * l);
+                }
+                block.qh[ib] = qh;
+            }
+        }
+    }
+}
+
+impl GgmlType for BlockIQ1S {
+    const DTYPE: GgmlDType = GgmlDType::IQ1S;
+    const BLCK_SIZE: usize = QK_K;
+    type VecDotType = BlockQ8K;
+
+    #[allow(unreachable_code)]
+    fn vec_dot(n: usize, xs: &[Self], ys: &[Self::VecDotType]) -> f32 {
+        #[cfg(target_feature = "avx2")]
+        return super::avx::vec_dot_iq1s_q8k(n, xs, ys);
+
+        Self::vec_dot_unopt(n, xs, ys)
+    }
+
+    fn vec_dot_unopt(n: usize, xs: &[Self], ys: &[Self::VecDotType]) -> f32 {
+        debug_assert!(
+            n.is_multiple_of(QK_K),
+            "vec_dot_iq1s_q8k: {n} is not divisible by {QK_K}"
+        );
+        let mut sumf = 0f32;
+        for (x, y) in xs.iter().zip(ys.iter()) {
+            let mut sum = 0f32;
+            for ib in 0..QK_K / 32 {
+                let qh = x.qh[ib];
+                let ls = 2 * ((qh >> 12) & 7) as i32 + 1;
+                let delta = if qh & 0x8000 != 0 {
+                    -IQ1S_DELTA
+                } else {
+                    IQ1S_DELTA
+                };
+                let (mut sumi, mut sumy) = (0i32, 0i32);
+                for l in 0..4 {
+                    let idx = x.qs[4 * ib + l] as usize | (((qh >> (3 * l)) & 7) as usize) << 8;
+                    let g = IQ1S_GRID[idx];
+                    for j in 0..8 {
+                        let q8 = y.qs[32 * ib + 8 * l + j] as i32;
+                        sumi += iq1_value(g, j) * q8;
+                        sumy += q8;
+                    }
+                }
+                sum += ls as f32 * (sumi as f32 + delta * sumy as f32)
+            }
+            sumf += x.d.to_f32() * y.d * sum
+        }
+        sumf
+    }
+
+    fn from_float(xs: &[f32], ys: &mut [Self]) {
+        Self::quantize(xs, ys, None, xs.len())
+    }
+
+    fn from_float_imatrix(xs: &[f32], ys: &mut [Self], imatrix_weights: &[f32], n_per_row: usize) {
+        Self::quantize(xs, ys, Some(imatrix_weights), n_per_row)
+    }
+
+    fn to_float(xs: &[Self], ys: &mut [f32]) {
+        for (block, y) in group_for_dequantization(xs, ys) {
+            let d = block.d.to_f32();
+            for ib in 0..QK_K / 32 {
+                let qh = block.qh[ib];
+                let dl = d * (2 * ((qh >> 12) & 7) + 1) as f32;
+                let delta = if qh & 0x8000 != 0 {
+                    -IQ1S_DELTA
+                } else {
+                    IQ1S_DELTA
+                };
+                for l in 0..4 {
+                    let idx = block.qs[4 * ib + l] as usize | (((qh >> (3 * l)) & 7) as usize) << 8;
+                    let g = IQ1S_GRID[idx];
+                    for j in 0..8 {
+                        y[32 * ib + 8 * l + j] = dl * (iq1_value(g, j) as f32 + delta)
+                    }
+                }
+            }
+        }
+    }
+}
+
+impl BlockIQ1M {
+    pub(crate) fn sc(&self) -> [u16; 4] {
+        let mut sc = [0u16; 4];
+        for (i, sc) in sc.iter_mut().enumerate() {
+            *sc = u16::from_le_bytes([self.scales[2 * i], self.scales[2 * i + 1]])
+        }
+        sc
+    }
+
+    pub(crate) fn d(sc: &[u16; 4]) -> f32 {
+        let d =
+            (sc[0] >> 12) | ((sc[1] >> 8) & 0x00f0) | ((sc[2] >> 4) & 0x0f00) | (sc[3] & 0xf000);
+        f16::from_bits(d).to_f32()
+    }
+
+    /// Returns the grid index and delta for the `l`-th group of 8 values in sub-block `ib`.
+    pub(crate) fn group(&self, ib: usize, l: usize) -> (usize, f32) {
+        let qh = self.qh[2 * ib + l / 2] >> (4 * (l % 2));
+        let idx = self.qs[4 * ib + l] as usize | ((qh & 7) as usize) << 8;
+        let delta = if qh & 8 != 0 { -IQ1M_DELTA } else { IQ1M_DELTA };
+        (idx, delta)
+    }
+
+    fn quantize(xs: &[f32], ys: &mut [Self], imatrix: Option<&[f32]>, n_per_row: usize) {
+        let grid = iq1s_grid();
+        let shifts = [IQ1M_DELTA, -IQ1M_DELTA];
+        let imatrix = imatrix_for_blocks(imatrix, n_per_row, QK_K);
+        for (blk_idx, (block, x)) in group_for_quantization(xs, ys).into_iter().enumerate() {
+            let mut ws = [0f32; QK_K];
+            quant_weights(x, imatrix(blk_idx), &mut ws);
+            let mut fits = Vec::with_capacity(QK_K / 16);
+            for ib in 0..QK_K / 16 {
+                let r = 16 * ib..16 * (ib + 1);
+                let top = 1. + IQ1M_DELTA;
+                fits.push(fit_grid(
+                    &x[r.clone()],
+                    &ws[r],
+                    grid,
+                    &shifts,
+                    8,
+                    top,
+                    (7, 0.05),
+                ))
+            }
+            let scales: Vec<f32> = fits.iter().map(|f| f.scale).collect();
+            let mut ls = [0u8; QK_K / 16];
+            let d = quantize_scales(&scales, (1., 2., 7), &mut ls);
+            let mut sc = [0u16; 4];
+            block.qh = [0; QK_K / 16];
+            for (i, fit) in fits.iter_mut().enumerate() {
+                let r = 16 * i..16 * (i + 1);
+                if fit.scale > 0. && d.to_f32() > 0. {
+                    let dl = d.to_f32() * (2. * ls[i] as f32 + 1.);
+                    *fit = fit_points(&x[r.clone()], &ws[r], grid, &shifts, 8, dl);
+                }
+                // Sub-block i covers the groups 2 * i and 2 * i + 1.
+                for (k, &p) in fit.points.iter().enumerate() {
+                    let g = 2 * i + k;
+                    block.qs[g] = p as u8;
+                    let mut h = (p >> 8) as u8 & 7;
+                    if fit.shifts[k] == 1 {
+                        h |= 8
+                    }
+                    block.qh[g / 2] |= h << (4 * (g % 2));
+                }
+                sc[i / 4] |= (ls[i] as u16) << (3 * (i % 4));
+            }
+            let d = d.to_bits();
+            for (i, sc) in sc.iter_mut().enumerate() {
+                *sc |= ((d >> (4 * i)) & 0xf) << 12
+            }
+            for (i, sc) in sc.iter().enumerate() {
+                block.scales[2 * i..2 * (i + 1)].copy_from_slice(&sc.to_le_bytes())
+            }
+        }
+    }
+}
+
+impl GgmlType for BlockIQ1M {
+    const DTYPE: GgmlDType = GgmlDType::IQ1M;
+    const BLCK_SIZE: usize = QK_K;
+    type VecDotType = BlockQ8K;
+
+    #[allow(unreachable_code)]
+    fn vec_dot(n: usize, xs: &[Self], ys: &[Self::VecDotType]) -> f32 {
+        #[cfg(target_feature = "avx2")]
+        return super::avx::vec_dot_iq1m_q8k(n, xs, ys);
+
+        Self::vec_dot_unopt(n, xs, ys)
+    }
+
+    fn vec_dot_unopt(n: usize, xs: &[Self], ys: &[Self::VecDotType]) -> f32 {
+        debug_assert!(
+            n.is_multiple_of(QK_K),
+            "vec_dot_iq1m_q8k: {n} is not divisible by {QK_K}"
+        );
+        let mut sumf = 0f32;
+        for (x, y) in xs.iter().zip(ys.iter()) {
+            let sc = x.sc();
+            let mut sum = 0f32;
+            for ib in 0..QK_K / 32 {
+                for half in 0..2 {
+                    let ls = 2 * ((sc[ib / 2] >> (6 * (ib % 2) + 3 * half)) & 7) as i32 + 1;
+                    let mut s = 0f32;
+                    for l in 2 * half..2 * half + 2 {
+                        let (idx, delta) = x.group(ib, l);
+                        let g = IQ1S_GRID[idx];
+                        let (mut sumi, mut sumy) = (0i32, 0i32);
+                        for j in 0..8 {
+                            let q8 = y.qs[32 * ib + 8 * l + j] as i32;
+                            sumi += iq1_value(g, j) * q8;
+                            sumy += q8;
+                        }
+                        s += sumi as f32 + delta * sumy as f32
+                    }
+                    sum += ls as f32 * s
+                }
+            }
+            sumf += Self::d(&sc) * y.d * sum
+        }
+        sumf
+    }
+
+    fn from_float(xs: &[f32], ys: &mut [Self]) {
+        Self::quantize(xs, ys, None, xs.len())
+    }
+
+    fn from_float_imatrix(xs: &[f32], ys: &mut [Self], imatrix_weights: &[f32], n_per_row: usize) {
+        Self::quantize(xs, ys, Some(imatrix_weights), n_per_row)
+    }
+
+    fn to_float(xs: &[Self], ys: &mut [f32]) {
+        for (block, y) in group_for_dequantization(xs, ys) {
+            let sc = block.sc();
+            let d = Self::d(&sc);
+            for ib in 0..QK_K / 32 {
+                for l in 0..4 {
+                    let ls = (sc[ib / 2] >> (6 * (ib % 2) + 3 * (l / 2))) & 7;
+                    let dl = d * (2 * ls + 1) as f32;
+                    let (idx, delta) = block.group(ib, l);
+                    let g = IQ1S_GRID[idx];
+                    for j in 0..8 {
+                        y[32 * ib + 8 * l + j] = dl * (iq1_value(g, j) as f32 + delta)
+                    }
+                }
+            }
+        }
+    }
+}
+
+const POW3: [u8; 6] = [1, 3, 9, 27, 81, 243];
+
+/// Extracts the `n`-th ternary digit, starting from the most significant one.
+#[inline(always)]
+pub(crate) fn tq1_trit(q: u8, n: usize) -> i32 {
+    let q = q.wrapping_mul(POW3[n]);
+    ((q as u16 * 3) >> 8) as i32
+}
+
+impl GgmlType for BlockTQ1_0 {
+    const DTYPE: GgmlDType = GgmlDType::TQ1_0;
+    const BLCK_SIZE: usize = QK_K;
+    type VecDotType = BlockQ8K;
+
+    #[allow(unreachable_code)]
+    fn vec_dot(n: usize, xs: &[Self], ys: &[Self::VecDotType]) -> f32 {
+        #[cfg(target_feature = "avx2")]
+        return super::avx::vec_dot_tq1_0_q8k(n, xs, ys);
+
+        Self::vec_dot_unopt(n, xs, ys)
+    }
+
+    fn vec_dot_unopt(n: usize, xs: &[Self], ys: &[Self::VecDotType]) -> f32 {
+        debug_assert!(
+            n.is_multiple_of(QK_K),
+            "vec_dot_tq1_0_q8k: {n} is not divisible by {QK_K}"
+        );
+        let mut ternary = [0i8; QK_K];
+        let mut sumf = 0f32;
+        for (x, y) in xs.iter().zip(ys.iter()) {
+            x.unpack(&mut ternary);
+            let sumi = ternary
+                .iter()
+                .zip(y.qs.iter())
+                .map(|(&t, &q)| t as i32 * q as i32)
+                .sum::<i32>();
+            sumf += sumi as f32 * x.d.to_f32() * y.d
+        }
+        sumf
+    }
+
+    fn from_float(xs: &[f32], ys: &mut [Self]) {
+        for (block, x) in group_for_quantization(xs, ys) {
+            let amax = x.iter().fold(0f32, |acc, x| acc.max(x.abs()));
+            let id = if amax > 0. { 1. / amax } else { 0. };
+            let trit = |v: f32| ((v * id).round() as i32 + 1).clamp(0, 2) as u16;
+            let mut x = x;
+            // The first 32 bytes hold 5 * 32 values, the next 16 bytes 5 * 16 values.
+            let mut j = 0;
+            for &(width, count) in [(32, 1), (16, 1)].iter() {
+                for _ in 0..count {
+                    for m in 0..width {
+                        let mut q = 0u16;
+                        for n in 0..5 {
+                            q = q * 3 + trit(x[m + n * width]);
+                        }
+                        // Ceiling division so that the value can be recovered by multiplications.
+                        block.qs[j + m] = ((q * 256).div_ceil(243)) as u8;
+                    }
+                    j += width;
+                    x = &x[5 * width..];
+                }
+            }
+            for j in 0..QK_K / 64 {
+                let mut q = 0u16;
+                for m in 0..4 {
+                    q = q * 3 + trit(x[j + m * 4]);
+                }
+                // Shift the first value to the most significant trit.
+                q *= 3;
+                block.qh[j] = ((q * 256).div_ceil(243)) as u8;
+            }
+            block.d = f16::from_f32(amax);
+        }
+    }
+
+    fn from_float_imatrix(xs: &[f32], ys: &mut [Self], _: &[f32], _: usize) {
+        Self::from_float(xs, ys)
+    }
+
+    fn to_float(xs: &[Self], ys: &mut [f32]) {
+        let mut ternary = [0i8; QK_K];
+        for (block, y) in group_for_dequantization(xs, ys) {
+            block.unpack(&mut ternary);
+            let d = block.d.to_f32();
+            for (y, &t) in y.iter_mut().zip(ternary.iter()) {
+                *y = d * t as f32
+            }
+        }
+    }
+}
+
+impl BlockTQ1_0 {
+    fn unpack(&self, ys: &mut [i8; QK_K]) {
+        let mut k = 0;
+        for (start, width) in [(0, 32), (32, 16)] {
+            for n in 0..5 {
+                for m in 0..width {
+                    ys[k] = (tq1_trit(self.qs[start + m], n) - 1) as i8;
+                    k += 1;
+                }
+            }
+        }
+        for n in 0..4 {
+            for j in 0..QK_K / 64 {
+                ys[k] = (tq1_trit(self.qh[j], n) - 1) as i8;
+                k += 1;
+            }
+        }
+    }
+}
+
+impl GgmlType for BlockTQ2_0 {
+    const DTYPE: GgmlDType = GgmlDType::TQ2_0;
+    const BLCK_SIZE: usize = QK_K;
+    type VecDotType = BlockQ8K;
+
+    #[allow(unreachable_code)]
+    fn vec_dot(n: usize, xs: &[Self], ys: &[Self::VecDotType]) -> f32 {
+        #[cfg(target_feature = "avx2")]
+        return super::avx::vec_dot_tq2_0_q8k(n, xs, ys);
+
+        Self::vec_dot_unopt(n, xs, ys)
+    }
+
+    fn vec_dot_unopt(n: usize, xs: &[Self], ys: &[Self::VecDotType]) -> f32 {
+        debug_assert!(
+            n.is_multiple_of(QK_K),
+            "vec_dot_tq2_0_q8k: {n} is not divisible by {QK_K}"
+        );
+        let mut sumf = 0f32;
+        for (x, y) in xs.iter().zip(ys.iter()) {
+            let mut sumi = 0i32;
+            for j in (0..QK_K / 4).step_by(32) {
+                for l in 0..4 {
+                    for k in 0..32 {
+                        let q = ((x.qs[j + k] >> (2 * l)) & 3) as i32 - 1;
+                        sumi += q * y.qs[4 * j + 32 * l + k] as i32
+                    }
+                }
+            }
+            sumf += sumi as f32 * x.d.to_f32() * y.d
+        }
+        sumf
+    }
+
+    fn from_float(xs: &[f32], ys: &mut [Self]) {
+        for (block, x) in group_for_quantization(xs, ys) {
+            let amax = x.iter().fold(0f32, |acc, x| acc.max(x.abs()));
+            let id = if amax > 0. { 1. / amax } else { 0. };
+            for j in (0..QK_K / 4).step_by(32) {
+                for m in 0..32 {
+                    let mut q = 0u8;
+                    for n in 0..4 {
+                        let v = ((x[4 * j + m + n * 32] * id).round() as i32 + 1).clamp(0, 2);
+                        q |= (v as u8) << (2 * n);
+                    }
+                    block.qs[j + m] = q;
+                }
+            }
+            block.d = f16::from_f32(amax);
+        }
+    }
+
+    fn from_float_imatrix(xs: &[f32], ys: &mut [Self], _: &[f32], _: usize) {
+        Self::from_float(xs, ys)
+    }
+
+    fn to_float(xs: &[Self], ys: &mut [f32]) {
+        for (block, y) in group_for_dequantization(xs, ys) {
+            let d = block.d.to_f32();
+            for j in (0..QK_K / 4).step_by(32) {
+                for l in 0..4 {
+                    for m in 0..32 {
+                        let q = ((block.qs[j + m] >> (2 * l)) & 3) as i32 - 1;
+                        y[4 * j + 32 * l + m] = d * q as f32
+                    }
+                }
+            }
+        }
+    }
+}
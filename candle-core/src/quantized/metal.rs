@@ -105,6 +105,7 @@ impl QMetalStorage {
                 let vec: Vec<crate::quantized::BlockQ8K> = read_to_vec(&buffer, block_len);
                 crate::quantized::BlockQ8K::to_float(&vec, &mut out);
             }
+            dtype => crate::bail!("{dtype:?} is not supported on metal"),
         }
 
         let buffer = self.device.new_buffer_with_data(&out)?;
@@ -229,7 +230,7 @@ impl QMetalStorage {
                 device.device(),
                 &encoder,
                 device.kernels(),
-                self.dtype.into(),
+                self.dtype.try_into()?,
                 (1, 1, n, k),
                 storage.buffer(),
                 (layout.start_offset() + batch_id * k) * storage.dtype().size_in_bytes(),
@@ -313,7 +314,7 @@ impl QMetalStorage {
             device.device(),
             &encoder,
             device.kernels(),
-            self.dtype.into(),
+            self.dtype.try_into()?,
             src0_l.dims(),
             &src0_stride,
             &self.buffer,
@@ -368,9 +369,11 @@ fn read_to_vec<T: Clone>(buffer: &Buffer, n: usize) -> Vec<T> {
     slice.to_vec()
 }
 
-impl From<GgmlDType> for candle_metal_kernels::GgmlDType {
-    fn from(value: GgmlDType) -> Self {
-        match value {
+impl TryFrom<GgmlDType> for candle_metal_kernels::GgmlDType {
+    type Error = crate::Error;
+
+    fn try_from(value: GgmlDType) -> Result<Self> {
+        let dtype = match value {
             GgmlDType::Q4_0 => candle_metal_kernels::GgmlDType::Q4_0,
             GgmlDType::Q4_1 => candle_metal_kernels::GgmlDType::Q4_1,
             GgmlDType::Q5_0 => candle_metal_kernels::GgmlDType::Q5_0,
@@ -386,6 +389,8 @@ impl From<GgmlDType> for candle_metal_kernels::GgmlDType {
             GgmlDType::F16 => candle_metal_kernels::GgmlDType::F16,
             GgmlDType::F32 => candle_metal_kernels::GgmlDType::F32,
             GgmlDType::BF16 => candle_metal_kernels::GgmlDType::F16,
-        }
+            dtype => crate::bail!("{dtype:?} is not supported on metal"),
+        };
+        Ok(dtype)
     }
 }
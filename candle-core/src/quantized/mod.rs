@@ -1,6 +1,7 @@
 use crate::{
     backend::BackendStorage, CpuStorage, DType, Device, Result, Shape, Storage, Tensor, D,
 };
+use iq_quants::*;
 use k_quants::*;
 use std::borrow::Cow;
 
@@ -10,7 +11,10 @@ mod dummy_cuda;
 mod dummy_metal;
 pub mod ggml_file;
 pub mod gguf_file;
+pub mod gptq;
 pub mod imatrix_file;
+mod iq_grids;
+pub mod iq_quants;
 pub mod k_quants;
 #[cfg(feature = "metal")]
 pub mod metal;
@@ -57,6 +61,9 @@ pub struct QTensor {
 
 impl Device {
     fn qzeros(&self, elem_count: usize, dtype: GgmlDType) -> Result<QStorage> {
+        if dtype.is_cpu_only() && !self.is_cpu() {
+            crate::bail!("{dtype:?} is only supported on cpu, got {self:?}")
+        }
         match self {
             Device::Cpu => {
                 let storage = dtype.cpu_zeros(elem_count);
@@ -84,6 +91,9 @@ impl QStorage {
     pub fn from_data(data: Cow<'_, [u8]>, device: &Device, dtype: GgmlDType) -> Result<Self> {
         match device {
             Device::Cpu => Ok(Self::Cpu(dtype.from_data(data))),
+            _ if dtype.is_cpu_only() => {
+                crate::bail!("{dtype:?} is only supported on cpu, got {device:?}")
+            }
             Device::Metal(d) => match dtype {
                 GgmlDType::F32 => metal::load_quantized(d, as_t_slice::<f32>(data)),
                 GgmlDType::F16 => metal::load_quantized(d, as_t_slice::<f16>(data)),
@@ -100,6 +110,7 @@ impl QStorage {
                 GgmlDType::Q6K => metal::load_quantized(d, as_t_slice::<BlockQ6K>(data)),
                 GgmlDType::Q8K => metal::load_quantized(d, as_t_slice::<BlockQ8K>(data)),
                 GgmlDType::BF16 => metal::load_quantized(d, as_t_slice::<bf16>(data)),
+                dtype => crate::bail!("{dtype:?} is not supported on metal"),
             },
             Device::Cuda(d) => match dtype {
                 GgmlDType::F32 => cuda::load_quantized(d, as_t_slice::<f32>(data)),
@@ -117,6 +128,7 @@ impl QStorage {
                 GgmlDType::Q6K => cuda::load_quantized(d, as_t_slice::<BlockQ6K>(data)),
                 GgmlDType::Q8K => cuda::load_quantized(d, as_t_slice::<BlockQ8K>(data)),
                 GgmlDType::BF16 => cuda::load_quantized(d, as_t_slice::<bf16>(data)),
+                dtype => crate::bail!("{dtype:?} is not supported on cuda"),
             },
         }
     }
@@ -267,6 +279,17 @@ pub enum GgmlDType {
     Q5K,
     Q6K,
     Q8K,
+    IQ2XXS,
+    IQ2XS,
+    IQ2S,
+    IQ3XXS,
+    IQ3S,
+    IQ1S,
+    IQ1M,
+    IQ4NL,
+    IQ4XS,
+    TQ1_0,
+    TQ2_0,
 }
 
 impl GgmlDType {
@@ -286,6 +309,17 @@ impl GgmlDType {
             13 => Self::Q5K,
             14 => Self::Q6K,
             15 => Self::Q8K,
+            16 => Self::IQ2XXS,
+            17 => Self::IQ2XS,
+            18 => Self::IQ3XXS,
+            19 => Self::IQ1S,
+            20 => Self::IQ4NL,
+            21 => Self::IQ3S,
+            22 => Self::IQ2S,
+            23 => Self::IQ4XS,
+            29 => Self::IQ1M,
+            34 => Self::TQ1_0,
+            35 => Self::TQ2_0,
             // https://github.com/ggerganov/ggml/blob/29d87fc6676e7ed0cdfdec0804b06001d9c2bb44/include/ggml.h#L389
             30 => Self::BF16,
             _ => crate::bail!("unknown dtype for tensor {u}"),
@@ -309,6 +343,17 @@ impl GgmlDType {
             Self::Q5K => 13,
             Self::Q6K => 14,
             Self::Q8K => 15,
+            Self::IQ2XXS => 16,
+            Self::IQ2XS => 17,
+            Self::IQ3XXS => 18,
+            Self::IQ1S => 19,
+            Self::IQ4NL => 20,
+            Self::IQ3S => 21,
+            Self::IQ2S => 22,
+            Self::IQ4XS => 23,
+            Self::IQ1M => 29,
+            Self::TQ1_0 => 34,
+            Self::TQ2_0 => 35,
             // https://github.com/ggerganov/ggml/blob/29d87fc6676e7ed0cdfdec0804b06001d9c2bb44/include/ggml.h#L389
             Self::BF16 => 30,
         }
@@ -331,6 +376,38 @@ impl GgmlDType {
             Self::Q5K => Box::new(vec![BlockQ5K::zeros(); elem_count / BlockQ5K::BLCK_SIZE]),
             Self::Q6K => Box::new(vec![BlockQ6K::zeros(); elem_count / BlockQ6K::BLCK_SIZE]),
             Self::Q8K => Box::new(vec![BlockQ8K::zeros(); elem_count / BlockQ8K::BLCK_SIZE]),
+            Self::IQ2XXS => Box::new(vec![
+                BlockIQ2XXS::zeros();
+                elem_count / BlockIQ2XXS::BLCK_SIZE
+            ]),
+            Self::IQ2XS => Box::new(vec![
+                BlockIQ2XS::zeros();
+                elem_count / BlockIQ2XS::BLCK_SIZE
+            ]),
+            Self::IQ2S => Box::new(vec![BlockIQ2S::zeros(); elem_count / BlockIQ2S::BLCK_SIZE]),
+            Self::IQ3XXS => Box::new(vec![
+                BlockIQ3XXS::zeros();
+                elem_count / BlockIQ3XXS::BLCK_SIZE
+            ]),
+            Self::IQ3S => Box::new(vec![BlockIQ3S::zeros(); elem_count / BlockIQ3S::BLCK_SIZE]),
+            Self::IQ1S => Box::new(vec![BlockIQ1S::zeros(); elem_count / BlockIQ1S::BLCK_SIZE]),
+            Self::IQ1M => Box::new(vec![BlockIQ1M::zeros(); elem_count / BlockIQ1M::BLCK_SIZE]),
+            Self::IQ4NL => Box::new(vec![
+                BlockIQ4NL::zeros();
+                elem_count / BlockIQ4NL::BLCK_SIZE
+            ]),
+            Self::IQ4XS => Box::new(vec![
+                BlockIQ4XS::zeros();
+                elem_count / BlockIQ4XS::BLCK_SIZE
+            ]),
+            Self::TQ1_0 => Box::new(vec![
+                BlockTQ1_0::zeros();
+                elem_count / BlockTQ1_0::BLCK_SIZE
+            ]),
+            Self::TQ2_0 => Box::new(vec![
+                BlockTQ2_0::zeros();
+                elem_count / BlockTQ2_0::BLCK_SIZE
+            ]),
             Self::BF16 => Box::new(vec![bf16::zeros(); elem_count]),
         }
     }
@@ -351,10 +428,39 @@ impl GgmlDType {
             Self::Q5K => Box::new(as_t_slice::<BlockQ5K>(data).to_vec()),
             Self::Q6K => Box::new(as_t_slice::<BlockQ6K>(data).to_vec()),
             Self::Q8K => Box::new(as_t_slice::<BlockQ8K>(data).to_vec()),
+            Self::IQ2XXS => Box::new(as_t_slice::<BlockIQ2XXS>(data).to_vec()),
+            Self::IQ2XS => Box::new(as_t_slice::<BlockIQ2XS>(data).to_vec()),
+            Self::IQ2S => Box::new(as_t_slice::<BlockIQ2S>(data).to_vec()),
+            Self::IQ3XXS => Box::new(as_t_slice::<BlockIQ3XXS>(data).to_vec()),
+            Self::IQ3S => Box::new(as_t_slice::<BlockIQ3S>(data).to_vec()),
+            Self::IQ1S => Box::new(as_t_slice::<BlockIQ1S>(data).to_vec()),
+            Self::IQ1M => Box::new(as_t_slice::<BlockIQ1M>(data).to_vec()),
+            Self::IQ4NL => Box::new(as_t_slice::<BlockIQ4NL>(data).to_vec()),
+            Self::IQ4XS => Box::new(as_t_slice::<BlockIQ4XS>(data).to_vec()),
+            Self::TQ1_0 => Box::new(as_t_slice::<BlockTQ1_0>(data).to_vec()),
+            Self::TQ2_0 => Box::new(as_t_slice::<BlockTQ2_0>(data).to_vec()),
             Self::BF16 => Box::new(as_t_slice::<bf16>(data).to_vec()),
         }
     }
 
+    /// The importance and ternary quantization types only have cpu kernels for now.
+    pub fn is_cpu_only(&self) -> bool {
+        matches!(
+            self,
+            Self::IQ2XXS
+                | Self::IQ2XS
+                | Self::IQ2S
+                | Self::IQ3XXS
+                | Self::IQ3S
+                | Self::IQ1S
+                | Self::IQ1M
+                | Self::IQ4NL
+                | Self::IQ4XS
+                | Self::TQ1_0
+                | Self::TQ2_0
+        )
+    }
+
     /// The type size for blocks in bytes.
     pub fn type_size(&self) -> usize {
         use k_quants::*;
@@ -374,6 +480,17 @@ impl GgmlDType {
             Self::Q5K => std::mem::size_of::<BlockQ5K>(),
             Self::Q6K => std::mem::size_of::<BlockQ6K>(),
             Self::Q8K => std::mem::size_of::<BlockQ8K>(),
+            Self::IQ2XXS => std::mem::size_of::<BlockIQ2XXS>(),
+            Self::IQ2XS => std::mem::size_of::<BlockIQ2XS>(),
+            Self::IQ2S => std::mem::size_of::<BlockIQ2S>(),
+            Self::IQ3XXS => std::mem::size_of::<BlockIQ3XXS>(),
+            Self::IQ3S => std::mem::size_of::<BlockIQ3S>(),
+            Self::IQ1S => std::mem::size_of::<BlockIQ1S>(),
+            Self::IQ1M => std::mem::size_of::<BlockIQ1M>(),
+            Self::IQ4NL => std::mem::size_of::<BlockIQ4NL>(),
+            Self::IQ4XS => std::mem::size_of::<BlockIQ4XS>(),
+            Self::TQ1_0 => std::mem::size_of::<BlockTQ1_0>(),
+            Self::TQ2_0 => std::mem::size_of::<BlockTQ2_0>(),
         }
     }
 
@@ -388,7 +505,23 @@ impl GgmlDType {
             Self::Q5_1 => k_quants::QK5_1,
             Self::Q8_0 => k_quants::QK8_0,
             Self::Q8_1 => k_quants::QK8_1,
-            Self::Q2K | Self::Q3K | Self::Q4K | Self::Q5K | Self::Q6K | Self::Q8K => k_quants::QK_K,
+            Self::IQ4NL => iq_quants::QK4_NL,
+            Self::Q2K
+            | Self::Q3K
+            | Self::Q4K
+            | Self::Q5K
+            | Self::Q6K
+            | Self::Q8K
+            | Self::IQ2XXS
+            | Self::IQ2XS
+            | Self::IQ2S
+            | Self::IQ3XXS
+            | Self::IQ3S
+            | Self::IQ1S
+            | Self::IQ1M
+            | Self::IQ4XS
+            | Self::TQ1_0
+            | Self::TQ2_0 => k_quants::QK_K,
         }
     }
 }
@@ -695,6 +828,7 @@ pub enum QMatMul {
     QTensor(std::sync::Arc<QTensor>),
     Tensor(Tensor),
     TensorF16(Tensor),
+    Gptq(std::sync::Arc<gptq::GptqTensor>),
 }
 
 thread_local! {
@@ -741,11 +875,25 @@ impl QMatMul {
         Self::from_arc(std::sync::Arc::new(qtensor))
     }
 
+    /// Builds a matmul from GPTQ/AWQ weights. The fused dequantize-matmul kernel is only
+    /// available on the cpu, on other devices the weights are dequantized upfront.
+    pub fn from_gptq(gptq: std::sync::Arc<gptq::GptqTensor>, device: &Device) -> Result<Self> {
+        let t = if !device.is_cpu() || DEQUANTIZE_ALL.with(|b| *b) {
+            Self::Tensor(gptq.dequantize(device)?)
+        } else if DEQUANTIZE_ALL_F16.with(|b| *b) {
+            Self::TensorF16(gptq.dequantize(device)?.to_dtype(DType::F16)?)
+        } else {
+            Self::Gptq(gptq)
+        };
+        Ok(t)
+    }
+
     pub fn dequantize_f16(&self) -> Result<Tensor> {
         match self {
             Self::QTensor(t) => t.dequantize_f16(&t.device()),
             Self::Tensor(t) => t.to_dtype(DType::F16),
             Self::TensorF16(t) => Ok(t.clone()),
+            Self::Gptq(t) => t.dequantize(&Device::Cpu)?.to_dtype(DType::F16),
         }
     }
 
@@ -876,6 +1024,13 @@ impl crate::Module for QMatMul {
                 };
                 xs.to_dtype(DType::F16)?.matmul(&w)?.to_dtype(in_dtype)
             }
+            Self::Gptq(t) => {
+                let in_dtype = xs.dtype();
+                xs.to_dtype(DType::F32)?
+                    .contiguous()?
+                    .apply_op1_no_bwd(t.as_ref())?
+                    .to_dtype(in_dtype)
+            }
         }
     }
 }
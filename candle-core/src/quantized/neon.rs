@@ -1,3 +1,5 @@
+use super::iq_grids::KVALUES_IQ4NL;
+use super::iq_quants::{BlockIQ4NL, BlockIQ4XS};
 use super::k_quants::{
     BlockQ2K, BlockQ3K, BlockQ4K, BlockQ4_0, BlockQ5K, BlockQ6K, BlockQ8K, BlockQ8_0, QK8_0, QK_K,
 };
@@ -94,6 +96,56 @@ pub(crate) fn vec_dot_q8_0_q8_0(n: usize, xs: &[BlockQ8_0], ys: &[BlockQ8_0]) ->
     }
 }
 
+#[inline(always)]
+pub(crate) fn vec_dot_iq4nl_q8_0(n: usize, xs: &[BlockIQ4NL], ys: &[BlockQ8_0]) -> f32 {
+    debug_assert!(
+        n.is_multiple_of(QK8_0),
+        "vec_dot_iq4nl_q8_0: {n} is not divisible by {QK8_0}"
+    );
+    let mut sumf = 0f32;
+    unsafe {
+        let values = vld1q_s8(KVALUES_IQ4NL.as_ptr());
+        let m4b = vdupq_n_u8(0x0F);
+        for (x, y) in xs.iter().zip(ys.iter()) {
+            let q4 = vld1q_u8(x.qs.as_ptr());
+            let q4l = vqtbl1q_s8(values, vandq_u8(q4, m4b));
+            let q4h = vqtbl1q_s8(values, vshrq_n_u8(q4, 4));
+            let q8l = vld1q_s8(y.qs.as_ptr());
+            let q8h = vld1q_s8(y.qs.as_ptr().add(16));
+            let p = vaddq_s32(vdotq_s32(q4l, q8l), vdotq_s32(q4h, q8h));
+            sumf += vaddvq_s32(p) as f32 * x.d.to_f32() * y.d.to_f32()
+        }
+    }
+    sumf
+}
+
+#[inline(always)]
+pub(crate) fn vec_dot_iq4xs_q8k(n: usize, xs: &[BlockIQ4XS], ys: &[BlockQ8K]) -> f32 {
+    debug_assert!(
+        n.is_multiple_of(QK_K),
+        "vec_dot_iq4xs_q8k: {n} is not divisible by {QK_K}"
+    );
+    let mut sumf = 0f32;
+    unsafe {
+        let values = vld1q_s8(KVALUES_IQ4NL.as_ptr());
+        let m4b = vdupq_n_u8(0x0F);
+        for (x, y) in xs.iter().zip(ys.iter()) {
+            let mut sumi = 0i32;
+            for ib in 0..QK_K / 32 {
+                let q4 = vld1q_u8(x.qs.as_ptr().add(16 * ib));
+                let q4l = vqtbl1q_s8(values, vandq_u8(q4, m4b));
+                let q4h = vqtbl1q_s8(values, vshrq_n_u8(q4, 4));
+                let q8l = vld1q_s8(y.qs.as_ptr().add(32 * ib));
+                let q8h = vld1q_s8(y.qs.as_ptr().add(32 * ib + 16));
+                let p = vaddq_s32(vdotq_s32(q4l, q8l), vdotq_s32(q4h, q8h));
+                sumi += vaddvq_s32(p) * x.scale(ib)
+            }
+            sumf += sumi as f32 * x.d.to_f32() * y.d
+        }
+    }
+    sumf
+}
+
 #[inline(always)]
 pub(crate) fn vec_dot_q8k_q8k(n: usize, xs: &[BlockQ8K], ys: &[BlockQ8K]) -> f32 {
     debug_assert!(
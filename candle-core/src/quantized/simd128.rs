@@ -1,3 +1,5 @@
+use super::iq_grids::KVALUES_IQ4NL;
+use super::iq_quants::{BlockIQ4NL, BlockIQ4XS};
 use super::k_quants::{BlockQ2K, BlockQ4K, BlockQ4_0, BlockQ6K, BlockQ8K, BlockQ8_0, QK8_0, QK_K};
 use byteorder::{ByteOrder, LittleEndian};
 use half::f16;
@@ -416,3 +418,74 @@ pub(crate) fn vec_dot_q8k_q8k(n: usize, xs: &[BlockQ8K], ys: &[BlockQ8K]) -> f32
         res
     }
 }
+
+/// Dot product of 16 iq4 values, given as indexes in `KVALUES_IQ4NL`, with 16 int8 values.
+#[inline(always)]
+unsafe fn iq4nl_dot_16(values: v128, q4: v128, q8: *const i8) -> v128 {
+    let q4 = i8x16_swizzle(values, q4);
+    let lo = i32x4_dot_i16x8(i16x8_extend_low_i8x16(q4), i16x8_load_extend_i8x8(q8));
+    let hi = i32x4_dot_i16x8(
+        i16x8_extend_high_i8x16(q4),
+        i16x8_load_extend_i8x8(q8.add(8)),
+    );
+    i32x4_add(lo, hi)
+}
+
+#[inline(always)]
+unsafe fn i32x4_hsum(v: v128) -> i32 {
+    i32x4_extract_lane::<0>(v)
+        + i32x4_extract_lane::<1>(v)
+        + i32x4_extract_lane::<2>(v)
+        + i32x4_extract_lane::<3>(v)
+}
+
+#[inline(always)]
+pub(crate) fn vec_dot_iq4nl_q8_0(n: usize, xs: &[BlockIQ4NL], ys: &[BlockQ8_0]) -> f32 {
+    debug_assert!(
+        n.is_multiple_of(QK8_0),
+        "vec_dot_iq4nl_q8_0: {n} is not divisible by {QK8_0}"
+    );
+    unsafe {
+        let values = v128_load(KVALUES_IQ4NL.as_ptr() as *const v128);
+        let mut sumf = 0f32;
+        for (x, y) in xs.iter().zip(ys.iter()) {
+            let q4 = v128_load(x.qs.as_ptr() as *const v128);
+            let q4l = v128_and(q4, u8x16_splat(0x0F));
+            let q4h = u8x16_shr(q4, 4);
+            let sumi = i32x4_add(
+                iq4nl_dot_16(values, q4l, y.qs.as_ptr()),
+                iq4nl_dot_16(values, q4h, y.qs.as_ptr().add(16)),
+            );
+            sumf += i32x4_hsum(sumi) as f32 * f16::to_f32(x.d) * f16::to_f32(y.d)
+        }
+        sumf
+    }
+}
+
+#[inline(always)]
+pub(crate) fn vec_dot_iq4xs_q8k(n: usize, xs: &[BlockIQ4XS], ys: &[BlockQ8K]) -> f32 {
+    debug_assert!(
+        n.is_multiple_of(QK_K),
+        "vec_dot_iq4xs_q8k: {n} is not divisible by {QK_K}"
+    );
+    unsafe {
+        let values = v128_load(KVALUES_IQ4NL.as_ptr() as *const v128);
+        let mut sumf = 0f32;
+        for (x, y) in xs.iter().zip(ys.iter()) {
+            let mut sumi = 0i32;
+            for ib in 0..QK_K / 32 {
+                let q4 = v128_load(x.qs.as_ptr().add(16 * ib) as *const v128);
+                let q4l = v128_and(q4, u8x16_splat(0x0F));
+                let q4h = u8x16_shr(q4, 4);
+                let q8 = y.qs.as_ptr().add(32 * ib);
+                let p = i32x4_add(
+                    iq4nl_dot_16(values, q4l, q8),
+                    iq4nl_dot_16(values, q4h, q8.add(16)),
+                );
+                sumi += i32x4_hsum(p) * x.scale(ib)
+            }
+            sumf += sumi as f32 * f16::to_f32(x.d) * y.d
+        }
+        sumf
+    }
+}
@@ -0,0 +1,514 @@
+//! Sparse tensors in the COO and CSR formats.
+//!
+//! The sparse tensors are not a storage kind of their own, instead they are made of a couple of
+//! dense tensors: `u32` index tensors and a values tensor. The sparse-dense operations such as
+//! [`CsrTensor::matmul`] or [`CsrTensor::sddmm`] are expressed with `index_select` and
+//! `index_add` so gradients flow through the values and the dense operands.
+//!
+//! ```rust
+//! use candle_core::{sparse::CsrTensor, Device, Tensor};
+//! # fn main() -> candle_core::Result<()> {
+//! let a = Tensor::new(&[[0f32, 2., 0.], [1., 0., 3.]], &Device::Cpu)?;
+//! let a = CsrTensor::from_dense(&a)?;
+//! assert_eq!(a.nnz(), 3);
+//! let b = Tensor::new(&[[1f32], [2.], [3.]], &Device::Cpu)?;
+//! let c = a.matmul(&b)?;
+//! assert_eq!(c.to_vec2::<f32>()?, [[4.], [10.]]);
+//! # Ok(()) }
+//! ```
+use crate::{DType, Device, Result, Shape, Tensor};
+
+fn u32_tensor(vs: Vec<u32>, device: &Device) -> Result<Tensor> {
+    let len = vs.len();
+    Tensor::from_vec(vs, len, device)
+}
+
+/// Returns the flat indexes of the non-zero rows of a 2D tensor.
+fn nonzero_rows(t: &Tensor) -> Result<Vec<u32>> {
+    let nonzero = t.ne(0f64)?.max(1)?.to_vec1::<u8>()?;
+    let rows = nonzero
+        .iter()
+        .enumerate()
+        .filter_map(|(i, &v)| (v != 0).then_some(i as u32))
+        .collect();
+    Ok(rows)
+}
+
+/// A sparse tensor in the coordinate (COO) format.
+///
+/// The first `sparse_dims` dimensions of the tensor are sparse, the remaining ones are dense. The
+/// `indices` tensor has shape `(sparse_dims, nnz)` and the `values` tensor has shape
+/// `(nnz, dense_dims...)`. When all the dimensions are sparse, `values` has shape `(nnz,)`.
+/// Indices can be repeated, in which case the values get summed, use [`CooTensor::coalesce`] to
+/// merge these.
+#[derive(Debug, Clone)]
+pub struct CooTensor {
+    indices: Tensor,
+    values: Tensor,
+    shape: Shape,
+    coalesced: bool,
+}
+
+impl CooTensor {
+    pub fn new<S: Into<Shape>>(indices: Tensor, values: Tensor, shape: S) -> Result<Self> {
+        let shape = shape.into();
+        let (sparse_dims, nnz) = indices.dims2()?;
+        if indices.dtype() != DType::U32 {
+            crate::bail!("sparse indices should be u32, got {:?}", indices.dtype())
+        }
+        if sparse_dims == 0 || sparse_dims > shape.rank() {
+            crate::bail!("unexpected indices shape {indices:?} for sparse shape {shape:?}")
+        }
+        let mut expected_values_dims = vec![nnz];
+        expected_values_dims.extend_from_slice(&shape.dims()[sparse_dims..]);
+        if values.dims() != expected_values_dims {
+            crate::bail!(
+                "unexpected values shape {:?} for sparse shape {shape:?}, expected {expected_values_dims:?}",
+                values.shape()
+            )
+        }
+        let indices = indices.to_device(values.device())?;
+        Ok(Self {
+            indices,
+            values,
+            shape,
+            coalesced: false,
+        })
+    }
+
+    /// Creates a sparse tensor from a dense one, all the dimensions being sparse.
+    pub fn from_dense(t: &Tensor) -> Result<Self> {
+        Self::from_dense_hybrid(t, t.rank())
+    }
+
+    /// Creates a sparse tensor from a dense one where only the first `sparse_dims` dimensions are
+    /// sparse. A slice along the dense dimensions is kept as soon as one of its values is non-zero.
+    pub fn from_dense_hybrid(t: &Tensor, sparse_dims: usize) -> Result<Self> {
+        let dims = t.dims();
+        if sparse_dims == 0 || sparse_dims > dims.len() {
+            crate::bail!("invalid number of sparse dims {sparse_dims} for shape {dims:?}")
+        }
+        let n_sparse = dims[..sparse_dims].iter().product::<usize>();
+        let flat = t.reshape((n_sparse, ()))?;
+        let rows = nonzero_rows(&flat)?;
+        let nnz = rows.len();
+        let mut indices = vec![0u32; sparse_dims * nnz];
+        for (i, &row) in rows.iter().enumerate() {
+            let mut row = row as usize;
+            for d in (0..sparse_dims).rev() {
+                indices[d * nnz + i] = (row % dims[d]) as u32;
+                row /= dims[d];
+            }
+        }
+        let rows = u32_tensor(rows, t.device())?;
+        let mut values_dims = vec![nnz];
+        values_dims.extend_from_slice(&dims[sparse_dims..]);
+        let values = flat.index_select(&rows, 0)?.reshape(values_dims)?;
+        let indices = Tensor::from_vec(indices, (sparse_dims, nnz), t.device())?;
+        Ok(Self {
+            indices,
+            values,
+            shape: t.shape().clone(),
+            coalesced: true,
+        })
+    }
+
+    pub fn indices(&self) -> &Tensor {
+        &self.indices
+    }
+
+    pub fn values(&self) -> &Tensor {
+        &self.values
+    }
+
+    pub fn shape(&self) -> &Shape {
+        &self.shape
+    }
+
+    pub fn dims(&self) -> &[usize] {
+        self.shape.dims()
+    }
+
+    pub fn dtype(&self) -> DType {
+        self.values.dtype()
+    }
+
+    pub fn device(&self) -> &Device {
+        self.values.device()
+    }
+
+    /// The number of specified entries.
+    pub fn nnz(&self) -> usize {
+        self.values.dims()[0]
+    }
+
+    pub fn sparse_dims(&self) -> usize {
+        self.indices.dims()[0]
+    }
+
+    pub fn dense_dims(&self) -> usize {
+        self.shape.rank() - self.sparse_dims()
+    }
+
+    /// Returns true if the indices are known to be sorted and without duplicates.
+    pub fn is_coalesced(&self) -> bool {
+        self.coalesced
+    }
+
+    /// Returns the position of each entry in the flattened sparse dimensions, these can exceed
+    /// the `u32` range for large tensors.
+    fn linear_indices(&self) -> Result<Vec<u64>> {
+        let indices = self.indices.to_vec2::<u32>()?;
+        let mut linear = vec![0u64; self.nnz()];
+        for (d, indices) in indices.iter().enumerate() {
+            let size = self.dims()[d] as u64;
+            for (l, &i) in linear.iter_mut().zip(indices.iter()) {
+                if i as u64 >= size {
+                    crate::bail!("sparse index {i} out of range for dim {d} of size {size}")
+                }
+                *l = match l.checked_mul(size).and_then(|l| l.checked_add(i as u64)) {
+                    Some(l) => l,
+                    None => crate::bail!("sparse dims {:?} are too large", self.dims()),
+                }
+            }
+        }
+        Ok(linear)
+    }
+
+    fn n_sparse(&self) -> usize {
+        self.dims()[..self.sparse_dims()].iter().product()
+    }
+
+    /// Converts the tensor to a dense one, duplicate entries are summed.
+    pub fn to_dense(&self) -> Result<Tensor> {
+        let linear: Vec<i64> = self
+            .linear_indices()?
+            .into_iter()
+            .map(|l| l as i64)
+            .collect();
+        let linear = Tensor::from_vec(linear, self.nnz(), self.device())?;
+        let values = self.values.reshape((self.nnz(), ()))?;
+        let dense_size = values.dim(1)?;
+        Tensor::zeros((self.n_sparse(), dense_size), self.dtype(), self.device())?
+            .index_add(&linear, &values, 0)?
+            .reshape(self.shape.clone())
+    }
+
+    /// Returns an equivalent tensor where the indices are sorted in row-major order and
+    /// duplicate entries have been summed.
+    pub fn coalesce(&self) -> Result<Self> {
+        if self.coalesced {
+            return Ok(self.clone());
+        }
+        let linear = self.linear_indices()?;
+        let mut unique = linear.clone();
+        unique.sort_unstable();
+        unique.dedup();
+        let dst = linear
+            .iter()
+            .map(|l| unique.binary_search(l).unwrap() as u32)
+            .collect::<Vec<_>>();
+        let dst = u32_tensor(dst, self.device())?;
+        let values = self.values.reshape((self.nnz(), ()))?;
+        let dense_size = values.dim(1)?;
+        let mut values_dims = self.values.dims().to_vec();
+        values_dims[0] = unique.len();
+        let values = Tensor::zeros((unique.len(), dense_size), self.dtype(), self.device())?
+            .index_add(&dst, &values, 0)?
+            .reshape(values_dims)?;
+        let sparse_dims = self.sparse_dims();
+        let nnz = unique.len();
+        let mut indices = vec![0u32; sparse_dims * nnz];
+        for (i, &l) in unique.iter().enumerate() {
+            let mut l = l;
+            for d in (0..sparse_dims).rev() {
+                let size = self.dims()[d] as u64;
+                indices[d * nnz + i] = (l % size) as u32;
+                l /= size;
+            }
+        }
+        let indices = Tensor::from_vec(indices, (sparse_dims, nnz), self.device())?;
+        Ok(Self {
+            indices,
+            values,
+            shape: self.shape.clone(),
+            coalesced: true,
+        })
+    }
+
+    /// Converts a 2D sparse tensor to the CSR format.
+    pub fn to_csr(&self) -> Result<CsrTensor> {
+        let (rows, cols) = self.shape.dims2()?;
+        if self.sparse_dims() != 2 {
+            crate::bail!(
+                "to_csr requires two sparse dims, got {}",
+                self.sparse_dims()
+            )
+        }
+        let coo = self.coalesce()?;
+        let row_indices = coo.indices.get(0)?.to_vec1::<u32>()?;
+        let mut crow_indices = vec![0u32; rows + 1];
+        for &r in row_indices.iter() {
+            crow_indices[r as usize + 1] += 1
+        }
+        for r in 0..rows {
+            crow_indices[r + 1] += crow_indices[r]
+        }
+        let crow_indices = u32_tensor(crow_indices, self.device())?;
+        let col_indices = coo.indices.get(1)?;
+        CsrTensor::new(crow_indices, col_indices, coo.values, (rows, cols))
+    }
+
+    /// Sparse-dense matrix multiplication, `self` should be a 2D tensor with two sparse dims
+    /// and `rhs` a 2D dense tensor.
+    pub fn matmul(&self, rhs: &Tensor) -> Result<Tensor> {
+        if self.sparse_dims() != 2 {
+            crate::bail!(
+                "matmul requires two sparse dims, got {}",
+                self.sparse_dims()
+            )
+        }
+        spmm(
+            &self.indices.get(0)?,
+            &self.indices.get(1)?,
+            &self.values,
+            self.shape.dims2()?,
+            rhs,
+        )
+    }
+
+    /// Sampled dense-dense matrix multiplication: returns a sparse tensor with the same
+    /// sparsity pattern as `self` where each value is multiplied by the corresponding entry of
+    /// `lhs.matmul(rhs)`. Only the entries of the pattern are computed.
+    pub fn sddmm(&self, lhs: &Tensor, rhs: &Tensor) -> Result<Self> {
+        if self.sparse_dims() != 2 {
+            crate::bail!("sddmm requires two sparse dims, got {}", self.sparse_dims())
+        }
+        let values = sddmm(
+            &self.indices.get(0)?,
+            &self.indices.get(1)?,
+            &self.values,
+            self.shape.dims2()?,
+            lhs,
+            rhs,
+        )?;
+        Ok(Self {
+            values,
+            ..self.clone()
+        })
+    }
+
+    pub fn to_device(&self, device: &Device) -> Result<Self> {
+        Ok(Self {
+            indices: self.indices.to_device(device)?,
+            values: self.values.to_device(device)?,
+            ..self.clone()
+        })
+    }
+
+    pub fn to_dtype(&self, dtype: DType) -> Result<Self> {
+        Ok(Self {
+            values: self.values.to_dtype(dtype)?,
+            ..self.clone()
+        })
+    }
+}
+
+/// A 2D sparse tensor in the compressed sparse row (CSR) format.
+///
+/// The column indices and values of row `i` are stored in `col_indices` and `values` between
+/// `crow_indices[i]` and `crow_indices[i + 1]`.
+#[derive(Debug, Clone)]
+pub struct CsrTensor {
+    crow_indices: Tensor,
+    col_indices: Tensor,
+    values: Tensor,
+    shape: (usize, usize),
+}
+
+impl CsrTensor {
+    pub fn new<S: Into<Shape>>(
+        crow_indices: Tensor,
+        col_indices: Tensor,
+        values: Tensor,
+        shape: S,
+    ) -> Result<Self> {
+        let shape = shape.into().dims2()?;
+        let crow_len = crow_indices.dims1()?;
+        let nnz = col_indices.dims1()?;
+        if crow_indices.dtype() != DType::U32 || col_indices.dtype() != DType::U32 {
+            crate::bail!("sparse indices should be u32")
+        }
+        if crow_len != shape.0 + 1 {
+            crate::bail!(
+                "crow_indices should have {} elements, got {crow_len}",
+                shape.0 + 1
+            )
+        }
+        if values.dims1()? != nnz {
+            crate::bail!("col_indices and values have different lengths")
+        }
+        let crows = crow_indices.to_vec1::<u32>()?;
+        if crows[0] != 0 || crows.windows(2).any(|w| w[1] < w[0]) {
+            crate::bail!("crow_indices should start at 0 and be non-decreasing, got {crows:?}")
+        }
+        if crows[shape.0] as usize != nnz {
+            crate::bail!(
+                "crow_indices should end with the number of entries {nnz}, got {}",
+                crows[shape.0]
+            )
+        }
+        let device = values.device();
+        Ok(Self {
+            crow_indices: crow_indices.to_device(device)?,
+            col_indices: col_indices.to_device(device)?,
+            values,
+            shape,
+        })
+    }
+
+    /// Creates a CSR tensor from a dense 2D tensor.
+    pub fn from_dense(t: &Tensor) -> Result<Self> {
+        CooTensor::from_dense(t)?.to_csr()
+    }
+
+    pub fn crow_indices(&self) -> &Tensor {
+        &self.crow_indices
+    }
+
+    pub fn col_indices(&self) -> &Tensor {
+        &self.col_indices
+    }
+
+    pub fn values(&self) -> &Tensor {
+        &self.values
+    }
+
+    pub fn dims2(&self) -> (usize, usize) {
+        self.shape
+    }
+
+    pub fn dtype(&self) -> DType {
+        self.values.dtype()
+    }
+
+    pub fn device(&self) -> &Device {
+        self.values.device()
+    }
+
+    /// The number of specified entries.
+    pub fn nnz(&self) -> usize {
+        self.values.dims()[0]
+    }
+
+    /// Returns the row index of each entry.
+    pub fn row_indices(&self) -> Result<Tensor> {
+        let crow_indices = self.crow_indices.to_vec1::<u32>()?;
+        let mut rows = Vec::with_capacity(self.nnz());
+        for (r, w) in crow_indices.windows(2).enumerate() {
+            rows.extend(std::iter::repeat_n(r as u32, (w[1] - w[0]) as usize))
+        }
+        u32_tensor(rows, self.device())
+    }
+
+    pub fn to_coo(&self) -> Result<CooTensor> {
+        let indices = Tensor::stack(&[&self.row_indices()?, &self.col_indices], 0)?;
+        let mut coo = CooTensor::new(indices, self.values.clone(), self.shape)?;
+        coo.coalesced = true;
+        Ok(coo)
+    }
+
+    pub fn to_dense(&self) -> Result<Tensor> {
+        self.to_coo()?.to_dense()
+    }
+
+    /// Sparse-dense matrix multiplication, `rhs` should be a 2D dense tensor.
+    pub fn matmul(&self, rhs: &Tensor) -> Result<Tensor> {
+        spmm(
+            &self.row_indices()?,
+            &self.col_indices,
+            &self.values,
+            self.shape,
+            rhs,
+        )
+    }
+
+    /// Sampled dense-dense matrix multiplication: returns a sparse tensor with the same
+    /// sparsity pattern as `self` where each value is multiplied by the corresponding entry of
+    /// `lhs.matmul(rhs)`. Only the entries of the pattern are computed.
+    pub fn sddmm(&self, lhs: &Tensor, rhs: &Tensor) -> Result<Self> {
+        let values = sddmm(
+            &self.row_indices()?,
+            &self.col_indices,
+            &self.values,
+            self.shape,
+            lhs,
+            rhs,
+        )?;
+        Ok(Self {
+            values,
+            ..self.clone()
+        })
+    }
+
+    pub fn to_device(&self, device: &Device) -> Result<Self> {
+        Ok(Self {
+            crow_indices: self.crow_indices.to_device(device)?,
+            col_indices: self.col_indices.to_device(device)?,
+            values: self.values.to_device(device)?,
+            shape: self.shape,
+        })
+    }
+
+    pub fn to_dtype(&self, dtype: DType) -> Result<Self> {
+        Ok(Self {
+            values: self.values.to_dtype(dtype)?,
+            ..self.clone()
+        })
+    }
+}
+
+fn spmm(
+    rows: &Tensor,
+    cols: &Tensor,
+    values: &Tensor,
+    (m, k): (usize, usize),
+    rhs: &Tensor,
+) -> Result<Tensor> {
+    let (rhs_k, n) = rhs.dims2()?;
+    if rhs_k != k {
+        Err(crate::Error::ShapeMismatchBinaryOp {
+            lhs: (m, k).into(),
+            rhs: rhs.shape().clone(),
+            op: "spmm",
+        }
+        .bt())?
+    }
+    let contributions = rhs
+        .index_select(cols, 0)?
+        .broadcast_mul(&values.unsqueeze(1)?)?;
+    Tensor::zeros((m, n), rhs.dtype(), rhs.device())?.index_add(rows, &contributions, 0)
+}
+
+fn sddmm(
+    rows: &Tensor,
+    cols: &Tensor,
+    values: &Tensor,
+    (m, n): (usize, usize),
+    lhs: &Tensor,
+    rhs: &Tensor,
+) -> Result<Tensor> {
+    let (lhs_m, k) = lhs.dims2()?;
+    let (rhs_k, rhs_n) = rhs.dims2()?;
+    if lhs_m != m || rhs_n != n || rhs_k != k {
+        Err(crate::Error::ShapeMismatchBinaryOp {
+            lhs: lhs.shape().clone(),
+            rhs: rhs.shape().clone(),
+            op: "sddmm",
+        }
+        .bt())?
+    }
+    let lhs = lhs.index_select(rows, 0)?;
+    let rhs = rhs.t()?.contiguous()?.index_select(cols, 0)?;
+    (lhs * rhs)?.sum(1)? * values
+}
@@ -708,6 +708,33 @@ impl Storage {
         Ok(())
     }
 
+    #[allow(clippy::too_many_arguments)]
+    pub(crate) fn scatter_reduce(
+        &mut self,
+        l: &Layout,
+        indexes: &Self,
+        indexes_l: &Layout,
+        source: &Self,
+        source_l: &Layout,
+        d: usize,
+        op: op::ScatterReduceOp,
+        include_self: bool,
+    ) -> Result<()> {
+        self.same_device(indexes, "scatter-reduce")?;
+        self.same_device(source, "scatter-reduce")?;
+        match (self, indexes, source) {
+            (Self::Cpu(s), Self::Cpu(indexes), Self::Cpu(source)) => {
+                s.scatter_reduce_set(l, indexes, indexes_l, source, source_l, d, op, include_self)?;
+            }
+            (s, _, _) => crate::bail!(
+                "scatter-reduce with {} is only supported on cpu, got {:?}",
+                op.name(),
+                s.device().location()
+            ),
+        }
+        Ok(())
+    }
+
     pub(crate) fn index_add(
         &self,
         l: &Layout,
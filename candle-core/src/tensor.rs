@@ -1,7 +1,7 @@
 //! Tensors are N-dimensional matrixes of elements using a single data type.
 #![allow(clippy::redundant_closure_call)]
 use crate::backend::{BackendDevice, BackendStorage};
-use crate::op::{BackpropOp, BinaryOp, CmpOp, Op, ReduceOp, UnaryOp};
+use crate::op::{BackpropOp, BinaryOp, CmpOp, Op, ReduceOp, ScatterReduceOp, UnaryOp};
 use crate::scalar::TensorOrScalar;
 use crate::shape::{Dim, Dims, ShapeWithOneHole};
 use crate::{bail, storage::Storage, DType, Device, Error, Layout, Result, Shape};
@@ -1719,6 +1719,63 @@ impl Tensor {
         Ok(())
     }
 
+    /// Reduces the values from `source` into `self` at the positions given by `indexes` along
+    /// dimension `dim`, similar to PyTorch's `scatter_reduce`.
+    ///
+    /// `indexes` and `source` must have the same shape. Each value `source[..., i, ...]` is
+    /// combined with `self[..., indexes[..., i, ...], ...]` using the reduction `op`. When
+    /// `include_self` is false, the original values of `self` are ignored for the positions that
+    /// receive at least one value. Positions that receive no values are left unchanged.
+    ///
+    /// In the backward pass, the gradient of `Max` and `Min` is split evenly between all the
+    /// values equal to the result.
+    ///
+    /// This is only supported on the cpu for now.
+    ///
+    /// ```rust
+    /// use candle_core::{op::ScatterReduceOp, Device, Tensor};
+    /// let init = Tensor::new(&[1f32, 1., 1.], &Device::Cpu)?;
+    /// let src = Tensor::new(&[3f32, -2., 5., 4.], &Device::Cpu)?;
+    /// let ids = Tensor::new(&[0u32, 0, 2, 2], &Device::Cpu)?;
+    /// let ys = init.scatter_reduce(&ids, &src, 0, ScatterReduceOp::Max, true)?;
+    /// assert_eq!(ys.to_vec1::<f32>()?, [3., 1., 5.]);
+    /// let ys = init.scatter_reduce(&ids, &src, 0, ScatterReduceOp::Mean, false)?;
+    /// assert_eq!(ys.to_vec1::<f32>()?, [0.5, 1., 4.5]);
+    /// # Ok::<(), candle_core::Error>(())
+    /// ```
+    pub fn scatter_reduce<D: Dim>(
+        &self,
+        indexes: &Self,
+        source: &Self,
+        dim: D,
+        op: ScatterReduceOp,
+        include_self: bool,
+    ) -> Result<Self> {
+        let dim = dim.to_index(self.shape(), "scatter-reduce")?;
+        self.scatter_checks(indexes, source, dim)?;
+        let shape = self.shape();
+        let mut storage = unsafe { self.device().alloc_uninit(shape, self.dtype())? };
+        self.storage()
+            .copy_strided_src(&mut storage, 0, self.layout())?;
+        let layout = Layout::contiguous(shape);
+        let indexes = indexes.contiguous()?;
+        let source = source.contiguous()?;
+        storage.scatter_reduce(
+            &layout,
+            &indexes.storage(),
+            indexes.layout(),
+            &source.storage(),
+            source.layout(),
+            dim,
+            op,
+            include_self,
+        )?;
+        let op = BackpropOp::new3(self, &indexes, &source, |t1, t2, t3| {
+            Op::ScatterReduce(t1, t2, t3, dim, op, include_self)
+        });
+        Ok(from_storage(storage, self.shape(), op, false))
+    }
+
     /// Embeds the values of the `src` tensor into the `self` tensor on the specified dimension.
     pub fn slice_scatter<D: Dim>(&self, src: &Self, dim: D, start: usize) -> Result<Self> {
         let dim = dim.to_index(self.shape(), "slice-scatter")?;
@@ -1839,6 +1896,77 @@ impl Tensor {
         Ok(from_storage(storage, self.shape(), op, false))
     }
 
+    /// Reduces the slices of `source` into `self` at the positions given by `indexes` along
+    /// dimension `dim`, similar to PyTorch's `index_reduce`. This is to [`Tensor::index_add`]
+    /// what [`Tensor::scatter_reduce`] is to [`Tensor::scatter_add`].
+    ///
+    /// `indexes` is a one dimensional tensor with as many elements as `source` has on dimension
+    /// `dim`.
+    pub fn index_reduce<D: Dim>(
+        &self,
+        indexes: &Self,
+        source: &Self,
+        dim: D,
+        op: ScatterReduceOp,
+        include_self: bool,
+    ) -> Result<Self> {
+        let dim = dim.to_index(self.shape(), "index-reduce")?;
+        let indexes_len = indexes.dims1()?;
+        if source.dim(dim)? != indexes_len {
+            Err(Error::ShapeMismatchBinaryOp {
+                op: "index-reduce (ids, source))",
+                lhs: indexes.shape().clone(),
+                rhs: source.shape().clone(),
+            }
+            .bt())?
+        }
+        let mut ids_shape = vec![1; source.rank()];
+        ids_shape[dim] = indexes_len;
+        let indexes = indexes
+            .reshape(ids_shape)?
+            .broadcast_as(source.shape())?
+            .contiguous()?;
+        self.scatter_reduce(&indexes, source, dim, op, include_self)
+    }
+
+    /// Reduces the rows of `self` that belong to the same segment. `segment_ids` is a sorted
+    /// one dimensional tensor with as many elements as `self` has rows, the result has
+    /// `last_segment_id + 1` rows and the rows for segments without elements are set to zero.
+    pub fn segment_reduce(&self, segment_ids: &Self, op: ScatterReduceOp) -> Result<Self> {
+        let n = segment_ids.dims1()?;
+        if self.dim(0)? != n {
+            Err(Error::ShapeMismatchBinaryOp {
+                op: "segment-reduce",
+                lhs: self.shape().clone(),
+                rhs: segment_ids.shape().clone(),
+            }
+            .bt())?
+        }
+        let ids = segment_ids.to_dtype(DType::I64)?.to_vec1::<i64>()?;
+        if ids.windows(2).any(|w| w[0] > w[1]) || ids.first().is_some_and(|&i| i < 0) {
+            crate::bail!("segment ids should be non-negative and sorted")
+        }
+        let num_segments = ids.last().map_or(0, |&i| i as usize + 1);
+        let mut dims = self.dims().to_vec();
+        dims[0] = num_segments;
+        let zeros = Tensor::zeros(dims, self.dtype(), self.device())?;
+        match op {
+            ScatterReduceOp::Sum => zeros.index_add(segment_ids, self, 0),
+            _ => zeros.index_reduce(segment_ids, self, 0, op, false),
+        }
+    }
+
+    /// Sums the rows of `self` that belong to the same segment, see [`Tensor::segment_reduce`].
+    pub fn segment_sum(&self, segment_ids: &Self) -> Result<Self> {
+        self.segment_reduce(segment_ids, ScatterReduceOp::Sum)
+    }
+
+    /// The maximum over the rows of `self` that belong to the same segment, see
+    /// [`Tensor::segment_reduce`].
+    pub fn segment_max(&self, segment_ids: &Self) -> Result<Self> {
+        self.segment_reduce(segment_ids, ScatterReduceOp::Max)
+    }
+
     /// Gather values across the target dimension.
     ///
     /// # Arguments
@@ -0,0 +1,190 @@
+use candle_core::{
+    quantized::{
+        gptq::{GptqConfig, GptqFormat, GptqTensor},
+        QMatMul,
+    },
+    DType, Device, Module, Result, Tensor,
+};
+use rand::prelude::*;
+
+const AWQ_ORDER: [usize; 8] = [0, 2, 4, 6, 1, 3, 5, 7];
+
+struct Reference {
+    weight: Vec<f32>,
+    qweight: Tensor,
+    qzeros: Tensor,
+    scales: Tensor,
+    g_idx: Option<Tensor>,
+}
+
+// Builds random quantized weights together with their packed representation and the reference
+// dequantized weights with shape (out, in).
+fn reference(
+    format: GptqFormat,
+    bits: usize,
+    (in_f, out_f): (usize, usize),
+    group_size: usize,
+    act_order: bool,
+) -> Result<Reference> {
+    let mut rng = StdRng::seed_from_u64(42);
+    let n_groups = in_f / group_size;
+    let max_q = 1u32 << bits;
+    let q: Vec<u32> = (0..in_f * out_f)
+        .map(|_| rng.random_range(0..max_q))
+        .collect();
+    let z: Vec<u32> = (0..n_groups * out_f)
+        .map(|_| rng.random_range(1..max_q))
+        .collect();
+    let s: Vec<f32> = (0..n_groups * out_f)
+        .map(|_| rng.random_range(0.001f32..0.01))
+        .collect();
+    let mut g_idx: Vec<u32> = (0..in_f).map(|k| (k / group_size) as u32).collect();
+    if act_order {
+        g_idx.shuffle(&mut rng)
+    }
+    let pack = 32 / bits;
+    let (qweight, qzeros) = match format {
+        GptqFormat::Gptq | GptqFormat::GptqV2 => {
+            let mut qweight = vec![0u32; in_f / pack * out_f];
+            for k in 0..in_f {
+                for n in 0..out_f {
+                    qweight[(k / pack) * out_f + n] |= q[k * out_f + n] << (bits * (k % pack))
+                }
+            }
+            let offset = (format == GptqFormat::Gptq) as u32;
+            let mut qzeros = vec![0u32; n_groups * out_f / pack];
+            for g in 0..n_groups {
+                for n in 0..out_f {
+                    let z = z[g * out_f + n] - offset;
+                    qzeros[g * out_f / pack + n / pack] |= z << (bits * (n % pack))
+                }
+            }
+            (qweight, qzeros)
+        }
+        GptqFormat::Awq => {
+            let pack_rows = |values: &[u32], rows: usize| {
+                let mut packed = vec![0u32; rows * out_f / 8];
+                for r in 0..rows {
+                    for c in 0..out_f / 8 {
+                        for (i, &o) in AWQ_ORDER.iter().enumerate() {
+                            let v = values[r * out_f + c * 8 + o];
+                            packed[r * out_f / 8 + c] |= v << (4 * i)
+                        }
+                    }
+                }
+                packed
+            };
+            (pack_rows(&q, in_f), pack_rows(&z, n_groups))
+        }
+    };
+    let (qw_shape, qz_shape) = match format {
+        GptqFormat::Gptq | GptqFormat::GptqV2 => ((in_f / pack, out_f), (n_groups, out_f / pack)),
+        GptqFormat::Awq => ((in_f, out_f / 8), (n_groups, out_f / 8)),
+    };
+    let as_i32 = |v: Vec<u32>| v.into_iter().map(|v| v as i32).collect::<Vec<_>>();
+    let qweight = Tensor::from_vec(as_i32(qweight), qw_shape, &Device::Cpu)?;
+    let qzeros = Tensor::from_vec(as_i32(qzeros), qz_shape, &Device::Cpu)?;
+    let scales = Tensor::from_vec(s, (n_groups, out_f), &Device::Cpu)?.to_dtype(DType::F16)?;
+    // The reference weights use the scales after the f16 rounding.
+    let s16 = scales
+        .to_dtype(DType::F32)?
+        .flatten_all()?
+        .to_vec1::<f32>()?;
+    let mut weight = vec![0f32; in_f * out_f];
+    for n in 0..out_f {
+        for k in 0..in_f {
+            let g = g_idx[k] as usize;
+            let (q, z) = (q[k * out_f + n] as f32, z[g * out_f + n] as f32);
+            weight[n * in_f + k] = s16[g * out_f + n] * (q - z)
+        }
+    }
+    let g_idx = if act_order {
+        Some(Tensor::from_vec(as_i32(g_idx), in_f, &Device::Cpu)?)
+    } else {
+        None
+    };
+    Ok(Reference {
+        weight,
+        qweight,
+        qzeros,
+        scales,
+        g_idx,
+    })
+}
+
+fn max_diff(a: &Tensor, b: &Tensor) -> Result<f32> {
+    (a - b)?.abs()?.flatten_all()?.max(0)?.to_scalar::<f32>()
+}
+
+fn check(format: GptqFormat, bits: usize, act_order: bool) -> Result<()> {
+    let (in_f, out_f, group_size) = (256, 320, 64);
+    let r = reference(format, bits, (in_f, out_f), group_size, act_order)?;
+    let config = GptqConfig {
+        format,
+        bits,
+        group_size: Some(group_size),
+    };
+    let w = GptqTensor::new(config, &r.qweight, &r.qzeros, &r.scales, r.g_idx.as_ref())?;
+    assert_eq!(w.shape().dims(), &[out_f, in_f]);
+    assert_eq!(w.has_act_order(), act_order);
+    // The values and zero points stay packed and the scales use f16.
+    let n_groups = in_f / group_size;
+    let packed_size = (in_f + n_groups) * out_f * bits / 8;
+    assert_eq!(
+        w.storage_size_in_bytes(),
+        packed_size + n_groups * out_f * 2 + in_f * 4
+    );
+    let expected = Tensor::from_vec(r.weight, (out_f, in_f), &Device::Cpu)?;
+    let deq = w.dequantize(&Device::Cpu)?;
+    assert!(max_diff(&deq, &expected)? < 1e-6);
+
+    let xs = Tensor::randn(0f32, 1., (2, 3, in_f), &Device::Cpu)?;
+    let mm = QMatMul::from_gptq(std::sync::Arc::new(w), &Device::Cpu)?;
+    assert!(matches!(mm, QMatMul::Gptq(_)));
+    let ys = mm.forward(&xs)?;
+    assert_eq!(ys.dims(), &[2, 3, out_f]);
+    let expected = xs.broadcast_matmul(&expected.t()?)?;
+    assert!(max_diff(&ys, &expected)? < 1e-3);
+
+    let xs = Tensor::zeros((0, in_f), DType::F32, &Device::Cpu)?;
+    assert_eq!(mm.forward(&xs)?.dims(), &[0, out_f]);
+    Ok(())
+}
+
+#[test]
+fn gptq_4bit() -> Result<()> {
+    check(GptqFormat::Gptq, 4, false)
+}
+
+#[test]
+fn gptq_8bit() -> Result<()> {
+    check(GptqFormat::Gptq, 8, false)
+}
+
+#[test]
+fn gptq_v2_4bit() -> Result<()> {
+    check(GptqFormat::GptqV2, 4, false)
+}
+
+#[test]
+fn gptq_act_order() -> Result<()> {
+    check(GptqFormat::Gptq, 4, true)?;
+    check(GptqFormat::Gptq, 8, true)
+}
+
+#[test]
+fn awq_4bit() -> Result<()> {
+    check(GptqFormat::Awq, 4, false)
+}
+
+#[test]
+fn gptq_invalid() -> Result<()> {
+    let r = reference(GptqFormat::Gptq, 4, (64, 32), 32, false)?;
+    let config = GptqConfig::gptq(3, 32);
+    assert!(GptqTensor::new(config, &r.qweight, &r.qzeros, &r.scales, None).is_err());
+    let config = GptqConfig::gptq(4, 16);
+    assert!(GptqTensor::new(config, &r.qweight, &r.qzeros, &r.scales, None).is_err());
+    let config = GptqConfig::gptq(4, 32);
+    assert!(GptqTensor::new(config, &r.qweight, &r.scales, &r.scales, None).is_err());
+    Ok(())
+}
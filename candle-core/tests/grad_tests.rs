@@ -535,6 +535,55 @@ fn test_flip_backprop() -> Result<()> {
     Ok(())
 }
 
+#[test]
+fn scatter_reduce_grad() -> Result<()> {
+    use candle_core::op::ScatterReduceOp;
+    let device = &Device::Cpu;
+
+    // Ties share the gradient.
+    let init = Var::new(&[1f32, 5.], device)?;
+    let src = Var::new(&[3f32, 5., 2.], device)?;
+    let ids = Tensor::new(&[0u32, 1, 1], device)?;
+    let ys = init.scatter_reduce(&ids, &src, 0, ScatterReduceOp::Max, true)?;
+    assert_eq!(ys.to_vec1::<f32>()?, [3., 5.]);
+    let grads = ys.sum_all()?.backward()?;
+    let grad_init = grads.get(&init).context("no grad for init")?;
+    let grad_src = grads.get(&src).context("no grad for src")?;
+    assert_eq!(grad_init.to_vec1::<f32>()?, [0., 0.5]);
+    assert_eq!(grad_src.to_vec1::<f32>()?, [1., 0.5, 0.]);
+
+    let init = Var::new(&[1f32, 1., 7.], device)?;
+    let src = Var::new(&[2f32, 4., 6.], device)?;
+    let ids = Tensor::new(&[0u32, 0, 1], device)?;
+    let ys = init.scatter_reduce(&ids, &src, 0, ScatterReduceOp::Mean, false)?;
+    assert_eq!(ys.to_vec1::<f32>()?, [3., 6., 7.]);
+    let grads = ys.sum_all()?.backward()?;
+    let grad_init = grads.get(&init).context("no grad for init")?;
+    let grad_src = grads.get(&src).context("no grad for src")?;
+    assert_eq!(grad_init.to_vec1::<f32>()?, [0., 0., 1.]);
+    assert_eq!(grad_src.to_vec1::<f32>()?, [0.5, 0.5, 1.]);
+
+    // The gradient of the product is well defined when some values are zero.
+    let init = Var::new(&[2f32, 3., 2.], device)?;
+    let src = Var::new(&[0f32, 4., 5., 0., 0., 3.], device)?;
+    let ids = Tensor::new(&[0u32, 0, 1, 1, 1, 2], device)?;
+    let ys = init.scatter_reduce(&ids, &src, 0, ScatterReduceOp::Prod, true)?;
+    assert_eq!(ys.to_vec1::<f32>()?, [0., 0., 6.]);
+    let grads = ys.sum_all()?.backward()?;
+    let grad_init = grads.get(&init).context("no grad for init")?;
+    let grad_src = grads.get(&src).context("no grad for src")?;
+    assert_eq!(grad_init.to_vec1::<f32>()?, [0., 0., 3.]);
+    assert_eq!(grad_src.to_vec1::<f32>()?, [8., 0., 0., 0., 0., 2.]);
+
+    let data = Var::new(&[[1f32, 6.], [3., 4.], [5., 6.]], device)?;
+    let segment_ids = Tensor::new(&[0u32, 0, 1], device)?;
+    let ys = data.segment_max(&segment_ids)?;
+    let grads = (ys * 2.)?.sum_all()?.backward()?;
+    let grad_data = grads.get(&data).context("no grad for data")?;
+    assert_eq!(grad_data.to_vec2::<f32>()?, [[0., 2.], [2., 0.], [2., 2.]]);
+    Ok(())
+}
+
 test_device!(
     simple_grad,
     simple_grad_cpu,
@@ -5,7 +5,7 @@ use candle_core::{
     test_utils::to_vec2_round,
     DType, Device, IndexOp, Module, Result, Tensor, Var,
 };
-use quantized::{k_quants, GgmlType};
+use quantized::{iq_quants, k_quants, GgmlType};
 use rand::prelude::*;
 
 const GGML_TEST_SIZE: usize = 32 * 128;
@@ -13,7 +13,11 @@ const GGML_TEST_SIZE: usize = 32 * 128;
 const GGML_MAX_QUANTIZATION_TOTAL_ERROR: f32 = 0.002;
 const GGML_MAX_QUANTIZATION_TOTAL_ERROR_2BITS: f32 = 0.0075;
 const GGML_MAX_QUANTIZATION_TOTAL_ERROR_3BITS: f32 = 0.0040;
+const GGML_MAX_QUANTIZATION_TOTAL_ERROR_3BITS_XXS: f32 = 0.0050;
+const GGML_MAX_QUANTIZATION_TOTAL_ERROR_TERNARY: f32 = 0.01;
 const GGML_MAX_DOT_PRODUCT_ERROR: f32 = 0.02;
+const GGML_MAX_DOT_PRODUCT_ERROR_LOWBIT: f32 = 0.04;
+const GGML_MAX_DOT_PRODUCT_ERROR_TERNARY: f32 = 0.15;
 
 fn test_matmul(
     device: &Device,
@@ -1016,8 +1020,8 @@ fn vec_dot_reference(a: &[f32], b: &[f32]) -> f32 {
     a.iter().zip(b).map(|(a, b)| a * b).sum()
 }
 
-/// Returns the error achieved by the GGML matmul unit test.
-fn ggml_reference_matmul_error(dtype: GgmlDType) -> Result<f32> {
+/// Returns the error achieved by the GGML matmul unit test, if any.
+fn ggml_reference_matmul_error(dtype: GgmlDType) -> Option<f32> {
     let err = match dtype {
         GgmlDType::F32 => 0.000000,
         GgmlDType::F16 => 0.000010,
@@ -1036,8 +1040,38 @@ fn ggml_reference_matmul_error(dtype: GgmlDType) -> Result<f32> {
 
         // Not from the ggml repo.
         GgmlDType::Q8K => 0.00065,
+
+        // There is no reference error for the IQ and TQ types, only the maximum dot product
+        // error used by the GGML tests applies.
+        GgmlDType::IQ2XXS
+        | GgmlDType::IQ2XS
+        | GgmlDType::IQ2S
+        | GgmlDType::IQ3XXS
+        | GgmlDType::IQ3S
+        | GgmlDType::IQ1S
+        | GgmlDType::IQ1M
+        | GgmlDType::IQ4NL
+        | GgmlDType::IQ4XS
+        | GgmlDType::TQ1_0
+        | GgmlDType::TQ2_0 => return None,
     };
-    Ok(err)
+    Some(err)
+}
+
+/// The maximum dot product error, the low-bit thresholds are the ones used by the GGML tests.
+fn ggml_max_dot_product_error(dtype: GgmlDType) -> f32 {
+    match dtype {
+        GgmlDType::Q2K
+        | GgmlDType::IQ2S
+        | GgmlDType::IQ3XXS
+        | GgmlDType::IQ3S
+        | GgmlDType::IQ1S
+        | GgmlDType::IQ1M => GGML_MAX_DOT_PRODUCT_ERROR_LOWBIT,
+        // GGML only tests these types with an imatrix, the error is larger without one.
+        GgmlDType::IQ2XXS | GgmlDType::IQ2XS => 2. * GGML_MAX_DOT_PRODUCT_ERROR_LOWBIT,
+        GgmlDType::TQ1_0 | GgmlDType::TQ2_0 => GGML_MAX_DOT_PRODUCT_ERROR_TERNARY,
+        _ => GGML_MAX_DOT_PRODUCT_ERROR,
+    }
 }
 
 /// Similar to the GGML matmul unit test:
@@ -1088,10 +1122,14 @@ fn ggml_matmul_error_test_<T: GgmlType>(a: &[f32], b: &[f32], err_m: f32) -> Res
 
     let verify_result = |result: f32, source: &str| {
         let error = (result - reference_result).abs() / length as f32;
-        let ggml_error = ggml_reference_matmul_error(T::DTYPE)? * err_m;
-        if !error.is_finite() || error > GGML_MAX_DOT_PRODUCT_ERROR {
-            bail!("Dot product with dtype {:?} error {error} exceeds max error {GGML_MAX_DOT_PRODUCT_ERROR}. Source: {source}", T::DTYPE);
+        let max_error = ggml_max_dot_product_error(T::DTYPE);
+        if !error.is_finite() || error > max_error {
+            bail!("Dot product with dtype {:?} error {error} exceeds max error {max_error}. Source: {source}", T::DTYPE);
         }
+        let Some(ggml_error) = ggml_reference_matmul_error(T::DTYPE) else {
+            return Ok(());
+        };
+        let ggml_error = ggml_error * err_m;
         // We diverge slightly due to different rounding behavior / f16 to f32 conversions in GGML
         // => we use a slightly higher error threshold
         const ERROR_LENIENCY: f32 = 0.00001;
@@ -1123,6 +1161,552 @@ fn quantized_mm() -> Result<()> {
     Ok(())
 }
 
+#[test]
+fn quantized_mm_iq() -> Result<()> {
+    ggml_matmul_error_test::<iq_quants::BlockIQ2XXS>()?;
+    ggml_matmul_error_test::<iq_quants::BlockIQ2XS>()?;
+    ggml_matmul_error_test::<iq_quants::BlockIQ2S>()?;
+    ggml_matmul_error_test::<iq_quants::BlockIQ3XXS>()?;
+    ggml_matmul_error_test::<iq_quants::BlockIQ3S>()?;
+    ggml_matmul_error_test::<iq_quants::BlockIQ1S>()?;
+    ggml_matmul_error_test::<iq_quants::BlockIQ1M>()?;
+    ggml_matmul_error_test::<iq_quants::BlockIQ4NL>()?;
+    ggml_matmul_error_test::<iq_quants::BlockIQ4XS>()?;
+    ggml_matmul_error_test::<iq_quants::BlockTQ1_0>()?;
+    ggml_matmul_error_test::<iq_quants::BlockTQ2_0>()?;
+    Ok(())
+}
+
+/// Compares the simd and the generic vec-dot implementations on random values, these exercise
+/// more grid points and sign patterns than the ggml like vectors.
+fn vec_dot_random_test<T: GgmlType>() -> Result<()> {
+    let mut rng = StdRng::seed_from_u64(314159265358979);
+    let length = 16 * T::BLCK_SIZE;
+    let a = (0..length)
+        .map(|_| rng.random::<f32>() - 0.5)
+        .collect::<Vec<_>>();
+    let b = (0..length)
+        .map(|_| rng.random::<f32>() - 0.5)
+        .collect::<Vec<_>>();
+    let mut a_quant = vec![T::zeros(); length / T::BLCK_SIZE];
+    let mut b_quant = vec![T::VecDotType::zeros(); length / T::VecDotType::BLCK_SIZE];
+    T::from_float(&a, &mut a_quant);
+    T::VecDotType::from_float(&b, &mut b_quant);
+    let result = T::vec_dot(length, &a_quant, &b_quant);
+    let result_unopt = T::vec_dot_unopt(length, &a_quant, &b_quant);
+    if (result - result_unopt).abs() > 1e-4 * result_unopt.abs().max(1.) {
+        bail!("{:?}: opt {result} vs unopt {result_unopt}", T::DTYPE)
+    }
+    Ok(())
+}
+
+#[test]
+fn vec_dot_iq() -> Result<()> {
+    vec_dot_random_test::<iq_quants::BlockIQ2XXS>()?;
+    vec_dot_random_test::<iq_quants::BlockIQ2XS>()?;
+    vec_dot_random_test::<iq_quants::BlockIQ2S>()?;
+    vec_dot_random_test::<iq_quants::BlockIQ3XXS>()?;
+    vec_dot_random_test::<iq_quants::BlockIQ3S>()?;
+    vec_dot_random_test::<iq_quants::BlockIQ1S>()?;
+    vec_dot_random_test::<iq_quants::BlockIQ1M>()?;
+    vec_dot_random_test::<iq_quants::BlockIQ4NL>()?;
+    vec_dot_random_test::<iq_quants::BlockIQ4XS>()?;
+    vec_dot_random_test::<iq_quants::BlockTQ1_0>()?;
+    vec_dot_random_test::<iq_quants::BlockTQ2_0>()?;
+    Ok(())
+}
+
+#[test]
+fn quantize_iq() -> Result<()> {
+    let cpu = &Device::Cpu;
+    ggml_quantization_error_test(
+        GgmlDType::IQ2S,
+        cpu,
+        GGML_MAX_QUANTIZATION_TOTAL_ERROR_2BITS,
+    )?;
+    ggml_quantization_error_test(
+        GgmlDType::IQ3XXS,
+        cpu,
+        GGML_MAX_QUANTIZATION_TOTAL_ERROR_3BITS_XXS,
+    )?;
+    ggml_quantization_error_test(
+        GgmlDType::IQ3S,
+        cpu,
+        GGML_MAX_QUANTIZATION_TOTAL_ERROR_3BITS,
+    )?;
+    ggml_quantization_error_test(GgmlDType::IQ4NL, cpu, GGML_MAX_QUANTIZATION_TOTAL_ERROR)?;
+    ggml_quantization_error_test(GgmlDType::IQ4XS, cpu, GGML_MAX_QUANTIZATION_TOTAL_ERROR)?;
+    ggml_quantization_error_test(
+        GgmlDType::TQ1_0,
+        cpu,
+        GGML_MAX_QUANTIZATION_TOTAL_ERROR_TERNARY,
+    )?;
+    ggml_quantization_error_test(
+        GgmlDType::TQ2_0,
+        cpu,
+        GGML_MAX_QUANTIZATION_TOTAL_ERROR_TERNARY,
+    )?;
+    // GGML only tests these types with an imatrix, hence the looser bounds.
+    ggml_quantization_error_test(GgmlDType::IQ2XXS, cpu, 0.0085)?;
+    ggml_quantization_error_test(GgmlDType::IQ2XS, cpu, 0.0080)?;
+    ggml_quantization_error_test(GgmlDType::IQ1S, cpu, 0.011)?;
+    ggml_quantization_error_test(GgmlDType::IQ1M, cpu, 0.011)?;
+
+    // The ternary types store the same values with a different packing.
+    let src = create_ggml_like_vector(0.0);
+    let src = Tensor::from_slice(&src, (GGML_TEST_SIZE,), cpu)?;
+    let tq1 = quantized::QTensor::quantize(&src, GgmlDType::TQ1_0)?.dequantize(cpu)?;
+    let tq2 = quantized::QTensor::quantize(&src, GgmlDType::TQ2_0)?.dequantize(cpu)?;
+    assert_eq!(tq1.to_vec1::<f32>()?, tq2.to_vec1::<f32>()?);
+    Ok(())
+}
+
+// Reference blocks for the IQ and TQ types, the expected values are obtained by running the
+// llama.cpp reference `dequantize_row_*` functions on these blocks. The blocks use grid points
+// from both ends of the grids and every sign pattern bit so that a wrong table entry or bit
+// layout changes the output.
+const IQ2XXS_REF_BLOCK: [u8; 66] = [
+    0x00, 0x40, 0x00, 0x01, 0x02, 0x03, 0x80, 0x84, 0x64, 0x03, 0x03, 0x04, 0x05, 0x06, 0xa4, 0x96,
+    0xed, 0x27, 0x06, 0x07, 0x08, 0x09, 0xc8, 0xa8, 0x76, 0x4c, 0x09, 0x0a, 0x0b, 0x0c, 0xec, 0xba,
+    0xff, 0x60, 0x0c, 0x0d, 0x00, 0x01, 0x90, 0x8c, 0x68, 0x85, 0x01, 0x02, 0x03, 0x04, 0xb4, 0x9e,
+    0xf1, 0xa9, 0xff, 0x05, 0x06, 0x07, 0xd8, 0xb0, 0x7a, 0xce, 0x07, 0x08, 0x09, 0xff, 0xfc, 0x82,
+    0xe3, 0xe2,
+];
+
+const IQ2XXS_REF_VALUES: [f32; 256] = [
+    2., 2., 2., 2., 2., 2., 2., 2., -10.75, 2., 2., -2., 2., 2., 2., 2., 6.25, -6.25, 2., 2., -2.,
+    2., 2., 2., -2., -10.75, 2., -2., -2., 2., 2., 2., 10., 53.75, -10., 10., 10., -10., 10., 10.,
+    -53.75, 53.75, -10., -10., 10., -10., 10., 10., 31.25, -10., -31.25, 10., -10., -10., 10., 10.,
+    -10., -31.25, -31.25, -10., -10., -10., 10., 10., 18., 56.25, 56.25, -18., 18., 18., -18., 18.,
+    -18., 18., 96.75, 18., -18., 18., -18., -18., 96.75, -18., 96.75, -18., -18., 18., -18., 18.,
+    -18., -96.75, 96.75, 18., 18., -18., -18., 18., 26., 139.75, -139.75, -26., 26., -26., -26.,
+    26., -139.75, 139.75, -139.75, 26., -26., -26., -26., -26., 81.25, -26., -26., -81.25, -26.,
+    -26., -26., 26., -26., -81.25, -26., 81.25, 26., 26., 26., -26., 34., 106.25, 34., 106.25,
+    -34., 34., 34., -34., -34., 34., 106.25, -106.25, -34., 34., 34., -34., 34., -34., 34., 34.,
+    34., -34., 34., 34., -182.75, -34., 34., -34., 34., -34., 34., 34., 225.75, 42., -42., 42.,
+    -42., -42., 42., -42., -131.25, 131.25, -42., -42., -42., -42., 42., -42., 42., -225.75, -42.,
+    42., 42., 42., -42., -42., -225.75, -225.75, -42., -42., 42., 42., -42., -42., 50., 156.25,
+    50., -50., -156.25, 268.75, -268.75, -268.75, -156.25, 50., 156.25, 50., 50., -50., -50., -50.,
+    50., -156.25, 156.25, -50., 50., -50., -50., 50., -50., -50., 268.75, 50., -50., -50., -50.,
+    -50., 58., 58., -311.75, -58., -58., -58., -58., -58., -311.75, 58., -311.75, 58., 58., 58.,
+    58., 58., 58., -311.75, -311.75, -58., 58., 58., 58., -58., -58., -181.25, -58., 58., -181.25,
+    311.75, 311.75, 311.75,
+];
+
+const IQ3S_REF_BLOCK: [u8; 110] = [
+    0x00, 0x38, 0x00, 0x05, 0x0a, 0x0f, 0x04, 0x09, 0x0e, 0x03, 0x08, 0x0d, 0x02, 0x07, 0x0c, 0x01,
+    0x06, 0x0b, 0x00, 0x05, 0x0a, 0x0f, 0x04, 0x09, 0x0e, 0x03, 0x08, 0x0d, 0x02, 0xff, 0x0c, 0x01,
+    0x06, 0x0b, 0x00, 0x05, 0x0a, 0x0f, 0x04, 0x09, 0x0e, 0x03, 0x08, 0x0d, 0x02, 0x07, 0x0c, 0x01,
+    0x06, 0x0b, 0xff, 0x05, 0x0a, 0x0f, 0x04, 0x09, 0x0e, 0x03, 0x08, 0x0d, 0x02, 0x07, 0x0c, 0x01,
+    0x06, 0x0b, 0x00, 0x00, 0x00, 0x08, 0x00, 0x00, 0x01, 0x00, 0x0b, 0x30, 0x55, 0x7a, 0x9f, 0xc4,
+    0xe9, 0x0e, 0x33, 0x58, 0x7d, 0xa2, 0xc7, 0xec, 0x11, 0x36, 0x5b, 0x80, 0xa5, 0xca, 0xef, 0x14,
+    0x39, 0x5e, 0x83, 0xa8, 0xcd, 0xf2, 0x17, 0x3c, 0x61, 0x86, 0x12, 0x43, 0x74, 0xa5,
+];
+
+const IQ3S_REF_VALUES: [f32; 256] = [
+    -2.5, -2.5, 2.5, -2.5, 2.5, 7.5, 2.5, 2.5, 2.5, 12.5, 2.5, 2.5, -12.5, -22.5, 2.5, 2.5, -37.5,
+    2.5, -2.5, 2.5, -32.5, 7.5, -2.5, 2.5, 2.5, -22.5, 2.5, -2.5, -27.5, -2.5, -2.5, 2.5, -13.5,
+    -4.5, -1.5, -1.5, -10.5, 10.5, 1.5, -1.5, 7.5, 1.5, -1.5, 1.5, 7.5, 4.5, -1.5, -1.5, -16.5,
+    7.5, 1.5, -1.5, 4.5, -1.5, -1.5, -1.5, 4.5, -4.5, -1.5, -1.5, 4.5, 7.5, 1.5, 1.5, -3.5, -3.5,
+    3.5, 3.5, -3.5, -10.5, 3.5, 3.5, 3.5, 17.5, 3.5, -3.5, -17.5, 31.5, -3.5, 3.5, -52.5, 3.5,
+    -3.5, -3.5, -45.5, -10.5, -3.5, 3.5, 3.5, -31.5, 3.5, 3.5, 38.5, -3.5, 3.5, -3.5, -40.5, -13.5,
+    -4.5, 4.5, 31.5, 31.5, -4.5, -4.5, 22.5, 4.5, -4.5, -4.5, 4.5, -4.5, -67.5, -67.5, -49.5, 22.5,
+    4.5, 4.5, -13.5, 4.5, 4.5, 4.5, 13.5, -13.5, -4.5, 4.5, -13.5, -22.5, 4.5, 4.5, -4.5, -4.5,
+    4.5, -4.5, -4.5, 13.5, -4.5, 4.5, 4.5, 22.5, 4.5, 4.5, 22.5, 40.5, 4.5, -4.5, -67.5, 4.5, -4.5,
+    4.5, 58.5, -13.5, 4.5, -4.5, 4.5, -40.5, 4.5, -4.5, 49.5, 4.5, -4.5, -4.5, -67.5, -22.5, -7.5,
+    -7.5, 52.5, -52.5, -7.5, -7.5, 37.5, 7.5, -7.5, 7.5, -37.5, 22.5, 7.5, 7.5, -82.5, 37.5, 7.5,
+    -7.5, -22.5, -7.5, 7.5, 7.5, 22.5, -22.5, -7.5, -7.5, -22.5, 37.5, -7.5, 7.5, -5.5, -5.5, 82.5,
+    82.5, 5.5, 16.5, 5.5, -5.5, 5.5, 27.5, 5.5, -5.5, 27.5, -49.5, 5.5, -5.5, -82.5, 5.5, -5.5,
+    -5.5, 71.5, 16.5, -5.5, -5.5, 5.5, -49.5, 5.5, 5.5, -60.5, -5.5, -5.5, -5.5, -94.5, -31.5,
+    -10.5, 10.5, -73.5, 73.5, 10.5, 10.5, 52.5, 10.5, -10.5, -10.5, -52.5, -31.5, 10.5, 10.5,
+    -115.5, 52.5, 10.5, 10.5, 31.5, -10.5, -10.5, 10.5, 31.5, -31.5, -10.5, 10.5, 31.5, 52.5, 10.5,
+    -10.5,
+];
+
+const IQ4NL_REF_BLOCK: [u8; 18] = [
+    0x00, 0x38, 0xf0, 0xe1, 0xd2, 0xc3, 0xb4, 0xa5, 0x96, 0x87, 0x78, 0x69, 0x5a, 0x4b, 0x3c, 0x2d,
+    0x1e, 0x0f,
+];
+
+const IQ4NL_REF_VALUES: [f32; 32] = [
+    -63.5, -52., -41.5, -32.5, -24.5, -17.5, -11., -5., 0.5, 6.5, 12.5, 19., 26.5, 34.5, 44.5,
+    56.5, 56.5, 44.5, 34.5, 26.5, 19., 12.5, 6.5, 0.5, -5., -11., -17.5, -24.5, -32.5, -41.5, -52.,
+    -63.5,
+];
+
+const TQ1_0_REF_BLOCK: [u8; 54] = [
+    0x07, 0x3c, 0x71, 0xa6, 0xdb, 0x10, 0x45, 0x7a, 0xaf, 0xe4, 0x19, 0x4e, 0x83, 0xb8, 0xed, 0x22,
+    0x57, 0x8c, 0xc1, 0xf6, 0x2b, 0x60, 0x95, 0xca, 0xff, 0x34, 0x69, 0x9e, 0xd3, 0x08, 0x3d, 0x72,
+    0xa7, 0xdc, 0x11, 0x46, 0x7b, 0xb0, 0xe5, 0x1a, 0x4f, 0x84, 0xb9, 0xee, 0x23, 0x58, 0x8d, 0xc2,
+    0x03, 0x4a, 0x91, 0xd8, 0x00, 0x34,
+];
+
+const TQ1_0_REF_VALUES: [f32; 256] = [
+    -0.25, -0.25, 0., 0., 0.25, -0.25, -0.25, 0., 0.25, 0.25, -0.25, -0.25, 0., 0.25, 0.25, -0.25,
+    0., 0., 0.25, 0.25, -0.25, 0., 0., 0.25, 0.25, -0.25, 0., 0., 0.25, -0.25, -0.25, 0., -0.25,
+    0.25, -0.25, 0.25, 0., -0.25, 0.25, 0., -0.25, 0.25, -0.25, 0.25, 0., -0.25, 0.25, 0., -0.25,
+    0., -0.25, 0.25, 0., -0.25, 0.25, 0., 0.25, 0., -0.25, 0.25, 0., -0.25, 0.25, 0., -0.25, -0.25,
+    0.25, 0.25, 0.25, 0., 0., -0.25, -0.25, -0.25, 0.25, 0.25, 0., 0., -0.25, -0.25, -0.25, 0.25,
+    0.25, 0., 0., 0., -0.25, -0.25, 0.25, 0.25, 0.25, 0., 0., -0.25, -0.25, -0.25, 0.25, -0.25,
+    0.25, 0., -0.25, 0.25, -0.25, 0.25, 0., -0.25, 0., -0.25, 0.25, 0., 0.25, 0., -0.25, 0.25, 0.,
+    0.25, 0., -0.25, 0.25, -0.25, 0.25, 0., -0.25, 0., -0.25, 0.25, 0., -0.25, -0.25, 0.25, 0.25,
+    0., -0.25, -0.25, 0.25, 0., 0., -0.25, 0.25, 0.25, 0., -0.25, 0.25, 0.25, 0., -0.25, -0.25,
+    0.25, 0., 0., -0.25, 0.25, 0.25, 0., -0.25, 0.25, 0.25, 0., -0.25, -0.25, 0., 0.25, -0.25,
+    -0.25, 0., 0.25, 0.25, -0.25, -0.25, 0., 0.25, 0.25, -0.25, 0., 0., 0.25, 0.25, 0., -0.25,
+    0.25, 0., -0.25, 0.25, -0.25, 0.25, 0., -0.25, 0.25, 0., -0.25, 0., -0.25, 0.25, 0.25, 0., 0.,
+    -0.25, -0.25, -0.25, 0.25, 0.25, 0., 0., 0., -0.25, -0.25, 0.25, 0.25, 0., -0.25, 0.25, 0.,
+    0.25, 0., -0.25, 0.25, -0.25, 0.25, 0., -0.25, 0.25, -0.25, 0.25, 0., 0.25, 0., 0., -0.25,
+    0.25, 0.25, 0., -0.25, 0.25, 0.25, 0., -0.25, -0.25, 0.25, 0., 0., -0.25, -0.25, 0., 0.25,
+    -0.25, 0.25, 0.25, 0., -0.25, 0., -0.25, 0., -0.25, 0.25, -0.25, 0.25,
+];
+
+const TQ2_0_REF_BLOCK: [u8; 66] = [
+    0x05, 0x22, 0x3f, 0x5c, 0x79, 0x96, 0xb3, 0xd0, 0xed, 0x0a, 0x27, 0x44, 0x61, 0x7e, 0x9b, 0xb8,
+    0xd5, 0xf2, 0x0f, 0x2c, 0x49, 0x66, 0x83, 0xa0, 0xbd, 0xda, 0xf7, 0x14, 0x31, 0x4e, 0x6b, 0x88,
+    0xa5, 0xc2, 0xdf, 0xfc, 0x19, 0x36, 0x53, 0x70, 0x8d, 0xaa, 0xc7, 0xe4, 0x01, 0x1e, 0x3b, 0x58,
+    0x75, 0x92, 0xaf, 0xcc, 0xe9, 0x06, 0x23, 0x40, 0x5d, 0x7a, 0x97, 0xb4, 0xd1, 0xee, 0x0b, 0x28,
+    0x00, 0x34,
+];
+
+const TQ2_0_REF_VALUES: [f32; 256] = [
+    0., 0.25, 0.5, -0.25, 0., 0.25, 0.5, -0.25, 0., 0.25, 0.5, -0.25, 0., 0.25, 0.5, -0.25, 0.,
+    0.25, 0.5, -0.25, 0., 0.25, 0.5, -0.25, 0., 0.25, 0.5, -0.25, 0., 0.25, 0.5, -0.25, 0., -0.25,
+    0.5, 0.5, 0.25, 0., -0.25, -0.25, 0.5, 0.25, 0., 0., -0.25, 0.5, 0.25, 0.25, 0., -0.25, 0.5,
+    0.5, 0.25, 0., -0.25, -0.25, 0.5, 0.25, 0., 0., -0.25, 0.5, 0.25, 0.25, -0.25, 0.25, 0.5, 0.,
+    0.5, 0., 0.5, 0., 0.25, -0.25, 0.25, -0.25, 0.25, 0.5, 0., 0.5, 0., 0.5, -0.25, 0.25, -0.25,
+    0.25, -0.25, 0.25, 0.5, 0., 0.5, 0., 0.5, -0.25, 0.25, -0.25, -0.25, -0.25, -0.25, 0., 0.,
+    0.25, 0.25, 0.5, 0.5, -0.25, -0.25, 0., 0., 0., 0.25, 0.25, 0.5, 0.5, -0.25, -0.25, 0., 0.,
+    0.25, 0.25, 0.25, 0.5, 0.5, -0.25, -0.25, 0., 0., 0.25, 0., 0.25, 0.5, -0.25, 0., 0.25, 0.5,
+    -0.25, 0., 0.25, 0.5, -0.25, 0., 0.25, 0.5, -0.25, 0., 0.25, 0.5, -0.25, 0., 0.25, 0.5, -0.25,
+    0., 0.25, 0.5, -0.25, 0., 0.25, 0.5, -0.25, 0., -0.25, 0.5, 0.5, 0.25, 0., -0.25, -0.25, 0.5,
+    0.25, 0., 0., -0.25, 0.5, 0.25, 0.25, 0., -0.25, 0.5, 0.5, 0.25, 0., -0.25, -0.25, 0.5, 0.25,
+    0., 0., -0.25, 0.5, 0.25, 0.25, 0.25, -0.25, 0., 0.5, 0., 0.5, 0., 0.5, -0.25, 0.25, -0.25,
+    0.25, -0.25, 0., 0.5, 0., 0.5, 0., 0.25, -0.25, 0.25, -0.25, 0.25, -0.25, 0., 0.5, 0., 0.5, 0.,
+    0.25, -0.25, 0.25, 0.25, 0.5, 0.5, 0.5, -0.25, -0.25, 0., 0., 0.25, 0.25, 0.5, 0.5, -0.25,
+    -0.25, -0.25, 0., 0., 0.25, 0.25, 0.5, 0.5, -0.25, -0.25, 0., 0., 0., 0.25, 0.25, 0.5, 0.5,
+    -0.25, -0.25,
+];
+
+const IQ2XS_REF_BLOCK: [u8; 74] = [
+    0x00, 0x38, 0x00, 0x0a, 0x01, 0x54, 0x02, 0x9e, 0x03, 0xe8, 0x04, 0x32, 0x05, 0x7c, 0x06, 0xc6,
+    0xff, 0x11, 0x00, 0x5a, 0x01, 0xa4, 0x02, 0xee, 0x03, 0x38, 0x04, 0x82, 0x05, 0xcc, 0x06, 0x16,
+    0xff, 0x61, 0x00, 0xaa, 0x01, 0xf4, 0x02, 0x3e, 0x03, 0x88, 0x04, 0xd2, 0x05, 0x1c, 0x06, 0x66,
+    0xff, 0xb1, 0x00, 0xfa, 0x01, 0x44, 0x02, 0x8e, 0x03, 0xd8, 0x04, 0x22, 0x05, 0x6c, 0x06, 0xb6,
+    0xff, 0x01, 0x10, 0x32, 0x54, 0x76, 0x98, 0xba, 0xdc, 0xfe,
+];
+
+const IQ2XS_REF_VALUES: [f32; 256] = [
+    -0.5, 0.5, -0.5, 0.5, 0.5, 0.5, 0.5, 0.5, 2.6875, -0.5, 0.5, -0.5, 0.5, -0.5, 0.5, -0.5,
+    -4.6875, -4.6875, -1.5, -1.5, 1.5, 1.5, -1.5, -1.5, 1.5, 8.0625, -1.5, 1.5, -1.5, -1.5, -1.5,
+    1.5, -13.4375, 13.4375, 2.5, -2.5, -2.5, 2.5, 2.5, -2.5, 7.8125, -2.5, -7.8125, -2.5, -2.5,
+    -2.5, 2.5, -2.5, -3.5, -10.9375, 10.9375, 3.5, 3.5, -3.5, -3.5, 3.5, 18.8125, 18.8125, 18.8125,
+    -18.8125, 18.8125, 18.8125, 18.8125, -18.8125, -4.5, 4.5, -4.5, -4.5, 4.5, -4.5, 4.5, 4.5,
+    24.1875, -4.5, 4.5, 4.5, -4.5, 4.5, -4.5, -4.5, -17.1875, -17.1875, -5.5, 5.5, -5.5, -5.5,
+    -5.5, 5.5, 5.5, 29.5625, -5.5, -5.5, -5.5, 5.5, 5.5, -5.5, -34.9375, 34.9375, 6.5, 6.5, 6.5,
+    6.5, -6.5, 6.5, 20.3125, -6.5, -20.3125, 6.5, 6.5, -6.5, -6.5, 6.5, -7.5, -23.4375, 23.4375,
+    -7.5, 7.5, 7.5, 7.5, -7.5, 40.3125, 40.3125, 40.3125, 40.3125, -40.3125, -40.3125, 40.3125,
+    40.3125, -8.5, 8.5, -8.5, 8.5, -8.5, 8.5, -8.5, 8.5, 45.6875, -8.5, 8.5, -8.5, -8.5, -8.5,
+    -8.5, -8.5, -29.6875, -29.6875, -9.5, -9.5, -9.5, 9.5, 9.5, -9.5, 9.5, 51.0625, -9.5, 9.5, 9.5,
+    9.5, -9.5, 9.5, -56.4375, 56.4375, 10.5, -10.5, 10.5, -10.5, -10.5, 10.5, 32.8125, -10.5,
+    -32.8125, -10.5, 10.5, 10.5, 10.5, -10.5, -11.5, -35.9375, 35.9375, 11.5, -11.5, -11.5, 11.5,
+    11.5, 61.8125, 61.8125, 61.8125, -61.8125, -61.8125, 61.8125, -61.8125, -61.8125, -12.5, 12.5,
+    -12.5, -12.5, -12.5, -12.5, -12.5, 12.5, 67.1875, -12.5, 12.5, 12.5, 12.5, -12.5, 12.5, 12.5,
+    -42.1875, -42.1875, -13.5, 13.5, 13.5, 13.5, -13.5, 13.5, 13.5, 72.5625, -13.5, -13.5, 13.5,
+    -13.5, -13.5, 13.5, -77.9375, 77.9375, 14.5, 14.5, -14.5, 14.5, 14.5, 14.5, 45.3125, -14.5,
+    -45.3125, 14.5, -14.5, -14.5, 14.5, 14.5, -15.5, -48.4375, 48.4375, -15.5, -15.5, 15.5, -15.5,
+    -15.5, 83.3125, 83.3125, 83.3125, 83.3125, 83.3125, 83.3125, 83.3125, 83.3125,
+];
+
+const IQ2S_REF_BLOCK: [u8; 82] = [
+    0x00, 0x38, 0x00, 0x03, 0xff, 0x02, 0x05, 0x01, 0x04, 0x00, 0x03, 0xff, 0x02, 0x05, 0x01, 0x04,
+    0x00, 0x03, 0xff, 0x02, 0x05, 0x01, 0x04, 0x00, 0x03, 0xff, 0x02, 0x05, 0x01, 0x04, 0x00, 0x03,
+    0xff, 0x02, 0x11, 0x6a, 0xc3, 0x1c, 0x75, 0xce, 0x27, 0x80, 0xd9, 0x32, 0x8b, 0xe4, 0x3d, 0x96,
+    0xef, 0x48, 0xa1, 0xfa, 0x53, 0xac, 0x05, 0x5e, 0xb7, 0x10, 0x69, 0xc2, 0x1b, 0x74, 0xcd, 0x26,
+    0x7f, 0xd8, 0x30, 0x00, 0x0c, 0x00, 0x03, 0xc0, 0x00, 0x30, 0x0f, 0x2d, 0x4b, 0x69, 0x87, 0xa5,
+    0xc3, 0xe1,
+];
+
+const IQ2S_REF_VALUES: [f32; 256] = [
+    -15.5, 15.5, 15.5, 15.5, -15.5, 15.5, 15.5, 15.5, 15.5, -83.3125, 15.5, -15.5, 15.5, -15.5,
+    -15.5, 15.5, -2.6875, -2.6875, 2.6875, 2.6875, 2.6875, 2.6875, -2.6875, -2.6875, 1.5625,
+    1.5625, -0.5, -0.5, -0.5, 0.5, 0.5, 0.5, -42.1875, 13.5, -42.1875, 13.5, -13.5, -13.5, -13.5,
+    13.5, 72.5625, -13.5, -13.5, -13.5, 13.5, 13.5, -13.5, -13.5, -13.4375, -13.4375, -2.5, 2.5,
+    2.5, -2.5, 2.5, 2.5, 2.5, 2.5, 2.5, 2.5, 2.5, 2.5, 2.5, -2.5, -11.5, 61.8125, 11.5, -11.5,
+    -11.5, 11.5, -11.5, -11.5, 61.8125, -61.8125, 61.8125, 61.8125, -61.8125, -61.8125, 61.8125,
+    61.8125, -14.0625, -14.0625, 4.5, -4.5, 4.5, 4.5, 4.5, -4.5, 14.0625, 4.5, -14.0625, 4.5, 4.5,
+    -4.5, -4.5, -4.5, -51.0625, 9.5, -9.5, -9.5, -9.5, -9.5, 9.5, 9.5, 51.0625, -51.0625, -9.5,
+    9.5, -9.5, 9.5, 9.5, -9.5, -6.5, -6.5, -6.5, -6.5, 6.5, -6.5, -6.5, -6.5, 6.5, 34.9375, 6.5,
+    -6.5, 6.5, 6.5, -6.5, 6.5, -40.3125, 40.3125, 40.3125, 40.3125, 40.3125, -40.3125, 40.3125,
+    -40.3125, 23.4375, -23.4375, 7.5, -7.5, -7.5, -7.5, -7.5, -7.5, -26.5625, -8.5, 26.5625, 8.5,
+    -8.5, 8.5, -8.5, 8.5, 45.6875, 8.5, -8.5, -8.5, 8.5, -8.5, 8.5, -8.5, -29.5625, 29.5625, -5.5,
+    5.5, 5.5, 5.5, 5.5, 5.5, 5.5, -5.5, -5.5, -5.5, -5.5, 5.5, -5.5, 5.5, -10.5, -56.4375, -10.5,
+    10.5, -10.5, -10.5, 10.5, -10.5, 56.4375, 56.4375, 56.4375, 56.4375, -56.4375, 56.4375,
+    56.4375, 56.4375, -10.9375, 10.9375, 3.5, -3.5, 3.5, -3.5, -3.5, 3.5, 10.9375, -3.5, 10.9375,
+    3.5, 3.5, 3.5, -3.5, -3.5, -67.1875, -12.5, 12.5, -12.5, -12.5, 12.5, 12.5, 12.5, 67.1875,
+    67.1875, -12.5, 12.5, -12.5, -12.5, -12.5, 12.5, -1.5, 1.5, -1.5, -1.5, 1.5, 1.5, -1.5, -1.5,
+    1.5, -8.0625, -1.5, 1.5, 1.5, -1.5, 1.5, 1.5, -77.9375, -77.9375, -77.9375, -77.9375, -77.9375,
+    -77.9375, -77.9375, 77.9375, 45.3125, 45.3125, 14.5, -14.5, -14.5, 14.5, -14.5, -14.5,
+];
+
+const IQ3XXS_REF_BLOCK: [u8; 98] = [
+    0x00, 0x34, 0x00, 0x05, 0x0a, 0x0f, 0x03, 0x08, 0x0d, 0x01, 0x06, 0x0b, 0xff, 0x04, 0x09, 0x0e,
+    0x02, 0x07, 0x0c, 0x00, 0x05, 0x0a, 0x0f, 0x03, 0x08, 0x0d, 0x01, 0x06, 0x0b, 0xff, 0x04, 0x09,
+    0x0e, 0x02, 0x07, 0x0c, 0x00, 0x05, 0x0a, 0x0f, 0x03, 0x08, 0x0d, 0x01, 0x06, 0x0b, 0xff, 0x04,
+    0x09, 0x0e, 0x02, 0x07, 0x0c, 0x00, 0x05, 0x0a, 0x0f, 0x03, 0x08, 0x0d, 0x01, 0x06, 0x0b, 0xff,
+    0x04, 0x09, 0x03, 0x4d, 0x0c, 0x19, 0x5f, 0x7b, 0x83, 0x34, 0x3b, 0x69, 0x1a, 0x50, 0x17, 0x57,
+    0x91, 0x7b, 0x73, 0x45, 0x08, 0x97, 0x4f, 0x73, 0x9f, 0xb2, 0x2b, 0x61, 0x16, 0xde, 0x07, 0x4f,
+    0x8d, 0xf9,
+];
+
+const IQ3XXS_REF_VALUES: [f32; 256] = [
+    -0.75, -0.75, 0.75, 0.75, 11.625, 2.25, 0.75, 0.75, 5.25, -11.625, 0.75, -0.75, -3.75, 2.25,
+    2.25, -0.75, -2.25, 2.25, 0.75, 0.75, -2.25, -5.25, 0.75, -0.75, 5.25, 0.75, 2.25, -0.75, 3.75,
+    0.75, -0.75, 0.75, -1.75, -8.75, -1.75, -1.75, -19.25, 27.125, -1.75, 1.75, 1.75, -12.25,
+    -22.75, 27.125, -12.25, -5.25, -1.75, -1.75, -8.75, 15.75, -1.75, -1.75, 1.75, 5.25, 5.25,
+    -1.75, 15.75, 1.75, -1.75, 1.75, 8.75, -8.75, 1.75, 1.75, -8.25, -2.75, 8.25, -2.75, -2.75,
+    -2.75, 2.75, -2.75, 42.625, -8.25, 2.75, 2.75, -19.25, 42.625, -2.75, -2.75, -13.75, 8.25,
+    8.25, -2.75, 8.25, -8.25, -2.75, 2.75, 8.25, 19.25, 2.75, 2.75, 19.25, 2.75, 8.25, 2.75,
+    -18.75, -3.75, -3.75, 3.75, -3.75, 18.75, 3.75, 3.75, 41.25, -58.125, -3.75, -3.75, 3.75,
+    -26.25, 48.75, 58.125, -26.25, 11.25, -3.75, 3.75, 18.75, 33.75, -3.75, -3.75, 3.75, 11.25,
+    -11.25, -3.75, -33.75, 3.75, -3.75, 3.75, -23.75, -23.75, 4.75, 4.75, -14.25, -4.75, -14.25,
+    -4.75, 4.75, -4.75, 4.75, -4.75, 73.625, 14.25, 4.75, 4.75, -33.25, 73.625, 4.75, 4.75, 23.75,
+    -14.25, 14.25, 4.75, 14.25, 14.25, 4.75, -4.75, -14.25, -33.25, 4.75, -4.75, -40.25, -5.75,
+    -17.25, -5.75, 28.75, 5.75, -5.75, -5.75, 5.75, -28.75, -5.75, 5.75, 63.25, -89.125, -5.75,
+    5.75, -5.75, 40.25, -74.75, -89.125, -40.25, -17.25, -5.75, 5.75, 28.75, 51.75, -5.75, 5.75,
+    -5.75, 17.25, 17.25, 5.75, -60.75, -6.75, 6.75, -6.75, 33.75, -33.75, 6.75, 6.75, 20.25, -6.75,
+    20.25, 6.75, 6.75, 6.75, -6.75, 6.75, -104.625, 20.25, 6.75, -6.75, -47.25, 104.625, -6.75,
+    6.75, 33.75, 20.25, 20.25, 6.75, -20.25, -20.25, -6.75, -6.75, -23.25, -54.25, -7.75, 7.75,
+    54.25, 7.75, 23.25, -7.75, 38.75, -7.75, -7.75, -7.75, -7.75, 38.75, 7.75, 7.75, -85.25,
+    120.125, -7.75, 7.75, -7.75, -54.25, 100.75, 120.125, 54.25, 23.25, -7.75, -7.75, 38.75, 69.75,
+    -7.75, -7.75,
+];
+
+const IQ1S_REF_BLOCK: [u8; 50] = [
+    0x00, 0x38, 0x01, 0x04, 0xff, 0x02, 0x05, 0x00, 0x03, 0x06, 0x01, 0x04, 0xff, 0x02, 0x05, 0x00,
+    0x03, 0x06, 0x01, 0x04, 0xff, 0x02, 0x05, 0x00, 0x03, 0x06, 0x01, 0x04, 0xff, 0x02, 0x05, 0x00,
+    0x03, 0x06, 0xc0, 0x01, 0x00, 0x90, 0xc0, 0x21, 0x00, 0xb0, 0xc0, 0x41, 0x00, 0xd0, 0xc0, 0x61,
+    0x00, 0xf0,
+];
+
+const IQ1S_REF_VALUES: [f32; 256] = [
+    0.5625, -0.4375, -0.4375, -0.4375, -0.4375, -0.4375, -0.4375, -0.4375, 0.5625, 0.5625, -0.4375,
+    -0.4375, -0.4375, -0.4375, -0.4375, -0.4375, 0.5625, 0.5625, 0.5625, 0.5625, 0.5625, 0.5625,
+    0.5625, 0.5625, 0.0625, 0.0625, -0.4375, -0.4375, -0.4375, -0.4375, -0.4375, -0.4375, -0.1875,
+    -1.6875, -0.1875, -1.6875, -1.6875, -1.6875, -1.6875, -1.6875, -1.6875, -1.6875, -1.6875,
+    -1.6875, -1.6875, -1.6875, -1.6875, -1.6875, -1.6875, 1.3125, -1.6875, -1.6875, -1.6875,
+    -1.6875, -1.6875, -1.6875, -0.1875, -0.1875, -0.1875, -1.6875, -1.6875, -1.6875, -1.6875,
+    -1.6875, 2.8125, -2.1875, -2.1875, -2.1875, -2.1875, -2.1875, -2.1875, -2.1875, 2.8125, 2.8125,
+    -2.1875, -2.1875, -2.1875, -2.1875, -2.1875, -2.1875, 2.8125, 2.8125, 2.8125, 2.8125, 2.8125,
+    2.8125, 2.8125, 2.8125, 0.3125, 0.3125, -2.1875, -2.1875, -2.1875, -2.1875, -2.1875, -2.1875,
+    -0.4375, -3.9375, -0.4375, -3.9375, -3.9375, -3.9375, -3.9375, -3.9375, -3.9375, -3.9375,
+    -3.9375, -3.9375, -3.9375, -3.9375, -3.9375, -3.9375, -3.9375, 3.0625, -3.9375, -3.9375,
+    -3.9375, -3.9375, -3.9375, -3.9375, -0.4375, -0.4375, -0.4375, -3.9375, -3.9375, -3.9375,
+    -3.9375, -3.9375, 5.0625, -3.9375, -3.9375, -3.9375, -3.9375, -3.9375, -3.9375, -3.9375,
+    5.0625, 5.0625, -3.9375, -3.9375, -3.9375, -3.9375, -3.9375, -3.9375, 5.0625, 5.0625, 5.0625,
+    5.0625, 5.0625, 5.0625, 5.0625, 5.0625, 0.5625, 0.5625, -3.9375, -3.9375, -3.9375, -3.9375,
+    -3.9375, -3.9375, -0.6875, -6.1875, -0.6875, -6.1875, -6.1875, -6.1875, -6.1875, -6.1875,
+    -6.1875, -6.1875, -6.1875, -6.1875, -6.1875, -6.1875, -6.1875, -6.1875, -6.1875, 4.8125,
+    -6.1875, -6.1875, -6.1875, -6.1875, -6.1875, -6.1875, -0.6875, -0.6875, -0.6875, -6.1875,
+    -6.1875, -6.1875, -6.1875, -6.1875, 7.3125, -5.6875, -5.6875, -5.6875, -5.6875, -5.6875,
+    -5.6875, -5.6875, 7.3125, 7.3125, -5.6875, -5.6875, -5.6875, -5.6875, -5.6875, -5.6875, 7.3125,
+    7.3125, 7.3125, 7.3125, 7.3125, 7.3125, 7.3125, 7.3125, 0.8125, 0.8125, -5.6875, -5.6875,
+    -5.6875, -5.6875, -5.6875, -5.6875, -0.9375, -8.4375, -0.9375, -8.4375, -8.4375, -8.4375,
+    -8.4375, -8.4375, -8.4375, -8.4375, -8.4375, -8.4375, -8.4375, -8.4375, -8.4375, -8.4375,
+    -8.4375, 6.5625, -8.4375, -8.4375, -8.4375, -8.4375, -8.4375, -8.4375, -0.9375, -0.9375,
+    -0.9375, -8.4375, -8.4375, -8.4375, -8.4375, -8.4375,
+];
+
+const IQ1M_REF_BLOCK: [u8; 56] = [
+    0x02, 0xff, 0x04, 0x01, 0x06, 0x03, 0x00, 0x05, 0x02, 0xff, 0x04, 0x01, 0x06, 0x03, 0x00, 0x05,
+    0x02, 0xff, 0x04, 0x01, 0x06, 0x03, 0x00, 0x05, 0x02, 0xff, 0x04, 0x01, 0x06, 0x03, 0x00, 0x05,
+    0x78, 0x80, 0x00, 0x08, 0xf0, 0x00, 0x08, 0x80, 0x70, 0x08, 0x80, 0x00, 0x78, 0x80, 0x00, 0x08,
+    0x98, 0x03, 0xbc, 0x0a, 0x98, 0x83, 0xbc, 0x3a,
+];
+
+const IQ1M_REF_VALUES: [f32; 256] = [
+    -0.0625, -0.0625, -0.5625, -0.5625, -0.5625, -0.5625, -0.5625, -0.5625, 0.5625, 0.5625, 0.5625,
+    0.5625, 0.5625, 0.5625, 0.5625, 0.5625, 3.9375, 3.9375, -3.0625, -3.0625, -3.0625, -3.0625,
+    -3.0625, -3.0625, 3.0625, -3.9375, -3.9375, -3.9375, -3.9375, -3.9375, -3.9375, -3.9375,
+    0.8125, 0.8125, 0.8125, -5.6875, -5.6875, -5.6875, -5.6875, -5.6875, -5.6875, 7.3125, -5.6875,
+    -5.6875, -5.6875, -5.6875, -5.6875, -5.6875, -1.6875, -1.6875, -1.6875, -1.6875, -1.6875,
+    -1.6875, -1.6875, -1.6875, 0.1875, -1.3125, 0.1875, -1.3125, -1.3125, -1.3125, -1.3125,
+    -1.3125, 0.5625, 0.5625, -3.9375, -3.9375, -3.9375, -3.9375, -3.9375, -3.9375, 3.9375, 3.9375,
+    3.9375, 3.9375, 3.9375, 3.9375, 3.9375, 3.9375, 8.4375, 8.4375, -6.5625, -6.5625, -6.5625,
+    -6.5625, -6.5625, -6.5625, 8.4375, -6.5625, -6.5625, -6.5625, -6.5625, -6.5625, -6.5625,
+    -6.5625, -0.3125, -0.3125, -0.3125, -2.8125, -2.8125, -2.8125, -2.8125, -2.8125, -2.1875,
+    2.8125, -2.1875, -2.1875, -2.1875, -2.1875, -2.1875, -2.1875, -4.8125, -4.8125, -4.8125,
+    -4.8125, -4.8125, -4.8125, -4.8125, -4.8125, -0.6875, -6.1875, -0.6875, -6.1875, -6.1875,
+    -6.1875, -6.1875, -6.1875, 0.0625, 0.0625, -0.4375, -0.4375, -0.4375, -0.4375, -0.4375,
+    -0.4375, 0.5625, 0.5625, 0.5625, 0.5625, 0.5625, 0.5625, 0.5625, 0.5625, 3.0625, 3.0625,
+    -3.9375, -3.9375, -3.9375, -3.9375, -3.9375, -3.9375, 3.9375, -3.0625, -3.0625, -3.0625,
+    -3.0625, -3.0625, -3.0625, -3.0625, 0.8125, 0.8125, 0.8125, -5.6875, -5.6875, -5.6875, -5.6875,
+    -5.6875, -7.3125, 5.6875, -7.3125, -7.3125, -7.3125, -7.3125, -7.3125, -7.3125, -1.3125,
+    -1.3125, -1.3125, -1.3125, -1.3125, -1.3125, -1.3125, -1.3125, 0.1875, -1.3125, 0.1875,
+    -1.3125, -1.3125, -1.3125, -1.3125, -1.3125, -0.5625, -0.5625, -5.0625, -5.0625, -5.0625,
+    -5.0625, -5.0625, -5.0625, 5.0625, 5.0625, 5.0625, 5.0625, 5.0625, 5.0625, 5.0625, 5.0625,
+    8.4375, 8.4375, -6.5625, -6.5625, -6.5625, -6.5625, -6.5625, -6.5625, 6.5625, -8.4375, -8.4375,
+    -8.4375, -8.4375, -8.4375, -8.4375, -8.4375, 0.3125, 0.3125, 0.3125, -2.1875, -2.1875, -2.1875,
+    -2.1875, -2.1875, -2.1875, 2.8125, -2.1875, -2.1875, -2.1875, -2.1875, -2.1875, -2.1875,
+    -6.1875, -6.1875, -6.1875, -6.1875, -6.1875, -6.1875, -6.1875, -6.1875, 0.6875, -4.8125,
+    0.6875, -4.8125, -4.8125, -4.8125, -4.8125, -4.8125,
+];
+
+const IQ4XS_REF_BLOCK: [u8; 136] = [
+    0x00, 0x34, 0x50, 0xfe, 0xc0, 0xe5, 0x07, 0xf9, 0x03, 0x0a, 0x11, 0x18, 0x1f, 0x26, 0x2d, 0x34,
+    0x3b, 0x42, 0x49, 0x50, 0x57, 0x5e, 0x65, 0x6c, 0x73, 0x7a, 0x81, 0x88, 0x8f, 0x96, 0x9d, 0xa4,
+    0xab, 0xb2, 0xb9, 0xc0, 0xc7, 0xce, 0xd5, 0xdc, 0xe3, 0xea, 0xf1, 0xf8, 0xff, 0x06, 0x0d, 0x14,
+    0x1b, 0x22, 0x29, 0x30, 0x37, 0x3e, 0x45, 0x4c, 0x53, 0x5a, 0x61, 0x68, 0x6f, 0x76, 0x7d, 0x84,
+    0x8b, 0x92, 0x99, 0xa0, 0xa7, 0xae, 0xb5, 0xbc, 0xc3, 0xca, 0xd1, 0xd8, 0xdf, 0xe6, 0xed, 0xf4,
+    0xfb, 0x02, 0x09, 0x10, 0x17, 0x1e, 0x25, 0x2c, 0x33, 0x3a, 0x41, 0x48, 0x4f, 0x56, 0x5d, 0x64,
+    0x6b, 0x72, 0x79, 0x80, 0x87, 0x8e, 0x95, 0x9c, 0xa3, 0xaa, 0xb1, 0xb8, 0xbf, 0xc6, 0xcd, 0xd4,
+    0xdb, 0xe2, 0xe9, 0xf0, 0xf7, 0xfe, 0x05, 0x0c, 0x13, 0x1a, 0x21, 0x28, 0x2f, 0x36, 0x3d, 0x44,
+    0x4b, 0x52, 0x59, 0x60, 0x67, 0x6e, 0x75, 0x7c,
+];
+
+const IQ4XS_REF_VALUES: [f32; 256] = [
+    520., -200., 832., -8., -904., 176., -552., 392., -304., 664., -104., 1016., 80., -712., 280.,
+    -424., 1016., 1016., 832., 832., 832., 664., 664., 520., 520., 392., 392., 280., 280., 280.,
+    176., 176., 325., -125., 520., -5., -565., 110., -345., 245., -190., 415., -65., 635., 50.,
+    -445., 175., -265., 50., 50., -5., -5., -5., -65., -65., -125., -125., -190., -190., -265.,
+    -265., -265., -345., -345., 178.75, -68.75, 286., -2.75, -310.75, 60.5, -189.75, 134.75,
+    -104.5, 228.25, -35.75, 349.25, 27.5, -244.75, 96.25, -145.75, -244.75, -244.75, -310.75,
+    -310.75, -310.75, 349.25, 349.25, 286., 286., 228.25, 228.25, 178.75, 178.75, 178.75, 134.75,
+    134.75, 32.5, -12.5, 52., -0.5, -56.5, 11., -34.5, 24.5, -19., 41.5, -6.5, 63.5, 5., -44.5,
+    17.5, -26.5, 17.5, 17.5, 11., 11., 11., 5., 5., -0.5, -0.5, -6.5, -6.5, -12.5, -12.5, -12.5,
+    -19., -19., -113.75, 43.75, -182., 1.75, 197.75, -38.5, 120.75, -85.75, 66.5, -145.25, 22.75,
+    -222.25, -17.5, 155.75, -61.25, 92.75, 92.75, 92.75, 120.75, 120.75, 120.75, 155.75, 155.75,
+    197.75, 197.75, -222.25, -222.25, -182., -182., -182., -145.25, -145.25, -260., 100., -416.,
+    4., 452., -88., 276., -196., 152., -332., 52., -508., -40., 356., -140., 212., -260., -260.,
+    -196., -196., -196., -140., -140., -88., -88., -40., -40., 4., 4., 4., 52., 52., -406.25,
+    156.25, -650., 6.25, 706.25, -137.5, 431.25, -306.25, 237.5, -518.75, 81.25, -793.75, -62.5,
+    556.25, -218.75, 331.25, 156.25, 156.25, 237.5, 237.5, 237.5, 331.25, 331.25, 431.25, 431.25,
+    556.25, 556.25, 706.25, 706.25, 706.25, -793.75, -793.75, -503.75, 193.75, -806., 7.75, 875.75,
+    -170.5, 534.75, -379.75, 294.5, -643.25, 100.75, -984.25, -77.5, 689.75, -271.25, 410.75,
+    -806., -806., -643.25, -643.25, -643.25, -503.75, -503.75, -379.75, -379.75, -271.25, -271.25,
+    -170.5, -170.5, -170.5, -77.5, -77.5,
+];
+
+fn reference_block_test(dtype: GgmlDType, block: &[u8], expected: &[f32]) -> Result<()> {
+    let cpu = &Device::Cpu;
+    let n = expected.len();
+    let qtensor = quantized::ggml_file::qtensor_from_ggml(dtype, block, vec![1, n], cpu)?;
+    let values = qtensor.dequantize(cpu)?.flatten_all()?.to_vec1::<f32>()?;
+    assert_eq!(values, expected, "{dtype:?}");
+
+    // The activations are integers with a block maximum chosen so that their q8 quantization
+    // is exact, the dot product is then fully determined by the reference values.
+    let y0 = if n == 32 { -127f32 } else { -128f32 };
+    let ys = (0..n)
+        .map(|i| {
+            if i == 0 {
+                y0
+            } else {
+                (i * 13 % 255) as f32 - 127.
+            }
+        })
+        .collect::<Vec<_>>();
+    let expected_dot = expected
+        .iter()
+        .zip(ys.iter())
+        .map(|(&x, &y)| x as f64 * y as f64)
+        .sum::<f64>();
+    let mm = quantized::QMatMul::from_qtensor(qtensor)?;
+    let dot = mm
+        .forward(&Tensor::from_vec(ys, (1, n), cpu)?)?
+        .flatten_all()?
+        .to_vec1::<f32>()?[0];
+    if (dot as f64 - expected_dot).abs() > 1e-4 * expected_dot.abs().max(1.) {
+        bail!("{dtype:?}: vec-dot {dot} vs reference {expected_dot}")
+    }
+    Ok(())
+}
+
+#[test]
+fn iq_reference_values() -> Result<()> {
+    reference_block_test(GgmlDType::IQ2XXS, &IQ2XXS_REF_BLOCK, &IQ2XXS_REF_VALUES)?;
+    reference_block_test(GgmlDType::IQ3S, &IQ3S_REF_BLOCK, &IQ3S_REF_VALUES)?;
+    reference_block_test(GgmlDType::IQ4NL, &IQ4NL_REF_BLOCK, &IQ4NL_REF_VALUES)?;
+    reference_block_test(GgmlDType::TQ1_0, &TQ1_0_REF_BLOCK, &TQ1_0_REF_VALUES)?;
+    reference_block_test(GgmlDType::TQ2_0, &TQ2_0_REF_BLOCK, &TQ2_0_REF_VALUES)?;
+    reference_block_test(GgmlDType::IQ2XS, &IQ2XS_REF_BLOCK, &IQ2XS_REF_VALUES)?;
+    reference_block_test(GgmlDType::IQ2S, &IQ2S_REF_BLOCK, &IQ2S_REF_VALUES)?;
+    reference_block_test(GgmlDType::IQ3XXS, &IQ3XXS_REF_BLOCK, &IQ3XXS_REF_VALUES)?;
+    reference_block_test(GgmlDType::IQ1S, &IQ1S_REF_BLOCK, &IQ1S_REF_VALUES)?;
+    reference_block_test(GgmlDType::IQ1M, &IQ1M_REF_BLOCK, &IQ1M_REF_VALUES)?;
+    reference_block_test(GgmlDType::IQ4XS, &IQ4XS_REF_BLOCK, &IQ4XS_REF_VALUES)?;
+    Ok(())
+}
+
+#[test]
+fn imatrix_quantize_iq() -> Result<()> {
+    let cpu = &Device::Cpu;
+    let (rows, cols) = (64, 512);
+    // Give more importance to some columns, as if they were often multiplied by large values.
+    let imatrix = (0..cols)
+        .map(|i| if i % 7 == 0 { 50. } else { 1. + (i % 5) as f32 })
+        .collect::<Vec<f32>>();
+    let weights = Tensor::new(imatrix.as_slice(), cpu)?;
+    let xs = Tensor::randn(0f32, 1f32, (rows, cols), cpu)?;
+    for dtype in [
+        GgmlDType::IQ2XXS,
+        GgmlDType::IQ2XS,
+        GgmlDType::IQ2S,
+        GgmlDType::IQ3XXS,
+        GgmlDType::IQ3S,
+        GgmlDType::IQ1S,
+        GgmlDType::IQ1M,
+        GgmlDType::IQ4NL,
+        GgmlDType::IQ4XS,
+    ] {
+        let quant1 = quantized::QTensor::quantize(&xs, dtype)?;
+        let quant2 = quantized::QTensor::quantize_imatrix(&xs, &imatrix, dtype)?;
+        let err = |q: &quantized::QTensor| -> Result<f32> {
+            (q.dequantize(cpu)? - &xs)?
+                .sqr()?
+                .broadcast_mul(&weights)?
+                .mean_all()?
+                .to_scalar::<f32>()
+        };
+        let (err1, err2) = (err(&quant1)?, err(&quant2)?);
+        assert!(err2 < err1, "{dtype:?}: err2 {err2} > err1 {err1}");
+    }
+    Ok(())
+}
+
+#[test]
+fn quantized_matmul_iq() -> Result<()> {
+    let cpu = &Device::Cpu;
+    let (m, k, n) = (3, 512, 5);
+    let (lhs, rhs, _mm) = get_random_tensors(m, k, n, cpu)?;
+    for dtype in [GgmlDType::IQ3S, GgmlDType::IQ4NL, GgmlDType::IQ4XS] {
+        let rhs = quantized::QTensor::quantize(&rhs, dtype)?;
+        let expected = lhs.matmul(&rhs.dequantize(cpu)?.t()?)?;
+        let mm = quantized::QMatMul::from_qtensor(rhs)?.forward(&lhs)?;
+        let diff = (mm - expected)?.abs()?.max_all()?.to_scalar::<f32>()?;
+        assert!(diff < 0.1, "{dtype:?}: {diff}");
+    }
+    Ok(())
+}
+
+#[test]
+fn gguf_iq_roundtrip() -> Result<()> {
+    use quantized::gguf_file;
+    let cpu = &Device::Cpu;
+    let xs = Tensor::randn(0f32, 1f32, (4, 256), cpu)?;
+    let q1 = quantized::QTensor::quantize(&xs, GgmlDType::IQ3XXS)?;
+    let q2 = quantized::QTensor::quantize(&xs, GgmlDType::TQ1_0)?;
+    let mut buf = std::io::Cursor::new(Vec::new());
+    gguf_file::write(&mut buf, &[], &[("q1", &q1), ("q2", &q2)])?;
+    buf.set_position(0);
+    let content = gguf_file::Content::read(&mut buf)?;
+    for (name, q) in [("q1", &q1), ("q2", &q2)] {
+        let t = content.tensor(&mut buf, name, cpu)?;
+        assert_eq!(t.dtype(), q.dtype());
+        assert_eq!(t.data()?, q.data()?);
+    }
+    Ok(())
+}
+
 /// generates random tensors of size `m x k` and `n x k` and calculates their expected matrix multiplication result.
 fn get_random_tensors(
     m: usize,
@@ -1400,3 +1984,36 @@ fn quantized_matmul_q8k() -> Result<()> {
     ggml_matmul_error_test::<BlockQ8K>()?;
     Ok(())
 }
+
+#[test]
+fn imatrix_collector_roundtrip() -> Result<()> {
+    use quantized::imatrix_file::{load_imatrix, ImatrixCollector};
+    let dev = &Device::Cpu;
+    let collector = ImatrixCollector::new();
+    let xs = Tensor::new(&[[[1f32, -2., 3.], [0., 2., -1.]]], dev)?;
+    collector.record("proj.weight", &xs)?;
+    collector.next_chunk();
+    let xs = Tensor::new(&[[2f32, 0., 1.]], dev)?;
+    collector.record("proj.weight", &xs)?;
+    collector.next_chunk();
+    assert!(collector
+        .record("proj.weight", &Tensor::zeros(2, DType::F32, dev)?)
+        .is_err());
+
+    let entries = collector.entries();
+    assert_eq!(entries["proj.weight"].rows, 3);
+    assert_eq!(entries["proj.weight"].ncall, 2);
+    let expected = vec![5f32 / 3., 8. / 3., 11. / 3.];
+    assert_eq!(collector.imatrix()["proj.weight"], expected);
+
+    let path = std::env::temp_dir().join(format!("candle-{}.imatrix", std::process::id()));
+    collector.save(&path, "calibration.txt")?;
+    let loaded = load_imatrix(&path);
+    std::fs::remove_file(&path)?;
+    let loaded = loaded?;
+    assert_eq!(loaded.len(), 1);
+    for (l, e) in loaded["proj.weight"].iter().zip(expected.iter()) {
+        assert!((l - e).abs() < 1e-6)
+    }
+    Ok(())
+}
@@ -0,0 +1,136 @@
+use anyhow::{Context, Result};
+use candle_core::sparse::{CooTensor, CsrTensor};
+use candle_core::{test_device, DType, Device, Tensor, Var};
+
+fn coo_roundtrip(device: &Device) -> Result<()> {
+    let t = Tensor::new(&[[0f32, 1., 0.], [2., 0., 3.]], device)?;
+    let coo = CooTensor::from_dense(&t)?;
+    assert_eq!(coo.nnz(), 3);
+    assert!(coo.is_coalesced());
+    assert_eq!(coo.indices().to_vec2::<u32>()?, [[0, 1, 1], [1, 0, 2]]);
+    assert_eq!(coo.values().to_vec1::<f32>()?, [1., 2., 3.]);
+    assert_eq!(coo.to_dense()?.to_vec2::<f32>()?, t.to_vec2::<f32>()?);
+
+    // Hybrid tensor with a single sparse dim.
+    let coo = CooTensor::from_dense_hybrid(&t, 1)?;
+    assert_eq!(coo.nnz(), 2);
+    assert_eq!(coo.dense_dims(), 1);
+    assert_eq!(coo.to_dense()?.to_vec2::<f32>()?, t.to_vec2::<f32>()?);
+    Ok(())
+}
+
+fn coo_coalesce(device: &Device) -> Result<()> {
+    let indices = Tensor::new(&[[1u32, 0, 1, 0], [2, 1, 2, 0]], device)?;
+    let values = Tensor::new(&[1f32, 2., 3., 4.], device)?;
+    let coo = CooTensor::new(indices, values, (2, 3))?;
+    assert!(!coo.is_coalesced());
+    assert_eq!(
+        coo.to_dense()?.to_vec2::<f32>()?,
+        [[4., 2., 0.], [0., 0., 4.]]
+    );
+    let coo = coo.coalesce()?;
+    assert_eq!(coo.indices().to_vec2::<u32>()?, [[0, 0, 1], [0, 1, 2]]);
+    assert_eq!(coo.values().to_vec1::<f32>()?, [4., 2., 4.]);
+
+    let csr = coo.to_csr()?;
+    assert_eq!(csr.crow_indices().to_vec1::<u32>()?, [0, 2, 3]);
+    assert_eq!(csr.col_indices().to_vec1::<u32>()?, [0, 1, 2]);
+    assert_eq!(
+        csr.to_dense()?.to_vec2::<f32>()?,
+        [[4., 2., 0.], [0., 0., 4.]]
+    );
+    let indices = Tensor::new(&[[2u32], [0]], device)?;
+    let values = Tensor::new(&[1f32], device)?;
+    let coo = CooTensor::new(indices, values, (2, 3))?;
+    assert!(coo.to_dense().is_err());
+
+    // The flattened positions of a 100k x 100k matrix do not fit in u32.
+    let n = 100_000u32;
+    let indices = Tensor::new(&[[n - 1, 1, n - 1], [n - 1, n - 2, n - 1]], device)?;
+    let values = Tensor::new(&[1f32, 2., 3.], device)?;
+    let coo = CooTensor::new(indices, values, (n as usize, n as usize))?.coalesce()?;
+    assert_eq!(
+        coo.indices().to_vec2::<u32>()?,
+        [[1, n - 1], [n - 2, n - 1]]
+    );
+    assert_eq!(coo.values().to_vec1::<f32>()?, [2., 4.]);
+    let csr = coo.to_csr()?;
+    assert_eq!(csr.col_indices().to_vec1::<u32>()?, [n - 2, n - 1]);
+    let crows = csr.crow_indices().to_vec1::<u32>()?;
+    assert_eq!((crows[1], crows[2], crows[n as usize]), (0, 1, 2));
+
+    let cols = Tensor::new(&[0u32, 1, 2], device)?;
+    let values = Tensor::new(&[1f32, 2., 3.], device)?;
+    for crows in [[0u32, 2, 1], [0, 1, 2], [1, 2, 3]] {
+        let crows = Tensor::new(&crows, device)?;
+        assert!(CsrTensor::new(crows, cols.clone(), values.clone(), (2, 3)).is_err());
+    }
+    Ok(())
+}
+
+fn spmm(device: &Device) -> Result<()> {
+    let a = Tensor::new(&[[0f32, 2., 0.], [1., 0., 3.], [0., 0., 0.]], device)?;
+    let b = Tensor::arange(0f32, 6., device)?.reshape((3, 2))?;
+    let expected = a.matmul(&b)?.to_vec2::<f32>()?;
+    let csr = CsrTensor::from_dense(&a)?;
+    assert_eq!(csr.row_indices()?.to_vec1::<u32>()?, [0, 1, 1]);
+    assert_eq!(csr.matmul(&b)?.to_vec2::<f32>()?, expected);
+    let coo = CooTensor::from_dense(&a)?;
+    assert_eq!(coo.matmul(&b)?.to_vec2::<f32>()?, expected);
+    assert!(csr.matmul(&b.t()?).is_err());
+    Ok(())
+}
+
+fn spmm_grad(device: &Device) -> Result<()> {
+    let a = Tensor::new(&[[0f32, 2., 0.], [1., 0., 3.]], device)?;
+    let csr = CsrTensor::from_dense(&a)?;
+    let values = Var::from_tensor(csr.values())?;
+    let csr = CsrTensor::new(
+        csr.crow_indices().clone(),
+        csr.col_indices().clone(),
+        values.as_tensor().clone(),
+        (2, 3),
+    )?;
+    let b = Var::new(&[[1f32, 2.], [3., 4.], [5., 6.]], device)?;
+    let loss = csr.matmul(&b)?.sum_all()?;
+    let grads = loss.backward()?;
+    // d(loss)/d(a_ij) = sum_k b_jk
+    let grad_values = grads.get(&values).context("no grad for values")?;
+    assert_eq!(grad_values.to_vec1::<f32>()?, [7., 3., 11.]);
+    // d(loss)/d(b_jk) = sum_i a_ij, same as the dense version.
+    let grad_b = grads.get(&b).context("no grad for b")?;
+    assert_eq!(grad_b.to_vec2::<f32>()?, [[1., 1.], [2., 2.], [3., 3.]]);
+    Ok(())
+}
+
+fn sddmm(device: &Device) -> Result<()> {
+    let lhs = Tensor::arange(0f32, 6., device)?.reshape((2, 3))?;
+    let rhs = Tensor::arange(0f32, 12., device)?.reshape((3, 4))?;
+    let pattern = Tensor::new(&[[1f32, 0., 0., 2.], [0., 1., 0., 0.]], device)?;
+    let dense = lhs.matmul(&rhs)?.mul(&pattern)?;
+    let csr = CsrTensor::from_dense(&pattern)?.sddmm(&lhs, &rhs)?;
+    assert_eq!(csr.nnz(), 3);
+    assert_eq!(csr.to_dense()?.to_vec2::<f32>()?, dense.to_vec2::<f32>()?);
+    let coo = CooTensor::from_dense(&pattern)?.sddmm(&lhs, &rhs)?;
+    assert_eq!(coo.to_dense()?.to_vec2::<f32>()?, dense.to_vec2::<f32>()?);
+    assert!(CsrTensor::from_dense(&pattern)?.sddmm(&rhs, &lhs).is_err());
+    let half = CsrTensor::from_dense(&pattern)?.to_dtype(DType::F16)?;
+    assert_eq!(half.dtype(), DType::F16);
+    Ok(())
+}
+
+test_device!(
+    coo_roundtrip,
+    coo_roundtrip_cpu,
+    coo_roundtrip_gpu,
+    coo_roundtrip_metal
+);
+test_device!(
+    coo_coalesce,
+    coo_coalesce_cpu,
+    coo_coalesce_gpu,
+    coo_coalesce_metal
+);
+test_device!(spmm, spmm_cpu, spmm_gpu, spmm_metal);
+test_device!(spmm_grad, spmm_grad_cpu, spmm_grad_gpu, spmm_grad_metal);
+test_device!(sddmm, sddmm_cpu, sddmm_gpu, sddmm_metal);
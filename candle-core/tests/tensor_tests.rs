@@ -1767,6 +1767,112 @@ test_device!(
     tensor_send_sync_metal
 );
 
+#[test]
+fn scatter_reduce() -> Result<()> {
+    use candle_core::op::ScatterReduceOp;
+    let device = &Device::Cpu;
+    let t = Tensor::arange(0f32, 12f32, device)?.reshape((4, 3))?;
+    let ids = Tensor::new(&[[0u32, 1, 2], [3, 4, 0], [3, 3, 1], [2, 0, 4]], device)?;
+    let init = Tensor::ones((4, 5), DType::F32, device)?;
+    let hs = init.scatter_reduce(&ids, &t, 1, ScatterReduceOp::Max, false)?;
+    assert_eq!(
+        hs.to_vec2::<f32>()?,
+        &[
+            [0.0, 1.0, 2.0, 1.0, 1.0],
+            [5.0, 1.0, 1.0, 3.0, 4.0],
+            [1.0, 8.0, 1.0, 7.0, 1.0],
+            [10.0, 1.0, 9.0, 1.0, 11.0]
+        ]
+    );
+    let hs = init.scatter_reduce(&ids, &t, 1, ScatterReduceOp::Min, true)?;
+    assert_eq!(
+        hs.to_vec2::<f32>()?,
+        &[
+            [0.0, 1.0, 1.0, 1.0, 1.0],
+            [1.0, 1.0, 1.0, 1.0, 1.0],
+            [1.0, 1.0, 1.0, 1.0, 1.0],
+            [1.0, 1.0, 1.0, 1.0, 1.0]
+        ]
+    );
+    let hs = (init.clone() * 2.)?.scatter_reduce(&ids, &t, 1, ScatterReduceOp::Prod, true)?;
+    assert_eq!(
+        hs.to_vec2::<f32>()?,
+        &[
+            [0.0, 2.0, 4.0, 2.0, 2.0],
+            [10.0, 2.0, 2.0, 6.0, 8.0],
+            [2.0, 16.0, 2.0, 84.0, 2.0],
+            [20.0, 2.0, 18.0, 2.0, 22.0]
+        ]
+    );
+    let hs = init.scatter_reduce(&ids, &t, 1, ScatterReduceOp::Mean, false)?;
+    assert_eq!(
+        hs.to_vec2::<f32>()?,
+        &[
+            [0.0, 1.0, 2.0, 1.0, 1.0],
+            [5.0, 1.0, 1.0, 3.0, 4.0],
+            [1.0, 8.0, 1.0, 6.5, 1.0],
+            [10.0, 1.0, 9.0, 1.0, 11.0]
+        ]
+    );
+    let hs = init.scatter_reduce(&ids, &t, 1, ScatterReduceOp::Sum, true)?;
+    assert_eq!(
+        hs.to_vec2::<f32>()?,
+        init.scatter_add(&ids, &t, 1)?.to_vec2::<f32>()?
+    );
+
+    // Integer means are rounded down.
+    let init = Tensor::new(&[0i64, 0], device)?;
+    let src = Tensor::new(&[3i64, 4, 5], device)?;
+    let ids = Tensor::new(&[0u32, 0, 1], device)?;
+    let hs = init.scatter_reduce(&ids, &src, 0, ScatterReduceOp::Mean, false)?;
+    assert_eq!(hs.to_vec1::<i64>()?, [3, 5]);
+    let ids = Tensor::new(&[0u32, 0, 2], device)?;
+    assert!(init
+        .scatter_reduce(&ids, &src, 0, ScatterReduceOp::Max, false)
+        .is_err());
+    Ok(())
+}
+
+#[test]
+fn index_reduce_and_segments() -> Result<()> {
+    use candle_core::op::ScatterReduceOp;
+    let device = &Device::Cpu;
+    let init = Tensor::zeros((3, 2), DType::F32, device)?;
+    let src = Tensor::new(&[[1f32, 2.], [3., -1.], [5., 6.], [0., 0.]], device)?;
+    let ids = Tensor::new(&[0u32, 2, 0, 2], device)?;
+    let hs = init.index_reduce(&ids, &src, 0, ScatterReduceOp::Max, false)?;
+    assert_eq!(hs.to_vec2::<f32>()?, [[5., 6.], [0., 0.], [3., 0.]]);
+    let hs = init.index_reduce(
+        &ids.i(..2)?,
+        &src.i(..2)?.t()?,
+        1,
+        ScatterReduceOp::Min,
+        true,
+    );
+    assert!(hs.is_err());
+    let init = Tensor::ones((2, 3), DType::F32, device)?;
+    let hs = init.index_reduce(
+        &ids.i(..2)?,
+        &src.i(..2)?.t()?,
+        1,
+        ScatterReduceOp::Min,
+        true,
+    )?;
+    assert_eq!(hs.to_vec2::<f32>()?, [[1., 1., 1.], [1., 1., -1.]]);
+
+    let data = Tensor::new(&[[1f32, 2.], [3., 4.], [5., 6.]], device)?;
+    let segment_ids = Tensor::new(&[0u32, 0, 2], device)?;
+    let hs = data.segment_sum(&segment_ids)?;
+    assert_eq!(hs.to_vec2::<f32>()?, [[4., 6.], [0., 0.], [5., 6.]]);
+    let hs = data.segment_max(&segment_ids)?;
+    assert_eq!(hs.to_vec2::<f32>()?, [[3., 4.], [0., 0.], [5., 6.]]);
+    let hs = data.segment_reduce(&segment_ids, ScatterReduceOp::Mean)?;
+    assert_eq!(hs.to_vec2::<f32>()?, [[2., 3.], [0., 0.], [5., 6.]]);
+    let segment_ids = Tensor::new(&[1u32, 0, 2], device)?;
+    assert!(data.segment_sum(&segment_ids).is_err());
+    Ok(())
+}
+
 // There was originally a bug on the CPU implementation for randn
 // https://github.com/huggingface/candle/issues/381
 #[test]
@@ -29,7 +29,7 @@ enum ArgDType {
 
 enum Transformer {
     Normal(transformer::Model),
-    Quantized(qtransformer::Model),
+    Quantized(Box<qtransformer::Model>),
 }
 
 #[derive(Parser, Debug)]
@@ -151,7 +151,7 @@ fn main() -> Result<()> {
         let vb =
             candle_transformers::quantized_var_builder::VarBuilder::from_gguf(filename, &device)?;
         let first_stage_model = qtransformer::Model::new(&first_stage_config, vb)?;
-        Transformer::Quantized(first_stage_model)
+        Transformer::Quantized(Box::new(first_stage_model))
     } else {
         let first_stage_weights = match &args.first_stage_weights {
             Some(w) => std::path::PathBuf::from(w),
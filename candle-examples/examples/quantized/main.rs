@@ -302,6 +302,11 @@ struct Args {
     /// Use the slower dmmv cuda kernel.
     #[arg(long)]
     force_dmmv: bool,
+
+    /// Store the kv-cache quantized, one of q8_0, q4_0 or f8e4m3 (f8e4m3 requires the float8
+    /// feature of candle-nn).
+    #[arg(long)]
+    kv_cache_dtype: Option<candle_nn::kv_cache::QuantizedKvDType>,
 }
 
 impl Args {
@@ -470,7 +475,7 @@ fn main() -> anyhow::Result<()> {
     let start = std::time::Instant::now();
     let device = candle_examples::device(args.cpu)?;
 
-    let mut model = match model_path.extension().and_then(|v| v.to_str()) {
+    let model = match model_path.extension().and_then(|v| v.to_str()) {
         Some("gguf") => {
             let model = gguf_file::Content::read(&mut file).map_err(|e| e.with_path(model_path))?;
             let mut total_size_in_bytes = 0;
@@ -533,6 +538,10 @@ fn main() -> anyhow::Result<()> {
             ModelWeights::from_ggml(model, args.gqa.unwrap_or(default_gqa))?
         }
     };
+    let mut model = match args.kv_cache_dtype {
+        Some(dtype) => model.with_kv_cache_dtype(dtype),
+        None => model,
+    };
     println!("model built");
 
     let tokenizer = args.tokenizer()?;
@@ -9,8 +9,8 @@ use clap::{Parser, ValueEnum};
 use std::io::Write;
 
 use candle::{DType, Device, Tensor};
-use candle_examples::chat_template::{ChatTemplate, ChatTemplateOptions, Message};
 use candle_examples::token_output_stream::TokenOutputStream;
+use candle_transformers::pipelines::chat_template::{ChatTemplate, ChatTemplateOptions, Message};
 
 use candle_nn::VarBuilder;
 use candle_transformers::generation::{LogitsProcessor, Sampling};
@@ -27,7 +27,7 @@ const DEFAULT_PROMPT: &str = "Write a Rust function to calculate the factorial o
 
 enum SmolLM3Model {
     Quantized(QuantizedModelForCausalLM),
-    Full(ModelForCausalLM, Config), // Store config alongside model
+    Full(ModelForCausalLM, Box<Config>), // Store config alongside model
 }
 
 impl SmolLM3Model {
@@ -306,17 +306,31 @@ fn load_full_model(args: &Args, device: &Device) -> Result<SmolLM3Model> {
     let vb = unsafe { VarBuilder::from_mmaped_safetensors(&filenames, dtype, device)? };
     let model = ModelForCausalLM::new(&config, vb)?;
 
-    Ok(SmolLM3Model::Full(model, config))
+    Ok(SmolLM3Model::Full(model, Box::new(config)))
 }
 
 // ==================== Text Generation ====================
 
-fn format_prompt(prompt: &str, use_chat_template: bool, enable_thinking: bool) -> String {
+// ChatML with the thinking block opened in the generation prompt when thinking is enabled.
+const CHAT_TEMPLATE: &str = r#"
+{%- for message in messages %}
+{{- '<|im_start|>' + message.role + '\n' + message.content | trim + '<|im_end|>\n' }}
+{%- endfor %}
+{%- if add_generation_prompt %}
+{%- if enable_thinking %}
+{{- '<|im_start|>assistant\n<think>\n' }}
+{%- else %}
+{{- '<|im_start|>assistant\n' }}
+{%- endif %}
+{%- endif %}
+"#;
+
+fn format_prompt(prompt: &str, use_chat_template: bool, enable_thinking: bool) -> Result<String> {
     if !use_chat_template {
-        return prompt.to_string();
+        return Ok(prompt.to_string());
     }
 
-    let template = ChatTemplate::chatml_with_thinking();
+    let template = ChatTemplate::new(CHAT_TEMPLATE)?;
 
     // Build system message with SmolLM3's metadata format
     let now = chrono::Local::now();
@@ -339,13 +353,10 @@ fn format_prompt(prompt: &str, use_chat_template: bool, enable_thinking: bool) -
 
     let messages = vec![Message::system(system_content), Message::user(prompt)];
 
-    let options = if enable_thinking {
-        ChatTemplateOptions::for_generation().with_thinking()
-    } else {
-        ChatTemplateOptions::for_generation()
-    };
+    let options = ChatTemplateOptions::for_generation()
+        .with_context("enable_thinking", serde_json::json!(enable_thinking));
 
-    template.apply(&messages, &options).unwrap()
+    Ok(template.apply(&messages, &options)?)
 }
 
 fn get_eos_token(tokenizer: &Tokenizer, config: &ModelConfig) -> u32 {
@@ -378,7 +389,7 @@ fn run_generation(
         .clone()
         .unwrap_or_else(|| DEFAULT_PROMPT.to_string());
     let use_chat_template = args.should_use_chat_template();
-    let formatted_prompt = format_prompt(&prompt_str, use_chat_template, args.thinking);
+    let formatted_prompt = format_prompt(&prompt_str, use_chat_template, args.thinking)?;
 
     println!("\n=== Generation Settings ===");
     println!("Model type: {:?}", args.model_type);
@@ -1,21 +1,39 @@
 use candle::Result;
+use candle_transformers::generation::constrained::Vocabulary;
+use candle_transformers::pipelines::detokenizer::IncrementalDecoder;
+use std::sync::Arc;
+
+/// Builds the byte vocabulary of a tokenizer, the special tokens decode to an empty text.
+pub fn vocabulary(tokenizer: &tokenizers::Tokenizer, eos_tokens: Vec<u32>) -> Result<Vocabulary> {
+    let special_tokens: Vec<u32> = tokenizer
+        .get_added_tokens_decoder()
+        .iter()
+        .filter(|(_, token)| token.special)
+        .map(|(&id, _)| id)
+        .collect();
+    Vocabulary::from_token_strings(&tokenizer.get_vocab(true), &special_tokens, eos_tokens)
+}
 
 /// This is a wrapper around a tokenizer to ensure that tokens can be returned to the user in a
-/// streaming way rather than having to wait for the full decoding.
+/// streaming way rather than having to wait for the full decoding. The decoding is done by the
+/// [`IncrementalDecoder`] of the text generation pipelines.
 pub struct TokenOutputStream {
     tokenizer: tokenizers::Tokenizer,
+    decoder: IncrementalDecoder,
     tokens: Vec<u32>,
-    prev_index: usize,
-    current_index: usize,
 }
 
 impl TokenOutputStream {
     pub fn new(tokenizer: tokenizers::Tokenizer) -> Self {
+        let vocab = vocabulary(&tokenizer, vec![]).expect("valid tokenizer vocabulary");
+        // Sentencepiece tokenizers encode spaces as `▁` and drop the one in front of the text.
+        let strip_leading_space = tokenizer.get_vocab(true).keys().any(|t| t.starts_with('▁'));
+        let decoder =
+            IncrementalDecoder::new(Arc::new(vocab)).with_strip_leading_space(strip_leading_space);
         Self {
             tokenizer,
+            decoder,
             tokens: Vec::new(),
-            prev_index: 0,
-            current_index: 0,
         }
     }
 
@@ -23,51 +41,24 @@ impl TokenOutputStream {
         self.tokenizer
     }
 
-    fn decode(&self, tokens: &[u32]) -> Result<String> {
-        match self.tokenizer.decode(tokens, true) {
-            Ok(str) => Ok(str),
-            Err(err) => candle::bail!("cannot decode: {err}"),
-        }
-    }
-
-    // https://github.com/huggingface/text-generation-inference/blob/5ba53d44a18983a4de32d122f4cb46f4a17d9ef6/server/text_generation_server/models/model.py#L68
+    /// Adds a token and returns the text it completes, if any.
     pub fn next_token(&mut self, token: u32) -> Result<Option<String>> {
-        let prev_text = if self.tokens.is_empty() {
-            String::new()
-        } else {
-            let tokens = &self.tokens[self.prev_index..self.current_index];
-            self.decode(tokens)?
-        };
         self.tokens.push(token);
-        let text = self.decode(&self.tokens[self.prev_index..])?;
-        if text.len() > prev_text.len() && text.chars().last().unwrap().is_alphanumeric() {
-            let text = text.split_at(prev_text.len());
-            self.prev_index = self.current_index;
-            self.current_index = self.tokens.len();
-            Ok(Some(text.1.to_string()))
-        } else {
-            Ok(None)
-        }
+        let text = self.decoder.push(token)?;
+        Ok((!text.is_empty()).then_some(text))
     }
 
-    pub fn decode_rest(&self) -> Result<Option<String>> {
-        let prev_text = if self.tokens.is_empty() {
-            String::new()
-        } else {
-            let tokens = &self.tokens[self.prev_index..self.current_index];
-            self.decode(tokens)?
-        };
-        let text = self.decode(&self.tokens[self.prev_index..])?;
-        if text.len() > prev_text.len() {
-            let text = text.split_at(prev_text.len());
-            Ok(Some(text.1.to_string()))
-        } else {
-            Ok(None)
-        }
+    /// Returns the bytes of an incomplete character at the end of the stream.
+    pub fn decode_rest(&mut self) -> Result<Option<String>> {
+        let text = self.decoder.flush();
+        Ok((!text.is_empty()).then_some(text))
     }
 
     pub fn decode_all(&self) -> Result<String> {
-        self.decode(&self.tokens)
+        match self.tokenizer.decode(&self.tokens, true) {
+            Ok(str) => Ok(str),
+            Err(err) => candle::bail!("cannot decode: {err}"),
+        }
     }
 
     pub fn get_token(&self, token_s: &str) -> Option<u32> {
@@ -80,7 +71,6 @@ impl TokenOutputStream {
 
     pub fn clear(&mut self) {
         self.tokens.clear();
-        self.prev_index = 0;
-        self.current_index = 0;
+        self.decoder.reset();
     }
 }
@@ -0,0 +1,112 @@
+#[cfg(feature = "mkl")]
+extern crate intel_mkl_src;
+
+#[cfg(feature = "accelerate")]
+extern crate accelerate_src;
+
+use candle::{DType, Device, Result, Tensor, D};
+use candle_nn::graph::{gcn_conv, EdgeIndex, GCNConv};
+use candle_nn::{loss, AdamW, Optimizer, ParamsAdamW, VarBuilder, VarMap};
+use rand::{rngs::StdRng, Rng, SeedableRng};
+
+const NUM_CLASSES: usize = 3;
+const NODES_PER_CLASS: usize = 40;
+const FEATURES: usize = 8;
+
+// Generate a graph from a stochastic block model: nodes from the same class are connected with a
+// high probability whereas nodes from different classes rarely are. The node features are very
+// noisy so that the neighbourhood has to be used to classify the nodes.
+fn gen_graph(dev: &Device) -> Result<(Tensor, EdgeIndex, Tensor)> {
+    let mut rng = StdRng::seed_from_u64(42);
+    let num_nodes = NUM_CLASSES * NODES_PER_CLASS;
+    let labels = (0..num_nodes)
+        .map(|i| (i / NODES_PER_CLASS) as u32)
+        .collect::<Vec<_>>();
+    let mut pairs = vec![];
+    for i in 0..num_nodes {
+        for j in i + 1..num_nodes {
+            let p = if labels[i] == labels[j] { 0.2 } else { 0.01 };
+            if rng.random::<f64>() < p {
+                pairs.push((i as u32, j as u32))
+            }
+        }
+    }
+    let edges = EdgeIndex::from_pairs(&pairs, num_nodes, dev)?.to_undirected()?;
+    let mut xs = vec![0f32; num_nodes * FEATURES];
+    for (i, &label) in labels.iter().enumerate() {
+        for f in 0..FEATURES {
+            let signal = if f % NUM_CLASSES == label as usize {
+                0.5
+            } else {
+                0.
+            };
+            xs[i * FEATURES + f] = signal + rng.random::<f32>() * 2. - 1.;
+        }
+    }
+    let xs = Tensor::from_vec(xs, (num_nodes, FEATURES), dev)?;
+    let labels = Tensor::new(labels, dev)?;
+    Ok((xs, edges, labels))
+}
+
+struct Gcn {
+    conv1: GCNConv,
+    conv2: GCNConv,
+}
+
+impl Gcn {
+    fn new(vb: VarBuilder) -> Result<Self> {
+        let conv1 = gcn_conv(FEATURES, 16, vb.pp("conv1"))?;
+        let conv2 = gcn_conv(16, NUM_CLASSES, vb.pp("conv2"))?;
+        Ok(Self { conv1, conv2 })
+    }
+
+    fn forward(&self, xs: &Tensor, edges: &EdgeIndex) -> Result<Tensor> {
+        let xs = self.conv1.forward(xs, edges)?.relu()?;
+        self.conv2.forward(&xs, edges)
+    }
+}
+
+fn main() -> Result<()> {
+    let dev = Device::Cpu;
+    let (xs, edges, labels) = gen_graph(&dev)?;
+    println!(
+        "graph with {} nodes and {} edges",
+        edges.num_nodes(),
+        edges.num_edges()
+    );
+    // Only the labels of the first few nodes of each class are used for training.
+    let train_nodes = (0..NUM_CLASSES)
+        .flat_map(|c| (0..5).map(move |i| (c * NODES_PER_CLASS + i) as u32))
+        .collect::<Vec<_>>();
+    let train_nodes = Tensor::new(train_nodes, &dev)?;
+    let train_labels = labels.index_select(&train_nodes, 0)?;
+
+    let varmap = VarMap::new();
+    let vb = VarBuilder::from_varmap(&varmap, DType::F32, &dev);
+    let model = Gcn::new(vb)?;
+    let params = ParamsAdamW {
+        lr: 0.01,
+        weight_decay: 5e-4,
+        ..Default::default()
+    };
+    let mut opt = AdamW::new(varmap.all_vars(), params)?;
+    for epoch in 0..200 {
+        let logits = model.forward(&xs, &edges)?;
+        let loss = loss::cross_entropy(&logits.index_select(&train_nodes, 0)?, &train_labels)?;
+        opt.backward_step(&loss)?;
+        if epoch % 20 == 0 {
+            let accuracy = logits
+                .argmax(D::Minus1)?
+                .eq(&labels)?
+                .to_dtype(DType::F32)?
+                .mean_all()?
+                .to_scalar::<f32>()?;
+            println!(
+                "{epoch:3} loss {:.4} accuracy {:.2}%",
+                loss.to_scalar::<f32>()?,
+                100. * accuracy
+            );
+        }
+    }
+    Ok(())
+}
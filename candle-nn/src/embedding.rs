@@ -1,5 +1,6 @@
 //! Embedding Layer.
-use candle::{Result, Tensor};
+use candle::sparse::CooTensor;
+use candle::{DType, Module, Result, Tensor, Var};
 
 #[derive(Clone, Debug)]
 pub struct Embedding {
@@ -47,3 +48,178 @@ pub fn embedding(in_size: usize, out_size: usize, vb: crate::VarBuilder) -> Resu
     )?;
     Ok(Embedding::new(embeddings, out_size))
 }
+
+impl Embedding {
+    /// Looks up the embeddings for `indexes` and returns them as a new variable that is detached
+    /// from the embedding matrix. This way the backward pass does not compute a dense gradient
+    /// for the whole embedding matrix, the gradient for the returned variable can then be turned
+    /// into a sparse gradient using [`Embedding::sparse_grad`].
+    pub fn forward_sparse(&self, indexes: &Tensor) -> Result<Var> {
+        let ys = self.forward(indexes)?.detach();
+        Var::from_tensor(&ys)
+    }
+
+    /// Returns the gradient of the embedding matrix as a sparse tensor, only the rows that
+    /// have been looked up in `indexes` are stored. `grad_output` is the gradient of the output
+    /// of the forward pass for these indexes, see [`Embedding::forward_sparse`].
+    pub fn sparse_grad(&self, indexes: &Tensor, grad_output: &Tensor) -> Result<CooTensor> {
+        let indexes = indexes.flatten_all()?.to_dtype(DType::U32)?;
+        let nnz = indexes.dim(0)?;
+        let values = grad_output.reshape((nnz, self.hidden_size))?;
+        let vocab_size = self.embeddings.dim(0)?;
+        CooTensor::new(
+            indexes.unsqueeze(0)?,
+            values,
+            (vocab_size, self.hidden_size),
+        )?
+        .coalesce()
+    }
+}
+
+/// The reduction applied over the embeddings of a bag.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EmbeddingBagMode {
+    Sum,
+    Mean,
+    Max,
+}
+
+/// Computes sums, means or maxes of bags of embeddings.
+///
+/// The embeddings of all the indexes are gathered with `index_select` before being reduced, so
+/// the intermediate `(n_indexes, hidden_size)` tensor is instantiated. With offsets, the sums and
+/// means are reduced with a single `index_add` whereas the maxes are computed bag by bag.
+///
+/// The bags can either be given as a 2D tensor of indexes where each row is a bag of fixed
+/// size, via the `Module` trait, or as a flat tensor of indexes together with the offset at
+/// which each bag starts using [`EmbeddingBag::forward_with_offsets`]. Empty bags result in
+/// zero vectors.
+#[derive(Clone, Debug)]
+pub struct EmbeddingBag {
+    embeddings: Tensor,
+    hidden_size: usize,
+    mode: EmbeddingBagMode,
+}
+
+impl EmbeddingBag {
+    pub fn new(embeddings: Tensor, hidden_size: usize, mode: EmbeddingBagMode) -> Self {
+        Self {
+            embeddings,
+            hidden_size,
+            mode,
+        }
+    }
+
+    pub fn embeddings(&self) -> &Tensor {
+        &self.embeddings
+    }
+
+    pub fn hidden_size(&self) -> usize {
+        self.hidden_size
+    }
+
+    pub fn mode(&self) -> EmbeddingBagMode {
+        self.mode
+    }
+
+    /// Reduces the bags of embeddings, bag `i` being made of the indexes between `offsets[i]`
+    /// and `offsets[i + 1]`, the last bag ending at the end of `indexes`.
+    ///
+    /// `per_sample_weights` can only be used with the `Sum` mode, in which case each embedding is
+    /// scaled by the matching weight before the reduction.
+    pub fn forward_with_offsets(
+        &self,
+        indexes: &Tensor,
+        offsets: &Tensor,
+        per_sample_weights: Option<&Tensor>,
+    ) -> Result<Tensor> {
+        let n_indexes = indexes.dims1()?;
+        let offsets = offsets.to_dtype(DType::U32)?.to_vec1::<u32>()?;
+        let n_bags = offsets.len();
+        let mut bag_ids = Vec::with_capacity(n_indexes);
+        let mut bag_sizes = Vec::with_capacity(n_bags);
+        for (bag_id, &start) in offsets.iter().enumerate() {
+            let end = offsets.get(bag_id + 1).map_or(n_indexes, |&o| o as usize);
+            let start = start as usize;
+            if start > end || end > n_indexes {
+                candle::bail!("invalid offsets {offsets:?} for {n_indexes} indexes")
+            }
+            bag_ids.extend(std::iter::repeat_n(bag_id as u32, end - start));
+            bag_sizes.push((start, end - start));
+        }
+        let values = self.embeddings.index_select(indexes, 0)?;
+        let values = match (per_sample_weights, self.mode) {
+            (None, _) => values,
+            (Some(w), EmbeddingBagMode::Sum) => values.broadcast_mul(&w.unsqueeze(1)?)?,
+            (Some(_), mode) => {
+                candle::bail!("per_sample_weights are only supported with Sum, got {mode:?}")
+            }
+        };
+        let zeros = Tensor::zeros((n_bags, self.hidden_size), values.dtype(), values.device())?;
+        match self.mode {
+            EmbeddingBagMode::Sum | EmbeddingBagMode::Mean => {
+                let bag_ids = Tensor::new(bag_ids, values.device())?;
+                let sums = zeros.index_add(&bag_ids, &values, 0)?;
+                if self.mode == EmbeddingBagMode::Sum {
+                    return Ok(sums);
+                }
+                let counts = bag_sizes
+                    .iter()
+                    .map(|&(_, size)| size.max(1) as f32)
+                    .collect::<Vec<_>>();
+                let counts = Tensor::new(counts, values.device())?.to_dtype(values.dtype())?;
+                sums.broadcast_div(&counts.unsqueeze(1)?)
+            }
+            EmbeddingBagMode::Max => {
+                let bags = bag_sizes
+                    .iter()
+                    .enumerate()
+                    .map(|(bag_id, &(start, size))| {
+                        if size == 0 {
+                            zeros.get(bag_id)
+                        } else {
+                            values.narrow(0, start, size)?.max(0)
+                        }
+                    })
+                    .collect::<Result<Vec<_>>>()?;
+                Tensor::stack(&bags, 0)
+            }
+        }
+    }
+}
+
+impl crate::Module for EmbeddingBag {
+    fn forward(&self, indexes: &Tensor) -> Result<Tensor> {
+        let (n_bags, bag_size) = indexes.dims2()?;
+        if bag_size == 0 {
+            let dtype = self.embeddings.dtype();
+            return Tensor::zeros((n_bags, self.hidden_size), dtype, indexes.device());
+        }
+        let values = self
+            .embeddings
+            .index_select(&indexes.flatten_all()?, 0)?
+            .reshape((n_bags, bag_size, self.hidden_size))?;
+        match self.mode {
+            EmbeddingBagMode::Sum => values.sum(1),
+            EmbeddingBagMode::Mean => values.mean(1),
+            EmbeddingBagMode::Max => values.max(1),
+        }
+    }
+}
+
+pub fn embedding_bag(
+    in_size: usize,
+    out_size: usize,
+    mode: EmbeddingBagMode,
+    vb: crate::VarBuilder,
+) -> Result<EmbeddingBag> {
+    let embeddings = vb.get_with_hints(
+        (in_size, out_size),
+        "weight",
+        crate::Init::Randn {
+            mean: 0.,
+            stdev: 1.,
+        },
+    )?;
+    Ok(EmbeddingBag::new(embeddings, out_size, mode))
+}
@@ -0,0 +1,396 @@
+//! Graph neural network layers.
+//!
+//! Graphs are represented with an [`EdgeIndex`], the list of the source and destination nodes
+//! of each edge. Node features are stored in a `(num_nodes, features)` tensor. Message passing
+//! gathers the features of the source nodes with `index_select` and aggregates the messages on
+//! the destination nodes with [`scatter_reduce`].
+//!
+//! ```rust
+//! use candle::{DType, Device, Tensor};
+//! use candle_nn::graph::{gcn_conv, EdgeIndex};
+//! # fn main() -> candle::Result<()> {
+//! let dev = Device::Cpu;
+//! let vb = candle_nn::VarBuilder::zeros(DType::F32, &dev);
+//! let edges = EdgeIndex::from_pairs(&[(0, 1), (1, 2), (2, 0)], 3, &dev)?.to_undirected()?;
+//! let conv = gcn_conv(4, 8, vb)?;
+//! let xs = Tensor::ones((3, 4), DType::F32, &dev)?;
+//! let ys = conv.forward(&xs, &edges)?;
+//! assert_eq!(ys.dims(), &[3, 8]);
+//! # Ok(()) }
+//! ```
+use candle::op::ScatterReduceOp;
+use candle::{DType, Device, Result, Tensor, D};
+
+/// The edges of a directed graph, edge `i` goes from node `src[i]` to node `dst[i]`.
+#[derive(Debug, Clone)]
+pub struct EdgeIndex {
+    src: Tensor,
+    dst: Tensor,
+    num_nodes: usize,
+}
+
+impl EdgeIndex {
+    pub fn new(src: Tensor, dst: Tensor, num_nodes: usize) -> Result<Self> {
+        let num_edges = src.dims1()?;
+        if dst.dims1()? != num_edges {
+            candle::bail!("src and dst have different lengths {src:?} {dst:?}")
+        }
+        let src = src.to_dtype(DType::U32)?;
+        let dst = dst.to_dtype(DType::U32)?;
+        for t in [&src, &dst] {
+            if num_edges > 0 && t.max(0)?.to_scalar::<u32>()? as usize >= num_nodes {
+                candle::bail!("edge index out of range for {num_nodes} nodes")
+            }
+        }
+        Ok(Self {
+            src,
+            dst,
+            num_nodes,
+        })
+    }
+
+    /// Creates an edge index from a `(2, num_edges)` tensor, the first row holding the source
+    /// nodes and the second one the destination nodes.
+    pub fn from_tensor(edge_index: &Tensor, num_nodes: usize) -> Result<Self> {
+        let (two, _num_edges) = edge_index.dims2()?;
+        if two != 2 {
+            candle::bail!("expected an edge index of shape (2, num_edges), got {edge_index:?}")
+        }
+        Self::new(edge_index.get(0)?, edge_index.get(1)?, num_nodes)
+    }
+
+    pub fn from_pairs(pairs: &[(u32, u32)], num_nodes: usize, device: &Device) -> Result<Self> {
+        let src = pairs.iter().map(|p| p.0).collect::<Vec<_>>();
+        let dst = pairs.iter().map(|p| p.1).collect::<Vec<_>>();
+        Self::new(
+            Tensor::new(src, device)?,
+            Tensor::new(dst, device)?,
+            num_nodes,
+        )
+    }
+
+    pub fn src(&self) -> &Tensor {
+        &self.src
+    }
+
+    pub fn dst(&self) -> &Tensor {
+        &self.dst
+    }
+
+    pub fn num_nodes(&self) -> usize {
+        self.num_nodes
+    }
+
+    pub fn num_edges(&self) -> usize {
+        self.src.dims()[0]
+    }
+
+    pub fn device(&self) -> &Device {
+        self.src.device()
+    }
+
+    /// Returns the edge index with an additional edge from each node to itself.
+    pub fn add_self_loops(&self) -> Result<Self> {
+        let nodes = Tensor::arange(0u32, self.num_nodes as u32, self.device())?;
+        Ok(Self {
+            src: Tensor::cat(&[&self.src, &nodes], 0)?,
+            dst: Tensor::cat(&[&self.dst, &nodes], 0)?,
+            num_nodes: self.num_nodes,
+        })
+    }
+
+    /// Returns the edge index with the reversed edges added, duplicate edges are removed.
+    pub fn to_undirected(&self) -> Result<Self> {
+        let src = self.src.to_vec1::<u32>()?;
+        let dst = self.dst.to_vec1::<u32>()?;
+        let mut pairs = src
+            .iter()
+            .zip(dst.iter())
+            .flat_map(|(&s, &d)| [(s, d), (d, s)])
+            .collect::<Vec<_>>();
+        pairs.sort_unstable();
+        pairs.dedup();
+        Self::from_pairs(&pairs, self.num_nodes, self.device())
+    }
+
+    /// The number of incoming edges for each node, as a `(num_nodes,)` tensor of type `dtype`.
+    pub fn in_degree(&self, dtype: DType) -> Result<Tensor> {
+        let ones = Tensor::ones(self.num_edges(), dtype, self.device())?;
+        Tensor::zeros(self.num_nodes, dtype, self.device())?.index_add(&self.dst, &ones, 0)
+    }
+}
+
+/// The reduction used to aggregate the messages arriving on a node.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Reduce {
+    Sum,
+    Mean,
+    Max,
+    Min,
+}
+
+/// Aggregates the rows of `src` into a `(dim_size, ...)` tensor, row `i` of `src` being reduced
+/// into row `index[i]` of the output. Rows of the output that receive no values are set to zero.
+pub fn scatter_reduce(
+    src: &Tensor,
+    index: &Tensor,
+    dim_size: usize,
+    reduce: Reduce,
+) -> Result<Tensor> {
+    let mut dims = src.dims().to_vec();
+    dims[0] = dim_size;
+    let zeros = Tensor::zeros(dims, src.dtype(), src.device())?;
+    match reduce {
+        Reduce::Sum => zeros.index_add(index, src, 0),
+        Reduce::Mean => {
+            let ones = Tensor::ones(index.dims1()?, src.dtype(), src.device())?;
+            let counts = Tensor::zeros(dim_size, src.dtype(), src.device())?
+                .index_add(index, &ones, 0)?
+                .clamp(1f64, f64::INFINITY)?;
+            let mut shape = vec![1; src.rank()];
+            shape[0] = dim_size;
+            zeros
+                .index_add(index, src, 0)?
+                .broadcast_div(&counts.reshape(shape)?)
+        }
+        Reduce::Max | Reduce::Min => {
+            let op = if reduce == Reduce::Max {
+                ScatterReduceOp::Max
+            } else {
+                ScatterReduceOp::Min
+            };
+            // `index_reduce` is only available on the cpu, the reduction goes through it for the
+            // other devices.
+            let device = src.device();
+            let cpu = &Device::Cpu;
+            zeros
+                .to_device(cpu)?
+                .index_reduce(&index.to_device(cpu)?, &src.to_device(cpu)?, 0, op, false)?
+                .to_device(device)
+        }
+    }
+}
+
+/// Graph convolution from [Semi-Supervised Classification with Graph Convolutional
+/// Networks](https://arxiv.org/abs/1609.02907).
+///
+/// Self-loops are added and the messages are normalized by `1 / sqrt(deg(i) deg(j))`.
+#[derive(Clone, Debug)]
+pub struct GCNConv {
+    weight: Tensor,
+    bias: Option<Tensor>,
+}
+
+impl GCNConv {
+    pub fn new(weight: Tensor, bias: Option<Tensor>) -> Self {
+        Self { weight, bias }
+    }
+
+    pub fn forward(&self, xs: &Tensor, edges: &EdgeIndex) -> Result<Tensor> {
+        let edges = edges.add_self_loops()?;
+        let xs = xs.matmul(&self.weight.t()?)?;
+        let deg_inv_sqrt = edges.in_degree(xs.dtype())?.sqrt()?.recip()?;
+        let norm = (deg_inv_sqrt.index_select(edges.src(), 0)?
+            * deg_inv_sqrt.index_select(edges.dst(), 0)?)?;
+        let messages = xs
+            .index_select(edges.src(), 0)?
+            .broadcast_mul(&norm.unsqueeze(1)?)?;
+        let ys = scatter_reduce(&messages, edges.dst(), edges.num_nodes(), Reduce::Sum)?;
+        match &self.bias {
+            None => Ok(ys),
+            Some(bias) => ys.broadcast_add(bias),
+        }
+    }
+}
+
+pub fn gcn_conv(in_dim: usize, out_dim: usize, vb: crate::VarBuilder) -> Result<GCNConv> {
+    let weight = vb.get_with_hints(
+        (out_dim, in_dim),
+        "weight",
+        crate::init::DEFAULT_KAIMING_NORMAL,
+    )?;
+    let bias = vb.get_with_hints(out_dim, "bias", crate::init::ZERO)?;
+    Ok(GCNConv::new(weight, Some(bias)))
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct GATConvConfig {
+    pub heads: usize,
+    /// Concatenate the outputs of the heads when true, average them otherwise.
+    pub concat: bool,
+    pub negative_slope: f64,
+    pub add_self_loops: bool,
+}
+
+impl Default for GATConvConfig {
+    fn default() -> Self {
+        Self {
+            heads: 1,
+            concat: true,
+            negative_slope: 0.2,
+            add_self_loops: true,
+        }
+    }
+}
+
+/// Graph attention layer from [Graph Attention Networks](https://arxiv.org/abs/1710.10903).
+#[derive(Clone, Debug)]
+pub struct GATConv {
+    weight: Tensor,
+    att_src: Tensor,
+    att_dst: Tensor,
+    bias: Option<Tensor>,
+    out_dim: usize,
+    config: GATConvConfig,
+}
+
+impl GATConv {
+    /// `weight` has shape `(heads * out_dim, in_dim)`, `att_src` and `att_dst` have shape
+    /// `(heads, out_dim)`.
+    pub fn new(
+        weight: Tensor,
+        att_src: Tensor,
+        att_dst: Tensor,
+        bias: Option<Tensor>,
+        config: GATConvConfig,
+    ) -> Result<Self> {
+        let (heads, out_dim) = att_src.dims2()?;
+        if heads != config.heads || att_dst.dims() != att_src.dims() {
+            candle::bail!("unexpected attention shapes {att_src:?} {att_dst:?} for {config:?}")
+        }
+        Ok(Self {
+            weight,
+            att_src,
+            att_dst,
+            bias,
+            out_dim,
+            config,
+        })
+    }
+
+    /// Returns the output node features together with the attention coefficients of shape
+    /// `(num_edges, heads)`, self-loops being included in the edges when enabled.
+    pub fn forward_with_attention(
+        &self,
+        xs: &Tensor,
+        edges: &EdgeIndex,
+    ) -> Result<(Tensor, Tensor)> {
+        let edges = if self.config.add_self_loops {
+            edges.add_self_loops()?
+        } else {
+            edges.clone()
+        };
+        let num_nodes = xs.dim(0)?;
+        let heads = self.config.heads;
+        let xs = xs
+            .matmul(&self.weight.t()?)?
+            .reshape((num_nodes, heads, self.out_dim))?;
+        let alpha_src = xs
+            .broadcast_mul(&self.att_src.unsqueeze(0)?)?
+            .sum(D::Minus1)?;
+        let alpha_dst = xs
+            .broadcast_mul(&self.att_dst.unsqueeze(0)?)?
+            .sum(D::Minus1)?;
+        let alpha =
+            (alpha_src.index_select(edges.src(), 0)? + alpha_dst.index_select(edges.dst(), 0)?)?;
+        let alpha = crate::ops::leaky_relu(&alpha, self.config.negative_slope)?;
+        // Softmax over the incoming edges of each node, the max is only used for numerical
+        // stability so there is no need to track its gradient.
+        let max = scatter_reduce(&alpha.detach(), edges.dst(), num_nodes, Reduce::Max)?;
+        let alpha = (alpha - max.index_select(edges.dst(), 0)?)?.exp()?;
+        let denom = scatter_reduce(&alpha, edges.dst(), num_nodes, Reduce::Sum)?;
+        let alpha = (alpha / denom.index_select(edges.dst(), 0)?)?;
+        let messages = xs
+            .index_select(edges.src(), 0)?
+            .broadcast_mul(&alpha.unsqueeze(D::Minus1)?)?;
+        let ys = scatter_reduce(&messages, edges.dst(), num_nodes, Reduce::Sum)?;
+        let ys = if self.config.concat {
+            ys.reshape((num_nodes, heads * self.out_dim))?
+        } else {
+            ys.mean(1)?
+        };
+        let ys = match &self.bias {
+            None => ys,
+            Some(bias) => ys.broadcast_add(bias)?,
+        };
+        Ok((ys, alpha))
+    }
+
+    pub fn forward(&self, xs: &Tensor, edges: &EdgeIndex) -> Result<Tensor> {
+        Ok(self.forward_with_attention(xs, edges)?.0)
+    }
+}
+
+pub fn gat_conv(
+    in_dim: usize,
+    out_dim: usize,
+    config: GATConvConfig,
+    vb: crate::VarBuilder,
+) -> Result<GATConv> {
+    let heads = config.heads;
+    let init = crate::init::DEFAULT_KAIMING_NORMAL;
+    let weight = vb.get_with_hints((heads * out_dim, in_dim), "weight", init)?;
+    let att_src = vb.get_with_hints((heads, out_dim), "att_src", init)?;
+    let att_dst = vb.get_with_hints((heads, out_dim), "att_dst", init)?;
+    let bias_dim = if config.concat {
+        heads * out_dim
+    } else {
+        out_dim
+    };
+    let bias = vb.get_with_hints(bias_dim, "bias", crate::init::ZERO)?;
+    GATConv::new(weight, att_src, att_dst, Some(bias), config)
+}
+
+/// GraphSAGE layer from [Inductive Representation Learning on Large
+/// Graphs](https://arxiv.org/abs/1706.02216), `y_i = W_r x_i + W_n aggr_{j -> i} x_j`.
+#[derive(Clone, Debug)]
+pub struct SAGEConv {
+    lin_root: crate::Linear,
+    lin_neigh: crate::Linear,
+    aggr: Reduce,
+    normalize: bool,
+}
+
+impl SAGEConv {
+    pub fn new(
+        lin_root: crate::Linear,
+        lin_neigh: crate::Linear,
+        aggr: Reduce,
+        normalize: bool,
+    ) -> Self {
+        Self {
+            lin_root,
+            lin_neigh,
+            aggr,
+            normalize,
+        }
+    }
+
+    pub fn forward(&self, xs: &Tensor, edges: &EdgeIndex) -> Result<Tensor> {
+        use candle::Module;
+        let messages = xs.index_select(edges.src(), 0)?;
+        let neigh = scatter_reduce(&messages, edges.dst(), xs.dim(0)?, self.aggr)?;
+        let ys = (self.lin_neigh.forward(&neigh)? + self.lin_root.forward(xs)?)?;
+        if self.normalize {
+            let norm = ys
+                .sqr()?
+                .sum_keepdim(D::Minus1)?
+                .sqrt()?
+                .clamp(1e-12, f64::INFINITY)?;
+            ys.broadcast_div(&norm)
+        } else {
+            Ok(ys)
+        }
+    }
+}
+
+pub fn sage_conv(
+    in_dim: usize,
+    out_dim: usize,
+    aggr: Reduce,
+    vb: crate::VarBuilder,
+) -> Result<SAGEConv> {
+    let lin_root = crate::linear_no_bias(in_dim, out_dim, vb.pp("lin_root"))?;
+    let lin_neigh = crate::linear(in_dim, out_dim, vb.pp("lin_neigh"))?;
+    Ok(SAGEConv::new(lin_root, lin_neigh, aggr, false))
+}
@@ -0,0 +1,358 @@
+//! Module introspection: named parameters, parameter counts and model summaries.
+//!
+//! Candle modules are plain structs so there is no generic way to walk their parameters once
+//! they have been built. The names are known at construction time though, as every tensor goes
+//! through a [`VarBuilder`]. A [`ParamRecorder`] can be attached to a `VarBuilder` via
+//! [`VarBuilder::record`] to keep track of every tensor that the model retrieves.
+//!
+//! ```rust
+//! use candle::{DType, Device, Module, Tensor};
+//! use candle_nn::{introspect::ParamRecorder, VarBuilder, VarMap};
+//!
+//! let varmap = VarMap::new();
+//! let recorder = ParamRecorder::new();
+//! let vb = VarBuilder::from_varmap(&varmap, DType::F32, &Device::Cpu).record(&recorder);
+//! let l1 = candle_nn::linear(4, 8, vb.pp("encoder.l1"))?;
+//! let l2 = candle_nn::linear(8, 2, vb.pp("head"))?;
+//! assert_eq!(recorder.num_parameters(), 4 * 8 + 8 + 8 * 2 + 2);
+//!
+//! // Only train the head.
+//! recorder.freeze("encoder");
+//! assert_eq!(recorder.trainable_vars()?.len(), 2);
+//! println!("{}", recorder.summary(1));
+//! # let _ = (l1, l2);
+//! # Ok::<(), candle::Error>(())
+//! ```
+//!
+//! [`VarBuilder`]: crate::VarBuilder
+//! [`VarBuilder::record`]: crate::var_builder::VarBuilderArgs::record
+use candle::{DType, Result, Tensor, Var};
+use std::collections::BTreeMap;
+use std::sync::{Arc, Mutex};
+
+/// A parameter retrieved through a recording `VarBuilder`.
+#[derive(Debug, Clone)]
+pub struct NamedParameter {
+    pub name: String,
+    pub tensor: Tensor,
+    pub frozen: bool,
+}
+
+impl NamedParameter {
+    pub fn num_parameters(&self) -> usize {
+        self.tensor.elem_count()
+    }
+
+    pub fn num_bytes(&self) -> usize {
+        self.tensor.elem_count() * self.tensor.dtype().size_in_bytes()
+    }
+}
+
+/// Returns true if `name` is `prefix` itself or lives under the `prefix` sub-tree.
+fn is_under(name: &str, prefix: &str) -> bool {
+    prefix.is_empty()
+        || name == prefix
+        || (name.starts_with(prefix) && name.as_bytes().get(prefix.len()) == Some(&b'.'))
+}
+
+#[derive(Debug, Default)]
+struct RecorderData {
+    params: Vec<NamedParameter>,
+    index: BTreeMap<String, usize>,
+}
+
+/// Records the tensors that a model retrieves from a `VarBuilder`.
+///
+/// The recorder is cheap to clone, all the clones share the same underlying storage. Parameters
+/// are kept in the order in which they were first requested, requesting the same name multiple
+/// times only records it once.
+#[derive(Debug, Clone, Default)]
+pub struct ParamRecorder {
+    data: Arc<Mutex<RecorderData>>,
+}
+
+impl ParamRecorder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub(crate) fn record(&self, name: &str, tensor: &Tensor) {
+        let mut data = self.data.lock().unwrap();
+        match data.index.get(name) {
+            Some(&idx) => data.params[idx].tensor = tensor.clone(),
+            None => {
+                let idx = data.params.len();
+                data.params.push(NamedParameter {
+                    name: name.to_string(),
+                    tensor: tensor.clone(),
+                    frozen: false,
+                });
+                data.index.insert(name.to_string(), idx);
+            }
+        }
+    }
+
+    /// All the recorded parameters, in the order in which they were retrieved.
+    pub fn named_parameters(&self) -> Vec<NamedParameter> {
+        self.data.lock().unwrap().params.clone()
+    }
+
+    /// The recorded parameters that live under `prefix`.
+    pub fn named_parameters_with_prefix(&self, prefix: &str) -> Vec<NamedParameter> {
+        let data = self.data.lock().unwrap();
+        data.params
+            .iter()
+            .filter(|p| is_under(&p.name, prefix))
+            .cloned()
+            .collect()
+    }
+
+    /// Returns the parameter recorded under `name` if any.
+    pub fn get(&self, name: &str) -> Option<Tensor> {
+        let data = self.data.lock().unwrap();
+        data.index
+            .get(name)
+            .map(|&idx| data.params[idx].tensor.clone())
+    }
+
+    pub fn len(&self) -> usize {
+        self.data.lock().unwrap().params.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// The total number of scalar values across all the recorded parameters.
+    pub fn num_parameters(&self) -> usize {
+        self.named_parameters_with_prefix("")
+            .iter()
+            .map(|p| p.num_parameters())
+            .sum()
+    }
+
+    /// The number of scalar values across the parameters that are not frozen.
+    pub fn num_trainable_parameters(&self) -> usize {
+        self.named_parameters_with_prefix("")
+            .iter()
+            .filter(|p| !p.frozen)
+            .map(|p| p.num_parameters())
+            .sum()
+    }
+
+    /// The total memory used by the recorded parameters, in bytes.
+    pub fn num_bytes(&self) -> usize {
+        self.named_parameters_with_prefix("")
+            .iter()
+            .map(|p| p.num_bytes())
+            .sum()
+    }
+
+    fn set_frozen(&self, prefix: &str, frozen: bool) -> usize {
+        let mut data = self.data.lock().unwrap();
+        let mut cnt = 0;
+        for p in data.params.iter_mut() {
+            if is_under(&p.name, prefix) {
+                p.frozen = frozen;
+                cnt += 1
+            }
+        }
+        cnt
+    }
+
+    /// Freezes all the parameters under `prefix`, an empty prefix freezes the whole model.
+    /// Returns the number of parameters that matched the prefix.
+    pub fn freeze(&self, prefix: &str) -> usize {
+        self.set_frozen(prefix, true)
+    }
+
+    /// Unfreezes all the parameters under `prefix`, an empty prefix unfreezes the whole model.
+    /// Returns the number of parameters that matched the prefix.
+    pub fn unfreeze(&self, prefix: &str) -> usize {
+        self.set_frozen(prefix, false)
+    }
+
+    pub fn is_frozen(&self, name: &str) -> bool {
+        let data = self.data.lock().unwrap();
+        data.index
+            .get(name)
+            .is_some_and(|&idx| data.params[idx].frozen)
+    }
+
+    /// The variables that should be passed to an optimizer, i.e. the recorded parameters that
+    /// are not frozen.
+    ///
+    /// An error is returned if one of these parameters is not backed by a variable, e.g. when
+    /// the model weights have been loaded from a safetensors file rather than a `VarMap`.
+    pub fn trainable_vars(&self) -> Result<Vec<Var>> {
+        let data = self.data.lock().unwrap();
+        let mut vars = Vec::new();
+        for p in data.params.iter().filter(|p| !p.frozen) {
+            if !p.tensor.is_variable() {
+                candle::bail!("parameter {} is not backed by a variable", p.name)
+            }
+            vars.push(Var::from_tensor(&p.tensor)?)
+        }
+        Ok(vars)
+    }
+
+    /// Aggregates the recorded parameters per module, the modules are the prefixes of the
+    /// parameter names with at most `max_depth` components. A `max_depth` of 0 only returns
+    /// the model root.
+    pub fn summary(&self, max_depth: usize) -> ModelSummary {
+        let params = self.named_parameters();
+        let mut rows: Vec<ModuleSummary> = vec![];
+        let mut index: BTreeMap<String, usize> = BTreeMap::new();
+        let mut total = ModuleSummary::new("", 0);
+        for p in params.iter() {
+            total.add(p);
+            let parts: Vec<&str> = p.name.split('.').collect();
+            // The last component is the tensor name, e.g. "weight", not a module.
+            let depth = usize::min(max_depth, parts.len().saturating_sub(1));
+            for d in 1..=depth {
+                let path = parts[..d].join(".");
+                let idx = match index.get(&path) {
+                    Some(&idx) => idx,
+                    None => {
+                        rows.push(ModuleSummary::new(&path, d));
+                        index.insert(path, rows.len() - 1);
+                        rows.len() - 1
+                    }
+                };
+                rows[idx].add(p)
+            }
+        }
+        ModelSummary { rows, total }
+    }
+}
+
+/// Parameter statistics for a single module of the model.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ModuleSummary {
+    /// The module prefix, empty for the model root.
+    pub path: String,
+    pub depth: usize,
+    pub num_tensors: usize,
+    pub num_parameters: usize,
+    pub num_trainable_parameters: usize,
+    pub num_bytes: usize,
+    /// The dtypes used by the tensors of this module, sorted and deduplicated.
+    pub dtypes: Vec<DType>,
+}
+
+impl ModuleSummary {
+    fn new(path: &str, depth: usize) -> Self {
+        Self {
+            path: path.to_string(),
+            depth,
+            num_tensors: 0,
+            num_parameters: 0,
+            num_trainable_parameters: 0,
+            num_bytes: 0,
+            dtypes: vec![],
+        }
+    }
+
+    fn add(&mut self, p: &NamedParameter) {
+        self.num_tensors += 1;
+        self.num_parameters += p.num_parameters();
+        if !p.frozen {
+            self.num_trainable_parameters += p.num_parameters();
+        }
+        self.num_bytes += p.num_bytes();
+        let dtype = p.tensor.dtype();
+        if !self.dtypes.contains(&dtype) {
+            self.dtypes.push(dtype);
+            self.dtypes.sort_by_key(|d| d.as_str());
+        }
+    }
+}
+
+/// A per-module breakdown of the model parameters, use the `Display` implementation to print it
+/// as a table.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ModelSummary {
+    /// One row per module, in the order in which the modules were first encountered.
+    pub rows: Vec<ModuleSummary>,
+    /// The statistics for the whole model.
+    pub total: ModuleSummary,
+}
+
+impl ModelSummary {
+    pub fn get(&self, path: &str) -> Option<&ModuleSummary> {
+        if path.is_empty() {
+            return Some(&self.total);
+        }
+        self.rows.iter().find(|r| r.path == path)
+    }
+}
+
+fn format_count(v: usize) -> String {
+    let s = v.to_string();
+    let mut out = String::with_capacity(s.len() + s.len() / 3);
+    for (i, c) in s.chars().enumerate() {
+        if i > 0 && (s.len() - i).is_multiple_of(3) {
+            out.push(',')
+        }
+        out.push(c)
+    }
+    out
+}
+
+fn format_bytes(v: usize) -> String {
+    const UNITS: [&str; 5] = ["B", "KiB", "MiB", "GiB", "TiB"];
+    let mut v = v as f64;
+    let mut unit = 0;
+    while v >= 1024. && unit + 1 < UNITS.len() {
+        v /= 1024.;
+        unit += 1
+    }
+    if unit == 0 {
+        format!("{v} {}", UNITS[unit])
+    } else {
+        format!("{v:.2} {}", UNITS[unit])
+    }
+}
+
+impl std::fmt::Display for ModelSummary {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let name = |r: &ModuleSummary| {
+            let last = r.path.rsplit('.').next().unwrap_or("");
+            format!("{}{last}", "  ".repeat(r.depth.saturating_sub(1)))
+        };
+        let dtypes = |r: &ModuleSummary| {
+            let d: Vec<&str> = r.dtypes.iter().map(|d| d.as_str()).collect();
+            d.join(",")
+        };
+        let name_w = self
+            .rows
+            .iter()
+            .map(|r| name(r).len())
+            .max()
+            .unwrap_or(0)
+            .max("Module".len());
+        let header = format!(
+            "{:<name_w$}  {:>8}  {:>14}  {:>14}  {:>11}  dtype",
+            "Module", "tensors", "params", "trainable", "size"
+        );
+        writeln!(f, "{header}")?;
+        writeln!(f, "{}", "-".repeat(header.len()))?;
+        let write_row = |f: &mut std::fmt::Formatter<'_>, n: &str, r: &ModuleSummary| {
+            writeln!(
+                f,
+                "{:<name_w$}  {:>8}  {:>14}  {:>14}  {:>11}  {}",
+                n,
+                r.num_tensors,
+                format_count(r.num_parameters),
+                format_count(r.num_trainable_parameters),
+                format_bytes(r.num_bytes),
+                dtypes(r)
+            )
+        };
+        for r in self.rows.iter() {
+            write_row(f, &name(r), r)?;
+        }
+        writeln!(f, "{}", "-".repeat(header.len()))?;
+        write_row(f, "Total", &self.total)
+    }
+}
@@ -1,5 +1,7 @@
 //! Cache Implementations
 //!
+use candle::quantized::k_quants::{BlockQ4_0, BlockQ8_0};
+use candle::quantized::GgmlType;
 use candle::{DType, Device, Result, Tensor};
 
 #[derive(Debug, Clone)]
@@ -148,6 +150,88 @@ impl KvCache {
         self.k.reset();
         self.v.reset();
     }
+
+    /// Returns a copy of the cached keys and values, `None` if the cache is empty.
+    pub fn snapshot(&self) -> Result<Option<KvSnapshot>> {
+        // The underlying buffers get updated in place so the data has to be copied.
+        match (self.k()?, self.v()?) {
+            (Some(k), Some(v)) => Ok(Some(KvSnapshot::new(k.copy()?, v.copy()?, self.k.dim)?)),
+            _ => Ok(None),
+        }
+    }
+
+    /// Replaces the content of the cache with the snapshot.
+    pub fn restore(&mut self, snapshot: &KvSnapshot) -> Result<()> {
+        self.reset();
+        let k = snapshot.k().contiguous()?;
+        let v = snapshot.v().contiguous()?;
+        self.append(&k, &v)?;
+        Ok(())
+    }
+}
+
+/// An immutable copy of the keys and values held by a KV-cache.
+///
+/// Snapshots can be taken and restored with the `snapshot` and `restore` methods of [`KvCache`],
+/// [`ConcatKvCache`] and [`QuantizedKvCache`], they can be truncated to resume from a shorter
+/// prefix of the cached sequence.
+#[derive(Debug, Clone)]
+pub struct KvSnapshot {
+    k: Tensor,
+    v: Tensor,
+    dim: usize,
+}
+
+impl KvSnapshot {
+    pub fn new(k: Tensor, v: Tensor, dim: usize) -> Result<Self> {
+        if k.dim(dim)? != v.dim(dim)? {
+            candle::bail!(
+                "kv-snapshot shape mismatch on dim {dim}, {:?} {:?}",
+                k.shape(),
+                v.shape()
+            )
+        }
+        Ok(Self { k, v, dim })
+    }
+
+    pub fn k(&self) -> &Tensor {
+        &self.k
+    }
+
+    pub fn v(&self) -> &Tensor {
+        &self.v
+    }
+
+    pub fn dim(&self) -> usize {
+        self.dim
+    }
+
+    pub fn seq_len(&self) -> usize {
+        self.k.dims()[self.dim]
+    }
+
+    /// Only keeps the first `len` elements of the sequence.
+    pub fn truncate(&self, len: usize) -> Result<Self> {
+        if len > self.seq_len() {
+            candle::bail!(
+                "cannot truncate a kv-snapshot of length {} to {len}",
+                self.seq_len()
+            )
+        }
+        let k = self.k.narrow(self.dim, 0, len)?;
+        let v = self.v.narrow(self.dim, 0, len)?;
+        Ok(Self {
+            k,
+            v,
+            dim: self.dim,
+        })
+    }
+
+    pub fn size_in_bytes(&self) -> usize {
+        let k = self.k.elem_count() * self.k.dtype().size_in_bytes();
+        let v = self.v.elem_count() * self.v.dtype().size_in_bytes();
+        k + v
+    }
 }
 
 #[derive(Debug, Clone)]
@@ -760,6 +844,29 @@ impl ConcatKvCache {
         self.v = None;
     }
 
+    /// Keeps the first `len` positions of the cache, e.g. to roll back rejected tokens.
+    pub fn truncate(&mut self, len: usize) -> Result<()> {
+        let seq_len = self.current_seq_len();
+        if len > seq_len {
+            candle::bail!("cannot truncate a kv-cache of length {seq_len} to {len}")
+        }
+        if len == 0 {
+            self.reset()
+        } else if len < seq_len {
+            self.k = self
+                .k
+                .as_ref()
+                .map(|k| k.narrow(self.dim, 0, len))
+                .transpose()?;
+            self.v = self
+                .v
+                .as_ref()
+                .map(|v| v.narrow(self.dim, 0, len))
+                .transpose()?;
+        }
+        Ok(())
+    }
+
     /// Get reference to current K cache data
     ///
     /// Returns `None` if the cache is empty.
@@ -797,6 +904,518 @@ impl ConcatKvCache {
             _ => None,
         }
     }
+
+    /// Returns the cached keys and values, `None` if the cache is empty.
+    ///
+    /// The cache never modifies its tensors in place so this does not copy any data.
+    pub fn snapshot(&self) -> Result<Option<KvSnapshot>> {
+        match (&self.k, &self.v) {
+            (Some(k), Some(v)) => Ok(Some(KvSnapshot::new(k.clone(), v.clone(), self.dim)?)),
+            _ => Ok(None),
+        }
+    }
+
+    /// Replaces the content of the cache with the snapshot.
+    pub fn restore(&mut self, snapshot: &KvSnapshot) -> Result<()> {
+        self.k = Some(snapshot.k().contiguous()?);
+        self.v = Some(snapshot.v().contiguous()?);
+        self.dim = snapshot.dim();
+        Ok(())
+    }
+}
+
+/// The storage format used by [`QuantizedKvCache`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum QuantizedKvDType {
+    /// Blocks of 32 values stored as 8-bit integers with a f16 scale, 8.5 bits per value.
+    Q8_0,
+    /// Blocks of 32 values stored as 4-bit integers with a f16 scale, 4.5 bits per value.
+    Q4_0,
+    /// Each value is stored as a 8-bit float with 4 exponent bits and 3 mantissa bits, this
+    /// requires the `float8` feature.
+    #[cfg(feature = "float8")]
+    F8E4M3,
+}
+
+impl QuantizedKvDType {
+    /// The number of values that are quantized together, the size of the cached tensors along
+    /// the dimensions following the concatenation dimension has to be a multiple of it.
+    pub fn block_size(&self) -> usize {
+        match self {
+            Self::Q8_0 => BlockQ8_0::BLCK_SIZE,
+            Self::Q4_0 => BlockQ4_0::BLCK_SIZE,
+            #[cfg(feature = "float8")]
+            Self::F8E4M3 => 1,
+        }
+    }
+
+    /// The number of bytes used to store a block.
+    pub fn type_size(&self) -> usize {
+        match self {
+            Self::Q8_0 => std::mem::size_of::<BlockQ8_0>(),
+            Self::Q4_0 => std::mem::size_of::<BlockQ4_0>(),
+            #[cfg(feature = "float8")]
+            Self::F8E4M3 => std::mem::size_of::<float8::F8E4M3>(),
+        }
+    }
+}
+
+impl std::str::FromStr for QuantizedKvDType {
+    type Err = candle::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.to_lowercase().as_str() {
+            "q8_0" => Ok(Self::Q8_0),
+            "q4_0" => Ok(Self::Q4_0),
+            #[cfg(feature = "float8")]
+            "f8e4m3" | "fp8" => Ok(Self::F8E4M3),
+            #[cfg(not(feature = "float8"))]
+            "f8e4m3" | "fp8" => candle::bail!("the {s} kv-cache requires the float8 feature"),
+            _ => candle::bail!("unsupported kv-cache dtype {s}, expected q8_0, q4_0 or f8e4m3"),
+        }
+    }
+}
+
+trait KvBlock: Clone + Send + Sync {
+    const BLOCK_SIZE: usize;
+    fn zero() -> Self;
+    fn quantize(xs: &[f32], ys: &mut [Self]);
+    fn dequantize(xs: &[Self], ys: &mut [f32]);
+}
+
+macro_rules! ggml_kv_block {
+    ($ty:ty) => {
+        impl KvBlock for $ty {
+            const BLOCK_SIZE: usize = <$ty as GgmlType>::BLCK_SIZE;
+
+            fn zero() -> Self {
+                <$ty as GgmlType>::zeros()
+            }
+
+            fn quantize(xs: &[f32], ys: &mut [Self]) {
+                <$ty as GgmlType>::from_float(xs, ys)
+            }
+
+            fn dequantize(xs: &[Self], ys: &mut [f32]) {
+                <$ty as GgmlType>::to_float(xs, ys)
+            }
+        }
+    };
+}
+ggml_kv_block!(BlockQ8_0);
+ggml_kv_block!(BlockQ4_0);
+
+#[cfg(feature = "float8")]
+impl KvBlock for float8::F8E4M3 {
+    const BLOCK_SIZE: usize = 1;
+
+    fn zero() -> Self {
+        float8::F8E4M3::ZERO
+    }
+
+    fn quantize(xs: &[f32], ys: &mut [Self]) {
+        for (x, y) in xs.iter().zip(ys.iter_mut()) {
+            *y = float8::F8E4M3::from_f32(*x)
+        }
+    }
+
+    fn dequantize(xs: &[Self], ys: &mut [f32]) {
+        for (x, y) in xs.iter().zip(ys.iter_mut()) {
+            *y = x.to_f32()
+        }
+    }
+}
+
+// One vector of blocks per index over the dimensions preceding the concatenation dimension, each
+// append pushes the newly quantized blocks at the end of these vectors.
+fn quantize_rows<T: KvBlock>(rows: &mut [Vec<T>], data: &[f32], seq_len: usize, inner: usize) {
+    use rayon::prelude::*;
+    let chunk = seq_len * inner;
+    // Appending an empty sequence leaves the rows unchanged.
+    if chunk == 0 {
+        return;
+    }
+    rows.par_iter_mut()
+        .zip(data.par_chunks(chunk))
+        .for_each(|(row, data)| {
+            let start = row.len();
+            row.resize(start + chunk / T::BLOCK_SIZE, T::zero());
+            T::quantize(data, &mut row[start..])
+        })
+}
+
+fn dequantize_rows<T: KvBlock>(rows: &[Vec<T>], dst: &mut [f32]) {
+    use rayon::prelude::*;
+    if rows.is_empty() {
+        return;
+    }
+    let chunk = dst.len() / rows.len();
+    // Nothing has been appended yet.
+    if chunk == 0 {
+        return;
+    }
+    dst.par_chunks_mut(chunk)
+        .zip(rows.par_iter())
+        .for_each(|(dst, row)| T::dequantize(row, dst))
+}
+
+#[derive(Debug, Clone)]
+enum QuantizedRows {
+    Q8_0(Vec<Vec<BlockQ8_0>>),
+    Q4_0(Vec<Vec<BlockQ4_0>>),
+    #[cfg(feature = "float8")]
+    F8E4M3(Vec<Vec<float8::F8E4M3>>),
+}
+
+// Applies a function that is generic over the block type to the rows.
+macro_rules! with_rows {
+    ($rows:expr, $r:ident => $e:expr) => {
+        match $rows {
+            QuantizedRows::Q8_0($r) => $e,
+            QuantizedRows::Q4_0($r) => $e,
+            #[cfg(feature = "float8")]
+            QuantizedRows::F8E4M3($r) => $e,
+        }
+    };
+}
+
+/// The number of positions kept in full precision by default, see
+/// [`QuantizedCache::with_residual_len`].
+pub const DEFAULT_RESIDUAL_LEN: usize = 64;
+
+/// A cache storing its content block-quantized on the cpu.
+///
+/// The values are quantized over the dimensions that follow the concatenation dimension, for
+/// attention with shape `[batch, heads, seq, head_dim]` and `dim=2` this means that the head
+/// dimension has to be a multiple of [`QuantizedKvDType::block_size`].
+///
+/// The most recent positions are kept in full precision, the older ones get quantized as new
+/// positions are appended so each step only quantizes the positions leaving this window rather
+/// than requantizing anything. The quantized part is dequantized when read, the returned tensors
+/// use the dtype and device of the appended tensors.
+#[derive(Debug, Clone)]
+pub struct QuantizedCache {
+    rows: Option<QuantizedRows>,
+    dim: usize,
+    dtype: QuantizedKvDType,
+    // The shape of the quantized part of the cache, the entry at `dim` is its sequence length.
+    shape: Vec<usize>,
+    src: Option<(DType, Device)>,
+    // The most recent positions, in full precision.
+    residual: Option<Tensor>,
+    residual_len: usize,
+}
+
+impl QuantizedCache {
+    pub fn new(dim: usize, dtype: QuantizedKvDType) -> Self {
+        Self {
+            rows: None,
+            dim,
+            dtype,
+            shape: vec![],
+            src: None,
+            residual: None,
+            residual_len: DEFAULT_RESIDUAL_LEN,
+        }
+    }
+
+    /// Sets the number of most recent positions that are kept in full precision, with `0` all
+    /// the positions get quantized when appended.
+    pub fn with_residual_len(mut self, residual_len: usize) -> Self {
+        self.residual_len = residual_len;
+        self
+    }
+
+    pub fn dim(&self) -> usize {
+        self.dim
+    }
+
+    pub fn dtype(&self) -> QuantizedKvDType {
+        self.dtype
+    }
+
+    pub fn residual_len(&self) -> usize {
+        self.residual_len
+    }
+
+    /// The number of positions stored quantized.
+    pub fn quantized_seq_len(&self) -> usize {
+        self.shape.get(self.dim).copied().unwrap_or(0)
+    }
+
+    fn residual_seq_len(&self) -> usize {
+        self.residual.as_ref().map_or(0, |r| r.dims()[self.dim])
+    }
+
+    pub fn current_seq_len(&self) -> usize {
+        self.quantized_seq_len() + self.residual_seq_len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.src.is_none()
+    }
+
+    /// The number of bytes used by the cache, including the full precision positions.
+    pub fn size_in_bytes(&self) -> usize {
+        let quantized = match self.rows {
+            None => 0,
+            Some(_) => {
+                let n_values = self.shape.iter().product::<usize>();
+                n_values / self.dtype.block_size() * self.dtype.type_size()
+            }
+        };
+        let residual = self
+            .residual
+            .as_ref()
+            .map_or(0, |r| r.elem_count() * r.dtype().size_in_bytes());
+        quantized + residual
+    }
+
+    pub fn reset(&mut self) {
+        self.rows = None;
+        self.shape = vec![];
+        self.src = None;
+        self.residual = None;
+    }
+
+    /// Keeps the first `len` positions of the cache.
+    pub fn truncate(&mut self, len: usize) -> Result<()> {
+        let seq_len = self.current_seq_len();
+        if len > seq_len {
+            candle::bail!("cannot truncate a kv-cache of length {seq_len} to {len}")
+        }
+        if len == 0 {
+            self.reset();
+            return Ok(());
+        }
+        let quantized_len = self.quantized_seq_len();
+        if len >= quantized_len {
+            if let Some(residual) = self.residual.as_mut() {
+                *residual = residual.narrow(self.dim, 0, len - quantized_len)?
+            }
+            return Ok(());
+        }
+        self.residual = None;
+        let inner = self.shape[self.dim + 1..].iter().product::<usize>();
+        let n_blocks = len * inner / self.dtype.block_size();
+        if let Some(rows) = self.rows.as_mut() {
+            with_rows!(rows, rows => rows.iter_mut().for_each(|r| r.truncate(n_blocks)))
+        }
+        self.shape[self.dim] = len;
+        Ok(())
+    }
+
+    pub fn append(&mut self, src: &Tensor) -> Result<()> {
+        let dims = src.dims();
+        if self.dim >= dims.len() {
+            candle::bail!(
+                "kv-cache dim {} is out of range for shape {dims:?}",
+                self.dim
+            )
+        }
+        let inner = dims[self.dim + 1..].iter().product::<usize>();
+        let block_size = self.dtype.block_size();
+        if !inner.is_multiple_of(block_size) {
+            candle::bail!(
+                "{:?} kv-cache requires the dims after {} to be a multiple of {block_size}, got {dims:?}",
+                self.dtype,
+                self.dim
+            )
+        }
+        match &self.src {
+            None => {
+                self.src = Some((src.dtype(), src.device().clone()));
+                self.shape = dims.to_vec();
+                self.shape[self.dim] = 0;
+            }
+            Some(_) => {
+                let mut expected = self.shape.clone();
+                expected[self.dim] = dims[self.dim];
+                if expected != dims {
+                    let mut cached = self.shape.clone();
+                    cached[self.dim] = self.current_seq_len();
+                    candle::bail!("kv-cache shape mismatch, cached {cached:?}, appending {dims:?}")
+                }
+            }
+        }
+        let residual = match self.residual.take() {
+            None => src.clone(),
+            Some(residual) => Tensor::cat(&[&residual, src], self.dim)?,
+        };
+        // Quantize the positions that do not fit in the full precision window anymore.
+        let residual_seq_len = residual.dims()[self.dim];
+        let n_quantize = residual_seq_len.saturating_sub(self.residual_len);
+        if n_quantize > 0 {
+            self.quantize(&residual.narrow(self.dim, 0, n_quantize)?)?;
+        }
+        if n_quantize < residual_seq_len {
+            let residual = residual.narrow(self.dim, n_quantize, residual_seq_len - n_quantize)?;
+            self.residual = Some(residual.contiguous()?)
+        }
+        Ok(())
+    }
+
+    fn quantize(&mut self, src: &Tensor) -> Result<()> {
+        let dims = src.dims();
+        let seq_len = dims[self.dim];
+        let outer = dims[..self.dim].iter().product::<usize>();
+        let inner = dims[self.dim + 1..].iter().product::<usize>();
+        let data = src
+            .to_device(&Device::Cpu)?
+            .to_dtype(DType::F32)?
+            .flatten_all()?
+            .to_vec1::<f32>()?;
+        let rows = self.rows.get_or_insert_with(|| match self.dtype {
+            QuantizedKvDType::Q8_0 => QuantizedRows::Q8_0(vec![vec![]; outer]),
+            QuantizedKvDType::Q4_0 => QuantizedRows::Q4_0(vec![vec![]; outer]),
+            #[cfg(feature = "float8")]
+            QuantizedKvDType::F8E4M3 => QuantizedRows::F8E4M3(vec![vec![]; outer]),
+        });
+        with_rows!(rows, rows => quantize_rows(rows, &data, seq_len, inner));
+        self.shape[self.dim] += seq_len;
+        Ok(())
+    }
+
+    /// Returns the cached values, the quantized positions get dequantized. Returns `None` if
+    /// the cache is empty.
+    pub fn current_data(&self) -> Result<Option<Tensor>> {
+        let (dtype, device) = match self.src.as_ref() {
+            None => return Ok(None),
+            Some(src) => src,
+        };
+        let quantized = match self.rows.as_ref() {
+            Some(rows) if self.quantized_seq_len() > 0 => {
+                let mut data = vec![0f32; self.shape.iter().product::<usize>()];
+                with_rows!(rows, rows => dequantize_rows(rows, &mut data));
+                let data = Tensor::from_vec(data, self.shape.as_slice(), &Device::Cpu)?
+                    .to_dtype(*dtype)?
+                    .to_device(device)?;
+                Some(data)
+            }
+            _ => None,
+        };
+        let data = match (quantized, self.residual.as_ref()) {
+            (Some(quantized), Some(residual)) => Tensor::cat(&[&quantized, residual], self.dim)?,
+            (Some(quantized), None) => quantized,
+            (None, Some(residual)) => residual.clone(),
+            (None, None) => {
+                // The cache only holds sequences of length zero.
+                let mut shape = self.shape.clone();
+                shape[self.dim] = 0;
+                Tensor::zeros(shape, *dtype, device)?
+            }
+        };
+        Ok(Some(data))
+    }
+}
+
+/// A KV-cache storing keys and values block-quantized, see [`QuantizedCache`].
+///
+/// This can be used in place of [`KvCache`] or [`ConcatKvCache`] to reduce the memory used by
+/// long contexts at the cost of some precision and of dequantizing the older positions on each
+/// step. The quantized llama, qwen2, qwen3, qwen3-moe, phi3 and gemma3 models from
+/// candle-transformers use it via their `with_kv_cache_dtype` method.
+///
+/// # Example
+///
+/// ```ignore
+/// use candle_nn::kv_cache::{QuantizedKvCache, QuantizedKvDType};
+///
+/// let mut cache = QuantizedKvCache::new(2, QuantizedKvDType::Q8_0);
+/// let k = Tensor::randn(0f32, 1., (1, 8, 10, 64), &device)?;
+/// let v = Tensor::randn(0f32, 1., (1, 8, 10, 64), &device)?;
+/// let (k, v) = cache.append(&k, &v)?;
+/// ```
+#[derive(Debug, Clone)]
+pub struct QuantizedKvCache {
+    k: QuantizedCache,
+    v: QuantizedCache,
+}
+
+impl QuantizedKvCache {
+    pub fn new(dim: usize, dtype: QuantizedKvDType) -> Self {
+        let k = QuantizedCache::new(dim, dtype);
+        let v = QuantizedCache::new(dim, dtype);
+        Self { k, v }
+    }
+
+    /// Sets the number of most recent positions kept in full precision, see
+    /// [`QuantizedCache::with_residual_len`].
+    pub fn with_residual_len(self, residual_len: usize) -> Self {
+        Self {
+            k: self.k.with_residual_len(residual_len),
+            v: self.v.with_residual_len(residual_len),
+        }
+    }
+
+    pub fn k_cache(&self) -> &QuantizedCache {
+        &self.k
+    }
+
+    pub fn v_cache(&self) -> &QuantizedCache {
+        &self.v
+    }
+
+    pub fn k_cache_mut(&mut self) -> &mut QuantizedCache {
+        &mut self.k
+    }
+
+    pub fn v_cache_mut(&mut self) -> &mut QuantizedCache {
+        &mut self.v
+    }
+
+    pub fn k(&self) -> Result<Option<Tensor>> {
+        self.k.current_data()
+    }
+
+    pub fn v(&self) -> Result<Option<Tensor>> {
+        self.v.current_data()
+    }
+
+    /// Appends the new keys and values and returns the dequantized content of the cache.
+    pub fn append(&mut self, k: &Tensor, v: &Tensor) -> Result<(Tensor, Tensor)> {
+        self.k.append(k)?;
+        self.v.append(v)?;
+        match (self.k.current_data()?, self.v.current_data()?) {
+            (Some(k), Some(v)) => Ok((k, v)),
+            _ => candle::bail!("empty kv-cache after appending"),
+        }
+    }
+
+    pub fn current_seq_len(&self) -> usize {
+        self.k.current_seq_len()
+    }
+
+    pub fn size_in_bytes(&self) -> usize {
+        self.k.size_in_bytes() + self.v.size_in_bytes()
+    }
+
+    pub fn reset(&mut self) {
+        self.k.reset();
+        self.v.reset();
+    }
+
+    /// Keeps the first `len` positions of the cache, this does not requantize the values.
+    pub fn truncate(&mut self, len: usize) -> Result<()> {
+        self.k.truncate(len)?;
+        self.v.truncate(len)
+    }
+
+    /// Returns the dequantized keys and values, `None` if the cache is empty.
+    pub fn snapshot(&self) -> Result<Option<KvSnapshot>> {
+        match (self.k()?, self.v()?) {
+            (Some(k), Some(v)) => Ok(Some(KvSnapshot::new(k, v, self.k.dim)?)),
+            _ => Ok(None),
+        }
+    }
+
+    /// Replaces the content of the cache with the snapshot, the values get quantized again.
+    pub fn restore(&mut self, snapshot: &KvSnapshot) -> Result<()> {
+        self.reset();
+        self.k.append(snapshot.k())?;
+        self.v.append(snapshot.v())?;
+        Ok(())
+    }
 }
 
 #[cfg(test)]
@@ -22,8 +22,10 @@ pub mod cpu_flash_attention;
 pub mod embedding;
 pub mod encoding;
 pub mod func;
+pub mod graph;
 pub mod group_norm;
 pub mod init;
+pub mod introspect;
 pub mod kv_cache;
 pub mod layer_norm;
 pub mod linear;
@@ -31,6 +33,9 @@ pub mod loss;
 pub mod moe;
 pub mod ops;
 pub mod optim;
+pub mod paged_attention;
+pub mod prefix_cache;
+pub mod prune;
 pub mod rnn;
 pub mod rotary_emb;
 pub mod sampling;
@@ -45,7 +50,7 @@ pub use conv::{
     conv_transpose2d, conv_transpose2d_no_bias, Conv1d, Conv1dConfig, Conv2d, Conv2dConfig,
     ConvTranspose1d, ConvTranspose1dConfig, ConvTranspose2d, ConvTranspose2dConfig,
 };
-pub use embedding::{embedding, Embedding};
+pub use embedding::{embedding, embedding_bag, Embedding, EmbeddingBag, EmbeddingBagMode};
 pub use func::{func, func_t, Func, FuncT};
 pub use group_norm::{group_norm, GroupNorm};
 pub use init::Init;
@@ -1,4 +1,5 @@
 //! Various optimization algorithms.
+use candle::sparse::CooTensor;
 use candle::{Result, Tensor, Var};
 
 /// The interface optimizers should implement.
@@ -77,6 +78,31 @@ impl SGD {
     pub fn push(&mut self, var: &Var) {
         self.vars.push(var.clone())
     }
+
+    /// Applies a gradient step using a sparse gradient such as the one returned by
+    /// [`crate::Embedding::sparse_grad`], the first dimension of the gradient being sparse.
+    pub fn sparse_step(&self, var: &Var, grad: &CooTensor) -> Result<()> {
+        if grad.sparse_dims() != 1 || grad.dims() != var.dims() {
+            candle::bail!(
+                "unexpected sparse grad {:?} ({} sparse dims) for var {:?}",
+                grad.shape(),
+                grad.sparse_dims(),
+                var.shape()
+            )
+        }
+        let update = (grad.values() * -self.learning_rate)?;
+        // Only the rows present in the gradient are updated, in place, rather than copying the
+        // whole tensor.
+        let mut rows_shape = vec![1; update.rank()];
+        rows_shape[0] = grad.nnz();
+        let rows = grad
+            .indices()
+            .get(0)?
+            .reshape(rows_shape)?
+            .broadcast_as(update.shape())?
+            .contiguous()?;
+        var.scatter_add_set(&rows, &update, 0)
+    }
 }
 
 #[derive(Clone, Debug)]
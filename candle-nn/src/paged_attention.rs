@@ -0,0 +1,706 @@
+//! Paged KV-cache for serving many concurrent sequences.
+//!
+//! The keys and values of all the sequences are stored in fixed size blocks taken from a shared
+//! pool and a per-sequence block table maps the logical positions to the physical blocks, this
+//! avoids reserving the full context for each sequence. Blocks are reference counted so that
+//! sequences can share a common prefix, a shared block is copied the first time one of its
+//! owners appends to it (copy-on-write).
+//!
+//! A [`BlockManager`] handles the block tables and is shared by all the layers of a model whereas
+//! each attention layer owns a [`PagedKvCache`] holding the actual data.
+use candle::{CpuStorage, DType, Device, Layout, Result, Shape, Tensor};
+use rayon::prelude::*;
+use std::collections::HashMap;
+
+/// A free-list allocator for reference counted cache blocks.
+#[derive(Debug, Clone)]
+pub struct BlockAllocator {
+    block_size: usize,
+    ref_counts: Vec<usize>,
+    free: Vec<usize>,
+}
+
+impl BlockAllocator {
+    pub fn new(num_blocks: usize, block_size: usize) -> Self {
+        // Blocks are handed out starting from the lowest index.
+        let free = (0..num_blocks).rev().collect();
+        Self {
+            block_size,
+            ref_counts: vec![0; num_blocks],
+            free,
+        }
+    }
+
+    pub fn block_size(&self) -> usize {
+        self.block_size
+    }
+
+    pub fn num_blocks(&self) -> usize {
+        self.ref_counts.len()
+    }
+
+    pub fn num_free_blocks(&self) -> usize {
+        self.free.len()
+    }
+
+    pub fn ref_count(&self, block: usize) -> usize {
+        self.ref_counts.get(block).copied().unwrap_or(0)
+    }
+
+    pub fn allocate(&mut self) -> Result<usize> {
+        match self.free.pop() {
+            None => candle::bail!("no free block left in the paged kv-cache"),
+            Some(block) => {
+                self.ref_counts[block] = 1;
+                Ok(block)
+            }
+        }
+    }
+
+    /// Adds a reference to an allocated block.
+    pub fn share(&mut self, block: usize) -> Result<()> {
+        if self.ref_count(block) == 0 {
+            candle::bail!("cannot share block {block} as it is not allocated")
+        }
+        self.ref_counts[block] += 1;
+        Ok(())
+    }
+
+    /// Removes a reference to an allocated block, the block is returned to the free list once
+    /// there are no references left.
+    pub fn free(&mut self, block: usize) -> Result<()> {
+        if self.ref_count(block) == 0 {
+            candle::bail!("cannot free block {block} as it is not allocated")
+        }
+        self.ref_counts[block] -= 1;
+        if self.ref_counts[block] == 0 {
+            self.free.push(block)
+        }
+        Ok(())
+    }
+}
+
+/// The physical blocks used by a sequence, position `p` is stored in `blocks[p / block_size]`.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct BlockTable {
+    blocks: Vec<usize>,
+    num_tokens: usize,
+}
+
+impl BlockTable {
+    pub fn blocks(&self) -> &[usize] {
+        &self.blocks
+    }
+
+    pub fn num_tokens(&self) -> usize {
+        self.num_tokens
+    }
+
+    /// The index of the cache slot where the token at position `pos` is stored.
+    pub fn slot(&self, pos: usize, block_size: usize) -> Option<usize> {
+        if pos >= self.num_tokens {
+            return None;
+        }
+        Some(self.blocks[pos / block_size] * block_size + pos % block_size)
+    }
+}
+
+/// Keeps track of the block tables for all the sequences using a paged cache.
+#[derive(Debug, Clone)]
+pub struct BlockManager {
+    allocator: BlockAllocator,
+    tables: HashMap<usize, BlockTable>,
+}
+
+impl BlockManager {
+    pub fn new(num_blocks: usize, block_size: usize) -> Self {
+        Self {
+            allocator: BlockAllocator::new(num_blocks, block_size),
+            tables: HashMap::new(),
+        }
+    }
+
+    pub fn allocator(&self) -> &BlockAllocator {
+        &self.allocator
+    }
+
+    pub fn block_size(&self) -> usize {
+        self.allocator.block_size()
+    }
+
+    pub fn num_free_blocks(&self) -> usize {
+        self.allocator.num_free_blocks()
+    }
+
+    pub fn contains(&self, seq_id: usize) -> bool {
+        self.tables.contains_key(&seq_id)
+    }
+
+    pub fn block_table(&self, seq_id: usize) -> Option<&BlockTable> {
+        self.tables.get(&seq_id)
+    }
+
+    /// The number of tokens stored for this sequence, 0 for unknown sequences.
+    pub fn num_tokens(&self, seq_id: usize) -> usize {
+        self.tables.get(&seq_id).map_or(0, |t| t.num_tokens)
+    }
+
+    fn table(&self, seq_id: usize) -> Result<&BlockTable> {
+        match self.tables.get(&seq_id) {
+            None => candle::bail!("unknown sequence {seq_id} in the paged kv-cache"),
+            Some(table) => Ok(table),
+        }
+    }
+
+    pub fn add_sequence(&mut self, seq_id: usize) -> Result<()> {
+        if self.tables.contains_key(&seq_id) {
+            candle::bail!("sequence {seq_id} is already in the paged kv-cache")
+        }
+        self.tables.insert(seq_id, BlockTable::default());
+        Ok(())
+    }
+
+    /// Creates a new sequence sharing all the blocks of `parent`.
+    pub fn fork_sequence(&mut self, parent: usize, child: usize) -> Result<()> {
+        if self.tables.contains_key(&child) {
+            candle::bail!("sequence {child} is already in the paged kv-cache")
+        }
+        let table = self.table(parent)?.clone();
+        for &block in table.blocks.iter() {
+            self.allocator.share(block)?
+        }
+        self.tables.insert(child, table);
+        Ok(())
+    }
+
+    /// Removes a sequence, releasing its blocks.
+    pub fn remove_sequence(&mut self, seq_id: usize) -> Result<()> {
+        let table = match self.tables.remove(&seq_id) {
+            None => candle::bail!("unknown sequence {seq_id} in the paged kv-cache"),
+            Some(table) => table,
+        };
+        for block in table.blocks {
+            self.allocator.free(block)?
+        }
+        Ok(())
+    }
+
+    /// Only keeps the first `num_tokens` tokens of a sequence, the blocks that are not used
+    /// anymore are released.
+    pub fn truncate(&mut self, seq_id: usize, num_tokens: usize) -> Result<()> {
+        let block_size = self.block_size();
+        let table = match self.tables.get_mut(&seq_id) {
+            None => candle::bail!("unknown sequence {seq_id} in the paged kv-cache"),
+            Some(table) => table,
+        };
+        if num_tokens > table.num_tokens {
+            candle::bail!(
+                "cannot truncate sequence {seq_id} to {num_tokens}, it only has {} tokens",
+                table.num_tokens
+            )
+        }
+        for block in table.blocks.drain(num_tokens.div_ceil(block_size)..) {
+            self.allocator.free(block)?
+        }
+        table.num_tokens = num_tokens;
+        Ok(())
+    }
+
+    /// The number of blocks that have to be allocated to append `num_tokens` to a sequence.
+    pub fn num_blocks_needed(&self, seq_id: usize, num_tokens: usize) -> usize {
+        let block_size = self.block_size();
+        let table = match self.tables.get(&seq_id) {
+            None => return num_tokens.div_ceil(block_size),
+            Some(table) => table,
+        };
+        if num_tokens == 0 {
+            return 0;
+        }
+        let copy_on_write = match table.blocks.last() {
+            Some(&last) => {
+                !table.num_tokens.is_multiple_of(block_size) && self.allocator.ref_count(last) > 1
+            }
+            None => false,
+        };
+        let total = (table.num_tokens + num_tokens).div_ceil(block_size);
+        total - table.blocks.len() + copy_on_write as usize
+    }
+
+    pub fn can_append(&self, seq_id: usize, num_tokens: usize) -> bool {
+        self.num_blocks_needed(seq_id, num_tokens) <= self.num_free_blocks()
+    }
+
+    /// Reserves the slots for `num_tokens` new tokens at the end of a sequence.
+    ///
+    /// When the last block of the sequence is shared with other sequences, it gets replaced by a
+    /// fresh block. The returned `(src, dst)` pairs indicate the blocks that have to be copied in
+    /// all the layer caches, see [`PagedKvCache::copy_blocks`].
+    pub fn append_slots(
+        &mut self,
+        seq_id: usize,
+        num_tokens: usize,
+    ) -> Result<Vec<(usize, usize)>> {
+        let needed = self.num_blocks_needed(seq_id, num_tokens);
+        if needed > self.num_free_blocks() {
+            candle::bail!(
+                "not enough free blocks in the paged kv-cache, {needed} needed, {} available",
+                self.num_free_blocks()
+            )
+        }
+        let block_size = self.block_size();
+        let Self { allocator, tables } = self;
+        let table = match tables.get_mut(&seq_id) {
+            None => candle::bail!("unknown sequence {seq_id} in the paged kv-cache"),
+            Some(table) => table,
+        };
+        let mut copies = vec![];
+        if num_tokens == 0 {
+            return Ok(copies);
+        }
+        if !table.num_tokens.is_multiple_of(block_size) {
+            if let Some(last) = table.blocks.last_mut() {
+                if allocator.ref_count(*last) > 1 {
+                    let block = allocator.allocate()?;
+                    allocator.free(*last)?;
+                    copies.push((*last, block));
+                    *last = block
+                }
+            }
+        }
+        let total = (table.num_tokens + num_tokens).div_ceil(block_size);
+        while table.blocks.len() < total {
+            table.blocks.push(allocator.allocate()?)
+        }
+        table.num_tokens += num_tokens;
+        Ok(copies)
+    }
+
+    /// Builds the attention metadata for a step processing the last `query_len` tokens of each of
+    /// the given `(seq_id, query_len)` sequences. The slots for these tokens should have been
+    /// reserved with [`BlockManager::append_slots`] beforehand.
+    pub fn metadata(
+        &self,
+        seqs: &[(usize, usize)],
+        device: &Device,
+    ) -> Result<PagedAttentionMetadata> {
+        let mut sequences = Vec::with_capacity(seqs.len());
+        for &(seq_id, query_len) in seqs.iter() {
+            let table = self.table(seq_id)?;
+            if query_len > table.num_tokens {
+                candle::bail!(
+                    "query length {query_len} exceeds the {} tokens of sequence {seq_id}",
+                    table.num_tokens
+                )
+            }
+            sequences.push(SequenceMetadata {
+                block_table: table.blocks.clone(),
+                context_len: table.num_tokens,
+                query_len,
+            })
+        }
+        PagedAttentionMetadata::new(self.block_size(), sequences, device)
+    }
+}
+
+/// The attention metadata for a single sequence of a batch.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SequenceMetadata {
+    pub block_table: Vec<usize>,
+    /// The number of tokens in the cache including the ones processed in this step.
+    pub context_len: usize,
+    /// The number of tokens processed in this step, these are the last tokens of the context.
+    pub query_len: usize,
+}
+
+/// The attention metadata for a batch step.
+///
+/// The tokens of all the sequences are packed together along the first dimension, so queries
+/// have a shape `(num_tokens, num_heads, head_dim)` where `num_tokens` is the sum of the query
+/// lengths. This makes it possible to mix prefill and decode sequences in the same step.
+#[derive(Debug, Clone)]
+pub struct PagedAttentionMetadata {
+    block_size: usize,
+    sequences: Vec<SequenceMetadata>,
+    slot_mapping: Tensor,
+    positions: Tensor,
+}
+
+impl PagedAttentionMetadata {
+    pub fn new(
+        block_size: usize,
+        sequences: Vec<SequenceMetadata>,
+        device: &Device,
+    ) -> Result<Self> {
+        let mut slot_mapping = vec![];
+        let mut positions = vec![];
+        for seq in sequences.iter() {
+            // Each sequence contributes its last token to the outputs of the step.
+            if seq.query_len == 0 {
+                candle::bail!("empty query for a sequence of length {}", seq.context_len)
+            }
+            if seq.context_len > seq.block_table.len() * block_size {
+                candle::bail!(
+                    "context length {} does not fit in {} blocks",
+                    seq.context_len,
+                    seq.block_table.len()
+                )
+            }
+            for pos in seq.context_len - seq.query_len..seq.context_len {
+                let slot = seq.block_table[pos / block_size] * block_size + pos % block_size;
+                slot_mapping.push(slot as u32);
+                positions.push(pos as u32);
+            }
+        }
+        let slot_mapping = Tensor::new(slot_mapping, device)?;
+        let positions = Tensor::new(positions, device)?;
+        Ok(Self {
+            block_size,
+            sequences,
+            slot_mapping,
+            positions,
+        })
+    }
+
+    pub fn block_size(&self) -> usize {
+        self.block_size
+    }
+
+    pub fn sequences(&self) -> &[SequenceMetadata] {
+        &self.sequences
+    }
+
+    /// The total number of tokens processed in this step.
+    pub fn num_tokens(&self) -> usize {
+        self.sequences.iter().map(|s| s.query_len).sum()
+    }
+
+    /// The cache slot for each processed token, a u32 tensor of shape `(num_tokens,)`.
+    pub fn slot_mapping(&self) -> &Tensor {
+        &self.slot_mapping
+    }
+
+    /// The position of each processed token in its sequence, a u32 tensor of shape
+    /// `(num_tokens,)`.
+    pub fn positions(&self) -> &Tensor {
+        &self.positions
+    }
+
+    /// Selects the rows of `xs` corresponding to the last token of each sequence, `xs` has the
+    /// packed tokens on its first dimension.
+    pub fn last_tokens(&self, xs: &Tensor) -> Result<Tensor> {
+        let mut indices = Vec::with_capacity(self.sequences.len());
+        let mut offset = 0u32;
+        for seq in self.sequences.iter() {
+            offset += seq.query_len as u32;
+            indices.push(offset - 1);
+        }
+        let indices = Tensor::new(indices, xs.device())?;
+        xs.index_select(&indices, 0)
+    }
+}
+
+/// The keys and values of an attention layer stored in blocks.
+///
+/// The data uses a shape `(num_blocks * block_size, num_kv_heads, head_dim)` so that each slot
+/// holds the key or value for a single token.
+#[derive(Debug)]
+pub struct PagedKvCache {
+    k: Tensor,
+    v: Tensor,
+    block_size: usize,
+}
+
+impl PagedKvCache {
+    pub fn new(
+        num_blocks: usize,
+        block_size: usize,
+        num_kv_heads: usize,
+        head_dim: usize,
+        dtype: DType,
+        device: &Device,
+    ) -> Result<Self> {
+        let shape = (num_blocks * block_size, num_kv_heads, head_dim);
+        let k = Tensor::zeros(shape, dtype, device)?;
+        let v = Tensor::zeros(shape, dtype, device)?;
+        Ok(Self { k, v, block_size })
+    }
+
+    pub fn k(&self) -> &Tensor {
+        &self.k
+    }
+
+    pub fn v(&self) -> &Tensor {
+        &self.v
+    }
+
+    pub fn block_size(&self) -> usize {
+        self.block_size
+    }
+
+    pub fn num_blocks(&self) -> usize {
+        self.k.dim(0).unwrap_or(0) / self.block_size
+    }
+
+    pub fn size_in_bytes(&self) -> usize {
+        2 * self.k.elem_count() * self.k.dtype().size_in_bytes()
+    }
+
+    /// Stores the keys and values of shape `(num_tokens, num_kv_heads, head_dim)` in the given
+    /// slots.
+    pub fn write(&mut self, k: &Tensor, v: &Tensor, slot_mapping: &Tensor) -> Result<()> {
+        let (num_tokens, _, _) = k.dims3()?;
+        if num_tokens == 0 {
+            return Ok(());
+        }
+        let indices = slot_mapping
+            .reshape((num_tokens, 1, 1))?
+            .broadcast_as(k.shape())?
+            .contiguous()?;
+        let k = k.to_dtype(self.k.dtype())?.contiguous()?;
+        let v = v.to_dtype(self.v.dtype())?.contiguous()?;
+        self.k.scatter_set(&indices, &k, 0)?;
+        self.v.scatter_set(&indices, &v, 0)?;
+        Ok(())
+    }
+
+    /// Copies the content of block `src` to block `dst` for each `(src, dst)` pair.
+    pub fn copy_blocks(&mut self, copies: &[(usize, usize)]) -> Result<()> {
+        let bs = self.block_size;
+        for &(src, dst) in copies.iter() {
+            for cache in [&self.k, &self.v] {
+                let block = cache.narrow(0, src * bs, bs)?.copy()?;
+                cache.slice_set(&block, 0, dst * bs)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Returns the first `len` keys and values of a sequence as contiguous tensors of shape
+    /// `(len, num_kv_heads, head_dim)`.
+    pub fn gather(&self, block_table: &[usize], len: usize) -> Result<(Tensor, Tensor)> {
+        let bs = self.block_size;
+        if len > block_table.len() * bs {
+            candle::bail!("length {len} does not fit in {} blocks", block_table.len())
+        }
+        let slots = (0..len)
+            .map(|p| (block_table[p / bs] * bs + p % bs) as u32)
+            .collect::<Vec<_>>();
+        let slots = Tensor::new(slots, self.k.device())?;
+        let k = self.k.index_select(&slots, 0)?;
+        let v = self.v.index_select(&slots, 0)?;
+        Ok((k, v))
+    }
+
+    /// Writes the new keys and values to the cache and computes the attention for the queries of
+    /// shape `(num_tokens, num_heads, head_dim)`, see [`paged_attention`].
+    pub fn forward(
+        &mut self,
+        q: &Tensor,
+        k: &Tensor,
+        v: &Tensor,
+        metadata: &PagedAttentionMetadata,
+        scale: f32,
+    ) -> Result<Tensor> {
+        self.write(k, v, metadata.slot_mapping())?;
+        paged_attention(q, &self.k, &self.v, metadata, scale)
+    }
+}
+
+struct PagedAttention {
+    block_size: usize,
+    block_tables: Vec<Vec<usize>>,
+    // For each query token, the index of its sequence and the number of cached tokens it attends
+    // to, the causal mask is implied by the latter.
+    tokens: Vec<(usize, usize)>,
+    scale: f32,
+}
+
+impl candle::CustomOp3 for PagedAttention {
+    fn name(&self) -> &'static str {
+        "paged-attention"
+    }
+
+    fn cpu_fwd(
+        &self,
+        s1: &CpuStorage,
+        l1: &Layout,
+        s2: &CpuStorage,
+        l2: &Layout,
+        s3: &CpuStorage,
+        l3: &Layout,
+    ) -> Result<(CpuStorage, Shape)> {
+        fn inner<
+            T: candle::WithDType
+                + num_traits::Float
+                + num_traits::AsPrimitive<f32>
+                + num_traits::FromPrimitive,
+        >(
+            op: &PagedAttention,
+            q: &[T],
+            q_l: &Layout,
+            k: &[T],
+            k_l: &Layout,
+            v: &[T],
+            v_l: &Layout,
+        ) -> Result<(CpuStorage, Shape)> {
+            let q = match q_l.contiguous_offsets() {
+                None => candle::bail!("q has to be contiguous"),
+                Some((o1, o2)) => &q[o1..o2],
+            };
+            let k = match k_l.contiguous_offsets() {
+                None => candle::bail!("k-cache has to be contiguous"),
+                Some((o1, o2)) => &k[o1..o2],
+            };
+            let v = match v_l.contiguous_offsets() {
+                None => candle::bail!("v-cache has to be contiguous"),
+                Some((o1, o2)) => &v[o1..o2],
+            };
+            let (_, num_heads, head_dim) = q_l.shape().dims3()?;
+            let (_, num_kv_heads, _) = k_l.shape().dims3()?;
+            let n_rep = num_heads / num_kv_heads;
+            let bs = op.block_size;
+            let mut dst = vec![T::zero(); q.len()];
+            dst.par_chunks_mut(head_dim)
+                .zip(q.par_chunks(head_dim))
+                .enumerate()
+                .for_each_with(Vec::new(), |scores, (idx, (dst, q))| {
+                    let (seq_idx, len) = op.tokens[idx / num_heads];
+                    let kv_head = (idx % num_heads) / n_rep;
+                    let table = &op.block_tables[seq_idx];
+                    let offset = |pos: usize| {
+                        let slot = table[pos / bs] * bs + pos % bs;
+                        (slot * num_kv_heads + kv_head) * head_dim
+                    };
+                    scores.clear();
+                    let mut max = f32::NEG_INFINITY;
+                    for pos in 0..len {
+                        let k = &k[offset(pos)..offset(pos) + head_dim];
+                        let s = q.iter().zip(k).map(|(q, k)| q.as_() * k.as_()).sum::<f32>();
+                        let s = s * op.scale;
+                        max = max.max(s);
+                        scores.push(s)
+                    }
+                    let mut sum = 0f32;
+                    for s in scores.iter_mut() {
+                        *s = (*s - max).exp();
+                        sum += *s
+                    }
+                    let mut acc = vec![0f32; head_dim];
+                    for (pos, s) in scores.iter().enumerate() {
+                        let v = &v[offset(pos)..offset(pos) + head_dim];
+                        for (a, v) in acc.iter_mut().zip(v) {
+                            *a += s * v.as_()
+                        }
+                    }
+                    for (d, a) in dst.iter_mut().zip(acc) {
+                        *d = T::from_f32(a / sum).unwrap_or_else(T::nan)
+                    }
+                });
+            let storage = candle::WithDType::to_cpu_storage_owned(dst);
+            Ok((storage, q_l.shape().clone()))
+        }
+
+        use candle::backend::BackendStorage;
+        use CpuStorage as C;
+        match (s1, s2, s3) {
+            (C::BF16(s1), C::BF16(s2), C::BF16(s3)) => inner(self, s1, l1, s2, l2, s3, l3),
+            (C::F16(s1), C::F16(s2), C::F16(s3)) => inner(self, s1, l1, s2, l2, s3, l3),
+            (C::F32(s1), C::F32(s2), C::F32(s3)) => inner(self, s1, l1, s2, l2, s3, l3),
+            _ => candle::bail!("unsupported dtype for paged-attention {:?}", s1.dtype()),
+        }
+    }
+}
+
+/// Attention over a paged cache.
+///
+/// The queries have shape `(num_tokens, num_heads, head_dim)` with the tokens of the sequences
+/// packed as described in [`PagedAttentionMetadata`], the caches have shape
+/// `(num_slots, num_kv_heads, head_dim)`. Each query token attends to the tokens of its sequence
+/// up to its own position. Grouped-query attention is supported when `num_heads` is a multiple of
+/// `num_kv_heads`.
+///
+/// The output has the same shape as the queries. A dedicated kernel is used on cpu, other devices
+/// gather the keys and values of each sequence and use a standard attention.
+pub fn paged_attention(
+    q: &Tensor,
+    k_cache: &Tensor,
+    v_cache: &Tensor,
+    metadata: &PagedAttentionMetadata,
+    scale: f32,
+) -> Result<Tensor> {
+    let (num_tokens, num_heads, head_dim) = q.dims3()?;
+    let (_, num_kv_heads, kv_head_dim) = k_cache.dims3()?;
+    if num_tokens != metadata.num_tokens() {
+        candle::bail!(
+            "paged-attention expected {} tokens, got {num_tokens}",
+            metadata.num_tokens()
+        )
+    }
+    if head_dim != kv_head_dim || !num_heads.is_multiple_of(num_kv_heads) {
+        candle::bail!(
+            "paged-attention shape mismatch, q {:?}, k-cache {:?}",
+            q.shape(),
+            k_cache.shape()
+        )
+    }
+    if q.device().is_cpu() {
+        let mut tokens = Vec::with_capacity(num_tokens);
+        for (seq_idx, seq) in metadata.sequences().iter().enumerate() {
+            let start = seq.context_len - seq.query_len;
+            tokens.extend((0..seq.query_len).map(|i| (seq_idx, start + i + 1)))
+        }
+        let op = PagedAttention {
+            block_size: metadata.block_size(),
+            block_tables: metadata
+                .sequences()
+                .iter()
+                .map(|s| s.block_table.clone())
+                .collect(),
+            tokens,
+            scale,
+        };
+        let in_dtype = q.dtype();
+        let q = q.to_dtype(k_cache.dtype())?.contiguous()?;
+        return q
+            .apply_op3_no_bwd(k_cache, v_cache, &op)?
+            .to_dtype(in_dtype);
+    }
+    let n_rep = num_heads / num_kv_heads;
+    let bs = metadata.block_size();
+    let mut ys = Vec::with_capacity(metadata.sequences().len());
+    let mut offset = 0;
+    for seq in metadata.sequences().iter() {
+        let (ctx, qlen) = (seq.context_len, seq.query_len);
+        let slots = (0..ctx)
+            .map(|p| (seq.block_table[p / bs] * bs + p % bs) as u32)
+            .collect::<Vec<_>>();
+        let slots = Tensor::new(slots, q.device())?;
+        let repeat = |xs: Tensor| -> Result<Tensor> {
+            xs.transpose(0, 1)?
+                .unsqueeze(1)?
+                .expand((num_kv_heads, n_rep, ctx, head_dim))?
+                .reshape((num_heads, ctx, head_dim))?
+                .to_dtype(q.dtype())
+        };
+        let k = repeat(k_cache.index_select(&slots, 0)?)?;
+        let v = repeat(v_cache.index_select(&slots, 0)?)?;
+        let qs = q.narrow(0, offset, qlen)?.transpose(0, 1)?.contiguous()?;
+        let mask: Vec<f32> = (0..qlen)
+            .flat_map(|i| {
+                (0..ctx).map(move |j| {
+                    if j + qlen <= ctx + i {
+                        0.
+                    } else {
+                        f32::NEG_INFINITY
+                    }
+                })
+            })
+            .collect();
+        let mask = Tensor::from_vec(mask, (qlen, ctx), q.device())?.to_dtype(q.dtype())?;
+        let att = (qs.matmul(&k.t()?)? * scale as f64)?.broadcast_add(&mask)?;
+        let att = crate::ops::softmax_last_dim(&att)?;
+        ys.push(att.matmul(&v.contiguous()?)?.transpose(0, 1)?);
+        offset += qlen;
+    }
+    Tensor::cat(&ys, 0)
+}
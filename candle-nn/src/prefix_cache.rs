@@ -0,0 +1,333 @@
+//! Prefix caching, reuse the KV-cache states computed for previous requests.
+//!
+//! The states are stored in a radix tree keyed on the token ids of the sequence that produced
+//! them. A lookup returns the state for the longest cached prefix of the new sequence so that a
+//! model only has to process the remaining tokens, states for longer sequences get truncated when
+//! the new sequence diverges from them. States are evicted in least-recently-used order once the
+//! total size goes over the memory budget.
+use crate::kv_cache::KvSnapshot;
+use candle::Result;
+use std::collections::HashMap;
+
+/// A cache state that can be stored in a [`PrefixCache`].
+pub trait CacheState: Clone {
+    /// The number of tokens covered by this state.
+    fn seq_len(&self) -> usize;
+
+    /// Returns the state for the first `len` tokens.
+    fn truncate(&self, len: usize) -> Result<Self>;
+
+    fn size_in_bytes(&self) -> usize;
+}
+
+impl CacheState for KvSnapshot {
+    fn seq_len(&self) -> usize {
+        KvSnapshot::seq_len(self)
+    }
+
+    fn truncate(&self, len: usize) -> Result<Self> {
+        KvSnapshot::truncate(self, len)
+    }
+
+    fn size_in_bytes(&self) -> usize {
+        KvSnapshot::size_in_bytes(self)
+    }
+}
+
+/// The states of all the layers of a model, this assumes that all the layers cover the same
+/// tokens.
+impl<S: CacheState> CacheState for Vec<S> {
+    fn seq_len(&self) -> usize {
+        self.first().map_or(0, |s| s.seq_len())
+    }
+
+    fn truncate(&self, len: usize) -> Result<Self> {
+        self.iter().map(|s| s.truncate(len)).collect()
+    }
+
+    fn size_in_bytes(&self) -> usize {
+        self.iter().map(|s| s.size_in_bytes()).sum()
+    }
+}
+
+#[derive(Debug, Clone)]
+struct Node<S> {
+    // The tokens on the edge from the parent node.
+    tokens: Vec<u32>,
+    parent: usize,
+    // Indexed by the first token of the child edge.
+    children: HashMap<u32, usize>,
+    state: Option<S>,
+    last_used: u64,
+}
+
+impl<S> Node<S> {
+    fn new(tokens: Vec<u32>, parent: usize) -> Self {
+        Self {
+            tokens,
+            parent,
+            children: HashMap::new(),
+            state: None,
+            last_used: 0,
+        }
+    }
+}
+
+const ROOT: usize = 0;
+
+/// A radix tree of cache states keyed on token ids.
+#[derive(Debug, Clone)]
+pub struct PrefixCache<S> {
+    nodes: HashMap<usize, Node<S>>,
+    next_id: usize,
+    budget: usize,
+    size_in_bytes: usize,
+    num_states: usize,
+    clock: u64,
+}
+
+impl<S: CacheState> PrefixCache<S> {
+    /// Creates an empty cache, `budget` is the maximum total size of the stored states in bytes.
+    pub fn new(budget: usize) -> Self {
+        let mut nodes = HashMap::new();
+        nodes.insert(ROOT, Node::new(vec![], ROOT));
+        Self {
+            nodes,
+            next_id: ROOT + 1,
+            budget,
+            size_in_bytes: 0,
+            num_states: 0,
+            clock: 0,
+        }
+    }
+
+    pub fn budget(&self) -> usize {
+        self.budget
+    }
+
+    /// The total size of the stored states.
+    pub fn size_in_bytes(&self) -> usize {
+        self.size_in_bytes
+    }
+
+    /// The number of stored states.
+    pub fn len(&self) -> usize {
+        self.num_states
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.num_states == 0
+    }
+
+    pub fn clear(&mut self) {
+        *self = Self::new(self.budget)
+    }
+
+    fn tick(&mut self) -> u64 {
+        self.clock += 1;
+        self.clock
+    }
+
+    fn node(&self, id: usize) -> &Node<S> {
+        &self.nodes[&id]
+    }
+
+    fn node_mut(&mut self, id: usize) -> &mut Node<S> {
+        self.nodes.get_mut(&id).expect("missing prefix-cache node")
+    }
+
+    // Splits the edge leading to `id` after `at` tokens and returns the new intermediate node.
+    fn split(&mut self, id: usize, at: usize) -> usize {
+        let new_id = self.next_id;
+        self.next_id += 1;
+        let node = self.node_mut(id);
+        let suffix = node.tokens.split_off(at);
+        let prefix = std::mem::replace(&mut node.tokens, suffix);
+        let parent = std::mem::replace(&mut node.parent, new_id);
+        let first_suffix = node.tokens[0];
+        let mut new_node = Node::new(prefix, parent);
+        new_node.children.insert(first_suffix, id);
+        let first_prefix = new_node.tokens[0];
+        self.nodes.insert(new_id, new_node);
+        self.node_mut(parent).children.insert(first_prefix, new_id);
+        new_id
+    }
+
+    // Walks down the tree following `tokens`, returns the deepest node fully matched, the number
+    // of tokens matched to reach it, and the child whose edge is partially matched if any
+    // together with the number of tokens matched on that edge.
+    fn walk(&self, tokens: &[u32]) -> (usize, usize, Option<(usize, usize)>) {
+        let (mut id, mut pos) = (ROOT, 0);
+        loop {
+            let child = match tokens.get(pos) {
+                None => return (id, pos, None),
+                Some(t) => match self.node(id).children.get(t) {
+                    None => return (id, pos, None),
+                    Some(&child) => child,
+                },
+            };
+            let edge = &self.node(child).tokens;
+            let common = edge
+                .iter()
+                .zip(tokens[pos..].iter())
+                .take_while(|(a, b)| a == b)
+                .count();
+            if common < edge.len() {
+                return (id, pos, Some((child, common)));
+            }
+            id = child;
+            pos += common;
+        }
+    }
+
+    /// Stores the state computed for `tokens`, the state has to cover all the tokens.
+    ///
+    /// This replaces a state previously stored for the same tokens. Least recently used states
+    /// get evicted when the budget is exceeded, including the new state if it does not fit on its
+    /// own.
+    pub fn insert(&mut self, tokens: &[u32], state: S) -> Result<()> {
+        if state.seq_len() != tokens.len() {
+            candle::bail!(
+                "prefix-cache state covers {} tokens, expected {}",
+                state.seq_len(),
+                tokens.len()
+            )
+        }
+        if tokens.is_empty() {
+            return Ok(());
+        }
+        let (mut id, pos, partial) = self.walk(tokens);
+        if let Some((child, common)) = partial {
+            id = self.split(child, common);
+            let pos = pos + common;
+            if pos < tokens.len() {
+                id = self.add_child(id, tokens[pos..].to_vec());
+            }
+        } else if pos < tokens.len() {
+            id = self.add_child(id, tokens[pos..].to_vec());
+        }
+        let size = state.size_in_bytes();
+        let last_used = self.tick();
+        let node = self.node_mut(id);
+        node.last_used = last_used;
+        let previous = node.state.replace(state);
+        match previous {
+            Some(previous) => self.size_in_bytes -= previous.size_in_bytes(),
+            None => self.num_states += 1,
+        }
+        self.size_in_bytes += size;
+        while self.size_in_bytes > self.budget {
+            if !self.evict_lru() {
+                break;
+            }
+        }
+        Ok(())
+    }
+
+    fn add_child(&mut self, parent: usize, tokens: Vec<u32>) -> usize {
+        let id = self.next_id;
+        self.next_id += 1;
+        let first = tokens[0];
+        self.nodes.insert(id, Node::new(tokens, parent));
+        self.node_mut(parent).children.insert(first, id);
+        id
+    }
+
+    /// Returns the state for the longest cached prefix of `tokens` together with the length of
+    /// this prefix, `None` if no state shares a prefix with `tokens`.
+    ///
+    /// The returned state may cover all the tokens, callers that need to compute some logits
+    /// should only look up the tokens preceding the last one.
+    pub fn lookup(&mut self, tokens: &[u32]) -> Result<Option<(usize, S)>> {
+        let (id, pos, partial) = self.walk(tokens);
+        let (found, len) = match partial {
+            // The tokens diverge in the middle of an edge, any state below it can be truncated.
+            Some((child, common)) => match self.any_state_below(child) {
+                Some(found) => (found, pos + common),
+                None => return Ok(None),
+            },
+            None => match self.any_state_below(id) {
+                Some(found) => (found, pos),
+                None => return Ok(None),
+            },
+        };
+        if len == 0 {
+            return Ok(None);
+        }
+        let last_used = self.tick();
+        let node = self.node_mut(found);
+        node.last_used = last_used;
+        let state = node.state.as_ref().expect("node without state");
+        let state = if state.seq_len() == len {
+            state.clone()
+        } else {
+            state.truncate(len)?
+        };
+        Ok(Some((len, state)))
+    }
+
+    // Returns a node with a state in the subtree rooted at `id`, preferring the shallowest one.
+    fn any_state_below(&self, id: usize) -> Option<usize> {
+        let mut queue = std::collections::VecDeque::from([id]);
+        while let Some(id) = queue.pop_front() {
+            let node = self.node(id);
+            if node.state.is_some() {
+                return Some(id);
+            }
+            queue.extend(node.children.values().copied())
+        }
+        None
+    }
+
+    /// Evicts the least recently used state, returns `false` if the cache is empty.
+    pub fn evict_lru(&mut self) -> bool {
+        let lru = self
+            .nodes
+            .iter()
+            .filter(|(_, n)| n.state.is_some())
+            .min_by_key(|(_, n)| n.last_used)
+            .map(|(&id, _)| id);
+        let id = match lru {
+            None => return false,
+            Some(id) => id,
+        };
+        if let Some(state) = self.node_mut(id).state.take() {
+            self.size_in_bytes -= state.size_in_bytes();
+            self.num_states -= 1;
+        }
+        self.prune(id);
+        true
+    }
+
+    // Removes the nodes that do not lead to any state anymore and merges the nodes that only have
+    // a single child, starting from `id` and going up the tree.
+    fn prune(&mut self, mut id: usize) {
+        while id != ROOT {
+            let node = self.node(id);
+            if node.state.is_some() {
+                return;
+            }
+            let parent = node.parent;
+            match node.children.len() {
+                0 => {
+                    let node = self.nodes.remove(&id).expect("missing prefix-cache node");
+                    self.node_mut(parent).children.remove(&node.tokens[0]);
+                    id = parent
+                }
+                1 => {
+                    let node = self.nodes.remove(&id).expect("missing prefix-cache node");
+                    let child = *node.children.values().next().expect("single child");
+                    let first = node.tokens[0];
+                    let child_node = self.node_mut(child);
+                    let mut tokens = node.tokens;
+                    tokens.append(&mut child_node.tokens);
+                    child_node.tokens = tokens;
+                    child_node.parent = parent;
+                    self.node_mut(parent).children.insert(first, child);
+                    return;
+                }
+                _ => return,
+            }
+        }
+    }
+}
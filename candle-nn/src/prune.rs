@@ -0,0 +1,533 @@
+//! Weight pruning and sparsity utilities.
+//!
+//! This module computes pruning masks for the weights of `Linear` and `Conv` layers, either for
+//! the variables of a [`VarMap`] that is being trained or for the tensors of an already loaded
+//! model. The following methods are supported:
+//!
+//! - [`PruneMethod::Magnitude`], unstructured pruning of the smallest weights in absolute value.
+//! - [`PruneMethod::Channel`], removes whole output channels (the first dimension of the weight).
+//! - [`PruneMethod::Head`], removes whole attention heads, the output channels being split in
+//!   `num_heads` contiguous groups.
+//! - [`PruneMethod::NM`], N:M semi-structured sparsity (e.g. 2:4), in each group of `m`
+//!   consecutive input weights only the `n` largest ones are kept.
+//!
+//! When fine-tuning a pruned model, [`PruneMasks::apply`] should be called after each optimizer
+//! step so that the pruned weights stay at zero.
+//!
+//! ```rust
+//! use candle::{DType, Device, Module, Tensor};
+//! use candle_nn::prune::{self, PruneMethod};
+//! # fn main() -> candle::Result<()> {
+//! let varmap = candle_nn::VarMap::new();
+//! let vb = candle_nn::VarBuilder::from_varmap(&varmap, DType::F32, &Device::Cpu);
+//! let layer = candle_nn::linear(8, 4, vb.pp("fc"))?;
+//! let masks = prune::prune_varmap(&varmap, PruneMethod::NM { n: 2, m: 4 }, prune::is_weight)?;
+//! let report = prune::sparsity_report(&masks.pruned_tensors(&varmap)?)?;
+//! assert_eq!(report[0].sparsity(), 0.5);
+//! # let _ = layer.forward(&Tensor::zeros((1, 8), DType::F32, &Device::Cpu)?)?;
+//! # Ok(()) }
+//! ```
+use crate::VarMap;
+use candle::{DType, Device, Result, Tensor};
+use std::collections::HashMap;
+
+/// The pruning strategy applied to a weight tensor.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum PruneMethod {
+    /// Zero the `sparsity` fraction of the weights with the smallest absolute value.
+    Magnitude { sparsity: f64 },
+    /// Zero the `sparsity` fraction of the output channels with the smallest L2 norm.
+    Channel { sparsity: f64 },
+    /// Zero the `sparsity` fraction of the heads with the smallest L2 norm, the output channels
+    /// being split in `num_heads` contiguous groups.
+    Head { num_heads: usize, sparsity: f64 },
+    /// In each group of `m` consecutive weights along the flattened input dimensions, keep the
+    /// `n` weights with the largest absolute value.
+    NM { n: usize, m: usize },
+}
+
+fn check_sparsity(sparsity: f64) -> Result<()> {
+    if !(0.0..=1.0).contains(&sparsity) {
+        candle::bail!("sparsity should be between 0 and 1, got {sparsity}")
+    }
+    Ok(())
+}
+
+/// Returns the indexes of `values` sorted by increasing value, ties being broken by index.
+fn arg_sort_f32(values: &[f32]) -> Vec<usize> {
+    let mut idxs = (0..values.len()).collect::<Vec<_>>();
+    idxs.sort_by(|&i, &j| values[i].total_cmp(&values[j]).then(i.cmp(&j)));
+    idxs
+}
+
+/// Returns the L2 norm of each of the `n_groups` contiguous chunks of `values`.
+fn group_norms(values: &[f32], n_groups: usize) -> Vec<f32> {
+    let group_size = values.len() / n_groups;
+    values
+        .chunks(group_size)
+        .map(|c| c.iter().map(|v| v * v).sum::<f32>().sqrt())
+        .collect()
+}
+
+/// Zero the mask for the `sparsity` fraction of the groups with the smallest norm.
+fn group_mask(values: &[f32], n_groups: usize, sparsity: f64) -> Vec<f32> {
+    // Weights with no elements, e.g. with zero output channels, have nothing to prune.
+    if values.is_empty() {
+        return vec![];
+    }
+    let group_size = values.len() / n_groups;
+    let norms = group_norms(values, n_groups);
+    let n_pruned = (sparsity * n_groups as f64).round() as usize;
+    let mut mask = vec![1f32; values.len()];
+    for &g in arg_sort_f32(&norms)[..n_pruned].iter() {
+        mask[g * group_size..(g + 1) * group_size].fill(0.);
+    }
+    mask
+}
+
+impl PruneMethod {
+    /// Computes the binary mask for a weight tensor. The first dimension of the weight is
+    /// assumed to be the output channels, as is the case for `Linear` and `Conv` weights. The
+    /// returned mask has the same shape, dtype and device as the weight.
+    pub fn mask(&self, weight: &Tensor) -> Result<Tensor> {
+        let dims = weight.dims();
+        if dims.len() < 2 {
+            candle::bail!("pruning requires a weight with at least two dims, got {dims:?}")
+        }
+        let out_c = dims[0];
+        let in_c = weight.elem_count() / out_c.max(1);
+        let values = weight
+            .flatten_all()?
+            .to_dtype(DType::F32)?
+            .to_vec1::<f32>()?;
+        let mask = match *self {
+            Self::Magnitude { sparsity } => {
+                check_sparsity(sparsity)?;
+                let abs = values.iter().map(|v| v.abs()).collect::<Vec<_>>();
+                let n_pruned = (sparsity * values.len() as f64).round() as usize;
+                let mut mask = vec![1f32; values.len()];
+                for &i in arg_sort_f32(&abs)[..n_pruned].iter() {
+                    mask[i] = 0.
+                }
+                mask
+            }
+            Self::Channel { sparsity } => {
+                check_sparsity(sparsity)?;
+                group_mask(&values, out_c, sparsity)
+            }
+            Self::Head {
+                num_heads,
+                sparsity,
+            } => {
+                check_sparsity(sparsity)?;
+                if num_heads == 0 || !out_c.is_multiple_of(num_heads) {
+                    candle::bail!("{out_c} output channels cannot be split in {num_heads} heads")
+                }
+                group_mask(&values, num_heads, sparsity)
+            }
+            Self::NM { n, m } => {
+                if n > m || m == 0 {
+                    candle::bail!("invalid N:M sparsity pattern {n}:{m}")
+                }
+                // The compact format stores the position of the kept weights within their
+                // group as a `u8`.
+                if m > 256 {
+                    candle::bail!("N:M sparsity only supports groups of up to 256, got {n}:{m}")
+                }
+                if !in_c.is_multiple_of(m) {
+                    candle::bail!("input size {in_c} is not divisible by {m} for {n}:{m} sparsity")
+                }
+                let mut mask = vec![0f32; values.len()];
+                for (group, mask) in values.chunks(m).zip(mask.chunks_mut(m)) {
+                    let abs = group.iter().map(|v| -v.abs()).collect::<Vec<_>>();
+                    for &i in arg_sort_f32(&abs)[..n].iter() {
+                        mask[i] = 1.
+                    }
+                }
+                mask
+            }
+        };
+        Tensor::from_vec(mask, dims, &Device::Cpu)?
+            .to_dtype(weight.dtype())?
+            .to_device(weight.device())
+    }
+}
+
+/// Default filter used to select the tensors to prune: the weights of `Linear` and `Conv`
+/// layers, i.e. tensors named `*weight` with at least two dimensions.
+pub fn is_weight(name: &str, tensor: &Tensor) -> bool {
+    name.ends_with("weight") && tensor.rank() >= 2
+}
+
+/// A mask computed for a given tensor together with the method used to obtain it.
+#[derive(Debug, Clone)]
+pub struct Mask {
+    pub method: PruneMethod,
+    pub mask: Tensor,
+}
+
+/// The set of pruning masks for a model, indexed by tensor name.
+#[derive(Debug, Clone, Default)]
+pub struct PruneMasks {
+    masks: HashMap<String, Mask>,
+}
+
+impl PruneMasks {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Computes the mask for `tensor` using `method` and records it under `name`.
+    pub fn add(&mut self, name: &str, tensor: &Tensor, method: PruneMethod) -> Result<&Mask> {
+        let mask = method.mask(tensor)?;
+        self.masks.insert(name.to_string(), Mask { method, mask });
+        Ok(&self.masks[name])
+    }
+
+    pub fn get(&self, name: &str) -> Option<&Mask> {
+        self.masks.get(name)
+    }
+
+    pub fn masks(&self) -> &HashMap<String, Mask> {
+        &self.masks
+    }
+
+    pub fn len(&self) -> usize {
+        self.masks.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.masks.is_empty()
+    }
+
+    /// Zeroes the pruned weights of the variables in `varmap`. This should be called after each
+    /// optimizer step when fine-tuning a pruned model.
+    pub fn apply(&self, varmap: &VarMap) -> Result<()> {
+        let data = varmap.data().lock().unwrap();
+        for (name, mask) in self.masks.iter() {
+            match data.get(name) {
+                None => candle::bail!("cannot find {name} in VarMap"),
+                Some(var) => var.set(&var.as_tensor().mul(&mask.mask)?)?,
+            }
+        }
+        Ok(())
+    }
+
+    /// Zeroes the pruned weights of a set of loaded tensors.
+    pub fn apply_to_tensors(&self, tensors: &mut HashMap<String, Tensor>) -> Result<()> {
+        for (name, mask) in self.masks.iter() {
+            match tensors.get_mut(name) {
+                None => candle::bail!("cannot find {name} in tensors"),
+                Some(t) => *t = t.mul(&mask.mask)?,
+            }
+        }
+        Ok(())
+    }
+
+    /// Returns the tensors from `varmap` that have a mask.
+    pub fn pruned_tensors(&self, varmap: &VarMap) -> Result<HashMap<String, Tensor>> {
+        let data = varmap.data().lock().unwrap();
+        let mut tensors = HashMap::new();
+        for name in self.masks.keys() {
+            match data.get(name) {
+                None => candle::bail!("cannot find {name} in VarMap"),
+                Some(var) => tensors.insert(name.clone(), var.as_tensor().clone()),
+            };
+        }
+        Ok(tensors)
+    }
+}
+
+/// Prunes the variables of `varmap` selected by `filter` in place and returns the masks so that
+/// they can be re-applied during fine-tuning.
+pub fn prune_varmap<F>(varmap: &VarMap, method: PruneMethod, filter: F) -> Result<PruneMasks>
+where
+    F: Fn(&str, &Tensor) -> bool,
+{
+    let mut masks = PruneMasks::new();
+    let data = varmap.data().lock().unwrap();
+    for (name, var) in data.iter() {
+        let tensor = var.as_tensor();
+        if filter(name, tensor) {
+            let mask = masks.add(name, tensor, method)?;
+            var.set(&tensor.mul(&mask.mask)?)?;
+        }
+    }
+    Ok(masks)
+}
+
+/// Prunes the tensors of a loaded model selected by `filter` in place and returns the masks.
+pub fn prune_tensors<F>(
+    tensors: &mut HashMap<String, Tensor>,
+    method: PruneMethod,
+    filter: F,
+) -> Result<PruneMasks>
+where
+    F: Fn(&str, &Tensor) -> bool,
+{
+    let mut masks = PruneMasks::new();
+    for (name, tensor) in tensors.iter_mut() {
+        if filter(name, tensor) {
+            let mask = masks.add(name, tensor, method)?;
+            *tensor = tensor.mul(&mask.mask)?;
+        }
+    }
+    Ok(masks)
+}
+
+/// Sparsity statistics for a single tensor.
+#[derive(Debug, Clone, PartialEq)]
+pub struct LayerSparsity {
+    pub name: String,
+    pub shape: Vec<usize>,
+    pub elem_count: usize,
+    pub zeros: usize,
+}
+
+impl LayerSparsity {
+    /// The fraction of zero elements.
+    pub fn sparsity(&self) -> f64 {
+        if self.elem_count == 0 {
+            0.
+        } else {
+            self.zeros as f64 / self.elem_count as f64
+        }
+    }
+}
+
+impl std::fmt::Display for LayerSparsity {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{}: {:?} {}/{} zeros ({:.2}%)",
+            self.name,
+            self.shape,
+            self.zeros,
+            self.elem_count,
+            100. * self.sparsity()
+        )
+    }
+}
+
+/// Computes the per-tensor sparsity, the result is sorted by tensor name.
+pub fn sparsity_report(tensors: &HashMap<String, Tensor>) -> Result<Vec<LayerSparsity>> {
+    let mut report = Vec::with_capacity(tensors.len());
+    for (name, tensor) in tensors.iter() {
+        let zeros = tensor
+            .eq(0.)?
+            .to_dtype(DType::U32)?
+            .sum_all()?
+            .to_scalar::<u32>()? as usize;
+        report.push(LayerSparsity {
+            name: name.clone(),
+            shape: tensor.dims().to_vec(),
+            elem_count: tensor.elem_count(),
+            zeros,
+        })
+    }
+    report.sort_by(|a, b| a.name.cmp(&b.name));
+    Ok(report)
+}
+
+/// Computes the per-variable sparsity of a `VarMap`, the result is sorted by variable name.
+pub fn varmap_sparsity_report(varmap: &VarMap) -> Result<Vec<LayerSparsity>> {
+    let tensors = varmap
+        .data()
+        .lock()
+        .unwrap()
+        .iter()
+        .map(|(k, v)| (k.clone(), v.as_tensor().clone()))
+        .collect::<HashMap<_, _>>();
+    sparsity_report(&tensors)
+}
+
+const SHAPE_SUFFIX: &str = ".prune_shape";
+const ROWS_SUFFIX: &str = ".prune_rows";
+const VALUES_SUFFIX: &str = ".prune_values";
+const BITMASK_SUFFIX: &str = ".prune_bitmask";
+const NM_SUFFIX: &str = ".prune_nm";
+const POSITIONS_SUFFIX: &str = ".prune_positions";
+
+fn u32_tensor(vs: &[usize]) -> Result<Tensor> {
+    let vs = vs.iter().map(|&v| v as u32).collect::<Vec<_>>();
+    Tensor::new(vs, &Device::Cpu)
+}
+
+fn usize_vec(t: &Tensor) -> Result<Vec<usize>> {
+    let vs = t.to_dtype(DType::U32)?.to_vec1::<u32>()?;
+    Ok(vs.into_iter().map(|v| v as usize).collect())
+}
+
+/// Returns a compact representation of the pruned tensors, suitable for serialization with
+/// `candle::safetensors::save`.
+///
+/// - Tensors pruned by channel or by head only store the kept output channels in `{name}`, the
+///   indexes of these channels being stored in `{name}.prune_rows`.
+/// - Tensors pruned by magnitude store the non-pruned values as a flat `{name}.prune_values`
+///   tensor and the mask as a `u8` bitmask in `{name}.prune_bitmask`, bit `i % 8` of byte
+///   `i / 8` being set when the flat index `i` is kept. When the sparsity is too low for this
+///   to be smaller than the dense tensor, the tensor is kept as is instead.
+/// - Tensors with N:M sparsity store the `n` kept values of each group in `{name}.prune_values`,
+///   their position within the group as `u8` in `{name}.prune_positions` and the pattern itself
+///   in `{name}.prune_nm`.
+///
+/// In all cases, the original shape is stored in `{name}.prune_shape`. Tensors without a mask
+/// are kept as is. Use [`decompact`] to recover the dense tensors.
+pub fn compact(
+    tensors: &HashMap<String, Tensor>,
+    masks: &PruneMasks,
+) -> Result<HashMap<String, Tensor>> {
+    let mut compacted = HashMap::new();
+    for (name, tensor) in tensors.iter() {
+        let mask = match masks.get(name) {
+            None => {
+                compacted.insert(name.clone(), tensor.clone());
+                continue;
+            }
+            Some(mask) => mask,
+        };
+        let mask_values = mask.mask.flatten_all()?.to_dtype(DType::F32)?;
+        let kept = |vs: &[f32]| {
+            vs.iter()
+                .enumerate()
+                .filter_map(|(i, &v)| (v > 0.).then_some(i))
+                .collect::<Vec<_>>()
+        };
+        match mask.method {
+            PruneMethod::Channel { .. } | PruneMethod::Head { .. } => {
+                let out_c = tensor.dim(0)?;
+                let rows = mask_values.reshape((out_c, ()))?.max(1)?.to_vec1::<f32>()?;
+                let rows = u32_tensor(&kept(&rows))?;
+                let values = tensor.index_select(&rows.to_device(tensor.device())?, 0)?;
+                compacted.insert(name.clone(), values);
+                compacted.insert(format!("{name}{ROWS_SUFFIX}"), rows);
+            }
+            PruneMethod::Magnitude { .. } => {
+                let mask_values = mask_values.to_vec1::<f32>()?;
+                let indexes = kept(&mask_values);
+                let elem_size = tensor.dtype().size_in_bytes();
+                let bitmask_size = mask_values.len().div_ceil(8);
+                if indexes.len() * elem_size + bitmask_size >= mask_values.len() * elem_size {
+                    compacted.insert(name.clone(), tensor.clone());
+                    continue;
+                }
+                let mut bitmask = vec![0u8; bitmask_size];
+                for &i in indexes.iter() {
+                    bitmask[i / 8] |= 1 << (i % 8)
+                }
+                let indexes = u32_tensor(&indexes)?;
+                let values = tensor
+                    .flatten_all()?
+                    .index_select(&indexes.to_device(tensor.device())?, 0)?;
+                compacted.insert(format!("{name}{VALUES_SUFFIX}"), values);
+                compacted.insert(
+                    format!("{name}{BITMASK_SUFFIX}"),
+                    Tensor::new(bitmask, &Device::Cpu)?,
+                );
+            }
+            PruneMethod::NM { n, m } => {
+                let indexes = kept(&mask_values.to_vec1::<f32>()?);
+                let positions = indexes.iter().map(|i| (i % m) as u8).collect::<Vec<_>>();
+                let indexes = u32_tensor(&indexes)?;
+                let values = tensor
+                    .flatten_all()?
+                    .index_select(&indexes.to_device(tensor.device())?, 0)?;
+                compacted.insert(format!("{name}{VALUES_SUFFIX}"), values);
+                compacted.insert(
+                    format!("{name}{POSITIONS_SUFFIX}"),
+                    Tensor::new(positions, &Device::Cpu)?,
+                );
+                compacted.insert(format!("{name}{NM_SUFFIX}"), u32_tensor(&[n, m])?);
+            }
+        }
+        compacted.insert(format!("{name}{SHAPE_SUFFIX}"), u32_tensor(tensor.dims())?);
+    }
+    Ok(compacted)
+}
+
+/// Recovers the dense tensors from the output of [`compact`].
+pub fn decompact(compacted: &HashMap<String, Tensor>) -> Result<HashMap<String, Tensor>> {
+    let get = |name: &str| match compacted.get(name) {
+        None => candle::bail!("cannot find {name} in compacted tensors"),
+        Some(t) => Ok(t),
+    };
+    let suffixes = [
+        ROWS_SUFFIX,
+        VALUES_SUFFIX,
+        BITMASK_SUFFIX,
+        NM_SUFFIX,
+        POSITIONS_SUFFIX,
+    ];
+    let mut tensors = HashMap::new();
+    for (name, tensor) in compacted.iter() {
+        if let Some(name) = name.strip_suffix(SHAPE_SUFFIX) {
+            let shape = usize_vec(tensor)?;
+            let dense = if let Some(rows) = compacted.get(&format!("{name}{ROWS_SUFFIX}")) {
+                let values = get(name)?;
+                let rows = rows.to_device(values.device())?;
+                Tensor::zeros(shape, values.dtype(), values.device())?
+                    .index_add(&rows, values, 0)?
+            } else {
+                let values = get(&format!("{name}{VALUES_SUFFIX}"))?;
+                let indexes = match compacted.get(&format!("{name}{NM_SUFFIX}")) {
+                    None => {
+                        let bitmask = get(&format!("{name}{BITMASK_SUFFIX}"))?;
+                        let indexes = bitmask
+                            .to_vec1::<u8>()?
+                            .iter()
+                            .enumerate()
+                            .flat_map(|(i, b)| {
+                                (0..8)
+                                    .filter(move |j| b & (1 << j) != 0)
+                                    .map(move |j| 8 * i + j)
+                            })
+                            .collect::<Vec<_>>();
+                        u32_tensor(&indexes)?
+                    }
+                    Some(nm) => {
+                        let (n, m) = match usize_vec(nm)?[..] {
+                            [n, m] => (n, m),
+                            ref nm => candle::bail!("unexpected N:M pattern {nm:?} for {name}"),
+                        };
+                        let positions = get(&format!("{name}{POSITIONS_SUFFIX}"))?;
+                        let indexes = usize_vec(positions)?
+                            .iter()
+                            .enumerate()
+                            .map(|(i, p)| (i / n) * m + p)
+                            .collect::<Vec<_>>();
+                        u32_tensor(&indexes)?
+                    }
+                };
+                let indexes = indexes.to_device(values.device())?;
+                let elem_count = shape.iter().product::<usize>();
+                Tensor::zeros(elem_count, values.dtype(), values.device())?
+                    .index_add(&indexes, values, 0)?
+                    .reshape(shape)?
+            };
+            tensors.insert(name.to_string(), dense);
+        } else if suffixes.iter().all(|s| !name.ends_with(s))
+            && !compacted.contains_key(&format!("{name}{SHAPE_SUFFIX}"))
+        {
+            tensors.insert(name.clone(), tensor.clone());
+        }
+    }
+    Ok(tensors)
+}
+
+/// Saves the pruned tensors in the compact format described in [`compact`] as a safetensors
+/// file.
+pub fn save_compact<P: AsRef<std::path::Path>>(
+    tensors: &HashMap<String, Tensor>,
+    masks: &PruneMasks,
+    path: P,
+) -> Result<()> {
+    candle::safetensors::save(&compact(tensors, masks)?, path)
+}
+
+/// Loads a safetensors file written by [`save_compact`] and returns the dense tensors.
+pub fn load_compact<P: AsRef<std::path::Path>>(
+    path: P,
+    device: &Device,
+) -> Result<HashMap<String, Tensor>> {
+    decompact(&candle::safetensors::load(path, device)?)
+}
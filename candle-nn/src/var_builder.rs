@@ -729,6 +729,72 @@ impl<'a> VarBuilder<'a> {
             _phantom: std::marker::PhantomData,
         }
     }
+
+    /// Gets a VarBuilder that records every tensor it returns in `recorder`, this can be used to
+    /// enumerate the parameters of a model after it has been built, see
+    /// [`crate::introspect`].
+    pub fn record(self, recorder: &crate::introspect::ParamRecorder) -> Self {
+        let dtype = self.dtype();
+        let device = self.device().clone();
+        let path = self.path.clone();
+        // The names passed to the backend already include the path prefix.
+        let inner = Self {
+            path: vec![],
+            ..self
+        };
+        let backend = Record {
+            inner,
+            recorder: recorder.clone(),
+        };
+        let backend: Box<dyn SimpleBackend + 'a> = Box::new(backend);
+        let data = TensorData {
+            backend: Arc::new(backend),
+            device,
+            dtype,
+        };
+        Self {
+            data: Arc::new(data),
+            dtype,
+            path,
+            _phantom: std::marker::PhantomData,
+        }
+    }
+}
+
+struct Record<'a> {
+    inner: VarBuilder<'a>,
+    recorder: crate::introspect::ParamRecorder,
+}
+
+impl SimpleBackend for Record<'_> {
+    fn get(
+        &self,
+        s: Shape,
+        name: &str,
+        h: crate::Init,
+        dtype: DType,
+        dev: &Device,
+    ) -> Result<Tensor> {
+        let t = self
+            .inner
+            .get_with_hints_dtype(s, name, h, dtype)?
+            .to_device(dev)?;
+        self.recorder.record(name, &t);
+        Ok(t)
+    }
+
+    fn get_unchecked(&self, name: &str, dtype: DType, dev: &Device) -> Result<Tensor> {
+        let t = self
+            .inner
+            .get_unchecked_dtype(name, dtype)?
+            .to_device(dev)?;
+        self.recorder.record(name, &t);
+        Ok(t)
+    }
+
+    fn contains_tensor(&self, name: &str) -> bool {
+        self.inner.contains_tensor(name)
+    }
 }
 
 pub struct ShardedSafeTensors(candle::safetensors::MmapedSafetensors);
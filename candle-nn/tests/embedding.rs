@@ -0,0 +1,97 @@
+#[cfg(feature = "mkl")]
+extern crate intel_mkl_src;
+
+#[cfg(feature = "accelerate")]
+extern crate accelerate_src;
+
+use anyhow::{Context, Result};
+use candle::{DType, Device, Module, Tensor, Var};
+use candle_nn::{Embedding, EmbeddingBag, EmbeddingBagMode, Optimizer};
+
+#[test]
+fn embedding_bag() -> Result<()> {
+    let dev = &Device::Cpu;
+    let emb = Tensor::new(&[[1f32, 2.], [3., 4.], [5., 0.], [-1., 8.]], dev)?;
+    let ids = Tensor::new(&[[0u32, 1], [2, 3]], dev)?;
+    let sum = EmbeddingBag::new(emb.clone(), 2, EmbeddingBagMode::Sum);
+    assert_eq!(sum.forward(&ids)?.to_vec2::<f32>()?, [[4., 6.], [4., 8.]]);
+    let mean = EmbeddingBag::new(emb.clone(), 2, EmbeddingBagMode::Mean);
+    assert_eq!(mean.forward(&ids)?.to_vec2::<f32>()?, [[2., 3.], [2., 4.]]);
+    let max = EmbeddingBag::new(emb.clone(), 2, EmbeddingBagMode::Max);
+    assert_eq!(max.forward(&ids)?.to_vec2::<f32>()?, [[3., 4.], [5., 8.]]);
+
+    // Variable size bags, the second one being empty.
+    let ids = Tensor::new(&[0u32, 1, 2, 3, 3], dev)?;
+    let offsets = Tensor::new(&[0u32, 2, 2], dev)?;
+    let ys = sum.forward_with_offsets(&ids, &offsets, None)?;
+    assert_eq!(ys.to_vec2::<f32>()?, [[4., 6.], [0., 0.], [3., 16.]]);
+    let ys = mean.forward_with_offsets(&ids, &offsets, None)?;
+    assert_eq!(ys.to_vec2::<f32>()?, [[2., 3.], [0., 0.], [1., 16. / 3.]]);
+    let ys = max.forward_with_offsets(&ids, &offsets, None)?;
+    assert_eq!(ys.to_vec2::<f32>()?, [[3., 4.], [0., 0.], [5., 8.]]);
+    let weights = Tensor::new(&[1f32, 0.5, 2., 0., 0.], dev)?;
+    let ys = sum.forward_with_offsets(&ids, &offsets, Some(&weights))?;
+    assert_eq!(ys.to_vec2::<f32>()?, [[2.5, 4.], [0., 0.], [10., 0.]]);
+    assert!(max
+        .forward_with_offsets(&ids, &offsets, Some(&weights))
+        .is_err());
+
+    // Fixed size bags with no indexes.
+    let ids = Tensor::zeros((2, 0), DType::U32, dev)?;
+    for bag in [&sum, &mean, &max] {
+        assert_eq!(bag.forward(&ids)?.to_vec2::<f32>()?, [[0., 0.], [0., 0.]]);
+    }
+    Ok(())
+}
+
+#[test]
+fn embedding_sparse_grad() -> Result<()> {
+    let dev = &Device::Cpu;
+    let emb = Var::new(&[[1f32, 2.], [3., 4.], [5., 0.], [-1., 8.]], dev)?;
+    let layer = Embedding::new(emb.as_tensor().clone(), 2);
+    let ids = Tensor::new(&[[3u32, 1, 3]], dev)?;
+    let loss_fn = |ys: &Tensor| ys.sqr()?.sum_all()? * 0.5;
+    let grads = loss_fn(&layer.forward(&ids)?)?.backward()?;
+    let dense_grad = grads.get(&emb).context("no grad for emb")?;
+
+    let ys = layer.forward_sparse(&ids)?;
+    let grads = loss_fn(&ys)?.backward()?;
+    assert!(grads.get(&emb).is_none());
+    let grad_ys = grads.get(&ys).context("no grad for ys")?;
+    let sparse_grad = layer.sparse_grad(&ids, grad_ys)?;
+    assert_eq!(sparse_grad.nnz(), 2);
+    assert_eq!(sparse_grad.indices().to_vec2::<u32>()?, [[1, 3]]);
+    assert_eq!(
+        sparse_grad.to_dense()?.to_vec2::<f32>()?,
+        dense_grad.to_vec2::<f32>()?
+    );
+
+    let sgd = candle_nn::SGD::new(vec![emb.clone()], 0.5)?;
+    sgd.sparse_step(&emb, &sparse_grad)?;
+    assert_eq!(
+        emb.to_vec2::<f32>()?,
+        [[1., 2.], [1.5, 2.], [5., 0.], [0., 0.]]
+    );
+    Ok(())
+}
+
+#[test]
+fn sparse_step_in_place() -> Result<()> {
+    let dev = &Device::Cpu;
+    let emb = Var::new(&[[1f32, 2.], [3., 4.], [f32::NAN, -0.], [-1., 8.]], dev)?;
+    let view = emb.as_tensor().clone();
+    let indices = Tensor::new(&[[3u32, 1, 3]], dev)?;
+    let values = Tensor::new(&[[1f32, 1.], [2., 0.], [1., -2.]], dev)?;
+    let grad = candle::sparse::CooTensor::new(indices, values, (4, 2))?;
+    let sgd = candle_nn::SGD::new(vec![emb.clone()], 0.5)?;
+    sgd.sparse_step(&emb, &grad)?;
+    // The update is applied to the storage of the variable itself and the rows without a
+    // gradient, including the nan and the negative zero, are left untouched.
+    let rows = view.to_vec2::<f32>()?;
+    assert_eq!(rows[0], [1., 2.]);
+    assert_eq!(rows[1], [2., 4.]);
+    assert!(rows[2][0].is_nan());
+    assert_eq!(rows[2][1].to_bits(), (-0f32).to_bits());
+    assert_eq!(rows[3], [-2., 8.5]);
+    Ok(())
+}
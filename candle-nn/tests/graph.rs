@@ -0,0 +1,161 @@
+#[cfg(feature = "mkl")]
+extern crate intel_mkl_src;
+
+#[cfg(feature = "accelerate")]
+extern crate accelerate_src;
+
+use anyhow::{Context, Result};
+use candle::{test_device, test_utils::to_vec2_round, DType, Device, Tensor, Var};
+use candle_nn::graph::{self, EdgeIndex, GATConvConfig, Reduce};
+use candle_nn::{Optimizer, VarBuilder, VarMap};
+
+#[test]
+fn edge_index() -> Result<()> {
+    let dev = &Device::Cpu;
+    let edges = EdgeIndex::from_pairs(&[(0, 1), (1, 2), (1, 0)], 3, dev)?;
+    assert_eq!(edges.num_edges(), 3);
+    assert_eq!(edges.in_degree(DType::F32)?.to_vec1::<f32>()?, [1., 1., 1.]);
+    let undirected = edges.to_undirected()?;
+    assert_eq!(undirected.src().to_vec1::<u32>()?, [0, 1, 1, 2]);
+    assert_eq!(undirected.dst().to_vec1::<u32>()?, [1, 0, 2, 1]);
+    let looped = edges.add_self_loops()?;
+    assert_eq!(looped.num_edges(), 6);
+    assert!(EdgeIndex::from_pairs(&[(0, 3)], 3, dev).is_err());
+    let t = Tensor::new(&[[0u32, 1], [1, 2]], dev)?;
+    let edges = EdgeIndex::from_tensor(&t, 3)?;
+    assert_eq!(edges.dst().to_vec1::<u32>()?, [1, 2]);
+    Ok(())
+}
+
+// The layers aggregate their messages with the sum and mean reductions, these should not go
+// through the cpu only `index_reduce` so that the layers also run on cuda and metal.
+fn scatter_reduce(dev: &Device) -> Result<()> {
+    let src = Tensor::new(&[[1f32, 5.], [3., 2.], [-1., 4.], [2., 2.]], dev)?;
+    let index = Tensor::new(&[0u32, 0, 2, 0], dev)?;
+    let sum = graph::scatter_reduce(&src, &index, 4, Reduce::Sum)?;
+    assert_eq!(
+        sum.to_vec2::<f32>()?,
+        [[6., 9.], [0., 0.], [-1., 4.], [0., 0.]]
+    );
+    let mean = graph::scatter_reduce(&src, &index, 4, Reduce::Mean)?;
+    assert_eq!(
+        mean.to_vec2::<f32>()?,
+        [[2., 3.], [0., 0.], [-1., 4.], [0., 0.]]
+    );
+    let max = graph::scatter_reduce(&src, &index, 4, Reduce::Max)?;
+    assert_eq!(
+        max.to_vec2::<f32>()?,
+        [[3., 5.], [0., 0.], [-1., 4.], [0., 0.]]
+    );
+    let min = graph::scatter_reduce(&src, &index, 4, Reduce::Min)?;
+    assert_eq!(
+        min.to_vec2::<f32>()?,
+        [[1., 2.], [0., 0.], [-1., 4.], [0., 0.]]
+    );
+
+    assert!(sum.device().same_device(dev));
+    assert!(max.device().same_device(dev));
+
+    // The gradient of min only flows to the minimum values, ties share it evenly.
+    let src = Var::from_tensor(&src)?;
+    let min = graph::scatter_reduce(&src, &index, 4, Reduce::Min)?;
+    let grads = min.sum_all()?.backward()?;
+    let grad = grads.get(&src).context("no grad for src")?;
+    assert_eq!(
+        grad.to_vec2::<f32>()?,
+        [[1., 0.], [0., 0.5], [1., 1.], [0., 0.5]]
+    );
+    Ok(())
+}
+
+test_device!(
+    scatter_reduce,
+    scatter_reduce_cpu,
+    scatter_reduce_gpu,
+    scatter_reduce_metal
+);
+
+#[test]
+fn gcn_conv() -> Result<()> {
+    let dev = &Device::Cpu;
+    let edges = EdgeIndex::from_pairs(&[(0, 1), (1, 2)], 3, dev)?.to_undirected()?;
+    let weight = Tensor::new(&[[1f32, 0.], [0., 1.]], dev)?;
+    let conv = graph::GCNConv::new(weight, None);
+    let xs = Tensor::new(&[[1f32, 0.], [0., 1.], [1., 1.]], dev)?;
+    let ys = conv.forward(&xs, &edges)?;
+    // Degrees with self-loops are [2, 3, 2].
+    let d = |i: f32, j: f32| 1. / (i * j).sqrt();
+    let expected = [
+        [d(2., 2.), d(2., 3.)],
+        [d(2., 3.) + d(3., 2.), d(3., 3.) + d(3., 2.)],
+        [d(2., 2.), d(3., 2.) + d(2., 2.)],
+    ];
+    let round = |v: f32| (v * 1e4).round() / 1e4;
+    let expected = expected.map(|r| r.map(round)).map(|r| r.to_vec()).to_vec();
+    assert_eq!(to_vec2_round(&ys, 4)?, expected);
+    Ok(())
+}
+
+#[test]
+fn gat_conv() -> Result<()> {
+    let dev = &Device::Cpu;
+    let edges = EdgeIndex::from_pairs(&[(0, 1), (1, 2), (2, 0)], 3, dev)?;
+    let vb = VarBuilder::from_varmap(&VarMap::new(), DType::F32, dev);
+    let config = GATConvConfig {
+        heads: 2,
+        ..Default::default()
+    };
+    let conv = graph::gat_conv(4, 3, config, vb)?;
+    let xs = Tensor::randn(0f32, 1., (3, 4), dev)?;
+    let (ys, alpha) = conv.forward_with_attention(&xs, &edges)?;
+    assert_eq!(ys.dims(), [3, 6]);
+    assert_eq!(alpha.dims(), [6, 2]);
+    // The attention coefficients of the edges arriving on each node sum to one.
+    let dst = edges.add_self_loops()?.dst().clone();
+    let sums = graph::scatter_reduce(&alpha, &dst, 3, Reduce::Sum)?;
+    assert_eq!(to_vec2_round(&sums, 4)?, [[1., 1.], [1., 1.], [1., 1.]]);
+
+    let config = GATConvConfig {
+        heads: 2,
+        concat: false,
+        ..Default::default()
+    };
+    let vb = VarBuilder::from_varmap(&VarMap::new(), DType::F32, dev);
+    let conv = graph::gat_conv(4, 3, config, vb)?;
+    assert_eq!(conv.forward(&xs, &edges)?.dims(), [3, 3]);
+    Ok(())
+}
+
+#[test]
+fn sage_conv_training() -> Result<()> {
+    let dev = &Device::Cpu;
+    // Two disconnected cliques, the label of a node is the clique it belongs to.
+    let mut pairs = vec![];
+    for c in [0u32, 4] {
+        for i in 0..4 {
+            for j in 0..4 {
+                if i != j {
+                    pairs.push((c + i, c + j))
+                }
+            }
+        }
+    }
+    let edges = EdgeIndex::from_pairs(&pairs, 8, dev)?;
+    let xs = Tensor::randn(0f32, 1., (8, 4), dev)?;
+    let labels = Tensor::new(&[0u32, 0, 0, 0, 1, 1, 1, 1], dev)?;
+    let varmap = VarMap::new();
+    let vb = VarBuilder::from_varmap(&varmap, DType::F32, dev);
+    let conv = graph::sage_conv(4, 2, Reduce::Mean, vb)?;
+    let mut opt = candle_nn::SGD::new(varmap.all_vars(), 0.5)?;
+    let loss = |conv: &graph::SAGEConv| {
+        let logits = conv.forward(&xs, &edges)?;
+        candle_nn::loss::cross_entropy(&logits, &labels)
+    };
+    let initial_loss = loss(&conv)?.to_scalar::<f32>()?;
+    for _step in 0..50 {
+        opt.backward_step(&loss(&conv)?)?;
+    }
+    let final_loss = loss(&conv)?.to_scalar::<f32>()?;
+    assert!(final_loss < initial_loss, "{initial_loss} {final_loss}");
+    Ok(())
+}
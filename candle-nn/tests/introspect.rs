@@ -0,0 +1,125 @@
+#[cfg(feature = "mkl")]
+extern crate intel_mkl_src;
+
+#[cfg(feature = "accelerate")]
+extern crate accelerate_src;
+
+use candle::{DType, Device, Module, Result, Tensor};
+use candle_nn::introspect::ParamRecorder;
+use candle_nn::{Optimizer, VarBuilder, VarMap};
+use std::collections::HashMap;
+
+#[test]
+fn record_named_parameters() -> Result<()> {
+    let varmap = VarMap::new();
+    let recorder = ParamRecorder::new();
+    let vb = VarBuilder::from_varmap(&varmap, DType::F32, &Device::Cpu).record(&recorder);
+    let vb = vb.pp("model");
+    let _l1 = candle_nn::linear(4, 8, vb.pp("encoder").pp("l1"))?;
+    let _l2 = candle_nn::linear_no_bias(8, 8, vb.pp("encoder").pp("l2"))?;
+    let _head = candle_nn::linear(8, 2, vb.pp("head"))?;
+    // Requesting the same tensor twice only records it once.
+    let _ = vb.get((2, 8), "head.weight")?;
+
+    let names: Vec<_> = recorder
+        .named_parameters()
+        .into_iter()
+        .map(|p| p.name)
+        .collect();
+    assert_eq!(
+        names,
+        [
+            "model.encoder.l1.weight",
+            "model.encoder.l1.bias",
+            "model.encoder.l2.weight",
+            "model.head.weight",
+            "model.head.bias"
+        ]
+    );
+    assert_eq!(recorder.num_parameters(), 32 + 8 + 64 + 16 + 2);
+    assert_eq!(recorder.num_bytes(), 4 * recorder.num_parameters());
+    assert_eq!(recorder.named_parameters_with_prefix("model.enc").len(), 0);
+    assert_eq!(
+        recorder.named_parameters_with_prefix("model.encoder").len(),
+        3
+    );
+
+    let summary = recorder.summary(3);
+    let encoder = summary.get("model.encoder").unwrap();
+    assert_eq!(encoder.num_tensors, 3);
+    assert_eq!(encoder.num_parameters, 104);
+    assert_eq!(encoder.dtypes, [DType::F32]);
+    assert_eq!(summary.get("model.encoder.l2").unwrap().num_bytes, 256);
+    assert_eq!(summary.get("").unwrap().num_parameters, 122);
+    let paths: Vec<_> = summary.rows.iter().map(|r| r.path.as_str()).collect();
+    assert_eq!(
+        paths,
+        [
+            "model",
+            "model.encoder",
+            "model.encoder.l1",
+            "model.encoder.l2",
+            "model.head"
+        ]
+    );
+    let table = summary.to_string();
+    assert!(table.contains("Total"));
+    assert!(table.contains("    l1"));
+    assert_eq!(recorder.summary(1).rows.len(), 1);
+    Ok(())
+}
+
+#[test]
+fn record_dtypes_from_tensors() -> Result<()> {
+    let dev = &Device::Cpu;
+    let ts: HashMap<String, Tensor> = [
+        (
+            "emb.weight".to_string(),
+            Tensor::zeros((10, 4), DType::F16, dev)?,
+        ),
+        ("ln.weight".to_string(), Tensor::ones(4, DType::F32, dev)?),
+    ]
+    .into_iter()
+    .collect();
+    let recorder = ParamRecorder::new();
+    let vb = VarBuilder::from_tensors(ts, DType::F16, dev).record(&recorder);
+    let _ = vb.get((10, 4), "emb.weight")?;
+    let _ = vb.get_with_hints_dtype(4, "ln.weight", Default::default(), DType::F32)?;
+    let summary = recorder.summary(1);
+    assert_eq!(summary.total.num_bytes, 10 * 4 * 2 + 4 * 4);
+    assert_eq!(summary.total.dtypes, [DType::F16, DType::F32]);
+    // These tensors are not variables so they cannot be trained.
+    assert!(recorder.trainable_vars().is_err());
+    recorder.freeze("");
+    assert!(recorder.trainable_vars()?.is_empty());
+    Ok(())
+}
+
+#[test]
+fn freeze_sub_trees() -> Result<()> {
+    let dev = &Device::Cpu;
+    let varmap = VarMap::new();
+    let recorder = ParamRecorder::new();
+    let vb = VarBuilder::from_varmap(&varmap, DType::F32, dev).record(&recorder);
+    let l1 = candle_nn::linear(2, 4, vb.pp("backbone"))?;
+    let l2 = candle_nn::linear(4, 1, vb.pp("head"))?;
+
+    assert_eq!(recorder.freeze("backbone"), 2);
+    assert!(recorder.is_frozen("backbone.weight"));
+    assert!(!recorder.is_frozen("head.weight"));
+    assert_eq!(recorder.num_trainable_parameters(), 5);
+    assert_eq!(recorder.summary(1).total.num_trainable_parameters, 5);
+
+    let backbone_before = l1.weight().to_vec2::<f32>()?;
+    let head_before = l2.weight().to_vec2::<f32>()?;
+    let mut sgd = candle_nn::SGD::new(recorder.trainable_vars()?, 0.1)?;
+    let xs = Tensor::new(&[[1f32, 2.], [3., 4.]], dev)?;
+    let loss = l2.forward(&l1.forward(&xs)?)?.sqr()?.sum_all()?;
+    sgd.backward_step(&loss)?;
+    assert_eq!(l1.weight().to_vec2::<f32>()?, backbone_before);
+    assert_ne!(l2.weight().to_vec2::<f32>()?, head_before);
+
+    assert_eq!(recorder.unfreeze(""), 4);
+    assert_eq!(recorder.trainable_vars()?.len(), 4);
+    Ok(())
+}
@@ -4,7 +4,8 @@ extern crate intel_mkl_src;
 #[cfg(feature = "accelerate")]
 extern crate accelerate_src;
 
-use candle::{Device, Result, Tensor};
+use candle::{DType, Device, Result, Tensor};
+use candle_nn::kv_cache::{ConcatKvCache, QuantizedKvCache, QuantizedKvDType};
 
 #[test]
 fn kv_cache() -> Result<()> {
@@ -121,3 +122,162 @@ fn rotating_kv_cache() -> Result<()> {
     }
     Ok(())
 }
+
+#[test]
+fn quantized_kv_cache() -> Result<()> {
+    let dev = &Device::Cpu;
+    let dtypes = [
+        (QuantizedKvDType::Q8_0, 0.01),
+        (QuantizedKvDType::Q4_0, 0.1),
+    ];
+    #[cfg(feature = "float8")]
+    let dtypes = dtypes.into_iter().chain([(QuantizedKvDType::F8E4M3, 0.05)]);
+    for (dtype, tol) in dtypes {
+        let mut cache = QuantizedKvCache::new(2, dtype).with_residual_len(0);
+        let mut reference = ConcatKvCache::new(2);
+        assert!(cache.k()?.is_none());
+        for seq_len in [5, 1, 1, 3] {
+            let k = Tensor::randn(0f32, 1., (2, 4, seq_len, 64), dev)?;
+            let v = Tensor::randn(0f32, 1., (2, 4, seq_len, 64), dev)?;
+            let (k_ref, v_ref) = reference.append(&k, &v)?;
+            let (k, v) = cache.append(&k, &v)?;
+            assert_eq!(k.dims(), k_ref.dims());
+            assert_eq!(v.dims(), v_ref.dims());
+            for (a, b) in [(&k, &k_ref), (&v, &v_ref)] {
+                let diff = (a - b)?.abs()?.mean_all()?.to_scalar::<f32>()?;
+                assert!(diff < tol, "{dtype:?} {diff}");
+            }
+        }
+        assert_eq!(cache.current_seq_len(), 10);
+        let n_values = 2 * 4 * 10 * 64;
+        let expected_size = n_values / dtype.block_size() * dtype.type_size();
+        assert_eq!(cache.size_in_bytes(), 2 * expected_size);
+        cache.reset();
+        assert_eq!(cache.current_seq_len(), 0);
+        assert_eq!(cache.size_in_bytes(), 0);
+
+        // Appending empty tensors leaves the quantized rows empty.
+        let empty = Tensor::zeros((1, 2, 3, 0), DType::F32, dev)?;
+        let (k, v) = cache.append(&empty, &empty)?;
+        assert_eq!(k.dims(), [1, 2, 3, 0]);
+        assert_eq!(v.dims(), [1, 2, 3, 0]);
+        cache.reset();
+    }
+    Ok(())
+}
+
+#[test]
+fn quantized_kv_cache_dtype() -> Result<()> {
+    let dev = &Device::Cpu;
+    let mut cache = QuantizedKvCache::new(2, QuantizedKvDType::Q8_0);
+    let k = Tensor::randn(0f32, 1., (1, 2, 3, 32), dev)?.to_dtype(DType::F16)?;
+    let (k, v) = cache.append(&k, &k)?;
+    assert_eq!(k.dtype(), DType::F16);
+    assert_eq!(v.dims(), &[1, 2, 3, 32]);
+    // The appended tensors must match the cached shape except on the concatenation dim.
+    let k = Tensor::zeros((1, 3, 1, 32), DType::F16, dev)?;
+    assert!(cache.append(&k, &k).is_err());
+    // The head dimension has to be a multiple of the block size.
+    let mut cache = QuantizedKvCache::new(2, QuantizedKvDType::Q4_0);
+    let k = Tensor::zeros((1, 2, 3, 24), DType::F32, dev)?;
+    assert!(cache.append(&k, &k).is_err());
+    #[cfg(feature = "float8")]
+    {
+        let mut cache = QuantizedKvCache::new(2, QuantizedKvDType::F8E4M3);
+        assert!(cache.append(&k, &k).is_ok());
+    }
+    #[cfg(not(feature = "float8"))]
+    assert!("f8e4m3".parse::<QuantizedKvDType>().is_err());
+    assert_eq!("q8_0".parse::<QuantizedKvDType>()?, QuantizedKvDType::Q8_0);
+    assert!("q5_k".parse::<QuantizedKvDType>().is_err());
+    Ok(())
+}
+
+#[test]
+fn quantized_kv_cache_residual() -> Result<()> {
+    let dev = &Device::Cpu;
+    let max_diff = |a: &Tensor, b: &Tensor| -> Result<f32> {
+        (a - b)?.abs()?.flatten_all()?.max(0)?.to_scalar::<f32>()
+    };
+    let mut cache = QuantizedKvCache::new(2, QuantizedKvDType::Q4_0).with_residual_len(4);
+    let mut reference = ConcatKvCache::new(2);
+    let mut k_ref = Tensor::zeros((1, 2, 0, 32), DType::F32, dev)?;
+    for (seq_len, quantized_len) in [(3, 0), (2, 1), (1, 2), (6, 8)] {
+        let k = Tensor::randn(0f32, 1., (1, 2, seq_len, 32), dev)?;
+        (k_ref, _) = reference.append(&k, &k)?;
+        let (k, _) = cache.append(&k, &k)?;
+        assert_eq!(cache.k_cache().quantized_seq_len(), quantized_len);
+        assert_eq!(k.dims(), k_ref.dims());
+        // The most recent positions are exact, the older ones went through the quantization.
+        let n = k.dim(2)?;
+        let recent = n - quantized_len;
+        assert_eq!(
+            max_diff(
+                &k.narrow(2, quantized_len, recent)?,
+                &k_ref.narrow(2, quantized_len, recent)?
+            )?,
+            0.
+        );
+        if quantized_len > 0 {
+            let diff = max_diff(
+                &k.narrow(2, 0, quantized_len)?,
+                &k_ref.narrow(2, 0, quantized_len)?,
+            )?;
+            assert!(diff > 0. && diff < 0.5, "{diff}");
+        }
+    }
+    // 8 positions stored as q4_0 blocks and 4 f32 positions, for both the keys and values.
+    let quantized = 2 * 8 * 32 / 32 * QuantizedKvDType::Q4_0.type_size();
+    assert_eq!(cache.size_in_bytes(), 2 * (quantized + 2 * 4 * 32 * 4));
+
+    // Truncating within the full precision positions keeps the quantized ones.
+    cache.truncate(10)?;
+    assert_eq!(cache.current_seq_len(), 10);
+    assert_eq!(cache.k_cache().quantized_seq_len(), 8);
+    let k = cache.k()?.unwrap();
+    assert_eq!(max_diff(&k.narrow(2, 8, 2)?, &k_ref.narrow(2, 8, 2)?)?, 0.);
+    cache.truncate(5)?;
+    assert_eq!(cache.current_seq_len(), 5);
+    assert_eq!(cache.k()?.unwrap().dims(), &[1, 2, 5, 32]);
+    // The cache keeps working after truncation.
+    let k = Tensor::randn(0f32, 1., (1, 2, 2, 32), dev)?;
+    let (k_out, _) = cache.append(&k, &k)?;
+    assert_eq!(k_out.dims(), &[1, 2, 7, 32]);
+    assert_eq!(max_diff(&k_out.narrow(2, 5, 2)?, &k)?, 0.);
+    Ok(())
+}
+
+#[test]
+fn kv_cache_truncate() -> Result<()> {
+    let dev = &Device::Cpu;
+    let k = Tensor::randn(0f32, 1., (1, 2, 5, 32), dev)?;
+    let next = Tensor::randn(0f32, 1., (1, 2, 1, 32), dev)?;
+    let expected = Tensor::cat(&[&k.narrow(2, 0, 3)?, &next], 2)?;
+
+    let mut cache = ConcatKvCache::new(2);
+    cache.append(&k, &k)?;
+    assert!(cache.truncate(6).is_err());
+    cache.truncate(3)?;
+    let (k2, _) = cache.append(&next, &next)?;
+    assert_eq!(
+        k2.flatten_all()?.to_vec1::<f32>()?,
+        expected.flatten_all()?.to_vec1::<f32>()?
+    );
+    cache.truncate(0)?;
+    assert!(cache.is_empty());
+
+    // Truncating a quantized cache gives the same values as quantizing the truncated input.
+    let mut cache = QuantizedKvCache::new(2, QuantizedKvDType::Q4_0);
+    cache.append(&k, &k)?;
+    cache.truncate(3)?;
+    assert_eq!(cache.current_seq_len(), 3);
+    let (k2, _) = cache.append(&next, &next)?;
+    let mut reference = QuantizedKvCache::new(2, QuantizedKvDType::Q4_0);
+    let (k3, _) = reference.append(&expected, &expected)?;
+    assert_eq!(
+        k2.flatten_all()?.to_vec1::<f32>()?,
+        k3.flatten_all()?.to_vec1::<f32>()?
+    );
+    assert_eq!(cache.size_in_bytes(), reference.size_in_bytes());
+    Ok(())
+}
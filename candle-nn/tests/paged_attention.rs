@@ -0,0 +1,199 @@
+#[cfg(feature = "mkl")]
+extern crate intel_mkl_src;
+
+#[cfg(feature = "accelerate")]
+extern crate accelerate_src;
+
+use candle::{DType, Device, IndexOp, Result, Tensor};
+use candle_nn::paged_attention::{paged_attention, BlockManager, PagedKvCache};
+
+#[test]
+fn block_manager() -> Result<()> {
+    let mut bm = BlockManager::new(8, 4);
+    bm.add_sequence(0)?;
+    assert!(bm.add_sequence(0).is_err());
+    assert!(bm.append_slots(0, 6)?.is_empty());
+    assert_eq!(bm.block_table(0).unwrap().blocks(), &[0, 1]);
+    assert_eq!(bm.block_table(0).unwrap().slot(5, 4), Some(5));
+    assert_eq!(bm.num_free_blocks(), 6);
+
+    // The child shares both blocks, appending to the partially filled one triggers a copy.
+    bm.fork_sequence(0, 1)?;
+    assert_eq!(bm.allocator().ref_count(1), 2);
+    assert_eq!(bm.num_blocks_needed(1, 1), 1);
+    assert_eq!(bm.append_slots(1, 1)?, [(1, 2)]);
+    assert_eq!(bm.block_table(1).unwrap().blocks(), &[0, 2]);
+    assert_eq!(bm.allocator().ref_count(1), 1);
+    // The parent is now the only owner of its last block so no copy is needed.
+    assert!(bm.append_slots(0, 3)?.is_empty());
+    assert_eq!(bm.block_table(0).unwrap().blocks(), &[0, 1, 3]);
+    assert_eq!(bm.num_tokens(0), 9);
+
+    bm.truncate(0, 4)?;
+    assert_eq!(bm.block_table(0).unwrap().blocks(), &[0]);
+    assert!(bm.truncate(0, 5).is_err());
+    assert!(!bm.can_append(0, 100));
+    assert!(bm.append_slots(0, 100).is_err());
+    assert_eq!(bm.num_tokens(0), 4);
+
+    bm.remove_sequence(0)?;
+    assert_eq!(bm.allocator().ref_count(0), 1);
+    bm.remove_sequence(1)?;
+    assert_eq!(bm.num_free_blocks(), 8);
+    assert!(bm.remove_sequence(1).is_err());
+    Ok(())
+}
+
+// Causal attention over contiguous keys and values of shape (ctx, num_kv_heads, head_dim) for
+// queries corresponding to the last positions.
+fn reference_attention(q: &Tensor, k: &Tensor, v: &Tensor, scale: f64) -> Result<Tensor> {
+    let (qlen, num_heads, head_dim) = q.dims3()?;
+    let (ctx, num_kv_heads, _) = k.dims3()?;
+    let n_rep = num_heads / num_kv_heads;
+    let mut ys = vec![];
+    for h in 0..num_heads {
+        let q = q.i((.., h))?;
+        let k = k.i((.., h / n_rep))?;
+        let v = v.i((.., h / n_rep))?;
+        let att = (q.matmul(&k.t()?)? * scale)?.to_vec2::<f32>()?;
+        let mut rows = vec![];
+        for (i, row) in att.into_iter().enumerate() {
+            let row: Vec<f32> = row
+                .into_iter()
+                .enumerate()
+                .map(|(j, s)| {
+                    if j + qlen <= ctx + i {
+                        s
+                    } else {
+                        f32::NEG_INFINITY
+                    }
+                })
+                .collect();
+            rows.push(Tensor::new(row, &Device::Cpu)?)
+        }
+        let att = candle_nn::ops::softmax_last_dim(&Tensor::stack(&rows, 0)?)?;
+        ys.push(att.matmul(&v)?)
+    }
+    Tensor::stack(&ys, 1)?.reshape((qlen, num_heads, head_dim))
+}
+
+#[test]
+fn paged_attention_mixed_batch() -> Result<()> {
+    let dev = &Device::Cpu;
+    let (num_heads, num_kv_heads, head_dim, block_size) = (4, 2, 16, 4);
+    let scale = 1. / (head_dim as f64).sqrt();
+    let mut bm = BlockManager::new(16, block_size);
+    let mut cache = PagedKvCache::new(16, block_size, num_kv_heads, head_dim, DType::F32, dev)?;
+    let mut keys = [vec![], vec![]];
+    let mut values = [vec![], vec![]];
+    // A first step prefills both sequences, the next ones mix a decode step for the first
+    // sequence with chunks of the second one.
+    for lens in [[7, 3], [1, 5], [1, 1]] {
+        let mut seqs = vec![];
+        let (mut qs, mut ks, mut vs) = (vec![], vec![], vec![]);
+        for (seq_id, &len) in lens.iter().enumerate() {
+            if !bm.contains(seq_id) {
+                bm.add_sequence(seq_id)?;
+            }
+            bm.append_slots(seq_id, len)?;
+            seqs.push((seq_id, len));
+            qs.push(Tensor::randn(0f32, 1., (len, num_heads, head_dim), dev)?);
+            let k = Tensor::randn(0f32, 1., (len, num_kv_heads, head_dim), dev)?;
+            let v = Tensor::randn(0f32, 1., (len, num_kv_heads, head_dim), dev)?;
+            keys[seq_id].push(k.clone());
+            values[seq_id].push(v.clone());
+            ks.push(k);
+            vs.push(v);
+        }
+        let metadata = bm.metadata(&seqs, dev)?;
+        assert_eq!(metadata.num_tokens(), lens.iter().sum::<usize>());
+        let q = Tensor::cat(&qs, 0)?;
+        let ys = cache.forward(
+            &q,
+            &Tensor::cat(&ks, 0)?,
+            &Tensor::cat(&vs, 0)?,
+            &metadata,
+            scale as f32,
+        )?;
+        assert_eq!(ys.dims(), q.dims());
+        let mut offset = 0;
+        for (seq_id, &len) in lens.iter().enumerate() {
+            let k = Tensor::cat(&keys[seq_id], 0)?;
+            let v = Tensor::cat(&values[seq_id], 0)?;
+            let (gk, gv) = cache.gather(bm.block_table(seq_id).unwrap().blocks(), k.dim(0)?)?;
+            assert_eq!(gk.to_vec3::<f32>()?, k.to_vec3::<f32>()?);
+            assert_eq!(gv.to_vec3::<f32>()?, v.to_vec3::<f32>()?);
+            let expected = reference_attention(&qs[seq_id], &k, &v, scale)?;
+            let diff = (ys.narrow(0, offset, len)? - expected)?
+                .abs()?
+                .flatten_all()?
+                .max(0)?
+                .to_scalar::<f32>()?;
+            assert!(diff < 1e-5, "{diff}");
+            offset += len;
+        }
+    }
+
+    let metadata = bm.metadata(&[(0, 2), (1, 3)], dev)?;
+    assert_eq!(metadata.positions().to_vec1::<u32>()?, [7, 8, 6, 7, 8]);
+    let xs = Tensor::arange(0u32, 5, dev)?.reshape((5, 1))?;
+    assert_eq!(metadata.last_tokens(&xs)?.to_vec2::<u32>()?, [[1], [4]]);
+    // A sequence without query tokens has no last token.
+    assert!(bm.metadata(&[(0, 0), (1, 3)], dev).is_err());
+    Ok(())
+}
+
+#[test]
+fn paged_attention_copy_on_write() -> Result<()> {
+    let dev = &Device::Cpu;
+    let (num_heads, head_dim, block_size) = (2, 8, 4);
+    let mut bm = BlockManager::new(8, block_size);
+    let mut cache = PagedKvCache::new(8, block_size, num_heads, head_dim, DType::F32, dev)?;
+    let prompt_k = Tensor::randn(0f32, 1., (6, num_heads, head_dim), dev)?;
+    let prompt_v = Tensor::randn(0f32, 1., (6, num_heads, head_dim), dev)?;
+    bm.add_sequence(0)?;
+    bm.append_slots(0, 6)?;
+    let metadata = bm.metadata(&[(0, 6)], dev)?;
+    cache.write(&prompt_k, &prompt_v, metadata.slot_mapping())?;
+
+    // Both sequences share the prompt and then diverge.
+    bm.fork_sequence(0, 1)?;
+    let mut continuations = vec![];
+    for seq_id in [0, 1] {
+        let copies = bm.append_slots(seq_id, 1)?;
+        cache.copy_blocks(&copies)?;
+        let k = Tensor::randn(0f32, 1., (1, num_heads, head_dim), dev)?;
+        let metadata = bm.metadata(&[(seq_id, 1)], dev)?;
+        cache.write(&k, &k, metadata.slot_mapping())?;
+        continuations.push(k);
+    }
+    for (seq_id, k) in continuations.iter().enumerate() {
+        let expected = Tensor::cat(&[&prompt_k, k], 0)?;
+        let (gk, _) = cache.gather(bm.block_table(seq_id).unwrap().blocks(), 7)?;
+        assert_eq!(gk.to_vec3::<f32>()?, expected.to_vec3::<f32>()?);
+    }
+
+    // The standalone function matches the cache forward.
+    let q = Tensor::randn(0f32, 1., (1, num_heads, head_dim), dev)?;
+    let metadata = bm.metadata(&[(1, 1)], dev)?;
+    let ys = paged_attention(&q, cache.k(), cache.v(), &metadata, 0.5)?;
+    let (k, v) = cache.gather(bm.block_table(1).unwrap().blocks(), 7)?;
+    let expected = reference_attention(&q, &k, &v, 0.5)?;
+    let diff = (ys - expected)?
+        .abs()?
+        .flatten_all()?
+        .max(0)?
+        .to_scalar::<f32>()?;
+    assert!(diff < 1e-5, "{diff}");
+
+    // The output keeps the dtype of the queries when it differs from the cache one.
+    let ys = paged_attention(
+        &q.to_dtype(DType::F16)?,
+        cache.k(),
+        cache.v(),
+        &metadata,
+        0.5,
+    )?;
+    assert_eq!(ys.dtype(), DType::F16);
+    Ok(())
+}
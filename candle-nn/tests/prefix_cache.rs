@@ -0,0 +1,119 @@
+#[cfg(feature = "mkl")]
+extern crate intel_mkl_src;
+
+#[cfg(feature = "accelerate")]
+extern crate accelerate_src;
+
+use candle::{DType, Device, Result, Tensor};
+use candle_nn::kv_cache::{ConcatKvCache, KvCache, KvSnapshot, QuantizedKvCache, QuantizedKvDType};
+use candle_nn::prefix_cache::PrefixCache;
+
+// A snapshot where the cached keys are the token ids, this makes it easy to check truncations.
+fn state(tokens: &[u32]) -> Result<KvSnapshot> {
+    let k = Tensor::new(tokens, &Device::Cpu)?.reshape((1, 1, tokens.len(), 1))?;
+    KvSnapshot::new(k.clone(), k, 2)
+}
+
+fn keys(state: &KvSnapshot) -> Result<Vec<u32>> {
+    state.k().flatten_all()?.to_vec1::<u32>()
+}
+
+#[test]
+fn prefix_cache_lookup() -> Result<()> {
+    let mut cache = PrefixCache::new(usize::MAX);
+    assert!(cache.lookup(&[1, 2])?.is_none());
+    cache.insert(&[1, 2, 3, 4], state(&[1, 2, 3, 4])?)?;
+    assert!(cache.insert(&[1, 2], state(&[1, 2, 3])?).is_err());
+
+    let (len, s) = cache.lookup(&[1, 2, 3, 4, 5])?.unwrap();
+    assert_eq!((len, keys(&s)?), (4, vec![1, 2, 3, 4]));
+    // The sequences diverge in the middle of an edge, the state gets truncated.
+    let (len, s) = cache.lookup(&[1, 2, 9])?.unwrap();
+    assert_eq!((len, keys(&s)?), (2, vec![1, 2]));
+    let (len, _) = cache.lookup(&[1, 2, 3])?.unwrap();
+    assert_eq!(len, 3);
+    assert!(cache.lookup(&[7, 1, 2])?.is_none());
+
+    // Splits the [1, 2, 3, 4] edge.
+    cache.insert(&[1, 2, 5], state(&[1, 2, 5])?)?;
+    cache.insert(&[1, 2], state(&[1, 2])?)?;
+    assert_eq!(cache.len(), 3);
+    let (len, s) = cache.lookup(&[1, 2, 5, 6])?.unwrap();
+    assert_eq!((len, keys(&s)?), (3, vec![1, 2, 5]));
+    let (len, s) = cache.lookup(&[1, 2, 3, 7])?.unwrap();
+    assert_eq!((len, keys(&s)?), (3, vec![1, 2, 3]));
+    let (len, s) = cache.lookup(&[1, 2, 8])?.unwrap();
+    assert_eq!((len, keys(&s)?), (2, vec![1, 2]));
+
+    // Replacing a state does not change the count.
+    cache.insert(&[1, 2, 5], state(&[1, 2, 5])?)?;
+    assert_eq!(cache.len(), 3);
+    assert_eq!(cache.size_in_bytes(), 2 * 4 * (4 + 3 + 2));
+    cache.clear();
+    assert!(cache.is_empty());
+    assert!(cache.lookup(&[1, 2])?.is_none());
+    Ok(())
+}
+
+#[test]
+fn prefix_cache_eviction() -> Result<()> {
+    // Each state with 4 tokens uses 32 bytes, the budget allows for two of them.
+    let mut cache = PrefixCache::new(64);
+    cache.insert(&[1, 2, 3, 4], state(&[1, 2, 3, 4])?)?;
+    cache.insert(&[1, 2, 5, 6], state(&[1, 2, 5, 6])?)?;
+    // Refresh the first state so that the second one is the least recently used.
+    cache.lookup(&[1, 2, 3, 4])?;
+    cache.insert(&[7, 8, 9, 10], state(&[7, 8, 9, 10])?)?;
+    assert_eq!(cache.len(), 2);
+    assert_eq!(cache.size_in_bytes(), 64);
+    let (len, _) = cache.lookup(&[1, 2, 5, 6])?.unwrap();
+    assert_eq!(len, 2);
+    assert_eq!(cache.lookup(&[7, 8, 9, 10])?.unwrap().0, 4);
+
+    // A state larger than the budget is not kept.
+    cache.insert(&[3; 9], state(&[3; 9])?)?;
+    assert!(cache.lookup(&[3; 9])?.is_none());
+    assert!(cache.size_in_bytes() <= 64);
+    while cache.evict_lru() {}
+    assert!(cache.is_empty());
+    assert_eq!(cache.size_in_bytes(), 0);
+    assert!(cache.lookup(&[1, 2, 3, 4])?.is_none());
+    Ok(())
+}
+
+#[test]
+fn kv_cache_snapshots() -> Result<()> {
+    let dev = &Device::Cpu;
+    let k = Tensor::randn(0f32, 1., (1, 2, 5, 32), dev)?;
+    let v = Tensor::randn(0f32, 1., (1, 2, 5, 32), dev)?;
+    let next = Tensor::randn(0f32, 1., (1, 2, 1, 32), dev)?;
+
+    let mut cache = KvCache::new(2, 8);
+    assert!(cache.snapshot()?.is_none());
+    cache.append(&k, &v)?;
+    let snapshot = cache.snapshot()?.unwrap();
+    // Resuming from a truncated snapshot must not alter the snapshot itself.
+    cache.restore(&snapshot.truncate(3)?)?;
+    assert_eq!(cache.current_seq_len(), 3);
+    cache.append(&next, &next)?;
+    assert_eq!(snapshot.seq_len(), 5);
+    assert_eq!(
+        snapshot.k().flatten_all()?.to_vec1::<f32>()?,
+        k.flatten_all()?.to_vec1::<f32>()?
+    );
+
+    let mut concat = ConcatKvCache::new(2);
+    concat.restore(&snapshot)?;
+    let (k2, _) = concat.append(&next, &next)?;
+    assert_eq!(k2.dims(), &[1, 2, 6, 32]);
+    assert_eq!(concat.snapshot()?.unwrap().seq_len(), 6);
+
+    let mut quantized = QuantizedKvCache::new(2, QuantizedKvDType::Q8_0);
+    quantized.restore(&snapshot)?;
+    assert_eq!(quantized.current_seq_len(), 5);
+    let restored = quantized.snapshot()?.unwrap();
+    assert_eq!(restored.k().dtype(), DType::F32);
+    let diff = (restored.v() - &v)?.abs()?.mean_all()?.to_scalar::<f32>()?;
+    assert!(diff < 0.01, "{diff}");
+    Ok(())
+}
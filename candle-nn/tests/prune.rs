@@ -0,0 +1,167 @@
+#[cfg(feature = "mkl")]
+extern crate intel_mkl_src;
+
+#[cfg(feature = "accelerate")]
+extern crate accelerate_src;
+
+use candle::{DType, Device, Result, Tensor};
+use candle_nn::prune::{self, PruneMethod};
+use candle_nn::{Optimizer, VarBuilder, VarMap};
+use std::collections::HashMap;
+
+#[test]
+fn magnitude_mask() -> Result<()> {
+    let w = Tensor::new(&[[1f32, -5., 3.], [-2., 6., 0.5]], &Device::Cpu)?;
+    let mask = PruneMethod::Magnitude { sparsity: 0.5 }.mask(&w)?;
+    assert_eq!(mask.to_vec2::<f32>()?, [[0., 1., 1.], [0., 1., 0.]]);
+    Ok(())
+}
+
+#[test]
+fn channel_and_head_masks() -> Result<()> {
+    let w = Tensor::new(&[[1f32, 1.], [3., 3.], [0.5, 0.], [2., -2.]], &Device::Cpu)?;
+    let mask = PruneMethod::Channel { sparsity: 0.5 }.mask(&w)?;
+    assert_eq!(
+        mask.to_vec2::<f32>()?,
+        [[0., 0.], [1., 1.], [0., 0.], [1., 1.]]
+    );
+    let mask = PruneMethod::Head {
+        num_heads: 2,
+        sparsity: 0.5,
+    }
+    .mask(&w)?;
+    assert_eq!(
+        mask.to_vec2::<f32>()?,
+        [[1., 1.], [1., 1.], [0., 0.], [0., 0.]]
+    );
+    assert!(PruneMethod::Head {
+        num_heads: 3,
+        sparsity: 0.5
+    }
+    .mask(&w)
+    .is_err());
+    Ok(())
+}
+
+#[test]
+fn empty_masks() -> Result<()> {
+    for shape in [(0, 4), (4, 0)] {
+        let w = Tensor::zeros(shape, DType::F32, &Device::Cpu)?;
+        for method in [
+            PruneMethod::Magnitude { sparsity: 0.5 },
+            PruneMethod::Channel { sparsity: 0.5 },
+            PruneMethod::Head {
+                num_heads: 2,
+                sparsity: 0.5,
+            },
+        ] {
+            let mask = method.mask(&w)?;
+            assert_eq!(mask.dims2()?, shape);
+        }
+    }
+    Ok(())
+}
+
+#[test]
+fn nm_mask() -> Result<()> {
+    let w = Tensor::new(&[[1f32, -4., 3., 2., 0., 0., 0.1, -0.2]], &Device::Cpu)?;
+    let mask = PruneMethod::NM { n: 2, m: 4 }.mask(&w)?;
+    assert_eq!(mask.to_vec2::<f32>()?, [[0., 1., 1., 0., 0., 0., 1., 1.]]);
+    // Conv weights are grouped over the flattened input dims.
+    let w = Tensor::arange(0f32, 16., &Device::Cpu)?.reshape((2, 2, 2, 2))?;
+    let mask = PruneMethod::NM { n: 2, m: 4 }.mask(&w)?;
+    assert_eq!(mask.sum_all()?.to_scalar::<f32>()?, 8.);
+    assert!(PruneMethod::NM { n: 2, m: 3 }.mask(&w).is_err());
+    // Positions within a group are stored as u8 in the compact format.
+    let w = Tensor::zeros((1, 512), DType::F32, &Device::Cpu)?;
+    assert!(PruneMethod::NM { n: 2, m: 512 }.mask(&w).is_err());
+    assert!(PruneMethod::NM { n: 2, m: 256 }.mask(&w).is_ok());
+    Ok(())
+}
+
+#[test]
+fn prune_and_finetune() -> Result<()> {
+    let dev = &Device::Cpu;
+    let varmap = VarMap::new();
+    let vb = VarBuilder::from_varmap(&varmap, DType::F32, dev);
+    let layer = candle_nn::linear(8, 4, vb.pp("fc"))?;
+    let masks = prune::prune_varmap(&varmap, PruneMethod::NM { n: 2, m: 4 }, prune::is_weight)?;
+    assert_eq!(masks.len(), 1);
+    assert!(masks.get("fc.weight").is_some());
+
+    let mut opt = candle_nn::SGD::new(varmap.all_vars(), 0.1)?;
+    let xs = Tensor::ones((3, 8), DType::F32, dev)?;
+    for _step in 0..3 {
+        let loss = candle::Module::forward(&layer, &xs)?.sqr()?.sum_all()?;
+        opt.backward_step(&loss)?;
+        masks.apply(&varmap)?;
+    }
+    let report = prune::varmap_sparsity_report(&varmap)?;
+    assert_eq!(report.len(), 2);
+    assert_eq!(report[0].name, "fc.bias");
+    assert_eq!(report[1].name, "fc.weight");
+    assert_eq!(report[1].zeros, 16);
+    assert_eq!(report[1].sparsity(), 0.5);
+    Ok(())
+}
+
+#[test]
+fn compact_roundtrip() -> Result<()> {
+    let dev = &Device::Cpu;
+    let mut tensors = HashMap::new();
+    tensors.insert(
+        "a.weight".to_string(),
+        Tensor::randn(0f32, 1., (6, 8), dev)?,
+    );
+    tensors.insert(
+        "b.weight".to_string(),
+        Tensor::randn(0f32, 1., (4, 3, 2, 2), dev)?,
+    );
+    tensors.insert("b.bias".to_string(), Tensor::new(&[1f32, 2., 3., 4.], dev)?);
+    for method in [
+        PruneMethod::Magnitude { sparsity: 0.5 },
+        PruneMethod::Channel { sparsity: 0.5 },
+        PruneMethod::NM { n: 2, m: 4 },
+    ] {
+        let mut tensors = tensors.clone();
+        let masks = prune::prune_tensors(&mut tensors, method, prune::is_weight)?;
+        assert_eq!(masks.len(), 2);
+        let compacted = prune::compact(&tensors, &masks)?;
+        let size_in_bytes = |ts: &HashMap<String, Tensor>| {
+            ts.values()
+                .map(|t| t.elem_count() * t.dtype().size_in_bytes())
+                .sum::<usize>()
+        };
+        let compact_size = size_in_bytes(&compacted);
+        let dense_size = size_in_bytes(&tensors);
+        assert!(compact_size < dense_size, "{method:?}");
+        let restored = prune::decompact(&compacted)?;
+        assert_eq!(restored.len(), tensors.len());
+        for (name, t) in tensors.iter() {
+            let diff = (t - &restored[name])?
+                .abs()?
+                .sum_all()?
+                .to_scalar::<f32>()?;
+            assert_eq!(diff, 0., "{method:?} {name}");
+        }
+    }
+    Ok(())
+}
+
+#[test]
+fn compact_falls_back_to_dense() -> Result<()> {
+    let dev = &Device::Cpu;
+    let w = Tensor::randn(0f32, 1., (4, 16), dev)?;
+    let mut tensors = HashMap::from([("a.weight".to_string(), w.clone())]);
+    let masks = prune::prune_tensors(
+        &mut tensors,
+        PruneMethod::Magnitude { sparsity: 0.0 },
+        prune::is_weight,
+    )?;
+    // Storing all the values and a bitmask would be larger than the dense tensor.
+    let compacted = prune::compact(&tensors, &masks)?;
+    assert_eq!(compacted.len(), 1);
+    let restored = prune::decompact(&compacted)?;
+    assert_eq!(restored["a.weight"].to_vec2::<f32>()?, w.to_vec2::<f32>()?);
+    Ok(())
+}
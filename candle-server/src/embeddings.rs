@@ -0,0 +1,93 @@
+//! Sentence embeddings computed with a BERT model, the token embeddings are pooled using the
+//! sentence-transformers pooling configuration (mean pooling by default) and normalized.
+use crate::server::internal;
+use anyhow::{bail, Context, Error as E, Result};
+use candle::Device;
+use candle_nn::VarBuilder;
+use candle_transformers::models::bert::{BertModel, Config, DTYPE};
+use candle_transformers::pipelines::embeddings::{
+    BatchConfig, EmbeddingsPipeline, EncodedInput, Pooling,
+};
+use std::path::Path;
+use std::sync::Mutex;
+use tokenizers::{Tokenizer, TruncationParams};
+
+pub struct EmbeddingModel {
+    pipeline: Mutex<EmbeddingsPipeline<BertModel>>,
+    tokenizer: Tokenizer,
+    hidden_size: usize,
+}
+
+impl EmbeddingModel {
+    /// Loads a model directory containing `config.json`, `tokenizer.json` and
+    /// `model.safetensors`, the pooling is read from `1_Pooling/config.json` when present.
+    pub fn load<P: AsRef<Path>>(dir: P, device: &Device) -> Result<Self> {
+        let dir = dir.as_ref();
+        let config = std::fs::read_to_string(dir.join("config.json"))
+            .with_context(|| format!("no config.json in {dir:?}"))?;
+        let config: Config = serde_json::from_str(&config)?;
+        let pooling = match std::fs::read_to_string(dir.join("1_Pooling").join("config.json")) {
+            Ok(pooling) => Pooling::from_sentence_transformers_config(&pooling)?,
+            Err(_) => Pooling::Mean,
+        };
+        let mut tokenizer = Tokenizer::from_file(dir.join("tokenizer.json")).map_err(E::msg)?;
+        tokenizer.with_padding(None);
+        tokenizer
+            .with_truncation(Some(TruncationParams {
+                max_length: config.max_position_embeddings,
+                ..Default::default()
+            }))
+            .map_err(E::msg)?;
+        let weights = dir.join("model.safetensors");
+        let vb = unsafe { VarBuilder::from_mmaped_safetensors(&[weights], DTYPE, device)? };
+        let model = BertModel::load(vb, &config)?;
+        let pipeline = EmbeddingsPipeline::new(model, device)
+            .with_pooling(pooling)
+            .with_batch_config(BatchConfig {
+                pad_token_id: config.pad_token_id as u32,
+                ..Default::default()
+            });
+        Ok(Self {
+            pipeline: Mutex::new(pipeline),
+            tokenizer,
+            hidden_size: config.hidden_size,
+        })
+    }
+
+    /// Returns the normalized embeddings for a batch of texts together with the number of tokens
+    /// that have been processed. The embeddings are truncated to `dimensions` before being
+    /// normalized when set.
+    pub fn embed(
+        &self,
+        texts: &[String],
+        dimensions: Option<usize>,
+    ) -> Result<(Vec<Vec<f32>>, usize)> {
+        if let Some(dimensions) = dimensions {
+            if dimensions == 0 || dimensions > self.hidden_size {
+                bail!("dimensions must be between 1 and {}", self.hidden_size)
+            }
+        }
+        if texts.is_empty() {
+            return Ok((vec![], 0));
+        }
+        let encodings = self
+            .tokenizer
+            .encode_batch(texts.to_vec(), true)
+            .map_err(E::msg)?;
+        let num_tokens = encodings.iter().map(|e| e.get_ids().len()).sum();
+        let inputs: Vec<EncodedInput> = encodings
+            .iter()
+            .map(|e| EncodedInput::with_type_ids(e.get_ids().to_vec(), e.get_type_ids().to_vec()))
+            .collect();
+        let mut pipeline = self
+            .pipeline
+            .lock()
+            .map_err(|_| internal(E::msg("the embedding model is poisoned")))?;
+        pipeline.set_dimensions(dimensions);
+        let embeddings = pipeline
+            .embed(&inputs)
+            .and_then(|e| e.to_vec2::<f32>())
+            .map_err(internal)?;
+        Ok((embeddings, num_tokens))
+    }
+}
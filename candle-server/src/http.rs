@@ -0,0 +1,199 @@
+//! A minimal HTTP/1.1 implementation, each connection serves a single request.
+use anyhow::{bail, Result};
+use serde::Serialize;
+use std::io::{BufRead, Write};
+use std::time::Duration;
+
+/// The maximum size of a request body.
+const MAX_BODY_SIZE: usize = 32 * 1024 * 1024;
+const MAX_HEADERS: usize = 128;
+/// The time to wait for the client to send more bytes of a request before closing the
+/// connection.
+pub const READ_TIMEOUT: Duration = Duration::from_secs(30);
+
+#[derive(Debug, Clone)]
+pub struct Request {
+    pub method: String,
+    pub path: String,
+    pub headers: Vec<(String, String)>,
+    pub body: Vec<u8>,
+}
+
+impl Request {
+    /// Returns the value of a header, the name is case insensitive.
+    pub fn header(&self, name: &str) -> Option<&str> {
+        self.headers
+            .iter()
+            .find(|(n, _)| n.eq_ignore_ascii_case(name))
+            .map(|(_, v)| v.as_str())
+    }
+}
+
+fn read_line<R: BufRead>(reader: &mut R) -> Result<Option<String>> {
+    let mut line = String::new();
+    if reader.read_line(&mut line)? == 0 {
+        return Ok(None);
+    }
+    let len = line.trim_end_matches(['\r', '\n']).len();
+    line.truncate(len);
+    Ok(Some(line))
+}
+
+/// Returns true if reading a request failed because the client stopped sending data.
+pub fn is_timeout(err: &anyhow::Error) -> bool {
+    err.downcast_ref::<std::io::Error>().is_some_and(|err| {
+        matches!(
+            err.kind(),
+            std::io::ErrorKind::WouldBlock | std::io::ErrorKind::TimedOut
+        )
+    })
+}
+
+/// Reads a request, returns `None` if the connection was closed before sending anything.
+pub fn read_request<R: BufRead>(reader: &mut R) -> Result<Option<Request>> {
+    let line = match read_line(reader)? {
+        None => return Ok(None),
+        Some(line) => line,
+    };
+    let mut parts = line.split_whitespace();
+    let (method, target) = match (parts.next(), parts.next(), parts.next()) {
+        (Some(method), Some(target), Some(version)) if version.starts_with("HTTP/1.") => {
+            (method.to_string(), target)
+        }
+        _ => bail!("malformed request line {line:?}"),
+    };
+    // The query string is not used by any of the endpoints.
+    let path = target.split('?').next().unwrap_or(target).to_string();
+    let mut headers = vec![];
+    loop {
+        let line = match read_line(reader)? {
+            None => bail!("connection closed while reading the headers"),
+            Some(line) => line,
+        };
+        if line.is_empty() {
+            break;
+        }
+        if headers.len() >= MAX_HEADERS {
+            bail!("too many headers")
+        }
+        match line.split_once(':') {
+            Some((name, value)) => {
+                headers.push((name.trim().to_string(), value.trim().to_string()))
+            }
+            None => bail!("malformed header {line:?}"),
+        }
+    }
+    let mut request = Request {
+        method,
+        path,
+        headers,
+        body: vec![],
+    };
+    if request.header("transfer-encoding").is_some() {
+        bail!("chunked request bodies are not supported")
+    }
+    if let Some(len) = request.header("content-length") {
+        let len: usize = len.parse()?;
+        if len > MAX_BODY_SIZE {
+            bail!("request body of {len} bytes is too large")
+        }
+        let mut body = vec![0u8; len];
+        reader.read_exact(&mut body)?;
+        request.body = body;
+    }
+    Ok(Some(request))
+}
+
+fn reason(status: u16) -> &'static str {
+    match status {
+        200 => "OK",
+        400 => "Bad Request",
+        404 => "Not Found",
+        405 => "Method Not Allowed",
+        408 => "Request Timeout",
+        413 => "Payload Too Large",
+        500 => "Internal Server Error",
+        503 => "Service Unavailable",
+        _ => "Unknown",
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Response {
+    pub status: u16,
+    pub content_type: &'static str,
+    pub body: Vec<u8>,
+}
+
+impl Response {
+    pub fn json<T: Serialize>(status: u16, value: &T) -> Self {
+        let body = serde_json::to_vec(value).expect("json serialization cannot fail");
+        Self {
+            status,
+            content_type: "application/json",
+            body,
+        }
+    }
+
+    pub fn text(status: u16, content_type: &'static str, body: impl Into<String>) -> Self {
+        Self {
+            status,
+            content_type,
+            body: body.into().into_bytes(),
+        }
+    }
+
+    /// An error in the format used by the OpenAI API.
+    pub fn error(status: u16, message: impl std::fmt::Display) -> Self {
+        let kind = if status >= 500 {
+            "server_error"
+        } else {
+            "invalid_request_error"
+        };
+        let error = serde_json::json!({
+            "error": { "message": message.to_string(), "type": kind, "param": null, "code": null }
+        });
+        Self::json(status, &error)
+    }
+
+    pub fn write<W: Write>(&self, writer: &mut W) -> std::io::Result<()> {
+        write!(
+            writer,
+            "HTTP/1.1 {} {}\r\nContent-Type: {}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n",
+            self.status,
+            reason(self.status),
+            self.content_type,
+            self.body.len()
+        )?;
+        writer.write_all(&self.body)?;
+        writer.flush()
+    }
+}
+
+/// A stream of server-sent events, the connection is closed at the end of the stream.
+pub struct EventStream<W: Write> {
+    writer: W,
+}
+
+impl<W: Write> EventStream<W> {
+    pub fn new(mut writer: W) -> std::io::Result<Self> {
+        writer.write_all(
+            b"HTTP/1.1 200 OK\r\nContent-Type: text/event-stream\r\nCache-Control: no-cache\r\nConnection: close\r\n\r\n",
+        )?;
+        writer.flush()?;
+        Ok(Self { writer })
+    }
+
+    /// Sends a json event, this fails if the client has disconnected.
+    pub fn send<T: Serialize>(&mut self, value: &T) -> std::io::Result<()> {
+        let data = serde_json::to_string(value).expect("json serialization cannot fail");
+        write!(self.writer, "data: {data}\n\n")?;
+        self.writer.flush()
+    }
+
+    /// Sends the final `[DONE]` event.
+    pub fn done(mut self) -> std::io::Result<()> {
+        self.writer.write_all(b"data: [DONE]\n\n")?;
+        self.writer.flush()
+    }
+}
@@ -0,0 +1,14 @@
+//! An OpenAI compatible inference server.
+//!
+//! The server loads a causal language model in the gguf format, or a HuggingFace directory with
+//! safetensors weights, and serves the `/v1/completions`, `/v1/chat/completions` and
+//! `/v1/embeddings` endpoints. The requests from all the connections are batched together by a
+//! single [`text_generation::Engine`](candle_transformers::pipelines::text_generation::Engine)
+//! running on a worker thread, and the Prometheus metrics are available on `/metrics`.
+pub mod embeddings;
+pub mod http;
+pub mod metrics;
+pub mod model;
+pub mod openai;
+pub mod server;
+pub mod worker;
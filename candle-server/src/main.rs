@@ -0,0 +1,178 @@
+#[cfg(feature = "mkl")]
+extern crate intel_mkl_src;
+
+#[cfg(feature = "accelerate")]
+extern crate accelerate_src;
+
+use anyhow::{Error as E, Result};
+use candle_server::embeddings::EmbeddingModel;
+use candle_server::model::{tokenizer_config_template, tokenizer_file, LoadedModel};
+use candle_server::server::Server;
+use candle_transformers::pipelines::chat_template::ChatTemplate;
+use candle_transformers::pipelines::text_generation::EngineConfig;
+use candle_transformers::pipelines::tool_calls::{ToolCallFormat, THINK_START};
+use clap::Parser;
+use std::path::PathBuf;
+use std::sync::Arc;
+
+#[derive(Parser, Debug)]
+#[command(author, version, about, long_about = None)]
+struct Args {
+    /// The model to serve, either a gguf file or a directory with safetensors weights.
+    model: PathBuf,
+
+    /// The tokenizer.json file, by default it is looked up next to the model.
+    #[arg(long)]
+    tokenizer: Option<PathBuf>,
+
+    /// The chat template, either a tokenizer_config.json file or a jinja template. By default
+    /// the template from the gguf metadata or from the tokenizer_config.json next to the
+    /// tokenizer is used.
+    #[arg(long)]
+    chat_template: Option<PathBuf>,
+
+    /// A directory with a BERT model used for the embeddings endpoint.
+    #[arg(long)]
+    embedding_model: Option<PathBuf>,
+
+    /// The name of the model returned by the API, defaults to the model file name.
+    #[arg(long)]
+    served_model_name: Option<String>,
+
+    #[arg(long, default_value = "127.0.0.1")]
+    host: String,
+
+    #[arg(long, default_value_t = 8080)]
+    port: u16,
+
+    /// Run on CPU rather than on GPU.
+    #[arg(long)]
+    cpu: bool,
+
+    /// The number of blocks in the paged kv-cache.
+    #[arg(long, default_value_t = 512)]
+    num_blocks: usize,
+
+    /// The number of tokens per kv-cache block.
+    #[arg(long, default_value_t = 16)]
+    block_size: usize,
+
+    /// The maximum number of requests processed together.
+    #[arg(long, default_value_t = 32)]
+    max_num_seqs: usize,
+
+    /// The maximum number of tokens processed in a single step.
+    #[arg(long, default_value_t = 512)]
+    max_num_batched_tokens: usize,
+
+    /// The maximum number of tokens of a request, prompt included. Defaults to the context
+    /// length of the model.
+    #[arg(long)]
+    max_seq_len: Option<usize>,
+
+    /// The maximum number of generated tokens for requests that do not specify it.
+    #[arg(long, default_value_t = 256)]
+    max_tokens: usize,
+}
+
+fn device(cpu: bool) -> Result<candle::Device> {
+    if cpu {
+        Ok(candle::Device::Cpu)
+    } else if candle::utils::cuda_is_available() {
+        Ok(candle::Device::new_cuda(0)?)
+    } else if candle::utils::metal_is_available() {
+        Ok(candle::Device::new_metal(0)?)
+    } else {
+        Ok(candle::Device::Cpu)
+    }
+}
+
+fn load_chat_template(path: &std::path::Path) -> Result<ChatTemplate> {
+    let template = if path.extension().is_some_and(|e| e == "json") {
+        ChatTemplate::from_tokenizer_config(path)?
+    } else {
+        ChatTemplate::new(std::fs::read_to_string(path)?)?
+    };
+    Ok(template)
+}
+
+fn main() -> Result<()> {
+    let args = Args::parse();
+    let device = device(args.cpu)?;
+    let tokenizer = match args.tokenizer {
+        Some(tokenizer) => tokenizer,
+        None => tokenizer_file(&args.model)?,
+    };
+    let start = std::time::Instant::now();
+    let model = LoadedModel::load(&args.model, &device)?;
+    println!(
+        "loaded {} model {:?} in {:.2}s",
+        model.architecture,
+        args.model,
+        start.elapsed().as_secs_f32()
+    );
+    let chat_template = match args.chat_template {
+        Some(path) => Some(load_chat_template(&path)?),
+        None => match model.chat_template {
+            Some(template) => Some(template),
+            None => tokenizer_config_template(&tokenizer.with_file_name("tokenizer_config.json"))?,
+        },
+    };
+    if chat_template.is_none() {
+        println!("no chat template found, /v1/chat/completions is disabled");
+    }
+    let tokenizer = tokenizers::Tokenizer::from_file(tokenizer).map_err(E::msg)?;
+    let mut eos_tokens: Vec<u32> = model.eos_token_id.into_iter().collect();
+    for eos in [
+        "<|im_end|>",
+        "<|eot_id|>",
+        "<|end_of_text|>",
+        "</s>",
+        "<end_of_turn>",
+    ] {
+        if let Some(id) = tokenizer.token_to_id(eos) {
+            if !eos_tokens.contains(&id) {
+                eos_tokens.push(id)
+            }
+        }
+    }
+    let model_name = args.served_model_name.unwrap_or_else(|| {
+        args.model
+            .file_stem()
+            .map_or("model".to_string(), |s| s.to_string_lossy().into_owned())
+    });
+    let config = EngineConfig {
+        num_blocks: args.num_blocks,
+        block_size: args.block_size,
+        max_num_seqs: args.max_num_seqs,
+        max_num_batched_tokens: args.max_num_batched_tokens,
+        max_seq_len: args.max_seq_len,
+    };
+    let tool_call_format = ToolCallFormat::from_architecture(&model.architecture);
+    // Reasoning models have a dedicated token to start the reasoning blocks.
+    let reasoning = tokenizer.token_to_id(THINK_START).is_some();
+    let mut server = Server::new(
+        model.model,
+        config,
+        &device,
+        tokenizer,
+        eos_tokens,
+        model_name,
+    )?
+    .with_tool_call_format(tool_call_format)
+    .with_reasoning(reasoning)
+    .with_max_tokens(args.max_tokens);
+    if let Some(chat_template) = chat_template {
+        server = server.with_chat_template(chat_template)
+    }
+    if let Some(dir) = args.embedding_model {
+        let name = dir.file_name().map_or("embedding".to_string(), |s| {
+            s.to_string_lossy().into_owned()
+        });
+        let embedding_model = EmbeddingModel::load(&dir, &device)?;
+        server = server.with_embedding_model(name, embedding_model)
+    }
+    let listener = std::net::TcpListener::bind((args.host.as_str(), args.port))?;
+    println!("listening on http://{}", listener.local_addr()?);
+    Arc::new(server).serve(listener)
+}
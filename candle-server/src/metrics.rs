@@ -0,0 +1,135 @@
+//! Server metrics in the Prometheus text format.
+use std::collections::BTreeMap;
+use std::fmt::Write;
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+use std::sync::Mutex;
+use std::time::Duration;
+
+#[derive(Debug, Default)]
+struct Summary {
+    sum: f64,
+    count: u64,
+}
+
+#[derive(Debug, Default)]
+pub struct Metrics {
+    // The number of requests per endpoint and status code.
+    requests: Mutex<BTreeMap<(String, u16), u64>>,
+    prompt_tokens: AtomicU64,
+    generation_tokens: AtomicU64,
+    num_running: AtomicUsize,
+    num_waiting: AtomicUsize,
+    time_to_first_token: Mutex<Summary>,
+    request_duration: Mutex<Summary>,
+}
+
+impl Metrics {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn record_request(&self, endpoint: &str, status: u16, duration: Duration) {
+        let mut requests = self.requests.lock().unwrap();
+        *requests.entry((endpoint.to_string(), status)).or_default() += 1;
+        let mut summary = self.request_duration.lock().unwrap();
+        summary.sum += duration.as_secs_f64();
+        summary.count += 1;
+    }
+
+    pub fn record_prompt_tokens(&self, n: usize) {
+        self.prompt_tokens.fetch_add(n as u64, Ordering::Relaxed);
+    }
+
+    pub fn record_generation_tokens(&self, n: usize) {
+        self.generation_tokens
+            .fetch_add(n as u64, Ordering::Relaxed);
+    }
+
+    pub fn record_time_to_first_token(&self, duration: Duration) {
+        let mut summary = self.time_to_first_token.lock().unwrap();
+        summary.sum += duration.as_secs_f64();
+        summary.count += 1;
+    }
+
+    /// Sets the number of requests being processed by the engine and waiting to be scheduled.
+    pub fn set_queue_sizes(&self, num_running: usize, num_waiting: usize) {
+        self.num_running.store(num_running, Ordering::Relaxed);
+        self.num_waiting.store(num_waiting, Ordering::Relaxed);
+    }
+
+    pub fn generation_tokens(&self) -> u64 {
+        self.generation_tokens.load(Ordering::Relaxed)
+    }
+
+    /// Renders the metrics in the Prometheus text exposition format.
+    pub fn render(&self) -> String {
+        let mut out = String::new();
+        let mut metric = |name: &str, kind: &str, help: &str, values: &[(String, String)]| {
+            let _ = writeln!(out, "# HELP {name} {help}");
+            let _ = writeln!(out, "# TYPE {name} {kind}");
+            for (labels, value) in values {
+                let _ = writeln!(out, "{name}{labels} {value}");
+            }
+        };
+        let requests: Vec<_> = self
+            .requests
+            .lock()
+            .unwrap()
+            .iter()
+            .map(|((endpoint, status), count)| {
+                let labels = format!("{{endpoint=\"{endpoint}\",status=\"{status}\"}}");
+                (labels, count.to_string())
+            })
+            .collect();
+        metric(
+            "candle_requests_total",
+            "counter",
+            "The number of HTTP requests.",
+            &requests,
+        );
+        let single = |v: String| [(String::new(), v)];
+        metric(
+            "candle_prompt_tokens_total",
+            "counter",
+            "The number of prompt tokens processed.",
+            &single(self.prompt_tokens.load(Ordering::Relaxed).to_string()),
+        );
+        metric(
+            "candle_generation_tokens_total",
+            "counter",
+            "The number of tokens generated.",
+            &single(self.generation_tokens().to_string()),
+        );
+        metric(
+            "candle_num_requests_running",
+            "gauge",
+            "The number of requests processed by the engine.",
+            &single(self.num_running.load(Ordering::Relaxed).to_string()),
+        );
+        metric(
+            "candle_num_requests_waiting",
+            "gauge",
+            "The number of requests waiting to be scheduled.",
+            &single(self.num_waiting.load(Ordering::Relaxed).to_string()),
+        );
+        for (name, help, summary) in [
+            (
+                "candle_time_to_first_token_seconds",
+                "The time until the first generated token.",
+                &self.time_to_first_token,
+            ),
+            (
+                "candle_request_duration_seconds",
+                "The time spent processing requests.",
+                &self.request_duration,
+            ),
+        ] {
+            let summary = summary.lock().unwrap();
+            let _ = writeln!(out, "# HELP {name} {help}");
+            let _ = writeln!(out, "# TYPE {name} summary");
+            let _ = writeln!(out, "{name}_sum {}", summary.sum);
+            let _ = writeln!(out, "{name}_count {}", summary.count);
+        }
+        out
+    }
+}
@@ -0,0 +1,189 @@
+//! Loading of the causal language models served by the engine.
+use anyhow::{bail, Context, Result};
+use candle::quantized::gguf_file;
+use candle::{Device, Tensor};
+use candle_nn::paged_attention::{PagedAttentionMetadata, PagedKvCache};
+use candle_transformers::models::auto::{architecture_names, safetensors_files};
+use candle_transformers::models::{llama, quantized_llama, quantized_qwen3, qwen3};
+use candle_transformers::pipelines::chat_template::ChatTemplate;
+use candle_transformers::pipelines::text_generation::PagedModel;
+use candle_transformers::quantized_var_builder::{QuantizationConfig, VarBuilder};
+use std::path::{Path, PathBuf};
+
+/// The model architectures that support paged attention.
+pub enum Model {
+    Llama(quantized_llama::ModelWeights),
+    Qwen3(quantized_qwen3::ModelWeights),
+}
+
+impl PagedModel for Model {
+    fn max_seq_len(&self) -> usize {
+        match self {
+            Self::Llama(m) => m.max_seq_len(),
+            Self::Qwen3(m) => m.max_seq_len(),
+        }
+    }
+
+    fn paged_kv_caches(
+        &self,
+        num_blocks: usize,
+        block_size: usize,
+    ) -> candle::Result<Vec<PagedKvCache>> {
+        match self {
+            Self::Llama(m) => m.paged_kv_caches(num_blocks, block_size),
+            Self::Qwen3(m) => m.paged_kv_caches(num_blocks, block_size),
+        }
+    }
+
+    fn forward_paged(
+        &self,
+        input: &Tensor,
+        caches: &mut [PagedKvCache],
+        metadata: &PagedAttentionMetadata,
+    ) -> candle::Result<Tensor> {
+        match self {
+            Self::Llama(m) => m.forward_paged(input, caches, metadata),
+            Self::Qwen3(m) => m.forward_paged(input, caches, metadata),
+        }
+    }
+}
+
+/// A model together with the information from its gguf metadata or its `config.json`.
+pub struct LoadedModel {
+    pub model: Model,
+    pub architecture: String,
+    pub eos_token_id: Option<u32>,
+    pub chat_template: Option<ChatTemplate>,
+}
+
+impl LoadedModel {
+    /// Loads a gguf file or a HuggingFace model directory with safetensors weights, plain or
+    /// GPTQ quantized.
+    pub fn load<P: AsRef<Path>>(path: P, device: &Device) -> Result<Self> {
+        let path = path.as_ref();
+        if path.is_dir() {
+            Self::from_hf_dir(path, device)
+        } else {
+            let mut file = std::fs::File::open(path).with_context(|| format!("{path:?}"))?;
+            Self::from_gguf(&mut file, device)
+        }
+    }
+
+    pub fn from_hf_dir(dir: &Path, device: &Device) -> Result<Self> {
+        let config_file = dir.join("config.json");
+        let config =
+            std::fs::read_to_string(&config_file).with_context(|| format!("{config_file:?}"))?;
+        let json: serde_json::Value = serde_json::from_str(&config)?;
+        let files = safetensors_files(dir)?;
+        let vb = match json.get("quantization_config") {
+            None => VarBuilder::from_safetensors(&files, device)?,
+            Some(quantization) => {
+                let quantization: QuantizationConfig =
+                    serde_json::from_value(quantization.clone())?;
+                VarBuilder::from_gptq_safetensors(&files, &quantization.gptq_config()?, device)?
+            }
+        };
+        // The names are looked up in the same order as in the model registry, only the
+        // architectures with a paged attention implementation are supported.
+        let names = architecture_names(&config)?;
+        let architecture = names.iter().find_map(|name| match name.as_str() {
+            "LlamaForCausalLM" | "MistralForCausalLM" | "llama" | "mistral" => Some("llama"),
+            "Qwen3ForCausalLM" | "qwen3" => Some("qwen3"),
+            _ => None,
+        });
+        let Some(architecture) = architecture else {
+            bail!("unsupported architecture {names:?} in {config_file:?}")
+        };
+        let model = match architecture {
+            "llama" => {
+                let cfg: llama::LlamaConfig = serde_json::from_str(&config)?;
+                Model::Llama(quantized_llama::ModelWeights::from_gptq(
+                    &cfg.into_config(false),
+                    vb,
+                )?)
+            }
+            _ => {
+                let cfg: qwen3::Config = serde_json::from_str(&config)?;
+                Model::Qwen3(quantized_qwen3::ModelWeights::from_var_builder(&cfg, vb)?)
+            }
+        };
+        // The end of sequence token can be a list, the first one ends the generation.
+        let eos_token_id = match &json["eos_token_id"] {
+            serde_json::Value::Array(ids) => ids.first().and_then(|id| id.as_u64()),
+            id => id.as_u64(),
+        };
+        Ok(Self {
+            model,
+            architecture: architecture.to_string(),
+            eos_token_id: eos_token_id.map(|id| id as u32),
+            chat_template: tokenizer_config_template(&dir.join("tokenizer_config.json"))?,
+        })
+    }
+
+    pub fn from_gguf<R: std::io::Read + std::io::Seek>(
+        reader: &mut R,
+        device: &Device,
+    ) -> Result<Self> {
+        let content = gguf_file::Content::read(reader)?;
+        let architecture = match content.metadata.get("general.architecture") {
+            None => "llama".to_string(),
+            Some(v) => v.to_string()?.clone(),
+        };
+        let eos_token_id = match content.metadata.get("tokenizer.ggml.eos_token_id") {
+            None => None,
+            Some(v) => Some(v.to_u32()?),
+        };
+        let chat_template = match content.metadata.get("tokenizer.chat_template") {
+            None => None,
+            Some(_) => {
+                Some(ChatTemplate::from_gguf(&content).context("invalid gguf chat template")?)
+            }
+        };
+        let model = match architecture.as_str() {
+            "llama" => Model::Llama(quantized_llama::ModelWeights::from_gguf(
+                content, reader, device,
+            )?),
+            "qwen3" => Model::Qwen3(quantized_qwen3::ModelWeights::from_gguf(
+                content, reader, device,
+            )?),
+            arch => bail!("unsupported architecture {arch}"),
+        };
+        Ok(Self {
+            model,
+            architecture,
+            eos_token_id,
+            chat_template,
+        })
+    }
+}
+
+/// Loads the chat template of a `tokenizer_config.json` file, returns `None` when the file does
+/// not exist or has no `chat_template` entry.
+pub fn tokenizer_config_template(path: &Path) -> Result<Option<ChatTemplate>> {
+    if !path.exists() {
+        return Ok(None);
+    }
+    let config = std::fs::read_to_string(path).with_context(|| format!("{path:?}"))?;
+    let json: serde_json::Value = serde_json::from_str(&config)?;
+    if json.get("chat_template").is_none() {
+        return Ok(None);
+    }
+    let template = ChatTemplate::from_tokenizer_config_str(&config)
+        .with_context(|| format!("invalid chat template in {path:?}"))?;
+    Ok(Some(template))
+}
+
+/// Returns the `tokenizer.json` file for a model, either in the model directory or next to the
+/// gguf file.
+pub fn tokenizer_file(model: &Path) -> Result<PathBuf> {
+    let dir = if model.is_dir() {
+        model
+    } else {
+        model.parent().unwrap_or(Path::new("."))
+    };
+    let file = dir.join("tokenizer.json");
+    if !file.exists() {
+        bail!("no tokenizer.json found in {dir:?}, use --tokenizer to specify one")
+    }
+    Ok(file)
+}
@@ -0,0 +1,754 @@
+//! The HTTP routes of the OpenAI compatible API.
+use crate::embeddings::EmbeddingModel;
+use crate::http::{is_timeout, read_request, EventStream, Request, Response, READ_TIMEOUT};
+use crate::metrics::Metrics;
+use crate::openai::*;
+use crate::worker::{EngineHandle, Generation};
+use anyhow::{bail, Error as E, Result};
+use candle::Device;
+use candle_transformers::generation::constrained::Vocabulary;
+use candle_transformers::generation::logprobs::SampledToken;
+use candle_transformers::generation::Sampling;
+use candle_transformers::pipelines::chat_template::{ChatTemplate, ChatTemplateOptions, ToolCall};
+use candle_transformers::pipelines::text_generation::{
+    Engine, EngineConfig, FinishReason, GenerationParams, PagedModel, TokenEvent,
+};
+use candle_transformers::pipelines::tool_calls::{ToolCallFormat, ToolCallParser};
+use std::io::{BufReader, Write};
+use std::net::{TcpListener, TcpStream};
+use std::sync::Arc;
+use std::time::{Instant, SystemTime, UNIX_EPOCH};
+use tokenizers::Tokenizer;
+
+fn unix_time() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |d| d.as_secs())
+}
+
+/// A failure of the engine or of a model rather than of the request, reported with a 500 status.
+#[derive(Debug)]
+pub(crate) struct InternalError(pub(crate) E);
+
+impl std::fmt::Display for InternalError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        self.0.fmt(f)
+    }
+}
+
+impl std::error::Error for InternalError {}
+
+pub(crate) fn internal(err: impl Into<E>) -> E {
+    E::new(InternalError(err.into()))
+}
+
+enum Reply {
+    Response(Response),
+    // The response has already been written as an event stream.
+    Streamed,
+}
+
+impl From<Response> for Reply {
+    fn from(response: Response) -> Self {
+        Self::Response(response)
+    }
+}
+
+/// The result of a completed generation.
+struct Output {
+    text: String,
+    num_tokens: usize,
+    logprobs: Vec<SampledToken>,
+    finish_reason: FinishReason,
+}
+
+pub struct Server {
+    engine: EngineHandle,
+    tokenizer: Tokenizer,
+    vocab: Arc<Vocabulary>,
+    chat_template: Option<ChatTemplate>,
+    tool_call_format: Option<ToolCallFormat>,
+    reasoning: bool,
+    embedding_model: Option<(String, EmbeddingModel)>,
+    metrics: Arc<Metrics>,
+    model_name: String,
+    max_tokens: usize,
+    max_seq_len: usize,
+    created: u64,
+}
+
+impl Server {
+    /// Starts the engine worker for `model`. The model stops generating when it samples one of
+    /// `eos_tokens`.
+    pub fn new<M: PagedModel + Send + 'static>(
+        model: M,
+        config: EngineConfig,
+        device: &Device,
+        tokenizer: Tokenizer,
+        eos_tokens: Vec<u32>,
+        model_name: impl Into<String>,
+    ) -> Result<Self> {
+        // The tool call tags are kept in the generated text so that the calls can be parsed.
+        let tool_call_tags: Vec<&str> = ToolCallFormat::ALL
+            .iter()
+            .filter_map(|f| f.start_tag())
+            .collect();
+        let special_tokens: Vec<u32> = tokenizer
+            .get_added_tokens_decoder()
+            .iter()
+            .filter(|(_, token)| token.special && !tool_call_tags.contains(&token.content.as_str()))
+            .map(|(&id, _)| id)
+            .collect();
+        let vocab = Vocabulary::from_token_strings(
+            &tokenizer.get_vocab(true),
+            &special_tokens,
+            eos_tokens,
+        )?;
+        let vocab = Arc::new(vocab);
+        let engine = Engine::new(model, config, device)?.with_vocabulary(vocab.clone());
+        let max_seq_len = engine.max_seq_len();
+        let metrics = Arc::new(Metrics::new());
+        let engine = EngineHandle::spawn(engine, metrics.clone());
+        Ok(Self {
+            engine,
+            tokenizer,
+            vocab,
+            chat_template: None,
+            tool_call_format: None,
+            reasoning: false,
+            embedding_model: None,
+            metrics,
+            model_name: model_name.into(),
+            max_tokens: 256,
+            max_seq_len,
+            created: unix_time(),
+        })
+    }
+
+    /// Sets the template used to format the messages of the chat completion requests, these
+    /// requests are rejected when no template is set.
+    pub fn with_chat_template(mut self, chat_template: ChatTemplate) -> Self {
+        self.chat_template = Some(chat_template);
+        self
+    }
+
+    /// Sets the format of the tool calls generated by the model, these are only parsed for the
+    /// requests that provide tools.
+    pub fn with_tool_call_format(mut self, format: Option<ToolCallFormat>) -> Self {
+        self.tool_call_format = format;
+        self
+    }
+
+    /// Returns the `<think></think>` blocks of the chat completions as `reasoning_content`
+    /// rather than as part of the content.
+    pub fn with_reasoning(mut self, reasoning: bool) -> Self {
+        self.reasoning = reasoning;
+        self
+    }
+
+    /// Sets the model used by the embeddings endpoint.
+    pub fn with_embedding_model(mut self, name: impl Into<String>, model: EmbeddingModel) -> Self {
+        self.embedding_model = Some((name.into(), model));
+        self
+    }
+
+    /// Sets the maximum number of generated tokens for the requests that do not specify it.
+    pub fn with_max_tokens(mut self, max_tokens: usize) -> Self {
+        self.max_tokens = max_tokens;
+        self
+    }
+
+    pub fn metrics(&self) -> &Metrics {
+        &self.metrics
+    }
+
+    /// Serves the incoming connections, each of them is handled on its own thread.
+    pub fn serve(self: Arc<Self>, listener: TcpListener) -> Result<()> {
+        for stream in listener.incoming() {
+            let stream = match stream {
+                Ok(stream) => stream,
+                Err(err) => {
+                    eprintln!("failed to accept connection: {err}");
+                    continue;
+                }
+            };
+            let server = self.clone();
+            std::thread::spawn(move || {
+                if let Err(err) = server.handle_connection(stream) {
+                    eprintln!("connection error: {err}")
+                }
+            });
+        }
+        Ok(())
+    }
+
+    fn handle_connection(&self, stream: TcpStream) -> Result<()> {
+        let start = Instant::now();
+        stream.set_read_timeout(Some(READ_TIMEOUT))?;
+        let mut writer = stream.try_clone()?;
+        let mut reader = BufReader::new(stream);
+        let request = match read_request(&mut reader) {
+            Ok(Some(request)) => request,
+            Ok(None) => return Ok(()),
+            Err(err) => {
+                let status = if is_timeout(&err) { 408 } else { 400 };
+                self.metrics
+                    .record_request("other", status, start.elapsed());
+                Response::error(status, err).write(&mut writer)?;
+                return Ok(());
+            }
+        };
+        let endpoint = match request.path.as_str() {
+            "/v1/models"
+            | "/health"
+            | "/metrics"
+            | "/v1/completions"
+            | "/v1/chat/completions"
+            | "/v1/embeddings" => request.path.as_str(),
+            _ => "other",
+        };
+        let status = match self.route(&request, &mut writer, start) {
+            Reply::Response(response) => {
+                response.write(&mut writer)?;
+                response.status
+            }
+            Reply::Streamed => 200,
+        };
+        self.metrics
+            .record_request(endpoint, status, start.elapsed());
+        Ok(())
+    }
+
+    fn route<W: Write>(&self, request: &Request, writer: W, start: Instant) -> Reply {
+        let result = match (request.method.as_str(), request.path.as_str()) {
+            ("GET", "/health") => Ok(Response::text(200, "text/plain", "ok").into()),
+            ("GET", "/metrics") => {
+                Ok(Response::text(200, "text/plain; version=0.0.4", self.metrics.render()).into())
+            }
+            ("GET", "/v1/models") => Ok(Response::json(200, &self.models()).into()),
+            ("POST", "/v1/completions") => serde_json::from_slice(&request.body)
+                .map_err(E::from)
+                .and_then(|r| self.completions(r, writer, start)),
+            ("POST", "/v1/chat/completions") => serde_json::from_slice(&request.body)
+                .map_err(E::from)
+                .and_then(|r| self.chat_completions(r, writer, start)),
+            ("POST", "/v1/embeddings") => serde_json::from_slice(&request.body)
+                .map_err(E::from)
+                .and_then(|r| self.embeddings(r).map(Reply::from)),
+            (_, "/health" | "/metrics" | "/v1/models" | "/v1/completions")
+            | (_, "/v1/chat/completions" | "/v1/embeddings") => {
+                Ok(Response::error(405, format!("method {} not allowed", request.method)).into())
+            }
+            (_, path) => Ok(Response::error(404, format!("unknown path {path}")).into()),
+        };
+        result.unwrap_or_else(|err| {
+            let status = if err.is::<InternalError>() { 500 } else { 400 };
+            Response::error(status, err).into()
+        })
+    }
+
+    fn models(&self) -> ModelList {
+        let card = |id: &str| ModelCard {
+            id: id.to_string(),
+            object: "model",
+            created: self.created,
+            owned_by: "candle",
+        };
+        let mut data = vec![card(&self.model_name)];
+        if let Some((name, _)) = self.embedding_model.as_ref() {
+            data.push(card(name))
+        }
+        ModelList {
+            object: "list",
+            data,
+        }
+    }
+
+    // Checks that the prompt and the generated tokens fit in the model context, the requests
+    // that do not set `max_tokens` generate at most until the end of the context.
+    fn generation_params(
+        &self,
+        sampling: SamplingParams,
+        prompt: &[u32],
+        max_tokens: Option<usize>,
+        logprobs: Option<usize>,
+    ) -> Result<GenerationParams> {
+        if sampling.n.is_some_and(|n| n != 1) {
+            bail!("only n=1 is supported")
+        }
+        if let Some(&token) = prompt.iter().find(|&&t| t as usize >= self.vocab.len()) {
+            bail!("token {token} is out of the vocabulary")
+        }
+        let max_seq_len = self.max_seq_len;
+        if prompt.len() >= max_seq_len {
+            bail!(
+                "the prompt has {} tokens, the maximum context length is {max_seq_len} tokens",
+                prompt.len()
+            )
+        }
+        let max_new_tokens = match max_tokens {
+            None => self.max_tokens.min(max_seq_len - prompt.len()),
+            Some(max_tokens) if prompt.len() + max_tokens > max_seq_len => bail!(
+                "{} tokens were requested, the maximum context length is {max_seq_len} tokens",
+                prompt.len() + max_tokens
+            ),
+            Some(max_tokens) => max_tokens,
+        };
+        let temperature = sampling.temperature.unwrap_or(1.);
+        let sampling_mode = if temperature <= 0. {
+            Sampling::ArgMax
+        } else {
+            match (sampling.top_k, sampling.top_p) {
+                (None, None) => Sampling::All { temperature },
+                (Some(k), None) => Sampling::TopK { k, temperature },
+                (None, Some(p)) => Sampling::TopP { p, temperature },
+                (Some(k), Some(p)) => Sampling::TopKThenTopP { k, p, temperature },
+            }
+        };
+        // Requests without a seed get a different one each time.
+        let seed = sampling.seed.unwrap_or_else(|| {
+            SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map_or(0, |d| d.as_nanos() as u64)
+        });
+        Ok(GenerationParams {
+            sampling: sampling_mode,
+            seed,
+            max_new_tokens,
+            stop_sequences: sampling.stop.map_or(vec![], StringOrList::into_vec),
+            repeat_penalty: sampling.repetition_penalty.unwrap_or(1.),
+            logprobs,
+            ..Default::default()
+        })
+    }
+
+    fn generate(&self, prompt: Vec<u32>, params: GenerationParams) -> Result<Generation> {
+        self.metrics.record_prompt_tokens(prompt.len());
+        self.engine.generate(prompt, params)
+    }
+
+    // Waits for the next event of a generation, the first event records the time to first token.
+    fn next_event(
+        &self,
+        generation: &Generation,
+        start: Instant,
+        first: &mut bool,
+    ) -> Result<TokenEvent> {
+        let event = match generation.events.recv() {
+            Ok(event) => event,
+            Err(_) => bail!("the engine worker has stopped"),
+        };
+        if *first {
+            *first = false;
+            self.metrics.record_time_to_first_token(start.elapsed());
+        }
+        if event.finish_reason == Some(FinishReason::Aborted) {
+            bail!("the generation failed")
+        }
+        Ok(event)
+    }
+
+    fn collect(&self, generation: &Generation, start: Instant) -> Result<Output> {
+        let mut output = Output {
+            text: String::new(),
+            num_tokens: 0,
+            logprobs: vec![],
+            finish_reason: FinishReason::Length,
+        };
+        let mut first = true;
+        loop {
+            let event = self.next_event(generation, start, &mut first)?;
+            output.num_tokens += usize::from(event.token.is_some());
+            output
+                .text
+                .push_str(event.text.as_deref().unwrap_or_default());
+            output.logprobs.extend(event.logprobs);
+            if let Some(finish_reason) = event.finish_reason {
+                output.finish_reason = finish_reason;
+                return Ok(output);
+            }
+        }
+    }
+
+    // The text of a token, special tokens have no bytes in the vocabulary.
+    fn token_bytes(&self, token: u32) -> Vec<u8> {
+        match self.vocab.token_bytes(token) {
+            Some(bytes) if !bytes.is_empty() => bytes.to_vec(),
+            _ => self
+                .tokenizer
+                .id_to_token(token)
+                .unwrap_or_default()
+                .into_bytes(),
+        }
+    }
+
+    fn chat_logprobs(&self, logprobs: &[SampledToken]) -> ChatLogprobs {
+        let content = logprobs
+            .iter()
+            .map(|sampled| {
+                let bytes = self.token_bytes(sampled.token);
+                let top_logprobs = sampled
+                    .top_logprobs
+                    .iter()
+                    .map(|t| {
+                        let bytes = self.token_bytes(t.token);
+                        TopLogprob {
+                            token: String::from_utf8_lossy(&bytes).into_owned(),
+                            logprob: t.logprob,
+                            bytes,
+                        }
+                    })
+                    .collect();
+                TokenLogprob {
+                    token: String::from_utf8_lossy(&bytes).into_owned(),
+                    logprob: sampled.logprob,
+                    bytes,
+                    top_logprobs,
+                }
+            })
+            .collect();
+        ChatLogprobs { content }
+    }
+
+    fn completion_logprobs(&self, logprobs: &[SampledToken], offset: usize) -> CompletionLogprobs {
+        let mut result = CompletionLogprobs::default();
+        let mut offset = offset;
+        for sampled in logprobs.iter() {
+            let token = String::from_utf8_lossy(&self.token_bytes(sampled.token)).into_owned();
+            let top = sampled
+                .top_logprobs
+                .iter()
+                .map(|t| {
+                    let token = String::from_utf8_lossy(&self.token_bytes(t.token)).into_owned();
+                    (token, t.logprob.into())
+                })
+                .collect();
+            result.text_offset.push(offset);
+            offset += token.len();
+            result.tokens.push(token);
+            result.token_logprobs.push(sampled.logprob);
+            result.top_logprobs.push(top);
+        }
+        result
+    }
+
+    fn completions<W: Write>(
+        &self,
+        request: CompletionRequest,
+        writer: W,
+        start: Instant,
+    ) -> Result<Reply> {
+        let prompt = match request.prompt {
+            Prompt::Tokens(tokens) => tokens,
+            Prompt::Text(text) => self
+                .tokenizer
+                .encode(text, true)
+                .map_err(E::msg)?
+                .get_ids()
+                .to_vec(),
+        };
+        if prompt.is_empty() {
+            bail!("the prompt is empty")
+        }
+        let prompt_tokens = prompt.len();
+        let include_usage = request.stream_options.is_some_and(|o| o.include_usage);
+        let params = self.generation_params(
+            request.sampling,
+            &prompt,
+            request.max_tokens,
+            request.logprobs,
+        )?;
+        let with_logprobs = params.logprobs.is_some();
+        let generation = self.generate(prompt, params)?;
+        let id = format!("cmpl-{}", generation.id);
+        let model = request.model.unwrap_or_else(|| self.model_name.clone());
+        let created = unix_time();
+        let response = |choice: CompletionChoice, usage: Option<Usage>| CompletionResponse {
+            id: id.clone(),
+            object: "text_completion",
+            created,
+            model: model.clone(),
+            choices: vec![choice],
+            usage,
+        };
+        if !request.stream {
+            let output = match self.collect(&generation, start) {
+                Ok(output) => output,
+                Err(err) => return Ok(Response::error(500, err).into()),
+            };
+            let choice = CompletionChoice {
+                index: 0,
+                logprobs: with_logprobs.then(|| self.completion_logprobs(&output.logprobs, 0)),
+                text: output.text,
+                finish_reason: Some(finish_reason(output.finish_reason)),
+            };
+            let usage = Usage::new(prompt_tokens, output.num_tokens);
+            return Ok(Response::json(200, &response(choice, Some(usage))).into());
+        }
+        let mut stream = EventStream::new(writer).map_err(internal)?;
+        let mut first = true;
+        let mut num_tokens = 0;
+        let mut offset = 0;
+        loop {
+            let event = match self.next_event(&generation, start, &mut first) {
+                Ok(event) => event,
+                // The client gets a truncated stream without the final `[DONE]` event.
+                Err(_) => return Ok(Reply::Streamed),
+            };
+            num_tokens += usize::from(event.token.is_some());
+            let text = event.text.unwrap_or_default();
+            let logprobs = event.logprobs.filter(|_| with_logprobs);
+            let choice = CompletionChoice {
+                index: 0,
+                logprobs: logprobs.map(|l| self.completion_logprobs(&[l], offset)),
+                finish_reason: event.finish_reason.map(finish_reason),
+                text,
+            };
+            offset += choice.text.len();
+            let is_last = event.finish_reason.is_some();
+            if stream.send(&response(choice, None)).is_err() {
+                self.engine.abort(generation.id);
+                return Ok(Reply::Streamed);
+            }
+            if is_last {
+                break;
+            }
+        }
+        if include_usage {
+            let usage = CompletionResponse {
+                choices: vec![],
+                ..response(
+                    CompletionChoice {
+                        index: 0,
+                        text: String::new(),
+                        logprobs: None,
+                        finish_reason: None,
+                    },
+                    Some(Usage::new(prompt_tokens, num_tokens)),
+                )
+            };
+            let _ = stream.send(&usage);
+        }
+        let _ = stream.done();
+        Ok(Reply::Streamed)
+    }
+
+    fn chat_completions<W: Write>(
+        &self,
+        request: ChatCompletionRequest,
+        writer: W,
+        start: Instant,
+    ) -> Result<Reply> {
+        let chat_template = match self.chat_template.as_ref() {
+            None => bail!("the model has no chat template"),
+            Some(chat_template) => chat_template,
+        };
+        let messages = request
+            .messages
+            .into_iter()
+            .map(ChatMessage::into_message)
+            .collect::<Result<Vec<_>>>()?;
+        let tools = match request.tool_choice.as_ref().and_then(|c| c.as_str()) {
+            Some("none") => vec![],
+            _ => request.tools,
+        };
+        let has_tools = !tools.is_empty();
+        let mut options = ChatTemplateOptions::for_generation().with_tools(tools);
+        for (name, value) in request.chat_template_kwargs.into_iter() {
+            options = options.with_context(name, value)
+        }
+        let prompt = chat_template.apply(&messages, &options)?;
+        // The special tokens such as the bos token are part of the rendered template.
+        let prompt = self
+            .tokenizer
+            .encode(prompt, false)
+            .map_err(E::msg)?
+            .get_ids()
+            .to_vec();
+        let prompt_tokens = prompt.len();
+        let include_usage = request.stream_options.is_some_and(|o| o.include_usage);
+        let logprobs = request.logprobs.then(|| request.top_logprobs.unwrap_or(0));
+        let max_tokens = request.max_completion_tokens.or(request.max_tokens);
+        let params = self.generation_params(request.sampling, &prompt, max_tokens, logprobs)?;
+        let generation = self.generate(prompt, params)?;
+        let id = format!("chatcmpl-{}", generation.id);
+        let model = request.model.unwrap_or_else(|| self.model_name.clone());
+        let created = unix_time();
+        let mut parser = ToolCallParser::new(self.tool_call_format.filter(|_| has_tools));
+        if self.reasoning {
+            parser = parser.with_reasoning(false)
+        }
+        // The calls get numbered in the order in which they are parsed.
+        let tool_calls = |first: usize, calls: Vec<ToolCall>, streamed: bool| {
+            calls
+                .into_iter()
+                .enumerate()
+                .map(|(i, call)| ResponseToolCall {
+                    index: streamed.then_some(first + i),
+                    id: call
+                        .id
+                        .unwrap_or_else(|| format!("call_{}_{}", generation.id, first + i)),
+                    kind: "function",
+                    function: ChatFunctionCall {
+                        name: call.function.name,
+                        arguments: call.function.arguments.to_string(),
+                    },
+                })
+                .collect::<Vec<_>>()
+        };
+        if !request.stream {
+            let output = match self.collect(&generation, start) {
+                Ok(output) => output,
+                Err(err) => return Ok(Response::error(500, err).into()),
+            };
+            let mut parsed = parser.push(&output.text);
+            let end = parser.finish();
+            parsed.reasoning.push_str(&end.reasoning);
+            parsed.content.push_str(&end.content);
+            parsed.tool_calls.extend(end.tool_calls);
+            let finish_reason = if parsed.tool_calls.is_empty() {
+                finish_reason(output.finish_reason)
+            } else {
+                "tool_calls"
+            };
+            let message = ResponseMessage {
+                role: Some("assistant"),
+                content: (parsed.tool_calls.is_empty() || !parsed.content.is_empty())
+                    .then_some(parsed.content),
+                reasoning_content: (!parsed.reasoning.is_empty()).then_some(parsed.reasoning),
+                tool_calls: tool_calls(0, parsed.tool_calls, false),
+            };
+            let response = ChatCompletionResponse {
+                id,
+                object: "chat.completion",
+                created,
+                model,
+                choices: vec![ChatChoice {
+                    index: 0,
+                    message,
+                    logprobs: logprobs.map(|_| self.chat_logprobs(&output.logprobs)),
+                    finish_reason,
+                }],
+                usage: Usage::new(prompt_tokens, output.num_tokens),
+            };
+            return Ok(Response::json(200, &response).into());
+        }
+
+        let mut stream = EventStream::new(writer).map_err(internal)?;
+        let chunk = |delta: ResponseMessage,
+                     logprobs: Option<ChatLogprobs>,
+                     finish_reason: Option<&'static str>| ChatCompletionChunk {
+            id: id.clone(),
+            object: "chat.completion.chunk",
+            created,
+            model: model.clone(),
+            choices: vec![ChatChunkChoice {
+                index: 0,
+                delta,
+                logprobs,
+                finish_reason,
+            }],
+            usage: None,
+        };
+        let first_delta = ResponseMessage {
+            role: Some("assistant"),
+            content: Some(String::new()),
+            ..Default::default()
+        };
+        if stream.send(&chunk(first_delta, None, None)).is_err() {
+            self.engine.abort(generation.id);
+            return Ok(Reply::Streamed);
+        }
+        let mut first = true;
+        let mut num_tokens = 0;
+        loop {
+            let event = match self.next_event(&generation, start, &mut first) {
+                Ok(event) => event,
+                Err(_) => return Ok(Reply::Streamed),
+            };
+            num_tokens += usize::from(event.token.is_some());
+            let num_calls = parser.num_tool_calls();
+            let mut parsed = parser.push(event.text.as_deref().unwrap_or_default());
+            if event.finish_reason.is_some() {
+                let end = parser.finish();
+                parsed.reasoning.push_str(&end.reasoning);
+                parsed.content.push_str(&end.content);
+                parsed.tool_calls.extend(end.tool_calls);
+            }
+            let logprobs = match (logprobs, event.logprobs) {
+                (Some(_), Some(l)) => Some(self.chat_logprobs(&[l])),
+                _ => None,
+            };
+            if !parsed.is_empty() || logprobs.is_some() {
+                let delta = ResponseMessage {
+                    content: (!parsed.content.is_empty()).then_some(parsed.content),
+                    reasoning_content: (!parsed.reasoning.is_empty()).then_some(parsed.reasoning),
+                    tool_calls: tool_calls(num_calls, parsed.tool_calls, true),
+                    ..Default::default()
+                };
+                if stream.send(&chunk(delta, logprobs, None)).is_err() {
+                    self.engine.abort(generation.id);
+                    return Ok(Reply::Streamed);
+                }
+            }
+            if let Some(reason) = event.finish_reason {
+                let reason = if parser.num_tool_calls() > 0 {
+                    "tool_calls"
+                } else {
+                    finish_reason(reason)
+                };
+                let _ = stream.send(&chunk(ResponseMessage::default(), None, Some(reason)));
+                break;
+            }
+        }
+        if include_usage {
+            let usage = ChatCompletionChunk {
+                choices: vec![],
+                usage: Some(Usage::new(prompt_tokens, num_tokens)),
+                ..chunk(ResponseMessage::default(), None, None)
+            };
+            let _ = stream.send(&usage);
+        }
+        let _ = stream.done();
+        Ok(Reply::Streamed)
+    }
+
+    fn embeddings(&self, request: EmbeddingRequest) -> Result<Response> {
+        let (name, model) = match self.embedding_model.as_ref() {
+            None => bail!("no embedding model has been loaded"),
+            Some(model) => model,
+        };
+        match request.encoding_format.as_deref() {
+            None | Some("float") => {}
+            Some(format) => bail!("unsupported encoding format {format}"),
+        }
+        let inputs = request.input.into_vec();
+        let (embeddings, num_tokens) = model.embed(&inputs, request.dimensions)?;
+        let data = embeddings
+            .into_iter()
+            .enumerate()
+            .map(|(index, embedding)| Embedding {
+                object: "embedding",
+                index,
+                embedding: embedding.into(),
+            })
+            .collect();
+        let response = EmbeddingResponse {
+            object: "list",
+            data,
+            model: request.model.unwrap_or_else(|| name.clone()),
+            usage: EmbeddingUsage {
+                prompt_tokens: num_tokens,
+                total_tokens: num_tokens,
+            },
+        };
+        Ok(Response::json(200, &response))
+    }
+}
+
+fn finish_reason(finish_reason: FinishReason) -> &'static str {
+    match finish_reason {
+        FinishReason::Stop | FinishReason::Eos | FinishReason::Aborted => "stop",
+        FinishReason::Length => "length",
+    }
+}
@@ -0,0 +1,108 @@
+//! The engine worker, a thread that owns the generation engine so that the requests from all the
+//! connections get batched together.
+use crate::metrics::Metrics;
+use crate::server::internal;
+use anyhow::Result;
+use candle_transformers::pipelines::text_generation::{
+    Engine, GenerationParams, PagedModel, TokenEvent,
+};
+use std::sync::{mpsc, Arc};
+
+enum Command {
+    Generate {
+        prompt: Vec<u32>,
+        params: GenerationParams,
+        reply: mpsc::Sender<Result<Generation>>,
+    },
+    Abort(usize),
+}
+
+/// A request being processed by the engine, the last event has its `finish_reason` set.
+pub struct Generation {
+    pub id: usize,
+    pub events: mpsc::Receiver<TokenEvent>,
+}
+
+/// A handle to the engine worker, this can be cloned and shared between threads.
+#[derive(Clone)]
+pub struct EngineHandle {
+    sender: mpsc::Sender<Command>,
+}
+
+impl EngineHandle {
+    /// Starts the worker thread, it stops once all the handles have been dropped and the pending
+    /// requests have completed.
+    pub fn spawn<M: PagedModel + Send + 'static>(engine: Engine<M>, metrics: Arc<Metrics>) -> Self {
+        let (sender, receiver) = mpsc::channel();
+        std::thread::spawn(move || run(engine, receiver, &metrics));
+        Self { sender }
+    }
+
+    /// Queues a new request in the engine.
+    pub fn generate(&self, prompt: Vec<u32>, params: GenerationParams) -> Result<Generation> {
+        let (reply, response) = mpsc::channel();
+        self.sender
+            .send(Command::Generate {
+                prompt,
+                params,
+                reply,
+            })
+            .map_err(|_| internal(anyhow::anyhow!("the engine worker has stopped")))?;
+        response
+            .recv()
+            .map_err(|_| internal(anyhow::anyhow!("the engine worker has stopped")))?
+    }
+
+    /// Aborts a request, e.g. when the client has disconnected.
+    pub fn abort(&self, id: usize) {
+        let _ = self.sender.send(Command::Abort(id));
+    }
+}
+
+fn handle<M: PagedModel>(engine: &mut Engine<M>, command: Command) {
+    match command {
+        Command::Generate {
+            prompt,
+            params,
+            reply,
+        } => {
+            let generation = engine
+                .add_streaming_request(prompt, params)
+                .map(|(id, events)| Generation { id, events })
+                .map_err(anyhow::Error::from);
+            let _ = reply.send(generation);
+        }
+        Command::Abort(id) => {
+            if let Err(err) = engine.abort(id) {
+                eprintln!("failed to abort request {id}: {err}")
+            }
+        }
+    }
+}
+
+fn run<M: PagedModel>(mut engine: Engine<M>, receiver: mpsc::Receiver<Command>, metrics: &Metrics) {
+    loop {
+        if !engine.has_pending_requests() {
+            // Block until a new command arrives rather than spinning while idle.
+            match receiver.recv() {
+                Ok(command) => handle(&mut engine, command),
+                Err(_) => return,
+            }
+        }
+        while let Ok(command) = receiver.try_recv() {
+            handle(&mut engine, command)
+        }
+        if engine.has_pending_requests() {
+            match engine.step() {
+                Ok(events) => {
+                    let num_tokens = events.iter().filter(|e| e.token.is_some()).count();
+                    metrics.record_generation_tokens(num_tokens);
+                }
+                // The engine aborts the requests of the failed batch, the other requests keep
+                // running.
+                Err(err) => eprintln!("engine step failed: {err}"),
+            }
+        }
+        metrics.set_queue_sizes(engine.num_running(), engine.num_waiting());
+    }
+}
@@ -0,0 +1,707 @@
+use anyhow::Result;
+use candle::quantized::{gguf_file, GgmlDType, QTensor};
+use candle::{DType, Device, Tensor};
+use candle_nn::paged_attention::{PagedAttentionMetadata, PagedKvCache};
+use candle_nn::{VarBuilder, VarMap};
+use candle_server::embeddings::EmbeddingModel;
+use candle_server::model::LoadedModel;
+use candle_server::server::Server;
+use candle_transformers::models::{bert, quantized_llama};
+use candle_transformers::pipelines::chat_template::ChatTemplate;
+use candle_transformers::pipelines::text_generation::{EngineConfig, PagedModel};
+use candle_transformers::pipelines::tool_calls::ToolCallFormat;
+use serde_json::{json, Value};
+use std::collections::HashMap;
+use std::io::{Read, Write};
+use std::net::{SocketAddr, TcpListener, TcpStream};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+const WORDS: [&str; 32] = [
+    "<unk>",
+    "<|im_start|>",
+    "<|im_end|>",
+    "user",
+    "assistant",
+    "system",
+    "hello",
+    "world",
+    "the",
+    "cat",
+    "dog",
+    "sat",
+    "on",
+    "mat",
+    "a",
+    "is",
+    "and",
+    "of",
+    "to",
+    "in",
+    "it",
+    ".",
+    ",",
+    "?",
+    "!",
+    "you",
+    "I",
+    "we",
+    "red",
+    "blue",
+    "green",
+    "sky",
+];
+
+const CHATML: &str = "{% for message in messages %}<|im_start|>{{ message.role }}\n{{ message.content }}<|im_end|>\n{% endfor %}{% if add_generation_prompt %}<|im_start|>assistant\n{% endif %}";
+
+// A word level tokenizer, the tokens in `special` are added as special tokens.
+fn tokenizer_json(words: &[&str], special: &[&str], unk: &str) -> String {
+    let vocab: serde_json::Map<String, Value> = words
+        .iter()
+        .enumerate()
+        .map(|(id, w)| (w.to_string(), json!(id)))
+        .collect();
+    let added_tokens: Vec<Value> = special
+        .iter()
+        .map(|w| {
+            let id = words.iter().position(|v| v == w).unwrap();
+            json!({
+                "id": id, "content": w, "single_word": false, "lstrip": false, "rstrip": false,
+                "normalized": false, "special": true
+            })
+        })
+        .collect();
+    json!({
+        "version": "1.0",
+        "truncation": null,
+        "padding": null,
+        "added_tokens": added_tokens,
+        "normalizer": null,
+        "pre_tokenizer": { "type": "Whitespace" },
+        "post_processor": null,
+        "decoder": null,
+        "model": { "type": "WordLevel", "vocab": vocab, "unk_token": unk }
+    })
+    .to_string()
+}
+
+// Serializes a tiny llama model with random weights and a ChatML template to the gguf format.
+fn tiny_llama() -> Result<Vec<u8>> {
+    let dev = &Device::Cpu;
+    let (dim, hidden, n_head, n_kv_head, n_layer) = (32, 64, 4, 2, 2);
+    let head_dim = dim / n_head;
+    let vocab = WORDS.len();
+    let mut tensors = vec![];
+    let mut add = |name: String, dims: &[usize]| -> Result<()> {
+        let t = Tensor::randn(0f32, 0.5, dims, dev)?;
+        tensors.push((name, QTensor::quantize(&t, GgmlDType::F32)?));
+        Ok(())
+    };
+    add("token_embd.weight".to_string(), &[vocab, dim])?;
+    add("output_norm.weight".to_string(), &[dim])?;
+    add("output.weight".to_string(), &[vocab, dim])?;
+    for i in 0..n_layer {
+        add(format!("blk.{i}.attn_q.weight"), &[dim, dim])?;
+        add(
+            format!("blk.{i}.attn_k.weight"),
+            &[n_kv_head * head_dim, dim],
+        )?;
+        add(
+            format!("blk.{i}.attn_v.weight"),
+            &[n_kv_head * head_dim, dim],
+        )?;
+        add(format!("blk.{i}.attn_output.weight"), &[dim, dim])?;
+        add(format!("blk.{i}.attn_norm.weight"), &[dim])?;
+        add(format!("blk.{i}.ffn_gate.weight"), &[hidden, dim])?;
+        add(format!("blk.{i}.ffn_up.weight"), &[hidden, dim])?;
+        add(format!("blk.{i}.ffn_down.weight"), &[dim, hidden])?;
+        add(format!("blk.{i}.ffn_norm.weight"), &[dim])?;
+    }
+    let tokens = WORDS
+        .iter()
+        .map(|w| gguf_file::Value::String(w.to_string()))
+        .collect();
+    let metadata = [
+        (
+            "general.architecture",
+            gguf_file::Value::String("llama".to_string()),
+        ),
+        (
+            "llama.attention.head_count",
+            gguf_file::Value::U32(n_head as u32),
+        ),
+        (
+            "llama.attention.head_count_kv",
+            gguf_file::Value::U32(n_kv_head as u32),
+        ),
+        ("llama.block_count", gguf_file::Value::U32(n_layer as u32)),
+        ("llama.embedding_length", gguf_file::Value::U32(dim as u32)),
+        (
+            "llama.rope.dimension_count",
+            gguf_file::Value::U32(head_dim as u32),
+        ),
+        (
+            "llama.attention.layer_norm_rms_epsilon",
+            gguf_file::Value::F32(1e-5),
+        ),
+        ("tokenizer.ggml.tokens", gguf_file::Value::Array(tokens)),
+        ("tokenizer.ggml.eos_token_id", gguf_file::Value::U32(2)),
+        (
+            "tokenizer.chat_template",
+            gguf_file::Value::String(CHATML.to_string()),
+        ),
+    ];
+    let metadata: Vec<_> = metadata.iter().map(|(k, v)| (*k, v)).collect();
+    let tensors: Vec<_> = tensors.iter().map(|(k, v)| (k.as_str(), v)).collect();
+    let mut buf = std::io::Cursor::new(Vec::new());
+    gguf_file::write(&mut buf, &metadata, &tensors)?;
+    Ok(buf.into_inner())
+}
+
+fn temp_dir(name: &str) -> Result<PathBuf> {
+    let dir = std::env::temp_dir().join(format!("candle-server-{}-{name}", std::process::id()));
+    std::fs::create_dir_all(&dir)?;
+    Ok(dir)
+}
+
+// Writes a tiny BERT model with random weights to `dir`.
+fn tiny_bert(dir: &Path) -> Result<()> {
+    let words = [
+        "[PAD]", "[UNK]", "hello", "world", "the", "cat", "sat", "on",
+    ];
+    std::fs::write(
+        dir.join("tokenizer.json"),
+        tokenizer_json(&words, &["[PAD]"], "[UNK]"),
+    )?;
+    let config = json!({
+        "vocab_size": words.len(),
+        "hidden_size": 16,
+        "num_hidden_layers": 2,
+        "num_attention_heads": 2,
+        "intermediate_size": 32,
+        "hidden_act": "gelu",
+        "hidden_dropout_prob": 0.0,
+        "max_position_embeddings": 32,
+        "type_vocab_size": 2,
+        "initializer_range": 0.02,
+        "layer_norm_eps": 1e-12,
+        "pad_token_id": 0,
+    });
+    std::fs::write(dir.join("config.json"), config.to_string())?;
+    let config: bert::Config = serde_json::from_value(config)?;
+    let varmap = VarMap::new();
+    let vb = VarBuilder::from_varmap(&varmap, DType::F32, &Device::Cpu);
+    bert::BertModel::load(vb, &config)?;
+    varmap.save(dir.join("model.safetensors"))?;
+    Ok(())
+}
+
+// Writes a tiny qwen3 model with random weights in the HuggingFace layout to `dir`.
+fn tiny_qwen3_dir(dir: &Path) -> Result<()> {
+    let dev = &Device::Cpu;
+    let (dim, hidden, n_head, n_kv_head, head_dim, n_layer) = (32, 64, 4, 2, 8, 2);
+    let vocab = WORDS.len();
+    let config = json!({
+        "model_type": "qwen3",
+        "vocab_size": vocab,
+        "hidden_size": dim,
+        "intermediate_size": hidden,
+        "num_hidden_layers": n_layer,
+        "num_attention_heads": n_head,
+        "num_key_value_heads": n_kv_head,
+        "head_dim": head_dim,
+        "attention_bias": false,
+        "max_position_embeddings": 64,
+        "sliding_window": null,
+        "max_window_layers": n_layer,
+        "tie_word_embeddings": true,
+        "rope_theta": 10000.0,
+        "rms_norm_eps": 1e-6,
+        "use_sliding_window": false,
+        "hidden_act": "silu",
+        "eos_token_id": [2, 1],
+    });
+    std::fs::write(dir.join("config.json"), config.to_string())?;
+    let tokenizer_config = json!({ "chat_template": CHATML, "eos_token": "<|im_end|>" });
+    std::fs::write(
+        dir.join("tokenizer_config.json"),
+        tokenizer_config.to_string(),
+    )?;
+    let mut tensors = std::collections::HashMap::new();
+    let mut add = |name: String, dims: &[usize]| -> Result<()> {
+        tensors.insert(name, Tensor::randn(0f32, 0.5, dims, dev)?);
+        Ok(())
+    };
+    add("model.embed_tokens.weight".to_string(), &[vocab, dim])?;
+    add("model.norm.weight".to_string(), &[dim])?;
+    for i in 0..n_layer {
+        let p = format!("model.layers.{i}");
+        add(
+            format!("{p}.self_attn.q_proj.weight"),
+            &[n_head * head_dim, dim],
+        )?;
+        add(
+            format!("{p}.self_attn.k_proj.weight"),
+            &[n_kv_head * head_dim, dim],
+        )?;
+        add(
+            format!("{p}.self_attn.v_proj.weight"),
+            &[n_kv_head * head_dim, dim],
+        )?;
+        add(
+            format!("{p}.self_attn.o_proj.weight"),
+            &[dim, n_head * head_dim],
+        )?;
+        add(format!("{p}.self_attn.q_norm.weight"), &[head_dim])?;
+        add(format!("{p}.self_attn.k_norm.weight"), &[head_dim])?;
+        add(format!("{p}.mlp.gate_proj.weight"), &[hidden, dim])?;
+        add(format!("{p}.mlp.up_proj.weight"), &[hidden, dim])?;
+        add(format!("{p}.mlp.down_proj.weight"), &[dim, hidden])?;
+        add(format!("{p}.input_layernorm.weight"), &[dim])?;
+        add(format!("{p}.post_attention_layernorm.weight"), &[dim])?;
+    }
+    candle::safetensors::save(&tensors, dir.join("model.safetensors"))?;
+    Ok(())
+}
+
+fn start_server(with_embeddings: bool) -> Result<SocketAddr> {
+    let dev = Device::Cpu;
+    let model = LoadedModel::from_gguf(&mut std::io::Cursor::new(tiny_llama()?), &dev)?;
+    assert_eq!(model.architecture, "llama");
+    assert_eq!(model.eos_token_id, Some(2));
+    let tokenizer = tokenizer_json(&WORDS, &["<|im_start|>", "<|im_end|>"], "<unk>");
+    let tokenizer = tokenizers::Tokenizer::from_bytes(tokenizer).map_err(anyhow::Error::msg)?;
+    let config = EngineConfig {
+        num_blocks: 64,
+        block_size: 4,
+        max_num_seqs: 4,
+        max_num_batched_tokens: 16,
+        max_seq_len: None,
+    };
+    let chat_template = model.chat_template.expect("no chat template in the gguf");
+    let mut server = Server::new(model.model, config, &dev, tokenizer, vec![2], "tiny-llama")?
+        .with_chat_template(chat_template)
+        .with_tool_call_format(ToolCallFormat::from_architecture("llama"))
+        .with_reasoning(true)
+        .with_max_tokens(8);
+    if with_embeddings {
+        let dir = temp_dir("bert")?;
+        tiny_bert(&dir)?;
+        server = server.with_embedding_model("tiny-bert", EmbeddingModel::load(&dir, &dev)?);
+    }
+    let listener = TcpListener::bind("127.0.0.1:0")?;
+    let addr = listener.local_addr()?;
+    std::thread::spawn(move || Arc::new(server).serve(listener));
+    Ok(addr)
+}
+
+fn request(addr: SocketAddr, method: &str, path: &str, body: Option<&Value>) -> (u16, String) {
+    let mut stream = TcpStream::connect(addr).unwrap();
+    let body = body.map_or(String::new(), |b| b.to_string());
+    write!(
+        stream,
+        "{method} {path} HTTP/1.1\r\nHost: localhost\r\nContent-Type: application/json\r\nContent-Length: {}\r\n\r\n{body}",
+        body.len()
+    )
+    .unwrap();
+    let mut response = String::new();
+    stream.read_to_string(&mut response).unwrap();
+    let (head, body) = response.split_once("\r\n\r\n").unwrap();
+    let status = head.split_whitespace().nth(1).unwrap().parse().unwrap();
+    (status, body.to_string())
+}
+
+fn post(addr: SocketAddr, path: &str, body: Value) -> (u16, Value) {
+    let (status, body) = request(addr, "POST", path, Some(&body));
+    (status, serde_json::from_str(&body).unwrap())
+}
+
+// The data of the server-sent events, the final `[DONE]` event is checked and removed.
+fn post_stream(addr: SocketAddr, path: &str, body: Value) -> Vec<Value> {
+    let (status, body) = request(addr, "POST", path, Some(&body));
+    assert_eq!(status, 200);
+    let mut events: Vec<&str> = body
+        .split("\n\n")
+        .filter_map(|e| e.strip_prefix("data: "))
+        .collect();
+    assert_eq!(events.pop(), Some("[DONE]"));
+    events
+        .iter()
+        .map(|e| serde_json::from_str(e).unwrap())
+        .collect()
+}
+
+#[test]
+fn completions() -> Result<()> {
+    let addr = start_server(false)?;
+    assert_eq!(request(addr, "GET", "/health", None), (200, "ok".into()));
+    let (status, models) = request(addr, "GET", "/v1/models", None);
+    assert_eq!(status, 200);
+    let models: Value = serde_json::from_str(&models)?;
+    assert_eq!(models["data"][0]["id"], "tiny-llama");
+
+    let body = json!({ "prompt": "hello world", "max_tokens": 5, "temperature": 0, "logprobs": 2 });
+    let (status, response) = post(addr, "/v1/completions", body.clone());
+    assert_eq!(status, 200);
+    assert_eq!(response["object"], "text_completion");
+    let usage = &response["usage"];
+    assert_eq!(usage["prompt_tokens"], 2);
+    let completion_tokens = usage["completion_tokens"].as_u64().unwrap();
+    assert!((1..=5).contains(&completion_tokens));
+    let choice = &response["choices"][0];
+    let finish_reason = choice["finish_reason"].as_str().unwrap();
+    assert!(finish_reason == "stop" || completion_tokens == 5);
+    let logprobs = &choice["logprobs"];
+    assert_eq!(
+        logprobs["tokens"].as_array().unwrap().len() as u64,
+        completion_tokens
+    );
+    assert_eq!(logprobs["top_logprobs"][0].as_object().unwrap().len(), 2);
+    let text = choice["text"].as_str().unwrap().to_string();
+
+    // The requests sent concurrently are batched and produce the same greedy completions.
+    let handles: Vec<_> = (0..4)
+        .map(|_| {
+            let body = body.clone();
+            std::thread::spawn(move || post(addr, "/v1/completions", body))
+        })
+        .collect();
+    for handle in handles {
+        let (status, response) = handle.join().unwrap();
+        assert_eq!(status, 200);
+        assert_eq!(response["choices"][0]["text"], text.as_str());
+    }
+
+    let mut body = body;
+    body["stream"] = json!(true);
+    body["stream_options"] = json!({ "include_usage": true });
+    let mut events = post_stream(addr, "/v1/completions", body);
+    let usage = events.pop().unwrap();
+    assert_eq!(usage["usage"]["completion_tokens"], completion_tokens);
+    assert_eq!(usage["choices"].as_array().unwrap().len(), 0);
+    let streamed: String = events
+        .iter()
+        .map(|e| e["choices"][0]["text"].as_str().unwrap())
+        .collect();
+    assert_eq!(streamed, text);
+    let last = events.last().unwrap();
+    assert_eq!(last["choices"][0]["finish_reason"], finish_reason);
+
+    let (status, _) = post(
+        addr,
+        "/v1/completions",
+        json!({ "prompt": "hello", "n": 2 }),
+    );
+    assert_eq!(status, 400);
+    let (status, body) = request(addr, "POST", "/v1/completions", None);
+    assert_eq!(status, 400);
+    assert!(body.contains("invalid_request_error"));
+    // The prompts with unknown tokens or that do not fit in the context are rejected before
+    // reaching the engine.
+    let (status, _) = post(
+        addr,
+        "/v1/completions",
+        json!({ "prompt": [1, WORDS.len() + 1000] }),
+    );
+    assert_eq!(status, 400);
+    let max_tokens = quantized_llama::MAX_SEQ_LEN;
+    let (status, _) = post(
+        addr,
+        "/v1/completions",
+        json!({ "prompt": "hello", "max_tokens": max_tokens }),
+    );
+    assert_eq!(status, 400);
+    assert_eq!(request(addr, "GET", "/v1/completions", None).0, 405);
+    assert_eq!(request(addr, "GET", "/unknown", None).0, 404);
+    let (status, _) = post(addr, "/v1/embeddings", json!({ "input": "hello" }));
+    assert_eq!(status, 400);
+
+    let (status, metrics) = request(addr, "GET", "/metrics", None);
+    assert_eq!(status, 200);
+    assert!(metrics.contains(r#"candle_requests_total{endpoint="/v1/completions",status="200"} 6"#));
+    assert!(metrics.contains(r#"candle_requests_total{endpoint="/v1/completions",status="400"} 4"#));
+    assert!(metrics.contains("candle_prompt_tokens_total 12"));
+    assert!(metrics.contains("candle_time_to_first_token_seconds_count 6"));
+    Ok(())
+}
+
+#[test]
+fn chat_completions() -> Result<()> {
+    let addr = start_server(false)?;
+    let body = json!({
+        "messages": [
+            { "role": "developer", "content": "the sky is blue" },
+            { "role": "user", "content": "hello" },
+        ],
+        "max_completion_tokens": 6,
+        "temperature": 0,
+        "logprobs": true,
+        "top_logprobs": 3,
+    });
+    let (status, response) = post(addr, "/v1/chat/completions", body.clone());
+    assert_eq!(status, 200);
+    assert_eq!(response["object"], "chat.completion");
+    // <|im_start|> system \n the sky is blue <|im_end|> \n <|im_start|> user hello <|im_end|>
+    // <|im_start|> assistant
+    assert_eq!(response["usage"]["prompt_tokens"], 13);
+    let completion_tokens = response["usage"]["completion_tokens"].as_u64().unwrap();
+    let choice = &response["choices"][0];
+    assert_eq!(choice["message"]["role"], "assistant");
+    let content = choice["message"]["content"].as_str().unwrap().to_string();
+    let logprobs = choice["logprobs"]["content"].as_array().unwrap();
+    assert_eq!(logprobs.len() as u64, completion_tokens);
+    assert_eq!(logprobs[0]["top_logprobs"].as_array().unwrap().len(), 3);
+
+    let mut body = body;
+    body["stream"] = json!(true);
+    body["logprobs"] = json!(false);
+    let events = post_stream(addr, "/v1/chat/completions", body.clone());
+    assert_eq!(events[0]["choices"][0]["delta"]["role"], "assistant");
+    let streamed: String = events
+        .iter()
+        .filter_map(|e| e["choices"][0]["delta"]["content"].as_str())
+        .collect();
+    assert_eq!(streamed, content);
+    let last = events.last().unwrap();
+    assert_eq!(last["object"], "chat.completion.chunk");
+    assert_eq!(last["choices"][0]["finish_reason"], choice["finish_reason"]);
+
+    // The tools are passed to the template, the random model is not expected to call them nor
+    // to generate reasoning.
+    body["tools"] = json!([{
+        "type": "function",
+        "function": { "name": "get_weather", "parameters": { "type": "object" } },
+    }]);
+    let events = post_stream(addr, "/v1/chat/completions", body);
+    let streamed: String = events
+        .iter()
+        .filter_map(|e| e["choices"][0]["delta"]["content"].as_str())
+        .collect();
+    assert_eq!(streamed, content);
+
+    let (status, _) = post(
+        addr,
+        "/v1/chat/completions",
+        json!({ "messages": [{ "role": "user", "content": 42 }] }),
+    );
+    assert_eq!(status, 400);
+    Ok(())
+}
+
+#[test]
+fn embeddings() -> Result<()> {
+    let addr = start_server(true)?;
+    let (status, models) = request(addr, "GET", "/v1/models", None);
+    assert_eq!(status, 200);
+    let models: Value = serde_json::from_str(&models)?;
+    assert_eq!(models["data"][1]["id"], "tiny-bert");
+
+    let embed = |input: Value, dimensions: Option<usize>| {
+        let (status, response) = post(
+            addr,
+            "/v1/embeddings",
+            json!({ "input": input, "dimensions": dimensions }),
+        );
+        assert_eq!(status, 200);
+        response
+    };
+    let response = embed(json!(["hello", "the cat sat on the world"]), None);
+    assert_eq!(response["usage"]["prompt_tokens"], 7);
+    assert_eq!(response["model"], "tiny-bert");
+    let embeddings: Vec<Vec<f32>> = response["data"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|e| serde_json::from_value(e["embedding"].clone()).unwrap())
+        .collect();
+    assert_eq!(embeddings.len(), 2);
+    for e in embeddings.iter() {
+        assert_eq!(e.len(), 16);
+        let norm = e.iter().map(|v| v * v).sum::<f32>().sqrt();
+        assert!((norm - 1.).abs() < 1e-4, "{norm}");
+    }
+    // The padding added when batching does not change the embeddings.
+    let single = embed(json!("hello"), None);
+    let single: Vec<f32> = serde_json::from_value(single["data"][0]["embedding"].clone())?;
+    for (a, b) in single.iter().zip(embeddings[0].iter()) {
+        assert!((a - b).abs() < 1e-4, "{single:?} {:?}", embeddings[0]);
+    }
+
+    let truncated = embed(json!("hello"), Some(4));
+    let truncated: Vec<f32> = serde_json::from_value(truncated["data"][0]["embedding"].clone())?;
+    assert_eq!(truncated.len(), 4);
+    let norm = truncated.iter().map(|v| v * v).sum::<f32>().sqrt();
+    assert!((norm - 1.).abs() < 1e-4, "{norm}");
+
+    let (status, response) = post(
+        addr,
+        "/v1/embeddings",
+        json!({ "input": "hello", "dimensions": 17 }),
+    );
+    assert_eq!(status, 400);
+    assert_eq!(response["error"]["type"], "invalid_request_error");
+    Ok(())
+}
+
+#[test]
+fn hf_dir() -> Result<()> {
+    let dev = Device::Cpu;
+    let dir = temp_dir("qwen3")?;
+    tiny_qwen3_dir(&dir)?;
+    let model = LoadedModel::load(&dir, &dev)?;
+    assert_eq!(model.architecture, "qwen3");
+    assert_eq!(model.eos_token_id, Some(2));
+    let chat_template = model
+        .chat_template
+        .expect("no chat template in the tokenizer config");
+    let tokenizer = tokenizer_json(&WORDS, &["<|im_start|>", "<|im_end|>"], "<unk>");
+    let tokenizer = tokenizers::Tokenizer::from_bytes(tokenizer).map_err(anyhow::Error::msg)?;
+    let config = EngineConfig {
+        num_blocks: 64,
+        block_size: 4,
+        max_num_seqs: 4,
+        max_num_batched_tokens: 16,
+        max_seq_len: None,
+    };
+    let server = Server::new(model.model, config, &dev, tokenizer, vec![2], "tiny-qwen3")?
+        .with_chat_template(chat_template)
+        .with_max_tokens(4);
+    let listener = TcpListener::bind("127.0.0.1:0")?;
+    let addr = listener.local_addr()?;
+    std::thread::spawn(move || Arc::new(server).serve(listener));
+    let body = json!({ "messages": [{ "role": "user", "content": "hello world" }] });
+    let (status, response) = post(addr, "/v1/chat/completions", body);
+    assert_eq!(status, 200, "{response}");
+    assert_eq!(response["model"], "tiny-qwen3");
+    assert!(response["usage"]["completion_tokens"].as_u64().unwrap() <= 4);
+
+    // An invalid template is reported rather than ignored.
+    let tokenizer_config = json!({ "chat_template": "{% for %}" });
+    std::fs::write(
+        dir.join("tokenizer_config.json"),
+        tokenizer_config.to_string(),
+    )?;
+    assert!(LoadedModel::load(&dir, &dev).is_err());
+    Ok(())
+}
+
+// A model generating the token that follows the last input token in `next`.
+struct ScriptedModel {
+    next: HashMap<u32, u32>,
+    vocab_size: usize,
+}
+
+impl PagedModel for ScriptedModel {
+    fn max_seq_len(&self) -> usize {
+        4096
+    }
+
+    fn paged_kv_caches(&self, _: usize, _: usize) -> candle::Result<Vec<PagedKvCache>> {
+        Ok(vec![])
+    }
+
+    fn forward_paged(
+        &self,
+        input: &Tensor,
+        _: &mut [PagedKvCache],
+        metadata: &PagedAttentionMetadata,
+    ) -> candle::Result<Tensor> {
+        let last = metadata.last_tokens(input)?.to_vec1::<u32>()?;
+        let mut logits = vec![0f32; last.len() * self.vocab_size];
+        for (i, token) in last.iter().enumerate() {
+            let next = self.next.get(token).copied().unwrap_or(2);
+            logits[i * self.vocab_size + next as usize] = 10.;
+        }
+        Tensor::from_vec(logits, (last.len(), self.vocab_size), input.device())
+    }
+}
+
+#[test]
+fn special_tool_call_tags() -> Result<()> {
+    let dev = Device::Cpu;
+    let call_list = r#"[{"name":"get_weather","arguments":{"city":"Paris"}}]"#;
+    let call_object = r#"{"name":"get_weather","parameters":{"city":"Paris"}}"#;
+    let words = [
+        "<unk>",
+        "<|im_start|>",
+        "<|im_end|>",
+        "user",
+        "assistant",
+        "hello",
+        "[TOOL_CALLS]",
+        "<|tool_call|>",
+        "<|python_tag|>",
+        call_list,
+        call_object,
+    ];
+    let special = [
+        "<|im_start|>",
+        "<|im_end|>",
+        "[TOOL_CALLS]",
+        "<|tool_call|>",
+        "<|python_tag|>",
+    ];
+    let id = |w: &str| words.iter().position(|v| *v == w).unwrap() as u32;
+    for (format, tag, call) in [
+        (ToolCallFormat::Mistral, "[TOOL_CALLS]", call_list),
+        (ToolCallFormat::Granite, "<|tool_call|>", call_list),
+        (ToolCallFormat::Llama3Json, "<|python_tag|>", call_object),
+    ] {
+        let tokenizer = tokenizer_json(&words, &special, "<unk>");
+        let tokenizer = tokenizers::Tokenizer::from_bytes(tokenizer).map_err(anyhow::Error::msg)?;
+        let model = ScriptedModel {
+            next: HashMap::from([(id("assistant"), id(tag)), (id(tag), id(call))]),
+            vocab_size: words.len(),
+        };
+        let config = EngineConfig {
+            num_blocks: 16,
+            block_size: 4,
+            max_num_seqs: 2,
+            max_num_batched_tokens: 16,
+            max_seq_len: None,
+        };
+        let server = Server::new(model, config, &dev, tokenizer, vec![2], "scripted")?
+            .with_chat_template(ChatTemplate::new(CHATML)?)
+            .with_tool_call_format(Some(format));
+        let listener = TcpListener::bind("127.0.0.1:0")?;
+        let addr = listener.local_addr()?;
+        std::thread::spawn(move || Arc::new(server).serve(listener));
+
+        let body = json!({
+            "messages": [{ "role": "user", "content": "hello" }],
+            "temperature": 0,
+            "tools": [{
+                "type": "function",
+                "function": { "name": "get_weather", "parameters": { "type": "object" } },
+            }],
+        });
+        let (status, response) = post(addr, "/v1/chat/completions", body.clone());
+        assert_eq!(status, 200, "{response}");
+        let choice = &response["choices"][0];
+        assert_eq!(
+            choice["finish_reason"], "tool_calls",
+            "{format:?} {response}"
+        );
+        let calls = choice["message"]["tool_calls"].as_array().unwrap();
+        assert_eq!(calls.len(), 1, "{format:?} {response}");
+        assert_eq!(calls[0]["function"]["name"], "get_weather");
+        let arguments: Value =
+            serde_json::from_str(calls[0]["function"]["arguments"].as_str().unwrap())?;
+        assert_eq!(arguments, json!({ "city": "Paris" }));
+
+        let mut body = body;
+        body["stream"] = json!(true);
+        let events = post_stream(addr, "/v1/chat/completions", body);
+        let names: Vec<&str> = events
+            .iter()
+            .filter_map(|e| e["choices"][0]["delta"]["tool_calls"][0]["function"]["name"].as_str())
+            .collect();
+        assert_eq!(names, ["get_weather"], "{format:?}");
+        let content: String = events
+            .iter()
+            .filter_map(|e| e["choices"][0]["delta"]["content"].as_str())
+            .collect();
+        assert_eq!(content, "", "{format:?}");
+    }
+    Ok(())
+}
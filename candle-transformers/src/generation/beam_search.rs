@@ -0,0 +1,636 @@
+//! Beam search, diverse beam search and contrastive search.
+//!
+//! [`BeamSearch`] keeps the `num_beams` most likely partial sequences at each step and returns
+//! the best finished ones, the kv-caches of the model are reordered along the batch dimension so
+//! that each beam continues from the cache of its parent. Splitting the beams in groups and
+//! penalizing the tokens picked by the previous groups gives the diverse beam search from
+//! [Vijayakumar et al.](https://arxiv.org/abs/1610.02424). [`ContrastiveSearch`] implements the
+//! decoding method from [Su et al.](https://arxiv.org/abs/2202.06417) that picks among the most
+//! likely tokens the one whose hidden state is the least similar to the previous ones.
+//!
+//! Encoder-decoder models are wrapped in a [`Seq2Seq`] together with the output of their encoder.
+//!
+//! ```ignore
+//! use candle_transformers::generation::beam_search::{BeamSearch, BeamSearchConfig, Seq2Seq};
+//!
+//! let encoder_output = model.encoder().forward(&input_ids, 0)?;
+//! let config = BeamSearchConfig {
+//!     num_beams: 5,
+//!     num_return_sequences: 3,
+//!     eos_tokens: vec![config.eos_token_id],
+//!     ..Default::default()
+//! };
+//! let mut beam_search = BeamSearch::new(config)?;
+//! let mut seq2seq = Seq2Seq::new(&mut model, encoder_output);
+//! let hypotheses = beam_search.generate(&mut seq2seq, &[config.decoder_start_token_id], &device)?;
+//! ```
+use super::logprobs::log_softmax_slice;
+use super::processors::{apply_processors, Processor};
+use crate::models::{marian, quantized_t5, t5, trocr, whisper};
+use candle::{DType, Device, IndexOp, Result, Tensor, D};
+
+/// A model with a kv-cache that can be reordered along the batch dimension.
+pub trait BeamSearchModel {
+    /// Processes the `(b_sz, seq_len)` tokens, the first `index_pos` tokens of each sequence
+    /// having already been processed. Returns the logits for the last position with shape
+    /// `(b_sz, vocab_size)` and the hidden states used to compute the logits for the new
+    /// positions with shape `(b_sz, seq_len - index_pos, hidden_size)`.
+    fn decode(&mut self, tokens: &Tensor, index_pos: usize) -> Result<(Tensor, Tensor)>;
+
+    /// Reorders the kv-cache so that the new batch element `i` continues the sequence of the
+    /// batch element `indices[i]`, the batch size changes when `indices` has a different length.
+    fn reorder_kv_cache(&mut self, indices: &Tensor) -> Result<()>;
+}
+
+/// The decoder of an encoder-decoder model.
+pub trait EncoderDecoderModel {
+    /// Same as [`BeamSearchModel::decode`], `encoder_output` has the same batch size as
+    /// `tokens`.
+    fn decode(
+        &mut self,
+        tokens: &Tensor,
+        encoder_output: &Tensor,
+        index_pos: usize,
+    ) -> Result<(Tensor, Tensor)>;
+
+    fn reorder_kv_cache(&mut self, indices: &Tensor) -> Result<()>;
+
+    fn reset_kv_cache(&mut self);
+}
+
+// Returns the tokens that are not in the kv-cache yet.
+fn new_tokens(tokens: &Tensor, index_pos: usize) -> Result<Tensor> {
+    let seq_len = tokens.dim(1)?;
+    tokens.narrow(1, index_pos, seq_len - index_pos)
+}
+
+fn last_position(hidden: &Tensor) -> Result<Tensor> {
+    hidden.i((.., hidden.dim(1)? - 1))
+}
+
+impl EncoderDecoderModel for marian::MTModel {
+    fn decode(
+        &mut self,
+        tokens: &Tensor,
+        encoder_output: &Tensor,
+        index_pos: usize,
+    ) -> Result<(Tensor, Tensor)> {
+        let tokens = new_tokens(tokens, index_pos)?;
+        let hidden = self.decode_hidden(&tokens, encoder_output, index_pos)?;
+        let logits = self.logits(&last_position(&hidden)?)?;
+        Ok((logits, hidden))
+    }
+
+    fn reorder_kv_cache(&mut self, indices: &Tensor) -> Result<()> {
+        self.reorder_kv_cache(indices)
+    }
+
+    fn reset_kv_cache(&mut self) {
+        self.reset_kv_cache()
+    }
+}
+
+// The t5 models only process the new tokens so they have to be loaded with `use_cache` set.
+impl EncoderDecoderModel for t5::T5ForConditionalGeneration {
+    fn decode(
+        &mut self,
+        tokens: &Tensor,
+        encoder_output: &Tensor,
+        index_pos: usize,
+    ) -> Result<(Tensor, Tensor)> {
+        let tokens = new_tokens(tokens, index_pos)?;
+        let hidden = self.decode_hidden(&tokens, encoder_output)?;
+        let logits = self.logits(&last_position(&hidden)?)?;
+        Ok((logits, hidden))
+    }
+
+    fn reorder_kv_cache(&mut self, indices: &Tensor) -> Result<()> {
+        self.reorder_kv_cache(indices)
+    }
+
+    fn reset_kv_cache(&mut self) {
+        self.clear_kv_cache()
+    }
+}
+
+impl EncoderDecoderModel for quantized_t5::T5ForConditionalGeneration {
+    fn decode(
+        &mut self,
+        tokens: &Tensor,
+        encoder_output: &Tensor,
+        index_pos: usize,
+    ) -> Result<(Tensor, Tensor)> {
+        let tokens = new_tokens(tokens, index_pos)?;
+        let hidden = self.decode_hidden(&tokens, encoder_output)?;
+        let logits = self.logits(&last_position(&hidden)?)?;
+        Ok((logits, hidden))
+    }
+
+    fn reorder_kv_cache(&mut self, indices: &Tensor) -> Result<()> {
+        self.reorder_kv_cache(indices)
+    }
+
+    fn reset_kv_cache(&mut self) {
+        self.clear_kv_cache()
+    }
+}
+
+impl EncoderDecoderModel for trocr::TrOCRModel {
+    fn decode(
+        &mut self,
+        tokens: &Tensor,
+        encoder_output: &Tensor,
+        index_pos: usize,
+    ) -> Result<(Tensor, Tensor)> {
+        let tokens = new_tokens(tokens, index_pos)?;
+        let hidden = self.decode_hidden(&tokens, encoder_output, index_pos)?;
+        let logits = self.logits(&last_position(&hidden)?)?;
+        Ok((logits, hidden))
+    }
+
+    fn reorder_kv_cache(&mut self, indices: &Tensor) -> Result<()> {
+        self.reorder_kv_cache(indices)
+    }
+
+    fn reset_kv_cache(&mut self) {
+        self.reset_kv_cache()
+    }
+}
+
+// The whisper decoder only caches the cross-attention keys and values, the whole sequence is
+// processed at each step.
+impl EncoderDecoderModel for whisper::model::Whisper {
+    fn decode(
+        &mut self,
+        tokens: &Tensor,
+        encoder_output: &Tensor,
+        index_pos: usize,
+    ) -> Result<(Tensor, Tensor)> {
+        let hidden = self
+            .decoder
+            .forward(tokens, encoder_output, index_pos == 0)?;
+        let logits = self
+            .decoder
+            .final_linear(&last_position(&hidden)?.unsqueeze(1)?)?
+            .squeeze(1)?;
+        Ok((logits, new_tokens(&hidden, index_pos)?))
+    }
+
+    fn reorder_kv_cache(&mut self, indices: &Tensor) -> Result<()> {
+        self.decoder.reorder_kv_cache(indices)
+    }
+
+    fn reset_kv_cache(&mut self) {
+        self.reset_kv_cache()
+    }
+}
+
+impl EncoderDecoderModel for whisper::quantized_model::Whisper {
+    fn decode(
+        &mut self,
+        tokens: &Tensor,
+        encoder_output: &Tensor,
+        index_pos: usize,
+    ) -> Result<(Tensor, Tensor)> {
+        let hidden = self
+            .decoder
+            .forward(tokens, encoder_output, index_pos == 0)?;
+        let logits = self
+            .decoder
+            .final_linear(&last_position(&hidden)?.unsqueeze(1)?)?
+            .squeeze(1)?;
+        Ok((logits, new_tokens(&hidden, index_pos)?))
+    }
+
+    fn reorder_kv_cache(&mut self, indices: &Tensor) -> Result<()> {
+        self.decoder.reorder_kv_cache(indices)
+    }
+
+    fn reset_kv_cache(&mut self) {
+        self.reset_kv_cache()
+    }
+}
+
+/// An encoder-decoder model together with the output of its encoder.
+pub struct Seq2Seq<'a, M: EncoderDecoderModel> {
+    model: &'a mut M,
+    encoder_output: Tensor,
+}
+
+impl<'a, M: EncoderDecoderModel> Seq2Seq<'a, M> {
+    /// Resets the kv-cache of the model, `encoder_output` is the encoder output for a single
+    /// input, i.e. with a batch size of one.
+    pub fn new(model: &'a mut M, encoder_output: Tensor) -> Self {
+        model.reset_kv_cache();
+        Self {
+            model,
+            encoder_output,
+        }
+    }
+
+    pub fn model(&mut self) -> &mut M {
+        self.model
+    }
+}
+
+impl<M: EncoderDecoderModel> BeamSearchModel for Seq2Seq<'_, M> {
+    fn decode(&mut self, tokens: &Tensor, index_pos: usize) -> Result<(Tensor, Tensor)> {
+        self.model.decode(tokens, &self.encoder_output, index_pos)
+    }
+
+    fn reorder_kv_cache(&mut self, indices: &Tensor) -> Result<()> {
+        self.encoder_output = self.encoder_output.index_select(indices, 0)?;
+        self.model.reorder_kv_cache(indices)
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct BeamSearchConfig {
+    pub num_beams: usize,
+    /// The beams are split in this number of groups, each group runs its own beam search.
+    pub num_beam_groups: usize,
+    /// Penalty subtracted from the log-probability of a token for each beam of the previous
+    /// groups that picked this token at the same step, only used with multiple groups.
+    pub diversity_penalty: f64,
+    /// The score of a hypothesis is its log-probability divided by `len ^ length_penalty`,
+    /// `len` being the number of generated tokens. Positive values favor longer sequences.
+    pub length_penalty: f64,
+    /// Stop a group as soon as it has `num_beams / num_beam_groups` finished hypotheses rather
+    /// than when none of its running beams can beat the finished ones.
+    pub early_stopping: bool,
+    pub num_return_sequences: usize,
+    pub max_new_tokens: usize,
+    pub eos_tokens: Vec<u32>,
+}
+
+impl Default for BeamSearchConfig {
+    fn default() -> Self {
+        Self {
+            num_beams: 4,
+            num_beam_groups: 1,
+            diversity_penalty: 0.,
+            length_penalty: 1.,
+            early_stopping: false,
+            num_return_sequences: 1,
+            max_new_tokens: 256,
+            eos_tokens: vec![],
+        }
+    }
+}
+
+/// A sequence returned by the beam search.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Hypothesis {
+    /// The generated tokens, excluding the prompt and the end-of-sequence token.
+    pub tokens: Vec<u32>,
+    /// The log-probability of the generated tokens including the end-of-sequence token, the
+    /// diversity penalties are included.
+    pub sum_logprobs: f64,
+    /// The length normalized log-probability used to rank the hypotheses.
+    pub score: f64,
+    /// Whether the sequence ended with an end-of-sequence token rather than by reaching the
+    /// maximum length.
+    pub finished: bool,
+}
+
+struct Beam {
+    // The prompt followed by the generated tokens.
+    tokens: Vec<u32>,
+    sum_logprobs: f64,
+    // The row of the last model output for this beam.
+    row: usize,
+}
+
+struct BeamGroup {
+    beams: Vec<Beam>,
+    // The best finished hypotheses, sorted by decreasing score.
+    hypotheses: Vec<Hypothesis>,
+    done: bool,
+}
+
+impl BeamGroup {
+    fn add(&mut self, hypothesis: Hypothesis, capacity: usize) {
+        let pos = self
+            .hypotheses
+            .partition_point(|h| h.score >= hypothesis.score);
+        self.hypotheses.insert(pos, hypothesis);
+        self.hypotheses.truncate(capacity)
+    }
+}
+
+/// Beam search decoding, see the [module documentation](self).
+pub struct BeamSearch {
+    config: BeamSearchConfig,
+    processors: Vec<Box<dyn Processor>>,
+}
+
+impl BeamSearch {
+    pub fn new(config: BeamSearchConfig) -> Result<Self> {
+        if config.num_beams == 0 || config.num_beam_groups == 0 {
+            candle::bail!("the number of beams and beam groups has to be positive")
+        }
+        if !config.num_beams.is_multiple_of(config.num_beam_groups) {
+            candle::bail!(
+                "the number of beams {} is not divisible by the number of groups {}",
+                config.num_beams,
+                config.num_beam_groups
+            )
+        }
+        if config.num_return_sequences == 0 || config.num_return_sequences > config.num_beams {
+            candle::bail!(
+                "cannot return {} sequences with {} beams",
+                config.num_return_sequences,
+                config.num_beams
+            )
+        }
+        Ok(Self {
+            config,
+            processors: vec![],
+        })
+    }
+
+    /// Adds a processor applied to the logits of each beam before the log-softmax, e.g. a
+    /// repetition penalty or a grammar constraint. The processors are shared by all the beams.
+    pub fn with<P: Processor + 'static>(mut self, processor: P) -> Self {
+        self.push(processor);
+        self
+    }
+
+    pub fn push<P: Processor + 'static>(&mut self, processor: P) {
+        self.processors.push(Box::new(processor))
+    }
+
+    pub fn config(&self) -> &BeamSearchConfig {
+        &self.config
+    }
+
+    fn score(&self, sum_logprobs: f64, len: usize) -> f64 {
+        sum_logprobs / (len as f64).powf(self.config.length_penalty)
+    }
+
+    /// Runs the beam search after `prompt` and returns the `num_return_sequences` best
+    /// hypotheses sorted by decreasing score.
+    pub fn generate<M: BeamSearchModel>(
+        &mut self,
+        model: &mut M,
+        prompt: &[u32],
+        device: &Device,
+    ) -> Result<Vec<Hypothesis>> {
+        if prompt.is_empty() {
+            candle::bail!("beam search requires a non-empty prompt")
+        }
+        let prompt_len = prompt.len();
+        let group_size = self.config.num_beams / self.config.num_beam_groups;
+        let mut groups: Vec<BeamGroup> = (0..self.config.num_beam_groups)
+            .map(|_| BeamGroup {
+                beams: vec![Beam {
+                    tokens: prompt.to_vec(),
+                    sum_logprobs: 0.,
+                    row: 0,
+                }],
+                hypotheses: vec![],
+                done: false,
+            })
+            .collect();
+        let input = Tensor::new(prompt, device)?.unsqueeze(0)?;
+        let (mut logits, _) = model.decode(&input, 0)?;
+        for step in 0..self.config.max_new_tokens {
+            let logits_rows = logits.to_dtype(DType::F32)?.to_vec2::<f32>()?;
+            let vocab_size = logits_rows.first().map_or(0, |l| l.len());
+            // The number of beams of the previous groups that picked each token at this step.
+            let mut counts = vec![0usize; vocab_size];
+            for group in groups.iter_mut().filter(|g| !g.done) {
+                let mut candidates = vec![];
+                for (beam_idx, beam) in group.beams.iter().enumerate() {
+                    let mut logits = logits_rows[beam.row].clone();
+                    apply_processors(&mut self.processors, &mut logits, &beam.tokens, prompt_len)?;
+                    for (token, logprob) in log_softmax_slice(&logits).into_iter().enumerate() {
+                        if logprob == f32::NEG_INFINITY {
+                            continue;
+                        }
+                        let penalty = self.config.diversity_penalty * counts[token] as f64;
+                        let sum_logprobs = beam.sum_logprobs + logprob as f64 - penalty;
+                        candidates.push((sum_logprobs, beam_idx, token as u32))
+                    }
+                }
+                // Keep enough candidates to fill the group even if some of them end the sequence.
+                let num_candidates = usize::min(2 * group_size, candidates.len());
+                if num_candidates < candidates.len() {
+                    candidates.select_nth_unstable_by(num_candidates, |a, b| b.0.total_cmp(&a.0));
+                    candidates.truncate(num_candidates);
+                }
+                candidates.sort_by(|a, b| b.0.total_cmp(&a.0));
+
+                let mut next_beams = vec![];
+                for (rank, (sum_logprobs, beam_idx, token)) in candidates.into_iter().enumerate() {
+                    let beam = &group.beams[beam_idx];
+                    if self.config.eos_tokens.contains(&token) {
+                        // End-of-sequence tokens ranked below the beams are not better than the
+                        // continuations.
+                        if rank < group_size {
+                            let hypothesis = Hypothesis {
+                                tokens: beam.tokens[prompt_len..].to_vec(),
+                                sum_logprobs,
+                                score: self.score(sum_logprobs, step + 1),
+                                finished: true,
+                            };
+                            group.add(hypothesis, group_size)
+                        }
+                    } else {
+                        let mut tokens = beam.tokens.clone();
+                        tokens.push(token);
+                        next_beams.push(Beam {
+                            tokens,
+                            sum_logprobs,
+                            row: beam.row,
+                        });
+                        if next_beams.len() == group_size {
+                            break;
+                        }
+                    }
+                }
+                for beam in next_beams.iter() {
+                    counts[beam.tokens[beam.tokens.len() - 1] as usize] += 1
+                }
+
+                group.done = match next_beams.first() {
+                    None => true,
+                    Some(_) if group.hypotheses.len() < group_size => false,
+                    Some(_) if self.config.early_stopping => true,
+                    Some(best) => {
+                        let best_score = self.score(best.sum_logprobs, step + 1);
+                        let worst_score = group.hypotheses[group_size - 1].score;
+                        best_score <= worst_score
+                    }
+                };
+                group.beams = if group.done { vec![] } else { next_beams };
+            }
+
+            if groups.iter().all(|g| g.done) || step + 1 == self.config.max_new_tokens {
+                break;
+            }
+            let mut rows = vec![];
+            let mut tokens = vec![];
+            for beam in groups.iter_mut().flat_map(|g| g.beams.iter_mut()) {
+                rows.push(beam.row as u32);
+                tokens.extend_from_slice(&beam.tokens);
+                beam.row = rows.len() - 1;
+            }
+            model.reorder_kv_cache(&Tensor::new(rows.as_slice(), device)?)?;
+            let seq_len = prompt_len + step + 1;
+            let tokens = Tensor::from_vec(tokens, (rows.len(), seq_len), device)?;
+            (logits, _) = model.decode(&tokens, seq_len - 1)?;
+        }
+
+        // The beams that reached the maximum length compete with the finished hypotheses.
+        let mut hypotheses = vec![];
+        for mut group in groups.into_iter() {
+            for beam in std::mem::take(&mut group.beams) {
+                let len = beam.tokens.len() - prompt_len;
+                let hypothesis = Hypothesis {
+                    tokens: beam.tokens[prompt_len..].to_vec(),
+                    sum_logprobs: beam.sum_logprobs,
+                    score: self.score(beam.sum_logprobs, len),
+                    finished: false,
+                };
+                group.add(hypothesis, group_size)
+            }
+            hypotheses.extend(group.hypotheses)
+        }
+        hypotheses.sort_by(|a, b| b.score.total_cmp(&a.score));
+        hypotheses.truncate(self.config.num_return_sequences);
+        Ok(hypotheses)
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct ContrastiveSearchConfig {
+    /// The number of most likely tokens considered at each step.
+    pub top_k: usize,
+    /// The weight of the degeneration penalty, i.e. the maximum cosine similarity between the
+    /// hidden state of a candidate and the ones of the previous tokens. Zero gives greedy
+    /// decoding.
+    pub penalty_alpha: f64,
+    pub max_new_tokens: usize,
+    pub eos_tokens: Vec<u32>,
+}
+
+impl Default for ContrastiveSearchConfig {
+    fn default() -> Self {
+        Self {
+            top_k: 4,
+            penalty_alpha: 0.6,
+            max_new_tokens: 256,
+            eos_tokens: vec![],
+        }
+    }
+}
+
+/// Contrastive search decoding, see the [module documentation](self).
+pub struct ContrastiveSearch {
+    config: ContrastiveSearchConfig,
+    processors: Vec<Box<dyn Processor>>,
+}
+
+fn normalize(xs: &Tensor) -> Result<Tensor> {
+    let norm = xs.sqr()?.sum_keepdim(D::Minus1)?.sqrt()?;
+    xs.broadcast_div(&(norm + 1e-8)?)
+}
+
+impl ContrastiveSearch {
+    pub fn new(config: ContrastiveSearchConfig) -> Result<Self> {
+        if config.top_k == 0 {
+            candle::bail!("contrastive search requires a positive top_k")
+        }
+        Ok(Self {
+            config,
+            processors: vec![],
+        })
+    }
+
+    /// Adds a processor applied to the logits before picking the candidate tokens.
+    pub fn with<P: Processor + 'static>(mut self, processor: P) -> Self {
+        self.push(processor);
+        self
+    }
+
+    pub fn push<P: Processor + 'static>(&mut self, processor: P) {
+        self.processors.push(Box::new(processor))
+    }
+
+    pub fn config(&self) -> &ContrastiveSearchConfig {
+        &self.config
+    }
+
+    /// Generates tokens after `prompt` and returns them, excluding the prompt and the
+    /// end-of-sequence token. Each step runs the model on the `top_k` candidates at once.
+    pub fn generate<M: BeamSearchModel>(
+        &mut self,
+        model: &mut M,
+        prompt: &[u32],
+        device: &Device,
+    ) -> Result<Vec<u32>> {
+        if prompt.is_empty() {
+            candle::bail!("contrastive search requires a non-empty prompt")
+        }
+        let prompt_len = prompt.len();
+        let mut tokens = prompt.to_vec();
+        let input = Tensor::new(prompt, device)?.unsqueeze(0)?;
+        let (logits, hidden) = model.decode(&input, 0)?;
+        // The normalized hidden states of the previous tokens, `(seq_len, hidden_size)`.
+        let mut context = normalize(&hidden.squeeze(0)?.to_dtype(DType::F32)?)?;
+        let mut logits = logits.squeeze(0)?.to_dtype(DType::F32)?.to_vec1::<f32>()?;
+        let alpha = self.config.penalty_alpha;
+        for _ in 0..self.config.max_new_tokens {
+            apply_processors(&mut self.processors, &mut logits, &tokens, prompt_len)?;
+            let probs: Vec<f32> = log_softmax_slice(&logits)
+                .into_iter()
+                .map(f32::exp)
+                .collect();
+            let mut candidates: Vec<u32> = (0..probs.len() as u32)
+                .filter(|&t| probs[t as usize] > 0.)
+                .collect();
+            if candidates.is_empty() {
+                candle::bail!("all the tokens have been filtered out by the logits processors")
+            }
+            candidates.sort_by(|&a, &b| probs[b as usize].total_cmp(&probs[a as usize]));
+            candidates.truncate(self.config.top_k);
+            let k = candidates.len();
+
+            // Run the model on all the candidates to get their hidden states.
+            let seq_len = tokens.len();
+            model.reorder_kv_cache(&Tensor::zeros(k, DType::U32, device)?)?;
+            let input: Vec<u32> = candidates
+                .iter()
+                .flat_map(|&c| tokens.iter().copied().chain(std::iter::once(c)))
+                .collect();
+            let input = Tensor::from_vec(input, (k, seq_len + 1), device)?;
+            let (next_logits, next_hidden) = model.decode(&input, seq_len)?;
+            let next_hidden = normalize(&next_hidden.squeeze(1)?.to_dtype(DType::F32)?)?;
+            let similarity = next_hidden
+                .matmul(&context.t()?)?
+                .max(D::Minus1)?
+                .to_vec1::<f32>()?;
+            let best = (0..k)
+                .map(|i| {
+                    let p = probs[candidates[i] as usize] as f64;
+                    ((1. - alpha) * p - alpha * similarity[i] as f64, i)
+                })
+                .max_by(|a, b| a.0.total_cmp(&b.0))
+                .map_or(0, |(_, i)| i);
+            let token = candidates[best];
+            if self.config.eos_tokens.contains(&token) {
+                break;
+            }
+            let best_tensor = Tensor::new(&[best as u32], device)?;
+            model.reorder_kv_cache(&best_tensor)?;
+            tokens.push(token);
+            context = Tensor::cat(&[&context, &next_hidden.i(best..best + 1)?], 0)?;
+            logits = next_logits
+                .i(best)?
+                .to_dtype(DType::F32)?
+                .to_vec1::<f32>()?;
+        }
+        Ok(tokens[prompt_len..].to_vec())
+    }
+}
@@ -0,0 +1,773 @@
+//! Context-free grammars, a GBNF parser and an incremental recognizer.
+//!
+//! The grammars use the same representation as the llama.cpp grammar engine: each rule is a set
+//! of alternatives, each alternative a sequence of character classes and references to other
+//! rules. The recognizer keeps the set of parse stacks that are compatible with the characters
+//! seen so far, the top of each stack is the next character class to match.
+use candle::Result;
+use std::collections::HashMap;
+
+const MAX_CHAR: u32 = 0x10FFFF;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) enum Element {
+    /// A character in one of the inclusive ranges, or in none of them when negated.
+    Chars {
+        ranges: Vec<(u32, u32)>,
+        negated: bool,
+    },
+    Rule(usize),
+}
+
+impl Element {
+    pub(crate) fn char(c: char) -> Self {
+        Self::Chars {
+            ranges: vec![(c as u32, c as u32)],
+            negated: false,
+        }
+    }
+
+    pub(crate) fn class(ranges: Vec<(u32, u32)>, negated: bool) -> Self {
+        Self::Chars { ranges, negated }
+    }
+
+    pub(crate) fn any() -> Self {
+        Self::class(vec![], true)
+    }
+
+    pub(crate) fn literal(s: &str) -> Vec<Self> {
+        s.chars().map(Self::char).collect()
+    }
+
+    fn matches(&self, c: u32) -> bool {
+        match self {
+            Self::Chars { ranges, negated } => {
+                ranges.iter().any(|&(lo, hi)| lo <= c && c <= hi) != *negated
+            }
+            Self::Rule(_) => false,
+        }
+    }
+
+    // Whether some character in `lo..=hi` can match, this is used to prune partial utf-8
+    // sequences.
+    fn intersects(&self, lo: u32, hi: u32) -> bool {
+        match self {
+            Self::Chars { ranges, negated } => {
+                if *negated {
+                    !ranges.iter().any(|&(l, h)| l <= lo && hi <= h)
+                } else {
+                    ranges.iter().any(|&(l, h)| l <= hi && lo <= h)
+                }
+            }
+            Self::Rule(_) => false,
+        }
+    }
+}
+
+/// Returns the ranges covering all the characters that are not in `ranges`.
+pub(crate) fn complement(ranges: &[(u32, u32)]) -> Vec<(u32, u32)> {
+    let mut ranges = ranges.to_vec();
+    ranges.sort();
+    let mut out = vec![];
+    let mut next = 0u32;
+    for (lo, hi) in ranges {
+        if lo > next {
+            out.push((next, lo - 1))
+        }
+        next = next.max(hi.saturating_add(1));
+    }
+    if next <= MAX_CHAR {
+        out.push((next, MAX_CHAR))
+    }
+    out
+}
+
+type Alternative = Vec<Element>;
+
+/// A context-free grammar over unicode characters.
+///
+/// Grammars can be written in the GBNF format used by llama.cpp, or compiled from a regular
+/// expression or a JSON schema. Left-recursive rules are not supported.
+#[derive(Debug, Clone)]
+pub struct Grammar {
+    rules: Vec<Vec<Alternative>>,
+    names: Vec<String>,
+    root: usize,
+}
+
+/// Incrementally builds the rules of a grammar.
+#[derive(Debug, Default)]
+pub(crate) struct GrammarBuilder {
+    rules: Vec<Option<Vec<Alternative>>>,
+    names: Vec<String>,
+    by_name: HashMap<String, usize>,
+}
+
+impl GrammarBuilder {
+    pub(crate) fn new() -> Self {
+        Self::default()
+    }
+
+    /// Returns the id of the rule with the given name, declaring it if necessary.
+    pub(crate) fn rule_id(&mut self, name: &str) -> usize {
+        if let Some(&id) = self.by_name.get(name) {
+            return id;
+        }
+        let id = self.rules.len();
+        self.rules.push(None);
+        self.names.push(name.to_string());
+        self.by_name.insert(name.to_string(), id);
+        id
+    }
+
+    /// Declares a new rule with a name derived from `base`.
+    pub(crate) fn fresh(&mut self, base: &str) -> usize {
+        let mut name = base.to_string();
+        let mut index = 1;
+        while self.by_name.contains_key(&name) {
+            name = format!("{base}-{index}");
+            index += 1;
+        }
+        self.rule_id(&name)
+    }
+
+    pub(crate) fn define(&mut self, id: usize, alternatives: Vec<Alternative>) -> Result<()> {
+        if self.rules[id].is_some() {
+            candle::bail!("grammar rule {} is defined multiple times", self.names[id])
+        }
+        self.rules[id] = Some(alternatives);
+        Ok(())
+    }
+
+    /// Adds a new rule and returns its id.
+    pub(crate) fn add(&mut self, base: &str, alternatives: Vec<Alternative>) -> usize {
+        let id = self.fresh(base);
+        self.rules[id] = Some(alternatives);
+        id
+    }
+
+    /// Returns a sequence matching `item` between `min` and `max` times, `None` meaning no upper
+    /// bound.
+    pub(crate) fn repeat(
+        &mut self,
+        base: &str,
+        item: Vec<Element>,
+        min: usize,
+        max: Option<usize>,
+    ) -> Result<Vec<Element>> {
+        if let Some(max) = max {
+            if max < min {
+                candle::bail!("invalid repetition bounds {{{min},{max}}}")
+            }
+        }
+        let mut out = Vec::with_capacity(item.len() * min + 1);
+        for _ in 0..min {
+            out.extend_from_slice(&item)
+        }
+        match max {
+            None => {
+                // star ::= item star | ""
+                let id = self.fresh(&format!("{base}-star"));
+                let mut alt = item;
+                alt.push(Element::Rule(id));
+                self.rules[id] = Some(vec![alt, vec![]]);
+                out.push(Element::Rule(id))
+            }
+            Some(max) if max > min => {
+                // opt-k ::= item opt-(k-1) | ""
+                let mut prev: Option<usize> = None;
+                for _ in min..max {
+                    let mut alt = item.clone();
+                    alt.extend(prev.map(Element::Rule));
+                    prev = Some(self.add(&format!("{base}-opt"), vec![alt, vec![]]));
+                }
+                out.extend(prev.map(Element::Rule))
+            }
+            Some(_) => {}
+        }
+        Ok(out)
+    }
+
+    pub(crate) fn build(self, root: usize) -> Result<Grammar> {
+        let mut rules = Vec::with_capacity(self.rules.len());
+        for (rule, name) in self.rules.into_iter().zip(self.names.iter()) {
+            match rule {
+                None => candle::bail!("grammar rule {name} is used but never defined"),
+                Some(rule) => rules.push(rule),
+            }
+        }
+        let grammar = Grammar {
+            rules,
+            names: self.names,
+            root,
+        };
+        grammar.check_left_recursion()?;
+        Ok(grammar)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+struct Pos {
+    rule: u32,
+    alt: u32,
+    elem: u32,
+}
+
+type Stack = Vec<Pos>;
+
+impl Grammar {
+    /// Parses a grammar in the GBNF format, the generation has to match the `root` rule.
+    ///
+    /// ```text
+    /// root   ::= answer (", " answer)*
+    /// answer ::= "yes" | "no" | [0-9]+
+    /// ```
+    pub fn from_gbnf(src: &str) -> Result<Self> {
+        let mut parser = GbnfParser {
+            chars: src.chars().collect(),
+            pos: 0,
+            builder: GrammarBuilder::new(),
+        };
+        parser.parse()?;
+        let root = match parser.builder.by_name.get("root") {
+            None => candle::bail!("the grammar does not have a root rule"),
+            Some(&root) => root,
+        };
+        parser.builder.build(root)
+    }
+
+    pub fn num_rules(&self) -> usize {
+        self.rules.len()
+    }
+
+    pub fn root_name(&self) -> &str {
+        &self.names[self.root]
+    }
+
+    fn check_left_recursion(&self) -> Result<()> {
+        // Compute the rules that can match the empty string.
+        let mut nullable = vec![false; self.rules.len()];
+        let mut changed = true;
+        while changed {
+            changed = false;
+            for (id, rule) in self.rules.iter().enumerate() {
+                if nullable[id] {
+                    continue;
+                }
+                let is_nullable = rule.iter().any(|alt| {
+                    alt.iter().all(|e| match e {
+                        Element::Rule(r) => nullable[*r],
+                        Element::Chars { .. } => false,
+                    })
+                });
+                if is_nullable {
+                    nullable[id] = true;
+                    changed = true;
+                }
+            }
+        }
+        // The rules that can be expanded without consuming any character.
+        let leftmost: Vec<Vec<usize>> = self
+            .rules
+            .iter()
+            .map(|rule| {
+                let mut out = vec![];
+                for alt in rule.iter() {
+                    for e in alt.iter() {
+                        match e {
+                            Element::Chars { .. } => break,
+                            Element::Rule(r) => {
+                                out.push(*r);
+                                if !nullable[*r] {
+                                    break;
+                                }
+                            }
+                        }
+                    }
+                }
+                out
+            })
+            .collect();
+        // 0: unvisited, 1: in progress, 2: done.
+        let mut status = vec![0u8; self.rules.len()];
+        fn visit(id: usize, leftmost: &[Vec<usize>], status: &mut [u8]) -> Option<usize> {
+            match status[id] {
+                1 => return Some(id),
+                2 => return None,
+                _ => {}
+            }
+            status[id] = 1;
+            for &next in leftmost[id].iter() {
+                if let Some(cycle) = visit(next, leftmost, status) {
+                    return Some(cycle);
+                }
+            }
+            status[id] = 2;
+            None
+        }
+        for id in 0..self.rules.len() {
+            if let Some(cycle) = visit(id, &leftmost, &mut status) {
+                candle::bail!("grammar rule {} is left-recursive", self.names[cycle])
+            }
+        }
+        Ok(())
+    }
+
+    fn element(&self, pos: Pos) -> Option<&Element> {
+        self.rules[pos.rule as usize][pos.alt as usize].get(pos.elem as usize)
+    }
+
+    // Expands the rule references at the top of the stack until a character class is reached.
+    fn expand(&self, mut stack: Stack, out: &mut Vec<Stack>) {
+        let top = match stack.last() {
+            None => return out.push(stack),
+            Some(&top) => top,
+        };
+        match self.element(top) {
+            Some(Element::Chars { .. }) => out.push(stack),
+            None => {
+                stack.pop();
+                self.expand(stack, out)
+            }
+            Some(Element::Rule(rule)) => {
+                stack.pop();
+                let next = Pos {
+                    elem: top.elem + 1,
+                    ..top
+                };
+                if self.element(next).is_some() {
+                    stack.push(next)
+                }
+                for (alt, elems) in self.rules[*rule].iter().enumerate() {
+                    let mut stack = stack.clone();
+                    if !elems.is_empty() {
+                        stack.push(Pos {
+                            rule: *rule as u32,
+                            alt: alt as u32,
+                            elem: 0,
+                        })
+                    }
+                    self.expand(stack, out)
+                }
+            }
+        }
+    }
+
+    fn initial_stacks(&self) -> Vec<Stack> {
+        let mut out = vec![];
+        for (alt, elems) in self.rules[self.root].iter().enumerate() {
+            let mut stack = vec![];
+            if !elems.is_empty() {
+                stack.push(Pos {
+                    rule: self.root as u32,
+                    alt: alt as u32,
+                    elem: 0,
+                })
+            }
+            self.expand(stack, &mut out)
+        }
+        out.sort();
+        out.dedup();
+        out
+    }
+
+    fn advance(&self, stacks: &[Stack], c: u32) -> Vec<Stack> {
+        let mut out = vec![];
+        for stack in stacks.iter() {
+            let top = match stack.last() {
+                None => continue,
+                Some(&top) => top,
+            };
+            if !self.element(top).is_some_and(|e| e.matches(c)) {
+                continue;
+            }
+            let mut stack = stack.clone();
+            stack.pop();
+            let next = Pos {
+                elem: top.elem + 1,
+                ..top
+            };
+            if self.element(next).is_some() {
+                stack.push(next)
+            }
+            self.expand(stack, &mut out)
+        }
+        out.sort();
+        out.dedup();
+        out
+    }
+}
+
+/// The state of a grammar recognizer after some bytes of utf-8 text.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub(crate) struct MatchState {
+    stacks: Vec<Stack>,
+    // The bytes of an incomplete utf-8 character.
+    partial: Vec<u8>,
+}
+
+fn utf8_len(lead: u8) -> Option<usize> {
+    match lead {
+        0x00..=0x7F => Some(1),
+        0xC0..=0xDF => Some(2),
+        0xE0..=0xEF => Some(3),
+        0xF0..=0xF7 => Some(4),
+        _ => None,
+    }
+}
+
+// The smallest and largest code points starting with the given bytes.
+fn utf8_bounds(partial: &[u8], len: usize) -> (u32, u32) {
+    let decode = |fill: u8| {
+        let mut bytes = partial.to_vec();
+        bytes.resize(len, fill);
+        let lead_bits = match len {
+            2 => 0x1F,
+            3 => 0x0F,
+            _ => 0x07,
+        };
+        bytes[1..]
+            .iter()
+            .fold((bytes[0] & lead_bits) as u32, |acc, &b| {
+                (acc << 6) | (b & 0x3F) as u32
+            })
+    };
+    (decode(0x80), decode(0xBF))
+}
+
+impl MatchState {
+    pub(crate) fn new(grammar: &Grammar) -> Self {
+        Self {
+            stacks: grammar.initial_stacks(),
+            partial: vec![],
+        }
+    }
+
+    /// Whether the text seen so far is a complete match of the grammar.
+    pub(crate) fn is_accepting(&self) -> bool {
+        self.partial.is_empty() && self.stacks.iter().any(|s| s.is_empty())
+    }
+
+    /// Whether no more characters can be added.
+    pub(crate) fn is_finished(&self) -> bool {
+        self.partial.is_empty() && self.stacks.iter().all(|s| s.is_empty())
+    }
+
+    pub(crate) fn advance_char(&self, grammar: &Grammar, c: char) -> Option<Self> {
+        if !self.partial.is_empty() {
+            return None;
+        }
+        let stacks = grammar.advance(&self.stacks, c as u32);
+        if stacks.is_empty() {
+            None
+        } else {
+            Some(Self {
+                stacks,
+                partial: vec![],
+            })
+        }
+    }
+
+    /// Returns the state after adding a byte, `None` if the grammar cannot match any more.
+    pub(crate) fn advance_byte(&self, grammar: &Grammar, b: u8) -> Option<Self> {
+        let mut partial = self.partial.clone();
+        partial.push(b);
+        let len = utf8_len(partial[0])?;
+        if partial.len() > 1 && b & 0xC0 != 0x80 {
+            return None;
+        }
+        if partial.len() < len {
+            let (lo, hi) = utf8_bounds(&partial, len);
+            let possible = self.stacks.iter().any(|stack| {
+                stack
+                    .last()
+                    .and_then(|&top| grammar.element(top))
+                    .is_some_and(|e| e.intersects(lo, hi))
+            });
+            return possible.then(|| Self {
+                stacks: self.stacks.clone(),
+                partial,
+            });
+        }
+        let c = std::str::from_utf8(&partial).ok()?.chars().next()?;
+        let stacks = grammar.advance(&self.stacks, c as u32);
+        if stacks.is_empty() {
+            None
+        } else {
+            Some(Self {
+                stacks,
+                partial: vec![],
+            })
+        }
+    }
+
+    pub(crate) fn advance_bytes(&self, grammar: &Grammar, bytes: &[u8]) -> Option<Self> {
+        let mut state = std::borrow::Cow::Borrowed(self);
+        for &b in bytes {
+            state = std::borrow::Cow::Owned(state.advance_byte(grammar, b)?)
+        }
+        Some(state.into_owned())
+    }
+}
+
+impl Grammar {
+    /// Whether the grammar matches the whole text.
+    pub fn matches(&self, text: &str) -> bool {
+        let mut state = MatchState::new(self);
+        for c in text.chars() {
+            match state.advance_char(self, c) {
+                None => return false,
+                Some(s) => state = s,
+            }
+        }
+        state.is_accepting()
+    }
+}
+
+struct GbnfParser {
+    chars: Vec<char>,
+    pos: usize,
+    builder: GrammarBuilder,
+}
+
+fn is_ident_char(c: char) -> bool {
+    c.is_ascii_alphanumeric() || c == '-' || c == '_'
+}
+
+impl GbnfParser {
+    fn peek(&self) -> Option<char> {
+        self.chars.get(self.pos).copied()
+    }
+
+    fn bump(&mut self) -> Result<char> {
+        match self.peek() {
+            None => candle::bail!("unexpected end of grammar"),
+            Some(c) => {
+                self.pos += 1;
+                Ok(c)
+            }
+        }
+    }
+
+    fn expect(&mut self, s: &str) -> Result<()> {
+        for expected in s.chars() {
+            let c = self.bump()?;
+            if c != expected {
+                candle::bail!("expected {s:?} at position {} in grammar", self.pos - 1)
+            }
+        }
+        Ok(())
+    }
+
+    fn skip_space(&mut self) {
+        while let Some(c) = self.peek() {
+            if c == '#' {
+                while self.peek().is_some_and(|c| c != '\n') {
+                    self.pos += 1
+                }
+            } else if c.is_whitespace() {
+                self.pos += 1
+            } else {
+                break;
+            }
+        }
+    }
+
+    fn ident(&mut self) -> Result<String> {
+        let start = self.pos;
+        while self.peek().is_some_and(is_ident_char) {
+            self.pos += 1
+        }
+        if start == self.pos {
+            candle::bail!("expected a rule name at position {start} in grammar")
+        }
+        Ok(self.chars[start..self.pos].iter().collect())
+    }
+
+    // Whether the next tokens are the start of a new rule definition.
+    fn at_rule_definition(&mut self) -> bool {
+        let start = self.pos;
+        let found = self.ident().is_ok() && {
+            self.skip_space();
+            self.chars[self.pos..].starts_with(&[':', ':', '='])
+        };
+        self.pos = start;
+        found
+    }
+
+    fn parse(&mut self) -> Result<()> {
+        loop {
+            self.skip_space();
+            if self.peek().is_none() {
+                return Ok(());
+            }
+            let name = self.ident()?;
+            self.skip_space();
+            self.expect("::=")?;
+            let alternatives = self.parse_alternatives()?;
+            let id = self.builder.rule_id(&name);
+            self.builder.define(id, alternatives)?
+        }
+    }
+
+    fn parse_alternatives(&mut self) -> Result<Vec<Alternative>> {
+        let mut alternatives = vec![self.parse_sequence()?];
+        while self.peek() == Some('|') {
+            self.pos += 1;
+            alternatives.push(self.parse_sequence()?)
+        }
+        Ok(alternatives)
+    }
+
+    fn escaped_char(&mut self) -> Result<char> {
+        let c = self.bump()?;
+        if c != '\\' {
+            return Ok(c);
+        }
+        let hex = |p: &mut Self, n: usize| -> Result<char> {
+            let mut value = 0u32;
+            for _ in 0..n {
+                let c = p.bump()?;
+                match c.to_digit(16) {
+                    None => candle::bail!("invalid hex escape in grammar"),
+                    Some(d) => value = value * 16 + d,
+                }
+            }
+            match char::from_u32(value) {
+                None => candle::bail!("invalid code point {value:x} in grammar"),
+                Some(c) => Ok(c),
+            }
+        };
+        match self.bump()? {
+            'n' => Ok('\n'),
+            'r' => Ok('\r'),
+            't' => Ok('\t'),
+            'x' => hex(self, 2),
+            'u' => hex(self, 4),
+            'U' => hex(self, 8),
+            c => Ok(c),
+        }
+    }
+
+    fn parse_sequence(&mut self) -> Result<Alternative> {
+        let mut elems = vec![];
+        // The start of the last item, quantifiers apply to it.
+        let mut last_start = None;
+        loop {
+            self.skip_space();
+            let c = match self.peek() {
+                None | Some('|') | Some(')') => break,
+                Some(c) => c,
+            };
+            let start = elems.len();
+            match c {
+                '"' => {
+                    self.pos += 1;
+                    while self.peek() != Some('"') {
+                        let c = self.escaped_char()?;
+                        elems.push(Element::char(c))
+                    }
+                    self.pos += 1;
+                }
+                '[' => {
+                    self.pos += 1;
+                    let negated = self.peek() == Some('^');
+                    if negated {
+                        self.pos += 1
+                    }
+                    let mut ranges = vec![];
+                    while self.peek() != Some(']') {
+                        let lo = self.escaped_char()? as u32;
+                        let is_range = self.peek() == Some('-')
+                            && self.chars.get(self.pos + 1).is_some_and(|&c| c != ']');
+                        if is_range {
+                            self.pos += 1;
+                            let hi = self.escaped_char()? as u32;
+                            ranges.push((lo, hi))
+                        } else {
+                            ranges.push((lo, lo))
+                        }
+                    }
+                    self.pos += 1;
+                    elems.push(Element::class(ranges, negated))
+                }
+                '.' => {
+                    self.pos += 1;
+                    elems.push(Element::any())
+                }
+                '(' => {
+                    self.pos += 1;
+                    let alternatives = self.parse_alternatives()?;
+                    self.skip_space();
+                    self.expect(")")?;
+                    let id = self.builder.add("group", alternatives);
+                    elems.push(Element::Rule(id))
+                }
+                '*' | '+' | '?' | '{' => {
+                    let start = match last_start {
+                        None => candle::bail!("quantifier without an item in grammar"),
+                        Some(start) => start,
+                    };
+                    let (min, max) = self.quantifier()?;
+                    let item = elems.split_off(start);
+                    let repeated = self.builder.repeat("rep", item, min, max)?;
+                    elems.extend(repeated);
+                    last_start = None;
+                    continue;
+                }
+                c if is_ident_char(c) => {
+                    if self.at_rule_definition() {
+                        break;
+                    }
+                    let name = self.ident()?;
+                    elems.push(Element::Rule(self.builder.rule_id(&name)))
+                }
+                c => candle::bail!(
+                    "unexpected character {c:?} at position {} in grammar",
+                    self.pos
+                ),
+            }
+            last_start = Some(start);
+        }
+        Ok(elems)
+    }
+
+    fn number(&mut self) -> Result<usize> {
+        let start = self.pos;
+        while self.peek().is_some_and(|c| c.is_ascii_digit()) {
+            self.pos += 1
+        }
+        let digits: String = self.chars[start..self.pos].iter().collect();
+        digits
+            .parse()
+            .map_err(|_| candle::Error::Msg(format!("invalid repetition count {digits:?}")))
+    }
+
+    fn quantifier(&mut self) -> Result<(usize, Option<usize>)> {
+        let bounds = match self.bump()? {
+            '*' => (0, None),
+            '+' => (1, None),
+            '?' => (0, Some(1)),
+            _ => {
+                self.skip_space();
+                let min = self.number()?;
+                self.skip_space();
+                let max = if self.peek() == Some(',') {
+                    self.pos += 1;
+                    self.skip_space();
+                    if self.peek() == Some('}') {
+                        None
+                    } else {
+                        Some(self.number()?)
+                    }
+                } else {
+                    Some(min)
+                };
+                self.skip_space();
+                self.expect("}")?;
+                (min, max)
+            }
+        };
+        Ok(bounds)
+    }
+}
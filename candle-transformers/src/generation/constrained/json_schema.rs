@@ -0,0 +1,410 @@
+//! Compiles JSON schemas to grammar rules.
+//!
+//! The supported keywords are `type` (including lists of types), `properties` and `required`,
+//! `additionalProperties` for objects without properties, `items`, `minItems` and `maxItems`,
+//! `enum`, `const`, `anyOf` and `oneOf`, `minLength`, `maxLength` and `pattern` for strings, and
+//! local `$ref` to `#/$defs/...` or `#/definitions/...`. The properties of an object are generated
+//! in the order in which they appear in the schema, and an object with `properties` does not
+//! accept other properties.
+//! Other keywords, e.g. numeric bounds or string formats, are ignored so the generated values
+//! always parse but may not fully validate.
+use super::grammar::{Element, Grammar, GrammarBuilder};
+use candle::Result;
+use serde_json::Value;
+use std::collections::HashMap;
+
+impl Grammar {
+    /// Compiles a JSON schema, the generated text is a JSON value matching the schema.
+    pub fn from_json_schema(schema: &Value) -> Result<Self> {
+        let mut compiler = SchemaCompiler {
+            builder: GrammarBuilder::new(),
+            root: schema,
+            refs: HashMap::new(),
+            primitives: HashMap::new(),
+        };
+        let root = compiler.builder.rule_id("root");
+        let elems = compiler.schema(schema)?;
+        compiler.builder.define(root, vec![elems])?;
+        compiler.builder.build(root)
+    }
+
+    /// A grammar matching any JSON value.
+    pub fn json() -> Result<Self> {
+        Self::from_json_schema(&Value::Object(Default::default()))
+    }
+}
+
+struct SchemaCompiler<'a> {
+    builder: GrammarBuilder,
+    root: &'a Value,
+    refs: HashMap<String, usize>,
+    primitives: HashMap<&'static str, usize>,
+}
+
+fn lit(s: &str) -> Vec<Element> {
+    Element::literal(s)
+}
+
+fn class(ranges: &[(char, char)], negated: bool) -> Element {
+    let ranges = ranges.iter().map(|&(l, h)| (l as u32, h as u32)).collect();
+    Element::class(ranges, negated)
+}
+
+impl SchemaCompiler<'_> {
+    fn rule(&mut self, id: usize) -> Vec<Element> {
+        vec![Element::Rule(id)]
+    }
+
+    // Returns a reference to one of the shared rules, defining it on first use.
+    fn primitive(&mut self, name: &'static str) -> Result<Vec<Element>> {
+        if let Some(&id) = self.primitives.get(name) {
+            return Ok(self.rule(id));
+        }
+        let id = self.builder.fresh(name);
+        self.primitives.insert(name, id);
+        let digit = class(&[('0', '9')], false);
+        let alternatives = match name {
+            // At most one newline and some indentation, unbounded whitespace lets models loop.
+            "space" => {
+                let indent = self.builder.repeat(
+                    "space",
+                    vec![class(&[(' ', ' '), ('\t', '\t')], false)],
+                    0,
+                    Some(20),
+                )?;
+                let mut newline = lit("\n");
+                newline.extend(indent);
+                vec![vec![], lit(" "), newline]
+            }
+            "char" => {
+                let plain = class(
+                    &[('"', '"'), ('\\', '\\'), ('\x00', '\x1F'), ('\x7F', '\x7F')],
+                    true,
+                );
+                let escaped = class(
+                    &[
+                        ('"', '"'),
+                        ('\\', '\\'),
+                        ('/', '/'),
+                        ('b', 'b'),
+                        ('f', 'f'),
+                        ('n', 'n'),
+                        ('r', 'r'),
+                        ('t', 't'),
+                    ],
+                    false,
+                );
+                let hex = class(&[('0', '9'), ('a', 'f'), ('A', 'F')], false);
+                let mut unicode = lit("\\u");
+                unicode.extend(vec![hex; 4]);
+                vec![vec![plain], vec![Element::char('\\'), escaped], unicode]
+            }
+            "string" => {
+                let char = self.primitive("char")?;
+                let mut alt = lit("\"");
+                alt.extend(self.builder.repeat("string", char, 0, None)?);
+                alt.extend(lit("\""));
+                vec![alt]
+            }
+            "integral" => {
+                let digits = self
+                    .builder
+                    .repeat("integral", vec![digit.clone()], 0, Some(15))?;
+                let mut non_zero = vec![class(&[('1', '9')], false)];
+                non_zero.extend(digits);
+                vec![lit("0"), non_zero]
+            }
+            "integer" => {
+                let sign = self.builder.repeat("integer", lit("-"), 0, Some(1))?;
+                let mut alt = sign;
+                alt.extend(self.primitive("integral")?);
+                vec![alt]
+            }
+            "number" => {
+                let mut alt = self.primitive("integer")?;
+                let mut fraction = lit(".");
+                fraction.extend(
+                    self.builder
+                        .repeat("number", vec![digit.clone()], 1, Some(16))?,
+                );
+                alt.extend(self.builder.repeat("number", fraction, 0, Some(1))?);
+                let mut exponent = vec![class(&[('e', 'e'), ('E', 'E')], false)];
+                exponent.extend(self.builder.repeat(
+                    "number",
+                    vec![class(&[('-', '-'), ('+', '+')], false)],
+                    0,
+                    Some(1),
+                )?);
+                exponent.extend(self.builder.repeat("number", vec![digit], 1, Some(15))?);
+                alt.extend(self.builder.repeat("number", exponent, 0, Some(1))?);
+                vec![alt]
+            }
+            "boolean" => vec![lit("true"), lit("false")],
+            "null" => vec![lit("null")],
+            "value" => {
+                let mut alternatives = vec![];
+                for name in ["object", "array", "string", "number", "boolean", "null"] {
+                    alternatives.push(self.primitive(name)?)
+                }
+                alternatives
+            }
+            "object" => {
+                let value = self.primitive("value")?;
+                vec![self.map(value)?]
+            }
+            "array" => {
+                let value = self.primitive("value")?;
+                vec![self.list(value, 0, None)?]
+            }
+            _ => candle::bail!("unknown json primitive {name}"),
+        };
+        self.builder.define(id, alternatives)?;
+        Ok(self.rule(id))
+    }
+
+    // An object with string keys and values matching `value`.
+    fn map(&mut self, value: Vec<Element>) -> Result<Vec<Element>> {
+        let space = self.primitive("space")?;
+        let mut kv = self.primitive("string")?;
+        kv.extend(space.clone());
+        kv.extend(lit(":"));
+        kv.extend(space.clone());
+        kv.extend(value);
+        kv.extend(space.clone());
+        let mut item = lit(",");
+        item.extend(space.clone());
+        item.extend(kv.clone());
+        let mut items = kv;
+        items.extend(self.builder.repeat("object", item, 0, None)?);
+        let mut alt = lit("{");
+        alt.extend(space);
+        alt.extend(self.builder.repeat("object", items, 0, Some(1))?);
+        alt.extend(lit("}"));
+        Ok(alt)
+    }
+
+    // A list of values matching `item`.
+    fn list(&mut self, item: Vec<Element>, min: usize, max: Option<usize>) -> Result<Vec<Element>> {
+        let space = self.primitive("space")?;
+        let mut first = item.clone();
+        first.extend(space.clone());
+        let mut next = lit(",");
+        next.extend(space.clone());
+        next.extend(item);
+        next.extend(space.clone());
+        let mut items = first;
+        let rest_max = max.map(|m| m.saturating_sub(1));
+        items.extend(
+            self.builder
+                .repeat("array", next, min.saturating_sub(1), rest_max)?,
+        );
+        let mut alt = lit("[");
+        alt.extend(space);
+        match max {
+            Some(0) => {}
+            _ if min == 0 => alt.extend(self.builder.repeat("array", items, 0, Some(1))?),
+            _ => alt.extend(items),
+        }
+        alt.extend(lit("]"));
+        Ok(alt)
+    }
+
+    fn json_literal(&mut self, value: &Value) -> Result<Vec<Element>> {
+        let s = serde_json::to_string(value).map_err(candle::Error::wrap)?;
+        Ok(lit(&s))
+    }
+
+    fn alternatives(&mut self, alternatives: Vec<Vec<Element>>) -> Vec<Element> {
+        if alternatives.len() == 1 {
+            return alternatives.into_iter().next().unwrap_or_default();
+        }
+        let id = self.builder.add("alt", alternatives);
+        self.rule(id)
+    }
+
+    fn reference(&mut self, reference: &str) -> Result<Vec<Element>> {
+        if let Some(&id) = self.refs.get(reference) {
+            return Ok(self.rule(id));
+        }
+        let path = match reference.strip_prefix("#/") {
+            None => {
+                candle::bail!("only local json schema references are supported, got {reference}")
+            }
+            Some(path) => path,
+        };
+        let mut schema = self.root;
+        for part in path.split('/') {
+            let part = part.replace("~1", "/").replace("~0", "~");
+            schema = match schema.get(&part) {
+                None => candle::bail!("cannot resolve json schema reference {reference}"),
+                Some(schema) => schema,
+            }
+        }
+        let name = path.rsplit('/').next().unwrap_or("ref");
+        let id = self.builder.fresh(&format!("ref-{name}"));
+        // Register the rule before compiling it so that recursive schemas terminate.
+        self.refs.insert(reference.to_string(), id);
+        let elems = self.schema(schema)?;
+        self.builder.define(id, vec![elems])?;
+        Ok(self.rule(id))
+    }
+
+    fn schema(&mut self, schema: &Value) -> Result<Vec<Element>> {
+        let schema = match schema {
+            Value::Bool(true) => return self.primitive("value"),
+            Value::Bool(false) => candle::bail!("the json schema false cannot be matched"),
+            Value::Object(schema) => schema,
+            _ => candle::bail!("invalid json schema {schema}"),
+        };
+        if let Some(Value::String(reference)) = schema.get("$ref") {
+            return self.reference(reference);
+        }
+        if let Some(value) = schema.get("const") {
+            return self.json_literal(value);
+        }
+        if let Some(values) = schema.get("enum") {
+            let values = match values.as_array() {
+                None => candle::bail!("json schema enum has to be an array"),
+                Some(values) => values,
+            };
+            let alternatives = values
+                .iter()
+                .map(|v| self.json_literal(v))
+                .collect::<Result<Vec<_>>>()?;
+            return Ok(self.alternatives(alternatives));
+        }
+        for key in ["anyOf", "oneOf"] {
+            if let Some(schemas) = schema.get(key) {
+                let schemas = match schemas.as_array() {
+                    None => candle::bail!("json schema {key} has to be an array"),
+                    Some(schemas) => schemas,
+                };
+                let alternatives = schemas
+                    .iter()
+                    .map(|s| self.schema(s))
+                    .collect::<Result<Vec<_>>>()?;
+                return Ok(self.alternatives(alternatives));
+            }
+        }
+        if let Some(Value::Array(schemas)) = schema.get("allOf") {
+            if let [schema] = schemas.as_slice() {
+                return self.schema(schema);
+            }
+            candle::bail!("json schema allOf is only supported with a single schema")
+        }
+        let types: Vec<&str> = match schema.get("type") {
+            Some(Value::String(t)) => vec![t.as_str()],
+            Some(Value::Array(ts)) => ts.iter().filter_map(|t| t.as_str()).collect(),
+            Some(t) => candle::bail!("invalid json schema type {t}"),
+            None if schema.contains_key("properties") => vec!["object"],
+            None if schema.contains_key("items") => vec!["array"],
+            None => return self.primitive("value"),
+        };
+        let alternatives = types
+            .into_iter()
+            .map(|t| self.typed(t, schema))
+            .collect::<Result<Vec<_>>>()?;
+        Ok(self.alternatives(alternatives))
+    }
+
+    fn typed(&mut self, t: &str, schema: &serde_json::Map<String, Value>) -> Result<Vec<Element>> {
+        let usize_key = |key: &str| schema.get(key).and_then(|v| v.as_u64()).map(|v| v as usize);
+        match t {
+            "object" => self.object(schema),
+            "array" => {
+                let item = match schema.get("items") {
+                    None => self.primitive("value")?,
+                    Some(items) => self.schema(items)?,
+                };
+                let min = usize_key("minItems").unwrap_or(0);
+                self.list(item, min, usize_key("maxItems"))
+            }
+            "string" => {
+                let min = usize_key("minLength");
+                let max = usize_key("maxLength");
+                if let Some(Value::String(pattern)) = schema.get("pattern") {
+                    let mut alt = lit("\"");
+                    alt.extend(super::regex::compile(&mut self.builder, pattern)?);
+                    alt.extend(lit("\""));
+                    return Ok(alt);
+                }
+                if min.is_none() && max.is_none() {
+                    return self.primitive("string");
+                }
+                let char = self.primitive("char")?;
+                let mut alt = lit("\"");
+                alt.extend(self.builder.repeat("string", char, min.unwrap_or(0), max)?);
+                alt.extend(lit("\""));
+                Ok(alt)
+            }
+            "integer" | "number" | "boolean" | "null" => {
+                let name = match t {
+                    "integer" => "integer",
+                    "number" => "number",
+                    "boolean" => "boolean",
+                    _ => "null",
+                };
+                self.primitive(name)
+            }
+            t => candle::bail!("unsupported json schema type {t}"),
+        }
+    }
+
+    fn object(&mut self, schema: &serde_json::Map<String, Value>) -> Result<Vec<Element>> {
+        let properties = match schema.get("properties") {
+            Some(Value::Object(properties)) if !properties.is_empty() => properties,
+            _ => {
+                let value = match schema.get("additionalProperties") {
+                    None | Some(Value::Bool(true)) => self.primitive("value")?,
+                    Some(Value::Bool(false)) => return Ok(lit("{}")),
+                    Some(value) => self.schema(value)?,
+                };
+                return self.map(value);
+            }
+        };
+        let required: Vec<&str> = match schema.get("required") {
+            Some(Value::Array(required)) => required.iter().filter_map(|v| v.as_str()).collect(),
+            _ => vec![],
+        };
+        let space = self.primitive("space")?;
+        let mut kvs = vec![];
+        for (name, value) in properties.iter() {
+            let mut kv = self.json_literal(&Value::String(name.clone()))?;
+            kv.extend(space.clone());
+            kv.extend(lit(":"));
+            kv.extend(space.clone());
+            kv.extend(self.schema(value)?);
+            kv.extend(space.clone());
+            kvs.push((kv, required.contains(&name.as_str())))
+        }
+        // after-i matches the properties from i on, each preceded by a comma, and first-i the
+        // properties from i on when none of the previous ones is present.
+        let n = kvs.len();
+        let mut after = vec![vec![]; n + 1];
+        let mut first = vec![vec![]; n + 1];
+        for (i, (kv, is_required)) in kvs.into_iter().enumerate().rev() {
+            let mut with_comma = lit(",");
+            with_comma.extend(space.clone());
+            with_comma.extend(kv.clone());
+            with_comma.extend(after[i + 1].clone());
+            let mut present = kv;
+            present.extend(after[i + 1].clone());
+            if is_required {
+                after[i] = self.alternatives(vec![with_comma]);
+                first[i] = self.alternatives(vec![present]);
+            } else {
+                let skip_after = after[i + 1].clone();
+                let skip_first = first[i + 1].clone();
+                let id = self.builder.add("after", vec![with_comma, skip_after]);
+                after[i] = self.rule(id);
+                let id = self.builder.add("first", vec![present, skip_first]);
+                first[i] = self.rule(id);
+            }
+        }
+        let mut alt = lit("{");
+        alt.extend(space);
+        alt.extend(first.swap_remove(0));
+        alt.extend(lit("}"));
+        Ok(alt)
+    }
+}
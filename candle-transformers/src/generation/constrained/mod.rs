@@ -0,0 +1,328 @@
+//! Grammar constrained decoding.
+//!
+//! A [`Grammar`] is compiled from a GBNF grammar, a regular expression or a JSON schema. A
+//! [`GrammarConstraint`] then tracks the text generated so far and masks the logits of the tokens
+//! that cannot extend it into a match of the grammar, the end-of-sequence tokens are only allowed
+//! once the text is a complete match. The tokenizer vocabulary is stored as a byte trie so that
+//! tokens sharing a prefix are only checked against the grammar once, and the allowed tokens are
+//! cached per grammar state as generation tends to revisit the same states.
+//!
+//! ```ignore
+//! use candle_transformers::generation::constrained::{Grammar, GrammarConstraint, Vocabulary};
+//! use candle_transformers::generation::processors::LogitsPipeline;
+//!
+//! let schema = serde_json::json!({
+//!     "type": "object",
+//!     "properties": { "name": { "type": "string" }, "age": { "type": "integer" } },
+//!     "required": ["name", "age"],
+//! });
+//! let grammar = std::sync::Arc::new(Grammar::from_json_schema(&schema)?);
+//! let vocab = std::sync::Arc::new(Vocabulary::from_token_strings(
+//!     &tokenizer.get_vocab(true),
+//!     &special_tokens,
+//!     vec![eos_token],
+//! )?);
+//! let mut pipeline = LogitsPipeline::new(42).with(GrammarConstraint::new(grammar, vocab));
+//! let next_token = pipeline.sample(&logits, &tokens, prompt_len)?;
+//! ```
+mod grammar;
+mod json_schema;
+mod regex;
+
+pub use grammar::Grammar;
+
+use super::processors::{Context, Processor};
+use candle::Result;
+use grammar::MatchState;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+
+// The maximum number of grammar states for which the allowed tokens are cached, the cache is
+// cleared once full.
+const MAX_CACHED_STATES: usize = 128;
+
+type MaskCache = Arc<Mutex<HashMap<MatchState, Arc<Vec<bool>>>>>;
+
+#[derive(Debug, Clone, Default)]
+struct TrieNode {
+    children: Vec<(u8, usize)>,
+    // The tokens whose bytes end at this node.
+    tokens: Vec<u32>,
+}
+
+/// The byte representation of the tokens of a tokenizer.
+#[derive(Debug, Clone)]
+pub struct Vocabulary {
+    tokens: Vec<Vec<u8>>,
+    eos_tokens: Vec<u32>,
+    nodes: Vec<TrieNode>,
+}
+
+// The inverse of the byte to unicode mapping used by byte-level BPE tokenizers.
+fn byte_level_decoder() -> HashMap<char, u8> {
+    let mut decoder = HashMap::new();
+    let mut n = 0;
+    for b in 0..=255u8 {
+        let printable = matches!(b, 33..=126 | 161..=172 | 174..=255);
+        let c = if printable {
+            b as u32
+        } else {
+            n += 1;
+            255 + n
+        };
+        if let Some(c) = char::from_u32(c) {
+            decoder.insert(c, b);
+        }
+    }
+    decoder
+}
+
+// Returns the byte encoded by sentencepiece byte-fallback tokens such as `<0x0A>`.
+fn byte_fallback(token: &str) -> Option<u8> {
+    let hex = token.strip_prefix("<0x")?.strip_suffix('>')?;
+    if hex.len() != 2 {
+        return None;
+    }
+    u8::from_str_radix(hex, 16).ok()
+}
+
+impl Vocabulary {
+    /// Creates a vocabulary from the bytes of each token, indexed by token id. Tokens with no
+    /// bytes, e.g. special tokens, are never allowed by the constraint.
+    pub fn new(tokens: Vec<Vec<u8>>, eos_tokens: Vec<u32>) -> Self {
+        let mut nodes = vec![TrieNode::default()];
+        for (id, bytes) in tokens.iter().enumerate() {
+            if bytes.is_empty() || eos_tokens.contains(&(id as u32)) {
+                continue;
+            }
+            let mut node = 0;
+            for &b in bytes.iter() {
+                node = match nodes[node].children.iter().find(|(c, _)| *c == b) {
+                    Some(&(_, child)) => child,
+                    None => {
+                        let child = nodes.len();
+                        nodes.push(TrieNode::default());
+                        nodes[node].children.push((b, child));
+                        child
+                    }
+                }
+            }
+            nodes[node].tokens.push(id as u32)
+        }
+        Self {
+            tokens,
+            eos_tokens,
+            nodes,
+        }
+    }
+
+    /// Creates a vocabulary from the token strings of a tokenizer, as returned by
+    /// `tokenizers::Tokenizer::get_vocab`. Both byte-level BPE tokens, where a space is encoded
+    /// as `Ġ`, and sentencepiece tokens, where a space is encoded as `▁` and bytes as `<0xAB>`,
+    /// are supported. The special tokens are excluded from the constrained generation.
+    pub fn from_token_strings(
+        vocab: &HashMap<String, u32>,
+        special_tokens: &[u32],
+        eos_tokens: Vec<u32>,
+    ) -> Result<Self> {
+        let size = vocab.values().map(|&id| id as usize + 1).max().unwrap_or(0);
+        let byte_level = vocab.keys().any(|t| t.contains('Ġ'));
+        let decoder = byte_level_decoder();
+        let mut tokens = vec![vec![]; size];
+        for (token, &id) in vocab.iter() {
+            if special_tokens.contains(&id) {
+                continue;
+            }
+            let bytes = if byte_level {
+                match token.chars().map(|c| decoder.get(&c).copied()).collect() {
+                    Some(bytes) => bytes,
+                    // Tokens that are not byte-level encoded are added tokens.
+                    None => token.as_bytes().to_vec(),
+                }
+            } else if let Some(b) = byte_fallback(token) {
+                vec![b]
+            } else {
+                token.replace('▁', " ").into_bytes()
+            };
+            tokens[id as usize] = bytes
+        }
+        Ok(Self::new(tokens, eos_tokens))
+    }
+
+    pub fn len(&self) -> usize {
+        self.tokens.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.tokens.is_empty()
+    }
+
+    /// The bytes of a token.
+    pub fn token_bytes(&self, token: u32) -> Option<&[u8]> {
+        self.tokens.get(token as usize).map(|b| b.as_slice())
+    }
+
+    pub fn eos_tokens(&self) -> &[u32] {
+        &self.eos_tokens
+    }
+}
+
+/// Restricts the generated tokens so that the generated text matches a grammar.
+#[derive(Debug, Clone)]
+pub struct GrammarConstraint {
+    grammar: Arc<Grammar>,
+    vocab: Arc<Vocabulary>,
+    state: MatchState,
+    // The generated tokens that have been applied to the state.
+    consumed: Vec<u32>,
+    finished: bool,
+    // Shared by the clones of the constraint.
+    cache: MaskCache,
+}
+
+impl GrammarConstraint {
+    pub fn new(grammar: Arc<Grammar>, vocab: Arc<Vocabulary>) -> Self {
+        let state = MatchState::new(&grammar);
+        Self {
+            grammar,
+            vocab,
+            state,
+            consumed: vec![],
+            finished: false,
+            cache: Arc::new(Mutex::new(HashMap::new())),
+        }
+    }
+
+    /// Restarts the constraint for a new generation.
+    pub fn reset(&mut self) {
+        self.state = MatchState::new(&self.grammar);
+        self.consumed.clear();
+        self.finished = false;
+    }
+
+    /// Advances the constraint with a generated token, an error is returned if the grammar does
+    /// not allow this token.
+    pub fn accept_token(&mut self, token: u32) -> Result<()> {
+        if self.finished {
+            candle::bail!("the constrained generation has already ended")
+        }
+        if self.vocab.eos_tokens.contains(&token) {
+            if !self.state.is_accepting() {
+                candle::bail!("end of sequence token {token} before the grammar is matched")
+            }
+            self.finished = true;
+        } else {
+            let bytes = match self.vocab.token_bytes(token) {
+                Some(bytes) if !bytes.is_empty() => bytes,
+                _ => candle::bail!("token {token} is not allowed by the grammar"),
+            };
+            self.state = match self.state.advance_bytes(&self.grammar, bytes) {
+                None => candle::bail!("token {token} is not allowed by the grammar"),
+                Some(state) => state,
+            };
+        }
+        self.consumed.push(token);
+        Ok(())
+    }
+
+    /// Returns whether each token of the vocabulary can be generated next.
+    pub fn allowed_tokens(&self) -> Vec<bool> {
+        self.mask().to_vec()
+    }
+
+    /// The number of grammar states for which the allowed tokens are currently cached.
+    pub fn num_cached_states(&self) -> usize {
+        self.cache.lock().map_or(0, |cache| cache.len())
+    }
+
+    fn mask(&self) -> Arc<Vec<bool>> {
+        if self.finished {
+            return Arc::new(vec![false; self.vocab.len()]);
+        }
+        let cached = self
+            .cache
+            .lock()
+            .ok()
+            .and_then(|cache| cache.get(&self.state).cloned());
+        if let Some(mask) = cached {
+            return mask;
+        }
+        let mask = Arc::new(self.walk_trie());
+        if let Ok(mut cache) = self.cache.lock() {
+            if cache.len() >= MAX_CACHED_STATES {
+                cache.clear()
+            }
+            cache.insert(self.state.clone(), mask.clone());
+        }
+        mask
+    }
+
+    // Walks the vocabulary trie from the current state, the subtrees for which the grammar cannot
+    // match the prefix are skipped.
+    fn walk_trie(&self) -> Vec<bool> {
+        let mut allowed = vec![false; self.vocab.len()];
+        if self.state.is_accepting() {
+            for &eos in self.vocab.eos_tokens.iter() {
+                if let Some(a) = allowed.get_mut(eos as usize) {
+                    *a = true
+                }
+            }
+        }
+        let nodes = &self.vocab.nodes;
+        let mut todo = vec![(0, self.state.clone())];
+        while let Some((node, state)) = todo.pop() {
+            for &(b, child) in nodes[node].children.iter() {
+                if let Some(state) = state.advance_byte(&self.grammar, b) {
+                    for &token in nodes[child].tokens.iter() {
+                        allowed[token as usize] = true
+                    }
+                    if !nodes[child].children.is_empty() {
+                        todo.push((child, state))
+                    }
+                }
+            }
+        }
+        allowed
+    }
+
+    /// Sets the logits of the tokens that are not allowed to `-inf`.
+    pub fn apply_mask(&self, logits: &mut [f32]) {
+        let allowed = self.mask();
+        for (i, logit) in logits.iter_mut().enumerate() {
+            if !allowed.get(i).copied().unwrap_or(false) {
+                *logit = f32::NEG_INFINITY
+            }
+        }
+    }
+
+    /// Whether the text generated so far is a complete match of the grammar.
+    pub fn is_complete(&self) -> bool {
+        self.state.is_accepting()
+    }
+
+    /// Whether the generation has to stop, either because an end-of-sequence token has been
+    /// generated or because the grammar cannot match any more text.
+    pub fn is_finished(&self) -> bool {
+        self.finished || self.state.is_finished()
+    }
+
+    pub fn grammar(&self) -> &Grammar {
+        &self.grammar
+    }
+}
+
+impl Processor for GrammarConstraint {
+    fn apply(&mut self, logits: &mut [f32], ctx: &Context) -> Result<()> {
+        let generated = ctx.generated();
+        // The processor may be shared by sequences that diverge, e.g. the beams of a beam
+        // search, in which case the state is rebuilt from scratch.
+        if !generated.starts_with(&self.consumed) {
+            self.reset()
+        }
+        for &token in generated[self.consumed.len()..].iter() {
+            self.accept_token(token)?
+        }
+        self.apply_mask(logits);
+        Ok(())
+    }
+}
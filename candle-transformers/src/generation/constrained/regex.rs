@@ -0,0 +1,262 @@
+//! Compiles regular expressions to grammar rules.
+//!
+//! This supports the usual subset of the regex syntax: literals and escapes, `.`, character
+//! classes with ranges and negation, the `\d`, `\w` and `\s` classes and their negations,
+//! groups (capturing or not), alternations and the `*`, `+`, `?`, `{n}`, `{n,}` and `{n,m}`
+//! quantifiers. The expression has to match the whole generated text so `^` and `$` are ignored,
+//! lazy quantifiers are treated as greedy ones.
+use super::grammar::{complement, Element, Grammar, GrammarBuilder};
+use candle::Result;
+
+impl Grammar {
+    /// Compiles a regular expression, the generated text has to fully match the expression.
+    pub fn from_regex(pattern: &str) -> Result<Self> {
+        let mut builder = GrammarBuilder::new();
+        let root = builder.rule_id("root");
+        let elems = compile(&mut builder, pattern)?;
+        builder.define(root, vec![elems])?;
+        builder.build(root)
+    }
+}
+
+/// Returns a sequence of grammar elements matching the regular expression.
+pub(crate) fn compile(builder: &mut GrammarBuilder, pattern: &str) -> Result<Vec<Element>> {
+    let mut parser = RegexParser {
+        chars: pattern.chars().collect(),
+        pos: 0,
+        builder,
+    };
+    let elems = parser.alternation()?;
+    if parser.pos < parser.chars.len() {
+        candle::bail!("unbalanced parenthesis in regex {pattern:?}")
+    }
+    Ok(elems)
+}
+
+fn digit() -> Vec<(u32, u32)> {
+    vec![('0' as u32, '9' as u32)]
+}
+
+fn word() -> Vec<(u32, u32)> {
+    vec![
+        ('0' as u32, '9' as u32),
+        ('A' as u32, 'Z' as u32),
+        ('_' as u32, '_' as u32),
+        ('a' as u32, 'z' as u32),
+    ]
+}
+
+fn space() -> Vec<(u32, u32)> {
+    [' ', '\t', '\n', '\r', '\x0B', '\x0C']
+        .iter()
+        .map(|&c| (c as u32, c as u32))
+        .collect()
+}
+
+struct RegexParser<'a> {
+    chars: Vec<char>,
+    pos: usize,
+    builder: &'a mut GrammarBuilder,
+}
+
+// An escape sequence, either a single character or a class of characters.
+enum Escape {
+    Char(char),
+    Class(Vec<(u32, u32)>),
+}
+
+impl RegexParser<'_> {
+    fn peek(&self) -> Option<char> {
+        self.chars.get(self.pos).copied()
+    }
+
+    fn bump(&mut self) -> Result<char> {
+        match self.peek() {
+            None => candle::bail!("unexpected end of regex"),
+            Some(c) => {
+                self.pos += 1;
+                Ok(c)
+            }
+        }
+    }
+
+    fn alternation(&mut self) -> Result<Vec<Element>> {
+        let mut alternatives = vec![self.concatenation()?];
+        while self.peek() == Some('|') {
+            self.pos += 1;
+            alternatives.push(self.concatenation()?)
+        }
+        if alternatives.len() == 1 {
+            return Ok(alternatives.remove(0));
+        }
+        let id = self.builder.add("regex", alternatives);
+        Ok(vec![Element::Rule(id)])
+    }
+
+    fn concatenation(&mut self) -> Result<Vec<Element>> {
+        let mut elems = vec![];
+        loop {
+            let item = match self.peek() {
+                None | Some('|') | Some(')') => break,
+                Some('^') | Some('$') => {
+                    self.pos += 1;
+                    continue;
+                }
+                Some(_) => self.atom()?,
+            };
+            let item = match self.quantifier()? {
+                None => item,
+                Some((min, max)) => self.builder.repeat("regex", item, min, max)?,
+            };
+            elems.extend(item)
+        }
+        Ok(elems)
+    }
+
+    fn atom(&mut self) -> Result<Vec<Element>> {
+        let elem = match self.bump()? {
+            '(' => {
+                if self.peek() == Some('?') {
+                    self.pos += 1;
+                    if self.bump()? != ':' {
+                        candle::bail!("only non-capturing groups (?:...) are supported in regex")
+                    }
+                }
+                let elems = self.alternation()?;
+                if self.bump()? != ')' {
+                    candle::bail!("unbalanced parenthesis in regex")
+                }
+                return Ok(elems);
+            }
+            '[' => self.class()?,
+            '.' => Element::class(vec![('\n' as u32, '\n' as u32)], true),
+            '\\' => match self.escape()? {
+                Escape::Char(c) => Element::char(c),
+                Escape::Class(ranges) => Element::class(ranges, false),
+            },
+            c @ ('*' | '+' | '?') => {
+                candle::bail!("quantifier {c:?} without an item in regex")
+            }
+            c => Element::char(c),
+        };
+        Ok(vec![elem])
+    }
+
+    fn escape(&mut self) -> Result<Escape> {
+        let escape = match self.bump()? {
+            'd' => Escape::Class(digit()),
+            'D' => Escape::Class(complement(&digit())),
+            'w' => Escape::Class(word()),
+            'W' => Escape::Class(complement(&word())),
+            's' => Escape::Class(space()),
+            'S' => Escape::Class(complement(&space())),
+            'n' => Escape::Char('\n'),
+            'r' => Escape::Char('\r'),
+            't' => Escape::Char('\t'),
+            'f' => Escape::Char('\x0C'),
+            'v' => Escape::Char('\x0B'),
+            c @ ('x' | 'u') => {
+                let n = if c == 'x' { 2 } else { 4 };
+                let mut value = 0u32;
+                for _ in 0..n {
+                    match self.bump()?.to_digit(16) {
+                        None => candle::bail!("invalid hex escape in regex"),
+                        Some(d) => value = value * 16 + d,
+                    }
+                }
+                match char::from_u32(value) {
+                    None => candle::bail!("invalid code point {value:x} in regex"),
+                    Some(c) => Escape::Char(c),
+                }
+            }
+            c => Escape::Char(c),
+        };
+        Ok(escape)
+    }
+
+    fn class_char(&mut self) -> Result<Escape> {
+        match self.bump()? {
+            '\\' => self.escape(),
+            c => Ok(Escape::Char(c)),
+        }
+    }
+
+    fn class(&mut self) -> Result<Element> {
+        let negated = self.peek() == Some('^');
+        if negated {
+            self.pos += 1
+        }
+        let mut ranges = vec![];
+        let mut first = true;
+        // A closing bracket right after the opening one is a literal.
+        while self.peek() != Some(']') || first {
+            first = false;
+            match self.class_char()? {
+                Escape::Class(r) => ranges.extend(r),
+                Escape::Char(lo) => {
+                    let is_range = self.peek() == Some('-')
+                        && self.chars.get(self.pos + 1).is_some_and(|&c| c != ']');
+                    if is_range {
+                        self.pos += 1;
+                        match self.class_char()? {
+                            Escape::Char(hi) => ranges.push((lo as u32, hi as u32)),
+                            Escape::Class(_) => candle::bail!("invalid range in regex class"),
+                        }
+                    } else {
+                        ranges.push((lo as u32, lo as u32))
+                    }
+                }
+            }
+        }
+        self.pos += 1;
+        Ok(Element::class(ranges, negated))
+    }
+
+    fn number(&mut self) -> Option<usize> {
+        let start = self.pos;
+        while self.peek().is_some_and(|c| c.is_ascii_digit()) {
+            self.pos += 1
+        }
+        let digits: String = self.chars[start..self.pos].iter().collect();
+        digits.parse().ok()
+    }
+
+    fn quantifier(&mut self) -> Result<Option<(usize, Option<usize>)>> {
+        let bounds = match self.peek() {
+            Some('*') => (0, None),
+            Some('+') => (1, None),
+            Some('?') => (0, Some(1)),
+            Some('{') => {
+                let start = self.pos;
+                self.pos += 1;
+                let bounds = match self.number() {
+                    None => None,
+                    Some(min) => match self.peek() {
+                        Some('}') => Some((min, Some(min))),
+                        Some(',') => {
+                            self.pos += 1;
+                            let max = self.number();
+                            (self.peek() == Some('}')).then_some((min, max))
+                        }
+                        _ => None,
+                    },
+                };
+                match bounds {
+                    Some(bounds) => bounds,
+                    None => {
+                        // Not a valid quantifier, the brace is a literal.
+                        self.pos = start;
+                        return Ok(None);
+                    }
+                }
+            }
+            _ => return Ok(None),
+        };
+        self.pos += 1;
+        // Lazy and possessive modifiers do not change what is matched.
+        if matches!(self.peek(), Some('?') | Some('+')) {
+            self.pos += 1
+        }
+        Ok(Some(bounds))
+    }
+}
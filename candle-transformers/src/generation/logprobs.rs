@@ -0,0 +1,177 @@
+//! Token log-probabilities and scoring of continuations.
+//!
+//! The log-probabilities are computed from the raw logits returned by the model, i.e. before the
+//! processors of a [`LogitsPipeline`](super::processors::LogitsPipeline) run, so the temperature,
+//! the penalties, the grammar constraints and any filtering are not reflected in them. This is
+//! what the `logprobs` field of the OpenAI API reports. [`score`] computes the log-likelihood and
+//! perplexity of a continuation in a single forward pass, this requires a [`CausalLM`] supporting
+//! [`CausalLM::forward_all`].
+//!
+//! ```ignore
+//! use candle_transformers::generation::logprobs;
+//!
+//! let score = logprobs::score(&mut model, &context, &continuation, 5, &device)?;
+//! println!("{} {}", score.log_likelihood(), score.perplexity());
+//! ```
+use crate::models::auto::CausalLM;
+use candle::{DType, Device, IndexOp, Result, Tensor, D};
+
+/// A token together with its log-probability.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct TokenLogprob {
+    pub token: u32,
+    pub logprob: f32,
+}
+
+/// A sampled token with its log-probability and the most likely alternatives.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SampledToken {
+    pub token: u32,
+    pub logprob: f32,
+    /// The most likely tokens at this position sorted by decreasing log-probability, these may
+    /// or may not include the sampled token.
+    pub top_logprobs: Vec<TokenLogprob>,
+}
+
+impl SampledToken {
+    pub fn new(token: u32, logprobs: &[f32], top_n: usize) -> Self {
+        Self {
+            token,
+            logprob: logprobs[token as usize],
+            top_logprobs: top_logprobs(logprobs, top_n),
+        }
+    }
+}
+
+/// Returns the log-softmax of a one dimensional logits tensor.
+pub fn log_softmax(logits: &Tensor) -> Result<Vec<f32>> {
+    candle_nn::ops::log_softmax(&logits.to_dtype(DType::F32)?, D::Minus1)?.to_vec1()
+}
+
+// Same as [`log_softmax`] on logits that have already been extracted, `-inf` logits keep a `-inf`
+// log-probability.
+pub(crate) fn log_softmax_slice(logits: &[f32]) -> Vec<f32> {
+    let max = logits.iter().copied().fold(f32::NEG_INFINITY, f32::max);
+    let log_sum = logits.iter().map(|l| (l - max).exp()).sum::<f32>().ln() + max;
+    logits.iter().map(|l| l - log_sum).collect()
+}
+
+/// Returns the `n` most likely tokens sorted by decreasing log-probability.
+pub fn top_logprobs(logprobs: &[f32], n: usize) -> Vec<TokenLogprob> {
+    let mut top: Vec<TokenLogprob> = logprobs
+        .iter()
+        .enumerate()
+        .map(|(token, &logprob)| TokenLogprob {
+            token: token as u32,
+            logprob,
+        })
+        .collect();
+    let n = n.min(top.len());
+    if n < top.len() {
+        top.select_nth_unstable_by(n, |a, b| b.logprob.total_cmp(&a.logprob));
+        top.truncate(n);
+    }
+    top.sort_by(|a, b| b.logprob.total_cmp(&a.logprob));
+    top
+}
+
+/// The log-probabilities of the tokens of a continuation.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ContinuationScore {
+    /// The log-probability of each token of the continuation given the previous ones.
+    pub logprobs: Vec<TokenLogprob>,
+    /// The most likely tokens at each position of the continuation.
+    pub top_logprobs: Vec<Vec<TokenLogprob>>,
+    /// Whether each token of the continuation is the most likely one, i.e. the continuation
+    /// would have been generated by greedy decoding.
+    pub is_greedy: bool,
+}
+
+impl ContinuationScore {
+    pub fn len(&self) -> usize {
+        self.logprobs.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.logprobs.is_empty()
+    }
+
+    /// The sum of the log-probabilities of the continuation tokens.
+    pub fn log_likelihood(&self) -> f64 {
+        self.logprobs.iter().map(|l| l.logprob as f64).sum()
+    }
+
+    /// The exponential of the average negative log-likelihood per token.
+    pub fn perplexity(&self) -> f64 {
+        (-self.log_likelihood() / self.len() as f64).exp()
+    }
+}
+
+/// Scores a continuation given the logits for the positions preceding each of its tokens,
+/// `logits` has shape `(continuation_len, vocab_size)`.
+pub fn score_logits(
+    logits: &Tensor,
+    continuation: &[u32],
+    top_n: usize,
+) -> Result<ContinuationScore> {
+    let (seq_len, _) = logits.dims2()?;
+    if seq_len != continuation.len() {
+        candle::bail!(
+            "got logits for {seq_len} positions for a continuation of {} tokens",
+            continuation.len()
+        )
+    }
+    let logprobs =
+        candle_nn::ops::log_softmax(&logits.to_dtype(DType::F32)?, D::Minus1)?.to_vec2::<f32>()?;
+    let mut score = ContinuationScore {
+        logprobs: Vec::with_capacity(seq_len),
+        top_logprobs: Vec::with_capacity(seq_len),
+        is_greedy: true,
+    };
+    for (logprobs, &token) in logprobs.iter().zip(continuation.iter()) {
+        let logprob = match logprobs.get(token as usize) {
+            None => candle::bail!("token {token} is out of the vocabulary"),
+            Some(&logprob) => logprob,
+        };
+        let is_best = logprobs.iter().all(|&l| l <= logprob);
+        score.is_greedy &= is_best;
+        score.logprobs.push(TokenLogprob { token, logprob });
+        score.top_logprobs.push(top_logprobs(logprobs, top_n))
+    }
+    Ok(score)
+}
+
+fn check_inputs(context: &[u32], continuation: &[u32]) -> Result<()> {
+    if context.is_empty() {
+        candle::bail!("scoring requires a non-empty context")
+    }
+    if continuation.is_empty() {
+        candle::bail!("cannot score an empty continuation")
+    }
+    Ok(())
+}
+
+/// Scores `continuation` following `context`, `context` has to be non-empty, e.g. only contain
+/// the beginning-of-sequence token. The context and the continuation are processed in a single
+/// forward pass so the model has to support [`CausalLM::forward_all`]. The kv-cache of the model
+/// is cleared first and contains the scored sequence afterwards.
+pub fn score<M: CausalLM + ?Sized>(
+    model: &mut M,
+    context: &[u32],
+    continuation: &[u32],
+    top_n: usize,
+    device: &Device,
+) -> Result<ContinuationScore> {
+    check_inputs(context, continuation)?;
+    model.clear_kv_cache();
+    // The last token of the continuation is not needed as an input.
+    let input: Vec<u32> = context
+        .iter()
+        .chain(continuation[..continuation.len() - 1].iter())
+        .copied()
+        .collect();
+    let input = Tensor::new(input, device)?.unsqueeze(0)?;
+    let logits = model.forward_all(&input, 0)?.i(0)?;
+    let logits = logits.narrow(0, context.len() - 1, continuation.len())?;
+    score_logits(&logits, continuation, top_n)
+}
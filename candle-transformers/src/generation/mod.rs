@@ -4,7 +4,13 @@
 //! with support for temperature-based sampling, top-k filtering, nucleus sampling (top-p),
 //! and combinations thereof.
 use candle::{DType, Error, Result, Tensor};
-use rand::{distr::Distribution, SeedableRng};
+use rand::{distr::Distribution, Rng, SeedableRng};
+
+pub mod beam_search;
+pub mod constrained;
+pub mod logprobs;
+pub mod processors;
+pub mod speculative;
 
 #[derive(Clone, PartialEq, Debug)]
 pub enum Sampling {
@@ -59,20 +65,7 @@ impl LogitsProcessor {
     /// probability top_p. This way we never sample tokens that have very low probabilities and are
     /// less likely to go "off the rails".
     fn sample_topp(&mut self, prs: &mut Vec<f32>, top_p: f32) -> Result<u32> {
-        let mut argsort_indices = (0..prs.len()).collect::<Vec<_>>();
-
-        // Sort by descending probability.
-        argsort_indices.sort_by(|&i, &j| prs[j].total_cmp(&prs[i]));
-
-        // Clamp smaller probabilities to zero.
-        let mut cumsum = 0.;
-        for index in &argsort_indices {
-            if cumsum >= top_p {
-                prs[*index] = 0.0;
-            } else {
-                cumsum += prs[*index];
-            }
-        }
+        clamp_topp(prs, top_p);
         // Sample with clamped probabilities.
         self.sample_multinomial(prs)
     }
@@ -111,10 +104,85 @@ impl LogitsProcessor {
         }
     }
 
+    pub fn sampling(&self) -> &Sampling {
+        &self.sampling
+    }
+
+    /// Returns the probability distribution that [`Self::sample`] draws the next token from.
+    ///
+    /// This applies the temperature as well as the top-k and top-p filtering, for `ArgMax` all
+    /// the probability mass is on the most likely token.
+    pub fn probs(&self, logits: &Tensor) -> Result<Vec<f32>> {
+        let logits = logits.to_dtype(DType::F32)?;
+        let softmax = |temperature: f64| -> Result<Vec<f32>> {
+            candle_nn::ops::softmax_last_dim(&(&logits / temperature)?)?.to_vec1()
+        };
+        let mut prs = match &self.sampling {
+            Sampling::ArgMax => {
+                let mut prs = vec![0f32; logits.dim(candle::D::Minus1)?];
+                let index = logits.argmax(candle::D::Minus1)?.to_scalar::<u32>()?;
+                prs[index as usize] = 1.;
+                return Ok(prs);
+            }
+            // The Gumbel-max trick samples from the softmax distribution.
+            Sampling::All { temperature } | Sampling::GumbelSoftmax { temperature } => {
+                return softmax(*temperature)
+            }
+            Sampling::TopP { p, temperature } => {
+                let mut prs = softmax(*temperature)?;
+                if *p > 0.0 && *p < 1.0 {
+                    clamp_topp(&mut prs, *p as f32)
+                }
+                prs
+            }
+            Sampling::TopK { k, temperature } => {
+                let mut prs = softmax(*temperature)?;
+                clamp_topk(&mut prs, *k);
+                prs
+            }
+            Sampling::TopKThenTopP { k, p, temperature } => {
+                let mut prs = softmax(*temperature)?;
+                clamp_topk(&mut prs, *k);
+                let sum_p = prs.iter().sum::<f32>();
+                if *p > 0.0 && (*p as f32) < sum_p {
+                    clamp_topp(&mut prs, *p as f32)
+                }
+                prs
+            }
+        };
+        let sum_p = prs.iter().sum::<f32>();
+        prs.iter_mut().for_each(|p| *p /= sum_p);
+        Ok(prs)
+    }
+
+    /// Samples a token from the given probabilities, these do not have to be normalized.
+    pub fn sample_from_probs(&mut self, prs: &[f32]) -> Result<u32> {
+        let distr = rand::distr::weighted::WeightedIndex::new(prs).map_err(Error::wrap)?;
+        Ok(distr.sample(&mut self.rng) as u32)
+    }
+
+    /// Draws a number uniformly in `[0, 1)` using the processor rng.
+    pub(crate) fn uniform(&mut self) -> f32 {
+        self.rng.random()
+    }
+
     pub fn sample(&mut self, logits: &Tensor) -> Result<u32> {
         self.sample_f(logits, |_| {})
     }
 
+    /// Samples a token and returns it with its log-probability and the `top_n` most likely
+    /// tokens. The log-probabilities are computed from `logits` before the temperature and the
+    /// filtering are applied.
+    pub fn sample_with_logprobs(
+        &mut self,
+        logits: &Tensor,
+        top_n: usize,
+    ) -> Result<logprobs::SampledToken> {
+        let logprobs = logprobs::log_softmax(logits)?;
+        let token = self.sample(logits)?;
+        Ok(logprobs::SampledToken::new(token, &logprobs, top_n))
+    }
+
     pub fn sample_f(&mut self, logits: &Tensor, f: impl FnOnce(&mut [f32])) -> Result<u32> {
         let logits = logits.to_dtype(DType::F32)?;
         let prs = |temperature: f64| -> Result<Vec<f32>> {
@@ -156,3 +224,33 @@ impl LogitsProcessor {
         Ok(next_token)
     }
 }
+
+// Sets the probabilities outside of the smallest set of tokens exceeding `top_p` to zero.
+fn clamp_topp(prs: &mut [f32], top_p: f32) {
+    let mut argsort_indices = (0..prs.len()).collect::<Vec<_>>();
+
+    // Sort by descending probability.
+    argsort_indices.sort_by(|&i, &j| prs[j].total_cmp(&prs[i]));
+
+    // Clamp smaller probabilities to zero.
+    let mut cumsum = 0.;
+    for index in &argsort_indices {
+        if cumsum >= top_p {
+            prs[*index] = 0.0;
+        } else {
+            cumsum += prs[*index];
+        }
+    }
+}
+
+// Sets the probabilities outside of the `top_k` largest ones to zero.
+fn clamp_topk(prs: &mut [f32], top_k: usize) {
+    if top_k >= prs.len() {
+        return;
+    }
+    let mut argsort_indices = (0..prs.len()).collect::<Vec<_>>();
+    argsort_indices.select_nth_unstable_by(top_k, |&i, &j| prs[j].total_cmp(&prs[i]));
+    for &index in &argsort_indices[top_k..] {
+        prs[index] = 0.0;
+    }
+}
@@ -0,0 +1,478 @@
+//! Composable logits processors.
+//!
+//! A [`LogitsPipeline`] applies a sequence of [`Processor`] to the logits produced by a model
+//! before sampling the next token with a [`LogitsProcessor`]. Processors can penalize repetitions,
+//! bias or ban some tokens, change the temperature, or filter out unlikely tokens by setting their
+//! logits to `-inf`.
+//!
+//! ```ignore
+//! use candle_transformers::generation::processors::*;
+//!
+//! let mut pipeline = LogitsPipeline::new(42)
+//!     .with(RepetitionPenalty::new(1.1, 64))
+//!     .with(MinLength::new(16, vec![eos_token]))
+//!     .with(Temperature::new(0.7))
+//!     .with(MinP(0.05));
+//! let next_token = pipeline.sample(&logits, &tokens, prompt_len)?;
+//! ```
+use super::logprobs::{log_softmax, SampledToken};
+use super::{LogitsProcessor, Sampling};
+use candle::{DType, Device, Result, Tensor};
+use std::collections::{HashMap, HashSet};
+
+/// The sequence being generated.
+#[derive(Debug, Clone, Copy)]
+pub struct Context<'a> {
+    /// The prompt followed by the tokens generated so far.
+    pub tokens: &'a [u32],
+    pub prompt_len: usize,
+}
+
+impl Context<'_> {
+    pub fn generated(&self) -> &[u32] {
+        &self.tokens[self.prompt_len.min(self.tokens.len())..]
+    }
+
+    pub fn num_generated(&self) -> usize {
+        self.generated().len()
+    }
+}
+
+/// A transformation of the logits for the next token.
+pub trait Processor: Send {
+    fn apply(&mut self, logits: &mut [f32], ctx: &Context) -> Result<()>;
+}
+
+// Returns the probabilities for the logits, `-inf` logits get a zero probability.
+fn softmax(logits: &[f32]) -> Vec<f32> {
+    let max = logits.iter().copied().fold(f32::NEG_INFINITY, f32::max);
+    let mut prs: Vec<f32> = logits.iter().map(|&l| (l - max).exp()).collect();
+    let sum = prs.iter().sum::<f32>();
+    prs.iter_mut().for_each(|p| *p /= sum);
+    prs
+}
+
+// Bans the tokens for which `keep` is false, the most likely token is always kept.
+fn retain(logits: &mut [f32], prs: &[f32], keep: impl Fn(usize, f32) -> bool) {
+    let best = prs
+        .iter()
+        .enumerate()
+        .max_by(|(_, a), (_, b)| a.total_cmp(b))
+        .map(|(i, _)| i);
+    for (i, (logit, &p)) in logits.iter_mut().zip(prs.iter()).enumerate() {
+        if Some(i) != best && !keep(i, p) {
+            *logit = f32::NEG_INFINITY
+        }
+    }
+}
+
+// The entropy of a distribution in nats.
+fn entropy(prs: &[f32]) -> f32 {
+    -prs.iter()
+        .filter(|&&p| p > 0.)
+        .map(|&p| p * p.ln())
+        .sum::<f32>()
+}
+
+/// Penalizes the tokens that appear in the last `last_n` tokens of the context, including the
+/// prompt, see [`crate::utils::apply_repeat_penalty`].
+#[derive(Debug, Clone)]
+pub struct RepetitionPenalty {
+    penalty: f32,
+    last_n: usize,
+}
+
+impl RepetitionPenalty {
+    pub fn new(penalty: f32, last_n: usize) -> Self {
+        Self { penalty, last_n }
+    }
+}
+
+impl Processor for RepetitionPenalty {
+    fn apply(&mut self, logits: &mut [f32], ctx: &Context) -> Result<()> {
+        let start_at = ctx.tokens.len().saturating_sub(self.last_n);
+        let values = Tensor::from_slice(logits, logits.len(), &Device::Cpu)?;
+        let values =
+            crate::utils::apply_repeat_penalty(&values, self.penalty, &ctx.tokens[start_at..])?;
+        logits.copy_from_slice(&values.to_vec1::<f32>()?);
+        Ok(())
+    }
+}
+
+/// Subtracts `penalty` times the number of occurrences of each token in the generated tokens.
+#[derive(Debug, Clone)]
+pub struct FrequencyPenalty(pub f32);
+
+impl Processor for FrequencyPenalty {
+    fn apply(&mut self, logits: &mut [f32], ctx: &Context) -> Result<()> {
+        for &token in ctx.generated() {
+            if let Some(logit) = logits.get_mut(token as usize) {
+                *logit -= self.0
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Subtracts `penalty` from the tokens that appear at least once in the generated tokens.
+#[derive(Debug, Clone)]
+pub struct PresencePenalty(pub f32);
+
+impl Processor for PresencePenalty {
+    fn apply(&mut self, logits: &mut [f32], ctx: &Context) -> Result<()> {
+        let seen: HashSet<u32> = ctx.generated().iter().copied().collect();
+        for token in seen {
+            if let Some(logit) = logits.get_mut(token as usize) {
+                *logit -= self.0
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Bans the tokens that would repeat an n-gram of size `n` already present in the context.
+#[derive(Debug, Clone)]
+pub struct NoRepeatNgram(pub usize);
+
+impl Processor for NoRepeatNgram {
+    fn apply(&mut self, logits: &mut [f32], ctx: &Context) -> Result<()> {
+        let n = self.0;
+        let tokens = ctx.tokens;
+        if n == 0 || tokens.len() < n {
+            return Ok(());
+        }
+        let prefix = &tokens[tokens.len() + 1 - n..];
+        for ngram in tokens.windows(n) {
+            if &ngram[..n - 1] == prefix {
+                if let Some(logit) = logits.get_mut(ngram[n - 1] as usize) {
+                    *logit = f32::NEG_INFINITY
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Adds a fixed bias to the logits of some tokens.
+#[derive(Debug, Clone)]
+pub struct LogitBias(pub HashMap<u32, f32>);
+
+impl Processor for LogitBias {
+    fn apply(&mut self, logits: &mut [f32], _ctx: &Context) -> Result<()> {
+        for (&token, &bias) in self.0.iter() {
+            if let Some(logit) = logits.get_mut(token as usize) {
+                *logit += bias
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Prevents some tokens from being sampled.
+#[derive(Debug, Clone)]
+pub struct BannedTokens(pub Vec<u32>);
+
+impl Processor for BannedTokens {
+    fn apply(&mut self, logits: &mut [f32], _ctx: &Context) -> Result<()> {
+        for &token in self.0.iter() {
+            if let Some(logit) = logits.get_mut(token as usize) {
+                *logit = f32::NEG_INFINITY
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Prevents the end-of-sequence tokens from being sampled before `min_new_tokens` tokens have
+/// been generated.
+#[derive(Debug, Clone)]
+pub struct MinLength {
+    min_new_tokens: usize,
+    eos_tokens: Vec<u32>,
+}
+
+impl MinLength {
+    pub fn new(min_new_tokens: usize, eos_tokens: Vec<u32>) -> Self {
+        Self {
+            min_new_tokens,
+            eos_tokens,
+        }
+    }
+}
+
+impl Processor for MinLength {
+    fn apply(&mut self, logits: &mut [f32], ctx: &Context) -> Result<()> {
+        if ctx.num_generated() < self.min_new_tokens {
+            BannedTokens(self.eos_tokens.clone()).apply(logits, ctx)?
+        }
+        Ok(())
+    }
+}
+
+/// Divides the logits by a fixed temperature.
+#[derive(Debug, Clone)]
+pub struct Temperature(f64);
+
+impl Temperature {
+    pub fn new(temperature: f64) -> Self {
+        Self(temperature)
+    }
+}
+
+impl Processor for Temperature {
+    fn apply(&mut self, logits: &mut [f32], _ctx: &Context) -> Result<()> {
+        if self.0 <= 0. {
+            candle::bail!("temperature has to be positive, got {}", self.0)
+        }
+        let t = self.0 as f32;
+        logits.iter_mut().for_each(|l| *l /= t);
+        Ok(())
+    }
+}
+
+/// A temperature that evolves with the number of generated tokens, going linearly from `start`
+/// to `end` over the first `num_tokens` tokens and staying at `end` afterwards.
+#[derive(Debug, Clone)]
+pub struct TemperatureSchedule {
+    start: f64,
+    end: f64,
+    num_tokens: usize,
+}
+
+impl TemperatureSchedule {
+    pub fn linear(start: f64, end: f64, num_tokens: usize) -> Self {
+        Self {
+            start,
+            end,
+            num_tokens,
+        }
+    }
+
+    /// The temperature used for the token following `num_generated` generated tokens.
+    pub fn temperature(&self, num_generated: usize) -> f64 {
+        if num_generated >= self.num_tokens {
+            self.end
+        } else {
+            let alpha = num_generated as f64 / self.num_tokens as f64;
+            self.start + (self.end - self.start) * alpha
+        }
+    }
+}
+
+impl Processor for TemperatureSchedule {
+    fn apply(&mut self, logits: &mut [f32], ctx: &Context) -> Result<()> {
+        Temperature(self.temperature(ctx.num_generated())).apply(logits, ctx)
+    }
+}
+
+/// Keeps the `k` most likely tokens.
+#[derive(Debug, Clone)]
+pub struct TopK(pub usize);
+
+impl Processor for TopK {
+    fn apply(&mut self, logits: &mut [f32], _ctx: &Context) -> Result<()> {
+        if self.0 >= logits.len() {
+            return Ok(());
+        }
+        let mut indices = (0..logits.len()).collect::<Vec<_>>();
+        indices.select_nth_unstable_by(self.0, |&i, &j| logits[j].total_cmp(&logits[i]));
+        for &i in indices[self.0.max(1)..].iter() {
+            logits[i] = f32::NEG_INFINITY
+        }
+        Ok(())
+    }
+}
+
+/// Keeps the smallest set of most likely tokens whose probabilities add up to at least `p`.
+#[derive(Debug, Clone)]
+pub struct TopP(pub f32);
+
+impl Processor for TopP {
+    fn apply(&mut self, logits: &mut [f32], _ctx: &Context) -> Result<()> {
+        let prs = softmax(logits);
+        let mut indices = (0..prs.len()).collect::<Vec<_>>();
+        indices.sort_by(|&i, &j| prs[j].total_cmp(&prs[i]));
+        let mut cumsum = 0.;
+        for &i in indices.iter() {
+            if cumsum >= self.0 {
+                logits[i] = f32::NEG_INFINITY
+            } else {
+                cumsum += prs[i]
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Removes the tokens whose probability is below `p` times the probability of the most likely
+/// token, see [Nguyen et al.](https://arxiv.org/abs/2407.01082).
+#[derive(Debug, Clone)]
+pub struct MinP(pub f32);
+
+impl Processor for MinP {
+    fn apply(&mut self, logits: &mut [f32], _ctx: &Context) -> Result<()> {
+        let prs = softmax(logits);
+        let threshold = self.0 * prs.iter().copied().fold(0., f32::max);
+        retain(logits, &prs, |_, p| p >= threshold);
+        Ok(())
+    }
+}
+
+/// Locally typical sampling, keeps the tokens whose information content is the closest to the
+/// entropy of the distribution until their probabilities add up to `mass`, see
+/// [Meister et al.](https://arxiv.org/abs/2202.00666).
+#[derive(Debug, Clone)]
+pub struct TypicalP(pub f32);
+
+impl Processor for TypicalP {
+    fn apply(&mut self, logits: &mut [f32], _ctx: &Context) -> Result<()> {
+        let prs = softmax(logits);
+        let entropy = entropy(&prs);
+        let surprise = |p: f32| (-p.ln() - entropy).abs();
+        let mut indices = (0..prs.len()).filter(|&i| prs[i] > 0.).collect::<Vec<_>>();
+        indices.sort_by(|&i, &j| surprise(prs[i]).total_cmp(&surprise(prs[j])));
+        let mut keep = vec![false; prs.len()];
+        let mut cumsum = 0.;
+        for &i in indices.iter() {
+            keep[i] = true;
+            cumsum += prs[i];
+            if cumsum >= self.0 {
+                break;
+            }
+        }
+        retain(logits, &prs, |i, _| keep[i]);
+        Ok(())
+    }
+}
+
+/// Removes the tokens with a probability below `epsilon`, see
+/// [Hewitt et al.](https://arxiv.org/abs/2210.15191).
+#[derive(Debug, Clone)]
+pub struct EpsilonCutoff(pub f32);
+
+impl Processor for EpsilonCutoff {
+    fn apply(&mut self, logits: &mut [f32], _ctx: &Context) -> Result<()> {
+        let prs = softmax(logits);
+        retain(logits, &prs, |_, p| p >= self.0);
+        Ok(())
+    }
+}
+
+/// Eta sampling, an entropy dependent variant of [`EpsilonCutoff`] using the threshold
+/// `min(eta, sqrt(eta) * exp(-entropy))`.
+#[derive(Debug, Clone)]
+pub struct EtaCutoff(pub f32);
+
+impl Processor for EtaCutoff {
+    fn apply(&mut self, logits: &mut [f32], _ctx: &Context) -> Result<()> {
+        let prs = softmax(logits);
+        let threshold = self.0.min(self.0.sqrt() * (-entropy(&prs)).exp());
+        retain(logits, &prs, |_, p| p >= threshold);
+        Ok(())
+    }
+}
+
+// Applies the processors in order, shared with the beam and contrastive searches.
+pub(crate) fn apply_processors(
+    processors: &mut [Box<dyn Processor>],
+    logits: &mut [f32],
+    tokens: &[u32],
+    prompt_len: usize,
+) -> Result<()> {
+    let ctx = Context { tokens, prompt_len };
+    for processor in processors.iter_mut() {
+        processor.apply(logits, &ctx)?
+    }
+    Ok(())
+}
+
+/// A chain of processors followed by a sampler.
+pub struct LogitsPipeline {
+    processors: Vec<Box<dyn Processor>>,
+    sampler: LogitsProcessor,
+}
+
+impl LogitsPipeline {
+    /// Samples from the processed logits without any further transformation, the temperature and
+    /// the filtering are expected to be handled by processors.
+    pub fn new(seed: u64) -> Self {
+        Self::from_logits_processor(LogitsProcessor::from_sampling(
+            seed,
+            Sampling::All { temperature: 1. },
+        ))
+    }
+
+    /// Picks the most likely token after processing.
+    pub fn greedy() -> Self {
+        Self::from_logits_processor(LogitsProcessor::from_sampling(0, Sampling::ArgMax))
+    }
+
+    /// Samples from the processed logits using an existing logits processor.
+    pub fn from_logits_processor(sampler: LogitsProcessor) -> Self {
+        Self {
+            processors: vec![],
+            sampler,
+        }
+    }
+
+    pub fn with<P: Processor + 'static>(mut self, processor: P) -> Self {
+        self.push(processor);
+        self
+    }
+
+    pub fn push<P: Processor + 'static>(&mut self, processor: P) {
+        self.processors.push(Box::new(processor))
+    }
+
+    pub fn len(&self) -> usize {
+        self.processors.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.processors.is_empty()
+    }
+
+    pub fn sampler(&self) -> &LogitsProcessor {
+        &self.sampler
+    }
+
+    pub fn sampler_mut(&mut self) -> &mut LogitsProcessor {
+        &mut self.sampler
+    }
+
+    /// Applies the processors to a one dimensional logits tensor.
+    pub fn process(
+        &mut self,
+        logits: &Tensor,
+        tokens: &[u32],
+        prompt_len: usize,
+    ) -> Result<Tensor> {
+        let mut values = logits.to_dtype(DType::F32)?.to_vec1::<f32>()?;
+        apply_processors(&mut self.processors, &mut values, tokens, prompt_len)?;
+        if values.iter().all(|v| *v == f32::NEG_INFINITY) {
+            candle::bail!("all the tokens have been filtered out by the logits processors")
+        }
+        Tensor::from_vec(values, logits.dims(), logits.device())
+    }
+
+    /// Processes the logits and samples the next token, `tokens` contains the prompt followed by
+    /// the tokens generated so far.
+    pub fn sample(&mut self, logits: &Tensor, tokens: &[u32], prompt_len: usize) -> Result<u32> {
+        let logits = self.process(logits, tokens, prompt_len)?;
+        self.sampler.sample(&logits)
+    }
+
+    /// Same as [`Self::sample`] but also returns the log-probability of the sampled token and
+    /// the `top_n` most likely tokens, computed from the logits before processing.
+    pub fn sample_with_logprobs(
+        &mut self,
+        logits: &Tensor,
+        tokens: &[u32],
+        prompt_len: usize,
+        top_n: usize,
+    ) -> Result<SampledToken> {
+        let logprobs = log_softmax(logits)?;
+        let token = self.sample(logits, tokens, prompt_len)?;
+        Ok(SampledToken::new(token, &logprobs, top_n))
+    }
+}
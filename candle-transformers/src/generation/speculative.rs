@@ -0,0 +1,340 @@
+//! Speculative decoding.
+//!
+//! A cheap [`Proposer`] guesses the next few tokens, either by running a small draft model or by
+//! looking up n-grams in the sequence generated so far, and the target model scores all of them
+//! in a single forward pass. The proposals are accepted or rejected using the rejection sampling
+//! scheme from [Leviathan et al.](https://arxiv.org/abs/2211.17192) so that the generated tokens
+//! follow exactly the same distribution as when sampling from the target model one token at a
+//! time. The kv-caches are truncated to drop the entries of the rejected tokens.
+//!
+//! ```ignore
+//! use candle_transformers::generation::{speculative, LogitsProcessor, Sampling};
+//!
+//! let sampling = Sampling::All { temperature: 0.8 };
+//! let draft_processor = LogitsProcessor::from_sampling(42, sampling.clone());
+//! let proposer = speculative::DraftModel::new(draft, draft_processor, &device);
+//! let logits_processor = LogitsProcessor::from_sampling(299792458, sampling);
+//! let mut decoder =
+//!     speculative::SpeculativeDecoder::new(target, proposer, logits_processor, 4, &device);
+//! let tokens = decoder.generate(&prompt, 256)?;
+//! ```
+use super::LogitsProcessor;
+use crate::models::{quantized_llama, quantized_qwen3};
+use candle::{Device, IndexOp, Result, Tensor};
+
+/// A model with a kv-cache that can be rolled back.
+pub trait SpeculativeModel {
+    /// Processes the `(b_sz, seq_len)` input tokens starting at position `index_pos` and returns
+    /// the logits for all the positions with shape `(b_sz, seq_len, vocab_size)`.
+    fn forward_all(&mut self, input: &Tensor, index_pos: usize) -> Result<Tensor>;
+
+    /// Drops the kv-cache entries after the first `len` positions.
+    fn truncate_kv_cache(&mut self, len: usize) -> Result<()>;
+}
+
+impl SpeculativeModel for quantized_llama::ModelWeights {
+    fn forward_all(&mut self, input: &Tensor, index_pos: usize) -> Result<Tensor> {
+        self.forward_all(input, index_pos)
+    }
+
+    fn truncate_kv_cache(&mut self, len: usize) -> Result<()> {
+        self.truncate_kv_cache(len)
+    }
+}
+
+impl SpeculativeModel for quantized_qwen3::ModelWeights {
+    fn forward_all(&mut self, input: &Tensor, index_pos: usize) -> Result<Tensor> {
+        self.forward_all(input, index_pos)
+    }
+
+    fn truncate_kv_cache(&mut self, len: usize) -> Result<()> {
+        self.truncate_kv_cache(len)
+    }
+}
+
+/// A proposed token.
+#[derive(Debug, Clone)]
+pub struct Proposal {
+    pub token: u32,
+    /// The distribution the token was sampled from, `None` if the token was picked
+    /// deterministically.
+    pub probs: Option<Vec<f32>>,
+}
+
+/// Proposes the tokens to be verified by the target model.
+pub trait Proposer {
+    /// Returns at most `k` tokens that are likely to follow `tokens`, the prompt followed by the
+    /// tokens generated so far.
+    fn propose(&mut self, tokens: &[u32], k: usize) -> Result<Vec<Proposal>>;
+
+    /// Called with the updated sequence once the proposals have been verified.
+    fn accept(&mut self, _tokens: &[u32]) -> Result<()> {
+        Ok(())
+    }
+}
+
+/// Proposes tokens by sampling from a small draft model.
+pub struct DraftModel<M: SpeculativeModel> {
+    model: M,
+    logits_processor: LogitsProcessor,
+    // The tokens currently stored in the draft model kv-cache.
+    cached: Vec<u32>,
+    device: Device,
+}
+
+impl<M: SpeculativeModel> DraftModel<M> {
+    pub fn new(model: M, logits_processor: LogitsProcessor, device: &Device) -> Self {
+        Self {
+            model,
+            logits_processor,
+            cached: vec![],
+            device: device.clone(),
+        }
+    }
+
+    pub fn model(&self) -> &M {
+        &self.model
+    }
+
+    // Processes the tokens that are not in the kv-cache yet and returns the logits for the last
+    // one.
+    fn forward(&mut self, tokens: &[u32]) -> Result<Tensor> {
+        let index_pos = self.cached.len();
+        let input = Tensor::new(&tokens[index_pos..], &self.device)?.unsqueeze(0)?;
+        let logits = self.model.forward_all(&input, index_pos)?.i(0)?;
+        self.cached.extend_from_slice(&tokens[index_pos..]);
+        logits.i(logits.dim(0)? - 1)
+    }
+}
+
+impl<M: SpeculativeModel> Proposer for DraftModel<M> {
+    fn propose(&mut self, tokens: &[u32], k: usize) -> Result<Vec<Proposal>> {
+        self.accept(tokens)?;
+        if self.cached.len() == tokens.len() {
+            // At least one token has to be processed to get some logits.
+            self.model.truncate_kv_cache(tokens.len() - 1)?;
+            self.cached.pop();
+        }
+        let mut tokens = tokens.to_vec();
+        let mut proposals = Vec::with_capacity(k);
+        for _ in 0..k {
+            let logits = self.forward(&tokens)?;
+            let probs = self.logits_processor.probs(&logits)?;
+            let token = self.logits_processor.sample_from_probs(&probs)?;
+            tokens.push(token);
+            proposals.push(Proposal {
+                token,
+                probs: Some(probs),
+            })
+        }
+        Ok(proposals)
+    }
+
+    fn accept(&mut self, tokens: &[u32]) -> Result<()> {
+        let common = self
+            .cached
+            .iter()
+            .zip(tokens.iter())
+            .take_while(|(a, b)| a == b)
+            .count();
+        if common < self.cached.len() {
+            self.model.truncate_kv_cache(common)?;
+            self.cached.truncate(common);
+        }
+        Ok(())
+    }
+}
+
+/// Proposes tokens by looking up the last generated n-gram earlier in the sequence and copying
+/// the tokens that followed it, also known as prompt lookup decoding. This works well when the
+/// output repeats parts of the prompt, e.g. for summarization or code editing.
+#[derive(Debug, Clone)]
+pub struct NgramProposer {
+    min_n: usize,
+    max_n: usize,
+}
+
+impl NgramProposer {
+    /// Matches n-grams of sizes `min_n` to `max_n`, longer matches are tried first.
+    pub fn new(min_n: usize, max_n: usize) -> Result<Self> {
+        if min_n == 0 || min_n > max_n {
+            candle::bail!("invalid n-gram sizes, min {min_n}, max {max_n}")
+        }
+        Ok(Self { min_n, max_n })
+    }
+}
+
+impl Proposer for NgramProposer {
+    fn propose(&mut self, tokens: &[u32], k: usize) -> Result<Vec<Proposal>> {
+        for n in (self.min_n..=self.max_n).rev() {
+            if n >= tokens.len() {
+                continue;
+            }
+            let suffix = &tokens[tokens.len() - n..];
+            // Look for the most recent earlier occurrence of the suffix.
+            let found = (0..tokens.len() - n)
+                .rev()
+                .find(|&start| &tokens[start..start + n] == suffix);
+            if let Some(start) = found {
+                let from = start + n;
+                let to = (from + k).min(tokens.len());
+                let proposals = tokens[from..to]
+                    .iter()
+                    .map(|&token| Proposal { token, probs: None })
+                    .collect();
+                return Ok(proposals);
+            }
+        }
+        Ok(vec![])
+    }
+}
+
+/// Generates tokens from a target model using speculative decoding.
+pub struct SpeculativeDecoder<M: SpeculativeModel, P: Proposer> {
+    target: M,
+    proposer: P,
+    logits_processor: LogitsProcessor,
+    num_speculative_tokens: usize,
+    tokens: Vec<u32>,
+    // The number of tokens stored in the target model kv-cache.
+    cache_len: usize,
+    num_proposed: usize,
+    num_accepted: usize,
+    device: Device,
+}
+
+impl<M: SpeculativeModel, P: Proposer> SpeculativeDecoder<M, P> {
+    /// Creates a decoder verifying up to `num_speculative_tokens` proposed tokens per step, the
+    /// tokens are sampled using `logits_processor`.
+    pub fn new(
+        target: M,
+        proposer: P,
+        logits_processor: LogitsProcessor,
+        num_speculative_tokens: usize,
+        device: &Device,
+    ) -> Self {
+        Self {
+            target,
+            proposer,
+            logits_processor,
+            num_speculative_tokens,
+            tokens: vec![],
+            cache_len: 0,
+            num_proposed: 0,
+            num_accepted: 0,
+            device: device.clone(),
+        }
+    }
+
+    pub fn target(&self) -> &M {
+        &self.target
+    }
+
+    pub fn proposer(&self) -> &P {
+        &self.proposer
+    }
+
+    /// The prompt followed by the generated tokens.
+    pub fn tokens(&self) -> &[u32] {
+        &self.tokens
+    }
+
+    /// The fraction of the proposed tokens that have been accepted.
+    pub fn acceptance_rate(&self) -> f64 {
+        if self.num_proposed == 0 {
+            0.
+        } else {
+            self.num_accepted as f64 / self.num_proposed as f64
+        }
+    }
+
+    /// Starts a new sequence, this resets the target kv-cache.
+    pub fn set_prompt(&mut self, prompt: &[u32]) -> Result<()> {
+        if prompt.is_empty() {
+            candle::bail!("empty prompt")
+        }
+        self.target.truncate_kv_cache(0)?;
+        self.tokens = prompt.to_vec();
+        self.cache_len = 0;
+        self.proposer.accept(&self.tokens)
+    }
+
+    /// Runs a single target forward pass and returns the new tokens, this includes the accepted
+    /// proposals followed by one token sampled from the target model.
+    pub fn step(&mut self) -> Result<Vec<u32>> {
+        if self.tokens.is_empty() {
+            candle::bail!("set_prompt has to be called before step")
+        }
+        let proposals = self
+            .proposer
+            .propose(&self.tokens, self.num_speculative_tokens)?;
+        let proposals = &proposals[..proposals.len().min(self.num_speculative_tokens)];
+        let len = self.tokens.len();
+        let mut input = self.tokens[self.cache_len..].to_vec();
+        input.extend(proposals.iter().map(|p| p.token));
+        let input = Tensor::new(input, &self.device)?.unsqueeze(0)?;
+        let logits = self.target.forward_all(&input, self.cache_len)?.i(0)?;
+        // The logits predicting the token at position `len`.
+        let offset = len - self.cache_len - 1;
+
+        let mut new_tokens = Vec::with_capacity(proposals.len() + 1);
+        let mut rejected = false;
+        for (i, proposal) in proposals.iter().enumerate() {
+            let p = self.logits_processor.probs(&logits.i(offset + i)?)?;
+            let token = proposal.token as usize;
+            let q = match &proposal.probs {
+                Some(q) => q[token],
+                None => 1.,
+            };
+            if self.logits_processor.uniform() < p[token] / q {
+                new_tokens.push(proposal.token);
+                continue;
+            }
+            // Resample from the residual distribution max(0, p - q).
+            let mut residual = p.clone();
+            match &proposal.probs {
+                Some(q) => residual.iter_mut().zip(q.iter()).for_each(|(r, q)| {
+                    *r = (*r - q).max(0.);
+                }),
+                None => residual[token] = 0.,
+            }
+            let token = if residual.iter().any(|&r| r > 0.) {
+                self.logits_processor.sample_from_probs(&residual)?
+            } else {
+                self.logits_processor.sample_from_probs(&p)?
+            };
+            new_tokens.push(token);
+            rejected = true;
+            break;
+        }
+        if !rejected {
+            // All the proposals were accepted, sample a bonus token from the last logits.
+            let p = self
+                .logits_processor
+                .probs(&logits.i(offset + proposals.len())?)?;
+            new_tokens.push(self.logits_processor.sample_from_probs(&p)?);
+        }
+        let num_accepted = new_tokens.len() - 1;
+        self.num_proposed += proposals.len();
+        self.num_accepted += num_accepted;
+
+        // Drop the kv-cache entries of the rejected proposals, the last new token is processed in
+        // the next step.
+        self.cache_len = len + num_accepted;
+        self.target.truncate_kv_cache(self.cache_len)?;
+        self.tokens.extend_from_slice(&new_tokens);
+        self.proposer.accept(&self.tokens)?;
+        Ok(new_tokens)
+    }
+
+    /// Generates `max_new_tokens` tokens following `prompt`.
+    pub fn generate(&mut self, prompt: &[u32], max_new_tokens: usize) -> Result<Vec<u32>> {
+        self.set_prompt(prompt)?;
+        let mut generated = vec![];
+        while generated.len() < max_new_tokens {
+            generated.extend(self.step()?)
+        }
+        generated.truncate(max_new_tokens);
+        Ok(generated)
+    }
+}
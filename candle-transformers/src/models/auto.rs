@@ -0,0 +1,527 @@
+//! Loading models from their architecture name.
+//!
+//! The [`Registry`] maps the architecture names found in a Hugging Face `config.json`, either
+//! the `architectures` list or the `model_type` field, and the `general.architecture` metadata of
+//! a GGUF file to the matching model implementation. The models are returned behind the
+//! [`CausalLM`] and [`Encoder`] traits so that they can be used without knowing the concrete
+//! type.
+//!
+//! ```ignore
+//! use candle_transformers::models::auto::Registry;
+//!
+//! let registry = Registry::default();
+//! let mut model = registry.load_causal_lm("Qwen3-0.6B", DType::BF16, &device)?;
+//! let logits = model.forward(&input_ids, 0)?;
+//!
+//! let mut file = std::fs::File::open("model.gguf")?;
+//! let mut model = registry.load_gguf(&mut file, &device)?;
+//! ```
+//!
+//! Additional architectures can be supported with [`Registry::register_causal_lm`],
+//! [`Registry::register_encoder`] and [`Registry::register_gguf`].
+use crate::models::{
+    bert, gemma, gemma2, gemma3, helium, llama, mistral, mixtral, modernbert, olmo2, phi3,
+    quantized_gemma3, quantized_llama, quantized_phi3, quantized_qwen2, quantized_qwen3,
+    quantized_qwen3_moe, qwen2, qwen2_moe, qwen3, qwen3_moe, smol::smollm3, starcoder2,
+    xlm_roberta,
+};
+use crate::pipelines::embeddings::EmbeddingModel;
+use candle::quantized::gguf_file;
+use candle::{DType, Device, IndexOp, Result, Tensor};
+use candle_nn::VarBuilder;
+use std::collections::HashMap;
+use std::io::{Read, Seek};
+use std::path::{Path, PathBuf};
+
+/// A decoder-only language model with a kv-cache.
+pub trait CausalLM: Send {
+    /// Processes `input_ids` with shape `(batch, seq_len)`, the first token being at `position`
+    /// in the sequence, and returns the logits for the last position with shape
+    /// `(batch, vocab_size)`.
+    fn forward(&mut self, input_ids: &Tensor, position: usize) -> Result<Tensor>;
+
+    /// Resets the kv-cache, the next call to `forward` should start at position 0.
+    fn clear_kv_cache(&mut self);
+
+    /// Same as [`CausalLM::forward`] but returns the logits for all the positions with shape
+    /// `(batch, seq_len, vocab_size)`. This is supported by all the safetensors models and by the
+    /// quantized llama and qwen3 models, the default implementation returns an error.
+    fn forward_all(&mut self, _input_ids: &Tensor, _position: usize) -> Result<Tensor> {
+        candle::bail!("this model only returns the logits for the last position")
+    }
+}
+
+/// An encoder-only model returning the hidden states of its last layer.
+pub trait Encoder: Send {
+    /// Returns the hidden states with shape `(batch, seq_len, hidden_size)`, the inputs are the
+    /// same as for [`EmbeddingModel::hidden_states`].
+    fn forward(
+        &mut self,
+        input_ids: &Tensor,
+        token_type_ids: &Tensor,
+        attention_mask: &Tensor,
+    ) -> Result<Tensor>;
+}
+
+impl EmbeddingModel for Box<dyn Encoder> {
+    fn hidden_states(
+        &mut self,
+        input_ids: &Tensor,
+        token_type_ids: &Tensor,
+        attention_mask: &Tensor,
+    ) -> Result<Tensor> {
+        self.as_mut()
+            .forward(input_ids, token_type_ids, attention_mask)
+    }
+}
+
+impl<M: EmbeddingModel + Send> Encoder for M {
+    fn forward(
+        &mut self,
+        input_ids: &Tensor,
+        token_type_ids: &Tensor,
+        attention_mask: &Tensor,
+    ) -> Result<Tensor> {
+        self.hidden_states(input_ids, token_type_ids, attention_mask)
+    }
+}
+
+// Some models return the logits with shape `(batch, 1, vocab_size)`.
+fn last_position(logits: Tensor) -> Result<Tensor> {
+    match logits.rank() {
+        3 => logits.i((.., logits.dim(1)? - 1)),
+        _ => Ok(logits),
+    }
+}
+
+macro_rules! impl_causal_lm {
+    (forward_all: $($model:ty),* $(,)?) => {
+        $(
+            impl CausalLM for $model {
+                fn forward(&mut self, input_ids: &Tensor, position: usize) -> Result<Tensor> {
+                    last_position(<$model>::forward(self, input_ids, position)?)
+                }
+
+                fn clear_kv_cache(&mut self) {
+                    <$model>::clear_kv_cache(self)
+                }
+
+                fn forward_all(&mut self, input_ids: &Tensor, position: usize) -> Result<Tensor> {
+                    <$model>::forward_all(self, input_ids, position)
+                }
+            }
+        )*
+    };
+    ($($model:ty),* $(,)?) => {
+        $(
+            impl CausalLM for $model {
+                fn forward(&mut self, input_ids: &Tensor, position: usize) -> Result<Tensor> {
+                    last_position(<$model>::forward(self, input_ids, position)?)
+                }
+
+                fn clear_kv_cache(&mut self) {
+                    <$model>::clear_kv_cache(self)
+                }
+            }
+        )*
+    };
+}
+
+impl_causal_lm!(
+    quantized_gemma3::ModelWeights,
+    quantized_phi3::ModelWeights,
+    quantized_qwen2::ModelWeights,
+    quantized_qwen3_moe::GGUFQWenMoE,
+);
+
+impl_causal_lm!(
+    forward_all: gemma::Model,
+    gemma2::Model,
+    gemma3::Model,
+    helium::Model,
+    mistral::Model,
+    mixtral::Model,
+    olmo2::Model,
+    phi3::Model,
+    qwen2::ModelForCausalLM,
+    qwen2_moe::Model,
+    qwen3::ModelForCausalLM,
+    qwen3_moe::ModelForCausalLM,
+    smollm3::ModelForCausalLM,
+    starcoder2::Model,
+    quantized_llama::ModelWeights,
+    quantized_qwen3::ModelWeights,
+);
+
+/// The llama model keeps its kv-cache outside of the model.
+struct Llama {
+    model: llama::Llama,
+    cache: llama::Cache,
+    config: llama::Config,
+    dtype: DType,
+    device: Device,
+}
+
+impl CausalLM for Llama {
+    fn forward(&mut self, input_ids: &Tensor, position: usize) -> Result<Tensor> {
+        self.model.forward(input_ids, position, &mut self.cache)
+    }
+
+    fn forward_all(&mut self, input_ids: &Tensor, position: usize) -> Result<Tensor> {
+        self.model.forward_all(input_ids, position, &mut self.cache)
+    }
+
+    fn clear_kv_cache(&mut self) {
+        // Creating the cache only allocates the rotary embeddings so this cannot fail once the
+        // model has been loaded.
+        if let Ok(cache) = llama::Cache::new(true, self.dtype, &self.config, &self.device) {
+            self.cache = cache
+        }
+    }
+}
+
+/// Builds a language model from the content of its `config.json` file.
+pub type CausalLMLoader = fn(&str, VarBuilder) -> Result<Box<dyn CausalLM>>;
+
+/// Builds an encoder from the content of its `config.json` file.
+pub type EncoderLoader = fn(&str, VarBuilder) -> Result<Box<dyn Encoder>>;
+
+/// Builds a quantized language model from the content of a GGUF file.
+pub type GgufLoader =
+    fn(gguf_file::Content, &mut dyn ReadSeek, &Device) -> Result<Box<dyn CausalLM>>;
+
+/// A reader for GGUF files, this is implemented for all the types implementing `Read` and `Seek`.
+pub trait ReadSeek: Read + Seek {}
+
+impl<R: Read + Seek> ReadSeek for R {}
+
+/// A model loaded by [`Registry::load`].
+pub enum AutoModel {
+    CausalLM(Box<dyn CausalLM>),
+    Encoder(Box<dyn Encoder>),
+}
+
+fn config<'a, C: serde::Deserialize<'a>>(config: &'a str) -> Result<C> {
+    serde_json::from_str(config).map_err(candle::Error::wrap)
+}
+
+/// The architecture names of a `config.json` file, in the order in which they are looked up.
+pub fn architecture_names(config: &str) -> Result<Vec<String>> {
+    #[derive(serde::Deserialize)]
+    struct Names {
+        #[serde(default)]
+        architectures: Vec<String>,
+        model_type: Option<String>,
+    }
+    let names: Names = self::config(config)?;
+    Ok(names
+        .architectures
+        .into_iter()
+        .chain(names.model_type)
+        .collect())
+}
+
+/// The weight files of a model directory, either listed in `model.safetensors.index.json` or all
+/// the `.safetensors` files.
+pub fn safetensors_files<P: AsRef<Path>>(dir: P) -> Result<Vec<PathBuf>> {
+    let dir = dir.as_ref();
+    let index = dir.join("model.safetensors.index.json");
+    let mut files: Vec<PathBuf> = if index.exists() {
+        let index: serde_json::Value = config(&std::fs::read_to_string(index)?)?;
+        let Some(weight_map) = index.get("weight_map").and_then(|m| m.as_object()) else {
+            candle::bail!("no weight_map in {dir:?}/model.safetensors.index.json")
+        };
+        weight_map
+            .values()
+            .filter_map(|file| file.as_str())
+            .map(|file| dir.join(file))
+            .collect()
+    } else {
+        std::fs::read_dir(dir)?
+            .map(|entry| Ok(entry?.path()))
+            .collect::<Result<Vec<_>>>()?
+            .into_iter()
+            .filter(|path| path.extension().is_some_and(|e| e == "safetensors"))
+            .collect()
+    };
+    files.sort();
+    files.dedup();
+    if files.is_empty() {
+        candle::bail!("no safetensors files in {dir:?}")
+    }
+    Ok(files)
+}
+
+/// The architectures supported by [`Registry::load_causal_lm`], [`Registry::load_encoder`] and
+/// [`Registry::load_gguf`].
+#[derive(Clone)]
+pub struct Registry {
+    causal_lms: HashMap<String, CausalLMLoader>,
+    encoders: HashMap<String, EncoderLoader>,
+    gguf: HashMap<String, GgufLoader>,
+}
+
+impl Default for Registry {
+    /// A registry with all the architectures supported by this crate.
+    fn default() -> Self {
+        let mut registry = Self::empty();
+        registry.register_causal_lm(&["LlamaForCausalLM", "llama"], |cfg, vb| {
+            let config = config::<llama::LlamaConfig>(cfg)?.into_config(false);
+            let cache = llama::Cache::new(true, vb.dtype(), &config, vb.device())?;
+            Ok(Box::new(Llama {
+                model: llama::Llama::load(vb.clone(), &config)?,
+                cache,
+                config,
+                dtype: vb.dtype(),
+                device: vb.device().clone(),
+            }))
+        });
+        registry.register_causal_lm(&["MistralForCausalLM", "mistral"], |cfg, vb| {
+            Ok(Box::new(mistral::Model::new(&config(cfg)?, vb)?))
+        });
+        registry.register_causal_lm(&["MixtralForCausalLM", "mixtral"], |cfg, vb| {
+            Ok(Box::new(mixtral::Model::new(&config(cfg)?, vb)?))
+        });
+        registry.register_causal_lm(&["Qwen2ForCausalLM", "qwen2"], |cfg, vb| {
+            Ok(Box::new(qwen2::ModelForCausalLM::new(&config(cfg)?, vb)?))
+        });
+        registry.register_causal_lm(&["Qwen2MoeForCausalLM", "qwen2_moe"], |cfg, vb| {
+            Ok(Box::new(qwen2_moe::Model::new(&config(cfg)?, vb)?))
+        });
+        registry.register_causal_lm(&["Qwen3ForCausalLM", "qwen3"], |cfg, vb| {
+            Ok(Box::new(qwen3::ModelForCausalLM::new(&config(cfg)?, vb)?))
+        });
+        registry.register_causal_lm(&["Qwen3MoeForCausalLM", "qwen3_moe"], |cfg, vb| {
+            Ok(Box::new(qwen3_moe::ModelForCausalLM::new(
+                &config(cfg)?,
+                vb,
+            )?))
+        });
+        registry.register_causal_lm(&["Phi3ForCausalLM", "phi3"], |cfg, vb| {
+            Ok(Box::new(phi3::Model::new(&config(cfg)?, vb)?))
+        });
+        registry.register_causal_lm(&["GemmaForCausalLM", "gemma"], |cfg, vb| {
+            Ok(Box::new(gemma::Model::new(false, &config(cfg)?, vb)?))
+        });
+        registry.register_causal_lm(&["Gemma2ForCausalLM", "gemma2"], |cfg, vb| {
+            Ok(Box::new(gemma2::Model::new(false, &config(cfg)?, vb)?))
+        });
+        registry.register_causal_lm(&["Gemma3ForCausalLM", "gemma3_text"], |cfg, vb| {
+            Ok(Box::new(gemma3::Model::new(false, &config(cfg)?, vb)?))
+        });
+        registry.register_causal_lm(&["Starcoder2ForCausalLM", "starcoder2"], |cfg, vb| {
+            Ok(Box::new(starcoder2::Model::new(&config(cfg)?, vb)?))
+        });
+        registry.register_causal_lm(&["Olmo2ForCausalLM", "olmo2"], |cfg, vb| {
+            Ok(Box::new(olmo2::Model::new(&config(cfg)?, vb)?))
+        });
+        registry.register_causal_lm(&["SmolLM3ForCausalLM", "smollm3"], |cfg, vb| {
+            Ok(Box::new(smollm3::ModelForCausalLM::new(&config(cfg)?, vb)?))
+        });
+        registry.register_causal_lm(&["HeliumForCausalLM", "helium"], |cfg, vb| {
+            Ok(Box::new(helium::Model::new(&config(cfg)?, vb)?))
+        });
+
+        registry.register_encoder(&["BertModel", "bert"], |cfg, vb| {
+            Ok(Box::new(bert::BertModel::load(vb, &config(cfg)?)?))
+        });
+        registry.register_encoder(&["XLMRobertaModel", "xlm-roberta"], |cfg, vb| {
+            Ok(Box::new(xlm_roberta::XLMRobertaModel::new(
+                &config(cfg)?,
+                vb,
+            )?))
+        });
+        registry.register_encoder(&["ModernBertModel", "modernbert"], |cfg, vb| {
+            Ok(Box::new(modernbert::ModernBert::load(vb, &config(cfg)?)?))
+        });
+
+        // The mistral and mixtral models use the llama architecture in GGUF files.
+        registry.register_gguf(&["llama"], |ct, mut reader, device| {
+            Ok(Box::new(quantized_llama::ModelWeights::from_gguf(
+                ct,
+                &mut reader,
+                device,
+            )?))
+        });
+        registry.register_gguf(&["qwen2"], |ct, mut reader, device| {
+            Ok(Box::new(quantized_qwen2::ModelWeights::from_gguf(
+                ct,
+                &mut reader,
+                device,
+            )?))
+        });
+        registry.register_gguf(&["qwen3"], |ct, mut reader, device| {
+            Ok(Box::new(quantized_qwen3::ModelWeights::from_gguf(
+                ct,
+                &mut reader,
+                device,
+            )?))
+        });
+        registry.register_gguf(&["qwen3moe"], |ct, mut reader, device| {
+            Ok(Box::new(quantized_qwen3_moe::GGUFQWenMoE::from_gguf(
+                ct,
+                &mut reader,
+                device,
+                DType::F32,
+            )?))
+        });
+        registry.register_gguf(&["phi3"], |ct, mut reader, device| {
+            Ok(Box::new(quantized_phi3::ModelWeights::from_gguf(
+                false,
+                ct,
+                &mut reader,
+                device,
+            )?))
+        });
+        registry.register_gguf(&["gemma3"], |ct, mut reader, device| {
+            Ok(Box::new(quantized_gemma3::ModelWeights::from_gguf(
+                ct,
+                &mut reader,
+                device,
+            )?))
+        });
+        registry
+    }
+}
+
+impl Registry {
+    /// A registry without any architecture.
+    pub fn empty() -> Self {
+        Self {
+            causal_lms: HashMap::new(),
+            encoders: HashMap::new(),
+            gguf: HashMap::new(),
+        }
+    }
+
+    /// Registers a language model under the given architecture names, this replaces the
+    /// existing loaders for these names.
+    pub fn register_causal_lm(&mut self, names: &[&str], loader: CausalLMLoader) {
+        for name in names {
+            self.causal_lms.insert(name.to_string(), loader);
+        }
+    }
+
+    pub fn register_encoder(&mut self, names: &[&str], loader: EncoderLoader) {
+        for name in names {
+            self.encoders.insert(name.to_string(), loader);
+        }
+    }
+
+    /// Registers a GGUF loader for the given `general.architecture` values.
+    pub fn register_gguf(&mut self, architectures: &[&str], loader: GgufLoader) {
+        for architecture in architectures {
+            self.gguf.insert(architecture.to_string(), loader);
+        }
+    }
+
+    fn find<L: Copy>(loaders: &HashMap<String, L>, config: &str) -> Result<Option<L>> {
+        let names = architecture_names(config)?;
+        Ok(names.iter().find_map(|name| loaders.get(name).copied()))
+    }
+
+    /// Returns true if the `config.json` content matches a registered language model.
+    pub fn is_causal_lm(&self, config: &str) -> Result<bool> {
+        Ok(Self::find(&self.causal_lms, config)?.is_some())
+    }
+
+    /// Returns true if the `config.json` content matches a registered encoder.
+    pub fn is_encoder(&self, config: &str) -> Result<bool> {
+        Ok(Self::find(&self.encoders, config)?.is_some())
+    }
+
+    /// Builds the language model matching the content of a `config.json` file.
+    pub fn causal_lm_from_config(&self, config: &str, vb: VarBuilder) -> Result<Box<dyn CausalLM>> {
+        match Self::find(&self.causal_lms, config)? {
+            Some(loader) => loader(config, vb),
+            None => candle::bail!(
+                "unsupported language model architecture {:?}",
+                architecture_names(config)?
+            ),
+        }
+    }
+
+    /// Builds the encoder matching the content of a `config.json` file.
+    pub fn encoder_from_config(&self, config: &str, vb: VarBuilder) -> Result<Box<dyn Encoder>> {
+        match Self::find(&self.encoders, config)? {
+            Some(loader) => loader(config, vb),
+            None => candle::bail!(
+                "unsupported encoder architecture {:?}",
+                architecture_names(config)?
+            ),
+        }
+    }
+
+    /// Loads a language model from a directory with a `config.json` file and safetensors
+    /// weights.
+    pub fn load_causal_lm<P: AsRef<Path>>(
+        &self,
+        dir: P,
+        dtype: DType,
+        device: &Device,
+    ) -> Result<Box<dyn CausalLM>> {
+        let (config, vb) = Self::read_dir(dir.as_ref(), dtype, device)?;
+        self.causal_lm_from_config(&config, vb)
+    }
+
+    /// Loads an encoder from a directory with a `config.json` file and safetensors weights.
+    pub fn load_encoder<P: AsRef<Path>>(
+        &self,
+        dir: P,
+        dtype: DType,
+        device: &Device,
+    ) -> Result<Box<dyn Encoder>> {
+        let (config, vb) = Self::read_dir(dir.as_ref(), dtype, device)?;
+        self.encoder_from_config(&config, vb)
+    }
+
+    /// Loads a model directory using the language model registrations first, then the encoder
+    /// ones.
+    pub fn load<P: AsRef<Path>>(&self, dir: P, dtype: DType, device: &Device) -> Result<AutoModel> {
+        let (config, vb) = Self::read_dir(dir.as_ref(), dtype, device)?;
+        if self.is_causal_lm(&config)? {
+            Ok(AutoModel::CausalLM(
+                self.causal_lm_from_config(&config, vb)?,
+            ))
+        } else {
+            Ok(AutoModel::Encoder(self.encoder_from_config(&config, vb)?))
+        }
+    }
+
+    fn read_dir<'a>(dir: &Path, dtype: DType, device: &Device) -> Result<(String, VarBuilder<'a>)> {
+        let config = match std::fs::read_to_string(dir.join("config.json")) {
+            Ok(config) => config,
+            Err(err) => candle::bail!("cannot read {dir:?}/config.json: {err}"),
+        };
+        let files = safetensors_files(dir)?;
+        let vb = unsafe { VarBuilder::from_mmaped_safetensors(&files, dtype, device)? };
+        Ok((config, vb))
+    }
+
+    /// Loads a quantized language model from a GGUF file.
+    pub fn load_gguf<R: Read + Seek>(
+        &self,
+        reader: &mut R,
+        device: &Device,
+    ) -> Result<Box<dyn CausalLM>> {
+        let content = gguf_file::Content::read(reader)?;
+        self.gguf_from_content(content, reader, device)
+    }
+
+    /// Builds the quantized language model matching the `general.architecture` metadata.
+    pub fn gguf_from_content<R: Read + Seek>(
+        &self,
+        content: gguf_file::Content,
+        reader: &mut R,
+        device: &Device,
+    ) -> Result<Box<dyn CausalLM>> {
+        let architecture = match content.metadata.get("general.architecture") {
+            Some(architecture) => architecture.to_string()?.clone(),
+            None => candle::bail!("no general.architecture in the gguf metadata"),
+        };
+        match self.gguf.get(&architecture) {
+            Some(loader) => loader(content, reader, device),
+            None => candle::bail!("unsupported gguf architecture {architecture}"),
+        }
+    }
+}
@@ -423,6 +423,29 @@ impl Model {
     }
 
     pub fn forward(&mut self, input_ids: &Tensor, seqlen_offset: usize) -> Result<Tensor> {
+        let xs = self.forward_hidden(input_ids, seqlen_offset)?;
+        let seq_len = xs.dim(1)?;
+        self.logits(&xs.narrow(1, seq_len - 1, 1)?)
+    }
+
+    /// Same as [`Self::forward`] but returns the logits for all the positions with shape
+    /// `(b_size, seq_len, vocab_size)`.
+    pub fn forward_all(&mut self, input_ids: &Tensor, seqlen_offset: usize) -> Result<Tensor> {
+        let xs = self.forward_hidden(input_ids, seqlen_offset)?;
+        self.logits(&xs)
+    }
+
+    fn logits(&self, xs: &Tensor) -> Result<Tensor> {
+        let logits = xs.apply(&self.norm)?.apply(&self.lm_head)?;
+        let logits = match self.final_logit_softcapping {
+            None => logits,
+            Some(sc) => ((logits / sc)?.tanh()? * sc)?,
+        };
+
+        Ok(logits)
+    }
+
+    fn forward_hidden(&mut self, input_ids: &Tensor, seqlen_offset: usize) -> Result<Tensor> {
         let (b_size, seq_len) = input_ids.dims2()?;
         let attention_mask = if seq_len <= 1 {
             None
@@ -435,16 +458,7 @@ impl Model {
         for layer in self.layers.iter_mut() {
             xs = layer.forward(&xs, attention_mask.as_ref(), seqlen_offset)?
         }
-        let logits = xs
-            .narrow(1, seq_len - 1, 1)?
-            .apply(&self.norm)?
-            .apply(&self.lm_head)?;
-        let logits = match self.final_logit_softcapping {
-            None => logits,
-            Some(sc) => ((logits / sc)?.tanh()? * sc)?,
-        };
-
-        Ok(logits)
+        Ok(xs)
     }
 
     pub fn clear_kv_cache(&mut self) {
@@ -371,6 +371,22 @@ impl Model {
     }
 
     pub fn forward(&mut self, input_ids: &Tensor, seqlen_offset: usize) -> Result<Tensor> {
+        let xs = self.forward_hidden(input_ids, seqlen_offset)?;
+        let seq_len = xs.dim(1)?;
+        xs.narrow(1, seq_len - 1, 1)?
+            .apply(&self.norm)?
+            .apply(&self.lm_head)
+    }
+
+    /// Same as [`Self::forward`] but returns the logits for all the positions with shape
+    /// `(b_size, seq_len, vocab_size)`.
+    pub fn forward_all(&mut self, input_ids: &Tensor, seqlen_offset: usize) -> Result<Tensor> {
+        self.forward_hidden(input_ids, seqlen_offset)?
+            .apply(&self.norm)?
+            .apply(&self.lm_head)
+    }
+
+    fn forward_hidden(&mut self, input_ids: &Tensor, seqlen_offset: usize) -> Result<Tensor> {
         let (_b_size, seq_len) = input_ids.dims2()?;
         let attention_mask = if seq_len <= 1 {
             None
@@ -382,9 +398,7 @@ impl Model {
         for layer in self.layers.iter_mut() {
             xs = layer.forward(&xs, attention_mask.as_ref(), seqlen_offset)?
         }
-        xs.narrow(1, seq_len - 1, 1)?
-            .apply(&self.norm)?
-            .apply(&self.lm_head)
+        Ok(xs)
     }
 
     pub fn clear_kv_cache(&mut self) {
@@ -160,42 +160,45 @@ fn calculate_default_inv_freq(cfg: &Config) -> Vec<f32> {
         .collect()
 }
 
+/// The inverse frequencies used by the rotary embeddings, including the llama 3 scaling.
+pub(crate) fn rope_inv_freq(config: &Config) -> Vec<f32> {
+    match &config.rope_scaling {
+        None
+        | Some(Llama3RopeConfig {
+            rope_type: Llama3RopeType::Default,
+            ..
+        }) => calculate_default_inv_freq(config),
+        Some(rope_scaling) => {
+            let low_freq_wavelen =
+                rope_scaling.original_max_position_embeddings as f32 / rope_scaling.low_freq_factor;
+            let high_freq_wavelen = rope_scaling.original_max_position_embeddings as f32
+                / rope_scaling.high_freq_factor;
+
+            calculate_default_inv_freq(config)
+                .into_iter()
+                .map(|freq| {
+                    let wavelen = 2. * PI / freq;
+                    if wavelen < high_freq_wavelen {
+                        freq
+                    } else if wavelen > low_freq_wavelen {
+                        freq / rope_scaling.factor
+                    } else {
+                        let smooth = (rope_scaling.original_max_position_embeddings as f32
+                            / wavelen
+                            - rope_scaling.low_freq_factor)
+                            / (rope_scaling.high_freq_factor - rope_scaling.low_freq_factor);
+                        (1. - smooth) * freq / rope_scaling.factor + smooth * freq
+                    }
+                })
+                .collect()
+        }
+    }
+}
+
 impl Cache {
     pub fn new(use_kv_cache: bool, dtype: DType, config: &Config, device: &Device) -> Result<Self> {
         // precompute freqs_cis
-        let theta = match &config.rope_scaling {
-            None
-            | Some(Llama3RopeConfig {
-                rope_type: Llama3RopeType::Default,
-                ..
-            }) => calculate_default_inv_freq(config),
-            Some(rope_scaling) => {
-                let low_freq_wavelen = rope_scaling.original_max_position_embeddings as f32
-                    / rope_scaling.low_freq_factor;
-                let high_freq_wavelen = rope_scaling.original_max_position_embeddings as f32
-                    / rope_scaling.high_freq_factor;
-
-                calculate_default_inv_freq(config)
-                    .into_iter()
-                    .map(|freq| {
-                        let wavelen = 2. * PI / freq;
-                        if wavelen < high_freq_wavelen {
-                            freq
-                        } else if wavelen > low_freq_wavelen {
-                            freq / rope_scaling.factor
-                        } else {
-                            let smooth = (rope_scaling.original_max_position_embeddings as f32
-                                / wavelen
-                                - rope_scaling.low_freq_factor)
-                                / (rope_scaling.high_freq_factor - rope_scaling.low_freq_factor);
-                            (1. - smooth) * freq / rope_scaling.factor + smooth * freq
-                        }
-                    })
-                    .collect::<Vec<_>>()
-            }
-        };
-
-        let theta = Tensor::new(theta, device)?;
+        let theta = Tensor::new(rope_inv_freq(config), device)?;
 
         let idx_theta = Tensor::arange(0, config.max_position_embeddings as u32, device)?
             .to_dtype(DType::F32)?
@@ -514,6 +517,18 @@ impl Llama {
         logits.to_dtype(DType::F32)
     }
 
+    /// Same as [`Self::forward`] but returns the logits for all the positions with shape
+    /// `(b_sz, seq_len, vocab_size)`.
+    pub fn forward_all(&self, x: &Tensor, index_pos: usize, cache: &mut Cache) -> Result<Tensor> {
+        let mut x = self.wte.forward(x)?;
+        for (block_idx, block) in self.blocks.iter().enumerate() {
+            x = block.forward(&x, index_pos, block_idx, cache)?;
+        }
+        let x = self.ln_f.forward(&x)?;
+        let logits = self.lm_head.forward(&x)?;
+        logits.to_dtype(DType::F32)
+    }
+
     pub fn load(vb: VarBuilder, cfg: &Config) -> Result<Self> {
         let wte = embedding(cfg.vocab_size, cfg.hidden_size, vb.pp("model.embed_tokens"))?;
         let lm_head = if cfg.tie_word_embeddings {
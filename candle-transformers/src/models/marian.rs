@@ -343,6 +343,13 @@ impl Attention {
     fn reset_kv_cache(&mut self) {
         self.kv_cache = None
     }
+
+    fn reorder_kv_cache(&mut self, indices: &Tensor) -> Result<()> {
+        if let Some((k, v)) = &self.kv_cache {
+            self.kv_cache = Some((k.index_select(indices, 0)?, v.index_select(indices, 0)?))
+        }
+        Ok(())
+    }
 }
 
 #[derive(Debug, Clone)]
@@ -453,6 +460,10 @@ impl DecoderLayer {
         self.self_attn.reset_kv_cache();
         self.encoder_attn.reset_kv_cache()
     }
+
+    fn reorder_kv_cache(&mut self, indices: &Tensor) -> Result<()> {
+        self.self_attn.reorder_kv_cache(indices)
+    }
 }
 
 #[derive(Debug, Clone)]
@@ -567,6 +578,15 @@ impl Decoder {
             layer.reset_kv_cache()
         }
     }
+
+    /// Reorders the kv-cache along the batch dimension, the new batch element `i` continues the
+    /// sequence of the batch element `indices[i]`.
+    pub fn reorder_kv_cache(&mut self, indices: &Tensor) -> Result<()> {
+        for layer in self.layers.iter_mut() {
+            layer.reorder_kv_cache(indices)?
+        }
+        Ok(())
+    }
 }
 
 #[derive(Debug, Clone)]
@@ -627,6 +647,17 @@ impl MTModel {
         xs: &Tensor,
         encoder_xs: &Tensor,
         past_kv_len: usize,
+    ) -> Result<Tensor> {
+        let xs = self.decode_hidden(xs, encoder_xs, past_kv_len)?;
+        self.logits(&xs)
+    }
+
+    /// Returns the decoder hidden states, i.e. the inputs of the language modeling head.
+    pub fn decode_hidden(
+        &mut self,
+        xs: &Tensor,
+        encoder_xs: &Tensor,
+        past_kv_len: usize,
     ) -> Result<Tensor> {
         let seq_len = xs.dim(1)?;
         let mask: Vec<_> = (0..seq_len)
@@ -635,12 +666,20 @@ impl MTModel {
         let mask = Tensor::from_vec(mask, (seq_len, seq_len), xs.device())?;
         self.model
             .decoder
-            .forward(xs, Some(encoder_xs), past_kv_len, &mask)?
-            .apply(&self.lm_head)?
+            .forward(xs, Some(encoder_xs), past_kv_len, &mask)
+    }
+
+    /// Applies the language modeling head to some decoder hidden states.
+    pub fn logits(&self, xs: &Tensor) -> Result<Tensor> {
+        xs.apply(&self.lm_head)?
             .broadcast_add(&self.final_logits_bias)
     }
 
     pub fn reset_kv_cache(&mut self) {
         self.model.reset_kv_cache();
     }
+
+    pub fn reorder_kv_cache(&mut self, indices: &Tensor) -> Result<()> {
+        self.model.decoder.reorder_kv_cache(indices)
+    }
 }
@@ -534,11 +534,11 @@ impl PaddleOCRVLModel {
             return Ok(generated_tokens);
         }
 
-        let mut seqlen_offset = current_ids.dim(1)?;
+        let seqlen_offset = current_ids.dim(1)?;
         current_ids = Tensor::new(&[next_token], &self.device)?.unsqueeze(0)?;
 
         // Subsequent forward passes (text only, using KV cache)
-        for _ in 1..max_new_tokens {
+        for seqlen_offset in (seqlen_offset..).take(max_new_tokens.saturating_sub(1)) {
             let logits = self.forward(&current_ids, None, None, seqlen_offset)?;
             let next_token = logits
                 .argmax(D::Minus1)?
@@ -551,7 +551,6 @@ impl PaddleOCRVLModel {
                 break;
             }
 
-            seqlen_offset += 1;
             current_ids = Tensor::new(&[next_token], &self.device)?.unsqueeze(0)?;
         }
 
@@ -595,12 +594,12 @@ impl PaddleOCRVLModel {
             return Ok(generated_tokens);
         }
 
-        let mut seqlen_offset = current_ids.dim(1)?;
+        let seqlen_offset = current_ids.dim(1)?;
         current_ids = Tensor::new(&[next_token], &self.device)?.unsqueeze(0)?;
 
         // Subsequent forward passes (text only, using KV cache)
         // Uses same incremental decoding as single-image generation
-        for _ in 1..max_new_tokens {
+        for seqlen_offset in (seqlen_offset..).take(max_new_tokens.saturating_sub(1)) {
             let logits = self.forward(&current_ids, None, None, seqlen_offset)?;
             let next_token = logits
                 .argmax(D::Minus1)?
@@ -613,7 +612,6 @@ impl PaddleOCRVLModel {
                 break;
             }
 
-            seqlen_offset += 1;
             current_ids = Tensor::new(&[next_token], &self.device)?.unsqueeze(0)?;
         }
 
@@ -661,11 +659,11 @@ impl PaddleOCRVLModel {
             return Ok(generated_tokens);
         }
 
-        let mut seqlen_offset = current_ids.dim(1)?;
+        let seqlen_offset = current_ids.dim(1)?;
         current_ids = Tensor::new(&[next_token], &self.device)?.unsqueeze(0)?;
 
         // Subsequent forward passes (text only, using KV cache)
-        for _ in 1..max_new_tokens {
+        for seqlen_offset in (seqlen_offset..).take(max_new_tokens.saturating_sub(1)) {
             let logits = self.forward(&current_ids, None, None, seqlen_offset)?;
             let next_token = logits
                 .argmax(D::Minus1)?
@@ -678,7 +676,6 @@ impl PaddleOCRVLModel {
                 break;
             }
 
-            seqlen_offset += 1;
             current_ids = Tensor::new(&[next_token], &self.device)?.unsqueeze(0)?;
         }
 
@@ -867,11 +864,11 @@ impl PaddleOCRVLModel {
             return Ok(generated_tokens);
         }
 
-        let mut seqlen_offset = current_ids.dim(1)?;
+        let seqlen_offset = current_ids.dim(1)?;
         current_ids = Tensor::new(&[next_token], &self.device)?.unsqueeze(0)?;
 
         // Subsequent forward passes (text only, using KV cache)
-        for _ in 1..max_new_tokens {
+        for seqlen_offset in (seqlen_offset..).take(max_new_tokens.saturating_sub(1)) {
             let logits = self.forward(&current_ids, None, None, seqlen_offset)?;
             let logits = apply_repetition_penalty(&logits, &generated_tokens, repetition_penalty)?;
             let next_token = logits
@@ -885,7 +882,6 @@ impl PaddleOCRVLModel {
                 break;
             }
 
-            seqlen_offset += 1;
             current_ids = Tensor::new(&[next_token], &self.device)?.unsqueeze(0)?;
         }
 
@@ -1044,10 +1040,10 @@ impl PaddleOCRVLModel {
         }
 
         // Generation steps
-        let mut seqlen_offset = input_ids.dim(1)?;
+        let seqlen_offset = input_ids.dim(1)?;
         let mut current_ids = Tensor::new(&[next_token], &self.device)?.unsqueeze(0)?;
 
-        for step in 1..max_steps {
+        for (step, seqlen_offset) in (1..max_steps).zip(seqlen_offset..) {
             // Compute position for M-RoPE
             let pos = seqlen_offset as i64 + self.mrope_position_delta;
             let (batch_size, seq_len, _) = {
@@ -1100,7 +1096,6 @@ impl PaddleOCRVLModel {
                 break;
             }
 
-            seqlen_offset += 1;
             current_ids = Tensor::new(&[next_token], &self.device)?.unsqueeze(0)?;
         }
 
@@ -19,6 +19,7 @@ use candle::quantized::gguf_file;
 use candle::quantized::QTensor;
 use candle::D;
 use candle::{DType, Device, IndexOp, Result, Tensor};
+use candle_nn::kv_cache::{QuantizedKvCache, QuantizedKvDType};
 use candle_nn::{Embedding, Module};
 
 pub const MAX_SEQ_LEN: usize = 131072; // Gemma 3 supports 128K context window
@@ -134,6 +135,7 @@ struct LayerWeights {
 
     // Cache
     kv_cache: Option<(Tensor, Tensor)>,
+    quantized_kv_cache: Option<QuantizedKvCache>,
 
     // Tracing
     span_attn: tracing::Span,
@@ -207,19 +209,30 @@ impl LayerWeights {
             .rotary_embedding
             .apply_rotary_emb_qkv(&q, &k, index_pos)?;
 
-        let (k, v) = match &self.kv_cache {
-            None => (k, v),
-            Some((k_cache, v_cache)) => {
+        let (k, v) = match &mut self.quantized_kv_cache {
+            Some(cache) => {
                 if index_pos == 0 {
-                    (k, v)
-                } else {
-                    let k = Tensor::cat(&[k_cache, &k], 2)?; // concat on seq dim
-                    let v = Tensor::cat(&[v_cache, &v], 2)?;
-                    (k, v)
+                    cache.reset()
                 }
+                cache.append(&k, &v)?
+            }
+            None => {
+                let (k, v) = match &self.kv_cache {
+                    None => (k, v),
+                    Some((k_cache, v_cache)) => {
+                        if index_pos == 0 {
+                            (k, v)
+                        } else {
+                            let k = Tensor::cat(&[k_cache, &k], 2)?; // concat on seq dim
+                            let v = Tensor::cat(&[v_cache, &v], 2)?;
+                            (k, v)
+                        }
+                    }
+                };
+                self.kv_cache = Some((k.clone(), v.clone())); // update cache
+                (k, v)
             }
         };
-        self.kv_cache = Some((k.clone(), v.clone())); // update cache
 
         // Repeat KV for GQA
         let k = crate::utils::repeat_kv(k, self.n_head / self.n_kv_head)?;
@@ -417,6 +430,7 @@ impl ModelWeights {
                 rotary_embedding,
                 neg_inf: neg_inf.clone(),
                 kv_cache: None,
+                quantized_kv_cache: None,
                 span_attn,
                 span_mlp,
             })
@@ -436,6 +450,25 @@ impl ModelWeights {
         })
     }
 
+    pub fn clear_kv_cache(&mut self) {
+        for layer in self.layers.iter_mut() {
+            layer.kv_cache = None;
+            if let Some(cache) = layer.quantized_kv_cache.as_mut() {
+                cache.reset()
+            }
+        }
+    }
+
+    /// Stores the keys and values block-quantized with the given dtype rather than in full
+    /// precision, this reduces the memory used by long contexts.
+    pub fn with_kv_cache_dtype(mut self, dtype: QuantizedKvDType) -> Self {
+        for layer in self.layers.iter_mut() {
+            layer.kv_cache = None;
+            layer.quantized_kv_cache = Some(QuantizedKvCache::new(2, dtype))
+        }
+        self
+    }
+
     pub fn forward(&mut self, x: &Tensor, index_pos: usize) -> Result<Tensor> {
         let (b_sz, seq_len) = x.dims2()?;
         let _enter = self.span.enter();
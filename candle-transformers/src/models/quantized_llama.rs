@@ -9,6 +9,7 @@
 //! - Support for 2/3/4/8-bit quantization
 //! - Optimized memory usage through quantization
 //! - Configurable model sizes and parameter counts
+//! - GPTQ/AWQ checkpoints can be loaded with [`ModelWeights::from_gptq`]
 //!
 //! - 💻 [GH Link](https://github.com/facebookresearch/llama)
 //! - 📝 [Paper](https://arxiv.org/abs/2302.13971)
@@ -19,29 +20,52 @@
 use std::collections::HashMap;
 
 use crate::quantized_nn::RmsNorm;
+use crate::quantized_var_builder::VarBuilder;
+use candle::quantized::imatrix_file::ImatrixCollector;
 use candle::quantized::QTensor;
 use candle::quantized::{ggml_file, gguf_file};
 use candle::{DType, Device, IndexOp, Result, Tensor};
+use candle_nn::kv_cache::{KvSnapshot, QuantizedKvCache, QuantizedKvDType};
+use candle_nn::paged_attention::{PagedAttentionMetadata, PagedKvCache};
 use candle_nn::{Embedding, Module};
 
 pub const MAX_SEQ_LEN: usize = 4096;
 
-// QMatMul wrapper adding some tracing.
-#[derive(Debug, Clone)]
+// QMatMul wrapper adding some tracing, the activations are also recorded when the weights come
+// from a var-builder with an imatrix collector.
+#[derive(Clone)]
 struct QMatMul {
     inner: candle::quantized::QMatMul,
+    imatrix: Option<Box<(String, ImatrixCollector)>>,
     span: tracing::Span,
 }
 
+impl std::fmt::Debug for QMatMul {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "QMatMul")
+    }
+}
+
 impl QMatMul {
     fn from_qtensor(qtensor: QTensor) -> Result<Self> {
         let inner = candle::quantized::QMatMul::from_qtensor(qtensor)?;
+        Ok(Self::from_inner(inner))
+    }
+
+    fn from_inner(inner: candle::quantized::QMatMul) -> Self {
         let span = tracing::span!(tracing::Level::TRACE, "qmatmul");
-        Ok(Self { inner, span })
+        Self {
+            inner,
+            imatrix: None,
+            span,
+        }
     }
 
     fn forward(&self, xs: &Tensor) -> Result<Tensor> {
         let _enter = self.span.enter();
+        if let Some((name, collector)) = self.imatrix.as_deref() {
+            collector.record(name, xs)?
+        }
         self.inner.forward(xs)
     }
 }
@@ -156,8 +180,12 @@ struct LayerWeights {
     head_dim: usize,
     cos: Tensor,
     sin: Tensor,
+    /// The gguf conversion permutes the q/k weights so that the interleaved rotary embeddings
+    /// apply, the HF checkpoints use the non-interleaved variant.
+    interleaved_rope: bool,
     neg_inf: Tensor,
     kv_cache: Option<(Tensor, Tensor)>,
+    quantized_kv_cache: Option<QuantizedKvCache>,
     span_attn: tracing::Span,
     span_rot: tracing::Span,
     span_mlp: tracing::Span,
@@ -177,7 +205,15 @@ impl LayerWeights {
         let sin = self.sin.narrow(0, index_pos, seq_len)?;
         // The call to contiguous below is only necessary when processing the prompt.
         // When the seq_len is 1 in the inference loop, this is a no-op.
-        candle_nn::rotary_emb::rope_i(&x.contiguous()?, &cos, &sin)
+        self.rope(&x.contiguous()?, &cos, &sin)
+    }
+
+    fn rope(&self, x: &Tensor, cos: &Tensor, sin: &Tensor) -> Result<Tensor> {
+        if self.interleaved_rope {
+            candle_nn::rotary_emb::rope_i(x, cos, sin)
+        } else {
+            candle_nn::rotary_emb::rope(x, cos, sin)
+        }
     }
 
     fn forward_attn(
@@ -209,19 +245,30 @@ impl LayerWeights {
         let q = self.apply_rotary_emb(&q, index_pos)?;
         let k = self.apply_rotary_emb(&k, index_pos)?;
 
-        let (k, v) = match &self.kv_cache {
-            None => (k, v),
-            Some((k_cache, v_cache)) => {
+        let (k, v) = match &mut self.quantized_kv_cache {
+            Some(cache) => {
                 if index_pos == 0 {
-                    (k, v)
-                } else {
-                    let k = Tensor::cat(&[k_cache, &k], 2)?;
-                    let v = Tensor::cat(&[v_cache, &v], 2)?;
-                    (k, v)
+                    cache.reset()
                 }
+                cache.append(&k, &v)?
+            }
+            None => {
+                let (k, v) = match &self.kv_cache {
+                    None => (k, v),
+                    Some((k_cache, v_cache)) => {
+                        if index_pos == 0 {
+                            (k, v)
+                        } else {
+                            let k = Tensor::cat(&[k_cache, &k], 2)?;
+                            let v = Tensor::cat(&[v_cache, &v], 2)?;
+                            (k, v)
+                        }
+                    }
+                };
+                self.kv_cache = Some((k.clone(), v.clone()));
+                (k, v)
             }
         };
-        self.kv_cache = Some((k.clone(), v.clone()));
 
         let y = if q.device().is_metal() && seq_len == 1 {
             // SDPA will do MQA for us
@@ -256,6 +303,44 @@ impl LayerWeights {
         let y = self.attention_wo.forward(&y)?;
         Ok(y)
     }
+
+    fn forward_attn_paged(
+        &self,
+        x: &Tensor,
+        cache: &mut PagedKvCache,
+        metadata: &PagedAttentionMetadata,
+    ) -> Result<Tensor> {
+        let _enter = self.span_attn.enter();
+        let (b_sz, seq_len, _n_embd) = x.dims3()?;
+        let q = self
+            .attention_wq
+            .forward(x)?
+            .reshape((b_sz, seq_len, self.n_head, self.head_dim))?
+            .transpose(1, 2)?;
+        let k = self
+            .attention_wk
+            .forward(x)?
+            .reshape((b_sz, seq_len, self.n_kv_head, self.head_dim))?
+            .transpose(1, 2)?;
+        let v = self
+            .attention_wv
+            .forward(x)?
+            .reshape((seq_len, self.n_kv_head, self.head_dim))?;
+
+        let _enter_rot = self.span_rot.enter();
+        let cos = self.cos.index_select(metadata.positions(), 0)?;
+        let sin = self.sin.index_select(metadata.positions(), 0)?;
+        let q = self.rope(&q.contiguous()?, &cos, &sin)?;
+        let k = self.rope(&k.contiguous()?, &cos, &sin)?;
+        drop(_enter_rot);
+        let q = q.transpose(1, 2)?.squeeze(0)?;
+        let k = k.transpose(1, 2)?.squeeze(0)?;
+
+        let scale = 1. / (self.head_dim as f32).sqrt();
+        let y = cache.forward(&q, &k, &v, metadata, scale)?;
+        let y = y.reshape((b_sz, seq_len, self.n_head * self.head_dim))?;
+        self.attention_wo.forward(&y)
+    }
 }
 
 #[derive(Debug, Clone)]
@@ -265,23 +350,36 @@ pub struct ModelWeights {
     norm: RmsNorm,
     output: QMatMul,
     masks: HashMap<usize, Tensor>,
+    // The number of positions of the rotary embedding tables.
+    max_seq_len: usize,
     span: tracing::Span,
     span_output: tracing::Span,
 }
 
+// The frequencies are divided by `freq_factors` when specified, this is used by the llama3 rope
+// scaling.
 fn precomput_freqs_cis(
     head_dim: usize,
     freq_base: f32,
+    freq_factors: Option<&[f32]>,
     device: &Device,
 ) -> Result<(Tensor, Tensor)> {
     let theta: Vec<_> = (0..head_dim)
         .step_by(2)
-        .map(|i| 1f32 / freq_base.powf(i as f32 / head_dim as f32))
+        .enumerate()
+        .map(|(idx, i)| {
+            let factor = freq_factors.map_or(1., |f| f[idx]);
+            1f32 / freq_base.powf(i as f32 / head_dim as f32) / factor
+        })
         .collect();
-    let theta = Tensor::new(theta.as_slice(), device)?;
-    let idx_theta = Tensor::arange(0, MAX_SEQ_LEN as u32, device)?
+    freqs_cis(theta, MAX_SEQ_LEN, device)
+}
+
+fn freqs_cis(theta: Vec<f32>, max_seq_len: usize, device: &Device) -> Result<(Tensor, Tensor)> {
+    let theta = Tensor::new(theta, device)?;
+    let idx_theta = Tensor::arange(0, max_seq_len as u32, device)?
         .to_dtype(DType::F32)?
-        .reshape((MAX_SEQ_LEN, 1))?
+        .reshape((max_seq_len, 1))?
         .matmul(&theta.reshape((1, theta.elem_count()))?)?;
     let cos = idx_theta.cos()?;
     let sin = idx_theta.sin()?;
@@ -291,7 +389,7 @@ fn precomput_freqs_cis(
 impl ModelWeights {
     pub fn from_ggml(mut ct: ggml_file::Content, gqa: usize) -> Result<Self> {
         let head_dim = (ct.hparams.n_embd / ct.hparams.n_head) as usize;
-        let (cos, sin) = precomput_freqs_cis(head_dim, 10000., &ct.device)?;
+        let (cos, sin) = precomput_freqs_cis(head_dim, 10000., None, &ct.device)?;
         let neg_inf = Tensor::new(f32::NEG_INFINITY, &ct.device)?;
         let tok_embeddings = ct.remove("tok_embeddings.weight")?;
         let tok_embeddings = tok_embeddings.dequantize(&ct.device)?;
@@ -332,8 +430,10 @@ impl ModelWeights {
                 head_dim: (ct.hparams.n_embd / ct.hparams.n_head) as usize,
                 cos: cos.clone(),
                 sin: sin.clone(),
+                interleaved_rope: true,
                 neg_inf: neg_inf.clone(),
                 kv_cache: None,
+                quantized_kv_cache: None,
                 span_attn,
                 span_rot,
                 span_mlp,
@@ -347,6 +447,7 @@ impl ModelWeights {
             norm,
             output: QMatMul::from_qtensor(output)?,
             masks: HashMap::new(),
+            max_seq_len: cos.dim(0)?,
             span,
             span_output,
         })
@@ -380,7 +481,22 @@ impl ModelWeights {
         let rope_freq_base = md_get("llama.rope.freq_base")
             .and_then(|m| m.to_f32())
             .unwrap_or(10000f32);
-        let (cos, sin) = precomput_freqs_cis(rope_dim, rope_freq_base, device)?;
+        let rope_freqs = if ct.tensor_infos.contains_key("rope_freqs.weight") {
+            let rope_freqs = ct.tensor(reader, "rope_freqs.weight", device)?;
+            let rope_freqs = rope_freqs.dequantize(&Device::Cpu)?.to_vec1::<f32>()?;
+            if rope_freqs.len() != rope_dim / 2 {
+                candle::bail!(
+                    "unexpected rope_freqs length {}, expected {}",
+                    rope_freqs.len(),
+                    rope_dim / 2
+                )
+            }
+            Some(rope_freqs)
+        } else {
+            None
+        };
+        let (cos, sin) =
+            precomput_freqs_cis(rope_dim, rope_freq_base, rope_freqs.as_deref(), device)?;
         let neg_inf = Tensor::new(f32::NEG_INFINITY, device)?;
 
         let tok_embeddings_q = ct.tensor(reader, "token_embd.weight", device)?;
@@ -455,8 +571,10 @@ impl ModelWeights {
                 head_dim: embedding_length / head_count,
                 cos: cos.clone(),
                 sin: sin.clone(),
+                interleaved_rope: true,
                 neg_inf: neg_inf.clone(),
                 kv_cache: None,
+                quantized_kv_cache: None,
                 span_attn,
                 span_rot,
                 span_mlp,
@@ -470,30 +588,228 @@ impl ModelWeights {
             norm,
             output: QMatMul::from_qtensor(output)?,
             masks: HashMap::new(),
+            max_seq_len: cos.dim(0)?,
             span,
             span_output,
         })
     }
 
-    fn mask(&mut self, t: usize, device: &Device) -> Result<Tensor> {
-        if let Some(mask) = self.masks.get(&t) {
-            Ok(mask.clone())
+    /// Loads a llama model from a HF checkpoint, typically a GPTQ or AWQ one loaded with
+    /// [`VarBuilder::from_gptq_safetensors`]. The linear layers use the GPTQ/AWQ weights when
+    /// available and the other tensors otherwise.
+    pub fn from_gptq(cfg: &crate::models::llama::Config, vb: VarBuilder) -> Result<Self> {
+        let device = vb.device().clone();
+        let (hidden, vocab) = (cfg.hidden_size, cfg.vocab_size);
+        let (n_head, n_kv_head) = (cfg.num_attention_heads, cfg.num_key_value_heads);
+        let head_dim = hidden / n_head;
+        let inter = cfg.intermediate_size;
+        let eps = cfg.rms_norm_eps;
+        let theta = crate::models::llama::rope_inv_freq(cfg);
+        let (cos, sin) = freqs_cis(theta, cfg.max_position_embeddings, &device)?;
+        let neg_inf = Tensor::new(f32::NEG_INFINITY, &device)?;
+
+        let vb_m = vb.pp("model");
+        let tok_embeddings_q = vb_m.pp("embed_tokens").get((vocab, hidden), "weight")?;
+        let tok_embeddings = tok_embeddings_q.dequantize(&device)?;
+        let norm = RmsNorm::new(hidden, eps, vb_m.pp("norm"))?;
+        let output = if cfg.tie_word_embeddings {
+            candle::quantized::QMatMul::from_arc(tok_embeddings_q)?
+        } else {
+            vb.pp("lm_head").get_qmatmul((vocab, hidden), "weight")?
+        };
+        let linear = |vb: &VarBuilder, name: &str, shape: (usize, usize)| -> Result<QMatMul> {
+            let vb = vb.pp(name);
+            let mut mm = QMatMul::from_inner(vb.get_qmatmul(shape, "weight")?);
+            mm.imatrix = vb
+                .imatrix_collector()
+                .map(|c| Box::new((vb.path("weight"), c.clone())));
+            Ok(mm)
+        };
+        let mut layers = Vec::with_capacity(cfg.num_hidden_layers);
+        for layer_idx in 0..cfg.num_hidden_layers {
+            let vb_l = vb_m.pp(format!("layers.{layer_idx}"));
+            let vb_a = vb_l.pp("self_attn");
+            let vb_mlp = vb_l.pp("mlp");
+            let mlp_or_moe = MlpOrMoe::Mlp(Mlp {
+                feed_forward_w1: linear(&vb_mlp, "gate_proj", (inter, hidden))?,
+                feed_forward_w2: linear(&vb_mlp, "down_proj", (hidden, inter))?,
+                feed_forward_w3: linear(&vb_mlp, "up_proj", (inter, hidden))?,
+            });
+            let span_attn = tracing::span!(tracing::Level::TRACE, "attn");
+            let span_rot = tracing::span!(tracing::Level::TRACE, "attn-rot");
+            let span_mlp = tracing::span!(tracing::Level::TRACE, "attn-mlp");
+            layers.push(LayerWeights {
+                attention_wq: linear(&vb_a, "q_proj", (n_head * head_dim, hidden))?,
+                attention_wk: linear(&vb_a, "k_proj", (n_kv_head * head_dim, hidden))?,
+                attention_wv: linear(&vb_a, "v_proj", (n_kv_head * head_dim, hidden))?,
+                attention_wo: linear(&vb_a, "o_proj", (hidden, n_head * head_dim))?,
+                attention_norm: RmsNorm::new(hidden, eps, vb_l.pp("input_layernorm"))?,
+                mlp_or_moe,
+                ffn_norm: RmsNorm::new(hidden, eps, vb_l.pp("post_attention_layernorm"))?,
+                n_head,
+                n_kv_head,
+                head_dim,
+                cos: cos.clone(),
+                sin: sin.clone(),
+                interleaved_rope: false,
+                neg_inf: neg_inf.clone(),
+                kv_cache: None,
+                quantized_kv_cache: None,
+                span_attn,
+                span_rot,
+                span_mlp,
+            })
+        }
+        let span = tracing::span!(tracing::Level::TRACE, "model");
+        let span_output = tracing::span!(tracing::Level::TRACE, "output");
+        Ok(Self {
+            tok_embeddings: Embedding::new(tok_embeddings, hidden),
+            layers,
+            norm,
+            output: QMatMul::from_inner(output),
+            masks: HashMap::new(),
+            max_seq_len: cos.dim(0)?,
+            span,
+            span_output,
+        })
+    }
+
+    /// Stores the keys and values block-quantized with the given dtype rather than in full
+    /// precision, this reduces the memory used by long contexts.
+    pub fn with_kv_cache_dtype(mut self, dtype: QuantizedKvDType) -> Self {
+        for layer in self.layers.iter_mut() {
+            layer.kv_cache = None;
+            layer.quantized_kv_cache = Some(QuantizedKvCache::new(2, dtype))
+        }
+        self
+    }
+
+    /// Returns a snapshot of the kv-cache of each layer, `None` if the cache is empty. This can be
+    /// used with [`candle_nn::prefix_cache::PrefixCache`] to skip recomputing a shared prompt
+    /// prefix.
+    pub fn kv_cache_snapshot(&self) -> Result<Option<Vec<KvSnapshot>>> {
+        let mut snapshots = Vec::with_capacity(self.layers.len());
+        for layer in self.layers.iter() {
+            let snapshot = match (&layer.quantized_kv_cache, &layer.kv_cache) {
+                (Some(cache), _) => cache.snapshot()?,
+                (None, Some((k, v))) => Some(KvSnapshot::new(k.clone(), v.clone(), 2)?),
+                (None, None) => None,
+            };
+            match snapshot {
+                None => return Ok(None),
+                Some(snapshot) => snapshots.push(snapshot),
+            }
+        }
+        Ok(Some(snapshots))
+    }
+
+    /// Restores the kv-caches from a snapshot, the next call to `forward` should then use the
+    /// snapshot length as `index_pos`.
+    pub fn restore_kv_cache(&mut self, snapshots: &[KvSnapshot]) -> Result<()> {
+        if snapshots.len() != self.layers.len() {
+            candle::bail!(
+                "expected {} kv-cache snapshots, got {}",
+                self.layers.len(),
+                snapshots.len()
+            )
+        }
+        for (layer, snapshot) in self.layers.iter_mut().zip(snapshots.iter()) {
+            match &mut layer.quantized_kv_cache {
+                Some(cache) => cache.restore(snapshot)?,
+                None => {
+                    let k = snapshot.k().contiguous()?;
+                    let v = snapshot.v().contiguous()?;
+                    layer.kv_cache = Some((k, v))
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// The maximum number of positions, i.e. the size of the rotary embedding tables.
+    pub fn max_seq_len(&self) -> usize {
+        self.max_seq_len
+    }
+
+    // The mask for `t` new tokens attending to `index_pos` cached tokens and to themselves. Only
+    // the causal `(t, t)` part is cached, the cached tokens are never masked.
+    fn mask(&mut self, t: usize, index_pos: usize, device: &Device) -> Result<Tensor> {
+        let mask = if let Some(mask) = self.masks.get(&t) {
+            mask.clone()
         } else {
             let mask: Vec<_> = (0..t)
                 .flat_map(|i| (0..t).map(move |j| u8::from(j > i)))
                 .collect();
             let mask = Tensor::from_slice(&mask, (t, t), device)?;
             self.masks.insert(t, mask.clone());
+            mask
+        };
+        if index_pos == 0 {
             Ok(mask)
+        } else {
+            let cached = Tensor::zeros((t, index_pos), DType::U8, device)?;
+            Tensor::cat(&[&cached, &mask], 1)
         }
     }
 
     pub fn forward(&mut self, x: &Tensor, index_pos: usize) -> Result<Tensor> {
+        let (_b_sz, seq_len) = x.dims2()?;
+        let x = self.forward_hidden(x, index_pos)?;
+        let x = x.i((.., seq_len - 1, ..))?;
+        let _enter = self.span_output.enter();
+        self.output.forward(&x)
+    }
+
+    /// Same as [`Self::forward`] but returns the logits for all the positions with shape
+    /// `(b_sz, seq_len, vocab_size)`.
+    pub fn forward_all(&mut self, x: &Tensor, index_pos: usize) -> Result<Tensor> {
+        let x = self.forward_hidden(x, index_pos)?;
+        let _enter = self.span_output.enter();
+        self.output.forward(&x)
+    }
+
+    pub fn clear_kv_cache(&mut self) {
+        for layer in self.layers.iter_mut() {
+            layer.kv_cache = None;
+            if let Some(cache) = layer.quantized_kv_cache.as_mut() {
+                cache.reset()
+            }
+        }
+    }
+
+    /// Drops the kv-cache entries after the first `len` positions, e.g. to roll back tokens that
+    /// were rejected during speculative decoding.
+    pub fn truncate_kv_cache(&mut self, len: usize) -> Result<()> {
+        for layer in self.layers.iter_mut() {
+            if let Some(cache) = layer.quantized_kv_cache.as_mut() {
+                cache.truncate(len)?;
+                continue;
+            }
+            let (k, v) = match &layer.kv_cache {
+                None if len == 0 => continue,
+                None => candle::bail!("cannot truncate an empty kv-cache to {len}"),
+                Some(kv) => kv,
+            };
+            let seq_len = k.dim(2)?;
+            if len > seq_len {
+                candle::bail!("cannot truncate a kv-cache of length {seq_len} to {len}")
+            }
+            layer.kv_cache = if len == 0 {
+                None
+            } else {
+                Some((k.narrow(2, 0, len)?, v.narrow(2, 0, len)?))
+            };
+        }
+        Ok(())
+    }
+
+    // Runs the layers and the final norm, returns the hidden states for all the positions.
+    fn forward_hidden(&mut self, x: &Tensor, index_pos: usize) -> Result<Tensor> {
         let (_b_sz, seq_len) = x.dims2()?;
         let mask = if seq_len == 1 {
             None
         } else {
-            Some(self.mask(seq_len, x.device())?)
+            Some(self.mask(seq_len, index_pos, x.device())?)
         };
         let _enter = self.span.enter();
         let mut layer_in = self.tok_embeddings.forward(x)?;
@@ -512,8 +828,68 @@ impl ModelWeights {
             let x = (x + residual)?;
             layer_in = x
         }
-        let x = self.norm.forward(&layer_in)?;
-        let x = x.i((.., seq_len - 1, ..))?;
+        self.norm.forward(&layer_in)
+    }
+
+    /// Creates one paged kv-cache per layer with the given number of blocks.
+    pub fn paged_kv_caches(
+        &self,
+        num_blocks: usize,
+        block_size: usize,
+    ) -> Result<Vec<PagedKvCache>> {
+        let dtype = self.tok_embeddings.embeddings().dtype();
+        self.layers
+            .iter()
+            .map(|l| {
+                let device = l.cos.device();
+                PagedKvCache::new(
+                    num_blocks,
+                    block_size,
+                    l.n_kv_head,
+                    l.head_dim,
+                    dtype,
+                    device,
+                )
+            })
+            .collect()
+    }
+
+    /// Runs a batch step using paged kv-caches, `input` holds the token ids of all the sequences
+    /// packed as a single dimension as described in [`PagedAttentionMetadata`]. The returned
+    /// logits have shape `(num_seqs, vocab_size)` and correspond to the last token of each
+    /// sequence.
+    pub fn forward_paged(
+        &self,
+        input: &Tensor,
+        caches: &mut [PagedKvCache],
+        metadata: &PagedAttentionMetadata,
+    ) -> Result<Tensor> {
+        if caches.len() != self.layers.len() {
+            candle::bail!(
+                "expected {} paged kv-caches, got {}",
+                self.layers.len(),
+                caches.len()
+            )
+        }
+        let _enter = self.span.enter();
+        let mut layer_in = self.tok_embeddings.forward(input)?.unsqueeze(0)?;
+        for (layer, cache) in self.layers.iter().zip(caches.iter_mut()) {
+            let x = layer_in;
+            let residual = &x;
+            let x = layer.attention_norm.forward(&x)?;
+            let attn = layer.forward_attn_paged(&x, cache, metadata)?;
+            let x = (attn + residual)?;
+
+            // MLP
+            let _enter = layer.span_mlp.enter();
+            let residual = &x;
+            let x = layer.ffn_norm.forward(&x)?;
+            let x = layer.mlp_or_moe.forward(&x)?;
+            let x = (x + residual)?;
+            layer_in = x
+        }
+        let x = self.norm.forward(&layer_in.squeeze(0)?)?;
+        let x = metadata.last_tokens(&x)?;
         let _enter = self.span_output.enter();
         self.output.forward(&x)
     }
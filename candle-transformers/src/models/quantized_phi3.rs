@@ -18,7 +18,8 @@ use std::collections::HashMap;
 use candle::quantized::gguf_file;
 use candle::quantized::QTensor;
 use candle::{DType, Device, IndexOp, Module, Result, Tensor, D};
-use candle_nn::{kv_cache::KvCache, Embedding, RmsNorm};
+use candle_nn::kv_cache::{KvCache, QuantizedKvCache, QuantizedKvDType};
+use candle_nn::{Embedding, RmsNorm};
 
 #[derive(Debug, Clone)]
 struct QLinear {
@@ -84,6 +85,7 @@ struct LayerWeights {
     sin: Tensor,
     neg_inf: Tensor,
     kv_cache: KvCache,
+    quantized_kv_cache: Option<QuantizedKvCache>,
     use_flash_attn: bool,
     span_attn: tracing::Span,
     span_rot: tracing::Span,
@@ -136,10 +138,20 @@ impl LayerWeights {
         let q = self.apply_rotary_emb(&q, index_pos)?.contiguous()?;
         let k = self.apply_rotary_emb(&k, index_pos)?;
 
-        if index_pos == 0 {
-            self.kv_cache.reset();
-        }
-        let (k, v) = self.kv_cache.append(&k.contiguous()?, &v.contiguous()?)?;
+        let (k, v) = match &mut self.quantized_kv_cache {
+            Some(cache) => {
+                if index_pos == 0 {
+                    cache.reset()
+                }
+                cache.append(&k.contiguous()?, &v.contiguous()?)?
+            }
+            None => {
+                if index_pos == 0 {
+                    self.kv_cache.reset();
+                }
+                self.kv_cache.append(&k.contiguous()?, &v.contiguous()?)?
+            }
+        };
 
         let k = crate::utils::repeat_kv(k, self.n_head / self.n_kv_head)?;
         let v = crate::utils::repeat_kv(v, self.n_head / self.n_kv_head)?;
@@ -283,6 +295,7 @@ impl ModelWeights {
                 sin: sin.clone(),
                 neg_inf: neg_inf.clone(),
                 kv_cache,
+                quantized_kv_cache: None,
                 use_flash_attn,
                 span_attn,
                 span_rot,
@@ -314,6 +327,25 @@ impl ModelWeights {
         }
     }
 
+    pub fn clear_kv_cache(&mut self) {
+        for layer in self.layers.iter_mut() {
+            layer.kv_cache.reset();
+            if let Some(cache) = layer.quantized_kv_cache.as_mut() {
+                cache.reset()
+            }
+        }
+    }
+
+    /// Stores the keys and values block-quantized with the given dtype rather than in full
+    /// precision, this reduces the memory used by long contexts.
+    pub fn with_kv_cache_dtype(mut self, dtype: QuantizedKvDType) -> Self {
+        for layer in self.layers.iter_mut() {
+            layer.kv_cache.reset();
+            layer.quantized_kv_cache = Some(QuantizedKvCache::new(2, dtype))
+        }
+        self
+    }
+
     pub fn forward(&mut self, xs: &Tensor, index_pos: usize) -> Result<Tensor> {
         let (_b_sz, seq_len) = xs.dims2()?;
         let mask = if seq_len == 1 {
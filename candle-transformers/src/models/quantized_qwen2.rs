@@ -18,6 +18,7 @@ use candle::{
     quantized::{gguf_file, QMatMul},
     DType, Device, IndexOp, Result, Tensor,
 };
+use candle_nn::kv_cache::{QuantizedKvCache, QuantizedKvDType};
 use candle_nn::{Embedding, Module};
 use std::collections::HashMap;
 
@@ -56,6 +57,7 @@ struct LayerWeights {
     sin: Tensor,
     neg_inf: Tensor,
     kv_cache: Option<(Tensor, Tensor)>,
+    quantized_kv_cache: Option<QuantizedKvCache>,
     span_attn: tracing::Span,
     span_rot: tracing::Span,
     span_mlp: tracing::Span,
@@ -112,19 +114,30 @@ impl LayerWeights {
         let q = self.apply_rotary_emb(&q, index_pos)?;
         let k = self.apply_rotary_emb(&k, index_pos)?;
 
-        let (k, v) = match &self.kv_cache {
-            None => (k, v),
-            Some((k_cache, v_cache)) => {
+        let (k, v) = match &mut self.quantized_kv_cache {
+            Some(cache) => {
                 if index_pos == 0 {
-                    (k, v)
-                } else {
-                    let k = Tensor::cat(&[k_cache, &k], 2)?;
-                    let v = Tensor::cat(&[v_cache, &v], 2)?;
-                    (k, v)
+                    cache.reset()
                 }
+                cache.append(&k, &v)?
+            }
+            None => {
+                let (k, v) = match &self.kv_cache {
+                    None => (k, v),
+                    Some((k_cache, v_cache)) => {
+                        if index_pos == 0 {
+                            (k, v)
+                        } else {
+                            let k = Tensor::cat(&[k_cache, &k], 2)?;
+                            let v = Tensor::cat(&[v_cache, &v], 2)?;
+                            (k, v)
+                        }
+                    }
+                };
+                self.kv_cache = Some((k.clone(), v.clone()));
+                (k, v)
             }
         };
-        self.kv_cache = Some((k.clone(), v.clone()));
 
         // Support for MQA, useful for 70B models and mistral.
         let k = repeat_kv(k, self.n_head / self.n_kv_head)?;
@@ -273,6 +286,7 @@ impl ModelWeights {
                 head_dim,
                 neg_inf: neg_inf.clone(),
                 kv_cache: None,
+                quantized_kv_cache: None,
                 span_attn,
                 span_rot,
                 span_mlp,
@@ -306,6 +320,25 @@ impl ModelWeights {
         }
     }
 
+    pub fn clear_kv_cache(&mut self) {
+        for layer in self.layers.iter_mut() {
+            layer.kv_cache = None;
+            if let Some(cache) = layer.quantized_kv_cache.as_mut() {
+                cache.reset()
+            }
+        }
+    }
+
+    /// Stores the keys and values block-quantized with the given dtype rather than in full
+    /// precision, this reduces the memory used by long contexts.
+    pub fn with_kv_cache_dtype(mut self, dtype: QuantizedKvDType) -> Self {
+        for layer in self.layers.iter_mut() {
+            layer.kv_cache = None;
+            layer.quantized_kv_cache = Some(QuantizedKvCache::new(2, dtype))
+        }
+        self
+    }
+
     pub fn forward(&mut self, x: &Tensor, index_pos: usize) -> Result<Tensor> {
         let (_b_sz, seq_len) = x.dims2()?;
         let mask = if seq_len == 1 {
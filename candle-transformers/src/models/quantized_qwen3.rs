@@ -7,10 +7,13 @@
 //! - [Qwen3 Models](https://huggingface.co/Qwen/Qwen3-0.6B) (architecture based on official implementations)
 //!
 use super::with_tracing::QMatMul;
+use crate::quantized_var_builder::VarBuilder;
 use crate::{quantized_nn::RmsNorm, utils::repeat_kv};
 use candle::quantized::{gguf_file, QTensor};
 use candle::{DType, Device, Result, Tensor};
-use candle_nn::{kv_cache::ConcatKvCache, Activation, Embedding, Module};
+use candle_nn::kv_cache::{ConcatKvCache, KvSnapshot, QuantizedKvCache, QuantizedKvDType};
+use candle_nn::paged_attention::{PagedAttentionMetadata, PagedKvCache};
+use candle_nn::{Activation, Embedding, Module};
 use std::io::{Read, Seek};
 use std::sync::Arc;
 
@@ -70,6 +73,19 @@ impl MlpWeights {
     }
 }
 
+impl MlpWeights {
+    fn from_var_builder(cfg: &super::qwen3::Config, vb: VarBuilder) -> Result<Self> {
+        let (hidden, inter) = (cfg.hidden_size, cfg.intermediate_size);
+        Ok(Self {
+            gate_proj: QMatMul::new(hidden, inter, vb.pp("gate_proj"))?,
+            up_proj: QMatMul::new(hidden, inter, vb.pp("up_proj"))?,
+            down_proj: QMatMul::new(inter, hidden, vb.pp("down_proj"))?,
+            act_fn: cfg.hidden_act,
+            span: tracing::span!(tracing::Level::TRACE, "mlp"),
+        })
+    }
+}
+
 impl Module for MlpWeights {
     fn forward(&self, x: &Tensor) -> Result<Tensor> {
         let _enter = self.span.enter();
@@ -121,6 +137,20 @@ impl RotaryEmbedding {
         let k_embed = candle_nn::rotary_emb::rope(&k.contiguous()?, &cos, &sin)?;
         Ok((q_embed, k_embed))
     }
+
+    /// Apply RoPE using an explicit position for each element of the L dimension.
+    pub fn apply_positions(
+        &self,
+        q: &Tensor,
+        k: &Tensor,
+        positions: &Tensor,
+    ) -> Result<(Tensor, Tensor)> {
+        let cos = self.cos.index_select(positions, 0)?.to_dtype(q.dtype())?;
+        let sin = self.sin.index_select(positions, 0)?.to_dtype(q.dtype())?;
+        let q_embed = candle_nn::rotary_emb::rope(&q.contiguous()?, &cos, &sin)?;
+        let k_embed = candle_nn::rotary_emb::rope(&k.contiguous()?, &cos, &sin)?;
+        Ok((q_embed, k_embed))
+    }
 }
 
 #[derive(Debug, Clone)]
@@ -137,6 +167,7 @@ struct AttentionWeights {
     head_dim: usize,
     rotary_emb: Arc<RotaryEmbedding>,
     kv_cache: ConcatKvCache,
+    quantized_kv_cache: Option<QuantizedKvCache>,
     span_attn: tracing::Span,
 }
 
@@ -177,10 +208,36 @@ impl AttentionWeights {
             head_dim,
             rotary_emb,
             kv_cache,
+            quantized_kv_cache: None,
             span_attn,
         })
     }
 
+    fn from_var_builder(
+        cfg: &super::qwen3::Config,
+        rotary_emb: Arc<RotaryEmbedding>,
+        vb: VarBuilder,
+    ) -> Result<Self> {
+        let (num_heads, num_kv_heads) = (cfg.num_attention_heads, cfg.num_key_value_heads);
+        let (hidden, head_dim) = (cfg.hidden_size, cfg.head_dim);
+        Ok(Self {
+            q_proj: QMatMul::new(hidden, num_heads * head_dim, vb.pp("q_proj"))?,
+            k_proj: QMatMul::new(hidden, num_kv_heads * head_dim, vb.pp("k_proj"))?,
+            v_proj: QMatMul::new(hidden, num_kv_heads * head_dim, vb.pp("v_proj"))?,
+            o_proj: QMatMul::new(num_heads * head_dim, hidden, vb.pp("o_proj"))?,
+            q_norm: RmsNorm::new(head_dim, cfg.rms_norm_eps, vb.pp("q_norm"))?,
+            k_norm: RmsNorm::new(head_dim, cfg.rms_norm_eps, vb.pp("k_norm"))?,
+            num_heads,
+            num_kv_heads,
+            num_kv_groups: num_heads / num_kv_heads,
+            head_dim,
+            rotary_emb,
+            kv_cache: ConcatKvCache::new(2),
+            quantized_kv_cache: None,
+            span_attn: tracing::span!(tracing::Level::TRACE, "attn"),
+        })
+    }
+
     fn forward(&mut self, x: &Tensor, attn_mask: Option<&Tensor>, offset: usize) -> Result<Tensor> {
         let _enter = self.span_attn.enter();
         let (b, l, _) = x.dims3()?;
@@ -209,7 +266,10 @@ impl AttentionWeights {
 
         let (q, k) = self.rotary_emb.apply(&q, &k, offset)?;
 
-        let (k, v) = self.kv_cache.append(&k, &v)?;
+        let (k, v) = match &mut self.quantized_kv_cache {
+            Some(cache) => cache.append(&k, &v)?,
+            None => self.kv_cache.append(&k, &v)?,
+        };
 
         let k = repeat_kv(k, self.num_kv_groups)?.contiguous()?;
         let v = repeat_kv(v, self.num_kv_groups)?.contiguous()?;
@@ -234,8 +294,52 @@ impl AttentionWeights {
         self.o_proj.forward(&reshaped_ctx)
     }
 
+    fn forward_paged(
+        &self,
+        x: &Tensor,
+        cache: &mut PagedKvCache,
+        metadata: &PagedAttentionMetadata,
+    ) -> Result<Tensor> {
+        let _enter = self.span_attn.enter();
+        let (b, l, _) = x.dims3()?;
+
+        let q = self
+            .q_proj
+            .forward(x)?
+            .reshape((b, l, self.num_heads, self.head_dim))?
+            .transpose(1, 2)?;
+        let k = self
+            .k_proj
+            .forward(x)?
+            .reshape((b, l, self.num_kv_heads, self.head_dim))?
+            .transpose(1, 2)?;
+        let v = self
+            .v_proj
+            .forward(x)?
+            .reshape((l, self.num_kv_heads, self.head_dim))?;
+
+        let q = self.q_norm.forward(&q.flatten(0, 2)?)?;
+        let k = self.k_norm.forward(&k.flatten(0, 2)?)?;
+        let q = q.reshape((b, self.num_heads, l, self.head_dim))?;
+        let k = k.reshape((b, self.num_kv_heads, l, self.head_dim))?;
+
+        let (q, k) = self
+            .rotary_emb
+            .apply_positions(&q, &k, metadata.positions())?;
+        let q = q.transpose(1, 2)?.squeeze(0)?;
+        let k = k.transpose(1, 2)?.squeeze(0)?;
+
+        let scale = 1.0 / (self.head_dim as f32).sqrt();
+        let ctx = cache.forward(&q, &k, &v, metadata, scale)?;
+        let ctx = ctx.reshape((b, l, self.num_heads * self.head_dim))?;
+        self.o_proj.forward(&ctx)
+    }
+
     fn clear_kv_cache(&mut self) {
         self.kv_cache.reset();
+        if let Some(cache) = self.quantized_kv_cache.as_mut() {
+            cache.reset()
+        }
     }
 }
 
@@ -279,6 +383,20 @@ impl LayerWeights {
         })
     }
 
+    fn from_var_builder(
+        cfg: &super::qwen3::Config,
+        rotary: Arc<RotaryEmbedding>,
+        vb: VarBuilder,
+    ) -> Result<Self> {
+        let (hidden, eps) = (cfg.hidden_size, cfg.rms_norm_eps);
+        Ok(Self {
+            self_attn: AttentionWeights::from_var_builder(cfg, rotary, vb.pp("self_attn"))?,
+            mlp: MlpWeights::from_var_builder(cfg, vb.pp("mlp"))?,
+            ln1: RmsNorm::new(hidden, eps, vb.pp("input_layernorm"))?,
+            ln2: RmsNorm::new(hidden, eps, vb.pp("post_attention_layernorm"))?,
+        })
+    }
+
     fn forward(&mut self, x: &Tensor, mask: Option<&Tensor>, offset: usize) -> Result<Tensor> {
         let h = self.ln1.forward(x)?;
         let h = self.self_attn.forward(&h, mask, offset)?;
@@ -288,6 +406,20 @@ impl LayerWeights {
         x + h2
     }
 
+    fn forward_paged(
+        &self,
+        x: &Tensor,
+        cache: &mut PagedKvCache,
+        metadata: &PagedAttentionMetadata,
+    ) -> Result<Tensor> {
+        let h = self.ln1.forward(x)?;
+        let h = self.self_attn.forward_paged(&h, cache, metadata)?;
+        let x = (x + h)?;
+        let h2 = self.ln2.forward(&x)?;
+        let h2 = h2.apply(&self.mlp)?;
+        x + h2
+    }
+
     fn clear_kv_cache(&mut self) {
         self.self_attn.clear_kv_cache();
     }
@@ -301,6 +433,7 @@ pub struct ModelWeights {
     lm_head: QMatMul,
     device: Device,
     dtype: DType,
+    max_seq_len: usize,
     span: tracing::Span,
     span_output: tracing::Span,
 }
@@ -375,11 +508,58 @@ impl ModelWeights {
             lm_head,
             device: device.clone(),
             dtype,
+            max_seq_len: max_position_embeddings,
             span,
             span_output,
         })
     }
 
+    /// Loads a qwen3 model from a HF checkpoint loaded with [`VarBuilder::from_safetensors`] or
+    /// [`VarBuilder::from_gptq_safetensors`], the tensors use the same names as in
+    /// [`super::qwen3`].
+    pub fn from_var_builder(cfg: &super::qwen3::Config, vb: VarBuilder) -> Result<Self> {
+        let device = vb.device().clone();
+        let vb_m = vb.pp("model");
+        let embed_tensor = vb_m
+            .pp("embed_tokens")
+            .get((cfg.vocab_size, cfg.hidden_size), "weight")?;
+        let embed_tokens = Embedding::new(embed_tensor.dequantize(&device)?, cfg.hidden_size);
+        let rotary = Arc::new(RotaryEmbedding::new(
+            DType::F32,
+            cfg.head_dim,
+            cfg.max_position_embeddings,
+            cfg.rope_theta,
+            &device,
+        )?);
+        let mut layers = Vec::with_capacity(cfg.num_hidden_layers);
+        for i in 0..cfg.num_hidden_layers {
+            let vb_l = vb_m.pp(format!("layers.{i}"));
+            layers.push(LayerWeights::from_var_builder(cfg, rotary.clone(), vb_l)?)
+        }
+        let norm = RmsNorm::new(cfg.hidden_size, cfg.rms_norm_eps, vb_m.pp("norm"))?;
+        let lm_head = if cfg.tie_word_embeddings {
+            QMatMul::from_weights(embed_tensor)?
+        } else {
+            QMatMul::new(cfg.hidden_size, cfg.vocab_size, vb.pp("lm_head"))?
+        };
+        Ok(Self {
+            embed_tokens,
+            layers,
+            norm,
+            lm_head,
+            device,
+            dtype: DType::F32,
+            max_seq_len: cfg.max_position_embeddings,
+            span: tracing::span!(tracing::Level::TRACE, "model"),
+            span_output: tracing::span!(tracing::Level::TRACE, "output"),
+        })
+    }
+
+    /// The maximum number of positions, i.e. the size of the rotary embedding tables.
+    pub fn max_seq_len(&self) -> usize {
+        self.max_seq_len
+    }
+
     fn causal_mask(
         &self,
         b: usize,
@@ -408,6 +588,35 @@ impl ModelWeights {
     }
 
     pub fn forward(&mut self, input: &Tensor, offset: usize) -> Result<Tensor> {
+        let (_, l) = input.dims2()?;
+        let h = self.forward_hidden(input, offset)?;
+        let _enter = self.span_output.enter();
+        let last_hidden = h.narrow(1, l - 1, 1)?;
+        self.lm_head.forward(&last_hidden)?.squeeze(1)
+    }
+
+    /// Same as [`Self::forward`] but returns the logits for all the positions with shape
+    /// `(b, seq_len, vocab_size)`.
+    pub fn forward_all(&mut self, input: &Tensor, offset: usize) -> Result<Tensor> {
+        let h = self.forward_hidden(input, offset)?;
+        let _enter = self.span_output.enter();
+        self.lm_head.forward(&h)
+    }
+
+    /// Drops the kv-cache entries after the first `len` positions, e.g. to roll back tokens that
+    /// were rejected during speculative decoding.
+    pub fn truncate_kv_cache(&mut self, len: usize) -> Result<()> {
+        for layer in &mut self.layers {
+            let attn = &mut layer.self_attn;
+            match &mut attn.quantized_kv_cache {
+                Some(cache) => cache.truncate(len)?,
+                None => attn.kv_cache.truncate(len)?,
+            }
+        }
+        Ok(())
+    }
+
+    fn forward_hidden(&mut self, input: &Tensor, offset: usize) -> Result<Tensor> {
         let _enter = self.span.enter();
         let (b, l) = input.dims2()?;
         let mut h = self.embed_tokens.forward(input)?;
@@ -419,10 +628,7 @@ impl ModelWeights {
         for layer in &mut self.layers {
             h = layer.forward(&h, causal_mask.as_ref(), offset)?;
         }
-        let h = self.norm.forward(&h)?;
-        let _enter = self.span_output.enter();
-        let last_hidden = h.narrow(1, l - 1, 1)?;
-        self.lm_head.forward(&last_hidden)?.squeeze(1)
+        self.norm.forward(&h)
     }
 
     pub fn clear_kv_cache(&mut self) {
@@ -430,4 +636,103 @@ impl ModelWeights {
             layer.clear_kv_cache();
         }
     }
+
+    /// Returns a snapshot of the kv-cache of each layer, `None` if the cache is empty. This can be
+    /// used with [`candle_nn::prefix_cache::PrefixCache`] to skip recomputing a shared prompt
+    /// prefix.
+    pub fn kv_cache_snapshot(&self) -> Result<Option<Vec<KvSnapshot>>> {
+        let mut snapshots = Vec::with_capacity(self.layers.len());
+        for layer in self.layers.iter() {
+            let attn = &layer.self_attn;
+            let snapshot = match &attn.quantized_kv_cache {
+                Some(cache) => cache.snapshot()?,
+                None => attn.kv_cache.snapshot()?,
+            };
+            match snapshot {
+                None => return Ok(None),
+                Some(snapshot) => snapshots.push(snapshot),
+            }
+        }
+        Ok(Some(snapshots))
+    }
+
+    /// Restores the kv-caches from a snapshot, the next call to `forward` should then use the
+    /// snapshot length as `offset`.
+    pub fn restore_kv_cache(&mut self, snapshots: &[KvSnapshot]) -> Result<()> {
+        if snapshots.len() != self.layers.len() {
+            candle::bail!(
+                "expected {} kv-cache snapshots, got {}",
+                self.layers.len(),
+                snapshots.len()
+            )
+        }
+        for (layer, snapshot) in self.layers.iter_mut().zip(snapshots.iter()) {
+            let attn = &mut layer.self_attn;
+            match &mut attn.quantized_kv_cache {
+                Some(cache) => cache.restore(snapshot)?,
+                None => attn.kv_cache.restore(snapshot)?,
+            }
+        }
+        Ok(())
+    }
+
+    /// Stores the keys and values block-quantized with the given dtype rather than in full
+    /// precision, this reduces the memory used by long contexts.
+    pub fn with_kv_cache_dtype(mut self, dtype: QuantizedKvDType) -> Self {
+        for layer in &mut self.layers {
+            layer.self_attn.kv_cache.reset();
+            layer.self_attn.quantized_kv_cache = Some(QuantizedKvCache::new(2, dtype))
+        }
+        self
+    }
+
+    /// Creates one paged kv-cache per layer with the given number of blocks.
+    pub fn paged_kv_caches(
+        &self,
+        num_blocks: usize,
+        block_size: usize,
+    ) -> Result<Vec<PagedKvCache>> {
+        self.layers
+            .iter()
+            .map(|l| {
+                let attn = &l.self_attn;
+                PagedKvCache::new(
+                    num_blocks,
+                    block_size,
+                    attn.num_kv_heads,
+                    attn.head_dim,
+                    self.dtype,
+                    &self.device,
+                )
+            })
+            .collect()
+    }
+
+    /// Runs a batch step using paged kv-caches, `input` holds the token ids of all the sequences
+    /// packed as a single dimension as described in [`PagedAttentionMetadata`]. The returned
+    /// logits have shape `(num_seqs, vocab_size)` and correspond to the last token of each
+    /// sequence.
+    pub fn forward_paged(
+        &self,
+        input: &Tensor,
+        caches: &mut [PagedKvCache],
+        metadata: &PagedAttentionMetadata,
+    ) -> Result<Tensor> {
+        if caches.len() != self.layers.len() {
+            candle::bail!(
+                "expected {} paged kv-caches, got {}",
+                self.layers.len(),
+                caches.len()
+            )
+        }
+        let _enter = self.span.enter();
+        let mut h = self.embed_tokens.forward(input)?.unsqueeze(0)?;
+        for (layer, cache) in self.layers.iter().zip(caches.iter_mut()) {
+            h = layer.forward_paged(&h, cache, metadata)?;
+        }
+        let h = self.norm.forward(&h.squeeze(0)?)?;
+        let _enter = self.span_output.enter();
+        let last_hidden = metadata.last_tokens(&h)?;
+        self.lm_head.forward(&last_hidden)
+    }
 }
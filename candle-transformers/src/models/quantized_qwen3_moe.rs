@@ -5,7 +5,7 @@ use crate::quantized_nn::RmsNorm;
 use crate::utils::repeat_kv;
 use candle::quantized::gguf_file;
 use candle::{DType, Device, Result, Tensor};
-use candle_nn::kv_cache::ConcatKvCache;
+use candle_nn::kv_cache::{ConcatKvCache, QuantizedKvCache, QuantizedKvDType};
 use candle_nn::Linear;
 use candle_nn::{Embedding, Module};
 use std::sync::Arc;
@@ -56,6 +56,7 @@ pub struct QuantizedAttention {
     rotary_emb: Arc<RotaryEmbedding>,
     dtype: DType,
     kv_cache: ConcatKvCache,
+    quantized_kv_cache: Option<QuantizedKvCache>,
 }
 
 impl QuantizedAttention {
@@ -119,9 +120,17 @@ impl QuantizedAttention {
             rotary_emb: rotary_emb.clone(),
             dtype,
             kv_cache,
+            quantized_kv_cache: None,
         })
     }
 
+    fn clear_kv_cache(&mut self) {
+        self.kv_cache.reset();
+        if let Some(cache) = self.quantized_kv_cache.as_mut() {
+            cache.reset()
+        }
+    }
+
     pub fn forward(
         &mut self,
         x: &Tensor,
@@ -190,7 +199,10 @@ impl QuantizedAttention {
 
         let (q, k) = self.rotary_emb.apply(&q, &k, input_pos)?;
 
-        let (k, v) = self.kv_cache.append(&k, &v)?;
+        let (k, v) = match &mut self.quantized_kv_cache {
+            Some(cache) => cache.append(&k, &v)?,
+            None => self.kv_cache.append(&k, &v)?,
+        };
 
         let k = repeat_kv(k, self.num_kv_groups)?.contiguous()?;
         let v = repeat_kv(v, self.num_kv_groups)?.contiguous()?;
@@ -418,6 +430,22 @@ impl GGUFQWenMoE {
         Tensor::from_slice(&mask, (b, 1, tgt, tgt + offset), &self.device)?.to_dtype(self.dtype)
     }
 
+    pub fn clear_kv_cache(&mut self) {
+        for layer in self.layers.iter_mut() {
+            layer.self_attn.clear_kv_cache()
+        }
+    }
+
+    /// Stores the keys and values block-quantized with the given dtype rather than in full
+    /// precision, this reduces the memory used by long contexts.
+    pub fn with_kv_cache_dtype(mut self, dtype: QuantizedKvDType) -> Self {
+        for layer in self.layers.iter_mut() {
+            layer.self_attn.kv_cache.reset();
+            layer.self_attn.quantized_kv_cache = Some(QuantizedKvCache::new(2, dtype))
+        }
+        self
+    }
+
     pub fn forward(&mut self, x: &Tensor, offset: usize) -> Result<Tensor> {
         let mut xs = self.tok_embeddings.forward(x)?;
         let (b, l) = x.dims2()?;
@@ -449,6 +449,13 @@ impl T5Attention {
     fn clear_kv_cache(&mut self) {
         self.kv_cache = None
     }
+
+    fn reorder_kv_cache(&mut self, indices: &Tensor) -> Result<()> {
+        if let Some((k, v)) = &self.kv_cache {
+            self.kv_cache = Some((k.index_select(indices, 0)?, v.index_select(indices, 0)?))
+        }
+        Ok(())
+    }
 }
 
 #[derive(Debug, Clone)]
@@ -488,6 +495,10 @@ impl T5LayerSelfAttention {
     fn clear_kv_cache(&mut self) {
         self.self_attention.clear_kv_cache()
     }
+
+    fn reorder_kv_cache(&mut self, indices: &Tensor) -> Result<()> {
+        self.self_attention.reorder_kv_cache(indices)
+    }
 }
 
 #[derive(Debug, Clone)]
@@ -601,6 +612,10 @@ impl T5Block {
         self.self_attn.clear_kv_cache();
         self.cross_attn.iter_mut().for_each(|c| c.clear_kv_cache());
     }
+
+    fn reorder_kv_cache(&mut self, indices: &Tensor) -> Result<()> {
+        self.self_attn.reorder_kv_cache(indices)
+    }
 }
 
 #[derive(Debug, Clone)]
@@ -651,6 +666,13 @@ impl T5Stack {
     fn clear_kv_cache(&mut self) {
         self.block.iter_mut().for_each(|b| b.clear_kv_cache())
     }
+
+    fn reorder_kv_cache(&mut self, indices: &Tensor) -> Result<()> {
+        for b in self.block.iter_mut() {
+            b.reorder_kv_cache(indices)?
+        }
+        Ok(())
+    }
 }
 
 #[derive(Debug, Clone)]
@@ -756,12 +778,28 @@ impl T5ForConditionalGeneration {
         &mut self,
         decoder_input_ids: &Tensor,
         encoder_output: &Tensor,
+    ) -> Result<Tensor> {
+        let decoder_output = self.decode_hidden(decoder_input_ids, encoder_output)?;
+        let sequence_output = decoder_output
+            .narrow(1, decoder_output.dim(1)? - 1, 1)?
+            .squeeze(1)?;
+        self.logits(&sequence_output)
+    }
+
+    /// Returns the decoder hidden states for all the positions, i.e. the inputs of the language
+    /// modeling head.
+    pub fn decode_hidden(
+        &mut self,
+        decoder_input_ids: &Tensor,
+        encoder_output: &Tensor,
     ) -> Result<Tensor> {
         let _enter = self.span_decode.enter();
-        let decoder_output = self
-            .decoder
-            .forward(decoder_input_ids, Some(encoder_output))?;
+        self.decoder
+            .forward(decoder_input_ids, Some(encoder_output))
+    }
 
+    /// Applies the language modeling head to some decoder hidden states.
+    pub fn logits(&self, decoder_output: &Tensor) -> Result<Tensor> {
         let scaling_factor = if self.tie_word_embeddings {
             // Rescale output before projecting on vocab
             // See https://github.com/tensorflow/mesh/blob/fa19d69eafc9a482aff0b59ddd96b025c0cb207d/mesh_tensorflow/transformer/transformer.py#L586
@@ -769,14 +807,11 @@ impl T5ForConditionalGeneration {
         } else {
             1.0
         };
-        let sequence_output = ((decoder_output
-            .narrow(1, decoder_output.dim(1)? - 1, 1)?
-            .squeeze(1)?)
-            * scaling_factor)?;
+        let sequence_output = (decoder_output * scaling_factor)?;
         let output = {
             let _enter = self.span_decode_head.enter();
             match self.lm_head {
-                None => sequence_output.matmul(&self.shared.embeddings().t()?)?,
+                None => sequence_output.broadcast_matmul(&self.shared.embeddings().t()?)?,
                 Some(ref lm_head) => lm_head.forward(&sequence_output)?,
             }
         };
@@ -796,4 +831,10 @@ impl T5ForConditionalGeneration {
         self.encoder.clear_kv_cache();
         self.decoder.clear_kv_cache();
     }
+
+    /// Reorders the decoder kv-cache along the batch dimension, the new batch element `i`
+    /// continues the sequence of the batch element `indices[i]`.
+    pub fn reorder_kv_cache(&mut self, indices: &Tensor) -> Result<()> {
+        self.decoder.reorder_kv_cache(indices)
+    }
 }
@@ -396,6 +396,14 @@ impl ModelForCausalLM {
             .apply(&self.lm_head)
     }
 
+    /// Same as [`Self::forward`] but returns the logits for all the positions with shape
+    /// `(b_size, seq_len, vocab_size)`.
+    pub fn forward_all(&mut self, input_ids: &Tensor, seqlen_offset: usize) -> Result<Tensor> {
+        self.base_model
+            .forward(input_ids, seqlen_offset, None)?
+            .apply(&self.lm_head)
+    }
+
     pub fn clear_kv_cache(&mut self) {
         self.base_model.clear_kv_cache()
     }
@@ -383,6 +383,12 @@ impl ModelForCausalLM {
             .apply(&self.lm_head)
     }
 
+    /// Same as [`Self::forward`] but returns the logits for all the positions with shape
+    /// `(b_size, seq_len, vocab_size)`.
+    pub fn forward_all(&mut self, input: &Tensor, offset: usize) -> Result<Tensor> {
+        self.base.forward(input, offset)?.apply(&self.lm_head)
+    }
+
     pub fn clear_kv_cache(&mut self) {
         self.base.clear_kv_cache();
     }
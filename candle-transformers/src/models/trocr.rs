@@ -196,6 +196,13 @@ impl TrOCRAttention {
         self.kv_cache = None
     }
 
+    fn reorder_kv_cache(&mut self, indices: &Tensor) -> Result<()> {
+        if let Some((k, v)) = &self.kv_cache {
+            self.kv_cache = Some((k.index_select(indices, 0)?, v.index_select(indices, 0)?))
+        }
+        Ok(())
+    }
+
     fn _shape(&self, tensor: &Tensor, bsz: usize) -> Result<Tensor> {
         tensor
             .reshape((bsz, (), self.num_heads, self.head_dim))?
@@ -299,6 +306,10 @@ impl TrOCRDecoderLayer {
         self.self_attn.reset_kv_cache();
     }
 
+    fn reorder_kv_cache(&mut self, indices: &Tensor) -> Result<()> {
+        self.self_attn.reorder_kv_cache(indices)
+    }
+
     fn forward(
         &mut self,
         xs: &Tensor,
@@ -375,6 +386,13 @@ impl TrOCRDecoder {
         self.layers.iter_mut().for_each(|l| l.reset_kv_cache())
     }
 
+    fn reorder_kv_cache(&mut self, indices: &Tensor) -> Result<()> {
+        for layer in self.layers.iter_mut() {
+            layer.reorder_kv_cache(indices)?
+        }
+        Ok(())
+    }
+
     pub fn forward(
         &mut self,
         xs: &Tensor,
@@ -501,17 +519,42 @@ impl TrOCRModel {
         encoder_xs: &Tensor,
         past_kv_len: usize,
     ) -> Result<Tensor> {
+        let mask = Self::causal_mask(xs)?;
+        self.decoder
+            .forward(xs, Some(encoder_xs), past_kv_len, &mask)
+    }
+
+    /// Returns the decoder hidden states, i.e. the inputs of the output projection.
+    pub fn decode_hidden(
+        &mut self,
+        xs: &Tensor,
+        encoder_xs: &Tensor,
+        past_kv_len: usize,
+    ) -> Result<Tensor> {
+        let mask = Self::causal_mask(xs)?;
+        self.decoder
+            .decoder
+            .forward(xs, Some(encoder_xs), past_kv_len, &mask)
+    }
+
+    /// Applies the output projection to some decoder hidden states.
+    pub fn logits(&self, xs: &Tensor) -> Result<Tensor> {
+        xs.apply(&self.decoder.output_projection)
+    }
+
+    fn causal_mask(xs: &Tensor) -> Result<Tensor> {
         let seq_len = xs.dim(1)?;
         let mask: Vec<_> = (0..seq_len)
             .flat_map(|i| (0..seq_len).map(move |j| if j > i { f32::NEG_INFINITY } else { 0f32 }))
             .collect();
-        let mask = Tensor::from_vec(mask, (seq_len, seq_len), xs.device())?;
-
-        self.decoder
-            .forward(xs, Some(encoder_xs), past_kv_len, &mask)
+        Tensor::from_vec(mask, (seq_len, seq_len), xs.device())
     }
 
     pub fn reset_kv_cache(&mut self) {
         self.decoder.reset_kv_cache();
     }
+
+    pub fn reorder_kv_cache(&mut self, indices: &Tensor) -> Result<()> {
+        self.decoder.decoder.reorder_kv_cache(indices)
+    }
 }
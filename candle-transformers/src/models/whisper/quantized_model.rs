@@ -130,6 +130,13 @@ impl MultiHeadAttention {
     fn reset_kv_cache(&mut self) {
         self.kv_cache = None;
     }
+
+    fn reorder_kv_cache(&mut self, indices: &Tensor) -> Result<()> {
+        if let Some((k, v)) = &self.kv_cache {
+            self.kv_cache = Some((k.index_select(indices, 0)?, v.index_select(indices, 0)?))
+        }
+        Ok(())
+    }
 }
 
 // https://github.com/openai/whisper/blob/f572f2161ba831bae131364c3bffdead7af6d210/whisper/model.py#L111
@@ -200,6 +207,13 @@ impl ResidualAttentionBlock {
             attn.reset_kv_cache();
         }
     }
+
+    fn reorder_kv_cache(&mut self, indices: &Tensor) -> Result<()> {
+        if let Some((attn, _)) = &mut self.cross_attn {
+            attn.reorder_kv_cache(indices)?
+        }
+        Ok(())
+    }
 }
 
 fn sinusoids(length: usize, channels: usize, device: &Device) -> Result<Tensor> {
@@ -373,6 +387,15 @@ impl TextDecoder {
             block.reset_kv_cache();
         }
     }
+
+    /// Reorders the cached cross-attention keys and values along the batch dimension, the new
+    /// batch element `i` uses the cache of the batch element `indices[i]`.
+    pub fn reorder_kv_cache(&mut self, indices: &Tensor) -> Result<()> {
+        for block in self.blocks.iter_mut() {
+            block.reorder_kv_cache(indices)?
+        }
+        Ok(())
+    }
 }
 
 // https://github.com/openai/whisper/blob/f572f2161ba831bae131364c3bffdead7af6d210/whisper/model.py#L221
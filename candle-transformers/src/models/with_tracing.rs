@@ -102,6 +102,7 @@ pub fn conv2d(
 #[derive(Clone)]
 pub struct QMatMul {
     inner: candle::quantized::QMatMul,
+    imatrix: Option<Box<(String, candle::quantized::imatrix_file::ImatrixCollector)>>,
     span: tracing::Span,
 }
 
@@ -111,22 +112,35 @@ impl QMatMul {
         in_dim: usize,
         vb: crate::quantized_var_builder::VarBuilder,
     ) -> Result<Self> {
-        let ws = vb.get((in_dim, out_dim), "weight")?;
-        let inner = candle::quantized::QMatMul::from_arc(ws)?;
+        let inner = vb.get_qmatmul((in_dim, out_dim), "weight")?;
+        let imatrix = vb
+            .imatrix_collector()
+            .map(|c| Box::new((vb.path("weight"), c.clone())));
         let span = tracing::span!(tracing::Level::TRACE, "qmatmul");
-        Ok(Self { inner, span })
+        Ok(Self {
+            inner,
+            imatrix,
+            span,
+        })
     }
 
     pub fn from_weights(ws: std::sync::Arc<candle::quantized::QTensor>) -> Result<Self> {
         let inner = candle::quantized::QMatMul::from_arc(ws)?;
         let span = tracing::span!(tracing::Level::TRACE, "qmatmul");
-        Ok(Self { inner, span })
+        Ok(Self {
+            inner,
+            imatrix: None,
+            span,
+        })
     }
 }
 
 impl Module for QMatMul {
     fn forward(&self, xs: &Tensor) -> Result<Tensor> {
         let _enter = self.span.enter();
+        if let Some((name, collector)) = self.imatrix.as_deref() {
+            collector.record(name, xs)?
+        }
         self.inner.forward(xs)
     }
 }
@@ -0,0 +1,732 @@
+//! Chat templates.
+//!
+//! Renders the Jinja chat templates shipped with the models, either in the `chat_template` field
+//! of the HuggingFace `tokenizer_config.json` or in the `tokenizer.chat_template` metadata of gguf
+//! files. The templates are rendered the same way as `apply_chat_template` in transformers: the
+//! blocks are trimmed, the `raise_exception` and `strftime_now` functions are available, `tojson`
+//! produces python style json and the common python string and dict methods are supported.
+//!
+//! ```ignore
+//! use candle_transformers::pipelines::chat_template::{ChatTemplate, ChatTemplateOptions, Message};
+//!
+//! let template = ChatTemplate::from_tokenizer_config("tokenizer_config.json")?;
+//! let messages = [Message::system("You are helpful."), Message::user("Hello!")];
+//! let prompt = template.apply(&messages, &ChatTemplateOptions::for_generation())?;
+//! ```
+use candle::quantized::gguf_file;
+use candle::{Error, Result};
+use minijinja::value::{Kwargs, Value};
+use minijinja::{AutoEscape, Environment, ErrorKind};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+use std::path::Path;
+
+/// The name of the template used when a model provides several ones.
+pub const DEFAULT_TEMPLATE: &str = "default";
+/// The name of the template used when tools are provided, if the model has one.
+pub const TOOL_USE_TEMPLATE: &str = "tool_use";
+
+/// A part of a multimodal message.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum ContentPart {
+    Text {
+        text: String,
+    },
+    Image {
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        url: Option<String>,
+    },
+    ImageUrl {
+        image_url: ImageUrl,
+    },
+    Audio {
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        url: Option<String>,
+    },
+    Video {
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        url: Option<String>,
+    },
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ImageUrl {
+    pub url: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub detail: Option<String>,
+}
+
+/// The content of a message, either some text or a list of parts.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum Content {
+    Text(String),
+    Parts(Vec<ContentPart>),
+}
+
+impl Content {
+    /// The text of the content, the text parts get concatenated.
+    pub fn text(&self) -> String {
+        match self {
+            Self::Text(text) => text.clone(),
+            Self::Parts(parts) => parts
+                .iter()
+                .filter_map(|part| match part {
+                    ContentPart::Text { text } => Some(text.as_str()),
+                    _ => None,
+                })
+                .collect(),
+        }
+    }
+}
+
+impl From<&str> for Content {
+    fn from(text: &str) -> Self {
+        Self::Text(text.to_string())
+    }
+}
+
+impl From<String> for Content {
+    fn from(text: String) -> Self {
+        Self::Text(text)
+    }
+}
+
+impl From<Vec<ContentPart>> for Content {
+    fn from(parts: Vec<ContentPart>) -> Self {
+        Self::Parts(parts)
+    }
+}
+
+/// A function call made by the model.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct FunctionCall {
+    pub name: String,
+    /// The arguments, most templates expect an object here rather than the json string used by
+    /// the OpenAI API.
+    pub arguments: serde_json::Value,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ToolCall {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub id: Option<String>,
+    #[serde(rename = "type", default = "function_type")]
+    pub kind: String,
+    pub function: FunctionCall,
+}
+
+impl ToolCall {
+    pub fn function(name: impl Into<String>, arguments: serde_json::Value) -> Self {
+        Self {
+            id: None,
+            kind: function_type(),
+            function: FunctionCall {
+                name: name.into(),
+                arguments,
+            },
+        }
+    }
+
+    pub fn with_id(mut self, id: impl Into<String>) -> Self {
+        self.id = Some(id.into());
+        self
+    }
+}
+
+fn function_type() -> String {
+    "function".to_string()
+}
+
+/// A chat message, the optional fields are only passed to the template when set.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Message {
+    pub role: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub content: Option<Content>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub tool_calls: Vec<ToolCall>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tool_call_id: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+}
+
+impl Message {
+    pub fn new(role: impl Into<String>, content: impl Into<Content>) -> Self {
+        Self {
+            role: role.into(),
+            content: Some(content.into()),
+            tool_calls: vec![],
+            tool_call_id: None,
+            name: None,
+        }
+    }
+
+    pub fn system(content: impl Into<Content>) -> Self {
+        Self::new("system", content)
+    }
+
+    pub fn user(content: impl Into<Content>) -> Self {
+        Self::new("user", content)
+    }
+
+    pub fn assistant(content: impl Into<Content>) -> Self {
+        Self::new("assistant", content)
+    }
+
+    /// An assistant message calling some tools.
+    pub fn tool_calls(tool_calls: Vec<ToolCall>) -> Self {
+        Self {
+            role: "assistant".to_string(),
+            content: None,
+            tool_calls,
+            tool_call_id: None,
+            name: None,
+        }
+    }
+
+    /// The result of a tool call.
+    pub fn tool(content: impl Into<Content>, tool_call_id: Option<String>) -> Self {
+        Self {
+            tool_call_id,
+            ..Self::new("tool", content)
+        }
+    }
+
+    pub fn with_name(mut self, name: impl Into<String>) -> Self {
+        self.name = Some(name.into());
+        self
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct FunctionDefinition {
+    pub name: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+    /// The json schema for the arguments.
+    pub parameters: serde_json::Value,
+}
+
+/// A tool that the model can call.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Tool {
+    #[serde(rename = "type", default = "function_type")]
+    pub kind: String,
+    pub function: FunctionDefinition,
+}
+
+impl Tool {
+    pub fn function(
+        name: impl Into<String>,
+        description: Option<String>,
+        parameters: serde_json::Value,
+    ) -> Self {
+        Self {
+            kind: function_type(),
+            function: FunctionDefinition {
+                name: name.into(),
+                description,
+                parameters,
+            },
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct ChatTemplateOptions {
+    /// Adds the tokens that start an assistant message at the end of the prompt.
+    pub add_generation_prompt: bool,
+    /// Leaves the final message open so that the model continues it, e.g. to prefill the
+    /// beginning of the assistant response.
+    pub continue_final_message: bool,
+    /// The tools available to the model.
+    pub tools: Vec<Tool>,
+    /// Additional variables passed to the template, e.g. `enable_thinking`.
+    pub extra_context: HashMap<String, serde_json::Value>,
+}
+
+impl ChatTemplateOptions {
+    pub fn for_generation() -> Self {
+        Self {
+            add_generation_prompt: true,
+            ..Default::default()
+        }
+    }
+
+    pub fn with_tools(mut self, tools: Vec<Tool>) -> Self {
+        self.tools = tools;
+        self
+    }
+
+    pub fn with_context(mut self, name: impl Into<String>, value: serde_json::Value) -> Self {
+        self.extra_context.insert(name.into(), value);
+        self
+    }
+}
+
+/// A chat template together with the special tokens it can refer to.
+#[derive(Debug)]
+pub struct ChatTemplate {
+    env: Environment<'static>,
+    names: Vec<String>,
+    special_tokens: BTreeMap<String, String>,
+}
+
+impl ChatTemplate {
+    /// Compiles a single template.
+    pub fn new(template: impl Into<String>) -> Result<Self> {
+        Self::from_named_templates(vec![(DEFAULT_TEMPLATE.to_string(), template.into())])
+    }
+
+    /// Compiles the templates of a model providing several ones, e.g. a `default` and a
+    /// `tool_use` template.
+    pub fn from_named_templates(templates: Vec<(String, String)>) -> Result<Self> {
+        if templates.is_empty() {
+            candle::bail!("no chat template provided")
+        }
+        let mut env = Environment::new();
+        env.set_trim_blocks(true);
+        env.set_lstrip_blocks(true);
+        env.set_auto_escape_callback(|_| AutoEscape::None);
+        env.set_unknown_method_callback(minijinja_contrib::pycompat::unknown_method_callback);
+        env.add_filter("tojson", tojson);
+        env.add_function("raise_exception", raise_exception);
+        env.add_function("strftime_now", strftime_now);
+        let mut names = Vec::with_capacity(templates.len());
+        for (name, template) in templates {
+            env.add_template_owned(name.clone(), template)
+                .map_err(Error::wrap)?;
+            names.push(name)
+        }
+        Ok(Self {
+            env,
+            names,
+            special_tokens: BTreeMap::new(),
+        })
+    }
+
+    /// Loads the chat template and the special tokens from a `tokenizer_config.json` file.
+    pub fn from_tokenizer_config<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let config = std::fs::read_to_string(path)?;
+        Self::from_tokenizer_config_str(&config)
+    }
+
+    /// Loads the chat template and the special tokens from the content of a
+    /// `tokenizer_config.json` file.
+    pub fn from_tokenizer_config_str(config: &str) -> Result<Self> {
+        let config: serde_json::Value = serde_json::from_str(config).map_err(Error::wrap)?;
+        let templates = match config.get("chat_template") {
+            Some(serde_json::Value::String(template)) => {
+                vec![(DEFAULT_TEMPLATE.to_string(), template.clone())]
+            }
+            Some(serde_json::Value::Array(templates)) => {
+                let mut named = Vec::with_capacity(templates.len());
+                for template in templates {
+                    match (template["name"].as_str(), template["template"].as_str()) {
+                        (Some(name), Some(template)) => {
+                            named.push((name.to_string(), template.to_string()))
+                        }
+                        _ => candle::bail!("unexpected chat template {template}"),
+                    }
+                }
+                named
+            }
+            _ => candle::bail!("no chat_template in the tokenizer config"),
+        };
+        let mut template = Self::from_named_templates(templates)?;
+        if let Some(config) = config.as_object() {
+            for (name, value) in config.iter() {
+                if !name.ends_with("_token") {
+                    continue;
+                }
+                // Tokens are either plain strings or added tokens with a content field.
+                let token = match value {
+                    serde_json::Value::String(token) => Some(token.as_str()),
+                    value => value.get("content").and_then(|c| c.as_str()),
+                };
+                if let Some(token) = token {
+                    template = template.with_special_token(name, token)
+                }
+            }
+        }
+        Ok(template)
+    }
+
+    /// Loads the chat templates from the `tokenizer.chat_template` metadata of a gguf file, the
+    /// beginning and end of sequence tokens are looked up in the gguf vocabulary.
+    pub fn from_gguf(content: &gguf_file::Content) -> Result<Self> {
+        let md = &content.metadata;
+        let template = match md.get("tokenizer.chat_template") {
+            None => candle::bail!("no tokenizer.chat_template in the gguf metadata"),
+            Some(template) => template.to_string()?.clone(),
+        };
+        let mut templates = vec![(DEFAULT_TEMPLATE.to_string(), template)];
+        if let Some(names) = md.get("tokenizer.chat_templates") {
+            for name in names.to_vec()? {
+                let name = name.to_string()?;
+                if let Some(t) = md.get(&format!("tokenizer.chat_template.{name}")) {
+                    templates.push((name.clone(), t.to_string()?.clone()))
+                }
+            }
+        }
+        let mut template = Self::from_named_templates(templates)?;
+        if let Some(tokens) = md.get("tokenizer.ggml.tokens") {
+            let tokens = tokens.to_vec()?;
+            for (name, key) in [
+                ("bos_token", "tokenizer.ggml.bos_token_id"),
+                ("eos_token", "tokenizer.ggml.eos_token_id"),
+                ("unk_token", "tokenizer.ggml.unknown_token_id"),
+                ("pad_token", "tokenizer.ggml.padding_token_id"),
+            ] {
+                let token = match md.get(key) {
+                    None => continue,
+                    Some(id) => tokens.get(id.to_u32()? as usize),
+                };
+                if let Some(token) = token {
+                    template = template.with_special_token(name, token.to_string()?)
+                }
+            }
+        }
+        Ok(template)
+    }
+
+    /// Sets a special token variable such as `bos_token` or `eos_token`.
+    pub fn with_special_token(mut self, name: impl Into<String>, token: impl Into<String>) -> Self {
+        self.special_tokens.insert(name.into(), token.into());
+        self
+    }
+
+    pub fn special_token(&self, name: &str) -> Option<&str> {
+        self.special_tokens.get(name).map(|s| s.as_str())
+    }
+
+    pub fn bos_token(&self) -> Option<&str> {
+        self.special_token("bos_token")
+    }
+
+    pub fn eos_token(&self) -> Option<&str> {
+        self.special_token("eos_token")
+    }
+
+    pub fn template_names(&self) -> &[String] {
+        &self.names
+    }
+
+    // The `tool_use` template is picked when tools are provided, the `default` one otherwise.
+    fn template_name(&self, options: &ChatTemplateOptions) -> Result<&str> {
+        if self.names.len() == 1 {
+            return Ok(&self.names[0]);
+        }
+        let has = |name: &str| self.names.iter().any(|n| n == name);
+        if !options.tools.is_empty() && has(TOOL_USE_TEMPLATE) {
+            Ok(TOOL_USE_TEMPLATE)
+        } else if has(DEFAULT_TEMPLATE) {
+            Ok(DEFAULT_TEMPLATE)
+        } else {
+            candle::bail!("no default chat template among {:?}", self.names)
+        }
+    }
+
+    /// Renders the conversation.
+    pub fn apply(&self, messages: &[Message], options: &ChatTemplateOptions) -> Result<String> {
+        if options.add_generation_prompt && options.continue_final_message {
+            candle::bail!("add_generation_prompt and continue_final_message cannot be combined")
+        }
+        let template = self
+            .env
+            .get_template(self.template_name(options)?)
+            .map_err(Error::wrap)?;
+        let mut ctx: BTreeMap<String, Value> = self
+            .special_tokens
+            .iter()
+            .map(|(name, token)| (name.clone(), Value::from(token.as_str())))
+            .collect();
+        for (name, value) in options.extra_context.iter() {
+            ctx.insert(name.clone(), Value::from_serialize(value));
+        }
+        let tools = if options.tools.is_empty() {
+            Value::from(())
+        } else {
+            Value::from_serialize(&options.tools)
+        };
+        ctx.insert("messages".to_string(), Value::from_serialize(messages));
+        ctx.insert("tools".to_string(), tools);
+        ctx.insert(
+            "add_generation_prompt".to_string(),
+            Value::from(options.add_generation_prompt),
+        );
+        let mut rendered = template.render(ctx).map_err(Error::wrap)?;
+        if options.continue_final_message {
+            continue_final_message(&mut rendered, messages)?
+        }
+        Ok(rendered)
+    }
+}
+
+// Removes what follows the content of the final message, e.g. the end of turn token.
+fn continue_final_message(rendered: &mut String, messages: &[Message]) -> Result<()> {
+    let content = match messages.last().and_then(|m| m.content.as_ref()) {
+        None => candle::bail!("continue_final_message requires a final message with some content"),
+        Some(Content::Text(text)) => text.as_str(),
+        Some(Content::Parts(parts)) => {
+            let text = parts.iter().rev().find_map(|part| match part {
+                ContentPart::Text { text } => Some(text.as_str()),
+                _ => None,
+            });
+            match text {
+                None => candle::bail!("continue_final_message requires some text to continue"),
+                Some(text) => text,
+            }
+        }
+    };
+    let pos = match rendered.rfind(content.trim()) {
+        None => candle::bail!("the final message does not appear in the rendered chat"),
+        Some(pos) => pos,
+    };
+    // Keep the trailing whitespaces of the message if the template did not strip them.
+    let content = content.trim_start();
+    let end = if rendered[pos..].starts_with(content) {
+        pos + content.len()
+    } else {
+        pos + content.trim().len()
+    };
+    rendered.truncate(end);
+    Ok(())
+}
+
+fn raise_exception(msg: String) -> std::result::Result<Value, minijinja::Error> {
+    Err(minijinja::Error::new(ErrorKind::InvalidOperation, msg))
+}
+
+// The json serialization used by transformers, i.e. `json.dumps` with `ensure_ascii=False` by
+// default, this uses `", "` and `": "` as separators unless an indentation is set.
+fn tojson(value: Value, kwargs: Kwargs) -> std::result::Result<Value, minijinja::Error> {
+    let indent: Option<usize> = kwargs.get("indent")?;
+    let sort_keys: Option<bool> = kwargs.get("sort_keys")?;
+    let ensure_ascii: Option<bool> = kwargs.get("ensure_ascii")?;
+    kwargs.assert_all_used()?;
+    let err = |e: serde_json::Error| {
+        minijinja::Error::new(ErrorKind::InvalidOperation, "cannot serialize to json")
+            .with_source(e)
+    };
+    let mut out = vec![];
+    let formatter = PythonFormatter {
+        indent: indent.map(|n| vec![b' '; n]),
+        level: 0,
+        has_value: false,
+    };
+    let mut ser = serde_json::Serializer::with_formatter(&mut out, formatter);
+    if sort_keys.unwrap_or(false) {
+        let mut value = serde_json::to_value(&value).map_err(err)?;
+        sort_keys_recursively(&mut value);
+        value.serialize(&mut ser).map_err(err)?;
+    } else {
+        value.serialize(&mut ser).map_err(err)?;
+    }
+    // The formatter only writes valid utf-8.
+    let json = String::from_utf8(out).unwrap();
+    let json = if ensure_ascii.unwrap_or(false) {
+        let mut ascii = String::with_capacity(json.len());
+        for c in json.chars() {
+            if c.is_ascii() {
+                ascii.push(c)
+            } else {
+                let mut buf = [0u16; 2];
+                for u in c.encode_utf16(&mut buf) {
+                    ascii.push_str(&format!("\\u{u:04x}"))
+                }
+            }
+        }
+        ascii
+    } else {
+        json
+    };
+    Ok(Value::from_safe_string(json))
+}
+
+// The maps keep their insertion order, as python dicts do, so sorting has to be explicit.
+fn sort_keys_recursively(value: &mut serde_json::Value) {
+    match value {
+        serde_json::Value::Object(map) => {
+            map.sort_keys();
+            map.values_mut().for_each(sort_keys_recursively)
+        }
+        serde_json::Value::Array(values) => values.iter_mut().for_each(sort_keys_recursively),
+        _ => {}
+    }
+}
+
+struct PythonFormatter {
+    indent: Option<Vec<u8>>,
+    level: usize,
+    has_value: bool,
+}
+
+impl PythonFormatter {
+    fn begin_value<W: ?Sized + std::io::Write>(
+        &mut self,
+        writer: &mut W,
+        first: bool,
+    ) -> std::io::Result<()> {
+        match self.indent.as_ref() {
+            None if first => Ok(()),
+            None => writer.write_all(b", "),
+            Some(indent) => {
+                writer.write_all(if first { b"\n" } else { b",\n" })?;
+                for _ in 0..self.level {
+                    writer.write_all(indent)?
+                }
+                Ok(())
+            }
+        }
+    }
+
+    fn end_nested<W: ?Sized + std::io::Write>(&mut self, writer: &mut W) -> std::io::Result<()> {
+        self.level -= 1;
+        if let Some(indent) = self.indent.as_ref() {
+            if self.has_value {
+                writer.write_all(b"\n")?;
+                for _ in 0..self.level {
+                    writer.write_all(indent)?
+                }
+            }
+        }
+        self.has_value = true;
+        Ok(())
+    }
+}
+
+impl serde_json::ser::Formatter for PythonFormatter {
+    fn begin_array<W: ?Sized + std::io::Write>(&mut self, writer: &mut W) -> std::io::Result<()> {
+        self.level += 1;
+        self.has_value = false;
+        writer.write_all(b"[")
+    }
+
+    fn end_array<W: ?Sized + std::io::Write>(&mut self, writer: &mut W) -> std::io::Result<()> {
+        self.end_nested(writer)?;
+        writer.write_all(b"]")
+    }
+
+    fn begin_array_value<W: ?Sized + std::io::Write>(
+        &mut self,
+        writer: &mut W,
+        first: bool,
+    ) -> std::io::Result<()> {
+        self.begin_value(writer, first)
+    }
+
+    fn end_array_value<W: ?Sized + std::io::Write>(&mut self, _: &mut W) -> std::io::Result<()> {
+        self.has_value = true;
+        Ok(())
+    }
+
+    fn begin_object<W: ?Sized + std::io::Write>(&mut self, writer: &mut W) -> std::io::Result<()> {
+        self.level += 1;
+        self.has_value = false;
+        writer.write_all(b"{")
+    }
+
+    fn end_object<W: ?Sized + std::io::Write>(&mut self, writer: &mut W) -> std::io::Result<()> {
+        self.end_nested(writer)?;
+        writer.write_all(b"}")
+    }
+
+    fn begin_object_key<W: ?Sized + std::io::Write>(
+        &mut self,
+        writer: &mut W,
+        first: bool,
+    ) -> std::io::Result<()> {
+        self.begin_value(writer, first)
+    }
+
+    fn begin_object_value<W: ?Sized + std::io::Write>(
+        &mut self,
+        writer: &mut W,
+    ) -> std::io::Result<()> {
+        writer.write_all(b": ")
+    }
+
+    fn end_object_value<W: ?Sized + std::io::Write>(&mut self, _: &mut W) -> std::io::Result<()> {
+        self.has_value = true;
+        Ok(())
+    }
+}
+
+// Formats the current UTC time, only the most common directives are supported.
+fn strftime_now(format: String) -> std::result::Result<Value, minijinja::Error> {
+    const MONTHS: [&str; 12] = [
+        "January",
+        "February",
+        "March",
+        "April",
+        "May",
+        "June",
+        "July",
+        "August",
+        "September",
+        "October",
+        "November",
+        "December",
+    ];
+    const DAYS: [&str; 7] = [
+        "Monday",
+        "Tuesday",
+        "Wednesday",
+        "Thursday",
+        "Friday",
+        "Saturday",
+        "Sunday",
+    ];
+    let secs = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map_err(|e| minijinja::Error::new(ErrorKind::InvalidOperation, e.to_string()))?
+        .as_secs() as i64;
+    let days = secs.div_euclid(86400);
+    let secs = secs.rem_euclid(86400);
+    // Converts the number of days since the epoch to a date in the proleptic gregorian calendar.
+    let z = days + 719468;
+    let era = z.div_euclid(146097);
+    let doe = z - era * 146097;
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + i64::from(month <= 2);
+    // The epoch was a Thursday.
+    let weekday = (days + 3).rem_euclid(7) as usize;
+    let month_name = MONTHS[month as usize - 1];
+
+    let mut out = String::new();
+    let mut chars = format.chars();
+    while let Some(c) = chars.next() {
+        if c != '%' {
+            out.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('d') => out.push_str(&format!("{day:02}")),
+            Some('m') => out.push_str(&format!("{month:02}")),
+            Some('Y') => out.push_str(&year.to_string()),
+            Some('y') => out.push_str(&format!("{:02}", year % 100)),
+            Some('b') => out.push_str(&month_name[..3]),
+            Some('B') => out.push_str(month_name),
+            Some('a') => out.push_str(&DAYS[weekday][..3]),
+            Some('A') => out.push_str(DAYS[weekday]),
+            Some('H') => out.push_str(&format!("{:02}", secs / 3600)),
+            Some('M') => out.push_str(&format!("{:02}", secs / 60 % 60)),
+            Some('S') => out.push_str(&format!("{:02}", secs % 60)),
+            Some('%') => out.push('%'),
+            Some(c) => {
+                let msg = format!("unsupported strftime directive %{c}");
+                return Err(minijinja::Error::new(ErrorKind::InvalidOperation, msg));
+            }
+            None => out.push('%'),
+        }
+    }
+    Ok(Value::from(out))
+}
@@ -0,0 +1,333 @@
+//! Streaming detokenization and stop sequences.
+//!
+//! The [`Detokenizer`] turns the generated tokens back into text one token at a time. It works on
+//! the byte representation of the tokens from a [`Vocabulary`] so that characters split across
+//! multiple tokens, e.g. by the byte-fallback tokens of sentencepiece tokenizers, are only emitted
+//! once complete. Generation stops when an end-of-sequence token is sampled or when the text
+//! contains one of the stop sequences, these can span token boundaries. The text that could still
+//! turn out to be the beginning of a stop sequence is held back until this is decided, so that the
+//! stop sequence never gets streamed.
+//!
+//! ```ignore
+//! use candle_transformers::pipelines::detokenizer::Detokenizer;
+//!
+//! let mut detokenizer = Detokenizer::new(vocab)
+//!     .with_stop_sequences(vec!["\nUser:".to_string()])?
+//!     .with_max_tokens(256);
+//! loop {
+//!     let next_token = logits_processor.sample(&logits)?;
+//!     let output = detokenizer.next_token(next_token)?;
+//!     print!("{}", output.text);
+//!     if let Some(finish_reason) = output.finish_reason {
+//!         break;
+//!     }
+//! }
+//! ```
+use super::text_generation::FinishReason;
+use crate::generation::constrained::Vocabulary;
+use candle::Result;
+use std::sync::Arc;
+
+/// Incrementally decodes tokens to text, the bytes of an incomplete utf-8 character are kept
+/// until the following tokens complete it.
+#[derive(Debug, Clone)]
+pub struct IncrementalDecoder {
+    vocab: Arc<Vocabulary>,
+    pending: Vec<u8>,
+    strip_leading_space: bool,
+    at_start: bool,
+}
+
+impl IncrementalDecoder {
+    pub fn new(vocab: Arc<Vocabulary>) -> Self {
+        Self {
+            vocab,
+            pending: vec![],
+            strip_leading_space: false,
+            at_start: true,
+        }
+    }
+
+    /// Removes the space at the beginning of the decoded text, sentencepiece tokenizers add one
+    /// in front of the first word.
+    pub fn with_strip_leading_space(mut self, strip_leading_space: bool) -> Self {
+        self.strip_leading_space = strip_leading_space;
+        self
+    }
+
+    pub fn vocab(&self) -> &Vocabulary {
+        &self.vocab
+    }
+
+    /// Adds a token and returns the newly completed text, this can be empty.
+    pub fn push(&mut self, token: u32) -> Result<String> {
+        let bytes = match self.vocab.token_bytes(token) {
+            None => candle::bail!("token {token} is out of the vocabulary"),
+            Some(bytes) => bytes,
+        };
+        let bytes = match bytes.split_first() {
+            Some((b' ', rest)) if self.at_start && self.strip_leading_space => rest,
+            _ => bytes,
+        };
+        if !bytes.is_empty() {
+            self.at_start = false;
+        }
+        self.pending.extend_from_slice(bytes);
+        let mut text = String::new();
+        let mut start = 0;
+        loop {
+            match std::str::from_utf8(&self.pending[start..]) {
+                Ok(s) => {
+                    text.push_str(s);
+                    start = self.pending.len();
+                    break;
+                }
+                Err(err) => {
+                    let valid = start + err.valid_up_to();
+                    // The bytes up to `valid` have just been checked.
+                    text.push_str(std::str::from_utf8(&self.pending[start..valid]).unwrap());
+                    match err.error_len() {
+                        // An incomplete character at the end of the bytes.
+                        None => {
+                            start = valid;
+                            break;
+                        }
+                        Some(len) => {
+                            text.push(char::REPLACEMENT_CHARACTER);
+                            start = valid + len;
+                        }
+                    }
+                }
+            }
+        }
+        self.pending.drain(..start);
+        Ok(text)
+    }
+
+    /// Returns the bytes of an incomplete character as replacement characters.
+    pub fn flush(&mut self) -> String {
+        let text = String::from_utf8_lossy(&self.pending).into_owned();
+        self.pending.clear();
+        text
+    }
+
+    pub fn reset(&mut self) {
+        self.pending.clear();
+        self.at_start = true;
+    }
+}
+
+/// Finds stop sequences in streamed text, the end of the text that is a prefix of a stop sequence
+/// is held back until the next chunks tell whether the stop sequence is matched or not.
+#[derive(Debug, Clone, Default)]
+pub struct StopSequences {
+    sequences: Vec<String>,
+    held: String,
+    matched: Option<usize>,
+}
+
+impl StopSequences {
+    pub fn new(sequences: Vec<String>) -> Result<Self> {
+        if sequences.iter().any(|s| s.is_empty()) {
+            candle::bail!("stop sequences cannot be empty")
+        }
+        Ok(Self {
+            sequences,
+            held: String::new(),
+            matched: None,
+        })
+    }
+
+    pub fn sequences(&self) -> &[String] {
+        &self.sequences
+    }
+
+    /// The stop sequence that has been matched if any.
+    pub fn matched(&self) -> Option<&str> {
+        self.matched.map(|i| self.sequences[i].as_str())
+    }
+
+    /// Adds some text and returns the part of it that can be emitted. Once a stop sequence has
+    /// been matched, the text preceding it is returned and the remaining text is discarded.
+    pub fn push(&mut self, text: &str) -> String {
+        if self.matched.is_some() {
+            return String::new();
+        }
+        self.held.push_str(text);
+        // The earliest match, the longest stop sequence is preferred when several ones start at
+        // the same position.
+        let matched = self
+            .sequences
+            .iter()
+            .enumerate()
+            .filter_map(|(i, s)| self.held.find(s.as_str()).map(|pos| (pos, i)))
+            .min_by_key(|&(pos, i)| (pos, std::cmp::Reverse(self.sequences[i].len())));
+        if let Some((pos, i)) = matched {
+            self.matched = Some(i);
+            let mut text = std::mem::take(&mut self.held);
+            text.truncate(pos);
+            return text;
+        }
+        let hold = self
+            .sequences
+            .iter()
+            .map(|s| partial_match_len(&self.held, s))
+            .max()
+            .unwrap_or(0);
+        let emitted = self.held[..self.held.len() - hold].to_string();
+        self.held.drain(..self.held.len() - hold);
+        emitted
+    }
+
+    /// Returns the held back text, this is used when generation ends without matching a stop
+    /// sequence.
+    pub fn flush(&mut self) -> String {
+        std::mem::take(&mut self.held)
+    }
+
+    pub fn reset(&mut self) {
+        self.held.clear();
+        self.matched = None;
+    }
+}
+
+// The length of the longest suffix of `text` that is a strict prefix of `stop`.
+pub(crate) fn partial_match_len(text: &str, stop: &str) -> usize {
+    let max_len = text.len().min(stop.len() - 1);
+    (1..=max_len)
+        .rev()
+        .find(|&len| stop.is_char_boundary(len) && text.ends_with(&stop[..len]))
+        .unwrap_or(0)
+}
+
+/// The text produced for a token.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DetokenizedToken {
+    /// The text that can be streamed, this can be empty when the token only contains part of a
+    /// character or when the text may be the beginning of a stop sequence.
+    pub text: String,
+    /// Set when this token ends the generation.
+    pub finish_reason: Option<FinishReason>,
+}
+
+/// Streams the generated text and detects the end of the generation.
+#[derive(Debug, Clone)]
+pub struct Detokenizer {
+    decoder: IncrementalDecoder,
+    stop_sequences: StopSequences,
+    max_tokens: Option<usize>,
+    num_tokens: usize,
+    text: String,
+    finish_reason: Option<FinishReason>,
+}
+
+impl Detokenizer {
+    pub fn new(vocab: Arc<Vocabulary>) -> Self {
+        Self {
+            decoder: IncrementalDecoder::new(vocab),
+            stop_sequences: StopSequences::default(),
+            max_tokens: None,
+            num_tokens: 0,
+            text: String::new(),
+            finish_reason: None,
+        }
+    }
+
+    /// Generation stops once the text contains one of these sequences, the stop sequence and the
+    /// text following it are not part of the output.
+    pub fn with_stop_sequences(mut self, stop_sequences: Vec<String>) -> Result<Self> {
+        self.stop_sequences = StopSequences::new(stop_sequences)?;
+        Ok(self)
+    }
+
+    /// Generation stops after this number of tokens.
+    pub fn with_max_tokens(mut self, max_tokens: usize) -> Self {
+        self.max_tokens = Some(max_tokens);
+        self
+    }
+
+    /// See [`IncrementalDecoder::with_strip_leading_space`].
+    pub fn with_strip_leading_space(mut self, strip_leading_space: bool) -> Self {
+        self.decoder = self.decoder.with_strip_leading_space(strip_leading_space);
+        self
+    }
+
+    /// Processes the next generated token. End-of-sequence tokens are not decoded and end the
+    /// generation, the held back text is then returned.
+    pub fn next_token(&mut self, token: u32) -> Result<DetokenizedToken> {
+        if self.finish_reason.is_some() {
+            candle::bail!("the generation has already finished")
+        }
+        self.num_tokens += 1;
+        let mut finish_reason = None;
+        let mut text = if self.decoder.vocab().eos_tokens().contains(&token) {
+            finish_reason = Some(FinishReason::Eos);
+            String::new()
+        } else {
+            let text = self.decoder.push(token)?;
+            let text = self.stop_sequences.push(&text);
+            if self.stop_sequences.matched.is_some() {
+                finish_reason = Some(FinishReason::Stop)
+            }
+            text
+        };
+        if finish_reason.is_none() && self.max_tokens.is_some_and(|m| self.num_tokens >= m) {
+            finish_reason = Some(FinishReason::Length)
+        }
+        if let Some(finish_reason) = finish_reason {
+            text.push_str(&self.end(finish_reason))
+        }
+        self.text.push_str(&text);
+        Ok(DetokenizedToken {
+            text,
+            finish_reason,
+        })
+    }
+
+    /// Ends the generation for a reason the detokenizer cannot detect, e.g. a stop token, and
+    /// returns the held back text. This returns an empty string if the generation has already
+    /// finished.
+    pub fn finish(&mut self, finish_reason: FinishReason) -> String {
+        let text = self.end(finish_reason);
+        self.text.push_str(&text);
+        text
+    }
+
+    // Marks the generation as finished and returns the held back text.
+    fn end(&mut self, finish_reason: FinishReason) -> String {
+        if self.finish_reason.is_some() {
+            return String::new();
+        }
+        self.finish_reason = Some(finish_reason);
+        let decoded = self.decoder.flush();
+        let mut text = self.stop_sequences.push(&decoded);
+        text.push_str(&self.stop_sequences.flush());
+        text
+    }
+
+    /// The text that has been emitted so far.
+    pub fn text(&self) -> &str {
+        &self.text
+    }
+
+    pub fn num_tokens(&self) -> usize {
+        self.num_tokens
+    }
+
+    pub fn finish_reason(&self) -> Option<FinishReason> {
+        self.finish_reason
+    }
+
+    /// The stop sequence that ended the generation if any.
+    pub fn matched_stop_sequence(&self) -> Option<&str> {
+        self.stop_sequences.matched()
+    }
+
+    pub fn reset(&mut self) {
+        self.decoder.reset();
+        self.stop_sequences.reset();
+        self.num_tokens = 0;
+        self.text.clear();
+        self.finish_reason = None;
+    }
+}
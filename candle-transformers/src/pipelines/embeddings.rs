@@ -0,0 +1,561 @@
+//! Sentence embeddings and cross-encoder reranking.
+//!
+//! The [`EmbeddingsPipeline`] runs an [`EmbeddingModel`] on tokenized inputs and turns the hidden
+//! states into one embedding per input: the hidden states are pooled with one of the [`Pooling`]
+//! strategies, optionally truncated to their first dimensions for the models trained with
+//! Matryoshka representation learning, and L2 normalized. The [`Reranker`] scores query-document
+//! pairs with a [`CrossEncoder`]. Both group the inputs of similar lengths in batches, the
+//! padding is masked so that the results do not depend on how the inputs are batched.
+//!
+//! ```ignore
+//! use candle_transformers::pipelines::embeddings::{EmbeddingsPipeline, Pooling};
+//!
+//! let mut pipeline = EmbeddingsPipeline::new(bert_model, &device)
+//!     .with_pooling(Pooling::Mean)
+//!     .with_dimensions(Some(256));
+//! let inputs: Vec<_> = encodings.iter().map(|e| e.get_ids().to_vec().into()).collect();
+//! let embeddings = pipeline.embed(&inputs)?;
+//! ```
+use crate::models::{bert, modernbert, qwen2, stella_en_v5, xlm_roberta};
+use candle::{DType, Device, IndexOp, Result, Tensor, D};
+use std::ops::Range;
+
+/// A model returning the hidden states of its last layer.
+pub trait EmbeddingModel {
+    /// Returns the hidden states with shape `(batch, seq_len, hidden_size)`. The attention mask
+    /// is a `u32` tensor with shape `(batch, seq_len)` set to 1 for the tokens and 0 for the
+    /// padding, the token type ids are 0 for inputs without type ids.
+    fn hidden_states(
+        &mut self,
+        input_ids: &Tensor,
+        token_type_ids: &Tensor,
+        attention_mask: &Tensor,
+    ) -> Result<Tensor>;
+}
+
+/// A sequence classification model, used to score the relevance of query-document pairs.
+pub trait CrossEncoder {
+    /// Returns the logits with shape `(batch, num_labels)`, the inputs are the same as for
+    /// [`EmbeddingModel::hidden_states`].
+    fn logits(
+        &mut self,
+        input_ids: &Tensor,
+        token_type_ids: &Tensor,
+        attention_mask: &Tensor,
+    ) -> Result<Tensor>;
+}
+
+impl EmbeddingModel for bert::BertModel {
+    fn hidden_states(
+        &mut self,
+        input_ids: &Tensor,
+        token_type_ids: &Tensor,
+        attention_mask: &Tensor,
+    ) -> Result<Tensor> {
+        self.forward(input_ids, token_type_ids, Some(attention_mask))
+    }
+}
+
+impl EmbeddingModel for modernbert::ModernBert {
+    fn hidden_states(
+        &mut self,
+        input_ids: &Tensor,
+        _token_type_ids: &Tensor,
+        attention_mask: &Tensor,
+    ) -> Result<Tensor> {
+        self.forward(input_ids, attention_mask)
+    }
+}
+
+impl EmbeddingModel for xlm_roberta::XLMRobertaModel {
+    fn hidden_states(
+        &mut self,
+        input_ids: &Tensor,
+        token_type_ids: &Tensor,
+        attention_mask: &Tensor,
+    ) -> Result<Tensor> {
+        self.forward(input_ids, attention_mask, token_type_ids, None, None, None)
+    }
+}
+
+/// The GTE-Qwen models, these use left padding and last-token pooling.
+impl EmbeddingModel for qwen2::Model {
+    fn hidden_states(
+        &mut self,
+        input_ids: &Tensor,
+        _token_type_ids: &Tensor,
+        attention_mask: &Tensor,
+    ) -> Result<Tensor> {
+        self.clear_kv_cache();
+        let xs = self.forward(input_ids, 0, Some(attention_mask));
+        self.clear_kv_cache();
+        xs
+    }
+}
+
+/// The stella base model, the embedding head has to be applied to the pooled embeddings.
+impl EmbeddingModel for stella_en_v5::Model {
+    fn hidden_states(
+        &mut self,
+        input_ids: &Tensor,
+        _token_type_ids: &Tensor,
+        attention_mask: &Tensor,
+    ) -> Result<Tensor> {
+        self.forward(input_ids, attention_mask)
+    }
+}
+
+impl CrossEncoder for xlm_roberta::XLMRobertaForSequenceClassification {
+    fn logits(
+        &mut self,
+        input_ids: &Tensor,
+        token_type_ids: &Tensor,
+        attention_mask: &Tensor,
+    ) -> Result<Tensor> {
+        self.forward(input_ids, attention_mask, token_type_ids)
+    }
+}
+
+impl CrossEncoder for modernbert::ModernBertForSequenceClassification {
+    fn logits(
+        &mut self,
+        input_ids: &Tensor,
+        _token_type_ids: &Tensor,
+        attention_mask: &Tensor,
+    ) -> Result<Tensor> {
+        self.forward(input_ids, attention_mask)
+    }
+}
+
+/// How the hidden states of the tokens are combined into a single embedding.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Pooling {
+    /// The hidden state of the first token.
+    Cls,
+    /// The average of the hidden states.
+    #[default]
+    Mean,
+    /// The hidden state of the last token, used by the decoder based models.
+    LastToken,
+    /// The average of the hidden states weighted by their position, as in SGPT.
+    WeightedMean,
+}
+
+impl Pooling {
+    /// Reads the pooling mode from the `1_Pooling/config.json` file of a sentence-transformers
+    /// model.
+    pub fn from_sentence_transformers_config(config: &str) -> Result<Self> {
+        let config: serde_json::Value =
+            serde_json::from_str(config).map_err(candle::Error::wrap)?;
+        for (key, pooling) in [
+            ("pooling_mode_cls_token", Self::Cls),
+            ("pooling_mode_mean_tokens", Self::Mean),
+            ("pooling_mode_lasttoken", Self::LastToken),
+            ("pooling_mode_weightedmean_tokens", Self::WeightedMean),
+        ] {
+            if config.get(key).and_then(|v| v.as_bool()) == Some(true) {
+                return Ok(pooling);
+            }
+        }
+        candle::bail!("unsupported pooling mode in {config}")
+    }
+
+    /// Pools hidden states with shape `(batch, seq_len, hidden_size)` using an attention mask
+    /// with shape `(batch, seq_len)`, the padding can be on either side. `tokens` holds the
+    /// positions of the non-padding tokens of each input. The result is a `f32` tensor with
+    /// shape `(batch, hidden_size)`.
+    pub fn pool(
+        &self,
+        hidden_states: &Tensor,
+        attention_mask: &Tensor,
+        tokens: &[Range<usize>],
+    ) -> Result<Tensor> {
+        let hidden_states = hidden_states.to_dtype(DType::F32)?;
+        let mask = attention_mask.to_dtype(DType::F32)?;
+        match self {
+            Self::Cls | Self::LastToken => {
+                let (b_sz, seq_len, hidden_size) = hidden_states.dims3()?;
+                if tokens.len() != b_sz {
+                    candle::bail!("got {} token ranges for a batch of {b_sz}", tokens.len())
+                }
+                let indices = tokens
+                    .iter()
+                    .enumerate()
+                    .map(|(b, tokens)| {
+                        if tokens.is_empty() || tokens.end > seq_len {
+                            candle::bail!("invalid tokens {tokens:?} for input {b}")
+                        }
+                        let position = match self {
+                            Self::Cls => tokens.start,
+                            _ => tokens.end - 1,
+                        };
+                        Ok((b * seq_len + position) as u32)
+                    })
+                    .collect::<Result<Vec<_>>>()?;
+                let indices = Tensor::new(indices, hidden_states.device())?;
+                hidden_states
+                    .reshape((b_sz * seq_len, hidden_size))?
+                    .index_select(&indices, 0)
+            }
+            Self::Mean | Self::WeightedMean => {
+                let weights = match self {
+                    // The positions are counted from the first token to support left padding.
+                    Self::WeightedMean => (mask.cumsum(1)? * &mask)?,
+                    _ => mask,
+                };
+                let weights = weights.unsqueeze(D::Minus1)?;
+                let sum = hidden_states.broadcast_mul(&weights)?.sum(1)?;
+                sum.broadcast_div(&weights.sum(1)?)
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum PaddingSide {
+    #[default]
+    Right,
+    Left,
+}
+
+#[derive(Debug, Clone)]
+pub struct BatchConfig {
+    pub pad_token_id: u32,
+    pub padding_side: PaddingSide,
+    /// The maximum number of inputs in a batch.
+    pub max_batch_size: usize,
+    /// The maximum number of tokens in a batch including the padding, a longer input is
+    /// processed on its own.
+    pub max_batch_tokens: usize,
+}
+
+impl Default for BatchConfig {
+    fn default() -> Self {
+        Self {
+            pad_token_id: 0,
+            padding_side: PaddingSide::Right,
+            max_batch_size: 32,
+            max_batch_tokens: 16384,
+        }
+    }
+}
+
+/// A tokenized input, the token type ids can be left empty.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct EncodedInput {
+    pub ids: Vec<u32>,
+    pub type_ids: Vec<u32>,
+}
+
+impl EncodedInput {
+    pub fn new(ids: Vec<u32>) -> Self {
+        Self {
+            ids,
+            type_ids: vec![],
+        }
+    }
+
+    /// An input with token type ids, e.g. a query-document pair for a cross-encoder.
+    pub fn with_type_ids(ids: Vec<u32>, type_ids: Vec<u32>) -> Self {
+        Self { ids, type_ids }
+    }
+}
+
+impl From<Vec<u32>> for EncodedInput {
+    fn from(ids: Vec<u32>) -> Self {
+        Self::new(ids)
+    }
+}
+
+impl From<&[u32]> for EncodedInput {
+    fn from(ids: &[u32]) -> Self {
+        Self::new(ids.to_vec())
+    }
+}
+
+// A padded batch, `indexes` are the positions of its inputs in the original order.
+struct Batch {
+    indexes: Vec<usize>,
+    // The positions of the tokens of each input, excluding the padding.
+    tokens: Vec<Range<usize>>,
+    input_ids: Tensor,
+    token_type_ids: Tensor,
+    attention_mask: Tensor,
+}
+
+// Groups the inputs of similar lengths together and pads them.
+fn batches(inputs: &[EncodedInput], config: &BatchConfig, device: &Device) -> Result<Vec<Batch>> {
+    for (i, input) in inputs.iter().enumerate() {
+        if input.ids.is_empty() {
+            candle::bail!("input {i} is empty")
+        }
+        if !input.type_ids.is_empty() && input.type_ids.len() != input.ids.len() {
+            candle::bail!(
+                "input {i} has {} tokens but {} type ids",
+                input.ids.len(),
+                input.type_ids.len()
+            )
+        }
+    }
+    let mut order: Vec<usize> = (0..inputs.len()).collect();
+    order.sort_by_key(|&i| inputs[i].ids.len());
+    let mut groups: Vec<Vec<usize>> = vec![];
+    for i in order {
+        let len = inputs[i].ids.len();
+        match groups.last_mut() {
+            // The inputs are sorted so the current one is the longest of the group.
+            Some(group)
+                if group.len() < config.max_batch_size
+                    && (group.len() + 1) * len <= config.max_batch_tokens =>
+            {
+                group.push(i)
+            }
+            _ => groups.push(vec![i]),
+        }
+    }
+    groups
+        .into_iter()
+        .map(|indexes| {
+            let max_len = indexes
+                .iter()
+                .map(|&i| inputs[i].ids.len())
+                .max()
+                .unwrap_or(0);
+            let mut ids = Vec::with_capacity(indexes.len() * max_len);
+            let mut type_ids = Vec::with_capacity(indexes.len() * max_len);
+            let mut mask = Vec::with_capacity(indexes.len() * max_len);
+            let mut tokens = Vec::with_capacity(indexes.len());
+            for &i in indexes.iter() {
+                let input = &inputs[i];
+                let len = input.ids.len();
+                let pad = max_len - len;
+                let input_type_ids = if input.type_ids.is_empty() {
+                    vec![0; len]
+                } else {
+                    input.type_ids.clone()
+                };
+                tokens.push(match config.padding_side {
+                    PaddingSide::Left => pad..max_len,
+                    PaddingSide::Right => 0..len,
+                });
+                if config.padding_side == PaddingSide::Left {
+                    ids.extend(std::iter::repeat_n(config.pad_token_id, pad));
+                    type_ids.extend(std::iter::repeat_n(0, pad));
+                    mask.extend(std::iter::repeat_n(0u32, pad));
+                }
+                ids.extend_from_slice(&input.ids);
+                type_ids.extend_from_slice(&input_type_ids);
+                mask.extend(std::iter::repeat_n(1u32, len));
+                if config.padding_side == PaddingSide::Right {
+                    ids.extend(std::iter::repeat_n(config.pad_token_id, pad));
+                    type_ids.extend(std::iter::repeat_n(0, pad));
+                    mask.extend(std::iter::repeat_n(0u32, pad));
+                }
+            }
+            let shape = (indexes.len(), max_len);
+            Ok(Batch {
+                input_ids: Tensor::from_vec(ids, shape, device)?,
+                token_type_ids: Tensor::from_vec(type_ids, shape, device)?,
+                attention_mask: Tensor::from_vec(mask, shape, device)?,
+                indexes,
+                tokens,
+            })
+        })
+        .collect()
+}
+
+// Puts the rows of the batch results back in the order of the inputs.
+fn reorder(results: Vec<(Vec<usize>, Tensor)>, len: usize) -> Result<Tensor> {
+    let mut rows = vec![None; len];
+    for (indexes, result) in results {
+        for (row, index) in indexes.into_iter().enumerate() {
+            rows[index] = Some(result.get(row)?)
+        }
+    }
+    let rows: Vec<Tensor> = rows.into_iter().flatten().collect();
+    Tensor::stack(&rows, 0)
+}
+
+/// Computes sentence embeddings with an [`EmbeddingModel`].
+pub struct EmbeddingsPipeline<M: EmbeddingModel> {
+    model: M,
+    device: Device,
+    pooling: Pooling,
+    normalize: bool,
+    dimensions: Option<usize>,
+    batch_config: BatchConfig,
+}
+
+impl<M: EmbeddingModel> EmbeddingsPipeline<M> {
+    /// Creates a pipeline using mean pooling and L2 normalization.
+    pub fn new(model: M, device: &Device) -> Self {
+        Self {
+            model,
+            device: device.clone(),
+            pooling: Pooling::Mean,
+            normalize: true,
+            dimensions: None,
+            batch_config: BatchConfig::default(),
+        }
+    }
+
+    pub fn with_pooling(mut self, pooling: Pooling) -> Self {
+        self.pooling = pooling;
+        self
+    }
+
+    pub fn with_normalize(mut self, normalize: bool) -> Self {
+        self.normalize = normalize;
+        self
+    }
+
+    /// Truncates the embeddings to their first `dimensions` values, before normalization.
+    pub fn with_dimensions(mut self, dimensions: Option<usize>) -> Self {
+        self.dimensions = dimensions;
+        self
+    }
+
+    pub fn with_batch_config(mut self, batch_config: BatchConfig) -> Self {
+        self.batch_config = batch_config;
+        self
+    }
+
+    pub fn set_dimensions(&mut self, dimensions: Option<usize>) {
+        self.dimensions = dimensions
+    }
+
+    pub fn pooling(&self) -> Pooling {
+        self.pooling
+    }
+
+    pub fn model(&self) -> &M {
+        &self.model
+    }
+
+    pub fn model_mut(&mut self) -> &mut M {
+        &mut self.model
+    }
+
+    /// Returns the embeddings of the inputs as a `f32` tensor with shape
+    /// `(num_inputs, dimensions)`.
+    pub fn embed(&mut self, inputs: &[EncodedInput]) -> Result<Tensor> {
+        if inputs.is_empty() {
+            candle::bail!("no inputs to embed")
+        }
+        let mut results = vec![];
+        for batch in batches(inputs, &self.batch_config, &self.device)? {
+            let hidden_states = self.model.hidden_states(
+                &batch.input_ids,
+                &batch.token_type_ids,
+                &batch.attention_mask,
+            )?;
+            let embeddings =
+                self.pooling
+                    .pool(&hidden_states, &batch.attention_mask, &batch.tokens)?;
+            let embeddings = match self.dimensions {
+                None => embeddings,
+                Some(dimensions) => {
+                    let hidden_size = embeddings.dim(1)?;
+                    if dimensions == 0 || dimensions > hidden_size {
+                        candle::bail!(
+                            "cannot truncate embeddings of size {hidden_size} to {dimensions}"
+                        )
+                    }
+                    embeddings.narrow(1, 0, dimensions)?
+                }
+            };
+            let embeddings = if self.normalize {
+                // The norm is clamped so that all-zero embeddings do not turn into NaNs.
+                let norm = embeddings.sqr()?.sum_keepdim(1)?.sqrt()?.maximum(1e-12)?;
+                embeddings.broadcast_div(&norm)?
+            } else {
+                embeddings
+            };
+            results.push((batch.indexes, embeddings))
+        }
+        reorder(results, inputs.len())
+    }
+
+    /// Returns the embedding of a single input.
+    pub fn embed_one(&mut self, input: impl Into<EncodedInput>) -> Result<Vec<f32>> {
+        self.embed(&[input.into()])?.get(0)?.to_vec1()
+    }
+}
+
+/// Scores query-document pairs with a [`CrossEncoder`].
+pub struct Reranker<M: CrossEncoder> {
+    model: M,
+    device: Device,
+    sigmoid: bool,
+    batch_config: BatchConfig,
+}
+
+impl<M: CrossEncoder> Reranker<M> {
+    pub fn new(model: M, device: &Device) -> Self {
+        Self {
+            model,
+            device: device.clone(),
+            sigmoid: false,
+            batch_config: BatchConfig::default(),
+        }
+    }
+
+    /// Applies a sigmoid to the scores of single label models so that they are in `[0, 1]`.
+    pub fn with_sigmoid(mut self, sigmoid: bool) -> Self {
+        self.sigmoid = sigmoid;
+        self
+    }
+
+    pub fn with_batch_config(mut self, batch_config: BatchConfig) -> Self {
+        self.batch_config = batch_config;
+        self
+    }
+
+    pub fn model(&self) -> &M {
+        &self.model
+    }
+
+    pub fn model_mut(&mut self) -> &mut M {
+        &mut self.model
+    }
+
+    /// Returns the relevance score of each pair, the pairs are tokenized together as done by the
+    /// tokenizer for sentence pairs. For single label models the score is the logit, for models
+    /// with several labels it is the probability of the last one.
+    pub fn scores(&mut self, pairs: &[EncodedInput]) -> Result<Vec<f32>> {
+        if pairs.is_empty() {
+            return Ok(vec![]);
+        }
+        let mut results = vec![];
+        for batch in batches(pairs, &self.batch_config, &self.device)? {
+            let logits = self
+                .model
+                .logits(
+                    &batch.input_ids,
+                    &batch.token_type_ids,
+                    &batch.attention_mask,
+                )?
+                .to_dtype(DType::F32)?;
+            let num_labels = logits.dim(1)?;
+            let scores = if num_labels == 1 {
+                let logits = logits.squeeze(1)?;
+                if self.sigmoid {
+                    candle_nn::ops::sigmoid(&logits)?
+                } else {
+                    logits
+                }
+            } else {
+                candle_nn::ops::softmax_last_dim(&logits)?.i((.., num_labels - 1))?
+            };
+            results.push((batch.indexes, scores))
+        }
+        reorder(results, pairs.len())?.to_vec1()
+    }
+
+    /// Returns the indexes of the pairs with their scores, sorted by decreasing score.
+    pub fn rerank(&mut self, pairs: &[EncodedInput]) -> Result<Vec<(usize, f32)>> {
+        let mut ranked: Vec<_> = self.scores(pairs)?.into_iter().enumerate().collect();
+        ranked.sort_by(|a, b| b.1.total_cmp(&a.1));
+        Ok(ranked)
+    }
+}
@@ -1 +1,7 @@
+#[cfg(feature = "chat-template")]
+pub mod chat_template;
+pub mod detokenizer;
+pub mod embeddings;
 pub mod text_generation;
+#[cfg(feature = "chat-template")]
+pub mod tool_calls;
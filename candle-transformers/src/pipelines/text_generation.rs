@@ -1 +1,627 @@
+//! Continuous-batching text generation.
+//!
+//! The [`Engine`] keeps a queue of requests and runs a scheduler on each step: the running
+//! sequences get one more token decoded, new requests are admitted as soon as there is room in
+//! the batch and in the paged kv-cache, and long prompts are prefilled in chunks alongside the
+//! decode steps of the other sequences. Each step returns the newly sampled tokens so that they
+//! can be streamed back per request. When the engine has a [`Vocabulary`], the events also carry
+//! the detokenized text and requests can be stopped with stop sequences, see
+//! [`super::detokenizer`].
+//!
+//! ```ignore
+//! use candle_transformers::pipelines::text_generation::{Engine, EngineConfig, GenerationParams};
+//!
+//! let mut engine = Engine::new(model, EngineConfig::default(), &device)?;
+//! let id = engine.add_request(prompt_tokens, GenerationParams::default())?;
+//! while engine.has_pending_requests() {
+//!     for event in engine.step()? {
+//!         println!("{} {}", event.request_id, event.token);
+//!     }
+//! }
+//! ```
+use super::detokenizer::Detokenizer;
+use crate::generation::constrained::Vocabulary;
+use crate::generation::logprobs::SampledToken;
+use crate::generation::processors::{LogitsPipeline, RepetitionPenalty};
+use crate::generation::{LogitsProcessor, Sampling};
+use crate::models::{quantized_llama, quantized_qwen3};
+use candle::{Device, IndexOp, Result, Tensor};
+use candle_nn::paged_attention::{BlockManager, PagedAttentionMetadata, PagedKvCache};
+use std::collections::{HashMap, VecDeque};
+use std::sync::{mpsc, Arc};
 
+/// A model that can process a batch of sequences stored in paged kv-caches.
+pub trait PagedModel {
+    /// The maximum number of positions that the model supports, e.g. the size of its rotary
+    /// embedding tables.
+    fn max_seq_len(&self) -> usize;
+
+    /// Creates one paged kv-cache per attention layer.
+    fn paged_kv_caches(&self, num_blocks: usize, block_size: usize) -> Result<Vec<PagedKvCache>>;
+
+    /// Processes the packed tokens described by `metadata` and returns the logits for the last
+    /// token of each sequence with shape `(num_seqs, vocab_size)`.
+    fn forward_paged(
+        &self,
+        input: &Tensor,
+        caches: &mut [PagedKvCache],
+        metadata: &PagedAttentionMetadata,
+    ) -> Result<Tensor>;
+}
+
+impl PagedModel for quantized_llama::ModelWeights {
+    fn max_seq_len(&self) -> usize {
+        self.max_seq_len()
+    }
+
+    fn paged_kv_caches(&self, num_blocks: usize, block_size: usize) -> Result<Vec<PagedKvCache>> {
+        self.paged_kv_caches(num_blocks, block_size)
+    }
+
+    fn forward_paged(
+        &self,
+        input: &Tensor,
+        caches: &mut [PagedKvCache],
+        metadata: &PagedAttentionMetadata,
+    ) -> Result<Tensor> {
+        self.forward_paged(input, caches, metadata)
+    }
+}
+
+impl PagedModel for quantized_qwen3::ModelWeights {
+    fn max_seq_len(&self) -> usize {
+        self.max_seq_len()
+    }
+
+    fn paged_kv_caches(&self, num_blocks: usize, block_size: usize) -> Result<Vec<PagedKvCache>> {
+        self.paged_kv_caches(num_blocks, block_size)
+    }
+
+    fn forward_paged(
+        &self,
+        input: &Tensor,
+        caches: &mut [PagedKvCache],
+        metadata: &PagedAttentionMetadata,
+    ) -> Result<Tensor> {
+        self.forward_paged(input, caches, metadata)
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct EngineConfig {
+    /// The number of blocks in the paged kv-cache.
+    pub num_blocks: usize,
+    /// The number of tokens per block.
+    pub block_size: usize,
+    /// The maximum number of sequences processed in a step.
+    pub max_num_seqs: usize,
+    /// The maximum number of tokens processed in a step, prompts longer than this are prefilled
+    /// over multiple steps.
+    pub max_num_batched_tokens: usize,
+    /// The maximum number of tokens of a sequence, prompt included. This is capped by the
+    /// context length of the model, `None` uses the model context length.
+    pub max_seq_len: Option<usize>,
+}
+
+impl Default for EngineConfig {
+    fn default() -> Self {
+        Self {
+            num_blocks: 512,
+            block_size: 16,
+            max_num_seqs: 32,
+            max_num_batched_tokens: 512,
+            max_seq_len: None,
+        }
+    }
+}
+
+/// The per-request generation parameters.
+#[derive(Debug, Clone)]
+pub struct GenerationParams {
+    pub sampling: Sampling,
+    pub seed: u64,
+    pub max_new_tokens: usize,
+    /// Generation stops after sampling one of these tokens, the token is still returned.
+    pub stop_tokens: Vec<u32>,
+    /// Generation stops once the generated text contains one of these strings, this requires the
+    /// engine to have a vocabulary. The stop sequence is not included in the event texts.
+    pub stop_sequences: Vec<String>,
+    /// Penalty to be applied for repeating tokens, 1. means no penalty.
+    pub repeat_penalty: f32,
+    /// The context size to consider for the repeat penalty.
+    pub repeat_last_n: usize,
+    /// When set, the events include the log-probability of each sampled token and this number
+    /// of most likely alternatives.
+    pub logprobs: Option<usize>,
+}
+
+impl Default for GenerationParams {
+    fn default() -> Self {
+        Self {
+            sampling: Sampling::ArgMax,
+            seed: 299792458,
+            max_new_tokens: 256,
+            stop_tokens: vec![],
+            stop_sequences: vec![],
+            repeat_penalty: 1.,
+            repeat_last_n: 64,
+            logprobs: None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FinishReason {
+    /// A stop token was sampled or a stop sequence was generated.
+    Stop,
+    /// An end-of-sequence token was sampled.
+    Eos,
+    /// The maximum number of new tokens was reached or the sequence does not fit in the cache.
+    Length,
+    /// The request was aborted.
+    Aborted,
+}
+
+/// A token produced for a request.
+#[derive(Debug, Clone, PartialEq)]
+pub struct TokenEvent {
+    pub request_id: usize,
+    /// The sampled token, not set when the request ends without producing a new token, e.g. when
+    /// it gets aborted or does not fit in the kv-cache anymore.
+    pub token: Option<u32>,
+    /// The log-probabilities of the token when requested with [`GenerationParams::logprobs`],
+    /// not set on the final event of aborted requests.
+    pub logprobs: Option<SampledToken>,
+    /// The text that can be streamed after this token when the engine has a vocabulary, text
+    /// that may be the beginning of a stop sequence is held back.
+    pub text: Option<String>,
+    /// Set on the last event of a request.
+    pub finish_reason: Option<FinishReason>,
+}
+
+struct Request {
+    id: usize,
+    // The prompt followed by the generated tokens.
+    tokens: Vec<u32>,
+    prompt_len: usize,
+    // The number of tokens for which the kv-cache has been computed.
+    num_computed: usize,
+    params: GenerationParams,
+    pipeline: LogitsPipeline,
+    detokenizer: Option<Detokenizer>,
+    sender: Option<mpsc::Sender<TokenEvent>>,
+}
+
+impl Request {
+    fn num_generated(&self) -> usize {
+        self.tokens.len() - self.prompt_len
+    }
+
+    fn send(&self, event: &TokenEvent) {
+        if let Some(sender) = self.sender.as_ref() {
+            // The receiver may have been dropped, the request keeps running until it completes or
+            // gets aborted.
+            let _ = sender.send(event.clone());
+        }
+    }
+}
+
+/// A continuous-batching generation engine over a [`PagedModel`].
+pub struct Engine<M: PagedModel> {
+    model: M,
+    caches: Vec<PagedKvCache>,
+    blocks: BlockManager,
+    config: EngineConfig,
+    max_seq_len: usize,
+    device: Device,
+    waiting: VecDeque<Request>,
+    running: Vec<Request>,
+    next_id: usize,
+    vocab: Option<Arc<Vocabulary>>,
+    // The final events of the requests that ended outside of a forward pass, returned by the next
+    // step.
+    finished: Vec<TokenEvent>,
+}
+
+impl<M: PagedModel> Engine<M> {
+    pub fn new(model: M, config: EngineConfig, device: &Device) -> Result<Self> {
+        if config.max_num_seqs == 0 || config.max_num_batched_tokens == 0 {
+            candle::bail!("the engine has to process at least one token per step")
+        }
+        let caches = model.paged_kv_caches(config.num_blocks, config.block_size)?;
+        let blocks = BlockManager::new(config.num_blocks, config.block_size);
+        let max_seq_len = match config.max_seq_len {
+            None => model.max_seq_len(),
+            Some(max_seq_len) => max_seq_len.min(model.max_seq_len()),
+        };
+        Ok(Self {
+            model,
+            caches,
+            blocks,
+            config,
+            max_seq_len,
+            device: device.clone(),
+            waiting: VecDeque::new(),
+            running: vec![],
+            next_id: 0,
+            vocab: None,
+            finished: vec![],
+        })
+    }
+
+    /// Detokenizes the generated tokens with this vocabulary, the end-of-sequence tokens of the
+    /// vocabulary end the requests.
+    pub fn with_vocabulary(mut self, vocab: Arc<Vocabulary>) -> Self {
+        self.vocab = Some(vocab);
+        self
+    }
+
+    pub fn vocabulary(&self) -> Option<&Vocabulary> {
+        self.vocab.as_deref()
+    }
+
+    pub fn model(&self) -> &M {
+        &self.model
+    }
+
+    pub fn config(&self) -> &EngineConfig {
+        &self.config
+    }
+
+    /// The maximum number of tokens of a sequence, sequences that reach it finish with
+    /// [`FinishReason::Length`].
+    pub fn max_seq_len(&self) -> usize {
+        self.max_seq_len
+    }
+
+    pub fn num_waiting(&self) -> usize {
+        self.waiting.len()
+    }
+
+    pub fn num_running(&self) -> usize {
+        self.running.len()
+    }
+
+    /// Returns `true` while there are requests to process or final events that have not been
+    /// returned by [`Engine::step`] yet.
+    pub fn has_pending_requests(&self) -> bool {
+        !self.waiting.is_empty() || !self.running.is_empty() || !self.finished.is_empty()
+    }
+
+    fn new_request(
+        &mut self,
+        prompt: Vec<u32>,
+        params: GenerationParams,
+        pipeline: Option<LogitsPipeline>,
+        sender: Option<mpsc::Sender<TokenEvent>>,
+    ) -> Result<usize> {
+        if prompt.is_empty() {
+            candle::bail!("empty prompt")
+        }
+        let capacity = self.config.num_blocks * self.config.block_size;
+        if prompt.len() >= capacity {
+            candle::bail!(
+                "prompt of {} tokens does not fit in the kv-cache ({capacity} tokens)",
+                prompt.len()
+            )
+        }
+        if prompt.len() >= self.max_seq_len {
+            candle::bail!(
+                "prompt of {} tokens does not fit in the model context ({} tokens)",
+                prompt.len(),
+                self.max_seq_len
+            )
+        }
+        let detokenizer = match self.vocab.as_ref() {
+            Some(vocab) => {
+                let detokenizer = Detokenizer::new(vocab.clone())
+                    .with_stop_sequences(params.stop_sequences.clone())?;
+                Some(detokenizer)
+            }
+            None if params.stop_sequences.is_empty() => None,
+            None => candle::bail!("stop sequences require the engine to have a vocabulary"),
+        };
+        let id = self.next_id;
+        self.next_id += 1;
+        let pipeline = match pipeline {
+            Some(pipeline) => pipeline,
+            None => {
+                let sampler = LogitsProcessor::from_sampling(params.seed, params.sampling.clone());
+                let mut pipeline = LogitsPipeline::from_logits_processor(sampler);
+                if params.repeat_penalty != 1. {
+                    pipeline.push(RepetitionPenalty::new(
+                        params.repeat_penalty,
+                        params.repeat_last_n,
+                    ))
+                }
+                pipeline
+            }
+        };
+        self.waiting.push_back(Request {
+            id,
+            prompt_len: prompt.len(),
+            tokens: prompt,
+            num_computed: 0,
+            params,
+            pipeline,
+            detokenizer,
+            sender,
+        });
+        Ok(id)
+    }
+
+    /// Queues a new request and returns its id, this can be called between any two steps.
+    pub fn add_request(&mut self, prompt: Vec<u32>, params: GenerationParams) -> Result<usize> {
+        self.new_request(prompt, params, None, None)
+    }
+
+    /// Queues a new request that samples its tokens with a custom logits pipeline, the sampling,
+    /// seed and repeat penalty from `params` are ignored.
+    pub fn add_request_with_pipeline(
+        &mut self,
+        prompt: Vec<u32>,
+        params: GenerationParams,
+        pipeline: LogitsPipeline,
+    ) -> Result<usize> {
+        self.new_request(prompt, params, Some(pipeline), None)
+    }
+
+    /// Queues a new request, the generated tokens are also sent on the returned channel.
+    pub fn add_streaming_request(
+        &mut self,
+        prompt: Vec<u32>,
+        params: GenerationParams,
+    ) -> Result<(usize, mpsc::Receiver<TokenEvent>)> {
+        let (sender, receiver) = mpsc::channel();
+        let id = self.new_request(prompt, params, None, Some(sender))?;
+        Ok((id, receiver))
+    }
+
+    /// Removes a request from the engine, returns `false` if the request is unknown or has
+    /// already finished. The final event of the request is returned by the next step.
+    pub fn abort(&mut self, request_id: usize) -> Result<bool> {
+        let request = if let Some(pos) = self.waiting.iter().position(|r| r.id == request_id) {
+            self.waiting.remove(pos)
+        } else if let Some(pos) = self.running.iter().position(|r| r.id == request_id) {
+            Some(self.running.remove(pos))
+        } else {
+            None
+        };
+        let request = match request {
+            None => return Ok(false),
+            Some(request) => request,
+        };
+        if self.blocks.contains(request.id) {
+            self.blocks.remove_sequence(request.id)?
+        }
+        self.finish(&request, FinishReason::Aborted);
+        Ok(true)
+    }
+
+    // Moves the most recently admitted running request back to the waiting queue, its kv-cache
+    // gets recomputed once it is scheduled again.
+    fn preempt_last(&mut self) -> Result<()> {
+        if let Some(mut request) = self.running.pop() {
+            self.blocks.remove_sequence(request.id)?;
+            request.num_computed = 0;
+            self.waiting.push_front(request)
+        }
+        Ok(())
+    }
+
+    // Ends a request that has been removed from the queues without sampling a new token.
+    fn finish(&mut self, request: &Request, finish_reason: FinishReason) {
+        let event = TokenEvent {
+            request_id: request.id,
+            token: None,
+            logprobs: None,
+            text: None,
+            finish_reason: Some(finish_reason),
+        };
+        request.send(&event);
+        self.finished.push(event)
+    }
+
+    // Picks the number of tokens to process for each running request and reserves their slots in
+    // the paged kv-cache, the blocks that have to be copied are added to `copies`.
+    fn schedule(&mut self, copies: &mut Vec<(usize, usize)>) -> Result<Vec<usize>> {
+        let mut budget = self.config.max_num_batched_tokens;
+        let mut scheduled: Vec<usize> = vec![];
+        while scheduled.len() < self.running.len() && budget > 0 {
+            let idx = scheduled.len();
+            let request = &self.running[idx];
+            let n = (request.tokens.len() - request.num_computed).min(budget);
+            if self.blocks.can_append(request.id, n) {
+                copies.extend(self.blocks.append_slots(request.id, n)?);
+                budget -= n;
+                scheduled.push(n);
+            } else if idx + 1 < self.running.len() || idx > 0 {
+                // Free some blocks by preempting the most recently admitted request, this may be
+                // the current one.
+                self.preempt_last()?;
+            } else {
+                // A single sequence that does not fit in the whole cache.
+                let request = self.running.remove(idx);
+                self.blocks.remove_sequence(request.id)?;
+                self.finish(&request, FinishReason::Length);
+            }
+        }
+        // Requests that did not fit in the token budget are skipped for this step, new requests
+        // can only be admitted once all the running ones are scheduled.
+        if scheduled.len() < self.running.len() {
+            return Ok(scheduled);
+        }
+
+        // Admit new requests while there is room, their prompts get prefilled in chunks.
+        while budget > 0 && self.running.len() < self.config.max_num_seqs {
+            let request = match self.waiting.front() {
+                None => break,
+                Some(request) => request,
+            };
+            let n = request.tokens.len().min(budget);
+            if !self.blocks.can_append(request.id, n) {
+                if self.running.is_empty() {
+                    // A preempted request that has grown larger than the whole cache.
+                    let request = self.waiting.pop_front().expect("non empty queue");
+                    self.finish(&request, FinishReason::Length);
+                    continue;
+                }
+                break;
+            }
+            let request = self.waiting.pop_front().expect("non empty queue");
+            self.blocks.add_sequence(request.id)?;
+            copies.extend(self.blocks.append_slots(request.id, n)?);
+            self.running.push(request);
+            budget -= n;
+            scheduled.push(n);
+        }
+        Ok(scheduled)
+    }
+
+    /// Runs a single scheduler step and returns the sampled tokens, as well as the final events of
+    /// the requests that ended since the previous step. When the step fails, the requests of the
+    /// batch being processed are aborted as their kv-cache cannot be trusted anymore, the other
+    /// requests are not affected. A failure while scheduling aborts all the running requests.
+    pub fn step(&mut self) -> Result<Vec<TokenEvent>> {
+        let mut batch = vec![];
+        let err = match self.step_batch(&mut batch) {
+            Ok(events) => return Ok(events),
+            Err(err) => err,
+        };
+        if batch.is_empty() {
+            batch = self.running.iter().map(|r| r.id).collect();
+        }
+        for request_id in batch {
+            self.abort(request_id)?;
+        }
+        Err(err)
+    }
+
+    // The ids of the scheduled requests are added to `batch` before running the model.
+    fn step_batch(&mut self, batch: &mut Vec<usize>) -> Result<Vec<TokenEvent>> {
+        let mut copies = vec![];
+        let scheduled = self.schedule(&mut copies)?;
+        if scheduled.is_empty() {
+            return Ok(std::mem::take(&mut self.finished));
+        }
+        batch.extend(self.running[..scheduled.len()].iter().map(|r| r.id));
+        if !copies.is_empty() {
+            for cache in self.caches.iter_mut() {
+                cache.copy_blocks(&copies)?
+            }
+        }
+        let mut input = Vec::with_capacity(scheduled.iter().sum());
+        let mut seqs = Vec::with_capacity(scheduled.len());
+        for (request, &n) in self.running.iter().zip(scheduled.iter()) {
+            input
+                .extend_from_slice(&request.tokens[request.num_computed..request.num_computed + n]);
+            seqs.push((request.id, n));
+        }
+        let metadata = self.blocks.metadata(&seqs, &self.device)?;
+        let input = Tensor::new(input, &self.device)?;
+        let logits = self
+            .model
+            .forward_paged(&input, &mut self.caches, &metadata)?;
+        let mut events = std::mem::take(&mut self.finished);
+
+        let max_seq_len = self.max_seq_len;
+        let mut finished = vec![];
+        for (idx, (request, &n)) in self.running.iter_mut().zip(scheduled.iter()).enumerate() {
+            request.num_computed += n;
+            // The prompt is still being prefilled.
+            if request.num_computed < request.tokens.len() {
+                continue;
+            }
+            let params = &request.params;
+            if params.max_new_tokens == 0 {
+                let event = TokenEvent {
+                    request_id: request.id,
+                    token: None,
+                    logprobs: None,
+                    text: None,
+                    finish_reason: Some(FinishReason::Length),
+                };
+                request.send(&event);
+                events.push(event);
+                finished.push(idx);
+                continue;
+            }
+            let logits = logits.i(idx)?;
+            let (token, logprobs) = match params.logprobs {
+                None => {
+                    let token =
+                        request
+                            .pipeline
+                            .sample(&logits, &request.tokens, request.prompt_len)?;
+                    (token, None)
+                }
+                Some(top_n) => {
+                    let sampled = request.pipeline.sample_with_logprobs(
+                        &logits,
+                        &request.tokens,
+                        request.prompt_len,
+                        top_n,
+                    )?;
+                    (sampled.token, Some(sampled))
+                }
+            };
+            request.tokens.push(token);
+            let (mut text, mut finish_reason) = match request.detokenizer.as_mut() {
+                None => (None, None),
+                Some(detokenizer) => {
+                    let output = detokenizer.next_token(token)?;
+                    (Some(output.text), output.finish_reason)
+                }
+            };
+            if finish_reason.is_none() {
+                finish_reason = if params.stop_tokens.contains(&token) {
+                    Some(FinishReason::Stop)
+                } else if request.num_generated() >= params.max_new_tokens
+                    || request.tokens.len() >= max_seq_len
+                {
+                    Some(FinishReason::Length)
+                } else {
+                    None
+                };
+                // Release the text held back by the detokenizer.
+                if let (Some(finish_reason), Some(text), Some(detokenizer)) =
+                    (finish_reason, text.as_mut(), request.detokenizer.as_mut())
+                {
+                    text.push_str(&detokenizer.finish(finish_reason))
+                }
+            }
+            let event = TokenEvent {
+                request_id: request.id,
+                token: Some(token),
+                logprobs,
+                text,
+                finish_reason,
+            };
+            request.send(&event);
+            events.push(event);
+            if finish_reason.is_some() {
+                finished.push(idx)
+            }
+        }
+        for idx in finished.into_iter().rev() {
+            let request = self.running.remove(idx);
+            self.blocks.remove_sequence(request.id)?;
+        }
+        Ok(events)
+    }
+
+    /// Runs steps until all the requests have completed and returns the generated tokens for each
+    /// request, including the aborted ones.
+    pub fn run_to_completion(&mut self) -> Result<HashMap<usize, Vec<u32>>> {
+        let mut outputs: HashMap<usize, Vec<u32>> = HashMap::new();
+        while self.has_pending_requests() {
+            for event in self.step()? {
+                outputs
+                    .entry(event.request_id)
+                    .or_default()
+                    .extend(event.token)
+            }
+        }
+        Ok(outputs)
+    }
+}
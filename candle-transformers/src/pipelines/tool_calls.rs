@@ -0,0 +1,735 @@
+//! Parsing of the tool calls and of the reasoning in the generated text.
+//!
+//! Each model family uses its own format for tool calls, see [`ToolCallFormat`]. The
+//! [`ToolCallParser`] consumes the generated text as it is streamed and splits it into plain
+//! content, reasoning and tool calls. The content is returned as soon as it cannot be part of a
+//! tool call, the tool calls are returned once complete. When the text of a tool call cannot be
+//! parsed, it is returned as content instead.
+//!
+//! ```ignore
+//! use candle_transformers::pipelines::tool_calls::{ToolCallFormat, ToolCallParser};
+//!
+//! let mut parser = ToolCallParser::new(Some(ToolCallFormat::Hermes)).with_reasoning(false);
+//! for text in ["<think>The user wants", " the weather.</think>", "<tool_call>{\"name\": "] {
+//!     let delta = parser.push(text);
+//!     print!("{}", delta.content);
+//! }
+//! let delta = parser.push("\"get_weather\", \"arguments\": {}}</tool_call>");
+//! assert_eq!(delta.tool_calls[0].function.name, "get_weather");
+//! let delta = parser.finish();
+//! ```
+use super::chat_template::ToolCall;
+use super::detokenizer::partial_match_len;
+use serde_json::{Map, Value};
+
+/// The tag that starts a reasoning block.
+pub const THINK_START: &str = "<think>";
+/// The tag that ends a reasoning block.
+pub const THINK_END: &str = "</think>";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ToolCallFormat {
+    /// Json objects `{"name": ..., "arguments": ...}` wrapped in `<tool_call></tool_call>` tags,
+    /// used by Qwen 2.5, Qwen 3 and the Hermes models.
+    Hermes,
+    /// Json objects `{"name": ..., "parameters": ...}` separated by `;`, possibly preceded by
+    /// `<|python_tag|>`. Used by Llama 3.1 and later.
+    Llama3Json,
+    /// A python list of calls with keyword arguments, `[get_weather(city="Paris")]`, used by the
+    /// pythonic templates of Llama 3.2 and Llama 4.
+    Pythonic,
+    /// `[TOOL_CALLS]` followed by a json list of calls, or by `name[ARGS]{...}` with the recent
+    /// Mistral tokenizers.
+    Mistral,
+    /// `<|tool_call|>` followed by a json list of calls, used by Granite 3.
+    Granite,
+}
+
+impl ToolCallFormat {
+    pub const ALL: [Self; 5] = [
+        Self::Hermes,
+        Self::Llama3Json,
+        Self::Pythonic,
+        Self::Mistral,
+        Self::Granite,
+    ];
+
+    /// The format used by a model architecture, as found in the `general.architecture` gguf
+    /// metadata or in the `model_type` field of `config.json`.
+    pub fn from_architecture(architecture: &str) -> Option<Self> {
+        let format = match architecture {
+            "qwen2" | "qwen2_moe" | "qwen3" | "qwen3_moe" => Self::Hermes,
+            "llama" => Self::Llama3Json,
+            "llama4" => Self::Pythonic,
+            "mistral" | "mixtral" => Self::Mistral,
+            "granite" | "granitemoe" => Self::Granite,
+            _ => return None,
+        };
+        Some(format)
+    }
+
+    /// The text that starts the tool calls. Tokenizers often have it as a special token, the
+    /// detokenized text has to include it for the calls to be parsed.
+    pub fn start_tag(&self) -> Option<&'static str> {
+        match self {
+            Self::Hermes => Some("<tool_call>"),
+            Self::Llama3Json => Some("<|python_tag|>"),
+            Self::Pythonic => None,
+            Self::Mistral => Some("[TOOL_CALLS]"),
+            Self::Granite => Some("<|tool_call|>"),
+        }
+    }
+
+    // The first character of tool calls that are generated without a start tag, these are only
+    // detected at the beginning of the output.
+    fn bare_start(&self) -> Option<char> {
+        match self {
+            Self::Llama3Json => Some('{'),
+            Self::Pythonic => Some('['),
+            Self::Hermes | Self::Mistral | Self::Granite => None,
+        }
+    }
+}
+
+/// The text and tool calls produced by [`ToolCallParser::push`].
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ParsedDelta {
+    pub reasoning: String,
+    pub content: String,
+    pub tool_calls: Vec<ToolCall>,
+}
+
+impl ParsedDelta {
+    pub fn is_empty(&self) -> bool {
+        self.reasoning.is_empty() && self.content.is_empty() && self.tool_calls.is_empty()
+    }
+}
+
+// Splits the text between the reasoning blocks and the rest, the whitespace following the tags
+// is removed.
+#[derive(Debug, Clone)]
+struct ReasoningSplitter {
+    in_reasoning: bool,
+    held: String,
+    trim_start: bool,
+}
+
+impl ReasoningSplitter {
+    fn push(&mut self, text: &str, out: &mut Vec<(bool, String)>) {
+        let mut text = std::mem::take(&mut self.held) + text;
+        loop {
+            let tag = if self.in_reasoning {
+                THINK_END
+            } else {
+                THINK_START
+            };
+            if let Some(pos) = text.find(tag) {
+                self.emit(&text[..pos], out);
+                text.drain(..pos + tag.len());
+                self.in_reasoning = !self.in_reasoning;
+                self.trim_start = true;
+            } else {
+                let hold = partial_match_len(&text, tag);
+                self.emit(&text[..text.len() - hold], out);
+                self.held = text[text.len() - hold..].to_string();
+                return;
+            }
+        }
+    }
+
+    fn emit(&mut self, text: &str, out: &mut Vec<(bool, String)>) {
+        let text = if self.trim_start {
+            text.trim_start()
+        } else {
+            text
+        };
+        if !text.is_empty() {
+            self.trim_start = false;
+            out.push((self.in_reasoning, text.to_string()))
+        }
+    }
+
+    fn flush(&mut self, out: &mut Vec<(bool, String)>) {
+        let held = std::mem::take(&mut self.held);
+        self.emit(&held, out)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum State {
+    Content,
+    ToolCalls,
+    // Tool call parsing failed, the remaining text is content.
+    Text,
+}
+
+/// Incrementally splits the generated text into content, reasoning and tool calls.
+#[derive(Debug, Clone)]
+pub struct ToolCallParser {
+    format: Option<ToolCallFormat>,
+    reasoning: Option<ReasoningSplitter>,
+    state: State,
+    // Content that may be the beginning of the start tag, or leading whitespace while looking for
+    // tool calls without a start tag.
+    held: String,
+    // The text of the tool calls that have not been parsed yet, and the start tag that preceded
+    // them so that the text can be restored if parsing fails.
+    calls: String,
+    start_tag: &'static str,
+    at_start: bool,
+    // Set after a tool call, the whitespace that follows it is dropped.
+    skip_whitespace: bool,
+    num_calls: usize,
+}
+
+impl ToolCallParser {
+    /// Creates a parser for the tool calls in `format`, with `None` all the text that is not
+    /// reasoning is content.
+    pub fn new(format: Option<ToolCallFormat>) -> Self {
+        Self {
+            format,
+            reasoning: None,
+            state: State::Content,
+            held: String::new(),
+            calls: String::new(),
+            start_tag: "",
+            at_start: true,
+            skip_whitespace: false,
+            num_calls: 0,
+        }
+    }
+
+    /// Separates the reasoning in `<think></think>` blocks from the content. Some templates
+    /// already add the opening tag to the prompt, in which case `starts_in_reasoning` should be
+    /// set so that the output is reasoning until the closing tag.
+    pub fn with_reasoning(mut self, starts_in_reasoning: bool) -> Self {
+        self.reasoning = Some(ReasoningSplitter {
+            in_reasoning: starts_in_reasoning,
+            held: String::new(),
+            trim_start: starts_in_reasoning,
+        });
+        self
+    }
+
+    pub fn format(&self) -> Option<ToolCallFormat> {
+        self.format
+    }
+
+    /// The number of tool calls returned so far.
+    pub fn num_tool_calls(&self) -> usize {
+        self.num_calls
+    }
+
+    /// Adds some generated text.
+    pub fn push(&mut self, text: &str) -> ParsedDelta {
+        let mut delta = ParsedDelta::default();
+        match self.reasoning.as_mut() {
+            None => self.push_content(text, &mut delta),
+            Some(reasoning) => {
+                let mut segments = vec![];
+                reasoning.push(text, &mut segments);
+                self.push_segments(segments, &mut delta)
+            }
+        }
+        delta
+    }
+
+    /// Ends the output, the held back text is returned and the pending tool calls get parsed.
+    pub fn finish(&mut self) -> ParsedDelta {
+        let mut delta = ParsedDelta::default();
+        if let Some(reasoning) = self.reasoning.as_mut() {
+            let mut segments = vec![];
+            reasoning.flush(&mut segments);
+            self.push_segments(segments, &mut delta)
+        }
+        match self.state {
+            State::Content => delta.content.push_str(&std::mem::take(&mut self.held)),
+            State::ToolCalls => self.parse_calls(true, &mut delta),
+            State::Text => {}
+        }
+        delta
+    }
+
+    fn push_segments(&mut self, segments: Vec<(bool, String)>, delta: &mut ParsedDelta) {
+        for (is_reasoning, text) in segments {
+            if is_reasoning {
+                delta.reasoning.push_str(&text)
+            } else {
+                self.push_content(&text, delta)
+            }
+        }
+    }
+
+    fn push_content(&mut self, text: &str, delta: &mut ParsedDelta) {
+        let text = if self.skip_whitespace && self.state != State::ToolCalls {
+            let text = text.trim_start();
+            if text.is_empty() {
+                return;
+            }
+            self.skip_whitespace = false;
+            text
+        } else {
+            text
+        };
+        let format = match (self.state, self.format) {
+            (State::Text, _) | (_, None) => {
+                delta.content.push_str(text);
+                return;
+            }
+            (State::ToolCalls, _) => {
+                self.calls.push_str(text);
+                self.parse_calls(false, delta);
+                return;
+            }
+            (State::Content, Some(format)) => format,
+        };
+        let text = std::mem::take(&mut self.held) + text;
+        if self.at_start {
+            let trimmed = text.trim_start();
+            if trimmed.is_empty() {
+                self.held = text;
+                return;
+            }
+            self.at_start = false;
+            if let Some(c) = format.bare_start() {
+                if trimmed.starts_with(c) {
+                    self.start_tag = "";
+                    self.calls = text;
+                    self.state = State::ToolCalls;
+                    self.parse_calls(false, delta);
+                    return;
+                }
+            }
+        }
+        match format.start_tag() {
+            Some(tag) => {
+                if let Some(pos) = text.find(tag) {
+                    delta.content.push_str(&text[..pos]);
+                    self.start_tag = tag;
+                    self.calls = text[pos + tag.len()..].to_string();
+                    self.state = State::ToolCalls;
+                    self.parse_calls(false, delta);
+                } else {
+                    let hold = partial_match_len(&text, tag);
+                    delta.content.push_str(&text[..text.len() - hold]);
+                    self.held = text[text.len() - hold..].to_string();
+                }
+            }
+            None => delta.content.push_str(&text),
+        }
+    }
+
+    fn add_call(&mut self, call: ToolCall, delta: &mut ParsedDelta) {
+        self.num_calls += 1;
+        delta.tool_calls.push(call)
+    }
+
+    // Returns the text that could not be parsed as content, the start tag is only restored if no
+    // tool call has been parsed since it.
+    fn fail(&mut self, delta: &mut ParsedDelta) {
+        delta.content.push_str(self.start_tag);
+        delta.content.push_str(&std::mem::take(&mut self.calls));
+        self.start_tag = "";
+        self.state = State::Text;
+    }
+
+    fn parse_calls(&mut self, finished: bool, delta: &mut ParsedDelta) {
+        let format = match self.format {
+            None => return,
+            Some(format) => format,
+        };
+        match format {
+            ToolCallFormat::Hermes => self.parse_hermes(finished, delta),
+            ToolCallFormat::Pythonic => self.parse_pythonic(finished, delta),
+            ToolCallFormat::Llama3Json | ToolCallFormat::Mistral | ToolCallFormat::Granite => {
+                self.parse_json(format, finished, delta)
+            }
+        }
+    }
+
+    fn parse_hermes(&mut self, finished: bool, delta: &mut ParsedDelta) {
+        const END: &str = "</tool_call>";
+        match self.calls.find(END) {
+            Some(end) => match json_call(&self.calls[..end]) {
+                Some(call) => {
+                    self.add_call(call, delta);
+                    let rest = self.calls[end + END.len()..].to_string();
+                    self.calls.clear();
+                    self.start_tag = "";
+                    self.state = State::Content;
+                    self.skip_whitespace = true;
+                    self.push_content(&rest, delta)
+                }
+                None => self.fail(delta),
+            },
+            // Generation may stop on the closing tag, in which case it is not part of the text.
+            None if finished => match json_call(&self.calls) {
+                Some(call) => {
+                    self.calls.clear();
+                    self.add_call(call, delta)
+                }
+                None => self.fail(delta),
+            },
+            None => {}
+        }
+    }
+
+    fn parse_json(&mut self, format: ToolCallFormat, finished: bool, delta: &mut ParsedDelta) {
+        loop {
+            // The separators between the calls are only removed once the next call is parsed, so
+            // that they are part of the content if parsing fails.
+            let rest = match format {
+                ToolCallFormat::Llama3Json => self
+                    .calls
+                    .trim_start_matches(|c: char| c.is_whitespace() || c == ';' || c == ','),
+                ToolCallFormat::Mistral => {
+                    let mut s = self
+                        .calls
+                        .trim_start_matches(|c: char| c.is_whitespace() || c == ',');
+                    while let Some(rest) = s.strip_prefix("[TOOL_CALLS]") {
+                        s = rest.trim_start_matches(|c: char| c.is_whitespace() || c == ',')
+                    }
+                    s
+                }
+                _ => self.calls.trim_start(),
+            };
+            let start = self.calls.len() - rest.len();
+            if rest.is_empty() {
+                if finished && self.num_calls == 0 {
+                    self.fail(delta)
+                }
+                return;
+            }
+            if format == ToolCallFormat::Mistral && !rest.starts_with(['[', '{']) {
+                match self.parse_mistral_args(start, finished, delta) {
+                    Some(true) => continue,
+                    Some(false) => return,
+                    None => return self.fail(delta),
+                }
+            }
+            let end = match balanced_end(rest, false) {
+                Some(end) => start + end,
+                None if finished => return self.fail(delta),
+                None => {
+                    // Only json objects and lists can hold tool calls, anything else is text.
+                    if !rest.starts_with(['[', '{']) {
+                        self.fail(delta)
+                    }
+                    return;
+                }
+            };
+            let calls = match serde_json::from_str(&self.calls[start..end]) {
+                Ok(Value::Array(values)) => values.iter().map(value_call).collect(),
+                Ok(value) => value_call(&value).map(|call| vec![call]),
+                Err(_) => None,
+            };
+            match calls {
+                None => return self.fail(delta),
+                Some(calls) => {
+                    self.calls.drain(..end);
+                    self.start_tag = "";
+                    for call in calls {
+                        self.add_call(call, delta)
+                    }
+                }
+            }
+        }
+    }
+
+    // Parses a `name[ARGS]{...}` call starting at `start`, returns `Some(false)` if more text is
+    // needed and `None` if the text is not a call.
+    fn parse_mistral_args(
+        &mut self,
+        start: usize,
+        finished: bool,
+        delta: &mut ParsedDelta,
+    ) -> Option<bool> {
+        const ARGS: &str = "[ARGS]";
+        let pos = match self.calls[start..].find(ARGS) {
+            Some(pos) => start + pos,
+            None if finished => return None,
+            None => return Some(false),
+        };
+        // Some tokenizer versions also include the call id, `name[CALL_ID]id[ARGS]{...}`.
+        let (name, id) = match self.calls[start..pos].split_once("[CALL_ID]") {
+            Some((name, id)) => (name.trim(), Some(id.trim().to_string())),
+            None => (self.calls[start..pos].trim(), None),
+        };
+        if name.is_empty() || name.contains(char::is_whitespace) {
+            return None;
+        }
+        let args = &self.calls[pos + ARGS.len()..];
+        let args_start = self.calls.len() - args.trim_start().len();
+        let end = match balanced_end(&self.calls[args_start..], false) {
+            Some(end) => args_start + end,
+            None if finished => return None,
+            None => return Some(false),
+        };
+        let arguments: Value = serde_json::from_str(&self.calls[args_start..end]).ok()?;
+        let mut call = ToolCall::function(name, arguments);
+        call.id = id;
+        self.calls.drain(..end);
+        self.start_tag = "";
+        self.add_call(call, delta);
+        Some(true)
+    }
+
+    fn parse_pythonic(&mut self, finished: bool, delta: &mut ParsedDelta) {
+        let start = self.calls.len() - self.calls.trim_start().len();
+        let end = match balanced_end(&self.calls[start..], true) {
+            Some(end) => start + end,
+            None if finished => return self.fail(delta),
+            None => return,
+        };
+        match PythonParser::new(&self.calls[start..end]).calls() {
+            Some(calls) => {
+                for call in calls {
+                    self.add_call(call, delta)
+                }
+                // The text after the list of calls is content.
+                let rest = self.calls[end..].to_string();
+                self.calls.clear();
+                self.state = State::Text;
+                self.skip_whitespace = true;
+                self.push_content(&rest, delta)
+            }
+            None => self.fail(delta),
+        }
+    }
+}
+
+// Converts a json object with a `name` and `arguments` or `parameters` fields to a call, the
+// arguments can also be a json encoded string.
+fn value_call(value: &Value) -> Option<ToolCall> {
+    let name = value.get("name")?.as_str()?;
+    let arguments = match value.get("arguments").or_else(|| value.get("parameters")) {
+        None | Some(Value::Null) => Value::Object(Map::new()),
+        Some(Value::String(s)) => serde_json::from_str(s).unwrap_or(Value::String(s.clone())),
+        Some(arguments) => arguments.clone(),
+    };
+    let mut call = ToolCall::function(name, arguments);
+    call.id = value.get("id").and_then(|id| id.as_str()).map(String::from);
+    Some(call)
+}
+
+fn json_call(text: &str) -> Option<ToolCall> {
+    let value: Value = serde_json::from_str(text.trim()).ok()?;
+    value_call(&value)
+}
+
+// Returns the length of the json or python value at the beginning of `text` if it is complete,
+// by matching the brackets outside of the strings.
+fn balanced_end(text: &str, python: bool) -> Option<usize> {
+    let mut depth = 0usize;
+    let mut quote = None;
+    let mut escaped = false;
+    for (i, c) in text.char_indices() {
+        if let Some(q) = quote {
+            if escaped {
+                escaped = false
+            } else if c == '\\' {
+                escaped = true
+            } else if c == q {
+                quote = None
+            }
+            continue;
+        }
+        match c {
+            '"' => quote = Some(c),
+            '\'' if python => quote = Some(c),
+            '[' | '{' | '(' => depth += 1,
+            ']' | '}' | ')' => {
+                depth = depth.checked_sub(1)?;
+                if depth == 0 {
+                    return Some(i + 1);
+                }
+            }
+            _ if depth == 0 && !c.is_whitespace() => return None,
+            _ => {}
+        }
+    }
+    None
+}
+
+// A parser for lists of python function calls with literal keyword arguments.
+struct PythonParser<'a> {
+    text: &'a str,
+    pos: usize,
+}
+
+impl<'a> PythonParser<'a> {
+    fn new(text: &'a str) -> Self {
+        Self { text, pos: 0 }
+    }
+
+    fn rest(&self) -> &'a str {
+        &self.text[self.pos..]
+    }
+
+    fn skip_whitespace(&mut self) {
+        let rest = self.rest();
+        self.pos += rest.len() - rest.trim_start().len()
+    }
+
+    fn eat(&mut self, c: char) -> bool {
+        self.skip_whitespace();
+        if self.rest().starts_with(c) {
+            self.pos += c.len_utf8();
+            true
+        } else {
+            false
+        }
+    }
+
+    fn identifier(&mut self) -> Option<&'a str> {
+        self.skip_whitespace();
+        let rest = self.rest();
+        let len = rest
+            .find(|c: char| !(c.is_alphanumeric() || c == '_' || c == '.'))
+            .unwrap_or(rest.len());
+        if len == 0 || rest.starts_with(|c: char| c.is_ascii_digit()) {
+            return None;
+        }
+        self.pos += len;
+        Some(&rest[..len])
+    }
+
+    fn calls(mut self) -> Option<Vec<ToolCall>> {
+        let mut calls = vec![];
+        if !self.eat('[') {
+            return None;
+        }
+        if !self.eat(']') {
+            loop {
+                calls.push(self.call()?);
+                if self.eat(']') {
+                    break;
+                }
+                if !self.eat(',') {
+                    return None;
+                }
+            }
+        }
+        self.skip_whitespace();
+        (self.pos == self.text.len()).then_some(calls)
+    }
+
+    fn call(&mut self) -> Option<ToolCall> {
+        let name = self.identifier()?;
+        if !self.eat('(') {
+            return None;
+        }
+        let mut arguments = Map::new();
+        if !self.eat(')') {
+            loop {
+                let key = self.identifier()?;
+                if !self.eat('=') {
+                    return None;
+                }
+                arguments.insert(key.to_string(), self.value()?);
+                if self.eat(')') {
+                    break;
+                }
+                if !self.eat(',') {
+                    return None;
+                }
+            }
+        }
+        Some(ToolCall::function(name, Value::Object(arguments)))
+    }
+
+    // Parses the items of a list or tuple up to the closing bracket.
+    fn items(&mut self, close: char) -> Option<Vec<Value>> {
+        let mut items = vec![];
+        loop {
+            if self.eat(close) {
+                return Some(items);
+            }
+            items.push(self.value()?);
+            if !self.eat(',') {
+                return self.eat(close).then_some(items);
+            }
+        }
+    }
+
+    fn value(&mut self) -> Option<Value> {
+        self.skip_whitespace();
+        let rest = self.rest();
+        let c = rest.chars().next()?;
+        match c {
+            '"' | '\'' => self.string(c).map(Value::String),
+            '[' => {
+                self.pos += 1;
+                self.items(']').map(Value::Array)
+            }
+            '(' => {
+                self.pos += 1;
+                self.items(')').map(Value::Array)
+            }
+            '{' => {
+                self.pos += 1;
+                let mut map = Map::new();
+                loop {
+                    if self.eat('}') {
+                        return Some(Value::Object(map));
+                    }
+                    let key = match self.value()? {
+                        Value::String(s) => s,
+                        key => key.to_string(),
+                    };
+                    if !self.eat(':') {
+                        return None;
+                    }
+                    map.insert(key, self.value()?);
+                    if !self.eat(',') {
+                        return self.eat('}').then_some(Value::Object(map));
+                    }
+                }
+            }
+            _ => {
+                let len = rest
+                    .find(|c: char| !(c.is_alphanumeric() || "+-._".contains(c)))
+                    .unwrap_or(rest.len());
+                let token = &rest[..len];
+                self.pos += len;
+                match token {
+                    "True" => Some(Value::Bool(true)),
+                    "False" => Some(Value::Bool(false)),
+                    "None" => Some(Value::Null),
+                    _ => serde_json::from_str::<serde_json::Number>(token)
+                        .ok()
+                        .map(Value::Number),
+                }
+            }
+        }
+    }
+
+    fn string(&mut self, quote: char) -> Option<String> {
+        let mut chars = self.rest().char_indices().skip(1);
+        let mut out = String::new();
+        while let Some((i, c)) = chars.next() {
+            match c {
+                '\\' => {
+                    let (_, c) = chars.next()?;
+                    match c {
+                        'n' => out.push('\n'),
+                        't' => out.push('\t'),
+                        'r' => out.push('\r'),
+                        'u' => {
+                            let hex: String =
+                                (0..4).filter_map(|_| chars.next()).map(|c| c.1).collect();
+                            out.push(char::from_u32(u32::from_str_radix(&hex, 16).ok()?)?)
+                        }
+                        c => out.push(c),
+                    }
+                }
+                c if c == quote => {
+                    self.pos += i + 1;
+                    return Some(out);
+                }
+                c => out.push(c),
+            }
+        }
+        None
+    }
+}
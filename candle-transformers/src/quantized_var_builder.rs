@@ -3,19 +3,71 @@
 //! VarBuilder is a utility to store quantized tensors from a [GGUF model file](https://huggingface.co/docs/hub/gguf).
 //! These tensors can be loaded from disk using `from_gguf` or from an in-memory
 //! buffer using `from_gguf_buffer`.
+//!
+//! GPTQ and AWQ checkpoints can be loaded from safetensors files using `from_gptq_safetensors`,
+//! the linear layers are then built using `get_qmatmul` without any conversion step.
 
-use candle::quantized::QTensor;
-use candle::{Device, Result, Shape};
+use candle::quantized::gptq::{GptqConfig, GptqFormat, GptqTensor};
+use candle::quantized::imatrix_file::ImatrixCollector;
+use candle::quantized::{GgmlDType, QMatMul, QTensor};
+use candle::{DType, Device, Result, Shape, Tensor};
+use std::collections::HashMap;
 use std::sync::Arc;
 
 // VarBuilder specialized for QTensors
 #[derive(Clone)]
 pub struct VarBuilder {
-    data: Arc<std::collections::HashMap<String, Arc<QTensor>>>,
+    data: Arc<HashMap<String, Arc<QTensor>>>,
+    gptq: Arc<HashMap<String, Arc<GptqTensor>>>,
+    imatrix: Option<ImatrixCollector>,
     path: Vec<String>,
     device: Device,
 }
 
+/// The `quantization_config` section of the `config.json` file of a GPTQ or AWQ model.
+#[derive(Debug, Clone, serde::Deserialize)]
+pub struct QuantizationConfig {
+    pub quant_method: String,
+    pub bits: usize,
+    /// The group size, -1 uses a single group per output feature.
+    pub group_size: i64,
+    #[serde(default)]
+    pub desc_act: bool,
+    #[serde(default)]
+    pub checkpoint_format: Option<String>,
+    /// The AWQ kernel layout, only `gemm` is supported.
+    #[serde(default)]
+    pub version: Option<String>,
+}
+
+impl QuantizationConfig {
+    pub fn gptq_config(&self) -> Result<GptqConfig> {
+        let format = match self.quant_method.as_str() {
+            "gptq" => match self.checkpoint_format.as_deref() {
+                None | Some("gptq") => GptqFormat::Gptq,
+                Some("gptq_v2") => GptqFormat::GptqV2,
+                Some(f) => candle::bail!("unsupported gptq checkpoint format {f}"),
+            },
+            "awq" => match self.version.as_deref().map(|v| v.to_lowercase()) {
+                None => GptqFormat::Awq,
+                Some(v) if v == "gemm" => GptqFormat::Awq,
+                Some(v) => candle::bail!("unsupported awq version {v}"),
+            },
+            m => candle::bail!("unsupported quantization method {m}"),
+        };
+        let group_size = if self.group_size <= 0 {
+            None
+        } else {
+            Some(self.group_size as usize)
+        };
+        Ok(GptqConfig {
+            format,
+            bits: self.bits,
+            group_size,
+        })
+    }
+}
+
 impl VarBuilder {
     pub fn from_gguf<P: AsRef<std::path::Path>>(p: P, device: &Device) -> Result<Self> {
         let mut file = std::fs::File::open(p)?;
@@ -27,6 +79,8 @@ impl VarBuilder {
         }
         Ok(Self {
             data: Arc::new(data),
+            gptq: Arc::new(HashMap::new()),
+            imatrix: None,
             path: Vec::new(),
             device: device.clone(),
         })
@@ -42,6 +96,79 @@ impl VarBuilder {
         }
         Ok(Self {
             data: Arc::new(data),
+            gptq: Arc::new(HashMap::new()),
+            imatrix: None,
+            path: Vec::new(),
+            device: device.clone(),
+        })
+    }
+
+    /// Loads unquantized safetensors files, the tensors keep their f16/bf16/f32 dtype.
+    pub fn from_safetensors<P: AsRef<std::path::Path>>(
+        paths: &[P],
+        device: &Device,
+    ) -> Result<Self> {
+        let mut tensors = HashMap::new();
+        for path in paths.iter() {
+            tensors.extend(candle::safetensors::load(path, &Device::Cpu)?)
+        }
+        Self::from_tensors(tensors, HashMap::new(), device)
+    }
+
+    /// Loads a GPTQ or AWQ checkpoint. The `qweight`, `qzeros`, `scales` and `g_idx` tensors of
+    /// each quantized layer are combined into a weight available as `<layer>.weight` through
+    /// `get_qmatmul`, the other tensors are kept unquantized.
+    pub fn from_gptq_safetensors<P: AsRef<std::path::Path>>(
+        paths: &[P],
+        config: &GptqConfig,
+        device: &Device,
+    ) -> Result<Self> {
+        let mut tensors = HashMap::new();
+        for path in paths.iter() {
+            tensors.extend(candle::safetensors::load(path, &Device::Cpu)?)
+        }
+        let prefixes: Vec<String> = tensors
+            .keys()
+            .filter_map(|k| k.strip_suffix(".qweight").map(|p| p.to_string()))
+            .collect();
+        let mut gptq = HashMap::new();
+        for prefix in prefixes {
+            let mut get = |name: &str| {
+                let name = format!("{prefix}.{name}");
+                match tensors.remove(&name) {
+                    Some(t) => Ok(t),
+                    None => candle::bail!("cannot find tensor {name}"),
+                }
+            };
+            let qweight = get("qweight")?;
+            let qzeros = get("qzeros")?;
+            let scales = get("scales")?;
+            let g_idx = tensors.remove(&format!("{prefix}.g_idx"));
+            let w = GptqTensor::new(*config, &qweight, &qzeros, &scales, g_idx.as_ref())?;
+            gptq.insert(format!("{prefix}.weight"), Arc::new(w));
+        }
+        Self::from_tensors(tensors, gptq, device)
+    }
+
+    fn from_tensors(
+        tensors: HashMap<String, Tensor>,
+        gptq: HashMap<String, Arc<GptqTensor>>,
+        device: &Device,
+    ) -> Result<Self> {
+        let mut data = HashMap::new();
+        for (name, tensor) in tensors.into_iter() {
+            let (tensor, dtype) = match tensor.dtype() {
+                DType::F16 => (tensor, GgmlDType::F16),
+                DType::BF16 => (tensor, GgmlDType::BF16),
+                _ => (tensor.to_dtype(DType::F32)?, GgmlDType::F32),
+            };
+            let tensor = QTensor::quantize(&tensor.to_device(device)?, dtype)?;
+            data.insert(name, Arc::new(tensor));
+        }
+        Ok(Self {
+            data: Arc::new(data),
+            gptq: Arc::new(gptq),
+            imatrix: None,
             path: Vec::new(),
             device: device.clone(),
         })
@@ -52,12 +179,14 @@ impl VarBuilder {
         path.push(s.to_string());
         Self {
             data: self.data.clone(),
+            gptq: self.gptq.clone(),
+            imatrix: self.imatrix.clone(),
             path,
             device: self.device.clone(),
         }
     }
 
-    fn path(&self, tensor_name: &str) -> String {
+    pub(crate) fn path(&self, tensor_name: &str) -> String {
         if self.path.is_empty() {
             tensor_name.to_string()
         } else {
@@ -94,11 +223,41 @@ impl VarBuilder {
         }
     }
 
+    /// Returns the matmul for the weight `name` with shape `(out_features, in_features)`, this
+    /// uses the GPTQ/AWQ weights when available and the quantized tensors otherwise.
+    pub fn get_qmatmul<S: Into<Shape>>(&self, s: S, name: &str) -> Result<QMatMul> {
+        let path = self.path(name);
+        match self.gptq.get(&path) {
+            None => QMatMul::from_arc(self.get(s, name)?),
+            Some(w) => {
+                let shape = s.into();
+                if w.shape() != shape {
+                    candle::bail!(
+                        "shape mismatch for {name}, got {:?}, expected {shape:?}",
+                        w.shape()
+                    )
+                }
+                QMatMul::from_gptq(w.clone(), &self.device)
+            }
+        }
+    }
+
+    /// Records the activations of the linear layers built from this var builder, this is used to
+    /// compute an importance matrix over some calibration data.
+    pub fn with_imatrix_collector(mut self, collector: ImatrixCollector) -> Self {
+        self.imatrix = Some(collector);
+        self
+    }
+
+    pub fn imatrix_collector(&self) -> Option<&ImatrixCollector> {
+        self.imatrix.as_ref()
+    }
+
     pub fn device(&self) -> &Device {
         &self.device
     }
 
     pub fn contains_key(&self, key: &str) -> bool {
-        self.data.contains_key(key)
+        self.data.contains_key(key) || self.gptq.contains_key(key)
     }
 }
@@ -0,0 +1,399 @@
+use candle::quantized::{gguf_file, GgmlDType, QTensor};
+use candle::{DType, Device, IndexOp, Result, Tensor};
+use candle_nn::{VarBuilder, VarMap};
+use candle_transformers::models::auto::{architecture_names, AutoModel, CausalLM, Registry};
+use candle_transformers::pipelines::embeddings::EmbeddingsPipeline;
+use serde_json::json;
+
+const VOCAB: usize = 32;
+
+fn max_diff(a: &Tensor, b: &Tensor) -> Result<f32> {
+    (a - b)?.abs()?.flatten_all()?.max(0)?.to_scalar()
+}
+
+// Checks that the kv-cache gives the same logits as processing the whole prompt at once and
+// that clearing it restarts the sequence.
+fn check_causal_lm(model: &mut dyn CausalLM) -> Result<()> {
+    let dev = &Device::Cpu;
+    let prompt = Tensor::new(&[[1u32, 5, 9, 3, 7]], dev)?;
+    let logits = model.forward(&prompt, 0)?;
+    assert_eq!(logits.dims(), [1, VOCAB]);
+    model.clear_kv_cache();
+    model.forward(&prompt.narrow(1, 0, 4)?, 0)?;
+    let incremental = model.forward(&prompt.narrow(1, 4, 1)?, 4)?;
+    assert!(max_diff(&logits, &incremental)? < 1e-4);
+    model.clear_kv_cache();
+    let again = model.forward(&prompt, 0)?;
+    assert!(max_diff(&logits, &again)? < 1e-5);
+    Ok(())
+}
+
+fn varmap_vb(varmap: &VarMap) -> VarBuilder<'static> {
+    VarBuilder::from_varmap(varmap, DType::F32, &Device::Cpu)
+}
+
+#[test]
+fn architectures() -> Result<()> {
+    let config = json!({ "architectures": ["Qwen3ForCausalLM"], "model_type": "qwen3" });
+    assert_eq!(
+        architecture_names(&config.to_string())?,
+        ["Qwen3ForCausalLM", "qwen3"]
+    );
+    let registry = Registry::default();
+    assert!(registry.is_causal_lm(&config.to_string())?);
+    assert!(!registry.is_encoder(&config.to_string())?);
+    // The model type is used when the architecture name is unknown.
+    let config = json!({ "architectures": ["CustomBert"], "model_type": "bert" });
+    assert!(registry.is_encoder(&config.to_string())?);
+    let config = json!({ "architectures": ["Unknown"] }).to_string();
+    let err = registry
+        .causal_lm_from_config(&config, varmap_vb(&VarMap::new()))
+        .err()
+        .unwrap();
+    assert!(err.to_string().contains("Unknown"), "{err}");
+
+    let mut registry = Registry::empty();
+    assert!(!registry.is_encoder(&json!({ "model_type": "bert" }).to_string())?);
+    registry.register_encoder(&["custom"], |_, _| candle::bail!("custom loader"));
+    let err = registry
+        .encoder_from_config(
+            &json!({ "model_type": "custom" }).to_string(),
+            varmap_vb(&VarMap::new()),
+        )
+        .err()
+        .unwrap();
+    assert!(err.to_string().contains("custom loader"), "{err}");
+    Ok(())
+}
+
+#[test]
+fn safetensors_causal_lms() -> Result<()> {
+    let registry = Registry::default();
+    let llama = json!({
+        "architectures": ["LlamaForCausalLM"],
+        "hidden_size": 32,
+        "intermediate_size": 64,
+        "vocab_size": VOCAB,
+        "num_hidden_layers": 2,
+        "num_attention_heads": 4,
+        "num_key_value_heads": 2,
+        "rms_norm_eps": 1e-5,
+        "max_position_embeddings": 64,
+    });
+    let mistral = json!({
+        "model_type": "mistral",
+        "vocab_size": VOCAB,
+        "hidden_size": 32,
+        "intermediate_size": 64,
+        "num_hidden_layers": 2,
+        "num_attention_heads": 4,
+        "num_key_value_heads": 2,
+        "max_position_embeddings": 64,
+        "rms_norm_eps": 1e-5,
+        "rope_theta": 10000.0,
+        "sliding_window": null,
+    });
+    for config in [llama, mistral] {
+        let varmap = VarMap::new();
+        let mut model = registry.causal_lm_from_config(&config.to_string(), varmap_vb(&varmap))?;
+        check_causal_lm(model.as_mut())?;
+        // Each position of the logits for all the positions matches the last position of the
+        // corresponding prefix.
+        let prompt = Tensor::new(&[[1u32, 5, 9, 3, 7]], &Device::Cpu)?;
+        model.clear_kv_cache();
+        let all = model.forward_all(&prompt, 0)?;
+        assert_eq!(all.dims(), [1, 5, VOCAB]);
+        for len in [2, 5] {
+            model.clear_kv_cache();
+            let logits = model.forward(&prompt.narrow(1, 0, len)?, 0)?;
+            assert!(max_diff(&all.i((.., len - 1))?, &logits)? < 1e-5);
+        }
+    }
+    Ok(())
+}
+
+#[test]
+fn load_from_dir() -> Result<()> {
+    let dev = &Device::Cpu;
+    let dir = std::env::temp_dir().join(format!("candle-auto-{}", std::process::id()));
+    std::fs::create_dir_all(&dir)?;
+    let config = json!({
+        "architectures": ["Qwen3ForCausalLM"],
+        "vocab_size": VOCAB,
+        "hidden_size": 32,
+        "intermediate_size": 64,
+        "num_hidden_layers": 2,
+        "num_attention_heads": 4,
+        "head_dim": 8,
+        "attention_bias": false,
+        "num_key_value_heads": 2,
+        "max_position_embeddings": 64,
+        "sliding_window": null,
+        "max_window_layers": 2,
+        "tie_word_embeddings": false,
+        "rope_theta": 10000.0,
+        "rms_norm_eps": 1e-6,
+        "use_sliding_window": false,
+        "hidden_act": "silu",
+    })
+    .to_string();
+    let registry = Registry::default();
+    let varmap = VarMap::new();
+    let mut reference = registry.causal_lm_from_config(&config, varmap_vb(&varmap))?;
+    std::fs::write(dir.join("config.json"), &config)?;
+    varmap.save(dir.join("model.safetensors"))?;
+    let model = registry.load(&dir, DType::F32, dev);
+    std::fs::remove_dir_all(&dir)?;
+    let mut model = match model? {
+        AutoModel::CausalLM(model) => model,
+        AutoModel::Encoder(_) => panic!("qwen3 loaded as an encoder"),
+    };
+    check_causal_lm(model.as_mut())?;
+    let prompt = Tensor::new(&[[4u32, 2, 8]], dev)?;
+    model.clear_kv_cache();
+    let logits = model.forward(&prompt, 0)?;
+    assert!(max_diff(&logits, &reference.forward(&prompt, 0)?)? < 1e-6);
+    Ok(())
+}
+
+#[test]
+fn encoder() -> Result<()> {
+    let config = json!({
+        "model_type": "bert",
+        "vocab_size": VOCAB,
+        "hidden_size": 16,
+        "num_hidden_layers": 2,
+        "num_attention_heads": 2,
+        "intermediate_size": 32,
+        "hidden_act": "gelu",
+        "hidden_dropout_prob": 0.1,
+        "max_position_embeddings": 32,
+        "type_vocab_size": 2,
+        "initializer_range": 0.02,
+        "layer_norm_eps": 1e-12,
+        "pad_token_id": 0,
+        "classifier_dropout": null,
+    })
+    .to_string();
+    let encoder = Registry::default().encoder_from_config(&config, varmap_vb(&VarMap::new()))?;
+    let mut pipeline = EmbeddingsPipeline::new(encoder, &Device::Cpu);
+    let embedding = pipeline.embed_one(vec![1, 7, 3, 2])?;
+    assert_eq!(embedding.len(), 16);
+    Ok(())
+}
+
+// Serializes a model with random weights to the gguf format, the metadata keys are prefixed
+// with the architecture name and the tensor shapes use the candle order.
+fn tiny_gguf(
+    arch: &str,
+    metadata: &[(&str, gguf_file::Value)],
+    tensors: &[(String, Vec<usize>)],
+) -> Result<Vec<u8>> {
+    let dev = &Device::Cpu;
+    let tensors = tensors
+        .iter()
+        .map(|(name, dims)| {
+            let t = Tensor::randn(0f32, 0.5, dims.as_slice(), dev)?;
+            Ok((name.as_str(), QTensor::quantize(&t, GgmlDType::F32)?))
+        })
+        .collect::<Result<Vec<_>>>()?;
+    let metadata: Vec<(String, gguf_file::Value)> = metadata
+        .iter()
+        .map(|(k, v)| (format!("{arch}.{k}"), v.clone()))
+        .chain([(
+            "general.architecture".to_string(),
+            gguf_file::Value::String(arch.to_string()),
+        )])
+        .collect();
+    let metadata: Vec<_> = metadata.iter().map(|(k, v)| (k.as_str(), v)).collect();
+    let tensors: Vec<_> = tensors.iter().map(|(k, v)| (*k, v)).collect();
+    let mut buf = std::io::Cursor::new(Vec::new());
+    gguf_file::write(&mut buf, &metadata, &tensors)?;
+    Ok(buf.into_inner())
+}
+
+const DIM: usize = 32;
+const HIDDEN: usize = 64;
+const N_HEAD: usize = 4;
+const N_KV_HEAD: usize = 2;
+const HEAD_DIM: usize = DIM / N_HEAD;
+
+// The metadata shared by most architectures, for a single block.
+fn base_metadata() -> Vec<(&'static str, gguf_file::Value)> {
+    vec![
+        ("attention.head_count", gguf_file::Value::U32(N_HEAD as u32)),
+        (
+            "attention.head_count_kv",
+            gguf_file::Value::U32(N_KV_HEAD as u32),
+        ),
+        ("block_count", gguf_file::Value::U32(1)),
+        ("embedding_length", gguf_file::Value::U32(DIM as u32)),
+        ("context_length", gguf_file::Value::U32(64)),
+        ("feed_forward_length", gguf_file::Value::U32(HIDDEN as u32)),
+        (
+            "attention.key_length",
+            gguf_file::Value::U32(HEAD_DIM as u32),
+        ),
+        (
+            "attention.value_length",
+            gguf_file::Value::U32(HEAD_DIM as u32),
+        ),
+        (
+            "rope.dimension_count",
+            gguf_file::Value::U32(HEAD_DIM as u32),
+        ),
+        ("rope.freq_base", gguf_file::Value::F32(10000.)),
+        (
+            "attention.layer_norm_rms_epsilon",
+            gguf_file::Value::F32(1e-5),
+        ),
+    ]
+}
+
+// The embeddings, the output and the tensors of a single block with separate q, k, v
+// projections and a gated feed-forward.
+fn base_tensors() -> Vec<(String, Vec<usize>)> {
+    let kv_dim = N_KV_HEAD * HEAD_DIM;
+    [
+        ("token_embd.weight", vec![VOCAB, DIM]),
+        ("output_norm.weight", vec![DIM]),
+        ("output.weight", vec![VOCAB, DIM]),
+        ("blk.0.attn_q.weight", vec![DIM, DIM]),
+        ("blk.0.attn_k.weight", vec![kv_dim, DIM]),
+        ("blk.0.attn_v.weight", vec![kv_dim, DIM]),
+        ("blk.0.attn_output.weight", vec![DIM, DIM]),
+        ("blk.0.attn_norm.weight", vec![DIM]),
+        ("blk.0.ffn_gate.weight", vec![HIDDEN, DIM]),
+        ("blk.0.ffn_up.weight", vec![HIDDEN, DIM]),
+        ("blk.0.ffn_down.weight", vec![DIM, HIDDEN]),
+        ("blk.0.ffn_norm.weight", vec![DIM]),
+    ]
+    .into_iter()
+    .map(|(name, dims)| (name.to_string(), dims))
+    .collect()
+}
+
+fn with_tensors(
+    mut tensors: Vec<(String, Vec<usize>)>,
+    extra: &[(&str, &[usize])],
+) -> Vec<(String, Vec<usize>)> {
+    tensors.extend(
+        extra
+            .iter()
+            .map(|(name, dims)| (name.to_string(), dims.to_vec())),
+    );
+    tensors
+}
+
+// Serializes a tiny llama model with random weights to the gguf format.
+fn tiny_llama() -> Result<Vec<u8>> {
+    tiny_gguf("llama", &base_metadata(), &base_tensors())
+}
+
+#[test]
+fn gguf() -> Result<()> {
+    let registry = Registry::default();
+    let buf = tiny_llama()?;
+    let mut model = registry.load_gguf(&mut std::io::Cursor::new(&buf), &Device::Cpu)?;
+    check_causal_lm(model.as_mut())?;
+    let err = Registry::empty()
+        .load_gguf(&mut std::io::Cursor::new(&buf), &Device::Cpu)
+        .err()
+        .unwrap();
+    assert!(
+        err.to_string()
+            .contains("unsupported gguf architecture llama"),
+        "{err}"
+    );
+    Ok(())
+}
+
+#[test]
+fn gguf_architectures() -> Result<()> {
+    let registry = Registry::default();
+    let kv_dim = N_KV_HEAD * HEAD_DIM;
+    let qk_norms: &[(&str, &[usize])] = &[
+        ("blk.0.attn_q_norm.weight", &[HEAD_DIM]),
+        ("blk.0.attn_k_norm.weight", &[HEAD_DIM]),
+    ];
+    let qwen2 = with_tensors(
+        base_tensors(),
+        &[
+            ("blk.0.attn_q.bias", &[DIM]),
+            ("blk.0.attn_k.bias", &[kv_dim]),
+            ("blk.0.attn_v.bias", &[kv_dim]),
+        ],
+    );
+    // The experts are only supported on cuda, a model without experts uses dense layers.
+    let mut moe_metadata = base_metadata();
+    moe_metadata.extend([
+        ("expert_count", gguf_file::Value::U32(0)),
+        ("expert_used_count", gguf_file::Value::U32(0)),
+        (
+            "expert_feed_forward_length",
+            gguf_file::Value::U32(HIDDEN as u32),
+        ),
+    ]);
+    let mut gemma3_metadata = base_metadata();
+    gemma3_metadata.push(("attention.sliding_window", gguf_file::Value::U32(4)));
+    let gemma3 = with_tensors(
+        base_tensors(),
+        &[
+            ("blk.0.attn_q_norm.weight", &[HEAD_DIM]),
+            ("blk.0.attn_k_norm.weight", &[HEAD_DIM]),
+            ("blk.0.post_attention_norm.weight", &[DIM]),
+            ("blk.0.post_ffw_norm.weight", &[DIM]),
+        ],
+    );
+    // Phi-3 has fused qkv and gate-up projections.
+    let phi3: Vec<(String, Vec<usize>)> = [
+        ("token_embd.weight", vec![VOCAB, DIM]),
+        ("output_norm.weight", vec![DIM]),
+        ("output.weight", vec![VOCAB, DIM]),
+        ("blk.0.attn_qkv.weight", vec![DIM + 2 * kv_dim, DIM]),
+        ("blk.0.attn_output.weight", vec![DIM, DIM]),
+        ("blk.0.attn_norm.weight", vec![DIM]),
+        ("blk.0.ffn_up.weight", vec![2 * HIDDEN, DIM]),
+        ("blk.0.ffn_down.weight", vec![DIM, HIDDEN]),
+        ("blk.0.ffn_norm.weight", vec![DIM]),
+    ]
+    .into_iter()
+    .map(|(name, dims)| (name.to_string(), dims))
+    .collect();
+    for (arch, metadata, tensors) in [
+        ("llama", base_metadata(), base_tensors()),
+        ("qwen2", base_metadata(), qwen2),
+        (
+            "qwen3",
+            base_metadata(),
+            with_tensors(base_tensors(), qk_norms),
+        ),
+        (
+            "qwen3moe",
+            moe_metadata,
+            with_tensors(base_tensors(), qk_norms),
+        ),
+        ("phi3", base_metadata(), phi3),
+        ("gemma3", gemma3_metadata, gemma3),
+    ] {
+        let buf = tiny_gguf(arch, &metadata, &tensors)?;
+        let mut model = registry
+            .load_gguf(&mut std::io::Cursor::new(&buf), &Device::Cpu)
+            .map_err(|e| e.context(format!("loading {arch}")))?;
+        check_causal_lm(model.as_mut()).map_err(|e| e.context(format!("running {arch}")))?;
+    }
+    // The older gemma models have a different architecture and are not registered.
+    for arch in ["gemma", "gemma2"] {
+        let buf = tiny_gguf(arch, &base_metadata(), &base_tensors())?;
+        let err = registry
+            .load_gguf(&mut std::io::Cursor::new(&buf), &Device::Cpu)
+            .err()
+            .unwrap();
+        assert!(
+            err.to_string()
+                .contains(&format!("unsupported gguf architecture {arch}")),
+            "{err}"
+        );
+    }
+    Ok(())
+}
@@ -0,0 +1,322 @@
+use candle::{DType, Device, Result, Tensor};
+use candle_nn::{VarBuilder, VarMap};
+use candle_transformers::generation::beam_search::{
+    BeamSearch, BeamSearchConfig, BeamSearchModel, ContrastiveSearch, ContrastiveSearchConfig,
+    Seq2Seq,
+};
+use candle_transformers::generation::processors::BannedTokens;
+use candle_transformers::models::marian;
+
+const VOCAB: usize = 4;
+const EOS: u32 = 3;
+
+// The next token only depends on the last one and the hidden states are one-hot encodings of
+// the tokens. The cached tokens of each batch element are tracked to detect invalid reorders.
+struct Bigram {
+    logits: [[f32; VOCAB]; VOCAB],
+    cache: Vec<Vec<u32>>,
+}
+
+impl Bigram {
+    fn new(logits: [[f32; VOCAB]; VOCAB]) -> Self {
+        Self {
+            logits,
+            cache: vec![],
+        }
+    }
+
+    fn logprob(&self, prev: u32, next: u32) -> f64 {
+        let logits = self.logits[prev as usize];
+        let log_sum = logits.iter().map(|l| (*l as f64).exp()).sum::<f64>().ln();
+        logits[next as usize] as f64 - log_sum
+    }
+
+    fn sequence_logprob(&self, prompt: &[u32], tokens: &[u32]) -> f64 {
+        let mut prev = *prompt.last().unwrap();
+        let mut sum = 0.;
+        for &t in tokens {
+            sum += self.logprob(prev, t);
+            prev = t
+        }
+        sum
+    }
+}
+
+impl BeamSearchModel for Bigram {
+    fn decode(&mut self, tokens: &Tensor, index_pos: usize) -> Result<(Tensor, Tensor)> {
+        let tokens = tokens.to_vec2::<u32>()?;
+        if index_pos == 0 {
+            self.cache = vec![vec![]; tokens.len()]
+        }
+        if self.cache.len() != tokens.len() {
+            candle::bail!("batch size mismatch {} {}", self.cache.len(), tokens.len())
+        }
+        let mut logits = vec![];
+        let mut hidden = vec![];
+        for (cache, tokens) in self.cache.iter_mut().zip(tokens.iter()) {
+            if cache.as_slice() != &tokens[..index_pos] {
+                candle::bail!("invalid cache {cache:?} for {tokens:?}")
+            }
+            *cache = tokens.clone();
+            logits.extend(self.logits[*tokens.last().unwrap() as usize]);
+            for &t in tokens[index_pos..].iter() {
+                hidden.extend((0..VOCAB).map(|i| if i == t as usize { 1f32 } else { 0. }))
+            }
+        }
+        let b_sz = tokens.len();
+        let seq_len = tokens[0].len() - index_pos;
+        let logits = Tensor::from_vec(logits, (b_sz, VOCAB), &Device::Cpu)?;
+        let hidden = Tensor::from_vec(hidden, (b_sz, seq_len, VOCAB), &Device::Cpu)?;
+        Ok((logits, hidden))
+    }
+
+    fn reorder_kv_cache(&mut self, indices: &Tensor) -> Result<()> {
+        let indices = indices.to_vec1::<u32>()?;
+        self.cache = indices
+            .iter()
+            .map(|&i| self.cache[i as usize].clone())
+            .collect();
+        Ok(())
+    }
+}
+
+// Greedy decoding picks 0 first but 1 leads to a more likely sequence.
+const LOGITS: [[f32; VOCAB]; VOCAB] = [
+    [0.0, 0.0, 0.0, -1.0],
+    [-1.0, -1.0, 3.0, -1.0],
+    [0.5, -1.0, 1.0, 0.0],
+    [0.0, 0.0, 0.0, 0.0],
+];
+
+#[test]
+fn beam_search_exhaustive() -> Result<()> {
+    let mut model = Bigram::new(LOGITS);
+    let prompt = [2, 2];
+    // With enough beams and no end-of-sequence token, the beam search is exhaustive.
+    let config = BeamSearchConfig {
+        num_beams: 16,
+        num_return_sequences: 5,
+        max_new_tokens: 3,
+        ..Default::default()
+    };
+    let hypotheses = BeamSearch::new(config)?.generate(&mut model, &prompt, &Device::Cpu)?;
+    let mut expected = vec![];
+    for i in 0..VOCAB.pow(3) {
+        let tokens = vec![(i / 16) as u32, (i / 4 % 4) as u32, (i % 4) as u32];
+        expected.push((model.sequence_logprob(&prompt, &tokens), tokens))
+    }
+    expected.sort_by(|a, b| b.0.total_cmp(&a.0));
+    assert_eq!(hypotheses.len(), 5);
+    for (hypothesis, (logprob, tokens)) in hypotheses.iter().zip(expected.iter()) {
+        assert_eq!(&hypothesis.tokens, tokens);
+        assert!((hypothesis.sum_logprobs - logprob).abs() < 1e-5);
+        assert!((hypothesis.score - logprob / 3.).abs() < 1e-5);
+        assert!(!hypothesis.finished);
+    }
+
+    // A single beam is greedy decoding.
+    let config = BeamSearchConfig {
+        num_beams: 1,
+        max_new_tokens: 3,
+        ..Default::default()
+    };
+    let hypotheses = BeamSearch::new(config)?.generate(&mut model, &[0], &Device::Cpu)?;
+    assert_eq!(hypotheses[0].tokens, [0, 0, 0]);
+    let config = BeamSearchConfig {
+        num_beams: 2,
+        max_new_tokens: 2,
+        ..Default::default()
+    };
+    let hypotheses = BeamSearch::new(config)?.generate(&mut model, &[0], &Device::Cpu)?;
+    assert_eq!(hypotheses[0].tokens, [1, 2]);
+
+    assert!(BeamSearch::new(BeamSearchConfig {
+        num_beams: 3,
+        num_beam_groups: 2,
+        ..Default::default()
+    })
+    .is_err());
+    assert!(BeamSearch::new(BeamSearchConfig {
+        num_return_sequences: 5,
+        ..Default::default()
+    })
+    .is_err());
+    Ok(())
+}
+
+#[test]
+fn beam_search_eos() -> Result<()> {
+    let mut model = Bigram::new(LOGITS);
+    let prompt = [1];
+    for (length_penalty, early_stopping) in [(1.0, false), (0.0, true), (-1.0, false)] {
+        let config = BeamSearchConfig {
+            num_beams: 3,
+            num_return_sequences: 3,
+            length_penalty,
+            early_stopping,
+            max_new_tokens: 20,
+            eos_tokens: vec![EOS],
+            ..Default::default()
+        };
+        let hypotheses = BeamSearch::new(config)?.generate(&mut model, &prompt, &Device::Cpu)?;
+        assert_eq!(hypotheses.len(), 3);
+        for (i, h) in hypotheses.iter().enumerate() {
+            assert!(!h.tokens.contains(&EOS));
+            let mut tokens = h.tokens.clone();
+            if h.finished {
+                tokens.push(EOS)
+            } else {
+                // Repeating 2 is more likely than ending the sequence.
+                assert_eq!(tokens.len(), 20);
+            }
+            let logprob = model.sequence_logprob(&prompt, &tokens);
+            assert!((h.sum_logprobs - logprob).abs() < 1e-5);
+            let score = logprob / (tokens.len() as f64).powf(length_penalty);
+            assert!((h.score - score).abs() < 1e-5);
+            if i > 0 {
+                assert!(hypotheses[i - 1].score >= h.score)
+            }
+        }
+        // Without length normalization the shortest sequences win.
+        if length_penalty == 0.0 {
+            assert_eq!(hypotheses[0].tokens, [2]);
+            assert!(hypotheses[0].finished);
+        }
+    }
+
+    // Processors constrain all the beams.
+    let config = BeamSearchConfig {
+        num_beams: 2,
+        max_new_tokens: 4,
+        ..Default::default()
+    };
+    let mut beam_search = BeamSearch::new(config)?.with(BannedTokens(vec![0, 2]));
+    let hypotheses = beam_search.generate(&mut model, &prompt, &Device::Cpu)?;
+    assert!(hypotheses[0].tokens.iter().all(|&t| t == 1 || t == 3));
+    Ok(())
+}
+
+#[test]
+fn diverse_beam_search() -> Result<()> {
+    let mut model = Bigram::new(LOGITS);
+    let config = |diversity_penalty| BeamSearchConfig {
+        num_beams: 2,
+        num_beam_groups: 2,
+        num_return_sequences: 2,
+        diversity_penalty,
+        max_new_tokens: 3,
+        ..Default::default()
+    };
+    // Without penalty both groups run the same beam search.
+    let hypotheses = BeamSearch::new(config(0.))?.generate(&mut model, &[2], &Device::Cpu)?;
+    assert_eq!(hypotheses[0].tokens, hypotheses[1].tokens);
+    let hypotheses = BeamSearch::new(config(10.))?.generate(&mut model, &[2], &Device::Cpu)?;
+    assert_ne!(hypotheses[0].tokens[0], hypotheses[1].tokens[0]);
+    Ok(())
+}
+
+#[test]
+fn contrastive_search() -> Result<()> {
+    let mut model = Bigram::new(LOGITS);
+    let config = |penalty_alpha| ContrastiveSearchConfig {
+        top_k: VOCAB,
+        penalty_alpha,
+        max_new_tokens: 3,
+        eos_tokens: vec![],
+    };
+    // No penalty is greedy decoding.
+    let tokens = ContrastiveSearch::new(config(0.))?.generate(&mut model, &[1], &Device::Cpu)?;
+    assert_eq!(tokens, [2, 2, 2]);
+    // With a large penalty the tokens that have not been seen yet are preferred.
+    let mut tokens =
+        ContrastiveSearch::new(config(0.9))?.generate(&mut model, &[0], &Device::Cpu)?;
+    tokens.sort();
+    assert_eq!(tokens, [1, 2, 3]);
+
+    let config = ContrastiveSearchConfig {
+        top_k: 2,
+        penalty_alpha: 0.5,
+        max_new_tokens: 10,
+        eos_tokens: vec![EOS],
+    };
+    let tokens = ContrastiveSearch::new(config)?.generate(&mut model, &[1], &Device::Cpu)?;
+    assert!(tokens.len() <= 10 && !tokens.contains(&EOS));
+    Ok(())
+}
+
+// Scores a sequence by running the decoder on all of it without kv-cache.
+fn marian_logprob(
+    model: &mut marian::MTModel,
+    encoder_output: &Tensor,
+    tokens: &[u32],
+) -> Result<f64> {
+    model.reset_kv_cache();
+    let input = Tensor::new(&tokens[..tokens.len() - 1], &Device::Cpu)?.unsqueeze(0)?;
+    let logits = model.decode(&input, encoder_output, 0)?.squeeze(0)?;
+    let logprobs = candle_nn::ops::log_softmax(&logits, 1)?.to_vec2::<f32>()?;
+    let sum = logprobs
+        .iter()
+        .zip(tokens[1..].iter())
+        .map(|(l, &t)| l[t as usize] as f64)
+        .sum();
+    Ok(sum)
+}
+
+#[test]
+fn beam_search_marian() -> Result<()> {
+    let dev = &Device::Cpu;
+    let cfg = marian::Config {
+        vocab_size: 16,
+        decoder_vocab_size: Some(16),
+        max_position_embeddings: 32,
+        encoder_layers: 1,
+        encoder_ffn_dim: 16,
+        encoder_attention_heads: 2,
+        decoder_layers: 2,
+        decoder_ffn_dim: 16,
+        decoder_attention_heads: 2,
+        use_cache: true,
+        is_encoder_decoder: true,
+        activation_function: candle_nn::Activation::Relu,
+        d_model: 8,
+        decoder_start_token_id: 15,
+        scale_embedding: true,
+        pad_token_id: 15,
+        eos_token_id: 0,
+        forced_eos_token_id: 0,
+        share_encoder_decoder_embeddings: true,
+    };
+    let varmap = VarMap::new();
+    let vb = VarBuilder::from_varmap(&varmap, DType::F32, dev);
+    let mut model = marian::MTModel::new(&cfg, vb)?;
+    let input = Tensor::new(&[[3u32, 5, 7, 0]], dev)?;
+    let encoder_output = model.encoder().forward(&input, 0)?;
+
+    let config = BeamSearchConfig {
+        num_beams: 4,
+        num_return_sequences: 4,
+        max_new_tokens: 6,
+        ..Default::default()
+    };
+    let mut beam_search = BeamSearch::new(config)?;
+    let hypotheses = {
+        let mut seq2seq = Seq2Seq::new(&mut model, encoder_output.clone());
+        beam_search.generate(&mut seq2seq, &[15], dev)?
+    };
+    assert_eq!(hypotheses.len(), 4);
+    for h in hypotheses.iter() {
+        let mut tokens = vec![15];
+        tokens.extend_from_slice(&h.tokens);
+        let expected = marian_logprob(&mut model, &encoder_output, &tokens)?;
+        assert!((h.sum_logprobs - expected).abs() < 1e-4, "{h:?} {expected}");
+    }
+
+    let config = ContrastiveSearchConfig {
+        max_new_tokens: 6,
+        ..Default::default()
+    };
+    let mut seq2seq = Seq2Seq::new(&mut model, encoder_output.clone());
+    let tokens = ContrastiveSearch::new(config)?.generate(&mut seq2seq, &[15], dev)?;
+    assert_eq!(tokens.len(), 6);
+    Ok(())
+}
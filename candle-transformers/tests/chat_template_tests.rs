@@ -0,0 +1,402 @@
+use candle::quantized::gguf_file;
+use candle::Result;
+use candle_transformers::pipelines::chat_template::{
+    ChatTemplate, ChatTemplateOptions, Content, ContentPart, ImageUrl, Message, Tool, ToolCall,
+};
+
+// The templates are the ones from the tokenizer configs of the corresponding models, the expected
+// outputs were produced with `apply_chat_template` from transformers.
+const QWEN2_5: &str = include_str!("chat_templates/qwen2_5.jinja");
+const LLAMA3_1: &str = include_str!("chat_templates/llama3_1.jinja");
+const MISTRAL: &str = include_str!("chat_templates/mistral.jinja");
+const GEMMA: &str = include_str!("chat_templates/gemma.jinja");
+const QWEN2_VL: &str = include_str!("chat_templates/qwen2_vl.jinja");
+const QWEN3: &str = include_str!("chat_templates/qwen3.jinja");
+
+fn weather_tool() -> Tool {
+    Tool::function(
+        "get_weather",
+        Some("Gets the current weather in a city".to_string()),
+        serde_json::json!({
+            "type": "object",
+            "properties": { "city": { "type": "string" } },
+            "required": ["city"],
+        }),
+    )
+}
+
+fn tool_messages() -> Vec<Message> {
+    vec![
+        Message::user("What's the weather in Paris?"),
+        Message::tool_calls(vec![ToolCall::function(
+            "get_weather",
+            serde_json::json!({ "city": "Paris" }),
+        )]),
+        Message::tool("22°C and sunny", Some("call_0".to_string())),
+    ]
+}
+
+fn conversation() -> Vec<Message> {
+    vec![
+        Message::user("Hi"),
+        Message::assistant("Hello!"),
+        Message::user("How are you?"),
+    ]
+}
+
+#[test]
+fn qwen2_5() -> Result<()> {
+    let template = ChatTemplate::new(QWEN2_5)?;
+    let mut messages = vec![Message::system("You are a bot.")];
+    messages.extend(conversation());
+    let prompt = template.apply(&messages, &ChatTemplateOptions::for_generation())?;
+    assert_eq!(
+        prompt,
+        r#"<|im_start|>system
+You are a bot.<|im_end|>
+<|im_start|>user
+Hi<|im_end|>
+<|im_start|>assistant
+Hello!<|im_end|>
+<|im_start|>user
+How are you?<|im_end|>
+<|im_start|>assistant
+"#
+    );
+
+    let options = ChatTemplateOptions::for_generation().with_tools(vec![weather_tool()]);
+    let prompt = template.apply(&tool_messages(), &options)?;
+    assert_eq!(
+        prompt,
+        r#"<|im_start|>system
+You are Qwen, created by Alibaba Cloud. You are a helpful assistant.
+
+# Tools
+
+You may call one or more functions to assist with the user query.
+
+You are provided with function signatures within <tools></tools> XML tags:
+<tools>
+{"type": "function", "function": {"name": "get_weather", "description": "Gets the current weather in a city", "parameters": {"type": "object", "properties": {"city": {"type": "string"}}, "required": ["city"]}}}
+</tools>
+
+For each function call, return a json object with function name and arguments within <tool_call></tool_call> XML tags:
+<tool_call>
+{"name": <function-name>, "arguments": <args-json-object>}
+</tool_call><|im_end|>
+<|im_start|>user
+What's the weather in Paris?<|im_end|>
+<|im_start|>assistant
+<tool_call>
+{"name": "get_weather", "arguments": {"city": "Paris"}}
+</tool_call><|im_end|>
+<|im_start|>user
+<tool_response>
+22°C and sunny
+</tool_response><|im_end|>
+<|im_start|>assistant
+"#
+    );
+    Ok(())
+}
+
+#[test]
+fn llama3_1() -> Result<()> {
+    let template = ChatTemplate::new(LLAMA3_1)?
+        .with_special_token("bos_token", "<|begin_of_text|>")
+        .with_special_token("eos_token", "<|eot_id|>");
+    let options = ChatTemplateOptions::for_generation()
+        .with_context("date_string", serde_json::json!("19 Oct 2026"));
+    let messages = [Message::system("You are a bot. "), Message::user("Hi")];
+    let prompt = template.apply(&messages, &options)?;
+    assert_eq!(
+        prompt,
+        r#"<|begin_of_text|><|start_header_id|>system<|end_header_id|>
+
+Cutting Knowledge Date: December 2023
+Today Date: 19 Oct 2026
+
+You are a bot.<|eot_id|><|start_header_id|>user<|end_header_id|>
+
+Hi<|eot_id|><|start_header_id|>assistant<|end_header_id|>
+
+"#
+    );
+
+    let options = options.with_tools(vec![weather_tool()]);
+    let prompt = template.apply(&tool_messages(), &options)?;
+    assert_eq!(
+        prompt,
+        r#"<|begin_of_text|><|start_header_id|>system<|end_header_id|>
+
+Environment: ipython
+Cutting Knowledge Date: December 2023
+Today Date: 19 Oct 2026
+
+<|eot_id|><|start_header_id|>user<|end_header_id|>
+
+Given the following functions, please respond with a JSON for a function call with its proper arguments that best answers the given prompt.
+
+Respond in the format {"name": function name, "parameters": dictionary of argument name and its value}.Do not use variables.
+
+{
+    "type": "function",
+    "function": {
+        "name": "get_weather",
+        "description": "Gets the current weather in a city",
+        "parameters": {
+            "type": "object",
+            "properties": {
+                "city": {
+                    "type": "string"
+                }
+            },
+            "required": [
+                "city"
+            ]
+        }
+    }
+}
+
+What's the weather in Paris?<|eot_id|><|start_header_id|>assistant<|end_header_id|>
+
+{"name": "get_weather", "parameters": {"city": "Paris"}}<|eot_id|><|start_header_id|>ipython<|end_header_id|>
+
+"22°C and sunny"<|eot_id|><|start_header_id|>assistant<|end_header_id|>
+
+"#
+    );
+    Ok(())
+}
+
+#[test]
+fn mistral_and_gemma() -> Result<()> {
+    let template = ChatTemplate::new(MISTRAL)?
+        .with_special_token("bos_token", "<s>")
+        .with_special_token("eos_token", "</s>");
+    let prompt = template.apply(&conversation(), &ChatTemplateOptions::for_generation())?;
+    assert_eq!(
+        prompt,
+        r#"<s>[INST] Hi [/INST]Hello!</s>[INST] How are you? [/INST]"#
+    );
+
+    // The special tokens can be plain strings or added tokens.
+    let config = serde_json::json!({
+        "bos_token": { "content": "<bos>", "lstrip": false, "special": true },
+        "eos_token": "<eos>",
+        "model_max_length": 8192,
+        "chat_template": GEMMA,
+    });
+    let template = ChatTemplate::from_tokenizer_config_str(&config.to_string())?;
+    assert_eq!(template.bos_token(), Some("<bos>"));
+    assert_eq!(template.eos_token(), Some("<eos>"));
+    let messages = [Message::user(" Hi "), Message::assistant("Hello!")];
+    let prompt = template.apply(&messages, &ChatTemplateOptions::for_generation())?;
+    assert_eq!(
+        prompt,
+        r#"<bos><start_of_turn>user
+Hi<end_of_turn>
+<start_of_turn>model
+Hello!<end_of_turn>
+<start_of_turn>model
+"#
+    );
+
+    // Errors raised by the template.
+    let messages = [Message::user("a"), Message::user("b")];
+    let err = template
+        .apply(&messages, &ChatTemplateOptions::default())
+        .unwrap_err();
+    assert!(err
+        .to_string()
+        .contains("Conversation roles must alternate"));
+    Ok(())
+}
+
+#[test]
+fn qwen2_vl_multimodal() -> Result<()> {
+    let template = ChatTemplate::new(QWEN2_VL)?;
+    let messages = [
+        Message::user(vec![
+            ContentPart::Image { url: None },
+            ContentPart::Text {
+                text: "Describe this image.".to_string(),
+            },
+            ContentPart::ImageUrl {
+                image_url: ImageUrl {
+                    url: "http://x/cat.png".to_string(),
+                    detail: None,
+                },
+            },
+        ]),
+        Message::assistant("Two cats."),
+        Message::user(vec![
+            ContentPart::Video { url: None },
+            ContentPart::Text {
+                text: "And this?".to_string(),
+            },
+        ]),
+    ];
+    let options = ChatTemplateOptions::for_generation().with_context("add_vision_id", true.into());
+    let prompt = template.apply(&messages, &options)?;
+    assert_eq!(
+        prompt,
+        r#"<|im_start|>system
+You are a helpful assistant.<|im_end|>
+<|im_start|>user
+Picture 1: <|vision_start|><|image_pad|><|vision_end|>Describe this image.Picture 2: <|vision_start|><|image_pad|><|vision_end|><|im_end|>
+<|im_start|>assistant
+Two cats.<|im_end|>
+<|im_start|>user
+Video 1: <|vision_start|><|video_pad|><|vision_end|>And this?<|im_end|>
+<|im_start|>assistant
+"#
+    );
+    Ok(())
+}
+
+#[test]
+fn qwen3_thinking() -> Result<()> {
+    let template = ChatTemplate::new(QWEN3)?;
+    let messages = [
+        Message::user("1+1?"),
+        Message::assistant("<think>\nEasy.\n</think>\n\n2"),
+        Message::user("2+2?"),
+    ];
+    let options =
+        ChatTemplateOptions::for_generation().with_context("enable_thinking", false.into());
+    let prompt = template.apply(&messages, &options)?;
+    assert_eq!(
+        prompt,
+        r#"<|im_start|>user
+1+1?<|im_end|>
+<|im_start|>assistant
+2<|im_end|>
+<|im_start|>user
+2+2?<|im_end|>
+<|im_start|>assistant
+<think>
+
+</think>
+
+"#
+    );
+
+    // Prefill the beginning of the assistant response.
+    let messages = [
+        Message::user("1+1?"),
+        Message::assistant("<think>\nEasy.\n</think>\n\nThe answer"),
+    ];
+    let options = ChatTemplateOptions {
+        continue_final_message: true,
+        ..Default::default()
+    };
+    let prompt = template.apply(&messages, &options)?;
+    assert_eq!(
+        prompt,
+        r#"<|im_start|>user
+1+1?<|im_end|>
+<|im_start|>assistant
+<think>
+Easy.
+</think>
+
+The answer"#
+    );
+    let options = ChatTemplateOptions {
+        add_generation_prompt: true,
+        ..options
+    };
+    assert!(template.apply(&messages, &options).is_err());
+    Ok(())
+}
+
+#[test]
+fn template_sources() -> Result<()> {
+    // Models with a separate template for tool use.
+    let config = serde_json::json!({
+        "chat_template": [
+            { "name": "default", "template": "default: {{ messages[0].content }}" },
+            { "name": "tool_use", "template": "{{ tools[0].function.name }}: {{ messages[0].content }}" },
+        ],
+    });
+    let template = ChatTemplate::from_tokenizer_config_str(&config.to_string())?;
+    assert_eq!(template.template_names(), ["default", "tool_use"]);
+    let messages = [Message::user("hi")];
+    let options = ChatTemplateOptions::default();
+    assert_eq!(template.apply(&messages, &options)?, "default: hi");
+    let options = options.with_tools(vec![weather_tool()]);
+    assert_eq!(template.apply(&messages, &options)?, "get_weather: hi");
+    assert!(ChatTemplate::from_tokenizer_config_str(r#"{"eos_token": "</s>"}"#).is_err());
+
+    // The gguf metadata.
+    let tokens = ["<unk>", "<s>", "</s>"]
+        .iter()
+        .map(|t| gguf_file::Value::String(t.to_string()))
+        .collect();
+    let metadata = [
+        (
+            "tokenizer.chat_template",
+            gguf_file::Value::String(MISTRAL.to_string()),
+        ),
+        ("tokenizer.ggml.tokens", gguf_file::Value::Array(tokens)),
+        ("tokenizer.ggml.bos_token_id", gguf_file::Value::U32(1)),
+        ("tokenizer.ggml.eos_token_id", gguf_file::Value::U32(2)),
+    ];
+    let metadata: Vec<_> = metadata.iter().map(|(k, v)| (*k, v)).collect();
+    let mut buf = std::io::Cursor::new(Vec::new());
+    gguf_file::write(&mut buf, &metadata, &[])?;
+    buf.set_position(0);
+    let content = gguf_file::Content::read(&mut buf)?;
+    let template = ChatTemplate::from_gguf(&content)?;
+    assert_eq!(template.bos_token(), Some("<s>"));
+    let prompt = template.apply(&conversation(), &ChatTemplateOptions::default())?;
+    assert_eq!(
+        prompt,
+        r#"<s>[INST] Hi [/INST]Hello!</s>[INST] How are you? [/INST]"#
+    );
+    Ok(())
+}
+
+#[test]
+fn tojson_filter() -> Result<()> {
+    let template = ChatTemplate::new(
+        "{{ messages[0] | tojson }}|{{ messages[0].content | tojson(ensure_ascii=true) }}|\
+         {{ {'b': [1, 2.5], 'a': []} | tojson(sort_keys=true) }}|{{ {'b': {'d': 1, 'c': 2}, 'a': []} | tojson }}",
+    )?;
+    let message = Message::user("Ça va ?");
+    assert_eq!(
+        template.apply(&[message], &ChatTemplateOptions::default())?,
+        r#"{"role": "user", "content": "Ça va ?"}|"\u00c7a va ?"|{"a": [], "b": [1, 2.5]}|{"b": {"d": 1, "c": 2}, "a": []}"#
+    );
+    assert_eq!(Content::from("x").text(), "x");
+    let text: Content = vec![
+        ContentPart::Text {
+            text: "a".to_string(),
+        },
+        ContentPart::Image { url: None },
+        ContentPart::Text {
+            text: "b".to_string(),
+        },
+    ]
+    .into();
+    assert_eq!(text.text(), "ab");
+    // Messages follow the OpenAI format.
+    let message: Message = serde_json::from_value(serde_json::json!({
+        "role": "assistant",
+        "content": null,
+        "tool_calls": [{
+            "id": "call_0",
+            "type": "function",
+            "function": { "name": "f", "arguments": { "x": 1 } },
+        }],
+    }))
+    .unwrap();
+    assert_eq!(
+        message,
+        Message::tool_calls(vec![
+            ToolCall::function("f", serde_json::json!({ "x": 1 })).with_id("call_0")
+        ])
+    );
+    Ok(())
+}
@@ -0,0 +1,257 @@
+use candle::Result;
+use candle_transformers::generation::constrained::{Grammar, GrammarConstraint, Vocabulary};
+use candle_transformers::generation::processors::LogitsPipeline;
+use std::collections::HashMap;
+use std::sync::Arc;
+
+#[test]
+fn gbnf_grammar() -> Result<()> {
+    let grammar = Grammar::from_gbnf(
+        r#"
+        # Arithmetic expressions.
+        root   ::= expr
+        expr   ::= term (("+" | "-") term)*
+        term   ::= factor ("*" factor)*
+        factor ::= [0-9]+ | "(" expr ")"
+        "#,
+    )?;
+    assert_eq!(grammar.root_name(), "root");
+    for text in ["1", "12+3*4", "(1+2)*3", "((7))"] {
+        assert!(grammar.matches(text), "{text}");
+    }
+    for text in ["", "1+", "(1", "1)", "a", "1 + 2"] {
+        assert!(!grammar.matches(text), "{text}");
+    }
+
+    let grammar = Grammar::from_gbnf(r#"root ::= [^a-c\n]{2,3} "é" . "x"? "#)?;
+    assert!(grammar.matches("dzé!"));
+    assert!(grammar.matches("日本é本x"));
+    assert!(!grammar.matches("dé!"));
+    assert!(!grammar.matches("abé!"));
+    assert!(!grammar.matches("ddddé!"));
+
+    assert!(Grammar::from_gbnf("root ::= expr\nexpr ::= expr \"a\" | \"b\"").is_err());
+    assert!(Grammar::from_gbnf("root ::= undefined").is_err());
+    assert!(Grammar::from_gbnf("start ::= \"a\"").is_err());
+    assert!(Grammar::from_gbnf("root ::= \"a").is_err());
+    Ok(())
+}
+
+#[test]
+fn regex_grammar() -> Result<()> {
+    let grammar = Grammar::from_regex(r"^[A-Z][a-z]*(?: [A-Z][a-z]*)*$")?;
+    assert!(grammar.matches("Hello World"));
+    assert!(!grammar.matches("hello"));
+    assert!(!grammar.matches("Hello  World"));
+
+    let grammar = Grammar::from_regex(r"\d{3}-\d{2,4}|n/a")?;
+    assert!(grammar.matches("123-45"));
+    assert!(grammar.matches("123-4567"));
+    assert!(grammar.matches("n/a"));
+    assert!(!grammar.matches("12-45"));
+    assert!(!grammar.matches("123-45678"));
+
+    let grammar = Grammar::from_regex(r"[^\s]+@\w+\.(com|org)")?;
+    assert!(grammar.matches("a.b@example.org"));
+    assert!(!grammar.matches("a b@example.org"));
+    assert!(!grammar.matches("a@example.net"));
+
+    let grammar = Grammar::from_regex(r"a{b}.")?;
+    assert!(grammar.matches("a{b}c"));
+    assert!(!grammar.matches("a{b}\n"));
+
+    assert!(Grammar::from_regex("(ab").is_err());
+    assert!(Grammar::from_regex("ab)").is_err());
+    assert!(Grammar::from_regex("*a").is_err());
+    Ok(())
+}
+
+#[test]
+fn json_schema_grammar() -> Result<()> {
+    let schema = serde_json::json!({
+        "type": "object",
+        "properties": {
+            "name": { "type": "string", "maxLength": 8 },
+            "age": { "type": "integer" },
+            "role": { "enum": ["admin", "user", null] },
+            "tags": { "type": "array", "items": { "$ref": "#/$defs/tag" }, "maxItems": 2 },
+            "score": { "type": ["number", "null"] },
+        },
+        "required": ["name", "age"],
+        "$defs": { "tag": { "type": "string", "pattern": "[a-z]+" } },
+    });
+    let grammar = Grammar::from_json_schema(&schema)?;
+    for text in [
+        r#"{"name": "bob", "age": 42}"#,
+        r#"{"name":"bob","age":-1,"role":"admin"}"#,
+        r#"{"name": "b\"oé", "age": 0, "role": null, "tags": ["x", "yz"], "score": 1.5e3}"#,
+        "{\n  \"name\": \"\",\n  \"age\": 7,\n  \"score\": null\n}",
+    ] {
+        assert!(grammar.matches(text), "{text}");
+    }
+    for text in [
+        // Missing the required age.
+        r#"{"name": "bob"}"#,
+        // Properties are generated in the order of the schema.
+        r#"{"age": 42, "name": "bob"}"#,
+        // Additional property.
+        r#"{"name": "bob", "age": 42, "x": 1}"#,
+        r#"{"name": "bob", "age": 4.2}"#,
+        r#"{"name": "bob", "age": 042}"#,
+        r#"{"name": "too long name", "age": 42}"#,
+        r#"{"name": "bob", "age": 42, "role": "root"}"#,
+        r#"{"name": "bob", "age": 42, "tags": ["X"]}"#,
+        r#"{"name": "bob", "age": 42, "tags": ["a", "b", "c"]}"#,
+        r#"{"name": "bob", "age": 42,}"#,
+        r#"{"name": "bob", "age": 42} "#,
+    ] {
+        assert!(!grammar.matches(text), "{text}");
+    }
+
+    // A recursive schema.
+    let schema = serde_json::json!({
+        "$ref": "#/definitions/node",
+        "definitions": {
+            "node": {
+                "type": "object",
+                "properties": {
+                    "value": { "type": "boolean" },
+                    "children": { "type": "array", "items": { "$ref": "#/definitions/node" } },
+                },
+                "required": ["value"],
+            }
+        },
+    });
+    let grammar = Grammar::from_json_schema(&schema)?;
+    assert!(grammar.matches(r#"{"value": true, "children": [{"value": false, "children": []}]}"#));
+    assert!(!grammar.matches(r#"{"value": true, "children": [{}]}"#));
+
+    let grammar = Grammar::json()?;
+    assert!(grammar.matches(r#"[1, {"a": [true, null]}, "s", -0.5]"#));
+    assert!(!grammar.matches(r#"[1, {"a": [true, nil]}]"#));
+    assert!(Grammar::from_json_schema(&serde_json::json!({"$ref": "#/$defs/missing"})).is_err());
+    Ok(())
+}
+
+#[test]
+fn vocabulary_from_token_strings() -> Result<()> {
+    let vocab: HashMap<String, u32> = [("Ġhello", 0), ("Ċ", 1), ("<|endoftext|>", 2), ("é", 3)]
+        .into_iter()
+        .map(|(t, i)| (t.to_string(), i))
+        .collect();
+    let vocab = Vocabulary::from_token_strings(&vocab, &[2], vec![2])?;
+    assert_eq!(vocab.len(), 4);
+    assert_eq!(vocab.token_bytes(0), Some(" hello".as_bytes()));
+    assert_eq!(vocab.token_bytes(1), Some("\n".as_bytes()));
+    assert_eq!(vocab.token_bytes(2), Some([].as_slice()));
+    // `é` is the byte-level encoding of 0xE9.
+    assert_eq!(vocab.token_bytes(3), Some([0xE9].as_slice()));
+
+    let vocab: HashMap<String, u32> = [("▁hello", 0), ("<0x0A>", 1), ("</s>", 2), ("é", 3)]
+        .into_iter()
+        .map(|(t, i)| (t.to_string(), i))
+        .collect();
+    let vocab = Vocabulary::from_token_strings(&vocab, &[2], vec![2])?;
+    assert_eq!(vocab.token_bytes(0), Some(" hello".as_bytes()));
+    assert_eq!(vocab.token_bytes(1), Some("\n".as_bytes()));
+    assert_eq!(vocab.token_bytes(3), Some("é".as_bytes()));
+    Ok(())
+}
+
+// A vocabulary with multi-character tokens, tokens splitting an utf-8 character and an eos token.
+fn vocab() -> Arc<Vocabulary> {
+    let mut tokens: Vec<Vec<u8>> = [
+        "{", "}", "\"", "a", "ab", "\"a", ": ", ":", "1", "12", " ", "\"}",
+    ]
+    .iter()
+    .map(|t| t.as_bytes().to_vec())
+    .collect();
+    tokens.push("é".as_bytes()[..1].to_vec());
+    tokens.push("é".as_bytes()[1..].to_vec());
+    tokens.push(vec![]);
+    Arc::new(Vocabulary::new(tokens, vec![14]))
+}
+
+fn allowed(constraint: &GrammarConstraint) -> Vec<u32> {
+    let allowed = constraint.allowed_tokens();
+    (0..allowed.len() as u32)
+        .filter(|&i| allowed[i as usize])
+        .collect()
+}
+
+#[test]
+fn grammar_constraint_mask() -> Result<()> {
+    let grammar = Arc::new(Grammar::from_gbnf(
+        r#"root ::= "\"" ("a" | "b" | "é")* "\"""#,
+    )?);
+    let mut constraint = GrammarConstraint::new(grammar, vocab());
+    assert_eq!(allowed(&constraint), [2, 5]);
+    constraint.accept_token(5)?;
+    assert_eq!(allowed(&constraint), [2, 3, 4, 12]);
+    assert!(constraint.accept_token(0).is_err());
+    constraint.accept_token(12)?;
+    // Only the continuation of the utf-8 character is allowed.
+    assert_eq!(allowed(&constraint), [13]);
+    constraint.accept_token(13)?;
+    assert!(!constraint.is_complete());
+    assert!(constraint.accept_token(14).is_err());
+    constraint.accept_token(2)?;
+    assert!(constraint.is_complete());
+    // Only the end of sequence token is allowed after the match.
+    assert_eq!(allowed(&constraint), [14]);
+    assert!(constraint.is_finished());
+    constraint.accept_token(14)?;
+    assert!(allowed(&constraint).is_empty());
+
+    // The states reached again reuse the cached masks, including in the clones.
+    let num_cached = constraint.num_cached_states();
+    constraint.reset();
+    constraint.accept_token(5)?;
+    let mut clone = constraint.clone();
+    clone.accept_token(3)?;
+    assert_eq!(allowed(&clone), [2, 3, 4, 12]);
+    assert_eq!(constraint.num_cached_states(), num_cached);
+
+    let mut logits = vec![0f32; 16];
+    constraint.reset();
+    constraint.apply_mask(&mut logits);
+    for (i, &l) in logits.iter().enumerate() {
+        assert_eq!(l == 0., i == 2 || i == 5, "{i}")
+    }
+    Ok(())
+}
+
+#[test]
+fn grammar_constraint_pipeline() -> Result<()> {
+    let schema = serde_json::json!({
+        "type": "object",
+        "properties": { "a": { "type": "integer" } },
+        "required": ["a"],
+    });
+    let grammar = Arc::new(Grammar::from_json_schema(&schema)?);
+    let vocab = vocab();
+    for seed in 0..20 {
+        let mut pipeline =
+            LogitsPipeline::new(seed).with(GrammarConstraint::new(grammar.clone(), vocab.clone()));
+        let prompt = [3, 4];
+        let mut tokens = prompt.to_vec();
+        let logits = candle::Tensor::zeros(16, candle::DType::F32, &candle::Device::Cpu)?;
+        loop {
+            let token = pipeline.sample(&logits, &tokens, prompt.len())?;
+            if token == 14 {
+                break;
+            }
+            tokens.push(token);
+            assert!(tokens.len() < 100);
+        }
+        let text: Vec<u8> = tokens[prompt.len()..]
+            .iter()
+            .flat_map(|&t| vocab.token_bytes(t).unwrap().to_vec())
+            .collect();
+        let text = String::from_utf8(text).unwrap();
+        let value: serde_json::Value = serde_json::from_str(&text).unwrap();
+        assert!(value["a"].is_i64(), "{text}");
+        assert!(grammar.matches(&text));
+    }
+    Ok(())
+}
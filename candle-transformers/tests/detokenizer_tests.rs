@@ -0,0 +1,123 @@
+use candle::Result;
+use candle_transformers::generation::constrained::Vocabulary;
+use candle_transformers::pipelines::detokenizer::{Detokenizer, IncrementalDecoder, StopSequences};
+use candle_transformers::pipelines::text_generation::FinishReason;
+use std::collections::HashMap;
+use std::sync::Arc;
+
+// A sentencepiece style vocabulary with byte-fallback tokens.
+fn vocab() -> Arc<Vocabulary> {
+    let tokens = [
+        "</s>", "▁Hello", "▁world", "<0xE6>", "<0x97>", "<0xA5>", "<0xFF>", "!", "\n", "User", ":",
+        "▁", "é",
+    ];
+    let vocab: HashMap<String, u32> = tokens
+        .iter()
+        .enumerate()
+        .map(|(i, t)| (t.to_string(), i as u32))
+        .collect();
+    Arc::new(Vocabulary::from_token_strings(&vocab, &[0], vec![0]).unwrap())
+}
+
+fn decode_all(decoder: &mut IncrementalDecoder, tokens: &[u32]) -> Result<Vec<String>> {
+    tokens.iter().map(|&t| decoder.push(t)).collect()
+}
+
+#[test]
+fn incremental_decoder() -> Result<()> {
+    let mut decoder = IncrementalDecoder::new(vocab()).with_strip_leading_space(true);
+    // The three byte-fallback tokens encode `日`, the character is only emitted once complete.
+    let texts = decode_all(&mut decoder, &[1, 3, 4, 5, 2, 12])?;
+    assert_eq!(texts, ["Hello", "", "", "日", " world", "é"]);
+    assert_eq!(decoder.flush(), "");
+
+    // Invalid bytes are replaced, an incomplete character gets replaced when flushing.
+    decoder.reset();
+    let texts = decode_all(&mut decoder, &[2, 6, 7, 3, 4])?;
+    assert_eq!(texts, ["world", "\u{FFFD}", "!", "", ""]);
+    assert_eq!(decoder.flush(), "\u{FFFD}");
+
+    let mut decoder = IncrementalDecoder::new(vocab());
+    assert_eq!(decoder.push(1)?, " Hello");
+    assert!(decoder.push(42).is_err());
+    Ok(())
+}
+
+#[test]
+fn stop_sequences() -> Result<()> {
+    let mut stop = StopSequences::new(vec!["\nUser:".to_string(), "日本".to_string()])?;
+    assert_eq!(stop.push("Hi"), "Hi");
+    assert_eq!(stop.push(" there\n"), " there");
+    // Not a stop sequence after all, the held back text is released.
+    assert_eq!(stop.push("Us"), "");
+    assert_eq!(stop.push("e!"), "\nUse!");
+    assert_eq!(stop.push("日"), "");
+    assert_eq!(stop.push("x\nUs"), "日x");
+    assert_eq!(stop.matched(), None);
+    assert_eq!(stop.push("er: bye"), "");
+    assert_eq!(stop.matched(), Some("\nUser:"));
+    assert_eq!(stop.push("more"), "");
+    assert_eq!(stop.flush(), "");
+
+    // The earliest stop sequence wins.
+    let mut stop = StopSequences::new(vec!["cd".to_string(), "bcde".to_string()])?;
+    assert_eq!(stop.push("abcdef"), "a");
+    assert_eq!(stop.matched(), Some("bcde"));
+
+    stop.reset();
+    assert_eq!(stop.push("xb"), "x");
+    assert_eq!(stop.flush(), "b");
+    assert!(StopSequences::new(vec![String::new()]).is_err());
+    Ok(())
+}
+
+#[test]
+fn detokenizer() -> Result<()> {
+    let stop = vec!["\nUser:".to_string()];
+    let mut detokenizer = Detokenizer::new(vocab())
+        .with_strip_leading_space(true)
+        .with_stop_sequences(stop.clone())?;
+    let mut texts = vec![];
+    for token in [1, 7, 8, 9, 10, 2] {
+        let output = detokenizer.next_token(token)?;
+        texts.push(output.text);
+        if output.finish_reason.is_some() {
+            assert_eq!(output.finish_reason, Some(FinishReason::Stop));
+            break;
+        }
+    }
+    // The stop sequence spans three tokens and is never emitted.
+    assert_eq!(texts, ["Hello", "!", "", "", ""]);
+    assert_eq!(detokenizer.text(), "Hello!");
+    assert_eq!(detokenizer.matched_stop_sequence(), Some("\nUser:"));
+    assert_eq!(detokenizer.finish_reason(), Some(FinishReason::Stop));
+    assert!(detokenizer.next_token(1).is_err());
+
+    // The end-of-sequence token releases the held back text.
+    detokenizer.reset();
+    let texts = [1, 8, 9, 0]
+        .iter()
+        .map(|&t| detokenizer.next_token(t))
+        .collect::<Result<Vec<_>>>()?;
+    let finish_reasons: Vec<_> = texts.iter().map(|o| o.finish_reason).collect();
+    assert_eq!(finish_reasons, [None, None, None, Some(FinishReason::Eos)]);
+    assert_eq!(texts[3].text, "\nUser");
+    assert_eq!(detokenizer.text(), "Hello\nUser");
+
+    let mut detokenizer = Detokenizer::new(vocab()).with_max_tokens(3);
+    let outputs = [1, 3, 4]
+        .iter()
+        .map(|&t| detokenizer.next_token(t))
+        .collect::<Result<Vec<_>>>()?;
+    assert_eq!(outputs[2].finish_reason, Some(FinishReason::Length));
+    assert_eq!(outputs[2].text, "\u{FFFD}");
+    assert_eq!(detokenizer.text(), " Hello\u{FFFD}");
+    assert_eq!(detokenizer.num_tokens(), 3);
+
+    // Generation can be ended by the caller, e.g. on a stop token.
+    let mut detokenizer = Detokenizer::new(vocab()).with_stop_sequences(stop)?;
+    assert_eq!(detokenizer.next_token(8)?.text, "");
+    assert_eq!(detokenizer.finish(FinishReason::Stop), "\n");
+    assert_eq!(detokenizer.finish(FinishReason::Stop), "");
+    Ok(())
+}
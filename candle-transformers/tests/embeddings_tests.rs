@@ -0,0 +1,209 @@
+use candle::{DType, Device, Result, Tensor};
+use candle_nn::{VarBuilder, VarMap};
+use candle_transformers::models::bert;
+use candle_transformers::pipelines::embeddings::{
+    BatchConfig, CrossEncoder, EmbeddingModel, EmbeddingsPipeline, EncodedInput, PaddingSide,
+    Pooling, Reranker,
+};
+
+// The hidden state of each token is `[id, 1]`, this records the shapes of the batches and the
+// padding side.
+#[derive(Default)]
+struct MockModel {
+    batches: Vec<(usize, usize)>,
+    left_padded: bool,
+}
+
+impl EmbeddingModel for MockModel {
+    fn hidden_states(
+        &mut self,
+        input_ids: &Tensor,
+        _token_type_ids: &Tensor,
+        attention_mask: &Tensor,
+    ) -> Result<Tensor> {
+        self.batches.push(input_ids.dims2()?);
+        let mask = attention_mask.to_vec2::<u32>()?;
+        if mask.iter().any(|m| m[0] == 0) {
+            self.left_padded = true
+        }
+        let ids = input_ids.to_dtype(DType::F32)?.unsqueeze(2)?;
+        Tensor::cat(&[&ids, &ids.ones_like()?], 2)
+    }
+}
+
+fn inputs(ids: &[&[u32]]) -> Vec<EncodedInput> {
+    ids.iter().map(|&ids| ids.into()).collect()
+}
+
+fn embed(pooling: Pooling, padding_side: PaddingSide, ids: &[&[u32]]) -> Result<Vec<Vec<f32>>> {
+    let batch_config = BatchConfig {
+        pad_token_id: 99,
+        padding_side,
+        ..Default::default()
+    };
+    let mut pipeline = EmbeddingsPipeline::new(MockModel::default(), &Device::Cpu)
+        .with_pooling(pooling)
+        .with_normalize(false)
+        .with_batch_config(batch_config);
+    pipeline.embed(&inputs(ids))?.to_vec2()
+}
+
+#[test]
+fn pooling() -> Result<()> {
+    let ids: &[&[u32]] = &[&[2, 4, 9], &[5], &[3, 6]];
+    for padding_side in [PaddingSide::Right, PaddingSide::Left] {
+        let cls = embed(Pooling::Cls, padding_side, ids)?;
+        assert_eq!(cls, [[2., 1.], [5., 1.], [3., 1.]]);
+        let last = embed(Pooling::LastToken, padding_side, ids)?;
+        assert_eq!(last, [[9., 1.], [5., 1.], [6., 1.]]);
+        let mean = embed(Pooling::Mean, padding_side, ids)?;
+        assert_eq!(mean, [[5., 1.], [5., 1.], [4.5, 1.]]);
+        let weighted = embed(Pooling::WeightedMean, padding_side, ids)?;
+        assert_eq!(weighted, [[(2. + 8. + 27.) / 6., 1.], [5., 1.], [5., 1.]]);
+    }
+    Ok(())
+}
+
+#[test]
+fn pooling_config() -> Result<()> {
+    let config = r#"{
+        "word_embedding_dimension": 384,
+        "pooling_mode_cls_token": false,
+        "pooling_mode_mean_tokens": true,
+        "pooling_mode_max_tokens": false
+    }"#;
+    assert_eq!(
+        Pooling::from_sentence_transformers_config(config)?,
+        Pooling::Mean
+    );
+    let config = r#"{"pooling_mode_lasttoken": true}"#;
+    assert_eq!(
+        Pooling::from_sentence_transformers_config(config)?,
+        Pooling::LastToken
+    );
+    assert!(
+        Pooling::from_sentence_transformers_config(r#"{"pooling_mode_max_tokens": true}"#).is_err()
+    );
+    Ok(())
+}
+
+#[test]
+fn dynamic_batching() -> Result<()> {
+    let batch_config = BatchConfig {
+        max_batch_size: 2,
+        max_batch_tokens: 6,
+        padding_side: PaddingSide::Left,
+        ..Default::default()
+    };
+    let mut pipeline = EmbeddingsPipeline::new(MockModel::default(), &Device::Cpu)
+        .with_pooling(Pooling::LastToken)
+        .with_normalize(false)
+        .with_batch_config(batch_config);
+    let ids: &[&[u32]] = &[
+        &[1, 2, 3, 4],
+        &[5],
+        &[6, 7],
+        &[8, 9, 10],
+        &[11, 12, 13, 14, 15, 16, 17],
+    ];
+    let embeddings = pipeline.embed(&inputs(ids))?.to_vec2::<f32>()?;
+    let last: Vec<f32> = embeddings.iter().map(|e| e[0]).collect();
+    assert_eq!(last, [4., 5., 7., 10., 17.]);
+    // The inputs are sorted by length, a batch is split when it would exceed either limit.
+    assert_eq!(pipeline.model().batches, [(2, 2), (1, 3), (1, 4), (1, 7)]);
+    assert!(pipeline.model().left_padded);
+    assert!(pipeline.embed(&inputs(&[&[1], &[]])).is_err());
+    Ok(())
+}
+
+#[test]
+fn matryoshka_and_normalization() -> Result<()> {
+    let mut pipeline = EmbeddingsPipeline::new(MockModel::default(), &Device::Cpu);
+    let embedding = pipeline.embed_one(vec![3, 3])?;
+    let norm = 10f32.sqrt();
+    assert_eq!(embedding, [3. / norm, 1. / norm]);
+    let mut pipeline = pipeline.with_dimensions(Some(1));
+    assert_eq!(pipeline.embed_one(vec![3, 3])?, [1.]);
+    // Embeddings that are all zeros stay zeros rather than becoming NaNs.
+    assert_eq!(pipeline.embed_one(vec![0, 0])?, [0.]);
+    pipeline.set_dimensions(Some(3));
+    assert!(pipeline.embed_one(vec![3, 3]).is_err());
+    Ok(())
+}
+
+#[test]
+fn bert_padding_invariance() -> Result<()> {
+    let device = Device::Cpu;
+    let config = bert::Config {
+        vocab_size: 32,
+        hidden_size: 16,
+        num_hidden_layers: 2,
+        num_attention_heads: 2,
+        intermediate_size: 32,
+        max_position_embeddings: 32,
+        ..Default::default()
+    };
+    let varmap = VarMap::new();
+    let vb = VarBuilder::from_varmap(&varmap, DType::F32, &device);
+    let model = bert::BertModel::load(vb, &config)?;
+    let mut pipeline = EmbeddingsPipeline::new(model, &device);
+    let ids: &[&[u32]] = &[&[1, 7, 3, 2], &[1, 5, 2], &[1, 9, 8, 4, 6, 2]];
+    let batched = pipeline.embed(&inputs(ids))?.to_vec2::<f32>()?;
+    for (ids, batched) in ids.iter().zip(batched.iter()) {
+        let single = pipeline.embed_one(*ids)?;
+        assert_eq!(single.len(), 16);
+        let norm: f32 = single.iter().map(|v| v * v).sum();
+        assert!((norm - 1.).abs() < 1e-5);
+        for (a, b) in single.iter().zip(batched.iter()) {
+            assert!((a - b).abs() < 1e-5, "{single:?} {batched:?}")
+        }
+    }
+    Ok(())
+}
+
+// Scores a pair with the sum of its ids, the second label gets the score and the first one its
+// opposite when there are two labels.
+struct MockCrossEncoder {
+    num_labels: usize,
+}
+
+impl CrossEncoder for MockCrossEncoder {
+    fn logits(
+        &mut self,
+        input_ids: &Tensor,
+        _token_type_ids: &Tensor,
+        attention_mask: &Tensor,
+    ) -> Result<Tensor> {
+        let ids = (input_ids.to_dtype(DType::F32)? * attention_mask.to_dtype(DType::F32)?)?;
+        let scores = ids.sum_keepdim(1)?;
+        match self.num_labels {
+            1 => Ok(scores),
+            _ => Tensor::cat(&[&scores.neg()?, &scores], 1),
+        }
+    }
+}
+
+#[test]
+fn reranker() -> Result<()> {
+    let pairs = vec![
+        EncodedInput::with_type_ids(vec![1, 0], vec![0, 1]),
+        EncodedInput::with_type_ids(vec![1, 2, 0], vec![0, 1, 1]),
+        EncodedInput::new(vec![0]),
+    ];
+    let mut reranker = Reranker::new(MockCrossEncoder { num_labels: 1 }, &Device::Cpu);
+    assert_eq!(reranker.scores(&pairs)?, [1., 3., 0.]);
+    assert_eq!(reranker.rerank(&pairs)?, [(1, 3.), (0, 1.), (2, 0.)]);
+    let mut reranker = reranker.with_sigmoid(true);
+    let scores = reranker.scores(&pairs)?;
+    assert!((scores[2] - 0.5).abs() < 1e-6);
+    let sigmoid = |x: f32| 1. / (1. + (-x).exp());
+    assert!((scores[1] - sigmoid(3.)).abs() < 1e-6);
+    let mut reranker = Reranker::new(MockCrossEncoder { num_labels: 2 }, &Device::Cpu);
+    let scores = reranker.scores(&pairs)?;
+    assert!((scores[0] - sigmoid(2.)).abs() < 1e-6);
+    assert!((scores[2] - 0.5).abs() < 1e-6);
+    assert!(reranker.scores(&[])?.is_empty());
+    let mismatched = EncodedInput::with_type_ids(vec![1, 2], vec![0]);
+    assert!(reranker.scores(&[mismatched]).is_err());
+    Ok(())
+}
@@ -1,5 +1,8 @@
 use candle::{Device, Result, Tensor};
+use candle_transformers::generation::processors::*;
 use candle_transformers::generation::LogitsProcessor;
+use candle_transformers::utils::apply_repeat_penalty;
+use std::collections::HashMap;
 
 #[test]
 fn sample_with_zero_temperature() -> Result<()> {
@@ -76,3 +79,159 @@ fn sample_gumbel() -> Result<()> {
     }
     Ok(())
 }
+
+fn apply(
+    processor: &mut impl Processor,
+    logits: &[f32],
+    tokens: &[u32],
+    prompt_len: usize,
+) -> Result<Vec<f32>> {
+    let mut logits = logits.to_vec();
+    processor.apply(&mut logits, &Context { tokens, prompt_len })?;
+    Ok(logits)
+}
+
+// Returns the indexes of the tokens that have not been filtered out.
+fn kept(logits: &[f32]) -> Vec<usize> {
+    (0..logits.len())
+        .filter(|&i| logits[i] != f32::NEG_INFINITY)
+        .collect()
+}
+
+#[test]
+fn logits_penalties() -> Result<()> {
+    let logits = [1.0f32, -1.0, 2.0, 0.5];
+    // The prompt is [0, 1], the generated tokens are [2, 2].
+    let tokens = [0, 1, 2, 2];
+    let penalized = apply(&mut RepetitionPenalty::new(2., 3), &logits, &tokens, 2)?;
+    assert_eq!(penalized, [1.0, -2.0, 1.0, 0.5]);
+    let expected = apply_repeat_penalty(&Tensor::new(&logits, &Device::Cpu)?, 2., &tokens[1..])?;
+    assert_eq!(expected.to_vec1::<f32>()?, penalized);
+
+    let penalized = apply(&mut FrequencyPenalty(0.5), &logits, &tokens, 2)?;
+    assert_eq!(penalized, [1.0, -1.0, 1.0, 0.5]);
+    let penalized = apply(&mut PresencePenalty(0.5), &logits, &tokens, 2)?;
+    assert_eq!(penalized, [1.0, -1.0, 1.5, 0.5]);
+
+    // The bigram [1, 2] has been generated before so 2 cannot follow 1.
+    let banned = apply(&mut NoRepeatNgram(2), &logits, &[1, 2, 3, 1], 0)?;
+    assert_eq!(kept(&banned), [0, 1, 3]);
+    let banned = apply(&mut NoRepeatNgram(3), &logits, &[1, 2, 3, 1], 0)?;
+    assert_eq!(kept(&banned), [0, 1, 2, 3]);
+
+    let bias = HashMap::from([(0, -1.0), (3, 2.0), (7, 1.0)]);
+    let biased = apply(&mut LogitBias(bias), &logits, &[], 0)?;
+    assert_eq!(biased, [0.0, -1.0, 2.0, 2.5]);
+    let banned = apply(&mut BannedTokens(vec![1, 2]), &logits, &[], 0)?;
+    assert_eq!(kept(&banned), [0, 3]);
+    let mut min_length = MinLength::new(2, vec![3]);
+    assert_eq!(
+        kept(&apply(&mut min_length, &logits, &[5, 6], 1)?),
+        [0, 1, 2]
+    );
+    assert_eq!(
+        kept(&apply(&mut min_length, &logits, &[5, 6, 7], 1)?),
+        [0, 1, 2, 3]
+    );
+    Ok(())
+}
+
+#[test]
+fn logits_filters() -> Result<()> {
+    // The probabilities are 0.5, 0.25, 0.125, 0.0625, 0.0625.
+    let logits: Vec<f32> = [8f32, 4., 2., 1., 1.].iter().map(|p| p.ln()).collect();
+    assert_eq!(kept(&apply(&mut TopK(2), &logits, &[], 0)?), [0, 1]);
+    assert_eq!(kept(&apply(&mut TopP(0.7), &logits, &[], 0)?), [0, 1]);
+    assert_eq!(kept(&apply(&mut MinP(0.2), &logits, &[], 0)?), [0, 1, 2]);
+    assert_eq!(
+        kept(&apply(&mut EpsilonCutoff(0.1), &logits, &[], 0)?),
+        [0, 1, 2]
+    );
+    // The most likely token is always kept.
+    assert_eq!(kept(&apply(&mut EpsilonCutoff(0.9), &logits, &[], 0)?), [0]);
+    // The entropy is 1.375 nats, the threshold is min(0.1, 0.316 * 0.253) = 0.08.
+    assert_eq!(
+        kept(&apply(&mut EtaCutoff(0.1), &logits, &[], 0)?),
+        [0, 1, 2]
+    );
+    // The token information contents are 0.69, 1.39, 2.08, 2.77 and 2.77 nats, the 0.25 token
+    // is the closest to the entropy followed by the 0.5 one.
+    assert_eq!(kept(&apply(&mut TypicalP(0.3), &logits, &[], 0)?), [0, 1]);
+    assert_eq!(kept(&apply(&mut TypicalP(0.2), &logits, &[], 0)?), [0, 1]);
+
+    let schedule = TemperatureSchedule::linear(1.0, 0.5, 4);
+    assert_eq!(schedule.temperature(0), 1.0);
+    assert_eq!(schedule.temperature(2), 0.75);
+    assert_eq!(schedule.temperature(10), 0.5);
+    let scaled = apply(
+        &mut TemperatureSchedule::linear(1.0, 0.5, 4),
+        &[1.0, 3.0],
+        &[1, 2],
+        0,
+    )?;
+    assert_eq!(scaled, [4.0 / 3.0, 4.0]);
+    assert!(apply(&mut Temperature::new(0.), &[1.0], &[], 0).is_err());
+    Ok(())
+}
+
+#[test]
+fn logits_pipeline() -> Result<()> {
+    let dev = &Device::Cpu;
+    let logits = Tensor::new(&[1.0f32, 1.2, 0.8, 1.1, 0.9], dev)?;
+    let pipeline = |seed| {
+        LogitsPipeline::new(seed)
+            .with(BannedTokens(vec![1]))
+            .with(MinLength::new(3, vec![4]))
+            .with(Temperature::new(0.5))
+            .with(MinP(0.1))
+    };
+    let sample = |seed| -> Result<Vec<u32>> {
+        let mut pipeline = pipeline(seed);
+        let mut tokens = vec![0];
+        for _ in 0..20 {
+            let token = pipeline.sample(&logits, &tokens, 1)?;
+            tokens.push(token)
+        }
+        Ok(tokens)
+    };
+    let tokens = sample(42)?;
+    assert!(!tokens.contains(&1));
+    assert!(!tokens[1..4].contains(&4));
+    // Sampling is deterministic for a given seed.
+    assert_eq!(tokens, sample(42)?);
+    assert_ne!(tokens, sample(1337)?);
+
+    let mut greedy = LogitsPipeline::greedy().with(PresencePenalty(0.25));
+    assert_eq!(greedy.sample(&logits, &[0], 1)?, 1);
+    assert_eq!(greedy.sample(&logits, &[0, 1], 1)?, 3);
+    let mut banned = LogitsPipeline::greedy().with(BannedTokens(vec![0, 1, 2, 3, 4]));
+    assert!(banned.sample(&logits, &[0], 1).is_err());
+    Ok(())
+}
+
+#[test]
+fn sample_with_logprobs() -> Result<()> {
+    let logits = Tensor::new(&[0.1f32, 0.4, 0.3, -0.2], &Device::Cpu)?;
+    let log_sum = [0.1f32, 0.4, 0.3, -0.2]
+        .iter()
+        .map(|l| l.exp())
+        .sum::<f32>()
+        .ln();
+    let mut processor = LogitsProcessor::new(42, None, None);
+    let sampled = processor.sample_with_logprobs(&logits, 2)?;
+    assert_eq!(sampled.token, 1);
+    assert!((sampled.logprob - (0.4 - log_sum)).abs() < 1e-6);
+    let top: Vec<u32> = sampled.top_logprobs.iter().map(|t| t.token).collect();
+    assert_eq!(top, [1, 2]);
+
+    // The log-probabilities do not depend on the temperature nor on the processors.
+    let mut pipeline = LogitsPipeline::new(42)
+        .with(BannedTokens(vec![1]))
+        .with(Temperature::new(0.1));
+    let sampled = pipeline.sample_with_logprobs(&logits, &[], 0, 10)?;
+    assert_eq!(sampled.token, 2);
+    assert!((sampled.logprob - (0.3 - log_sum)).abs() < 1e-6);
+    assert_eq!(sampled.top_logprobs.len(), 4);
+    assert_eq!(sampled.top_logprobs[0].token, 1);
+    Ok(())
+}
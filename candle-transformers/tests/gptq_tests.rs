@@ -0,0 +1,181 @@
+use candle::{DType, Device, Module, Result, Tensor};
+use candle_transformers::quantized_nn::linear_no_bias;
+use candle_transformers::quantized_var_builder::{QuantizationConfig, VarBuilder};
+use std::collections::HashMap;
+
+#[test]
+fn gptq_var_builder() -> Result<()> {
+    let dev = &Device::Cpu;
+    let cfg: QuantizationConfig = serde_json::from_str(
+        r#"{"quant_method": "gptq", "bits": 4, "group_size": -1, "desc_act": false}"#,
+    )
+    .map_err(candle::Error::wrap)?;
+    let cfg = cfg.gptq_config()?;
+    assert_eq!(cfg.group_size, None);
+
+    // A 4-bit layer with 16 inputs and 8 outputs, all the values are 9 with a zero point of 8
+    // (stored as 7) so that the dequantized weights are equal to the scales.
+    let (in_f, out_f) = (16, 8);
+    let qweight = Tensor::full(0x9999_9999u32 as i32, (in_f / 8, out_f), dev)?;
+    let qzeros = Tensor::full(0x7777_7777u32 as i32, (1, out_f / 8), dev)?;
+    let scales = Tensor::arange(1f32, (out_f + 1) as f32, dev)?
+        .reshape((1, out_f))?
+        .to_dtype(DType::F16)?;
+    let lm_head = Tensor::ones((4, out_f), DType::F16, dev)?;
+    let tensors: HashMap<String, Tensor> = [
+        ("proj.qweight", qweight),
+        ("proj.qzeros", qzeros),
+        ("proj.scales", scales),
+        ("lm_head.weight", lm_head),
+    ]
+    .into_iter()
+    .map(|(k, v)| (k.to_string(), v))
+    .collect();
+    let path = std::env::temp_dir().join(format!("candle-gptq-{}.safetensors", std::process::id()));
+    candle::safetensors::save(&tensors, &path)?;
+    let vb = VarBuilder::from_gptq_safetensors(&[&path], &cfg, dev);
+    std::fs::remove_file(&path)?;
+    let vb = vb?;
+    assert!(vb.contains_key("proj.weight"));
+    assert!(!vb.contains_key("proj.qweight"));
+
+    let proj = linear_no_bias(in_f, out_f, vb.pp("proj"))?;
+    let lm_head = linear_no_bias(out_f, 4, vb.pp("lm_head"))?;
+    assert!(linear_no_bias(out_f, in_f, vb.pp("proj")).is_err());
+    let xs = Tensor::ones((1, 2, in_f), DType::F32, dev)?;
+    let ys = proj.forward(&xs)?;
+    assert_eq!(
+        ys.squeeze(0)?.to_vec2::<f32>()?,
+        vec![(1..=8).map(|v| (v * 16) as f32).collect::<Vec<_>>(); 2]
+    );
+    let ys = lm_head.forward(&ys)?;
+    assert_eq!(ys.squeeze(0)?.to_vec2::<f32>()?, vec![vec![576f32; 4]; 2]);
+    Ok(())
+}
+
+/// Packs a `(out_f, in_f)` 4-bit GPTQ weight with a group size of 16 and returns the packed
+/// tensors together with the dequantized weight.
+fn pack_gptq(out_f: usize, in_f: usize, seed: usize) -> Result<(Vec<(String, Tensor)>, Tensor)> {
+    let (bits, pack, group_size) = (4, 8, 16);
+    let n_groups = in_f / group_size;
+    let q = |k: usize, n: usize| ((k * 7 + n * 13 + seed) % 16) as u32;
+    let zero = |g: usize, n: usize| ((g + n + seed) % 3 + 7) as u32;
+    // The scales are stored as f16, these values are exactly representable.
+    let scale = |g: usize, n: usize| (5 + (g * 5 + n + seed) % 7) as f32 / 512.;
+    let mut qweight = vec![0u32; in_f / pack * out_f];
+    let mut weight = vec![0f32; out_f * in_f];
+    for k in 0..in_f {
+        for n in 0..out_f {
+            let g = k / group_size;
+            qweight[k / pack * out_f + n] |= q(k, n) << (bits * (k % pack));
+            weight[n * in_f + k] = scale(g, n) * (q(k, n) as f32 - zero(g, n) as f32);
+        }
+    }
+    let mut qzeros = vec![0u32; n_groups * out_f / pack];
+    let mut scales = vec![0f32; n_groups * out_f];
+    for g in 0..n_groups {
+        for n in 0..out_f {
+            // The GPTQ zero points are stored with an offset of -1.
+            qzeros[g * out_f / pack + n / pack] |= (zero(g, n) - 1) << (bits * (n % pack));
+            scales[g * out_f + n] = scale(g, n);
+        }
+    }
+    let as_i32 = |v: Vec<u32>| v.into_iter().map(|v| v as i32).collect::<Vec<_>>();
+    let dev = &Device::Cpu;
+    let tensors = vec![
+        (
+            "qweight".to_string(),
+            Tensor::from_vec(as_i32(qweight), (in_f / pack, out_f), dev)?,
+        ),
+        (
+            "qzeros".to_string(),
+            Tensor::from_vec(as_i32(qzeros), (n_groups, out_f / pack), dev)?,
+        ),
+        (
+            "scales".to_string(),
+            Tensor::from_vec(scales, (n_groups, out_f), dev)?,
+        ),
+    ];
+    Ok((tensors, Tensor::from_vec(weight, (out_f, in_f), dev)?))
+}
+
+#[test]
+fn gptq_llama() -> Result<()> {
+    use candle_transformers::models::{llama, quantized_llama};
+
+    let dev = &Device::Cpu;
+    let cfg: llama::LlamaConfig = serde_json::from_str(
+        r#"{"hidden_size": 32, "intermediate_size": 48, "vocab_size": 64,
+            "num_hidden_layers": 2, "num_attention_heads": 4, "num_key_value_heads": 2,
+            "rms_norm_eps": 1e-5, "max_position_embeddings": 64}"#,
+    )
+    .map_err(candle::Error::wrap)?;
+    let cfg = cfg.into_config(false);
+    let (hidden, inter, kv) = (32, 48, 16);
+
+    // The checkpoint tensors and the dequantized ones used by the reference model.
+    let randn = |shape: (usize, usize)| Tensor::randn(0f32, 1., shape, dev);
+    let ones = Tensor::ones(hidden, DType::F32, dev)?;
+    let mut gptq: HashMap<String, Tensor> = [
+        ("model.embed_tokens.weight", randn((64, hidden))?),
+        ("lm_head.weight", (randn((64, hidden))? * 0.1)?),
+        ("model.norm.weight", ones.clone()),
+    ]
+    .into_iter()
+    .map(|(k, v)| (k.to_string(), v))
+    .collect();
+    for i in 0..2 {
+        for norm in ["input_layernorm", "post_attention_layernorm"] {
+            gptq.insert(format!("model.layers.{i}.{norm}.weight"), ones.clone());
+        }
+    }
+    let mut dense = gptq.clone();
+    let mut seed = 0;
+    for i in 0..2 {
+        let prefix = format!("model.layers.{i}");
+        for (name, out_f, in_f) in [
+            ("self_attn.q_proj", hidden, hidden),
+            ("self_attn.k_proj", kv, hidden),
+            ("self_attn.v_proj", kv, hidden),
+            ("self_attn.o_proj", hidden, hidden),
+            ("mlp.gate_proj", inter, hidden),
+            ("mlp.up_proj", inter, hidden),
+            ("mlp.down_proj", hidden, inter),
+        ] {
+            seed += 1;
+            let (packed, weight) = pack_gptq(out_f, in_f, seed)?;
+            for (suffix, t) in packed {
+                gptq.insert(format!("{prefix}.{name}.{suffix}"), t);
+            }
+            dense.insert(format!("{prefix}.{name}.weight"), weight);
+        }
+    }
+
+    let path = std::env::temp_dir().join(format!(
+        "candle-gptq-llama-{}.safetensors",
+        std::process::id()
+    ));
+    candle::safetensors::save(&gptq, &path)?;
+    let qcfg = candle::quantized::gptq::GptqConfig::gptq(4, 16);
+    let vb = VarBuilder::from_gptq_safetensors(&[&path], &qcfg, dev);
+    std::fs::remove_file(&path)?;
+    let mut model = quantized_llama::ModelWeights::from_gptq(&cfg, vb?)?;
+    // The rotary embedding tables cover the positions of the config.
+    assert_eq!(model.max_seq_len(), 64);
+
+    let vb = candle_nn::VarBuilder::from_tensors(dense, DType::F32, dev);
+    let reference = llama::Llama::load(vb, &cfg)?;
+    let mut cache = llama::Cache::new(true, DType::F32, &cfg, dev)?;
+
+    let prompt = Tensor::new(&[[1u32, 5, 9, 2, 33]], dev)?;
+    let logits = model.forward(&prompt, 0)?;
+    let expected = reference.forward(&prompt, 0, &mut cache)?;
+    let diff = (logits - expected)?.abs()?.max_all()?.to_scalar::<f32>()?;
+    assert!(diff < 1e-4, "{diff}");
+    let next = Tensor::new(&[[7u32]], dev)?;
+    let logits = model.forward(&next, 5)?;
+    let expected = reference.forward(&next, 5, &mut cache)?;
+    let diff = (logits - expected)?.abs()?.max_all()?.to_scalar::<f32>()?;
+    assert!(diff < 1e-4, "{diff}");
+    Ok(())
+}
@@ -0,0 +1,37 @@
+use candle::quantized::imatrix_file::ImatrixCollector;
+use candle::{DType, Device, Module, Result, Tensor};
+use candle_transformers::quantized_nn::linear_no_bias;
+use candle_transformers::quantized_var_builder::VarBuilder;
+use std::collections::HashMap;
+
+#[test]
+fn imatrix_var_builder() -> Result<()> {
+    let dev = &Device::Cpu;
+    let tensors: HashMap<String, Tensor> = [
+        ("model.up.weight", Tensor::ones((8, 4), DType::F32, dev)?),
+        ("model.down.weight", Tensor::ones((4, 8), DType::F16, dev)?),
+    ]
+    .into_iter()
+    .map(|(k, v)| (k.to_string(), v))
+    .collect();
+    let path =
+        std::env::temp_dir().join(format!("candle-imatrix-{}.safetensors", std::process::id()));
+    candle::safetensors::save(&tensors, &path)?;
+    let vb = VarBuilder::from_safetensors(&[&path], dev);
+    std::fs::remove_file(&path)?;
+
+    let collector = ImatrixCollector::new();
+    let vb = vb?.with_imatrix_collector(collector.clone()).pp("model");
+    let up = linear_no_bias(4, 8, vb.pp("up"))?;
+    let down = linear_no_bias(8, 4, vb.pp("down"))?;
+    let xs = Tensor::new(&[[[1f32, 2., 0., -1.]]], dev)?;
+    let ys = down.forward(&up.forward(&xs)?)?;
+    assert_eq!(ys.to_vec3::<f32>()?, [[[16f32; 4]]]);
+    collector.next_chunk();
+
+    let imatrix = collector.imatrix();
+    assert_eq!(imatrix.len(), 2);
+    assert_eq!(imatrix["model.up.weight"], [1f32, 4., 0., 1.]);
+    assert_eq!(imatrix["model.down.weight"], [4f32; 8]);
+    Ok(())
+}
@@ -0,0 +1,170 @@
+use candle::{Device, Result, Tensor};
+use candle_transformers::generation::speculative::{
+    DraftModel, NgramProposer, Proposer, SpeculativeDecoder, SpeculativeModel,
+};
+use candle_transformers::generation::{LogitsProcessor, Sampling};
+
+const VOCAB: usize = 4;
+
+// A model where the next token only depends on the last one, the kv-cache is only tracked through
+// its length so that invalid rollbacks get detected.
+struct Bigram {
+    logits: Tensor,
+    cache_len: usize,
+}
+
+impl Bigram {
+    fn new(logits: &[[f32; VOCAB]; VOCAB]) -> Result<Self> {
+        let logits = Tensor::new(logits, &Device::Cpu)?;
+        Ok(Self {
+            logits,
+            cache_len: 0,
+        })
+    }
+
+    fn probs(&self) -> Result<Vec<Vec<f32>>> {
+        candle_nn::ops::softmax_last_dim(&self.logits)?.to_vec2()
+    }
+}
+
+impl SpeculativeModel for Bigram {
+    fn forward_all(&mut self, input: &Tensor, index_pos: usize) -> Result<Tensor> {
+        if index_pos != self.cache_len {
+            candle::bail!(
+                "index_pos {index_pos} does not match the cache {}",
+                self.cache_len
+            )
+        }
+        let input = input.squeeze(0)?;
+        self.cache_len += input.dim(0)?;
+        self.logits.index_select(&input, 0)?.unsqueeze(0)
+    }
+
+    fn truncate_kv_cache(&mut self, len: usize) -> Result<()> {
+        if len > self.cache_len {
+            candle::bail!("cannot truncate {} to {len}", self.cache_len)
+        }
+        self.cache_len = len;
+        Ok(())
+    }
+}
+
+const TARGET: [[f32; VOCAB]; VOCAB] = [
+    [1.0, 0.2, -0.5, 0.3],
+    [0.1, 1.5, 0.0, -1.0],
+    [-0.3, 0.4, 0.9, 0.2],
+    [0.6, -0.2, 0.1, 1.2],
+];
+
+const DRAFT: [[f32; VOCAB]; VOCAB] = [
+    [0.5, 0.5, 0.0, 0.0],
+    [1.0, 0.3, 0.2, -0.5],
+    [-0.3, 0.4, 0.9, 0.2],
+    [0.0, 0.0, 1.0, 0.0],
+];
+
+// Samples two tokens following `prompt` many times and returns the total variation distance
+// between the empirical distribution and the target one.
+fn tv_distance<P: Proposer>(
+    mut proposer: impl FnMut() -> Result<P>,
+    prompt: &[u32],
+) -> Result<f32> {
+    const N: usize = 5000;
+    let probs = Bigram::new(&TARGET)?.probs()?;
+    let sampling = Sampling::All { temperature: 1. };
+    let mut counts = [0usize; VOCAB * VOCAB];
+    let mut acceptance_rate = 0.;
+    for seed in 0..N {
+        let mut decoder = SpeculativeDecoder::new(
+            Bigram::new(&TARGET)?,
+            proposer()?,
+            LogitsProcessor::from_sampling(seed as u64, sampling.clone()),
+            3,
+            &Device::Cpu,
+        );
+        let tokens = decoder.generate(prompt, 2)?;
+        counts[tokens[0] as usize * VOCAB + tokens[1] as usize] += 1;
+        acceptance_rate += decoder.acceptance_rate() / N as f64;
+    }
+    // Some proposals are accepted and some are rejected.
+    assert!(
+        acceptance_rate > 0.1 && acceptance_rate < 0.9,
+        "{acceptance_rate}"
+    );
+    let last = *prompt.last().unwrap() as usize;
+    let mut tv = 0.;
+    for (i, &count) in counts.iter().enumerate() {
+        let (t0, t1) = (i / VOCAB, i % VOCAB);
+        let expected = probs[last][t0] * probs[t0][t1];
+        tv += (count as f32 / N as f32 - expected).abs() / 2.;
+    }
+    Ok(tv)
+}
+
+#[test]
+fn speculative_draft_model_distribution() -> Result<()> {
+    let mut seed = 0;
+    let tv = tv_distance(
+        || {
+            seed += 1;
+            let sampling = Sampling::All { temperature: 1. };
+            let processor = LogitsProcessor::from_sampling(seed, sampling);
+            Ok(DraftModel::new(
+                Bigram::new(&DRAFT)?,
+                processor,
+                &Device::Cpu,
+            ))
+        },
+        &[0, 2, 1],
+    )?;
+    assert!(tv < 0.05, "{tv}");
+    Ok(())
+}
+
+#[test]
+fn speculative_ngram_distribution() -> Result<()> {
+    let tv = tv_distance(|| NgramProposer::new(1, 2), &[1, 0, 3, 2, 1, 0, 3])?;
+    assert!(tv < 0.05, "{tv}");
+    Ok(())
+}
+
+#[test]
+fn speculative_greedy() -> Result<()> {
+    let dev = &Device::Cpu;
+    // Greedy decoding with the target model alone.
+    let mut expected = vec![];
+    let mut last = 2;
+    for _ in 0..10 {
+        last = (0..VOCAB)
+            .max_by(|&i, &j| TARGET[last][i].total_cmp(&TARGET[last][j]))
+            .unwrap();
+        expected.push(last as u32)
+    }
+
+    let draft = DraftModel::new(
+        Bigram::new(&DRAFT)?,
+        LogitsProcessor::from_sampling(0, Sampling::ArgMax),
+        dev,
+    );
+    let target = Bigram::new(&TARGET)?;
+    let processor = LogitsProcessor::from_sampling(0, Sampling::ArgMax);
+    let mut decoder = SpeculativeDecoder::new(target, draft, processor, 4, dev);
+    assert_eq!(decoder.generate(&[2], 10)?, expected);
+    // Restarting reuses the decoder with a fresh cache.
+    assert_eq!(decoder.generate(&[2], 10)?, expected);
+    assert_eq!(decoder.tokens()[0], 2);
+
+    // The target model as its own draft gets all its proposals accepted.
+    let draft = DraftModel::new(
+        Bigram::new(&TARGET)?,
+        LogitsProcessor::from_sampling(0, Sampling::ArgMax),
+        dev,
+    );
+    let target = Bigram::new(&TARGET)?;
+    let processor = LogitsProcessor::from_sampling(0, Sampling::ArgMax);
+    let mut decoder = SpeculativeDecoder::new(target, draft, processor, 4, dev);
+    assert!(decoder.step().is_err());
+    assert_eq!(decoder.generate(&[2], 10)?, expected);
+    assert_eq!(decoder.acceptance_rate(), 1.);
+    Ok(())
+}
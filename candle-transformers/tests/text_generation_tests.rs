@@ -0,0 +1,502 @@
+use candle::quantized::{gguf_file, GgmlDType, QTensor};
+use candle::{Device, IndexOp, Result, Tensor};
+use candle_nn::paged_attention::{PagedAttentionMetadata, PagedKvCache};
+use candle_transformers::generation::constrained::Vocabulary;
+use candle_transformers::generation::logprobs;
+use candle_transformers::generation::speculative::{DraftModel, NgramProposer, SpeculativeDecoder};
+use candle_transformers::generation::{LogitsProcessor, Sampling};
+use candle_transformers::models::auto::CausalLM;
+use candle_transformers::models::quantized_llama::{self, ModelWeights};
+use candle_transformers::pipelines::text_generation::{
+    Engine, EngineConfig, FinishReason, GenerationParams, PagedModel, TokenEvent,
+};
+use std::sync::Arc;
+
+const VOCAB: usize = 32;
+
+// Serializes a tiny llama model with random weights to the gguf format.
+fn tiny_llama() -> Result<Vec<u8>> {
+    let dev = &Device::Cpu;
+    let (dim, hidden, n_head, n_kv_head, n_layer) = (32, 64, 4, 2, 2);
+    let head_dim = dim / n_head;
+    let mut tensors = vec![];
+    let mut add = |name: String, dims: &[usize]| -> Result<()> {
+        let t = Tensor::randn(0f32, 0.5, dims, dev)?;
+        tensors.push((name, QTensor::quantize(&t, GgmlDType::F32)?));
+        Ok(())
+    };
+    add("token_embd.weight".to_string(), &[VOCAB, dim])?;
+    add("output_norm.weight".to_string(), &[dim])?;
+    add("output.weight".to_string(), &[VOCAB, dim])?;
+    for i in 0..n_layer {
+        add(format!("blk.{i}.attn_q.weight"), &[dim, dim])?;
+        add(
+            format!("blk.{i}.attn_k.weight"),
+            &[n_kv_head * head_dim, dim],
+        )?;
+        add(
+            format!("blk.{i}.attn_v.weight"),
+            &[n_kv_head * head_dim, dim],
+        )?;
+        add(format!("blk.{i}.attn_output.weight"), &[dim, dim])?;
+        add(format!("blk.{i}.attn_norm.weight"), &[dim])?;
+        add(format!("blk.{i}.ffn_gate.weight"), &[hidden, dim])?;
+        add(format!("blk.{i}.ffn_up.weight"), &[hidden, dim])?;
+        add(format!("blk.{i}.ffn_down.weight"), &[dim, hidden])?;
+        add(format!("blk.{i}.ffn_norm.weight"), &[dim])?;
+    }
+    let metadata = [
+        (
+            "llama.attention.head_count",
+            gguf_file::Value::U32(n_head as u32),
+        ),
+        (
+            "llama.attention.head_count_kv",
+            gguf_file::Value::U32(n_kv_head as u32),
+        ),
+        ("llama.block_count", gguf_file::Value::U32(n_layer as u32)),
+        ("llama.embedding_length", gguf_file::Value::U32(dim as u32)),
+        (
+            "llama.rope.dimension_count",
+            gguf_file::Value::U32(head_dim as u32),
+        ),
+        (
+            "llama.attention.layer_norm_rms_epsilon",
+            gguf_file::Value::F32(1e-5),
+        ),
+    ];
+    let metadata: Vec<_> = metadata.iter().map(|(k, v)| (*k, v)).collect();
+    let tensors: Vec<_> = tensors.iter().map(|(k, v)| (k.as_str(), v)).collect();
+    let mut buf = std::io::Cursor::new(Vec::new());
+    gguf_file::write(&mut buf, &metadata, &tensors)?;
+    Ok(buf.into_inner())
+}
+
+fn load(buf: &[u8]) -> Result<ModelWeights> {
+    let mut reader = std::io::Cursor::new(buf);
+    let content = gguf_file::Content::read(&mut reader)?;
+    ModelWeights::from_gguf(content, &mut reader, &Device::Cpu)
+}
+
+// Greedy decoding of a single sequence with the standard kv-cache.
+fn greedy(model: &mut ModelWeights, prompt: &[u32], max_new_tokens: usize) -> Result<Vec<u32>> {
+    let mut tokens = prompt.to_vec();
+    let mut index_pos = 0;
+    for _ in 0..max_new_tokens {
+        let input = Tensor::new(&tokens[index_pos..], &Device::Cpu)?.unsqueeze(0)?;
+        let logits = model.forward(&input, index_pos)?.i(0)?;
+        index_pos = tokens.len();
+        tokens.push(logits.argmax(0)?.to_scalar::<u32>()?);
+    }
+    Ok(tokens[prompt.len()..].to_vec())
+}
+
+#[test]
+fn continuous_batching() -> Result<()> {
+    let buf = tiny_llama()?;
+    let mut reference = load(&buf)?;
+    let prompts: [&[u32]; 4] = [
+        &[1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11],
+        &[4, 5, 6],
+        &[7],
+        &[12, 9, 3, 3, 17, 30, 2, 8],
+    ];
+    let expected = prompts
+        .iter()
+        .map(|p| greedy(&mut reference, p, 6))
+        .collect::<Result<Vec<_>>>()?;
+
+    // A small token budget forces chunked prefills, and the small cache forces preemptions.
+    let config = EngineConfig {
+        num_blocks: 8,
+        block_size: 4,
+        max_num_seqs: 3,
+        max_num_batched_tokens: 8,
+        max_seq_len: None,
+    };
+    let mut engine = Engine::new(load(&buf)?, config, &Device::Cpu)?;
+    let params = GenerationParams {
+        max_new_tokens: 6,
+        ..Default::default()
+    };
+    let mut ids = vec![engine.add_request(prompts[0].to_vec(), params.clone())?];
+    let (id, receiver) = engine.add_streaming_request(prompts[1].to_vec(), params.clone())?;
+    ids.push(id);
+    let mut outputs = vec![vec![]; prompts.len()];
+    let mut steps = 0;
+    while engine.has_pending_requests() {
+        // Add the remaining requests mid-flight.
+        if steps == 2 {
+            for prompt in prompts[2..].iter() {
+                ids.push(engine.add_request(prompt.to_vec(), params.clone())?);
+            }
+        }
+        for event in engine.step()? {
+            let idx = ids.iter().position(|&id| id == event.request_id).unwrap();
+            outputs[idx].push(event.token.unwrap());
+            if outputs[idx].len() == 6 {
+                assert_eq!(event.finish_reason, Some(FinishReason::Length))
+            } else {
+                assert_eq!(event.finish_reason, None)
+            }
+        }
+        steps += 1;
+    }
+    assert_eq!(outputs, expected);
+    let streamed: Vec<u32> = receiver.try_iter().filter_map(|e| e.token).collect();
+    assert_eq!(streamed, expected[1]);
+
+    // Stop tokens end a request early.
+    let stop = GenerationParams {
+        stop_tokens: vec![expected[0][2]],
+        ..params
+    };
+    let id = engine.add_request(prompts[0].to_vec(), stop)?;
+    let other = engine.add_request(prompts[2].to_vec(), GenerationParams::default())?;
+    assert!(engine.abort(other)?);
+    assert!(!engine.abort(other)?);
+    // The final event of the aborted request is returned by the next step.
+    let events = engine.step()?;
+    let aborted = events.iter().find(|e| e.request_id == other).unwrap();
+    assert_eq!(aborted.token, None);
+    assert_eq!(aborted.finish_reason, Some(FinishReason::Aborted));
+    let outputs = engine.run_to_completion()?;
+    let first_stop = expected[0]
+        .iter()
+        .position(|&t| t == expected[0][2])
+        .unwrap();
+    let generated: Vec<u32> = events
+        .iter()
+        .filter(|e| e.request_id == id)
+        .filter_map(|e| e.token)
+        .chain(outputs[&id].iter().copied())
+        .collect();
+    assert_eq!(generated, expected[0][..=first_stop]);
+    assert!(!outputs.contains_key(&other));
+
+    // Requests that do not generate any token end right after the prefill.
+    let (id, receiver) = engine.add_streaming_request(
+        prompts[0].to_vec(),
+        GenerationParams {
+            max_new_tokens: 0,
+            ..Default::default()
+        },
+    )?;
+    let mut events = vec![];
+    while engine.has_pending_requests() {
+        events.extend(engine.step()?)
+    }
+    assert_eq!(events.len(), 1);
+    assert_eq!(events[0].request_id, id);
+    assert_eq!(events[0].token, None);
+    assert_eq!(events[0].finish_reason, Some(FinishReason::Length));
+    assert_eq!(receiver.try_iter().collect::<Vec<_>>(), events);
+    assert!(engine
+        .add_request(vec![1; 32], GenerationParams::default())
+        .is_err());
+    Ok(())
+}
+
+// The tiny llama without the all-positions forward.
+struct PerToken(ModelWeights);
+
+impl CausalLM for PerToken {
+    fn forward(&mut self, input_ids: &Tensor, position: usize) -> Result<Tensor> {
+        self.0.forward(input_ids, position)
+    }
+
+    fn clear_kv_cache(&mut self) {
+        self.0.clear_kv_cache()
+    }
+}
+
+// The tiny llama with a context shorter than its kv-cache, positions past the context fail as
+// they would when indexing the rope tables of the model.
+struct ShortContext(ModelWeights);
+
+impl PagedModel for ShortContext {
+    fn max_seq_len(&self) -> usize {
+        16
+    }
+
+    fn paged_kv_caches(&self, num_blocks: usize, block_size: usize) -> Result<Vec<PagedKvCache>> {
+        self.0.paged_kv_caches(num_blocks, block_size)
+    }
+
+    fn forward_paged(
+        &self,
+        input: &Tensor,
+        caches: &mut [PagedKvCache],
+        metadata: &PagedAttentionMetadata,
+    ) -> Result<Tensor> {
+        let max_pos = metadata.positions().max(0)?.to_scalar::<u32>()? as usize;
+        if max_pos >= self.max_seq_len() {
+            candle::bail!("position {max_pos} is out of the model context")
+        }
+        self.0.forward_paged(input, caches, metadata)
+    }
+}
+
+#[test]
+fn engine_max_seq_len() -> Result<()> {
+    let buf = tiny_llama()?;
+    let prompt = |len: usize| (0..len).map(|i| (i % VOCAB) as u32).collect::<Vec<_>>();
+    let params = GenerationParams {
+        max_new_tokens: 10,
+        ..Default::default()
+    };
+    let engine = Engine::new(load(&buf)?, EngineConfig::default(), &Device::Cpu)?;
+    assert_eq!(engine.max_seq_len(), quantized_llama::MAX_SEQ_LEN);
+
+    // The kv-cache can hold more tokens than the model context.
+    let config = EngineConfig {
+        num_blocks: 8,
+        block_size: 4,
+        ..Default::default()
+    };
+    let mut engine = Engine::new(ShortContext(load(&buf)?), config, &Device::Cpu)?;
+    assert_eq!(engine.max_seq_len(), 16);
+    assert!(engine.add_request(prompt(16), params.clone()).is_err());
+    // The sequence ends once it reaches the model context instead of failing the step.
+    let id = engine.add_request(prompt(13), params.clone())?;
+    let mut events = vec![];
+    while engine.has_pending_requests() {
+        events.extend(engine.step()?)
+    }
+    assert_eq!(events.len(), 3);
+    assert!(events
+        .iter()
+        .all(|e| e.request_id == id && e.token.is_some()));
+    assert_eq!(events[2].finish_reason, Some(FinishReason::Length));
+
+    // The engine config can use a shorter context than the model.
+    let config = EngineConfig {
+        max_seq_len: Some(8),
+        ..Default::default()
+    };
+    let mut engine = Engine::new(ShortContext(load(&buf)?), config, &Device::Cpu)?;
+    assert_eq!(engine.max_seq_len(), 8);
+    assert!(engine.add_request(prompt(8), params.clone()).is_err());
+    let id = engine.add_request(prompt(5), params)?;
+    let outputs = engine.run_to_completion()?;
+    assert_eq!(outputs[&id].len(), 3);
+    Ok(())
+}
+
+// The tiny llama failing on the batches that contain the token `VOCAB - 1`.
+struct Poisoned(ModelWeights);
+
+impl PagedModel for Poisoned {
+    fn max_seq_len(&self) -> usize {
+        self.0.max_seq_len()
+    }
+
+    fn paged_kv_caches(&self, num_blocks: usize, block_size: usize) -> Result<Vec<PagedKvCache>> {
+        self.0.paged_kv_caches(num_blocks, block_size)
+    }
+
+    fn forward_paged(
+        &self,
+        input: &Tensor,
+        caches: &mut [PagedKvCache],
+        metadata: &PagedAttentionMetadata,
+    ) -> Result<Tensor> {
+        if input.to_vec1::<u32>()?.contains(&(VOCAB as u32 - 1)) {
+            candle::bail!("poisoned batch")
+        }
+        self.0.forward_paged(input, caches, metadata)
+    }
+}
+
+#[test]
+fn engine_failed_step() -> Result<()> {
+    let buf = tiny_llama()?;
+    let params = GenerationParams {
+        max_new_tokens: 4,
+        stop_tokens: vec![VOCAB as u32 - 1],
+        ..Default::default()
+    };
+    let config = EngineConfig {
+        max_num_seqs: 1,
+        ..Default::default()
+    };
+    let mut engine = Engine::new(Poisoned(load(&buf)?), config, &Device::Cpu)?;
+    let poisoned = engine.add_request(vec![1, 2, VOCAB as u32 - 1], params.clone())?;
+    let (id, receiver) = engine.add_streaming_request(vec![1, 2, 3], params)?;
+    // Only the request in the failed batch gets aborted, the waiting one keeps its state.
+    assert!(engine.step().is_err());
+    let events = engine.step()?;
+    assert_eq!(events[0].request_id, poisoned);
+    assert_eq!(events[0].finish_reason, Some(FinishReason::Aborted));
+    engine.run_to_completion()?;
+    // The weights are random so the request can sample the poisoned token, which stops it.
+    let events: Vec<_> = receiver.try_iter().collect();
+    assert!((1..=4).contains(&events.len()));
+    assert!(events
+        .iter()
+        .all(|e| e.request_id == id && e.token.is_some()));
+    let finish_reason = events.last().unwrap().finish_reason;
+    assert!(matches!(
+        finish_reason,
+        Some(FinishReason::Length | FinishReason::Stop)
+    ));
+    Ok(())
+}
+
+#[test]
+fn speculative_decoding_rollback() -> Result<()> {
+    let dev = &Device::Cpu;
+    let buf = tiny_llama()?;
+    let prompt = [1, 2, 3, 4, 1, 2, 3, 4, 1, 2];
+    let expected = greedy(&mut load(&buf)?, &prompt, 12)?;
+
+    // The n-gram proposals that get rejected are dropped from the kv-cache.
+    let processor = LogitsProcessor::from_sampling(0, Sampling::ArgMax);
+    let proposer = NgramProposer::new(1, 3)?;
+    let mut decoder = SpeculativeDecoder::new(load(&buf)?, proposer, processor, 3, dev);
+    assert_eq!(decoder.generate(&prompt, 12)?, expected);
+
+    let draft = DraftModel::new(
+        load(&buf)?,
+        LogitsProcessor::from_sampling(0, Sampling::ArgMax),
+        dev,
+    );
+    let processor = LogitsProcessor::from_sampling(0, Sampling::ArgMax);
+    let mut decoder = SpeculativeDecoder::new(load(&buf)?, draft, processor, 4, dev);
+    assert_eq!(decoder.generate(&prompt, 12)?, expected);
+    assert_eq!(decoder.acceptance_rate(), 1.);
+    Ok(())
+}
+
+#[test]
+fn logprobs_and_scoring() -> Result<()> {
+    let dev = &Device::Cpu;
+    let buf = tiny_llama()?;
+    let prompt = [3, 1, 4, 1, 5];
+    let generated = greedy(&mut load(&buf)?, &prompt, 8)?;
+
+    // The engine reports the log-probabilities of the sampled tokens.
+    let mut engine = Engine::new(load(&buf)?, EngineConfig::default(), dev)?;
+    let params = GenerationParams {
+        max_new_tokens: 8,
+        logprobs: Some(3),
+        ..Default::default()
+    };
+    let (_, receiver) = engine.add_streaming_request(prompt.to_vec(), params)?;
+    engine.run_to_completion()?;
+    let events: Vec<_> = receiver.try_iter().collect();
+    assert_eq!(events.len(), 8);
+
+    // Scoring the greedy continuation gives the same log-probabilities.
+    let mut model = load(&buf)?;
+    let score = logprobs::score(&mut model, &prompt, &generated, 3, dev)?;
+    assert!(score.is_greedy);
+    assert_eq!(score.len(), 8);
+    for ((event, logprob), top) in events
+        .iter()
+        .zip(score.logprobs.iter())
+        .zip(score.top_logprobs.iter())
+    {
+        let sampled = event.logprobs.as_ref().unwrap();
+        assert_eq!(Some(sampled.token), event.token);
+        assert_eq!(Some(logprob.token), event.token);
+        assert!((sampled.logprob - logprob.logprob).abs() < 1e-4);
+        // Greedy decoding picks the most likely alternative.
+        assert_eq!(sampled.top_logprobs.len(), 3);
+        assert_eq!(sampled.top_logprobs[0].token, sampled.token);
+        assert_eq!(top[0].token, sampled.token);
+        assert!(top.windows(2).all(|w| w[0].logprob >= w[1].logprob));
+    }
+    let log_likelihood: f64 = events
+        .iter()
+        .map(|e| e.logprobs.as_ref().unwrap().logprob as f64)
+        .sum();
+    assert!((score.log_likelihood() - log_likelihood).abs() < 1e-3);
+    let perplexity = (-log_likelihood / 8.).exp();
+    assert!((score.perplexity() - perplexity).abs() < 1e-3 * perplexity);
+
+    // Token by token decoding matches the scoring, and the model can be reused.
+    let mut continuation = generated.clone();
+    continuation[2] = (continuation[2] + 1) % VOCAB as u32;
+    let score = logprobs::score(&mut model, &prompt, &continuation, 0, dev)?;
+    assert!(!score.is_greedy);
+    assert!(score.top_logprobs.iter().all(|t| t.is_empty()));
+    let mut tokens = prompt.to_vec();
+    let mut fresh = load(&buf)?;
+    for (i, &token) in continuation.iter().enumerate() {
+        let index_pos = if i == 0 { 0 } else { tokens.len() - 1 };
+        let input = Tensor::new(&tokens[index_pos..], dev)?.unsqueeze(0)?;
+        let logits = fresh.forward(&input, index_pos)?.i(0)?;
+        let logprob = logprobs::log_softmax(&logits)?[token as usize];
+        assert!((score.logprobs[i].logprob - logprob).abs() < 1e-4);
+        tokens.push(token);
+    }
+    // The models only returning the last logits cannot be scored.
+    let mut per_token = PerToken(load(&buf)?);
+    assert!(logprobs::score(&mut per_token, &prompt, &continuation, 0, dev).is_err());
+    assert!(logprobs::score(&mut model, &[], &continuation, 0, dev).is_err());
+    assert!(logprobs::score(&mut model, &prompt, &[], 0, dev).is_err());
+    Ok(())
+}
+
+#[test]
+fn detokenized_stop_sequences() -> Result<()> {
+    let buf = tiny_llama()?;
+    let prompt = [3, 1, 4, 1, 5];
+    let expected = greedy(&mut load(&buf)?, &prompt, 8)?;
+    // Each token is a single letter apart from the end-of-sequence ones.
+    let vocab = |eos: Option<u32>| {
+        let tokens = (0..VOCAB as u32)
+            .map(|t| match eos {
+                Some(eos) if eos == t => vec![],
+                _ => vec![b'a' + (t % 26) as u8],
+            })
+            .collect();
+        Arc::new(Vocabulary::new(tokens, eos.into_iter().collect()))
+    };
+    let letters = |tokens: &[u32]| -> String {
+        tokens
+            .iter()
+            .map(|&t| (b'a' + (t % 26) as u8) as char)
+            .collect()
+    };
+    let streamed = |events: &[TokenEvent]| -> String {
+        events.iter().map(|e| e.text.clone().unwrap()).collect()
+    };
+
+    let mut engine = Engine::new(load(&buf)?, EngineConfig::default(), &Device::Cpu)?;
+    let params = GenerationParams {
+        stop_sequences: vec![letters(&expected[1..3])],
+        max_new_tokens: 8,
+        ..Default::default()
+    };
+    assert!(engine.add_request(prompt.to_vec(), params.clone()).is_err());
+
+    // The stop sequence spans two tokens and is not part of the streamed text.
+    let mut engine = engine.with_vocabulary(vocab(None));
+    let (_, receiver) = engine.add_streaming_request(prompt.to_vec(), params.clone())?;
+    engine.run_to_completion()?;
+    let text = letters(&expected);
+    let pos = text.find(&letters(&expected[1..3])).unwrap();
+    let events: Vec<_> = receiver.try_iter().collect();
+    assert_eq!(events.len(), pos + 2);
+    assert_eq!(events[pos + 1].finish_reason, Some(FinishReason::Stop));
+    assert_eq!(streamed(&events), text[..pos]);
+
+    // The end-of-sequence tokens of the vocabulary end the requests.
+    let eos = expected[3];
+    let mut engine = Engine::new(load(&buf)?, EngineConfig::default(), &Device::Cpu)?
+        .with_vocabulary(vocab(Some(eos)));
+    let params = GenerationParams {
+        max_new_tokens: 8,
+        ..Default::default()
+    };
+    let (_, receiver) = engine.add_streaming_request(prompt.to_vec(), params)?;
+    engine.run_to_completion()?;
+    let first_eos = expected.iter().position(|&t| t == eos).unwrap();
+    let events: Vec<_> = receiver.try_iter().collect();
+    assert_eq!(events.len(), first_eos + 1);
+    assert_eq!(events[first_eos].finish_reason, Some(FinishReason::Eos));
+    assert_eq!(streamed(&events), letters(&expected[..first_eos]));
+    Ok(())
+}
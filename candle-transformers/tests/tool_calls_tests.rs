@@ -0,0 +1,219 @@
+use candle_transformers::pipelines::chat_template::ToolCall;
+use candle_transformers::pipelines::tool_calls::{ParsedDelta, ToolCallFormat, ToolCallParser};
+use serde_json::json;
+
+// Feeds the text to the parser in chunks of `chunk_size` characters and merges the deltas.
+fn parse(parser: &ToolCallParser, text: &str, chunk_size: usize) -> ParsedDelta {
+    let mut parser = parser.clone();
+    let mut merged = ParsedDelta::default();
+    let mut merge = |delta: ParsedDelta| {
+        merged.reasoning.push_str(&delta.reasoning);
+        merged.content.push_str(&delta.content);
+        merged.tool_calls.extend(delta.tool_calls);
+    };
+    let chars: Vec<char> = text.chars().collect();
+    for chunk in chars.chunks(chunk_size) {
+        merge(parser.push(&chunk.iter().collect::<String>()))
+    }
+    merge(parser.finish());
+    merged
+}
+
+// Checks that the output does not depend on how the text is split.
+fn check(parser: &ToolCallParser, text: &str, content: &str, calls: &[ToolCall]) {
+    for chunk_size in [1, 2, 3, 7, text.len().max(1)] {
+        let delta = parse(parser, text, chunk_size);
+        assert_eq!(delta.content, content, "{text:?} {chunk_size}");
+        assert_eq!(delta.tool_calls, calls, "{text:?} {chunk_size}");
+    }
+}
+
+fn call(name: &str, arguments: serde_json::Value) -> ToolCall {
+    ToolCall::function(name, arguments)
+}
+
+#[test]
+fn hermes() {
+    let parser = ToolCallParser::new(Some(ToolCallFormat::Hermes));
+    let weather = call("get_weather", json!({ "city": "Paris", "unit": "celsius" }));
+    check(
+        &parser,
+        "Let me check.\n<tool_call>\n{\"name\": \"get_weather\", \"arguments\": {\"city\": \"Paris\", \"unit\": \"celsius\"}}\n</tool_call>\n<tool_call>\n{\"name\": \"now\", \"arguments\": {}}\n</tool_call>",
+        "Let me check.\n",
+        &[weather.clone(), call("now", json!({}))],
+    );
+    // Generation usually stops on the closing tag.
+    check(
+        &parser,
+        "<tool_call>\n{\"name\": \"get_weather\", \"arguments\": {\"city\": \"Paris\", \"unit\": \"celsius\"}}\n",
+        "",
+        &[weather],
+    );
+    // Content that only looks like a tool call.
+    check(&parser, "a <tool_ b", "a <tool_ b", &[]);
+    check(
+        &parser,
+        "<tool_call>not json</tool_call> ok",
+        "<tool_call>not json</tool_call> ok",
+        &[],
+    );
+
+    // The content is streamed up to the start tag, the call is returned once complete.
+    let mut parser = parser.clone();
+    assert_eq!(parser.push("Sure <tool").content, "Sure ");
+    assert!(parser.push("_call>{\"name\": \"f\",").is_empty());
+    let delta = parser.push(" \"arguments\": {\"x\": 1}}</tool_call>");
+    assert_eq!(delta.tool_calls, [call("f", json!({ "x": 1 }))]);
+    assert_eq!(parser.num_tool_calls(), 1);
+    assert!(parser.finish().is_empty());
+}
+
+#[test]
+fn llama3_json() {
+    let parser = ToolCallParser::new(Some(ToolCallFormat::Llama3Json));
+    check(
+        &parser,
+        "{\"name\": \"get_weather\", \"parameters\": {\"city\": \"Paris\"}}",
+        "",
+        &[call("get_weather", json!({ "city": "Paris" }))],
+    );
+    check(
+        &parser,
+        "<|python_tag|>{\"name\": \"f\", \"parameters\": {\"s\": \"}{\"}}; {\"name\": \"g\", \"arguments\": \"{\\\"y\\\": 2}\"}",
+        "",
+        &[call("f", json!({ "s": "}{" })), call("g", json!({ "y": 2 }))],
+    );
+    // Tool calls without the tag are only detected at the beginning of the output.
+    check(
+        &parser,
+        "The json is {\"name\": \"f\"}",
+        "The json is {\"name\": \"f\"}",
+        &[],
+    );
+    check(&parser, "  {\"answer\": 42}", "  {\"answer\": 42}", &[]);
+    check(&parser, "Hello!", "Hello!", &[]);
+}
+
+#[test]
+fn pythonic() {
+    let parser = ToolCallParser::new(Some(ToolCallFormat::Pythonic));
+    check(
+        &parser,
+        "[get_weather(city='San Francisco', days=3, metric=True), search(query=\"it's \\\"here\\\"\", filters={'lang': ['en', 'fr'], 'max': 1.5}, page=None), now()]",
+        "",
+        &[
+            call(
+                "get_weather",
+                json!({ "city": "San Francisco", "days": 3, "metric": true }),
+            ),
+            call(
+                "search",
+                json!({
+                    "query": "it's \"here\"",
+                    "filters": { "lang": ["en", "fr"], "max": 1.5 },
+                    "page": null
+                }),
+            ),
+            call("now", json!({})),
+        ],
+    );
+    check(&parser, "[1] is a citation.", "[1] is a citation.", &[]);
+    check(&parser, "[f(1)]", "[f(1)]", &[]);
+    check(&parser, "No calls.", "No calls.", &[]);
+}
+
+#[test]
+fn mistral() {
+    let parser = ToolCallParser::new(Some(ToolCallFormat::Mistral));
+    check(
+        &parser,
+        "[TOOL_CALLS] [{\"name\": \"get_weather\", \"arguments\": {\"city\": \"Paris\"}, \"id\": \"abcDEF123\"}, {\"name\": \"now\", \"arguments\": {}}]",
+        "",
+        &[
+            call("get_weather", json!({ "city": "Paris" })).with_id("abcDEF123"),
+            call("now", json!({})),
+        ],
+    );
+    // The format of the v11 tokenizers.
+    check(
+        &parser,
+        "[TOOL_CALLS]get_weather[ARGS]{\"city\": \"Paris\"}[TOOL_CALLS]now[ARGS]{}",
+        "",
+        &[
+            call("get_weather", json!({ "city": "Paris" })),
+            call("now", json!({})),
+        ],
+    );
+    check(
+        &parser,
+        "[TOOL_CALLS]f[CALL_ID]a1b2c3d4e[ARGS]{\"x\": [1, 2]}",
+        "",
+        &[call("f", json!({ "x": [1, 2] })).with_id("a1b2c3d4e")],
+    );
+    check(
+        &parser,
+        "Bonjour [TOOL_CALLS] nope",
+        "Bonjour [TOOL_CALLS] nope",
+        &[],
+    );
+}
+
+#[test]
+fn granite() {
+    let parser = ToolCallParser::new(Some(ToolCallFormat::Granite));
+    check(
+        &parser,
+        "<|tool_call|>[{\"name\": \"get_weather\", \"arguments\": {\"city\": \"Paris\"}}]",
+        "",
+        &[call("get_weather", json!({ "city": "Paris" }))],
+    );
+    check(
+        &parser,
+        "<|tool_call|>[{\"name\": ",
+        "<|tool_call|>[{\"name\": ",
+        &[],
+    );
+    assert_eq!(
+        ToolCallFormat::from_architecture("granite"),
+        Some(ToolCallFormat::Granite)
+    );
+    assert_eq!(
+        ToolCallFormat::from_architecture("qwen3"),
+        Some(ToolCallFormat::Hermes)
+    );
+    assert_eq!(ToolCallFormat::from_architecture("bert"), None);
+}
+
+#[test]
+fn reasoning() {
+    let parser = ToolCallParser::new(Some(ToolCallFormat::Hermes)).with_reasoning(false);
+    let text = "<think>\nThe user wants the weather.\n</think>\n\nLet me check.<tool_call>{\"name\": \"get_weather\", \"arguments\": {}}</tool_call>";
+    for chunk_size in [1, 3, text.len()] {
+        let delta = parse(&parser, text, chunk_size);
+        assert_eq!(delta.reasoning, "The user wants the weather.\n");
+        assert_eq!(delta.content, "Let me check.");
+        assert_eq!(delta.tool_calls, [call("get_weather", json!({}))]);
+    }
+    // A <tool_call> tag in the reasoning is not a call.
+    let delta = parse(
+        &parser,
+        "<think>maybe <tool_call>{\"name\": \"f\"}</tool_call></think>no",
+        2,
+    );
+    assert_eq!(
+        delta.reasoning,
+        "maybe <tool_call>{\"name\": \"f\"}</tool_call>"
+    );
+    assert_eq!(delta.content, "no");
+    assert!(delta.tool_calls.is_empty());
+
+    // The opening tag is part of the prompt.
+    let parser = ToolCallParser::new(None).with_reasoning(true);
+    let delta = parse(&parser, "\nHmm.</think>\n\nHello <tool_call>", 4);
+    assert_eq!(delta.reasoning, "Hmm.");
+    assert_eq!(delta.content, "Hello <tool_call>");
+    // Generation stopped while reasoning.
+    let delta = parse(&parser, "Hmm.</thi", 1);
+    assert_eq!(delta.reasoning, "Hmm.</thi");
+    assert_eq!(delta.content, "");
+}
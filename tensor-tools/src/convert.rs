@@ -0,0 +1,846 @@
+//! Conversion of HuggingFace model directories to gguf files.
+//!
+//! The directory should contain a `config.json` file, the weights as one or multiple safetensors
+//! files and optionally a `tokenizer.json` file. The tensor names and metadata follow the
+//! llama.cpp conventions so that the generated files can be used with the quantized models from
+//! candle-transformers.
+use candle::quantized::gguf_file::Value;
+use candle::{Result, Tensor};
+use serde_json::Value as Json;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Architecture {
+    Llama,
+    Qwen2,
+    Qwen3,
+}
+
+impl Architecture {
+    pub fn from_model_type(model_type: &str) -> Result<Self> {
+        let arch = match model_type {
+            "llama" | "mistral" => Self::Llama,
+            "qwen2" => Self::Qwen2,
+            "qwen3" => Self::Qwen3,
+            m => candle::bail!("unsupported model type {m}"),
+        };
+        Ok(arch)
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Llama => "llama",
+            Self::Qwen2 => "qwen2",
+            Self::Qwen3 => "qwen3",
+        }
+    }
+
+    /// The llama gguf models use the interleaved rotary embeddings whereas the HuggingFace
+    /// checkpoints rotate the two halves, so the q and k projections have to be permuted.
+    fn permute_qk(&self) -> bool {
+        *self == Self::Llama
+    }
+}
+
+/// Maps a HuggingFace tensor name to its gguf equivalent, returns `None` for the tensors that are
+/// not used by the gguf models.
+pub fn gguf_name(name: &str) -> Result<Option<String>> {
+    let name = match name {
+        "model.embed_tokens.weight" => "token_embd.weight".to_string(),
+        "model.norm.weight" => "output_norm.weight".to_string(),
+        "lm_head.weight" => "output.weight".to_string(),
+        _ if name.ends_with("rotary_emb.inv_freq") => return Ok(None),
+        _ => {
+            let rest = match name.strip_prefix("model.layers.") {
+                Some(rest) => rest,
+                None => candle::bail!("unexpected tensor name {name}"),
+            };
+            let (layer_idx, rest) = match rest.split_once('.') {
+                Some(v) => v,
+                None => candle::bail!("unexpected tensor name {name}"),
+            };
+            let (module, suffix) = match rest.rsplit_once('.') {
+                Some(v) => v,
+                None => candle::bail!("unexpected tensor name {name}"),
+            };
+            let module = match module {
+                "input_layernorm" => "attn_norm",
+                "post_attention_layernorm" => "ffn_norm",
+                "self_attn.q_proj" => "attn_q",
+                "self_attn.k_proj" => "attn_k",
+                "self_attn.v_proj" => "attn_v",
+                "self_attn.o_proj" => "attn_output",
+                "self_attn.q_norm" => "attn_q_norm",
+                "self_attn.k_norm" => "attn_k_norm",
+                "mlp.gate_proj" => "ffn_gate",
+                "mlp.up_proj" => "ffn_up",
+                "mlp.down_proj" => "ffn_down",
+                _ => candle::bail!("unexpected tensor name {name}"),
+            };
+            format!("blk.{layer_idx}.{module}.{suffix}")
+        }
+    };
+    Ok(Some(name))
+}
+
+/// Maps the name of a weight recorded by an imatrix collector to the gguf name expected by
+/// llama.cpp. The names recorded on HuggingFace checkpoints are converted, the other ones are
+/// assumed to already use the gguf conventions.
+pub fn imatrix_name(name: &str) -> Result<String> {
+    if !name.starts_with("model.") && name != "lm_head.weight" {
+        return Ok(name.to_string());
+    }
+    match gguf_name(name)? {
+        Some(gguf_name) => Ok(gguf_name),
+        None => candle::bail!("no gguf equivalent for {name}"),
+    }
+}
+
+fn permute(w: &Tensor, n_head: usize) -> Result<Tensor> {
+    let (out_dim, in_dim) = w.dims2()?;
+    w.reshape((n_head, 2, out_dim / n_head / 2, in_dim))?
+        .transpose(1, 2)?
+        .reshape((out_dim, in_dim))
+}
+
+fn get_usize(config: &Json, key: &str) -> Result<Option<usize>> {
+    match config.get(key) {
+        None | Some(Json::Null) => Ok(None),
+        Some(v) => match v.as_u64() {
+            Some(v) => Ok(Some(v as usize)),
+            None => candle::bail!("unexpected value for {key} in config {v}"),
+        },
+    }
+}
+
+fn req_usize(config: &Json, key: &str) -> Result<usize> {
+    match get_usize(config, key)? {
+        Some(v) => Ok(v),
+        None => candle::bail!("missing {key} in config"),
+    }
+}
+
+fn get_f64(config: &Json, key: &str) -> Option<f64> {
+    config.get(key).and_then(|v| v.as_f64())
+}
+
+/// Returns the model hyper-parameters as gguf metadata.
+pub fn model_metadata(arch: Architecture, config: &Json) -> Result<Vec<(String, Value)>> {
+    let a = arch.name();
+    let hidden_size = req_usize(config, "hidden_size")?;
+    let n_head = req_usize(config, "num_attention_heads")?;
+    let n_kv_head = get_usize(config, "num_key_value_heads")?.unwrap_or(n_head);
+    let head_dim = get_usize(config, "head_dim")?.unwrap_or(hidden_size / n_head);
+    let u32_ = |v: usize| Value::U32(v as u32);
+    let mut md = vec![
+        (
+            "general.architecture".to_string(),
+            Value::String(a.to_string()),
+        ),
+        (
+            format!("{a}.context_length"),
+            u32_(req_usize(config, "max_position_embeddings")?),
+        ),
+        (format!("{a}.embedding_length"), u32_(hidden_size)),
+        (
+            format!("{a}.block_count"),
+            u32_(req_usize(config, "num_hidden_layers")?),
+        ),
+        (
+            format!("{a}.feed_forward_length"),
+            u32_(req_usize(config, "intermediate_size")?),
+        ),
+        (format!("{a}.attention.head_count"), u32_(n_head)),
+        (format!("{a}.attention.head_count_kv"), u32_(n_kv_head)),
+        (format!("{a}.attention.key_length"), u32_(head_dim)),
+        (format!("{a}.attention.value_length"), u32_(head_dim)),
+        (format!("{a}.rope.dimension_count"), u32_(head_dim)),
+        (
+            format!("{a}.rope.freq_base"),
+            Value::F32(get_f64(config, "rope_theta").unwrap_or(10000.) as f32),
+        ),
+        (
+            format!("{a}.attention.layer_norm_rms_epsilon"),
+            Value::F32(get_f64(config, "rms_norm_eps").unwrap_or(1e-6) as f32),
+        ),
+        (
+            format!("{a}.vocab_size"),
+            u32_(req_usize(config, "vocab_size")?),
+        ),
+    ];
+    md.extend(rope_scaling_metadata(a, config)?);
+    for (key, name) in [("bos_token_id", "bos"), ("eos_token_id", "eos")] {
+        // The eos token can be a list, in which case the first element is used.
+        let id = match config.get(key) {
+            Some(Json::Array(ids)) => ids.first().and_then(|v| v.as_u64()),
+            Some(v) => v.as_u64(),
+            None => None,
+        };
+        if let Some(id) = id {
+            md.push((
+                format!("tokenizer.ggml.{name}_token_id"),
+                Value::U32(id as u32),
+            ))
+        }
+    }
+    Ok(md)
+}
+
+fn rope_scaling(config: &Json) -> Option<(&str, &Json)> {
+    let rope_scaling = config.get("rope_scaling").filter(|v| v.is_object())?;
+    // Older configs use `type` rather than `rope_type`.
+    let rope_type = rope_scaling
+        .get("rope_type")
+        .or_else(|| rope_scaling.get("type"))
+        .and_then(|v| v.as_str())?;
+    Some((rope_type, rope_scaling))
+}
+
+/// Returns the rope scaling of the config as gguf metadata. The llama3 scaling has no metadata,
+/// it is exported as a `rope_freqs.weight` tensor, see [`rope_freqs`].
+fn rope_scaling_metadata(a: &str, config: &Json) -> Result<Vec<(String, Value)>> {
+    let (rope_type, rope_scaling) = match rope_scaling(config) {
+        None => return Ok(vec![]),
+        Some(v) => v,
+    };
+    let factor = match get_f64(rope_scaling, "factor") {
+        Some(factor) => Value::F32(factor as f32),
+        None => candle::bail!("missing factor in rope_scaling"),
+    };
+    let mut md = vec![];
+    match rope_type {
+        "default" | "llama3" => {}
+        "linear" => {
+            md.push((
+                format!("{a}.rope.scaling.type"),
+                Value::String("linear".to_string()),
+            ));
+            md.push((format!("{a}.rope.scaling.factor"), factor))
+        }
+        "yarn" => {
+            md.push((
+                format!("{a}.rope.scaling.type"),
+                Value::String("yarn".to_string()),
+            ));
+            md.push((format!("{a}.rope.scaling.factor"), factor));
+            if let Some(ctx) = get_usize(rope_scaling, "original_max_position_embeddings")? {
+                md.push((
+                    format!("{a}.rope.scaling.original_context_length"),
+                    Value::U32(ctx as u32),
+                ))
+            }
+        }
+        t => candle::bail!("unsupported rope scaling type {t}"),
+    }
+    Ok(md)
+}
+
+/// Returns the per-frequency divisors used by the llama3 rope scaling, llama.cpp stores them in
+/// a `rope_freqs.weight` tensor. `None` is returned for the other scaling types.
+pub fn rope_freqs(config: &Json) -> Result<Option<Tensor>> {
+    let rope_scaling = match rope_scaling(config) {
+        Some(("llama3", rope_scaling)) => rope_scaling,
+        _ => return Ok(None),
+    };
+    let hidden_size = req_usize(config, "hidden_size")?;
+    let n_head = req_usize(config, "num_attention_heads")?;
+    let head_dim = get_usize(config, "head_dim")?.unwrap_or(hidden_size / n_head);
+    let base = get_f64(config, "rope_theta").unwrap_or(10000.);
+    let factor = get_f64(rope_scaling, "factor").unwrap_or(8.);
+    let low_freq_factor = get_f64(rope_scaling, "low_freq_factor").unwrap_or(1.);
+    let high_freq_factor = get_f64(rope_scaling, "high_freq_factor").unwrap_or(4.);
+    let old_ctx_len = get_f64(rope_scaling, "original_max_position_embeddings").unwrap_or(8192.);
+    let low_freq_wavelen = old_ctx_len / low_freq_factor;
+    let high_freq_wavelen = old_ctx_len / high_freq_factor;
+    let factors: Vec<f32> = (0..head_dim)
+        .step_by(2)
+        .map(|i| {
+            let freq = 1. / base.powf(i as f64 / head_dim as f64);
+            let wavelen = 2. * std::f64::consts::PI / freq;
+            let f = if wavelen < high_freq_wavelen {
+                1.
+            } else if wavelen > low_freq_wavelen {
+                factor
+            } else {
+                let smooth = (old_ctx_len / wavelen - low_freq_factor)
+                    / (high_freq_factor - low_freq_factor);
+                1. / ((1. - smooth) / factor + smooth)
+            };
+            f as f32
+        })
+        .collect();
+    let n = factors.len();
+    Ok(Some(Tensor::from_vec(factors, n, &candle::Device::Cpu)?))
+}
+
+// The token types used by llama.cpp.
+const TOKEN_NORMAL: i32 = 1;
+const TOKEN_CONTROL: i32 = 3;
+const TOKEN_USER_DEFINED: i32 = 4;
+const TOKEN_UNUSED: i32 = 5;
+const TOKEN_BYTE: i32 = 6;
+
+/// Returns the vocabulary of a `tokenizer.json` file as gguf metadata. Only BPE tokenizers are
+/// supported, the ones using byte fallback are exported as sentencepiece `llama` tokenizers and
+/// the other ones as `gpt2` tokenizers.
+///
+/// The sentencepiece tokenizers require a score per token, these are taken from `scores`, usually
+/// read from the `tokenizer.model` file with [`sentencepiece_scores`], and otherwise derived from
+/// the merges so that the tokens are merged in the same order as the BPE model.
+pub fn tokenizer_metadata(
+    tokenizer: &Json,
+    vocab_size: usize,
+    scores: Option<&[f32]>,
+) -> Result<Vec<(String, Value)>> {
+    let model = &tokenizer["model"];
+    if model["type"].as_str() != Some("BPE") {
+        candle::bail!("unsupported tokenizer type {}", model["type"])
+    }
+    let byte_fallback = model["byte_fallback"].as_bool().unwrap_or(false);
+    let mut tokens: HashMap<usize, (String, i32)> = HashMap::new();
+    if let Some(vocab) = model["vocab"].as_object() {
+        for (token, id) in vocab.iter() {
+            let id = match id.as_u64() {
+                Some(id) => id as usize,
+                None => candle::bail!("unexpected token id {id}"),
+            };
+            let is_byte = byte_fallback
+                && token.len() == 6
+                && token.starts_with("<0x")
+                && token.ends_with('>');
+            let token_type = if is_byte { TOKEN_BYTE } else { TOKEN_NORMAL };
+            tokens.insert(id, (token.clone(), token_type));
+        }
+    }
+    if let Some(added_tokens) = tokenizer["added_tokens"].as_array() {
+        for token in added_tokens.iter() {
+            let (id, content) = match (token["id"].as_u64(), token["content"].as_str()) {
+                (Some(id), Some(content)) => (id as usize, content),
+                _ => candle::bail!("unexpected added token {token}"),
+            };
+            let token_type = if token["special"].as_bool().unwrap_or(false) {
+                TOKEN_CONTROL
+            } else {
+                TOKEN_USER_DEFINED
+            };
+            tokens.insert(id, (content.to_string(), token_type));
+        }
+    }
+    let n_tokens = tokens.keys().max().map_or(0, |v| v + 1).max(vocab_size);
+    let mut names = Vec::with_capacity(n_tokens);
+    let mut types = Vec::with_capacity(n_tokens);
+    for id in 0..n_tokens {
+        let (name, token_type) = match tokens.remove(&id) {
+            Some(v) => v,
+            None => (format!("[PAD{id}]"), TOKEN_UNUSED),
+        };
+        names.push(Value::String(name));
+        types.push(Value::I32(token_type));
+    }
+    let mut md = vec![];
+    if byte_fallback {
+        md.push((
+            "tokenizer.ggml.model".to_string(),
+            Value::String("llama".to_string()),
+        ));
+        let scores = match scores {
+            Some(scores) => scores.to_vec(),
+            None => merge_scores(model, &names)?,
+        };
+        if scores.len() > n_tokens {
+            candle::bail!("got {} scores for {n_tokens} tokens", scores.len())
+        }
+        // The padding tokens that are not part of the tokenizer model get a zero score.
+        let scores = (0..n_tokens)
+            .map(|i| Value::F32(scores.get(i).copied().unwrap_or(0.)))
+            .collect();
+        md.push(("tokenizer.ggml.scores".to_string(), Value::Array(scores)));
+    } else {
+        md.push((
+            "tokenizer.ggml.model".to_string(),
+            Value::String("gpt2".to_string()),
+        ));
+        md.push((
+            "tokenizer.ggml.pre".to_string(),
+            Value::String("default".to_string()),
+        ));
+    }
+    md.push(("tokenizer.ggml.tokens".to_string(), Value::Array(names)));
+    md.push(("tokenizer.ggml.token_type".to_string(), Value::Array(types)));
+    if model["merges"].is_array() {
+        let merges = merges(model)?
+            .into_iter()
+            .map(|(l, r)| Value::String(format!("{l} {r}")))
+            .collect();
+        md.push(("tokenizer.ggml.merges".to_string(), Value::Array(merges)));
+    }
+    Ok(md)
+}
+
+fn merges(model: &Json) -> Result<Vec<(String, String)>> {
+    let merges = match model["merges"].as_array() {
+        None => return Ok(vec![]),
+        Some(merges) => merges,
+    };
+    merges
+        .iter()
+        .map(|m| {
+            let merge = match m {
+                Json::String(m) => m.split_once(' '),
+                Json::Array(m) => match m.as_slice() {
+                    [Json::String(l), Json::String(r)] => Some((l.as_str(), r.as_str())),
+                    _ => None,
+                },
+                _ => None,
+            };
+            match merge {
+                Some((l, r)) => Ok((l.to_string(), r.to_string())),
+                None => candle::bail!("unexpected merge {m}"),
+            }
+        })
+        .collect()
+}
+
+// The sentencepiece BPE models merge the pair resulting in the token with the highest score, using
+// minus the merge rank reproduces the order of the HuggingFace merges. The tokens that do not
+// result from a merge, e.g. the bytes or the special tokens, get a zero score.
+fn merge_scores(model: &Json, names: &[Value]) -> Result<Vec<f32>> {
+    let mut ranks = HashMap::new();
+    for (rank, (l, r)) in merges(model)?.into_iter().enumerate() {
+        ranks.entry(format!("{l}{r}")).or_insert(rank);
+    }
+    names
+        .iter()
+        .map(|name| {
+            let name = name.to_string()?;
+            Ok(ranks.get(name).map_or(0., |&rank| -(rank as f32)))
+        })
+        .collect()
+}
+
+/// Returns the score of each piece from a sentencepiece `tokenizer.model` file.
+pub fn sentencepiece_scores(model: &[u8]) -> Result<Vec<f32>> {
+    fn varint(buf: &[u8], pos: &mut usize) -> Result<u64> {
+        let mut v = 0u64;
+        for shift in (0..64).step_by(7) {
+            let b = match buf.get(*pos) {
+                Some(b) => *b,
+                None => candle::bail!("unexpected end of sentencepiece model"),
+            };
+            *pos += 1;
+            v |= ((b & 0x7f) as u64) << shift;
+            if b & 0x80 == 0 {
+                return Ok(v);
+            }
+        }
+        candle::bail!("invalid varint in sentencepiece model")
+    }
+    // Returns the protobuf fields as (field number, wire type, value) where the value holds the
+    // bytes of the length delimited and fixed size fields.
+    fn fields(buf: &[u8]) -> Result<Vec<(u64, u64, &[u8])>> {
+        let mut pos = 0;
+        let mut fields = vec![];
+        while pos < buf.len() {
+            let key = varint(buf, &mut pos)?;
+            let (field, wire_type) = (key >> 3, key & 7);
+            let len = match wire_type {
+                0 => {
+                    varint(buf, &mut pos)?;
+                    0
+                }
+                1 => 8,
+                2 => varint(buf, &mut pos)? as usize,
+                5 => 4,
+                w => candle::bail!("unsupported wire type {w} in sentencepiece model"),
+            };
+            match buf.get(pos..pos + len) {
+                Some(value) => fields.push((field, wire_type, value)),
+                None => candle::bail!("unexpected end of sentencepiece model"),
+            }
+            pos += len
+        }
+        Ok(fields)
+    }
+    let mut scores = vec![];
+    // The pieces are the first field of the model proto, the score is the second field of each
+    // piece.
+    for (field, wire_type, piece) in fields(model)? {
+        if field != 1 || wire_type != 2 {
+            continue;
+        }
+        let score = fields(piece)?
+            .into_iter()
+            .find(|(field, wire_type, _)| *field == 2 && *wire_type == 5)
+            .map_or(0., |(_, _, v)| f32::from_le_bytes([v[0], v[1], v[2], v[3]]));
+        scores.push(score)
+    }
+    Ok(scores)
+}
+
+fn read_json<P: AsRef<Path>>(p: P) -> Result<Json> {
+    let p = p.as_ref();
+    let content = std::fs::read(p)?;
+    serde_json::from_slice(&content)
+        .map_err(|e| candle::Error::Msg(format!("cannot parse {p:?}: {e}")))
+}
+
+/// Returns the safetensors files for a model, using the index file for sharded checkpoints.
+pub fn safetensors_files(dir: &Path) -> Result<Vec<PathBuf>> {
+    let index = dir.join("model.safetensors.index.json");
+    if index.exists() {
+        return index_files(&index);
+    }
+    let mut files = vec![];
+    for entry in std::fs::read_dir(dir)? {
+        let path = entry?.path();
+        if path.extension().is_some_and(|e| e == "safetensors") {
+            files.push(path)
+        }
+    }
+    files.sort();
+    if files.is_empty() {
+        candle::bail!("no safetensors files in {dir:?}")
+    }
+    Ok(files)
+}
+
+/// Returns the files listed in the `weight_map` of a safetensors index file.
+pub fn index_files(index: &Path) -> Result<Vec<PathBuf>> {
+    let json = read_json(index)?;
+    let weight_map = match json.get("weight_map").and_then(|v| v.as_object()) {
+        Some(weight_map) => weight_map,
+        None => candle::bail!("no weight map in {index:?}"),
+    };
+    let dir = index.parent().unwrap_or(Path::new("."));
+    let mut files: Vec<PathBuf> = weight_map
+        .values()
+        .filter_map(|v| v.as_str())
+        .map(|v| dir.join(v))
+        .collect();
+    files.sort();
+    files.dedup();
+    Ok(files)
+}
+
+pub struct Converted {
+    pub metadata: Vec<(String, Value)>,
+    /// The tensors using their gguf names.
+    pub tensors: Vec<(String, Tensor)>,
+}
+
+/// Loads a HuggingFace model directory, renaming the tensors and computing the gguf metadata.
+pub fn load_hf_dir(dir: &Path) -> Result<Converted> {
+    let config = read_json(dir.join("config.json"))?;
+    let model_type = match config["model_type"].as_str() {
+        Some(m) => m,
+        None => candle::bail!("no model_type in config"),
+    };
+    let arch = Architecture::from_model_type(model_type)?;
+    let mut metadata = model_metadata(arch, &config)?;
+    if let Some(name) = dir.file_name().and_then(|v| v.to_str()) {
+        metadata.push(("general.name".to_string(), Value::String(name.to_string())))
+    }
+    let tokenizer = dir.join("tokenizer.json");
+    if tokenizer.exists() {
+        let vocab_size = req_usize(&config, "vocab_size")?;
+        let sp_model = dir.join("tokenizer.model");
+        let scores = if sp_model.exists() {
+            Some(sentencepiece_scores(&std::fs::read(sp_model)?)?)
+        } else {
+            None
+        };
+        let tokenizer = read_json(tokenizer)?;
+        metadata.extend(tokenizer_metadata(
+            &tokenizer,
+            vocab_size,
+            scores.as_deref(),
+        )?)
+    }
+    let tokenizer_config = dir.join("tokenizer_config.json");
+    if tokenizer_config.exists() {
+        if let Some(template) = read_json(tokenizer_config)?["chat_template"].as_str() {
+            let template = Value::String(template.to_string());
+            metadata.push(("tokenizer.chat_template".to_string(), template))
+        }
+    }
+
+    let n_head = req_usize(&config, "num_attention_heads")?;
+    let n_kv_head = get_usize(&config, "num_key_value_heads")?.unwrap_or(n_head);
+    let mut tensors = vec![];
+    for file in safetensors_files(dir)?.iter() {
+        for (name, tensor) in candle::safetensors::load(file, &candle::Device::Cpu)? {
+            let name = match gguf_name(&name)? {
+                None => continue,
+                Some(name) => name,
+            };
+            let tensor = if arch.permute_qk() && name.ends_with("attn_q.weight") {
+                permute(&tensor, n_head)?
+            } else if arch.permute_qk() && name.ends_with("attn_k.weight") {
+                permute(&tensor, n_kv_head)?
+            } else {
+                tensor
+            };
+            tensors.push((name, tensor))
+        }
+    }
+    if let Some(rope_freqs) = rope_freqs(&config)? {
+        tensors.push(("rope_freqs.weight".to_string(), rope_freqs))
+    }
+    tensors.sort_by(|a, b| a.0.cmp(&b.0));
+    Ok(Converted { metadata, tensors })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn gguf_names() -> Result<()> {
+        for (name, expected) in [
+            ("model.embed_tokens.weight", "token_embd.weight"),
+            ("model.norm.weight", "output_norm.weight"),
+            ("lm_head.weight", "output.weight"),
+            (
+                "model.layers.0.input_layernorm.weight",
+                "blk.0.attn_norm.weight",
+            ),
+            (
+                "model.layers.1.post_attention_layernorm.weight",
+                "blk.1.ffn_norm.weight",
+            ),
+            ("model.layers.2.self_attn.q_proj.bias", "blk.2.attn_q.bias"),
+            (
+                "model.layers.12.self_attn.o_proj.weight",
+                "blk.12.attn_output.weight",
+            ),
+            (
+                "model.layers.3.self_attn.k_norm.weight",
+                "blk.3.attn_k_norm.weight",
+            ),
+            (
+                "model.layers.4.mlp.gate_proj.weight",
+                "blk.4.ffn_gate.weight",
+            ),
+        ] {
+            assert_eq!(gguf_name(name)?.as_deref(), Some(expected))
+        }
+        assert_eq!(
+            gguf_name("model.layers.0.self_attn.rotary_emb.inv_freq")?,
+            None
+        );
+        assert!(gguf_name("model.layers.0.mlp.unknown.weight").is_err());
+        assert!(gguf_name("transformer.wte.weight").is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn imatrix_names() -> Result<()> {
+        for (name, expected) in [
+            (
+                "model.layers.3.self_attn.q_proj.weight",
+                "blk.3.attn_q.weight",
+            ),
+            (
+                "model.layers.0.mlp.down_proj.weight",
+                "blk.0.ffn_down.weight",
+            ),
+            ("lm_head.weight", "output.weight"),
+            ("blk.1.attn_k.weight", "blk.1.attn_k.weight"),
+        ] {
+            assert_eq!(imatrix_name(name)?, expected)
+        }
+        assert!(imatrix_name("model.layers.0.mlp.unknown.weight").is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn permute_qk() -> Result<()> {
+        // Two heads with a head dimension of 4, each head goes from the rotate-half layout
+        // (x0, x1, y0, y1) to the interleaved one (x0, y0, x1, y1).
+        let w = Tensor::arange(0f32, 16., &candle::Device::Cpu)?.reshape((8, 2))?;
+        let w = permute(&w, 2)?;
+        let rows: Vec<f32> = w.to_vec2::<f32>()?.iter().map(|r| r[0] / 2.).collect();
+        assert_eq!(rows, [0., 2., 1., 3., 4., 6., 5., 7.]);
+        Ok(())
+    }
+
+    fn md_get<'a>(md: &'a [(String, Value)], key: &str) -> Option<&'a Value> {
+        md.iter().find(|(k, _)| k == key).map(|(_, v)| v)
+    }
+
+    fn md_str(md: &[(String, Value)], key: &str) -> Option<String> {
+        md_get(md, key).and_then(|v| v.to_string().ok()).cloned()
+    }
+
+    fn strings(v: Option<&Value>) -> Result<Vec<String>> {
+        match v {
+            Some(Value::Array(v)) => v.iter().map(|v| Ok(v.to_string()?.clone())).collect(),
+            v => candle::bail!("unexpected value {v:?}"),
+        }
+    }
+
+    fn numbers(v: Option<&Value>) -> Result<Vec<f32>> {
+        match v {
+            Some(Value::Array(v)) => v
+                .iter()
+                .map(|v| match v {
+                    Value::I32(v) => Ok(*v as f32),
+                    v => v.to_f32(),
+                })
+                .collect(),
+            v => candle::bail!("unexpected value {v:?}"),
+        }
+    }
+
+    #[test]
+    fn tokenizer_byte_fallback() -> Result<()> {
+        let tokenizer: Json = serde_json::json!({
+            "model": {
+                "type": "BPE",
+                "byte_fallback": true,
+                "vocab": {"<unk>": 0, "<0x41>": 1, "▁": 2, "a": 3, "b": 4, "ab": 5, "▁ab": 6},
+                "merges": ["a b", "▁ ab"],
+            },
+            "added_tokens": [{"id": 0, "content": "<unk>", "special": true}],
+        });
+        let md = tokenizer_metadata(&tokenizer, 8, None)?;
+        assert_eq!(
+            md_str(&md, "tokenizer.ggml.model").as_deref(),
+            Some("llama")
+        );
+        assert_eq!(
+            strings(md_get(&md, "tokenizer.ggml.tokens"))?,
+            ["<unk>", "<0x41>", "▁", "a", "b", "ab", "▁ab", "[PAD7]"]
+        );
+        assert_eq!(
+            numbers(md_get(&md, "tokenizer.ggml.token_type"))?,
+            [3., 6., 1., 1., 1., 1., 1., 5.]
+        );
+        // Without a sentencepiece model the scores follow the merge ranks.
+        assert_eq!(
+            numbers(md_get(&md, "tokenizer.ggml.scores"))?,
+            [0., 0., 0., 0., 0., 0., -1., 0.]
+        );
+        assert_eq!(
+            strings(md_get(&md, "tokenizer.ggml.merges"))?,
+            ["a b", "▁ ab"]
+        );
+        let scores = [0., 0., -1., -2., -3., -4., -5.];
+        let md = tokenizer_metadata(&tokenizer, 8, Some(&scores))?;
+        assert_eq!(
+            numbers(md_get(&md, "tokenizer.ggml.scores"))?,
+            [0., 0., -1., -2., -3., -4., -5., 0.]
+        );
+        assert!(tokenizer_metadata(&tokenizer, 8, Some(&[0.; 9])).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn tokenizer_gpt2() -> Result<()> {
+        let tokenizer: Json = serde_json::json!({
+            "model": {
+                "type": "BPE",
+                "vocab": {"a": 0, "b": 1, "ab": 2},
+                "merges": [["a", "b"]],
+            },
+            "added_tokens": [
+                {"id": 3, "content": "<|endoftext|>", "special": true},
+                {"id": 4, "content": "<think>", "special": false},
+            ],
+        });
+        let md = tokenizer_metadata(&tokenizer, 4, None)?;
+        assert_eq!(md_str(&md, "tokenizer.ggml.model").as_deref(), Some("gpt2"));
+        assert!(md_get(&md, "tokenizer.ggml.scores").is_none());
+        assert_eq!(
+            strings(md_get(&md, "tokenizer.ggml.tokens"))?,
+            ["a", "b", "ab", "<|endoftext|>", "<think>"]
+        );
+        assert_eq!(
+            numbers(md_get(&md, "tokenizer.ggml.token_type"))?,
+            [1., 1., 1., 3., 4.]
+        );
+        assert_eq!(strings(md_get(&md, "tokenizer.ggml.merges"))?, ["a b"]);
+        let tokenizer = serde_json::json!({"model": {"type": "Unigram"}});
+        assert!(tokenizer_metadata(&tokenizer, 4, None).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn sentencepiece_model() -> Result<()> {
+        fn piece(piece: &str, score: f32) -> Vec<u8> {
+            let mut buf = vec![0x0a, piece.len() as u8];
+            buf.extend_from_slice(piece.as_bytes());
+            buf.push(0x15);
+            buf.extend_from_slice(&score.to_le_bytes());
+            // The piece type, a varint field.
+            buf.extend_from_slice(&[0x18, 0x01]);
+            buf
+        }
+        let mut model = vec![];
+        for (p, score) in [("<unk>", 0.), ("▁t", -1.5), ("he", -2.25)] {
+            let p = piece(p, score);
+            model.push(0x0a);
+            model.push(p.len() as u8);
+            model.extend_from_slice(&p)
+        }
+        // A trainer spec that should be skipped.
+        model.extend_from_slice(&[0x12, 0x02, 0x08, 0x96]);
+        assert_eq!(sentencepiece_scores(&model)?, [0., -1.5, -2.25]);
+        assert!(sentencepiece_scores(&model[..model.len() - 1]).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn rope_scaling() -> Result<()> {
+        let config = serde_json::json!({
+            "hidden_size": 4096, "num_attention_heads": 32, "rope_theta": 500000.0,
+            "rope_scaling": {
+                "rope_type": "llama3", "factor": 8.0, "low_freq_factor": 1.0,
+                "high_freq_factor": 4.0, "original_max_position_embeddings": 8192
+            }
+        });
+        assert!(rope_scaling_metadata("llama", &config)?.is_empty());
+        let factors = match rope_freqs(&config)? {
+            Some(factors) => factors.to_vec1::<f32>()?,
+            None => candle::bail!("no rope_freqs for llama3"),
+        };
+        assert_eq!(factors.len(), 64);
+        assert_eq!(factors[0], 1.);
+        assert_eq!(factors[63], 8.);
+        assert!(factors.windows(2).all(|w| w[0] <= w[1]));
+        assert!(factors.iter().any(|&f| f > 1. && f < 8.));
+
+        let config = serde_json::json!({
+            "rope_scaling": {"type": "linear", "factor": 2.0}
+        });
+        let md = rope_scaling_metadata("llama", &config)?;
+        assert_eq!(
+            md_str(&md, "llama.rope.scaling.type").as_deref(),
+            Some("linear")
+        );
+        assert_eq!(
+            md_get(&md, "llama.rope.scaling.factor")
+                .map(|v| v.to_f32())
+                .transpose()?,
+            Some(2.)
+        );
+        assert!(rope_freqs(&config)?.is_none());
+
+        let config = serde_json::json!({
+            "rope_scaling": {
+                "rope_type": "yarn", "factor": 4.0, "original_max_position_embeddings": 32768
+            }
+        });
+        let md = rope_scaling_metadata("qwen2", &config)?;
+        assert_eq!(
+            md_get(&md, "qwen2.rope.scaling.original_context_length")
+                .map(|v| v.to_u32())
+                .transpose()?,
+            Some(32768)
+        );
+        let config = serde_json::json!({"rope_scaling": {"rope_type": "dynamic", "factor": 2.0}});
+        assert!(rope_scaling_metadata("llama", &config).is_err());
+        Ok(())
+    }
+}
@@ -1,7 +1,13 @@
-use candle::quantized::{gguf_file, GgmlDType, QTensor};
-use candle::{Device, Result};
+use candle::quantized::{gguf_file, imatrix_file, GgmlDType, QTensor};
+use candle::{Device, Result, Tensor};
 use clap::{Parser, Subcommand, ValueEnum};
 use rayon::prelude::*;
+use std::collections::HashMap;
+
+mod convert;
+mod recipe;
+
+use recipe::Recipe;
 
 #[derive(ValueEnum, Debug, Clone)]
 enum QuantizationMode {
@@ -51,6 +57,28 @@ enum Quantization {
     Q5k,
     Q6k,
     Q8k,
+    #[value(name = "iq1_s")]
+    Iq1s,
+    #[value(name = "iq1_m")]
+    Iq1m,
+    #[value(name = "iq2_xxs")]
+    Iq2xxs,
+    #[value(name = "iq2_xs")]
+    Iq2xs,
+    #[value(name = "iq2_s")]
+    Iq2s,
+    #[value(name = "iq3_xxs")]
+    Iq3xxs,
+    #[value(name = "iq3_s")]
+    Iq3s,
+    #[value(name = "iq4_nl")]
+    Iq4nl,
+    #[value(name = "iq4_xs")]
+    Iq4xs,
+    #[value(name = "tq1_0")]
+    Tq1_0,
+    #[value(name = "tq2_0")]
+    Tq2_0,
     F16,
     F32,
 }
@@ -70,12 +98,31 @@ impl Quantization {
             Quantization::Q5k => GgmlDType::Q5K,
             Quantization::Q6k => GgmlDType::Q6K,
             Quantization::Q8k => GgmlDType::Q8K,
+            Quantization::Iq1s => GgmlDType::IQ1S,
+            Quantization::Iq1m => GgmlDType::IQ1M,
+            Quantization::Iq2xxs => GgmlDType::IQ2XXS,
+            Quantization::Iq2xs => GgmlDType::IQ2XS,
+            Quantization::Iq2s => GgmlDType::IQ2S,
+            Quantization::Iq3xxs => GgmlDType::IQ3XXS,
+            Quantization::Iq3s => GgmlDType::IQ3S,
+            Quantization::Iq4nl => GgmlDType::IQ4NL,
+            Quantization::Iq4xs => GgmlDType::IQ4XS,
+            Quantization::Tq1_0 => GgmlDType::TQ1_0,
+            Quantization::Tq2_0 => GgmlDType::TQ2_0,
             Quantization::F16 => GgmlDType::F16,
             Quantization::F32 => GgmlDType::F32,
         }
     }
 }
 
+#[derive(ValueEnum, Debug, Clone, Copy)]
+enum ImatrixModel {
+    /// The mistral architecture, `models::quantized_mistral`.
+    Mistral,
+    /// The llama architecture, `models::quantized_llama`, GPTQ and AWQ checkpoints are supported.
+    Llama,
+}
+
 #[derive(ValueEnum, Debug, Clone)]
 enum Format {
     Safetensors,
@@ -143,13 +190,90 @@ enum Command {
         #[arg(long)]
         out_file: std::path::PathBuf,
 
-        /// The quantization schema to apply.
+        /// The quantization schema to apply, this is the default type when used together with a
+        /// preset or a recipe.
         #[arg(long, value_enum)]
-        quantization: Quantization,
+        quantization: Option<Quantization>,
 
         /// Which tensor to quantize.
         #[arg(long, value_enum, default_value_t = QuantizationMode::Llama)]
         mode: QuantizationMode,
+
+        /// An importance matrix file in the llama.cpp format, used when quantizing safetensors
+        /// files.
+        #[arg(long)]
+        imatrix: Option<std::path::PathBuf>,
+
+        /// Per-tensor quantization rules with the format `regex=dtype`, e.g. `output.*=q6k`. The
+        /// first rule matching a tensor name takes precedence over the default quantization.
+        #[arg(long)]
+        rule: Vec<String>,
+
+        /// A recipe file with one `regex = dtype` rule per line.
+        #[arg(long)]
+        recipe: Option<std::path::PathBuf>,
+
+        /// A llama.cpp quantization preset, e.g. Q4_K_M, Q5_K_S or Q8_0.
+        #[arg(long)]
+        preset: Option<String>,
+    },
+
+    /// Converts a HuggingFace model directory to a gguf file.
+    Convert {
+        /// The directory containing the config.json, tokenizer.json and safetensors files.
+        dir: std::path::PathBuf,
+
+        /// The output file, in gguf format.
+        #[arg(long)]
+        out_file: std::path::PathBuf,
+
+        /// The quantization schema to apply to the 2d weights.
+        #[arg(long, value_enum, default_value_t = Quantization::F16)]
+        quantization: Quantization,
+
+        /// Per-tensor quantization rules with the format `regex=dtype`, the regexes apply to the
+        /// gguf tensor names.
+        #[arg(long)]
+        rule: Vec<String>,
+    },
+
+    /// Prints or edits the metadata of a gguf file.
+    Metadata {
+        file: std::path::PathBuf,
+
+        /// Sets a metadata value, with the format `key=value` or `key:type=value`. When no type
+        /// is specified, the type of the existing value is used and strings otherwise.
+        #[arg(long)]
+        set: Vec<String>,
+
+        /// Removes a metadata key.
+        #[arg(long)]
+        delete: Vec<String>,
+
+        /// The output file for edits, the file is modified in place if unspecified.
+        #[arg(long)]
+        out_file: Option<std::path::PathBuf>,
+    },
+
+    /// Merges sharded safetensors files into a single file.
+    Merge {
+        /// The input files, or the model.safetensors.index.json file of the checkpoint.
+        in_file: Vec<std::path::PathBuf>,
+
+        /// The output file, in safetensors format.
+        #[arg(long)]
+        out_file: std::path::PathBuf,
+    },
+
+    /// Compares the tensors of two checkpoints.
+    Diff {
+        file1: std::path::PathBuf,
+
+        file2: std::path::PathBuf,
+
+        /// Only print the tensors with a max abs error above this threshold.
+        #[arg(long, default_value_t = 0.)]
+        threshold: f32,
     },
 
     Dequantize {
@@ -160,6 +284,40 @@ enum Command {
         #[arg(long)]
         out_file: std::path::PathBuf,
     },
+
+    /// Computes an importance matrix by running a llama-like model over some calibration text.
+    Imatrix {
+        /// The model weights, either safetensors files or a single gguf file.
+        model: Vec<std::path::PathBuf>,
+
+        /// The model architecture.
+        #[arg(long, value_enum, default_value_t = ImatrixModel::Mistral)]
+        which: ImatrixModel,
+
+        /// The model config, in the HuggingFace config.json format.
+        #[arg(long)]
+        config: std::path::PathBuf,
+
+        /// The tokenizer, in the HuggingFace tokenizer.json format.
+        #[arg(long)]
+        tokenizer: std::path::PathBuf,
+
+        /// The calibration text file.
+        #[arg(long)]
+        text: std::path::PathBuf,
+
+        /// The output file, in the llama.cpp imatrix format.
+        #[arg(long)]
+        out_file: std::path::PathBuf,
+
+        /// The number of tokens per chunk.
+        #[arg(long, default_value_t = 512)]
+        ctx_size: usize,
+
+        /// The maximum number of chunks to process, all the text is used by default.
+        #[arg(long)]
+        chunks: Option<usize>,
+    },
 }
 
 #[derive(Parser, Debug, Clone)]
@@ -395,35 +553,83 @@ fn run_ls(
     Ok(())
 }
 
+/// Builds the quantization recipe from the command line arguments, the rules passed on the
+/// command line take precedence over the recipe file which itself takes precedence over the
+/// preset. The preset uses the llama type selection unless an architecture is specified.
+fn build_recipe(
+    q: Option<Quantization>,
+    rules: &[String],
+    recipe_file: Option<&std::path::Path>,
+    preset: Option<&str>,
+    arch: Option<&str>,
+    names: &[&str],
+) -> Result<Recipe> {
+    let mut recipe = Recipe::new(q.map(|q| q.dtype()));
+    for rule in rules.iter() {
+        recipe.push(recipe::Rule::parse(rule)?)
+    }
+    if let Some(recipe_file) = recipe_file {
+        recipe.extend(Recipe::from_file(recipe_file)?)
+    }
+    if let Some(preset) = preset {
+        let preset: recipe::Preset = preset.parse()?;
+        let n_layers = recipe::count_layers(names.iter().copied());
+        let arch = arch.unwrap_or("llama");
+        recipe.extend(Recipe::preset_for_arch(preset, arch, n_layers)?)
+    }
+    if recipe.default_dtype().is_none() && recipe.rules().is_empty() {
+        candle::bail!("no quantization specified, use --quantization, --preset or --recipe")
+    }
+    Ok(recipe)
+}
+
+// Matches the imatrix entries with the tensors, the imatrix files use the gguf tensor names
+// whereas the safetensors files use the Hugging Face ones. Fails when an imatrix was given but
+// none of its entries matches a tensor.
+fn imatrix_entries<'a>(
+    imatrix: &'a HashMap<String, Vec<f32>>,
+    names: &[&str],
+) -> Result<HashMap<String, &'a [f32]>> {
+    let entries: HashMap<String, &[f32]> = names
+        .iter()
+        .filter_map(|&name| {
+            let entry = imatrix.get(name).or_else(|| {
+                let name = convert::imatrix_name(name).ok()?;
+                imatrix.get(&name)
+            })?;
+            Some((name.to_string(), entry.as_slice()))
+        })
+        .collect();
+    if !imatrix.is_empty() && entries.is_empty() {
+        candle::bail!(
+            "none of the {} imatrix entries matches a tensor",
+            imatrix.len()
+        )
+    }
+    Ok(entries)
+}
+
 fn run_quantize_safetensors(
     in_files: &[std::path::PathBuf],
     out_file: std::path::PathBuf,
-    q: Quantization,
+    recipe: &Recipe,
+    imatrix: &HashMap<String, Vec<f32>>,
+    tensors: HashMap<String, Tensor>,
 ) -> Result<()> {
     let mut out_file = std::fs::File::create(out_file)?;
-    let mut tensors = std::collections::HashMap::new();
-    for in_file in in_files.iter() {
-        let in_tensors = candle::safetensors::load(in_file, &Device::Cpu)?;
-        tensors.extend(in_tensors)
-    }
-    println!("tensors: {}", tensors.len());
-
-    let dtype = q.dtype();
-    let block_size = dtype.block_size();
-
+    println!("tensors: {} from {} files", tensors.len(), in_files.len());
+    let names = tensors.keys().map(|v| v.as_str()).collect::<Vec<_>>();
+    let imatrix = imatrix_entries(imatrix, &names)?;
     let qtensors = tensors
         .into_par_iter()
         .map(|(name, tensor)| {
-            let should_quantize = tensor.rank() == 2 && tensor.dim(1)? % block_size == 0;
-            println!("  quantizing {name} {tensor:?} {should_quantize}");
-            let tensor = if should_quantize {
-                QTensor::quantize(&tensor, dtype)?
-            } else {
-                QTensor::quantize(&tensor, GgmlDType::F32)?
-            };
-            Ok((name, tensor))
+            let imatrix = imatrix.get(&name).copied();
+            let qtensor = recipe.quantize_imatrix(&name, &tensor, imatrix)?;
+            println!("  quantizing {name} {tensor:?} {:?}", qtensor.dtype());
+            Ok((name, qtensor))
         })
         .collect::<Result<Vec<_>>>()?;
+    print_stats(&qtensors);
     let qtensors = qtensors
         .iter()
         .map(|(k, v)| (k.as_str(), v))
@@ -432,6 +638,14 @@ fn run_quantize_safetensors(
     Ok(())
 }
 
+fn print_stats(qtensors: &[(String, QTensor)]) {
+    let mut stats = recipe::QuantizationStats::new();
+    for (name, qtensor) in qtensors.iter() {
+        stats.add(name, qtensor)
+    }
+    println!("{stats}")
+}
+
 fn run_dequantize(
     in_file: std::path::PathBuf,
     out_file: std::path::PathBuf,
@@ -449,11 +663,22 @@ fn run_dequantize(
     Ok(())
 }
 
+// How the tensors of a gguf file get quantized.
+enum GgufQuantization {
+    Recipe(Recipe),
+    Mode(GgmlDType),
+}
+
+#[allow(clippy::too_many_arguments)]
 fn run_quantize(
     in_files: &[std::path::PathBuf],
     out_file: std::path::PathBuf,
-    q: Quantization,
+    q: Option<Quantization>,
     qmode: QuantizationMode,
+    imatrix: Option<&std::path::PathBuf>,
+    rules: &[String],
+    recipe_file: Option<&std::path::Path>,
+    preset: Option<&str>,
     device: &Device,
 ) -> Result<()> {
     if in_files.is_empty() {
@@ -464,9 +689,20 @@ fn run_quantize(
             candle::bail!("the generated file cannot use the safetensors extension")
         }
     }
+    let imatrix = match imatrix {
+        Some(imatrix) => Some(imatrix_file::load_imatrix(imatrix)?),
+        None => None,
+    };
     if let Some(extension) = in_files[0].extension() {
         if extension == "safetensors" {
-            return run_quantize_safetensors(in_files, out_file, q);
+            let mut tensors = HashMap::new();
+            for in_file in in_files.iter() {
+                tensors.extend(candle::safetensors::load(in_file, &Device::Cpu)?)
+            }
+            let names = tensors.keys().map(|v| v.as_str()).collect::<Vec<_>>();
+            let recipe = build_recipe(q, rules, recipe_file, preset, None, &names)?;
+            let imatrix = imatrix.unwrap_or_default();
+            return run_quantize_safetensors(in_files, out_file, &recipe, &imatrix, tensors);
         }
     }
 
@@ -480,7 +716,32 @@ fn run_quantize(
     let content = gguf_file::Content::read(&mut in_)?;
     println!("tensors: {}", content.tensor_infos.len());
 
-    let dtype = q.dtype();
+    let names = content
+        .tensor_infos
+        .keys()
+        .map(|v| v.as_str())
+        .collect::<Vec<_>>();
+    // The quantization mode is used unless some rules, a recipe, a preset or an imatrix are
+    // specified.
+    let quantization =
+        if rules.is_empty() && recipe_file.is_none() && preset.is_none() && imatrix.is_none() {
+            match q {
+                Some(q) => GgufQuantization::Mode(q.dtype()),
+                None => candle::bail!(
+                    "no quantization specified, use --quantization, --preset or --recipe"
+                ),
+            }
+        } else {
+            let arch = content
+                .metadata
+                .get("general.architecture")
+                .and_then(|v| v.to_string().ok());
+            let arch = arch.map(|v| v.as_str());
+            let recipe = build_recipe(q, rules, recipe_file, preset, arch, &names)?;
+            GgufQuantization::Recipe(recipe)
+        };
+    let imatrix = imatrix.unwrap_or_default();
+    let imatrix = imatrix_entries(&imatrix, &names)?;
     let qtensors = content
         .tensor_infos
         .par_iter()
@@ -488,10 +749,19 @@ fn run_quantize(
             println!("  quantizing {name}");
             let mut in_file = std::fs::File::open(&in_files[0])?;
             let tensor = content.tensor(&mut in_file, name, device)?;
-            let tensor = qmode.quantize(name, tensor, dtype)?;
-            Ok((name, tensor))
+            let tensor = match &quantization {
+                GgufQuantization::Recipe(recipe) if tensor.rank() == 2 => {
+                    let tensor = tensor.dequantize(&Device::Cpu)?;
+                    let imatrix = imatrix.get(name).copied();
+                    recipe.quantize_imatrix(name, &tensor, imatrix)?
+                }
+                GgufQuantization::Recipe(_) => tensor,
+                GgufQuantization::Mode(dtype) => qmode.quantize(name, tensor, *dtype)?,
+            };
+            Ok((name.to_string(), tensor))
         })
         .collect::<Result<Vec<_>>>()?;
+    print_stats(&qtensors);
     let qtensors = qtensors
         .iter()
         .map(|(k, v)| (k.as_str(), v))
@@ -506,6 +776,462 @@ fn run_quantize(
     Ok(())
 }
 
+fn write_gguf(
+    out_file: &std::path::Path,
+    metadata: &[(String, gguf_file::Value)],
+    qtensors: &[(String, QTensor)],
+) -> Result<()> {
+    let mut out_file = std::fs::File::create(out_file)?;
+    let metadata = metadata
+        .iter()
+        .map(|(k, v)| (k.as_str(), v))
+        .collect::<Vec<_>>();
+    let qtensors = qtensors
+        .iter()
+        .map(|(k, v)| (k.as_str(), v))
+        .collect::<Vec<_>>();
+    gguf_file::write(&mut out_file, &metadata, &qtensors)
+}
+
+fn run_convert(
+    dir: &std::path::Path,
+    out_file: &std::path::Path,
+    q: Quantization,
+    rules: &[String],
+) -> Result<()> {
+    let converted = convert::load_hf_dir(dir)?;
+    println!(
+        "metadata: {}, tensors: {}",
+        converted.metadata.len(),
+        converted.tensors.len()
+    );
+    let recipe = build_recipe(Some(q), rules, None, None, None, &[])?;
+    let qtensors = converted
+        .tensors
+        .into_par_iter()
+        .map(|(name, tensor)| {
+            let qtensor = recipe.quantize(&name, &tensor)?;
+            println!("  quantizing {name} {tensor:?} {:?}", qtensor.dtype());
+            Ok((name, qtensor))
+        })
+        .collect::<Result<Vec<_>>>()?;
+    print_stats(&qtensors);
+    write_gguf(out_file, &converted.metadata, &qtensors)
+}
+
+fn format_value(value: &gguf_file::Value) -> String {
+    use gguf_file::Value;
+    match value {
+        Value::String(v) if v.chars().count() > 80 => {
+            let head = v.chars().take(80).collect::<String>();
+            format!("{head:?}... ({} chars)", v.chars().count())
+        }
+        Value::String(v) => format!("{v:?}"),
+        Value::Array(vs) => {
+            let head = vs.iter().take(5).map(format_value).collect::<Vec<_>>();
+            let ellipsis = if vs.len() > 5 { ", ..." } else { "" };
+            format!("[{}{ellipsis}] ({} values)", head.join(", "), vs.len())
+        }
+        Value::U8(v) => format!("{v}u8"),
+        Value::I8(v) => format!("{v}i8"),
+        Value::U16(v) => format!("{v}u16"),
+        Value::I16(v) => format!("{v}i16"),
+        Value::U32(v) => format!("{v}u32"),
+        Value::I32(v) => format!("{v}i32"),
+        Value::U64(v) => format!("{v}u64"),
+        Value::I64(v) => format!("{v}i64"),
+        Value::F32(v) => format!("{v}f32"),
+        Value::F64(v) => format!("{v}f64"),
+        Value::Bool(v) => format!("{v}"),
+    }
+}
+
+fn parse_value(
+    kv: &str,
+    metadata: &HashMap<String, gguf_file::Value>,
+) -> Result<(String, gguf_file::Value)> {
+    use gguf_file::{Value, ValueType};
+    let (key, value) = match kv.split_once('=') {
+        Some(v) => v,
+        None => candle::bail!("invalid metadata {kv}, expected key=value"),
+    };
+    let (key, value_type) = match key.split_once(':') {
+        Some((key, value_type)) => {
+            let value_type = match value_type {
+                "u8" => ValueType::U8,
+                "i8" => ValueType::I8,
+                "u16" => ValueType::U16,
+                "i16" => ValueType::I16,
+                "u32" => ValueType::U32,
+                "i32" => ValueType::I32,
+                "u64" => ValueType::U64,
+                "i64" => ValueType::I64,
+                "f32" => ValueType::F32,
+                "f64" => ValueType::F64,
+                "bool" => ValueType::Bool,
+                "str" | "string" => ValueType::String,
+                t => candle::bail!("unsupported metadata type {t}"),
+            };
+            (key, value_type)
+        }
+        None => match metadata.get(key).map(|v| v.value_type()) {
+            None | Some(ValueType::Array) => (key, ValueType::String),
+            Some(value_type) => (key, value_type),
+        },
+    };
+    fn p<T: std::str::FromStr>(v: &str) -> Result<T>
+    where
+        T::Err: std::fmt::Display,
+    {
+        v.parse::<T>()
+            .map_err(|e| candle::Error::Msg(format!("cannot parse {v}: {e}")))
+    }
+    let value = match value_type {
+        ValueType::U8 => Value::U8(p(value)?),
+        ValueType::I8 => Value::I8(p(value)?),
+        ValueType::U16 => Value::U16(p(value)?),
+        ValueType::I16 => Value::I16(p(value)?),
+        ValueType::U32 => Value::U32(p(value)?),
+        ValueType::I32 => Value::I32(p(value)?),
+        ValueType::U64 => Value::U64(p(value)?),
+        ValueType::I64 => Value::I64(p(value)?),
+        ValueType::F32 => Value::F32(p(value)?),
+        ValueType::F64 => Value::F64(p(value)?),
+        ValueType::Bool => Value::Bool(p(value)?),
+        ValueType::String | ValueType::Array => Value::String(value.to_string()),
+    };
+    Ok((key.to_string(), value))
+}
+
+fn run_metadata(
+    file: &std::path::Path,
+    set: &[String],
+    delete: &[String],
+    out_file: Option<&std::path::Path>,
+) -> Result<()> {
+    let mut reader = std::fs::File::open(file)?;
+    let content = gguf_file::Content::read(&mut reader)?;
+    if set.is_empty() && delete.is_empty() {
+        let mut metadata = content.metadata.iter().collect::<Vec<_>>();
+        metadata.sort_by(|a, b| a.0.cmp(b.0));
+        for (key, value) in metadata.iter() {
+            println!("{key}: {}", format_value(value));
+        }
+        return Ok(());
+    }
+    let mut metadata = content.metadata.clone();
+    for key in delete.iter() {
+        if metadata.remove(key).is_none() {
+            candle::bail!("cannot find metadata key {key}")
+        }
+    }
+    for kv in set.iter() {
+        let (key, value) = parse_value(kv, &metadata)?;
+        println!("{key}: {}", format_value(&value));
+        metadata.insert(key, value);
+    }
+    let mut metadata = metadata.into_iter().collect::<Vec<_>>();
+    metadata.sort_by(|a, b| a.0.cmp(&b.0));
+    let mut names = content.tensor_infos.keys().collect::<Vec<_>>();
+    names.sort();
+    let qtensors = names
+        .into_iter()
+        .map(|name| {
+            Ok((
+                name.clone(),
+                content.tensor(&mut reader, name, &Device::Cpu)?,
+            ))
+        })
+        .collect::<Result<Vec<_>>>()?;
+    match out_file {
+        Some(out_file) => write_gguf(out_file, &metadata, &qtensors)?,
+        None => {
+            // Write to a temporary file first so that the original is preserved on errors.
+            let tmp_file = file.with_extension("gguf.tmp");
+            write_gguf(&tmp_file, &metadata, &qtensors)?;
+            std::fs::rename(&tmp_file, file)?
+        }
+    }
+    Ok(())
+}
+
+/// Loads the tensors from multiple safetensors files, a single json file is used as the index of a
+/// sharded checkpoint.
+fn merge_files(in_files: &[std::path::PathBuf]) -> Result<HashMap<String, Tensor>> {
+    let in_files = match in_files {
+        [index] if index.extension().is_some_and(|e| e == "json") => convert::index_files(index)?,
+        [] => candle::bail!("no specified input files"),
+        _ => in_files.to_vec(),
+    };
+    let mut tensors = HashMap::new();
+    for in_file in in_files.iter() {
+        for (name, tensor) in candle::safetensors::load(in_file, &Device::Cpu)? {
+            if tensors.insert(name.clone(), tensor).is_some() {
+                candle::bail!("duplicate tensor {name} in {in_file:?}")
+            }
+        }
+    }
+    Ok(tensors)
+}
+
+fn run_merge(in_files: &[std::path::PathBuf], out_file: &std::path::Path) -> Result<()> {
+    let tensors = merge_files(in_files)?;
+    println!(
+        "merged {} tensors from {} files",
+        tensors.len(),
+        in_files.len()
+    );
+    candle::safetensors::save(&tensors, out_file)
+}
+
+/// Loads all the tensors from a file as f32, the quantized tensors are dequantized.
+fn load_tensors(file: &std::path::Path) -> Result<HashMap<String, Tensor>> {
+    let format = match Format::infer(file) {
+        Some(format) => format,
+        None => candle::bail!("{file:?}: cannot infer format from file extension"),
+    };
+    let tensors: Vec<(String, Tensor)> = match format {
+        Format::Safetensors => candle::safetensors::load(file, &Device::Cpu)?
+            .into_iter()
+            .collect(),
+        Format::Npz => Tensor::read_npz(file)?,
+        Format::Pth => candle::pickle::read_all(file)?,
+        Format::Gguf => {
+            let mut reader = std::fs::File::open(file)?;
+            let content = gguf_file::Content::read(&mut reader)?;
+            content
+                .tensor_infos
+                .keys()
+                .map(|name| {
+                    let tensor = content.tensor(&mut reader, name, &Device::Cpu)?;
+                    Ok((name.clone(), tensor.dequantize(&Device::Cpu)?))
+                })
+                .collect::<Result<Vec<_>>>()?
+        }
+        Format::Ggml => {
+            let mut reader = std::fs::File::open(file)?;
+            let content = candle::quantized::ggml_file::Content::read(&mut reader, &Device::Cpu)?;
+            content
+                .tensors
+                .into_iter()
+                .map(|(name, tensor)| Ok((name, tensor.dequantize(&Device::Cpu)?)))
+                .collect::<Result<Vec<_>>>()?
+        }
+        Format::Pickle => candle::bail!("pickle format is not supported for diff"),
+    };
+    tensors
+        .into_iter()
+        .map(|(name, tensor)| Ok((name, tensor.to_dtype(candle::DType::F32)?)))
+        .collect()
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum TensorDiff {
+    /// The tensor only appears in one of the files, `in_first` is true for the first one.
+    Missing { name: String, in_first: bool },
+    ShapeMismatch {
+        name: String,
+        shape1: candle::Shape,
+        shape2: candle::Shape,
+    },
+    /// The max abs error, the relative error uses the largest magnitude of the first tensor.
+    Compared {
+        name: String,
+        max_abs: f32,
+        rel: f32,
+    },
+}
+
+/// Compares the tensors with the same names, the tensors from the first set come first in
+/// alphabetical order followed by the ones only in the second set.
+fn diff_tensors(
+    tensors1: &HashMap<String, Tensor>,
+    tensors2: &HashMap<String, Tensor>,
+) -> Result<Vec<TensorDiff>> {
+    let mut names = tensors1.keys().collect::<Vec<_>>();
+    names.sort();
+    let mut diffs = vec![];
+    for name in names {
+        let t1 = &tensors1[name];
+        let name = name.clone();
+        let t2 = match tensors2.get(&name) {
+            None => {
+                diffs.push(TensorDiff::Missing {
+                    name,
+                    in_first: true,
+                });
+                continue;
+            }
+            Some(t2) => t2,
+        };
+        if t1.shape() != t2.shape() {
+            diffs.push(TensorDiff::ShapeMismatch {
+                name,
+                shape1: t1.shape().clone(),
+                shape2: t2.shape().clone(),
+            });
+            continue;
+        }
+        let max_abs = (t1 - t2)?
+            .abs()?
+            .flatten_all()?
+            .max(0)?
+            .to_scalar::<f32>()?;
+        let max_ref = t1.abs()?.flatten_all()?.max(0)?.to_scalar::<f32>()?;
+        let rel = if max_ref > 0. {
+            max_abs / max_ref
+        } else {
+            max_abs
+        };
+        diffs.push(TensorDiff::Compared { name, max_abs, rel })
+    }
+    let mut only2 = tensors2
+        .keys()
+        .filter(|k| !tensors1.contains_key(*k))
+        .collect::<Vec<_>>();
+    only2.sort();
+    for name in only2 {
+        diffs.push(TensorDiff::Missing {
+            name: name.clone(),
+            in_first: false,
+        })
+    }
+    Ok(diffs)
+}
+
+fn run_diff(file1: &std::path::Path, file2: &std::path::Path, threshold: f32) -> Result<()> {
+    let tensors1 = load_tensors(file1)?;
+    let tensors2 = load_tensors(file2)?;
+    let mut n_compared = 0;
+    for diff in diff_tensors(&tensors1, &tensors2)? {
+        match diff {
+            TensorDiff::Missing { name, in_first } => {
+                let file = if in_first { file1 } else { file2 };
+                println!("{name}: only in {file:?}")
+            }
+            TensorDiff::ShapeMismatch {
+                name,
+                shape1,
+                shape2,
+            } => println!("{name}: shape mismatch {shape1:?} {shape2:?}"),
+            TensorDiff::Compared { name, max_abs, rel } => {
+                n_compared += 1;
+                if max_abs > threshold {
+                    println!("{name}: max abs {max_abs:.6e} rel {rel:.6e}");
+                }
+            }
+        }
+    }
+    println!("compared {n_compared} tensors");
+    Ok(())
+}
+
+enum ImatrixRunner {
+    Mistral(candle_transformers::models::quantized_mistral::Model),
+    Llama(candle_transformers::models::quantized_llama::ModelWeights),
+}
+
+impl ImatrixRunner {
+    fn new(
+        which: ImatrixModel,
+        config: &std::path::Path,
+        vb: candle_transformers::quantized_var_builder::VarBuilder,
+    ) -> anyhow::Result<Self> {
+        use candle_transformers::models::{llama, mistral, quantized_llama, quantized_mistral};
+
+        let config = std::fs::read(config)?;
+        let model = match which {
+            ImatrixModel::Mistral => {
+                let config: mistral::Config = serde_json::from_slice(&config)?;
+                Self::Mistral(quantized_mistral::Model::new(&config, vb)?)
+            }
+            ImatrixModel::Llama => {
+                let config: llama::LlamaConfig = serde_json::from_slice(&config)?;
+                let config = config.into_config(false);
+                Self::Llama(quantized_llama::ModelWeights::from_gptq(&config, vb)?)
+            }
+        };
+        Ok(model)
+    }
+
+    fn forward_chunk(&mut self, input: &Tensor) -> Result<()> {
+        match self {
+            Self::Mistral(m) => {
+                m.clear_kv_cache();
+                m.forward(input, 0)?;
+            }
+            Self::Llama(m) => {
+                m.clear_kv_cache();
+                m.forward(input, 0)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+fn run_imatrix(
+    model_files: &[std::path::PathBuf],
+    which: ImatrixModel,
+    config: &std::path::Path,
+    tokenizer: &std::path::Path,
+    text: &std::path::Path,
+    out_file: &std::path::Path,
+    ctx_size: usize,
+    chunks: Option<usize>,
+    device: &Device,
+) -> anyhow::Result<()> {
+    use candle_transformers::quantized_var_builder::VarBuilder;
+
+    if model_files.is_empty() {
+        anyhow::bail!("no specified model files")
+    }
+    if ctx_size == 0 {
+        anyhow::bail!("the context size must be positive")
+    }
+    let tokenizer = tokenizers::Tokenizer::from_file(tokenizer).map_err(anyhow::Error::msg)?;
+    let vb = match model_files[0].extension() {
+        Some(extension) if extension == "gguf" => {
+            if model_files.len() != 1 {
+                anyhow::bail!("only a single gguf file can be used")
+            }
+            VarBuilder::from_gguf(&model_files[0], device)?
+        }
+        _ => VarBuilder::from_safetensors(model_files, device)?,
+    };
+    let collector = imatrix_file::ImatrixCollector::new();
+    let mut model =
+        ImatrixRunner::new(which, config, vb.with_imatrix_collector(collector.clone()))?;
+
+    let content = std::fs::read_to_string(text)?;
+    let tokens = tokenizer
+        .encode(content, true)
+        .map_err(anyhow::Error::msg)?
+        .get_ids()
+        .to_vec();
+    let n_chunks = usize::max(tokens.len() / ctx_size, 1);
+    let n_chunks = chunks.map_or(n_chunks, |c| usize::min(c, n_chunks));
+    println!("tokens: {}, chunks: {n_chunks}", tokens.len());
+    for (index, chunk) in tokens.chunks(ctx_size).take(n_chunks).enumerate() {
+        let start = std::time::Instant::now();
+        let input = candle::Tensor::new(chunk, device)?.unsqueeze(0)?;
+        model.forward_chunk(&input)?;
+        collector.next_chunk();
+        println!("  chunk {}/{n_chunks} {:?}", index + 1, start.elapsed());
+    }
+    // The activations are recorded using the names from the checkpoint, llama.cpp expects the
+    // gguf ones.
+    let entries = collector
+        .entries()
+        .into_iter()
+        .map(|(name, entry)| Ok((convert::imatrix_name(&name)?, entry)))
+        .collect::<Result<HashMap<_, _>>>()?;
+    let dataset = text.to_string_lossy();
+    imatrix_file::save_imatrix(out_file, &entries, collector.chunks(), &dataset)?;
+    println!("wrote {} entries to {out_file:?}", entries.len());
+    Ok(())
+}
+
 fn main() -> anyhow::Result<()> {
     let args = Args::parse();
     let device = Device::Cpu;
@@ -535,8 +1261,199 @@ fn main() -> anyhow::Result<()> {
             out_file,
             quantization,
             mode,
-        } => run_quantize(&in_file, out_file, quantization, mode, &device)?,
+            imatrix,
+            rule,
+            recipe,
+            preset,
+        } => run_quantize(
+            &in_file,
+            out_file,
+            quantization,
+            mode,
+            imatrix.as_ref(),
+            &rule,
+            recipe.as_deref(),
+            preset.as_deref(),
+            &device,
+        )?,
+        Command::Convert {
+            dir,
+            out_file,
+            quantization,
+            rule,
+        } => run_convert(&dir, &out_file, quantization, &rule)?,
+        Command::Metadata {
+            file,
+            set,
+            delete,
+            out_file,
+        } => run_metadata(&file, &set, &delete, out_file.as_deref())?,
+        Command::Merge { in_file, out_file } => run_merge(&in_file, &out_file)?,
+        Command::Diff {
+            file1,
+            file2,
+            threshold,
+        } => run_diff(&file1, &file2, threshold)?,
         Command::Dequantize { in_file, out_file } => run_dequantize(in_file, out_file, &device)?,
+        Command::Imatrix {
+            model,
+            which,
+            config,
+            tokenizer,
+            text,
+            out_file,
+            ctx_size,
+            chunks,
+        } => run_imatrix(
+            &model, which, &config, &tokenizer, &text, &out_file, ctx_size, chunks, &device,
+        )?,
     }
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use gguf_file::Value;
+
+    #[test]
+    fn parse_values() -> Result<()> {
+        let mut metadata = HashMap::new();
+        metadata.insert("llama.rope.freq_base".to_string(), Value::F32(1e4));
+        metadata.insert("tokenizer.ggml.tokens".to_string(), Value::Array(vec![]));
+
+        let (key, value) = parse_value("llama.block_count:u32=32", &metadata)?;
+        assert_eq!(key, "llama.block_count");
+        assert_eq!(value.to_u32()?, 32);
+        let (_, value) = parse_value("general.flag:bool=true", &metadata)?;
+        assert!(value.to_bool()?);
+        // The type of existing keys is preserved.
+        let (_, value) = parse_value("llama.rope.freq_base=5e5", &metadata)?;
+        assert_eq!(value.to_f32()?, 5e5);
+        // New keys and arrays are set as strings, the value can contain separators.
+        let (key, value) = parse_value("general.name=a=b:c", &metadata)?;
+        assert_eq!(
+            (key.as_str(), value.to_string()?.as_str()),
+            ("general.name", "a=b:c")
+        );
+        let (_, value) = parse_value("tokenizer.ggml.tokens=foo", &metadata)?;
+        assert_eq!(value.to_string()?, "foo");
+
+        assert!(parse_value("general.name", &metadata).is_err());
+        assert!(parse_value("x:u8=300", &metadata).is_err());
+        assert!(parse_value("x:f16=1", &metadata).is_err());
+        assert!(parse_value("llama.rope.freq_base=abc", &metadata).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn imatrix_lookup() -> Result<()> {
+        let imatrix = HashMap::from([
+            ("blk.0.attn_q.weight".to_string(), vec![1f32, 2.]),
+            ("output.weight".to_string(), vec![3f32]),
+        ]);
+        // The Hugging Face names are matched with the gguf ones used in the imatrix files.
+        let names = [
+            "model.layers.0.self_attn.q_proj.weight",
+            "model.layers.1.self_attn.q_proj.weight",
+            "lm_head.weight",
+            "blk.0.attn_q.weight",
+        ];
+        let entries = imatrix_entries(&imatrix, &names)?;
+        assert_eq!(entries.len(), 3);
+        assert_eq!(entries["model.layers.0.self_attn.q_proj.weight"], [1., 2.]);
+        assert_eq!(entries["lm_head.weight"], [3.]);
+        assert_eq!(entries["blk.0.attn_q.weight"], [1., 2.]);
+
+        assert!(imatrix_entries(&imatrix, &["model.layers.1.mlp.up_proj.weight"]).is_err());
+        assert!(imatrix_entries(&HashMap::new(), &names)?.is_empty());
+        Ok(())
+    }
+
+    fn tmp_file(name: &str) -> std::path::PathBuf {
+        std::env::temp_dir().join(format!("tensor-tools-{}-{name}", std::process::id()))
+    }
+
+    #[test]
+    fn merge() -> Result<()> {
+        let dev = &Device::Cpu;
+        let (f1, f2) = (
+            tmp_file("shard1.safetensors"),
+            tmp_file("shard2.safetensors"),
+        );
+        let index = tmp_file("index.json");
+        let a = Tensor::arange(0f32, 6., dev)?.reshape((2, 3))?;
+        let b = Tensor::new(&[1u32, 2], dev)?;
+        candle::safetensors::save(&HashMap::from([("a".to_string(), a.clone())]), &f1)?;
+        candle::safetensors::save(&HashMap::from([("b".to_string(), b.clone())]), &f2)?;
+        let weight_map = serde_json::json!({"weight_map": {
+            "a": f1.file_name().unwrap().to_string_lossy(),
+            "b": f2.file_name().unwrap().to_string_lossy(),
+        }});
+        std::fs::write(&index, weight_map.to_string())?;
+
+        let merged = merge_files(&[f1.clone(), f2.clone()]);
+        let from_index = merge_files(std::slice::from_ref(&index));
+        let duplicate = merge_files(&[f1.clone(), f1.clone()]);
+        for f in [&f1, &f2, &index] {
+            std::fs::remove_file(f)?
+        }
+        for merged in [merged?, from_index?] {
+            assert_eq!(merged.len(), 2);
+            assert_eq!(merged["a"].to_vec2::<f32>()?, a.to_vec2::<f32>()?);
+            assert_eq!(merged["b"].to_vec1::<u32>()?, [1, 2]);
+        }
+        assert!(duplicate.is_err());
+        assert!(merge_files(&[]).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn diff() -> Result<()> {
+        let dev = &Device::Cpu;
+        let t = |v: &[f32]| Tensor::new(v, dev);
+        let tensors1 = HashMap::from([
+            ("a".to_string(), t(&[1., -4., 2.])?),
+            ("b".to_string(), t(&[1., 2.])?),
+            ("c".to_string(), t(&[0., 0.])?),
+            ("only1".to_string(), t(&[1.])?),
+        ]);
+        let tensors2 = HashMap::from([
+            ("a".to_string(), t(&[1., -3., 2.])?),
+            ("b".to_string(), t(&[1., 2., 3.])?),
+            ("c".to_string(), t(&[0.5, 0.])?),
+            ("only2".to_string(), t(&[1.])?),
+        ]);
+        let diffs = diff_tensors(&tensors1, &tensors2)?;
+        assert_eq!(
+            diffs,
+            [
+                TensorDiff::Compared {
+                    name: "a".to_string(),
+                    max_abs: 1.,
+                    rel: 0.25
+                },
+                TensorDiff::ShapeMismatch {
+                    name: "b".to_string(),
+                    shape1: 2.into(),
+                    shape2: 3.into(),
+                },
+                // Without a reference magnitude, the relative error is the absolute one.
+                TensorDiff::Compared {
+                    name: "c".to_string(),
+                    max_abs: 0.5,
+                    rel: 0.5
+                },
+                TensorDiff::Missing {
+                    name: "only1".to_string(),
+                    in_first: true
+                },
+                TensorDiff::Missing {
+                    name: "only2".to_string(),
+                    in_first: false
+                },
+            ]
+        );
+        Ok(())
+    }
+}
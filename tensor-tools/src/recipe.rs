@@ -0,0 +1,593 @@
+//! Mixed quantization recipes.
+//!
+//! A recipe maps tensor names to quantization types using a list of regex rules, the first
+//! matching rule is used and the tensors that don't match any rule use the default type. The
+//! weights whose rows are not aligned with the block size of the selected type fall back to a
+//! type with a smaller block size, similar to what llama.cpp does.
+//!
+//! Recipes can be read from a text file where each line contains a rule `regex = dtype`, the
+//! special `default = dtype` line sets the default type and lines starting with `#` are comments.
+//! The llama.cpp presets such as `Q4_K_M` are available via [`Recipe::preset_for_arch`].
+use candle::quantized::{GgmlDType, QTensor};
+use candle::{Result, Shape, Tensor};
+
+/// Parses a quantization type name, e.g. `q4_0`, `q4k`, `Q4_K` or `iq2_xxs`.
+pub fn parse_dtype(s: &str) -> Result<GgmlDType> {
+    let dtype = match s.to_lowercase().replace('_', "").as_str() {
+        "f32" => GgmlDType::F32,
+        "f16" => GgmlDType::F16,
+        "bf16" => GgmlDType::BF16,
+        "q40" => GgmlDType::Q4_0,
+        "q41" => GgmlDType::Q4_1,
+        "q50" => GgmlDType::Q5_0,
+        "q51" => GgmlDType::Q5_1,
+        "q80" => GgmlDType::Q8_0,
+        "q81" => GgmlDType::Q8_1,
+        "q2k" => GgmlDType::Q2K,
+        "q3k" => GgmlDType::Q3K,
+        "q4k" => GgmlDType::Q4K,
+        "q5k" => GgmlDType::Q5K,
+        "q6k" => GgmlDType::Q6K,
+        "q8k" => GgmlDType::Q8K,
+        "iq1s" => GgmlDType::IQ1S,
+        "iq1m" => GgmlDType::IQ1M,
+        "iq2xxs" => GgmlDType::IQ2XXS,
+        "iq2xs" => GgmlDType::IQ2XS,
+        "iq2s" => GgmlDType::IQ2S,
+        "iq3xxs" => GgmlDType::IQ3XXS,
+        "iq3s" => GgmlDType::IQ3S,
+        "iq4nl" => GgmlDType::IQ4NL,
+        "iq4xs" => GgmlDType::IQ4XS,
+        "tq10" => GgmlDType::TQ1_0,
+        "tq20" => GgmlDType::TQ2_0,
+        _ => candle::bail!("unknown quantization type {s}"),
+    };
+    Ok(dtype)
+}
+
+/// Returns a type that can be used for rows of `ncols` elements. The k-quants and importance
+/// quants fall back to the 32 elements block types, and these fall back to f16.
+pub fn fallback_dtype(dtype: GgmlDType, ncols: usize) -> GgmlDType {
+    let mut dtype = dtype;
+    while !ncols.is_multiple_of(dtype.block_size()) {
+        dtype = match dtype {
+            GgmlDType::Q4K => GgmlDType::Q5_0,
+            GgmlDType::Q5K => GgmlDType::Q5_1,
+            GgmlDType::Q6K | GgmlDType::Q8K => GgmlDType::Q8_0,
+            GgmlDType::Q2K
+            | GgmlDType::Q3K
+            | GgmlDType::IQ2XXS
+            | GgmlDType::IQ2XS
+            | GgmlDType::IQ2S
+            | GgmlDType::IQ3XXS
+            | GgmlDType::IQ3S
+            | GgmlDType::IQ1S
+            | GgmlDType::IQ1M
+            | GgmlDType::IQ4XS
+            | GgmlDType::TQ1_0
+            | GgmlDType::TQ2_0 => GgmlDType::IQ4NL,
+            _ => GgmlDType::F16,
+        }
+    }
+    dtype
+}
+
+#[derive(Debug, Clone)]
+pub struct Rule {
+    regex: regex::Regex,
+    dtype: GgmlDType,
+}
+
+impl Rule {
+    pub fn new(pattern: &str, dtype: GgmlDType) -> Result<Self> {
+        let regex = regex::Regex::new(pattern)
+            .map_err(|e| candle::Error::Msg(format!("invalid rule regex {pattern}: {e}")))?;
+        Ok(Self { regex, dtype })
+    }
+
+    /// Parses a rule with the format `regex=dtype`.
+    pub fn parse(rule: &str) -> Result<Self> {
+        match rule.rsplit_once('=') {
+            Some((pattern, dtype)) => Self::new(pattern.trim(), parse_dtype(dtype.trim())?),
+            None => candle::bail!("invalid rule {rule}, expected regex=dtype"),
+        }
+    }
+
+    pub fn matches(&self, name: &str) -> bool {
+        self.regex.is_match(name)
+    }
+}
+
+/// The llama.cpp quantization presets.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Preset {
+    Q2K,
+    Q3KS,
+    Q3KM,
+    Q3KL,
+    Q4_0,
+    Q4_1,
+    Q4KS,
+    Q4KM,
+    Q5_0,
+    Q5_1,
+    Q5KS,
+    Q5KM,
+    Q6K,
+    Q8_0,
+}
+
+impl Preset {
+    pub const ALL: [Self; 14] = [
+        Self::Q2K,
+        Self::Q3KS,
+        Self::Q3KM,
+        Self::Q3KL,
+        Self::Q4_0,
+        Self::Q4_1,
+        Self::Q4KS,
+        Self::Q4KM,
+        Self::Q5_0,
+        Self::Q5_1,
+        Self::Q5KS,
+        Self::Q5KM,
+        Self::Q6K,
+        Self::Q8_0,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Q2K => "Q2_K",
+            Self::Q3KS => "Q3_K_S",
+            Self::Q3KM => "Q3_K_M",
+            Self::Q3KL => "Q3_K_L",
+            Self::Q4_0 => "Q4_0",
+            Self::Q4_1 => "Q4_1",
+            Self::Q4KS => "Q4_K_S",
+            Self::Q4KM => "Q4_K_M",
+            Self::Q5_0 => "Q5_0",
+            Self::Q5_1 => "Q5_1",
+            Self::Q5KS => "Q5_K_S",
+            Self::Q5KM => "Q5_K_M",
+            Self::Q6K => "Q6_K",
+            Self::Q8_0 => "Q8_0",
+        }
+    }
+
+    /// The type used for the weights that are not handled by a specific rule.
+    pub fn base_dtype(&self) -> GgmlDType {
+        match self {
+            Self::Q2K => GgmlDType::Q2K,
+            Self::Q3KS | Self::Q3KM | Self::Q3KL => GgmlDType::Q3K,
+            Self::Q4_0 => GgmlDType::Q4_0,
+            Self::Q4_1 => GgmlDType::Q4_1,
+            Self::Q4KS | Self::Q4KM => GgmlDType::Q4K,
+            Self::Q5_0 => GgmlDType::Q5_0,
+            Self::Q5_1 => GgmlDType::Q5_1,
+            Self::Q5KS | Self::Q5KM => GgmlDType::Q5K,
+            Self::Q6K => GgmlDType::Q6K,
+            Self::Q8_0 => GgmlDType::Q8_0,
+        }
+    }
+}
+
+impl std::str::FromStr for Preset {
+    type Err = candle::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let normalized = s.to_uppercase().replace('-', "_");
+        match Self::ALL.iter().find(|p| p.name() == normalized) {
+            Some(p) => Ok(*p),
+            None => candle::bail!("unknown quantization preset {s}"),
+        }
+    }
+}
+
+// The tensor names for the gguf and HuggingFace naming conventions.
+const OUTPUT: &str = r"^(output|lm_head)\.weight$";
+const ATTN_V: (&str, &str) = ("attn_v", r"self_attn\.v_proj");
+const FFN_DOWN: (&str, &str) = ("ffn_down", r"mlp\.down_proj");
+const ATTN_OUTPUT: &str =
+    r"^(blk\.\d+\.attn_output|model\.layers\.\d+\.self_attn\.o_proj)\.weight$";
+
+fn layer_pattern((gguf, hf): (&str, &str), layers: &[usize]) -> String {
+    let layers = layers
+        .iter()
+        .map(|l| l.to_string())
+        .collect::<Vec<_>>()
+        .join("|");
+    format!(r"^(blk\.({layers})\.{gguf}|model\.layers\.({layers})\.{hf})\.weight$")
+}
+
+/// Same as `use_more_bits` in llama.cpp, this selects the first and last eighth of the layers as
+/// well as every third layer in between.
+pub fn use_more_bits(i_layer: usize, n_layers: usize) -> bool {
+    i_layer < n_layers / 8 || i_layer >= 7 * n_layers / 8 || (i_layer - n_layers / 8) % 3 == 2
+}
+
+/// Returns the number of layers based on the tensor names, using either the `blk.N.` gguf prefix
+/// or the `model.layers.N.` HuggingFace one.
+pub fn count_layers<'a, I: IntoIterator<Item = &'a str>>(names: I) -> usize {
+    names
+        .into_iter()
+        .filter_map(|name| {
+            let rest = name
+                .strip_prefix("blk.")
+                .or_else(|| name.strip_prefix("model.layers."))?;
+            rest.split('.').next()?.parse::<usize>().ok()
+        })
+        .max()
+        .map_or(0, |l| l + 1)
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct Recipe {
+    rules: Vec<Rule>,
+    default: Option<GgmlDType>,
+}
+
+impl Recipe {
+    /// Creates an empty recipe, the 2d weights that don't match any rule use `default` and are
+    /// kept as f32 if `default` is `None`.
+    pub fn new(default: Option<GgmlDType>) -> Self {
+        Self {
+            rules: vec![],
+            default,
+        }
+    }
+
+    pub fn push(&mut self, rule: Rule) {
+        self.rules.push(rule)
+    }
+
+    pub fn with_rule(mut self, pattern: &str, dtype: GgmlDType) -> Result<Self> {
+        self.push(Rule::new(pattern, dtype)?);
+        Ok(self)
+    }
+
+    /// Appends the rules of `other` after the current ones, the default type of `other` is only
+    /// used if this recipe has none.
+    pub fn extend(&mut self, other: Recipe) {
+        self.rules.extend(other.rules);
+        if self.default.is_none() {
+            self.default = other.default
+        }
+    }
+
+    pub fn rules(&self) -> &[Rule] {
+        &self.rules
+    }
+
+    pub fn default_dtype(&self) -> Option<GgmlDType> {
+        self.default
+    }
+
+    pub fn parse(content: &str) -> Result<Self> {
+        let mut recipe = Self::new(None);
+        for (line_idx, line) in content.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let (pattern, dtype) = match line.rsplit_once('=') {
+                Some((pattern, dtype)) => (pattern.trim(), parse_dtype(dtype.trim())?),
+                None => candle::bail!("line {}: expected regex = dtype, got {line}", line_idx + 1),
+            };
+            if pattern == "default" {
+                recipe.default = Some(dtype)
+            } else {
+                recipe.push(Rule::new(pattern, dtype)?)
+            }
+        }
+        Ok(recipe)
+    }
+
+    pub fn from_file<P: AsRef<std::path::Path>>(p: P) -> Result<Self> {
+        Self::parse(&std::fs::read_to_string(p)?)
+    }
+
+    /// The recipe for a llama.cpp preset and a gguf architecture name, llama.cpp only uses a
+    /// different type selection for the `falcon` architecture.
+    pub fn preset_for_arch(preset: Preset, arch: &str, n_layers: usize) -> Result<Self> {
+        use GgmlDType as T;
+        let falcon = arch == "falcon";
+        let more_bits = |i: usize| use_more_bits(i, n_layers);
+        let base = preset.base_dtype();
+        let output = if base == T::Q8_0 || falcon {
+            T::Q8_0
+        } else {
+            T::Q6K
+        };
+        let mut recipe = Self::new(Some(base)).with_rule(OUTPUT, output)?;
+        let attn_v_dtype = |i: usize| match preset {
+            Preset::Q2K => T::Q3K,
+            Preset::Q3KM if i < 2 => T::Q5K,
+            Preset::Q3KM => T::Q4K,
+            Preset::Q3KL => T::Q5K,
+            Preset::Q4KM | Preset::Q5KM if more_bits(i) => T::Q6K,
+            Preset::Q4KS if i < 4 => T::Q5K,
+            _ => base,
+        };
+        let ffn_down_dtype = |i: usize| match preset {
+            Preset::Q2K => T::Q3K,
+            Preset::Q3KM if i < n_layers / 16 => T::Q5K,
+            Preset::Q3KM if !falcon || more_bits(i) => T::Q4K,
+            Preset::Q3KM => T::Q3K,
+            Preset::Q3KL if falcon => T::Q4K,
+            Preset::Q3KL => T::Q5K,
+            Preset::Q4KM | Preset::Q5KM if more_bits(i) => T::Q6K,
+            Preset::Q4KS if !falcon && i < n_layers / 8 => T::Q5K,
+            _ => base,
+        };
+        let layer_dtypes: [(_, &dyn Fn(usize) -> GgmlDType); 2] =
+            [(ATTN_V, &attn_v_dtype), (FFN_DOWN, &ffn_down_dtype)];
+        for (names, layer_dtype) in layer_dtypes {
+            let mut by_dtype: Vec<(GgmlDType, Vec<usize>)> = vec![];
+            for i in 0..n_layers {
+                let dtype = layer_dtype(i);
+                if dtype == base {
+                    continue;
+                }
+                match by_dtype.iter_mut().find(|(d, _)| *d == dtype) {
+                    Some((_, layers)) => layers.push(i),
+                    None => by_dtype.push((dtype, vec![i])),
+                }
+            }
+            for (dtype, layers) in by_dtype {
+                recipe.push(Rule::new(&layer_pattern(names, &layers), dtype)?)
+            }
+        }
+        let attn_output = match preset {
+            Preset::Q3KL if falcon => Some(T::Q4K),
+            _ if falcon => None,
+            Preset::Q2K => Some(T::Q3K),
+            Preset::Q3KM => Some(T::Q4K),
+            Preset::Q3KL => Some(T::Q5K),
+            _ => None,
+        };
+        if let Some(dtype) = attn_output {
+            recipe.push(Rule::new(ATTN_OUTPUT, dtype)?)
+        }
+        Ok(recipe)
+    }
+
+    /// The type to use for a tensor, this returns f32 for the tensors that are not 2d and takes
+    /// care of the fallback when the rows are not aligned with the block size.
+    pub fn dtype_for(&self, name: &str, shape: &Shape) -> Result<GgmlDType> {
+        if shape.rank() != 2 {
+            return Ok(GgmlDType::F32);
+        }
+        let mut dtype = self.default;
+        for rule in self.rules.iter() {
+            if rule.matches(name) {
+                dtype = Some(rule.dtype);
+                break;
+            }
+        }
+        let ncols = shape.dims()[1];
+        Ok(dtype.map_or(GgmlDType::F32, |dtype| fallback_dtype(dtype, ncols)))
+    }
+
+    pub fn quantize(&self, name: &str, tensor: &Tensor) -> Result<QTensor> {
+        self.quantize_imatrix(name, tensor, None)
+    }
+
+    /// Quantizes a tensor, using the importance matrix if provided and if its size matches the
+    /// number of columns of the tensor.
+    pub fn quantize_imatrix(
+        &self,
+        name: &str,
+        tensor: &Tensor,
+        imatrix: Option<&[f32]>,
+    ) -> Result<QTensor> {
+        let dtype = self.dtype_for(name, tensor.shape())?;
+        let is_float = matches!(dtype, GgmlDType::F32 | GgmlDType::F16 | GgmlDType::BF16);
+        match imatrix {
+            Some(imatrix) if !is_float && imatrix.len() == tensor.dim(1)? => {
+                QTensor::quantize_imatrix(tensor, imatrix, dtype)
+            }
+            _ => QTensor::quantize(tensor, dtype),
+        }
+    }
+}
+
+/// Size statistics for a set of quantized tensors.
+#[derive(Debug, Clone, Default)]
+pub struct QuantizationStats {
+    tensors: Vec<(String, GgmlDType, usize, usize)>,
+}
+
+impl QuantizationStats {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn add(&mut self, name: &str, tensor: &QTensor) {
+        let n_params = tensor.shape().elem_count();
+        let size = tensor.storage_size_in_bytes();
+        self.tensors
+            .push((name.to_string(), tensor.dtype(), n_params, size))
+    }
+
+    pub fn n_params(&self) -> usize {
+        self.tensors.iter().map(|t| t.2).sum()
+    }
+
+    pub fn size_in_bytes(&self) -> usize {
+        self.tensors.iter().map(|t| t.3).sum()
+    }
+
+    pub fn bits_per_weight(&self) -> f64 {
+        let n_params = self.n_params();
+        if n_params == 0 {
+            return 0.;
+        }
+        self.size_in_bytes() as f64 * 8. / n_params as f64
+    }
+
+    /// The number of tensors, parameters and bytes for each type.
+    pub fn by_dtype(&self) -> Vec<(GgmlDType, usize, usize, usize)> {
+        let mut by_dtype: Vec<(GgmlDType, usize, usize, usize)> = vec![];
+        for &(_, dtype, n_params, size) in self.tensors.iter() {
+            match by_dtype.iter_mut().find(|v| v.0 == dtype) {
+                Some(v) => {
+                    v.1 += 1;
+                    v.2 += n_params;
+                    v.3 += size;
+                }
+                None => by_dtype.push((dtype, 1, n_params, size)),
+            }
+        }
+        by_dtype.sort_by_key(|v| std::cmp::Reverse(v.2));
+        by_dtype
+    }
+}
+
+impl std::fmt::Display for QuantizationStats {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        const MIB: f64 = 1024. * 1024.;
+        for (dtype, n_tensors, n_params, size) in self.by_dtype() {
+            let bpw = size as f64 * 8. / n_params.max(1) as f64;
+            writeln!(
+                f,
+                "{dtype:?}: {n_tensors} tensors, {n_params} params, {:.2} MiB, {bpw:.2} bpw",
+                size as f64 / MIB
+            )?;
+        }
+        write!(
+            f,
+            "total: {} params, {:.2} MiB, {:.2} bpw",
+            self.n_params(),
+            self.size_in_bytes() as f64 / MIB,
+            self.bits_per_weight()
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use candle::Device;
+
+    #[test]
+    fn quantization_recipe() -> Result<()> {
+        let recipe = Recipe::parse(
+            "# keep the embeddings at a higher precision\n\
+             default = q4_0\n\
+             token_embd\\.weight = q8_0\n\
+             \n\
+             blk\\.\\d+\\.ffn_down\\.weight=Q6_K\n",
+        )?;
+        assert_eq!(recipe.rules().len(), 2);
+        let shape = candle::Shape::from((64, 512));
+        assert_eq!(
+            recipe.dtype_for("token_embd.weight", &shape)?,
+            GgmlDType::Q8_0
+        );
+        assert_eq!(
+            recipe.dtype_for("blk.3.ffn_down.weight", &shape)?,
+            GgmlDType::Q6K
+        );
+        assert_eq!(
+            recipe.dtype_for("blk.3.ffn_up.weight", &shape)?,
+            GgmlDType::Q4_0
+        );
+        assert_eq!(
+            recipe.dtype_for("blk.3.ffn_norm.weight", &512.into())?,
+            GgmlDType::F32
+        );
+        // Rows that are not aligned with the k-quants block size fall back to smaller blocks.
+        let unaligned = candle::Shape::from((64, 96));
+        assert_eq!(
+            recipe.dtype_for("blk.0.ffn_down.weight", &unaligned)?,
+            GgmlDType::Q8_0
+        );
+        assert_eq!(fallback_dtype(GgmlDType::Q4K, 96), GgmlDType::Q5_0);
+        assert_eq!(fallback_dtype(GgmlDType::IQ2XS, 96), GgmlDType::IQ4NL);
+        assert_eq!(fallback_dtype(GgmlDType::Q4K, 40), GgmlDType::F16);
+        assert!(Recipe::parse("blk = q4").is_err());
+
+        let names = [
+            "blk.0.attn_v.weight",
+            "blk.15.ffn_down.weight",
+            "output.weight",
+        ];
+        assert_eq!(count_layers(names), 16);
+        let preset: Preset = "q4_k_m".parse()?;
+        assert_eq!(preset, Preset::Q4KM);
+        let recipe = Recipe::preset_for_arch(preset, "llama", 16)?;
+        let dtype = |name: &str| recipe.dtype_for(name, &shape);
+        assert_eq!(dtype("output.weight")?, GgmlDType::Q6K);
+        assert_eq!(dtype("lm_head.weight")?, GgmlDType::Q6K);
+        assert_eq!(dtype("token_embd.weight")?, GgmlDType::Q4K);
+        // use_more_bits selects the layers 0, 1, 4, 7, 10, 13, 14 and 15 out of 16.
+        let attn_v = (0..16)
+            .filter(|i| dtype(&format!("blk.{i}.attn_v.weight")).unwrap() == GgmlDType::Q6K)
+            .collect::<Vec<_>>();
+        assert_eq!(attn_v, [0, 1, 4, 7, 10, 13, 14, 15]);
+        assert_eq!(
+            dtype("model.layers.4.mlp.down_proj.weight")?,
+            GgmlDType::Q6K
+        );
+        assert_eq!(dtype("blk.5.ffn_down.weight")?, GgmlDType::Q4K);
+        assert_eq!(dtype("blk.5.attn_q.weight")?, GgmlDType::Q4K);
+        let recipe = Recipe::preset_for_arch(Preset::Q4KS, "llama", 16)?;
+        assert_eq!(
+            recipe.dtype_for("blk.3.attn_v.weight", &shape)?,
+            GgmlDType::Q5K
+        );
+        assert_eq!(
+            recipe.dtype_for("blk.4.attn_v.weight", &shape)?,
+            GgmlDType::Q4K
+        );
+        let recipe = Recipe::preset_for_arch(Preset::Q8_0, "llama", 16)?;
+        assert_eq!(recipe.dtype_for("output.weight", &shape)?, GgmlDType::Q8_0);
+
+        // The per-layer types picked by llama.cpp for Q3_K_M with 32 layers, ffn_down only uses
+        // use_more_bits for falcon.
+        use GgmlDType::{Q3K, Q4K, Q5K};
+        let more_bits = (0..32)
+            .filter(|&i| use_more_bits(i, 32))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            more_bits,
+            [0, 1, 2, 3, 6, 9, 12, 15, 18, 21, 24, 27, 28, 29, 30, 31]
+        );
+        let layer_dtypes = |recipe: &Recipe, name: &str| {
+            (0..32)
+                .map(|i| recipe.dtype_for(&format!("blk.{i}.{name}.weight"), &shape))
+                .collect::<Result<Vec<_>>>()
+        };
+        let recipe = Recipe::preset_for_arch(Preset::Q3KM, "llama", 32)?;
+        let mut expected = vec![Q4K; 32];
+        expected[..2].copy_from_slice(&[Q5K, Q5K]);
+        assert_eq!(layer_dtypes(&recipe, "ffn_down")?, expected);
+        assert_eq!(layer_dtypes(&recipe, "attn_v")?, expected);
+        assert_eq!(layer_dtypes(&recipe, "attn_output")?, vec![Q4K; 32]);
+        assert_eq!(layer_dtypes(&recipe, "ffn_up")?, vec![Q3K; 32]);
+        assert_eq!(recipe.dtype_for("output.weight", &shape)?, GgmlDType::Q6K);
+        let recipe = Recipe::preset_for_arch(Preset::Q3KM, "falcon", 32)?;
+        #[rustfmt::skip]
+        let expected = [
+            Q5K, Q5K, Q4K, Q4K, Q3K, Q3K, Q4K, Q3K, Q3K, Q4K, Q3K, Q3K, Q4K, Q3K, Q3K, Q4K,
+            Q3K, Q3K, Q4K, Q3K, Q3K, Q4K, Q3K, Q3K, Q4K, Q3K, Q3K, Q4K, Q4K, Q4K, Q4K, Q4K,
+        ];
+        assert_eq!(layer_dtypes(&recipe, "ffn_down")?, expected);
+        assert_eq!(layer_dtypes(&recipe, "attn_output")?, vec![Q3K; 32]);
+        assert_eq!(recipe.dtype_for("output.weight", &shape)?, GgmlDType::Q8_0);
+
+        let dev = &Device::Cpu;
+        let recipe = Recipe::parse("default = q8_0\noutput\\.weight = f16")?;
+        let mut stats = QuantizationStats::new();
+        for (name, shape) in [("w.weight", (64, 512)), ("output.weight", (32, 512))] {
+            let tensor = Tensor::randn(0f32, 1., shape, dev)?;
+            stats.add(name, &recipe.quantize(name, &tensor)?);
+        }
+        assert_eq!(stats.n_params(), 96 * 512);
+        assert_eq!(stats.size_in_bytes(), 64 * 16 * 34 + 32 * 512 * 2);
+        let bpw = stats.bits_per_weight();
+        assert!((bpw - (2. * 8.5 + 16.) / 3.).abs() < 1e-6, "{bpw}");
+        assert_eq!(stats.by_dtype()[0].0, GgmlDType::Q8_0);
+        Ok(())
+    }
+}